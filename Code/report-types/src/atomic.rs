@@ -0,0 +1,18 @@
+//! The diagnosis of atomicity violation reports.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AtomicityViolationDiagnosis {
+    pub fn_name: String,
+    pub atomic_reader: String,
+    pub atomic_writer: String,
+    pub dep_kind: String,
+    /// `unknown` if the atomics are only reported under `--paranoid`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_confidence: Option<String>,
+    /// The source lines of the spans with one line of context, keyed by the span strings, under `--with-snippets`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub snippet: BTreeMap<String, String>,
+}
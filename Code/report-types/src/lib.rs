@@ -0,0 +1,2449 @@
+//! The serde types of the reports emitted by lockbud, without rustc dependencies.
+//! lockbud serializes its output through these types, so the consumers can deserialize it safely, e.g.,
+//! ```
+//! use lockbud_report_types::ReportEnvelope;
+//! let json = r#"{"version": 1, "crate_name": "inter", "summary": {}, "reports": []}"#;
+//! let envelope: ReportEnvelope = serde_json::from_str(json).unwrap();
+//! assert!(envelope.reports.is_empty());
+//! ```
+//! The output of lockbud is one `ReportEnvelope` per detected crate.
+//! `ReportContent` includes bug kind, possibility, diagnosis, explanation, and level.
+//! The diagnosis for different kinds of bugs may be different, see `lock`, `atomic`, `panic`, and `plugin`.
+//! `fingerprint` identifies a report by its content for baselines, see `Report::compute_fingerprint`.
+//! `AnalysisMetadata` records the analysis producing an envelope (or a `Baseline`) for tracking the reports over time.
+//! The JSON Schema of `ReportEnvelope` is `schema/report.schema.json`, generated by `schema()`.
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+pub mod atomic;
+pub mod lock;
+pub mod panic;
+pub mod plugin;
+
+use atomic::AtomicityViolationDiagnosis;
+use lock::{
+    AwaitHoldingLockDiagnosis, BlockOnHoldingLockDiagnosis, CondvarDeadlockDiagnosis,
+    CondvarMultipleMutexesDiagnosis, DeadlockDiagnosis, DiscardedGuardDiagnosis,
+    ForceUnlockDiagnosis, HotPathLockDiagnosis, ImplicitReleaseOrderDiagnosis, LockClass,
+    LockCoverageDiagnosis, LockGuardLeakedDiagnosis, OnceInitDeadlockDiagnosis,
+    OnceReentrancyDiagnosis, PoolSelfJoinDiagnosis, RayonDeadlockDiagnosis,
+    RendezvousDeadlockDiagnosis, SendHazardDiagnosis,
+};
+use panic::{PanicDiagnosis, PanicHoldingLockDiagnosis, PanicInDropDiagnosis};
+use plugin::PluginDiagnosis;
+
+/// The version of `ReportEnvelope`, bumped on incompatible changes to the types.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The `alias_confidence` of the diagnoses relying on unknown alias results,
+/// e.g., of the fns skipped by the budgets, which are only reported under `--paranoid`.
+pub const ALIAS_CONFIDENCE_UNKNOWN: &str = "unknown";
+
+/// The `alias_confidence` of the doublelocks whose locks are not known to alias,
+/// but are fields of the same struct type with the second one only reachable through virtual calls.
+pub const ALIAS_CONFIDENCE_SAME_OWNER: &str = "same_owner";
+
+/// The `heuristic` of the deadlock diagnoses whose second lock is only reachable
+/// through the closures assumed to be called by the generic fns without MIR they are passed to.
+pub const HEURISTIC_CLOSURE_ARG: &str = "closure_arg";
+
+/// The `inversion` of the conflictlock relations formed in the closures of `catch_unwind`,
+/// e.g., by a retry after recovering a poisoned lock, which acquire the locks
+/// in the opposite order of the relations formed before the panic.
+pub const INVERSION_PANIC_RETRY: &str = "panic_retry";
+
+/// The reports of a crate.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ReportEnvelope {
+    /// `SCHEMA_VERSION` of the producer.
+    pub version: u32,
+    pub crate_name: String,
+    pub summary: Summary,
+    pub reports: Vec<Report>,
+    /// The locks of the deadlock reports, referred to by the `LockClass` ids in the diagnoses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locks: Vec<LockClass>,
+    /// The reports grouped by their primary locks with `--group-by lock`,
+    /// the reports in no group are ungrouped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<LockGroup>,
+    /// The analysis producing the reports, absent in the envelopes of older lockbud.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AnalysisMetadata>,
+    /// The fns skipped by the budgets of the analyses, whose bugs may be missed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_functions: Vec<SkippedFunction>,
+    /// The counters of the alias queries and the points-to cache with `--alias-stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_stats: Option<AliasStats>,
+}
+
+impl ReportEnvelope {
+    pub fn new(crate_name: String, reports: Vec<Report>) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            crate_name,
+            summary: Summary::new(&reports),
+            reports,
+            locks: Vec::new(),
+            groups: Vec::new(),
+            metadata: None,
+            skipped_functions: Vec::new(),
+            alias_stats: None,
+        }
+    }
+}
+
+/// A fn skipped by an analysis after exceeding its budget (`--fn-time-budget` or `--fn-node-budget`).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedFunction {
+    pub def_path: String,
+    /// The analysis skipping the fn, e.g., `points-to`.
+    pub analysis: String,
+    /// The budget exceeded, e.g., `exceeded the time budget of 10s`.
+    pub reason: String,
+}
+
+/// The detector issuing alias queries.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+pub enum AliasQueryContext {
+    #[default]
+    Unknown,
+    Deadlock,
+    AtomicityViolation,
+    InvalidFree,
+    UseAfterFree,
+    DoubleFree,
+    /// The points-to analysis to resolve indirect calls when building the callgraph.
+    IndirectCalls,
+}
+
+/// Count each kind of query result.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AliasKindCounter {
+    pub probably: usize,
+    pub possibly: usize,
+    pub unlikely: usize,
+    pub unknown: usize,
+}
+
+impl AliasKindCounter {
+    pub fn total(&self) -> usize {
+        self.probably + self.possibly + self.unlikely + self.unknown
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AliasQueryCounters {
+    pub alias: AliasKindCounter,
+    pub points_to: AliasKindCounter,
+    /// Interproc queries resolved by pointing to params of the same type.
+    pub same_type_param: usize,
+    /// The reports of the alias-gated kinds (e.g., DoubleLock, UseAfterFree) whose possibility is Possibly.
+    pub reports_gated_on_possibly: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PointsToCacheCounters {
+    pub hits: usize,
+    /// The hits on the points-to info computed for another detector.
+    pub cross_detector_hits: usize,
+    pub misses: usize,
+}
+
+/// The counters of the alias queries and the points-to cache shared by the detectors, keyed by the detectors issuing them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AliasStats {
+    pub queries: BTreeMap<AliasQueryContext, AliasQueryCounters>,
+    pub points_to_cache: BTreeMap<AliasQueryContext, PointsToCacheCounters>,
+}
+
+/// The analysis producing the reports, recorded once per envelope rather than per report.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AnalysisMetadata {
+    pub lockbud_version: String,
+    /// The start of the analysis in RFC 3339, e.g., `2024-01-31T12:00:00Z`.
+    pub analyzed_at: String,
+    /// The git commit of the analyzed workspace, given by `cargo lockbud` if discoverable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    /// The hash of the options changing the reports, the same across the crates of a `cargo lockbud` run.
+    pub options_hash: String,
+}
+
+impl fmt::Display for AnalysisMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lockbud {} at {}",
+            self.lockbud_version, self.analyzed_at
+        )?;
+        if let Some(git_commit) = &self.git_commit {
+            write!(f, " on commit {}", git_commit)?;
+        }
+        write!(f, " with options {}", self.options_hash)
+    }
+}
+
+/// A baseline file, i.e., the fingerprints of the accepted reports
+/// with the metadata of the analysis writing them (if known).
+/// The baseline files of older lockbud, i.e., plain JSON lists of the fingerprints, are also accepted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "BaselineFile")]
+pub struct Baseline {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AnalysisMetadata>,
+    pub fingerprints: BTreeSet<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BaselineFile {
+    Fingerprints(BTreeSet<String>),
+    WithMetadata {
+        #[serde(default)]
+        metadata: Option<AnalysisMetadata>,
+        fingerprints: BTreeSet<String>,
+    },
+}
+
+impl From<BaselineFile> for Baseline {
+    fn from(file: BaselineFile) -> Self {
+        match file {
+            BaselineFile::Fingerprints(fingerprints) => Self {
+                metadata: None,
+                fingerprints,
+            },
+            BaselineFile::WithMetadata {
+                metadata,
+                fingerprints,
+            } => Self {
+                metadata,
+                fingerprints,
+            },
+        }
+    }
+}
+
+/// The reports whose primary locks are in the same `LockClass`:
+/// the first lock of DoubleLock and ConflictLock, and the first wait lock of CondvarDeadlock.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LockGroup {
+    pub lock: LockClass,
+    /// The indices of the reports in `ReportEnvelope::reports`.
+    pub reports: Vec<usize>,
+}
+
+/// Group the reports by the `LockClass` of their primary locks, in the order of `classes`.
+/// The classes without reports are skipped.
+pub fn group_by_lock(reports: &[Report], classes: &[LockClass]) -> Vec<LockGroup> {
+    classes
+        .iter()
+        .filter_map(|class| {
+            let indices = reports
+                .iter()
+                .enumerate()
+                .filter(|(_, report)| report.lock_class() == Some(class.id))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            (!indices.is_empty()).then(|| LockGroup {
+                lock: class.clone(),
+                reports: indices,
+            })
+        })
+        .collect()
+}
+
+/// The classes of the locks in the reports, in the order of `classes`.
+pub fn referenced_locks(reports: &[Report], classes: &[LockClass]) -> Vec<LockClass> {
+    let referenced = reports
+        .iter()
+        .flat_map(Report::lock_classes)
+        .collect::<BTreeSet<_>>();
+    classes
+        .iter()
+        .filter(|class| referenced.contains(&class.id))
+        .cloned()
+        .collect()
+}
+
+/// The level of a kind of reports.
+/// `Allow` drops the reports, `Warn` emits them, and `Deny` emits them and makes lockbud exit with an error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ReportContent<D> {
+    pub bug_kind: String,
+    pub possibility: String,
+    pub diagnosis: D,
+    pub explanation: String,
+    pub level: Level,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub fingerprint: String,
+    /// The confidence (0-100) of the deadlock reports, combining the evidence of the locks aliasing
+    /// and the feasibility of the callchains, see `--min-confidence`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<u8>,
+}
+
+impl<D: std::fmt::Debug> ReportContent<D> {
+    pub fn new(bug_kind: String, possibility: String, diagnosis: D, explanation: String) -> Self {
+        Self {
+            bug_kind,
+            possibility,
+            diagnosis,
+            explanation,
+            level: Level::default(),
+            fingerprint: String::new(),
+            confidence: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub enum Report {
+    DoubleLock(ReportContent<DeadlockDiagnosis>),
+    ConflictLock(ReportContent<Vec<DeadlockDiagnosis>>),
+    CondvarDeadlock(ReportContent<CondvarDeadlockDiagnosis>),
+    CondvarMultipleMutexes(ReportContent<CondvarMultipleMutexesDiagnosis>),
+    AwaitHoldingLock(ReportContent<AwaitHoldingLockDiagnosis>),
+    BlockOnHoldingLock(ReportContent<BlockOnHoldingLockDiagnosis>),
+    PoolSelfJoin(ReportContent<PoolSelfJoinDiagnosis>),
+    RayonDeadlock(ReportContent<RayonDeadlockDiagnosis>),
+    RendezvousDeadlock(ReportContent<RendezvousDeadlockDiagnosis>),
+    OnceReentrancy(ReportContent<OnceReentrancyDiagnosis>),
+    OnceInitDeadlock(ReportContent<OnceInitDeadlockDiagnosis>),
+    LockGuardLeaked(ReportContent<LockGuardLeakedDiagnosis>),
+    DoubleUnlock(ReportContent<ForceUnlockDiagnosis>),
+    UseAfterUnlock(ReportContent<ForceUnlockDiagnosis>),
+    ImplicitReleaseOrder(ReportContent<ImplicitReleaseOrderDiagnosis>),
+    SendHazard(ReportContent<SendHazardDiagnosis>),
+    HotPathLock(ReportContent<HotPathLockDiagnosis>),
+    DiscardedGuard(ReportContent<DiscardedGuardDiagnosis>),
+    LockCoverage(ReportContent<LockCoverageDiagnosis>),
+    AtomicityViolation(ReportContent<AtomicityViolationDiagnosis>),
+    InvalidFree(ReportContent<String>),
+    UseAfterFree(ReportContent<String>),
+    DoubleFree(ReportContent<String>),
+    PanicInDrop(ReportContent<PanicInDropDiagnosis>),
+    PanicHoldingLock(ReportContent<PanicHoldingLockDiagnosis>),
+    RefCellDoubleBorrow(ReportContent<DeadlockDiagnosis>),
+    Panic(ReportContent<PanicDiagnosis>),
+    Plugin(ReportContent<PluginDiagnosis>),
+}
+
+impl Report {
+    pub fn possibility(&self) -> &str {
+        match self {
+            Report::DoubleLock(content) => &content.possibility,
+            Report::ConflictLock(content) => &content.possibility,
+            Report::CondvarDeadlock(content) => &content.possibility,
+            Report::CondvarMultipleMutexes(content) => &content.possibility,
+            Report::AwaitHoldingLock(content) => &content.possibility,
+            Report::BlockOnHoldingLock(content) => &content.possibility,
+            Report::PoolSelfJoin(content) => &content.possibility,
+            Report::RayonDeadlock(content) => &content.possibility,
+            Report::RendezvousDeadlock(content) => &content.possibility,
+            Report::OnceReentrancy(content) => &content.possibility,
+            Report::OnceInitDeadlock(content) => &content.possibility,
+            Report::LockGuardLeaked(content) => &content.possibility,
+            Report::DoubleUnlock(content) => &content.possibility,
+            Report::UseAfterUnlock(content) => &content.possibility,
+            Report::ImplicitReleaseOrder(content) => &content.possibility,
+            Report::SendHazard(content) => &content.possibility,
+            Report::HotPathLock(content) => &content.possibility,
+            Report::DiscardedGuard(content) => &content.possibility,
+            Report::LockCoverage(content) => &content.possibility,
+            Report::AtomicityViolation(content) => &content.possibility,
+            Report::InvalidFree(content) => &content.possibility,
+            Report::UseAfterFree(content) => &content.possibility,
+            Report::DoubleFree(content) => &content.possibility,
+            Report::PanicInDrop(content) => &content.possibility,
+            Report::PanicHoldingLock(content) => &content.possibility,
+            Report::RefCellDoubleBorrow(content) => &content.possibility,
+            Report::Panic(content) => &content.possibility,
+            Report::Plugin(content) => &content.possibility,
+        }
+    }
+
+    pub fn level(&self) -> Level {
+        match self {
+            Report::DoubleLock(content) => content.level,
+            Report::ConflictLock(content) => content.level,
+            Report::CondvarDeadlock(content) => content.level,
+            Report::CondvarMultipleMutexes(content) => content.level,
+            Report::AwaitHoldingLock(content) => content.level,
+            Report::BlockOnHoldingLock(content) => content.level,
+            Report::PoolSelfJoin(content) => content.level,
+            Report::RayonDeadlock(content) => content.level,
+            Report::RendezvousDeadlock(content) => content.level,
+            Report::OnceReentrancy(content) => content.level,
+            Report::OnceInitDeadlock(content) => content.level,
+            Report::LockGuardLeaked(content) => content.level,
+            Report::DoubleUnlock(content) => content.level,
+            Report::UseAfterUnlock(content) => content.level,
+            Report::ImplicitReleaseOrder(content) => content.level,
+            Report::SendHazard(content) => content.level,
+            Report::HotPathLock(content) => content.level,
+            Report::DiscardedGuard(content) => content.level,
+            Report::LockCoverage(content) => content.level,
+            Report::AtomicityViolation(content) => content.level,
+            Report::InvalidFree(content) => content.level,
+            Report::UseAfterFree(content) => content.level,
+            Report::DoubleFree(content) => content.level,
+            Report::PanicInDrop(content) => content.level,
+            Report::PanicHoldingLock(content) => content.level,
+            Report::RefCellDoubleBorrow(content) => content.level,
+            Report::Panic(content) => content.level,
+            Report::Plugin(content) => content.level,
+        }
+    }
+
+    /// The confidence of the report if scored, see `ReportContent::confidence`.
+    pub fn confidence(&self) -> Option<u8> {
+        match self {
+            Report::DoubleLock(content) => content.confidence,
+            Report::ConflictLock(content) => content.confidence,
+            Report::CondvarDeadlock(content) => content.confidence,
+            Report::CondvarMultipleMutexes(content) => content.confidence,
+            Report::AwaitHoldingLock(content) => content.confidence,
+            Report::BlockOnHoldingLock(content) => content.confidence,
+            Report::PoolSelfJoin(content) => content.confidence,
+            Report::RayonDeadlock(content) => content.confidence,
+            Report::RendezvousDeadlock(content) => content.confidence,
+            Report::OnceReentrancy(content) => content.confidence,
+            Report::OnceInitDeadlock(content) => content.confidence,
+            Report::LockGuardLeaked(content) => content.confidence,
+            Report::DoubleUnlock(content) => content.confidence,
+            Report::UseAfterUnlock(content) => content.confidence,
+            Report::ImplicitReleaseOrder(content) => content.confidence,
+            Report::SendHazard(content) => content.confidence,
+            Report::HotPathLock(content) => content.confidence,
+            Report::DiscardedGuard(content) => content.confidence,
+            Report::LockCoverage(content) => content.confidence,
+            Report::AtomicityViolation(content) => content.confidence,
+            Report::InvalidFree(content) => content.confidence,
+            Report::UseAfterFree(content) => content.confidence,
+            Report::DoubleFree(content) => content.confidence,
+            Report::PanicInDrop(content) => content.confidence,
+            Report::PanicHoldingLock(content) => content.confidence,
+            Report::RefCellDoubleBorrow(content) => content.confidence,
+            Report::Panic(content) => content.confidence,
+            Report::Plugin(content) => content.confidence,
+        }
+    }
+
+    pub fn set_level(&mut self, level: Level) {
+        match self {
+            Report::DoubleLock(content) => content.level = level,
+            Report::ConflictLock(content) => content.level = level,
+            Report::CondvarDeadlock(content) => content.level = level,
+            Report::CondvarMultipleMutexes(content) => content.level = level,
+            Report::AwaitHoldingLock(content) => content.level = level,
+            Report::BlockOnHoldingLock(content) => content.level = level,
+            Report::PoolSelfJoin(content) => content.level = level,
+            Report::RayonDeadlock(content) => content.level = level,
+            Report::RendezvousDeadlock(content) => content.level = level,
+            Report::OnceReentrancy(content) => content.level = level,
+            Report::OnceInitDeadlock(content) => content.level = level,
+            Report::LockGuardLeaked(content) => content.level = level,
+            Report::DoubleUnlock(content) => content.level = level,
+            Report::UseAfterUnlock(content) => content.level = level,
+            Report::ImplicitReleaseOrder(content) => content.level = level,
+            Report::SendHazard(content) => content.level = level,
+            Report::HotPathLock(content) => content.level = level,
+            Report::DiscardedGuard(content) => content.level = level,
+            Report::LockCoverage(content) => content.level = level,
+            Report::AtomicityViolation(content) => content.level = level,
+            Report::InvalidFree(content) => content.level = level,
+            Report::UseAfterFree(content) => content.level = level,
+            Report::DoubleFree(content) => content.level = level,
+            Report::PanicInDrop(content) => content.level = level,
+            Report::PanicHoldingLock(content) => content.level = level,
+            Report::RefCellDoubleBorrow(content) => content.level = level,
+            Report::Panic(content) => content.level = level,
+            Report::Plugin(content) => content.level = level,
+        }
+    }
+
+    pub fn fingerprint(&self) -> &str {
+        match self {
+            Report::DoubleLock(content) => &content.fingerprint,
+            Report::ConflictLock(content) => &content.fingerprint,
+            Report::CondvarDeadlock(content) => &content.fingerprint,
+            Report::CondvarMultipleMutexes(content) => &content.fingerprint,
+            Report::AwaitHoldingLock(content) => &content.fingerprint,
+            Report::BlockOnHoldingLock(content) => &content.fingerprint,
+            Report::PoolSelfJoin(content) => &content.fingerprint,
+            Report::RayonDeadlock(content) => &content.fingerprint,
+            Report::RendezvousDeadlock(content) => &content.fingerprint,
+            Report::OnceReentrancy(content) => &content.fingerprint,
+            Report::OnceInitDeadlock(content) => &content.fingerprint,
+            Report::LockGuardLeaked(content) => &content.fingerprint,
+            Report::DoubleUnlock(content) => &content.fingerprint,
+            Report::UseAfterUnlock(content) => &content.fingerprint,
+            Report::ImplicitReleaseOrder(content) => &content.fingerprint,
+            Report::SendHazard(content) => &content.fingerprint,
+            Report::HotPathLock(content) => &content.fingerprint,
+            Report::DiscardedGuard(content) => &content.fingerprint,
+            Report::LockCoverage(content) => &content.fingerprint,
+            Report::AtomicityViolation(content) => &content.fingerprint,
+            Report::InvalidFree(content) => &content.fingerprint,
+            Report::UseAfterFree(content) => &content.fingerprint,
+            Report::DoubleFree(content) => &content.fingerprint,
+            Report::PanicInDrop(content) => &content.fingerprint,
+            Report::PanicHoldingLock(content) => &content.fingerprint,
+            Report::RefCellDoubleBorrow(content) => &content.fingerprint,
+            Report::Panic(content) => &content.fingerprint,
+            Report::Plugin(content) => &content.fingerprint,
+        }
+    }
+
+    pub fn set_fingerprint(&mut self, fingerprint: String) {
+        match self {
+            Report::DoubleLock(content) => content.fingerprint = fingerprint,
+            Report::ConflictLock(content) => content.fingerprint = fingerprint,
+            Report::CondvarDeadlock(content) => content.fingerprint = fingerprint,
+            Report::CondvarMultipleMutexes(content) => content.fingerprint = fingerprint,
+            Report::AwaitHoldingLock(content) => content.fingerprint = fingerprint,
+            Report::BlockOnHoldingLock(content) => content.fingerprint = fingerprint,
+            Report::PoolSelfJoin(content) => content.fingerprint = fingerprint,
+            Report::RayonDeadlock(content) => content.fingerprint = fingerprint,
+            Report::RendezvousDeadlock(content) => content.fingerprint = fingerprint,
+            Report::OnceReentrancy(content) => content.fingerprint = fingerprint,
+            Report::OnceInitDeadlock(content) => content.fingerprint = fingerprint,
+            Report::LockGuardLeaked(content) => content.fingerprint = fingerprint,
+            Report::DoubleUnlock(content) => content.fingerprint = fingerprint,
+            Report::UseAfterUnlock(content) => content.fingerprint = fingerprint,
+            Report::ImplicitReleaseOrder(content) => content.fingerprint = fingerprint,
+            Report::SendHazard(content) => content.fingerprint = fingerprint,
+            Report::HotPathLock(content) => content.fingerprint = fingerprint,
+            Report::DiscardedGuard(content) => content.fingerprint = fingerprint,
+            Report::LockCoverage(content) => content.fingerprint = fingerprint,
+            Report::AtomicityViolation(content) => content.fingerprint = fingerprint,
+            Report::InvalidFree(content) => content.fingerprint = fingerprint,
+            Report::UseAfterFree(content) => content.fingerprint = fingerprint,
+            Report::DoubleFree(content) => content.fingerprint = fingerprint,
+            Report::PanicInDrop(content) => content.fingerprint = fingerprint,
+            Report::PanicHoldingLock(content) => content.fingerprint = fingerprint,
+            Report::RefCellDoubleBorrow(content) => content.fingerprint = fingerprint,
+            Report::Panic(content) => content.fingerprint = fingerprint,
+            Report::Plugin(content) => content.fingerprint = fingerprint,
+        }
+    }
+
+    /// The content identifying the report regardless of where it is in the file:
+    /// groups of (types, texts containing spans), one group per deadlock of a conflictlock.
+    /// The types are lock types, or the APIs for the reports without locks.
+    fn fingerprint_fields(&self) -> Vec<(Vec<&str>, Vec<&str>)> {
+        fn deadlock(diagnosis: &DeadlockDiagnosis) -> (Vec<&str>, Vec<&str>) {
+            (
+                vec![&diagnosis.first_lock_type, &diagnosis.second_lock_type],
+                vec![&diagnosis.first_lock_span, &diagnosis.second_lock_span],
+            )
+        }
+        match self {
+            Report::DoubleLock(content) => vec![deadlock(&content.diagnosis)],
+            Report::ConflictLock(content) => content.diagnosis.iter().map(deadlock).collect(),
+            Report::CondvarDeadlock(content) => {
+                let diagnosis = &content.diagnosis;
+                let mut types = vec![
+                    diagnosis.condvar_wait_type.as_str(),
+                    &diagnosis.condvar_notify_type,
+                ];
+                let mut spans = vec![
+                    diagnosis.condvar_wait_callsite_span.as_str(),
+                    &diagnosis.condvar_notify_callsite_span,
+                ];
+                for locks in &diagnosis.deadlocks {
+                    types.extend([locks.wait_lock_type.as_str(), &locks.notify_lock_type]);
+                    spans.extend([locks.wait_lock_span.as_str(), &locks.notify_lock_span]);
+                }
+                vec![(types, spans)]
+            }
+            Report::CondvarMultipleMutexes(content) => {
+                let diagnosis = &content.diagnosis;
+                let mut types = vec![diagnosis.condvar_wait_type.as_str()];
+                let mut spans = Vec::new();
+                for mutex in &diagnosis.mutexes {
+                    types.push(&mutex.mutex);
+                    spans.push(mutex.declaration_span.as_str());
+                    spans.extend(mutex.wait_spans.iter().map(String::as_str));
+                }
+                vec![(types, spans)]
+            }
+            Report::AwaitHoldingLock(content) => vec![(
+                vec![&content.diagnosis.lock_type],
+                vec![&content.diagnosis.lock_span],
+            )],
+            Report::BlockOnHoldingLock(content) => vec![(
+                vec![
+                    &content.diagnosis.lock_type,
+                    &content.diagnosis.block_on_api,
+                ],
+                vec![
+                    &content.diagnosis.lock_span,
+                    &content.diagnosis.block_on_span,
+                ],
+            )],
+            Report::PoolSelfJoin(content) => vec![(
+                vec![&content.diagnosis.pool, &content.diagnosis.wait_api],
+                vec![
+                    &content.diagnosis.install_span,
+                    &content.diagnosis.wait_span,
+                ],
+            )],
+            Report::RayonDeadlock(content) => vec![(
+                vec![&content.diagnosis.lock_type, &content.diagnosis.rayon_api],
+                vec![
+                    &content.diagnosis.outer_lock_span,
+                    &content.diagnosis.rayon_span,
+                    &content.diagnosis.inner_lock_span,
+                ],
+            )],
+            Report::RendezvousDeadlock(content) => vec![(
+                vec![
+                    &content.diagnosis.lock_type,
+                    &content.diagnosis.rendezvous_api,
+                ],
+                vec![
+                    &content.diagnosis.lock_span,
+                    &content.diagnosis.rendezvous_span,
+                    &content.diagnosis.peer_lock_span,
+                ],
+            )],
+            Report::OnceReentrancy(content) => vec![(
+                vec![&content.diagnosis.once_api],
+                vec![&content.diagnosis.outer_span, &content.diagnosis.inner_span],
+            )],
+            Report::OnceInitDeadlock(content) => vec![(
+                vec![&content.diagnosis.lock_type, &content.diagnosis.once_api],
+                vec![
+                    &content.diagnosis.outer_lock_span,
+                    &content.diagnosis.init_span,
+                    &content.diagnosis.inner_lock_span,
+                ],
+            )],
+            Report::LockGuardLeaked(content) => vec![(
+                vec![&content.diagnosis.lock_type, &content.diagnosis.leak_api],
+                vec![&content.diagnosis.lock_span, &content.diagnosis.leak_span],
+            )],
+            Report::DoubleUnlock(content) | Report::UseAfterUnlock(content) => vec![(
+                vec![&content.diagnosis.lock_type, &content.diagnosis.unlock_api],
+                vec![
+                    &content.diagnosis.unlock_span,
+                    &content.diagnosis.later_span,
+                ],
+            )],
+            Report::ImplicitReleaseOrder(content) => {
+                let (first, second) = (&content.diagnosis.first, &content.diagnosis.second);
+                let mut types = vec![first.name.as_str(), &second.name];
+                types.extend(first.lock_types.iter().map(String::as_str));
+                vec![(types, vec![&first.span, &second.span])]
+            }
+            Report::SendHazard(content) => vec![(
+                vec![&content.diagnosis.guard_type, &content.diagnosis.spawn_api],
+                vec![&content.diagnosis.guard_span],
+            )],
+            Report::HotPathLock(content) => {
+                let diagnosis = &content.diagnosis;
+                let mut types = vec![diagnosis.entry_fn.as_str()];
+                let mut spans = vec![diagnosis.entry_span.as_str()];
+                for lock in &diagnosis.must_acquire {
+                    types.push(&lock.name);
+                    spans.push(&lock.declaration_span);
+                }
+                vec![(types, spans)]
+            }
+            Report::DiscardedGuard(content) => vec![(
+                vec![&content.diagnosis.lock_type],
+                vec![&content.diagnosis.lock_span],
+            )],
+            Report::LockCoverage(content) => {
+                let diagnosis = &content.diagnosis;
+                let mut spans = vec![diagnosis.field_span.as_str()];
+                spans.extend(diagnosis.unprotected_spans.iter().map(String::as_str));
+                vec![(vec![&diagnosis.field, &diagnosis.lock], spans)]
+            }
+            Report::AtomicityViolation(content) => vec![(
+                vec![&content.diagnosis.dep_kind],
+                vec![
+                    &content.diagnosis.atomic_reader,
+                    &content.diagnosis.atomic_writer,
+                ],
+            )],
+            Report::InvalidFree(content) => vec![(Vec::new(), vec![&content.diagnosis])],
+            Report::UseAfterFree(content) => vec![(Vec::new(), vec![&content.diagnosis])],
+            Report::DoubleFree(content) => vec![(Vec::new(), vec![&content.diagnosis])],
+            Report::PanicInDrop(content) => vec![(
+                vec![&content.diagnosis.drop_type, &content.diagnosis.panic_api],
+                vec![&content.diagnosis.drop_span, &content.diagnosis.panic_span],
+            )],
+            Report::PanicHoldingLock(content) => vec![(
+                vec![&content.diagnosis.lock_type, &content.diagnosis.panic_api],
+                vec![&content.diagnosis.guard_span, &content.diagnosis.panic_span],
+            )],
+            Report::RefCellDoubleBorrow(content) => vec![deadlock(&content.diagnosis)],
+            Report::Panic(content) => vec![(
+                vec![&content.diagnosis.def_path, &content.diagnosis.panic_api],
+                vec![&content.diagnosis.callsite_span],
+            )],
+            Report::Plugin(content) => vec![(
+                vec![&content.diagnosis.plugin, &content.diagnosis.subject],
+                vec![&content.diagnosis.span],
+            )],
+        }
+    }
+
+    /// The fingerprint of the report: a hash of its kind, types, and spans,
+    /// where each span is replaced with `locate(file, line)` to ignore the columns
+    /// and, e.g., to be relative to the enclosing fn so that moving the fn keeps the fingerprint.
+    /// The deadlocks of a conflictlock are hashed in a canonical order since they form a cycle.
+    pub fn compute_fingerprint(&self, locate: impl Fn(&str, usize) -> String) -> String {
+        let mut groups = self
+            .fingerprint_fields()
+            .into_iter()
+            .map(|(types, texts)| {
+                let mut parts = types.into_iter().map(str::to_owned).collect::<Vec<_>>();
+                for text in texts {
+                    parts.extend(
+                        span_lines(text)
+                            .into_iter()
+                            .map(|(file, line)| locate(file, line)),
+                    );
+                }
+                parts
+            })
+            .collect::<Vec<_>>();
+        groups.sort();
+        let mut hasher = Fnv1a::new();
+        hasher.write(self.level_keys()[0]);
+        for part in groups.iter().flatten() {
+            hasher.write(part);
+        }
+        format!("{:016x}", hasher.0)
+    }
+
+    /// The `LockClass` ids of all the locks in the report.
+    pub fn lock_classes(&self) -> Vec<usize> {
+        fn deadlock(diagnosis: &DeadlockDiagnosis) -> [Option<usize>; 2] {
+            [diagnosis.lock_class, diagnosis.second_lock_class]
+        }
+        let classes = match self {
+            Report::DoubleLock(content) => deadlock(&content.diagnosis).to_vec(),
+            Report::ConflictLock(content) => content.diagnosis.iter().flat_map(deadlock).collect(),
+            Report::CondvarDeadlock(content) => content
+                .diagnosis
+                .deadlocks
+                .iter()
+                .flat_map(|locks| [locks.wait_lock_class, locks.notify_lock_class])
+                .collect(),
+            _ => Vec::new(),
+        };
+        classes.into_iter().flatten().collect()
+    }
+
+    /// The `LockClass` id of the primary lock, see `LockGroup`.
+    pub fn lock_class(&self) -> Option<usize> {
+        match self {
+            Report::DoubleLock(content) => content.diagnosis.lock_class,
+            Report::ConflictLock(content) => content.diagnosis.first()?.lock_class,
+            Report::CondvarDeadlock(content) => content.diagnosis.lock_class,
+            _ => None,
+        }
+    }
+
+    /// Whether the report relies on unknown alias results, i.e., only emitted under `--paranoid`.
+    pub fn is_paranoid(&self) -> bool {
+        let unknown =
+            |confidence: &Option<String>| confidence.as_deref() == Some(ALIAS_CONFIDENCE_UNKNOWN);
+        match self {
+            Report::DoubleLock(content) | Report::RefCellDoubleBorrow(content) => {
+                unknown(&content.diagnosis.alias_confidence)
+            }
+            Report::ConflictLock(content) => content
+                .diagnosis
+                .iter()
+                .any(|diagnosis| unknown(&diagnosis.alias_confidence)),
+            Report::CondvarDeadlock(content) => unknown(&content.diagnosis.alias_confidence),
+            Report::RayonDeadlock(content) => unknown(&content.diagnosis.alias_confidence),
+            Report::RendezvousDeadlock(content) => unknown(&content.diagnosis.alias_confidence),
+            Report::OnceReentrancy(content) => unknown(&content.diagnosis.alias_confidence),
+            Report::OnceInitDeadlock(content) => unknown(&content.diagnosis.alias_confidence),
+            Report::AtomicityViolation(content) => unknown(&content.diagnosis.alias_confidence),
+            _ => false,
+        }
+    }
+
+    /// The keys of `levels` in the config file applying to the report, from the most specific one.
+    pub fn level_keys(&self) -> [&'static str; 2] {
+        match self {
+            Report::DoubleLock(_) => ["double_lock", "deadlock"],
+            Report::ConflictLock(_) => ["conflict_lock", "deadlock"],
+            Report::CondvarDeadlock(_) => ["condvar", "deadlock"],
+            Report::CondvarMultipleMutexes(_) => ["condvar_multiple_mutexes", "deadlock"],
+            Report::AwaitHoldingLock(_) => ["await_holding_lock", "deadlock"],
+            Report::BlockOnHoldingLock(_) => ["block_on_holding_lock", "deadlock"],
+            Report::PoolSelfJoin(_) => ["pool_self_join", "deadlock"],
+            Report::RayonDeadlock(_) => ["rayon_deadlock", "deadlock"],
+            Report::RendezvousDeadlock(_) => ["rendezvous_deadlock", "deadlock"],
+            Report::OnceReentrancy(_) => ["once_reentrancy", "deadlock"],
+            Report::OnceInitDeadlock(_) => ["once_init_deadlock", "deadlock"],
+            Report::LockGuardLeaked(_) => ["lock_guard_leaked", "deadlock"],
+            Report::DoubleUnlock(_) => ["double_unlock", "deadlock"],
+            Report::UseAfterUnlock(_) => ["use_after_unlock", "deadlock"],
+            Report::ImplicitReleaseOrder(_) => ["implicit_release_order", "deadlock"],
+            Report::SendHazard(_) => ["send_hazard", "deadlock"],
+            Report::HotPathLock(_) => ["hot_path_lock", "deadlock"],
+            Report::DiscardedGuard(_) => ["discarded_guard", "deadlock"],
+            Report::LockCoverage(_) => ["lock_coverage", "deadlock"],
+            Report::AtomicityViolation(_) => ["atomicity_violation", "atomicity_violation"],
+            Report::InvalidFree(_) => ["invalid_free", "memory"],
+            Report::UseAfterFree(_) => ["use_after_free", "memory"],
+            Report::DoubleFree(_) => ["double_free", "memory"],
+            Report::PanicInDrop(_) => ["panic_in_drop", "panic"],
+            Report::PanicHoldingLock(_) => ["panic_holding_lock", "panic"],
+            Report::RefCellDoubleBorrow(_) => ["refcell_double_borrow", "panic"],
+            Report::Panic(_) => ["panic_site", "panic"],
+            Report::Plugin(_) => ["plugin", "deadlock"],
+        }
+    }
+
+    /// The primary and secondary spans of the report.
+    /// CondvarMultipleMutexes has the first wait spans of the first two mutexes.
+    /// The memory reports only have textual diagnoses, where the first span is used.
+    pub fn spans(&self) -> (&str, &str) {
+        match self {
+            Report::DoubleLock(content) | Report::RefCellDoubleBorrow(content) => (
+                &content.diagnosis.first_lock_span,
+                &content.diagnosis.second_lock_span,
+            ),
+            Report::ConflictLock(content) => content
+                .diagnosis
+                .first()
+                .map(|diagnosis| {
+                    (
+                        diagnosis.first_lock_span.as_str(),
+                        diagnosis.second_lock_span.as_str(),
+                    )
+                })
+                .unwrap_or_default(),
+            Report::CondvarDeadlock(content) => (
+                &content.diagnosis.condvar_wait_callsite_span,
+                &content.diagnosis.condvar_notify_callsite_span,
+            ),
+            Report::CondvarMultipleMutexes(content) => {
+                let mut wait_spans = content
+                    .diagnosis
+                    .mutexes
+                    .iter()
+                    .filter_map(|mutex| mutex.wait_spans.first());
+                (
+                    wait_spans.next().map_or("", String::as_str),
+                    wait_spans.next().map_or("", String::as_str),
+                )
+            }
+            Report::AwaitHoldingLock(content) => (&content.diagnosis.lock_span, ""),
+            Report::BlockOnHoldingLock(content) => (
+                &content.diagnosis.lock_span,
+                &content.diagnosis.block_on_span,
+            ),
+            Report::PoolSelfJoin(content) => (
+                &content.diagnosis.install_span,
+                &content.diagnosis.wait_span,
+            ),
+            Report::RayonDeadlock(content) => (
+                &content.diagnosis.outer_lock_span,
+                &content.diagnosis.inner_lock_span,
+            ),
+            Report::RendezvousDeadlock(content) => (
+                &content.diagnosis.rendezvous_span,
+                &content.diagnosis.peer_lock_span,
+            ),
+            Report::OnceReentrancy(content) => {
+                (&content.diagnosis.outer_span, &content.diagnosis.inner_span)
+            }
+            Report::OnceInitDeadlock(content) => (
+                &content.diagnosis.outer_lock_span,
+                &content.diagnosis.inner_lock_span,
+            ),
+            Report::LockGuardLeaked(content) => {
+                (&content.diagnosis.lock_span, &content.diagnosis.leak_span)
+            }
+            Report::DoubleUnlock(content) | Report::UseAfterUnlock(content) => (
+                &content.diagnosis.unlock_span,
+                &content.diagnosis.later_span,
+            ),
+            Report::ImplicitReleaseOrder(content) => (
+                &content.diagnosis.first.span,
+                &content.diagnosis.second.span,
+            ),
+            Report::SendHazard(content) => {
+                (&content.diagnosis.guard_span, &content.diagnosis.spawn_span)
+            }
+            Report::HotPathLock(content) => (
+                &content.diagnosis.entry_span,
+                content
+                    .diagnosis
+                    .must_acquire
+                    .first()
+                    .map_or("", |lock| lock.declaration_span.as_str()),
+            ),
+            Report::DiscardedGuard(content) => (&content.diagnosis.lock_span, ""),
+            Report::LockCoverage(content) => (
+                content
+                    .diagnosis
+                    .unprotected_spans
+                    .first()
+                    .map_or("", String::as_str),
+                &content.diagnosis.field_span,
+            ),
+            Report::AtomicityViolation(content) => (
+                &content.diagnosis.atomic_reader,
+                &content.diagnosis.atomic_writer,
+            ),
+            Report::InvalidFree(content) => (&content.diagnosis, ""),
+            Report::UseAfterFree(content) => (&content.diagnosis, ""),
+            Report::DoubleFree(content) => (&content.diagnosis, ""),
+            Report::PanicInDrop(content) => {
+                (&content.diagnosis.drop_span, &content.diagnosis.panic_span)
+            }
+            Report::PanicHoldingLock(content) => {
+                (&content.diagnosis.guard_span, &content.diagnosis.panic_span)
+            }
+            Report::Panic(content) => (
+                &content.diagnosis.callsite_span,
+                &content.diagnosis.outermost_span,
+            ),
+            Report::Plugin(content) => (&content.diagnosis.span, ""),
+        }
+    }
+
+    /// The priority of the report when truncated, the smaller the more actionable:
+    /// deny-level reports first, then probably before possibly, and the informational ones last,
+    /// the more confident first among the reports of the same possibility,
+    /// then the shorter `lock_extent` first, whose lock is held over fewer lines to check.
+    fn priority(&self) -> (bool, usize, Reverse<Option<u8>>, usize) {
+        let possibility = match self.possibility() {
+            _ if matches!(
+                self,
+                Report::ImplicitReleaseOrder(_)
+                    | Report::SendHazard(_)
+                    | Report::HotPathLock(_)
+                    | Report::DiscardedGuard(_)
+                    | Report::Panic(_)
+            ) =>
+            {
+                2
+            }
+            "Probably" => 0,
+            "Possibly" => 1,
+            _ => 2,
+        };
+        (
+            self.level() != Level::Deny,
+            possibility,
+            Reverse(self.confidence()),
+            self.lock_extent().unwrap_or(usize::MAX),
+        )
+    }
+
+    /// The number of lines from the first span (e.g., the first lock) to the second one
+    /// (e.g., the second lock, or the await and the panic holding the lock), both included,
+    /// or `None` if they are in different files (e.g., the second lock in a callee) or there is no second span.
+    pub fn lock_extent(&self) -> Option<usize> {
+        let (first, second) = self.spans();
+        let (first_file, (first_line, _), _) = span_range(first)?;
+        let (second_file, (second_line, _), _) = span_range(second)?;
+        let lines = second_line.checked_sub(first_line)? + 1;
+        (first_file == second_file).then_some(lines)
+    }
+
+    /// The rank of the report kind, in the order of the variants.
+    fn kind_rank(&self) -> usize {
+        match self {
+            Report::DoubleLock(_) => 0,
+            Report::ConflictLock(_) => 1,
+            Report::CondvarDeadlock(_) => 2,
+            Report::CondvarMultipleMutexes(_) => 3,
+            Report::AwaitHoldingLock(_) => 4,
+            Report::BlockOnHoldingLock(_) => 5,
+            Report::PoolSelfJoin(_) => 6,
+            Report::RayonDeadlock(_) => 7,
+            Report::RendezvousDeadlock(_) => 8,
+            Report::OnceReentrancy(_) => 9,
+            Report::OnceInitDeadlock(_) => 10,
+            Report::LockGuardLeaked(_) => 11,
+            Report::DoubleUnlock(_) => 12,
+            Report::UseAfterUnlock(_) => 13,
+            Report::ImplicitReleaseOrder(_) => 14,
+            Report::SendHazard(_) => 15,
+            Report::HotPathLock(_) => 16,
+            Report::DiscardedGuard(_) => 17,
+            Report::LockCoverage(_) => 18,
+            Report::AtomicityViolation(_) => 19,
+            Report::InvalidFree(_) => 20,
+            Report::UseAfterFree(_) => 21,
+            Report::DoubleFree(_) => 22,
+            Report::PanicInDrop(_) => 23,
+            Report::PanicHoldingLock(_) => 24,
+            Report::RefCellDoubleBorrow(_) => 25,
+            Report::Panic(_) => 26,
+            Report::Plugin(_) => 27,
+        }
+    }
+}
+
+/// The (file, line, column) of the first span like `src/main.rs:9:5: 9:20 (#0)` in `text`,
+/// so that line 9 sorts before line 10.
+fn span_key(text: &str) -> (&str, usize, usize) {
+    let parse = || {
+        let end = text.find(".rs:")? + ".rs".len();
+        let start = text[..end]
+            .rfind(|c: char| c.is_whitespace())
+            .map_or(0, |idx| idx + 1);
+        let mut pos = text[end + 1..].splitn(3, |c: char| !c.is_ascii_digit());
+        let line = pos.next()?.parse().ok()?;
+        let col = pos.next()?.parse().ok()?;
+        Some((&text[start..end], line, col))
+    };
+    parse().unwrap_or((text, 0, 0))
+}
+
+/// The (file, line) of each span like `src/main.rs:9:5: 9:20 (#0)` in `text`.
+pub fn span_lines(text: &str) -> Vec<(&str, usize)> {
+    let mut lines = Vec::new();
+    let mut rest = 0;
+    while let Some(idx) = text[rest..].find(".rs:") {
+        let end = rest + idx + ".rs".len();
+        rest = end;
+        let start = text[..end]
+            .rfind(|c: char| c.is_whitespace())
+            .map_or(0, |idx| idx + 1);
+        let mut pos = text[end + 1..].splitn(3, |c: char| !c.is_ascii_digit());
+        if let (Some(Ok(line)), Some(Ok(_col))) = (
+            pos.next().map(str::parse::<usize>),
+            pos.next().map(str::parse::<usize>),
+        ) {
+            lines.push((&text[start..end], line));
+        }
+    }
+    lines
+}
+
+/// (line, column), both start from 1.
+pub type LineCol = (usize, usize);
+
+/// The (file, lo, hi) of a span like `src/main.rs:9:5: 9:20 (#0)`.
+pub fn span_range(text: &str) -> Option<(&str, LineCol, LineCol)> {
+    let end = text.find(".rs:")? + ".rs".len();
+    let mut pos = text[end + 1..]
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(str::parse::<usize>);
+    let mut next = || pos.next()?.ok();
+    let lo = (next()?, next()?);
+    let hi = (next()?, next()?);
+    Some((&text[..end], lo, hi))
+}
+
+/// 64-bit FNV-1a, which is stable across platforms and Rust versions unlike `DefaultHasher`.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    /// Hash `part` followed by a separator, so that ("ab", "c") differs from ("a", "bc").
+    fn write(&mut self, part: &str) {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Sort the reports by kind, primary span, and secondary span, then by the JSON of the reports,
+/// so that the order does not depend on the iteration order of the detectors.
+/// The diagnoses of a conflictlock form a cycle, which starts from the smallest first lock span.
+pub fn sort_reports(reports: &mut [Report]) {
+    for report in reports.iter_mut() {
+        if let Report::ConflictLock(content) = report {
+            if let Some(min) = (0..content.diagnosis.len())
+                .min_by_key(|idx| span_key(&content.diagnosis[*idx].first_lock_span))
+            {
+                content.diagnosis.rotate_left(min);
+            }
+        }
+    }
+    fn owned_span_key(text: &str) -> (String, usize, usize) {
+        let (file, line, col) = span_key(text);
+        (file.to_owned(), line, col)
+    }
+    reports.sort_by_cached_key(|report| {
+        let (primary, secondary) = report.spans();
+        (
+            report.kind_rank(),
+            owned_span_key(primary),
+            owned_span_key(secondary),
+            serde_json::to_string(report).unwrap(),
+        )
+    });
+}
+
+/// Keep the `max` most actionable reports ranked by `Report::priority` and return the dropped ones.
+/// The reports of the same priority keep their order, so they should be sorted by `sort_reports` first.
+/// Both the kept and the dropped reports are in the order of `sort_reports`.
+pub fn truncate_reports(reports: &mut Vec<Report>, max: usize) -> Vec<Report> {
+    if reports.len() <= max {
+        return Vec::new();
+    }
+    reports.sort_by_key(Report::priority);
+    let mut dropped = reports.split_off(max);
+    sort_reports(reports);
+    sort_reports(&mut dropped);
+    dropped
+}
+
+/// The numbers of probably and possibly reports of a kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PossibilityCounts {
+    pub probably: usize,
+    pub possibly: usize,
+}
+
+impl PossibilityCounts {
+    fn is_zero(&self) -> bool {
+        self.probably + self.possibly == 0
+    }
+
+    fn count(&mut self, possibility: &str) {
+        match possibility {
+            "Probably" => self.probably += 1,
+            "Possibly" => self.possibly += 1,
+            _ => {}
+        }
+    }
+}
+
+/// The numbers of reports of each kind.
+/// The kinds other than doublelock, conflictlock, condvar deadlock, and refcell double borrow are only reported as possibly.
+/// `block_on_holding_lock` counts the locks held while blocking on futures by the executor APIs, e.g., `futures::executor::block_on`.
+/// `rayon_deadlock` counts the locks held by the callers of the rayon APIs and acquired again by the closures run on the pool.
+/// `rendezvous_deadlock` counts the locks held while blocking on `Barrier::wait` or a zero-capacity `SyncSender::send`
+/// and acquired by the peer threads.
+/// `once_reentrancy` counts the initializers of `Once`, `OnceLock`, or `lazy_static` reaching the same initialization again,
+/// and `once_init_deadlock` the locks held around the initializations and acquired again by the initializers.
+/// `lock_guard_leaked` counts the lockguards leaked by `std::mem::forget`, `ManuallyDrop::new`, or `Box::leak`.
+/// `double_unlock` and `use_after_unlock` count the misuses of the raw unlock APIs, e.g., `force_unlock`, with live lockguards.
+/// `implicit_release_order` counts the informational reports of the detector off by default.
+/// `send_hazard` counts the informational reports of the non-Send guards held across awaits in locally spawned futures.
+/// `hot_path_lock` counts the informational reports of the entry points given by `--hot-path-fn`.
+/// `discarded_guard` counts the informational reports of the locks released right after `let _ = ..` acquires them.
+/// `lock_coverage` counts the fields accessed without the lock held at most of their accesses.
+/// `panic_in_drop` counts the panic sites in the `Drop::drop` impls, which are reported as probably.
+/// `panic_holding_lock` counts the panic sites reached with live guards poisoning their locks on panic.
+/// `refcell_double_borrow` counts the `RefCell` borrows conflicting with the borrows still live, which panic.
+/// `panic_site` counts the informational reports of the panic sites, and `panic_apis` counts them by the kinds of the APIs,
+/// of which `panic_apis_in_tests` counts the ones in the test code, see `PanicDiagnosis::in_test`.
+/// `plugin` counts the reports by the plugins of all kinds.
+/// `dropped` counts the reports dropped by `truncate_reports` of each kind, keyed by `Report::level_keys`.
+/// `paranoid` counts the reports only emitted under `--paranoid`, see `Report::is_paranoid`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Summary {
+    pub double_lock: PossibilityCounts,
+    pub conflict_lock: PossibilityCounts,
+    pub condvar_deadlock: PossibilityCounts,
+    pub condvar_multiple_mutexes: usize,
+    pub await_holding_lock: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub block_on_holding_lock: usize,
+    pub pool_self_join: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub rayon_deadlock: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub rendezvous_deadlock: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub once_reentrancy: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub once_init_deadlock: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub lock_guard_leaked: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub double_unlock: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub use_after_unlock: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub implicit_release_order: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub send_hazard: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub hot_path_lock: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub discarded_guard: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub lock_coverage: usize,
+    pub atomicity_violation: usize,
+    pub invalid_free: usize,
+    pub use_after_free: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub double_free: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub panic_in_drop: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub panic_holding_lock: usize,
+    #[serde(skip_serializing_if = "PossibilityCounts::is_zero")]
+    pub refcell_double_borrow: PossibilityCounts,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub panic_site: usize,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub panic_apis: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub panic_apis_in_tests: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub plugin: usize,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub dropped: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub paranoid: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+impl Summary {
+    pub fn new(reports: &[Report]) -> Self {
+        let mut summary = Self::default();
+        for report in reports {
+            match report {
+                Report::DoubleLock(content) => summary.double_lock.count(&content.possibility),
+                Report::ConflictLock(content) => summary.conflict_lock.count(&content.possibility),
+                Report::CondvarDeadlock(content) => {
+                    summary.condvar_deadlock.count(&content.possibility)
+                }
+                Report::CondvarMultipleMutexes(_) => summary.condvar_multiple_mutexes += 1,
+                Report::AwaitHoldingLock(_) => summary.await_holding_lock += 1,
+                Report::BlockOnHoldingLock(_) => summary.block_on_holding_lock += 1,
+                Report::PoolSelfJoin(_) => summary.pool_self_join += 1,
+                Report::RayonDeadlock(_) => summary.rayon_deadlock += 1,
+                Report::RendezvousDeadlock(_) => summary.rendezvous_deadlock += 1,
+                Report::OnceReentrancy(_) => summary.once_reentrancy += 1,
+                Report::OnceInitDeadlock(_) => summary.once_init_deadlock += 1,
+                Report::LockGuardLeaked(_) => summary.lock_guard_leaked += 1,
+                Report::DoubleUnlock(_) => summary.double_unlock += 1,
+                Report::UseAfterUnlock(_) => summary.use_after_unlock += 1,
+                Report::ImplicitReleaseOrder(_) => summary.implicit_release_order += 1,
+                Report::SendHazard(_) => summary.send_hazard += 1,
+                Report::HotPathLock(_) => summary.hot_path_lock += 1,
+                Report::DiscardedGuard(_) => summary.discarded_guard += 1,
+                Report::LockCoverage(_) => summary.lock_coverage += 1,
+                Report::AtomicityViolation(_) => summary.atomicity_violation += 1,
+                Report::InvalidFree(_) => summary.invalid_free += 1,
+                Report::UseAfterFree(_) => summary.use_after_free += 1,
+                Report::DoubleFree(_) => summary.double_free += 1,
+                Report::PanicInDrop(_) => summary.panic_in_drop += 1,
+                Report::PanicHoldingLock(_) => summary.panic_holding_lock += 1,
+                Report::RefCellDoubleBorrow(content) => {
+                    summary.refcell_double_borrow.count(&content.possibility)
+                }
+                Report::Panic(content) => {
+                    summary.panic_site += 1;
+                    *summary
+                        .panic_apis
+                        .entry(content.diagnosis.panic_api.clone())
+                        .or_default() += 1;
+                    if content.diagnosis.in_test {
+                        *summary
+                            .panic_apis_in_tests
+                            .entry(content.diagnosis.panic_api.clone())
+                            .or_default() += 1;
+                    }
+                }
+                Report::Plugin(_) => summary.plugin += 1,
+            }
+            if report.is_paranoid() {
+                summary.paranoid += 1;
+            }
+        }
+        summary
+    }
+
+    /// Add the counts of another summary, e.g., to aggregate the summaries of the crates.
+    pub fn merge(&mut self, other: &Summary) {
+        for (counts, other) in [
+            (&mut self.double_lock, &other.double_lock),
+            (&mut self.conflict_lock, &other.conflict_lock),
+            (&mut self.condvar_deadlock, &other.condvar_deadlock),
+            (
+                &mut self.refcell_double_borrow,
+                &other.refcell_double_borrow,
+            ),
+        ] {
+            counts.probably += other.probably;
+            counts.possibly += other.possibly;
+        }
+        self.condvar_multiple_mutexes += other.condvar_multiple_mutexes;
+        self.await_holding_lock += other.await_holding_lock;
+        self.block_on_holding_lock += other.block_on_holding_lock;
+        self.pool_self_join += other.pool_self_join;
+        self.rayon_deadlock += other.rayon_deadlock;
+        self.rendezvous_deadlock += other.rendezvous_deadlock;
+        self.once_reentrancy += other.once_reentrancy;
+        self.once_init_deadlock += other.once_init_deadlock;
+        self.lock_guard_leaked += other.lock_guard_leaked;
+        self.double_unlock += other.double_unlock;
+        self.use_after_unlock += other.use_after_unlock;
+        self.implicit_release_order += other.implicit_release_order;
+        self.send_hazard += other.send_hazard;
+        self.hot_path_lock += other.hot_path_lock;
+        self.discarded_guard += other.discarded_guard;
+        self.lock_coverage += other.lock_coverage;
+        self.atomicity_violation += other.atomicity_violation;
+        self.invalid_free += other.invalid_free;
+        self.use_after_free += other.use_after_free;
+        self.double_free += other.double_free;
+        self.panic_in_drop += other.panic_in_drop;
+        self.panic_holding_lock += other.panic_holding_lock;
+        self.panic_site += other.panic_site;
+        for (panic_api, num) in &other.panic_apis {
+            *self.panic_apis.entry(panic_api.clone()).or_default() += num;
+        }
+        for (panic_api, num) in &other.panic_apis_in_tests {
+            *self
+                .panic_apis_in_tests
+                .entry(panic_api.clone())
+                .or_default() += num;
+        }
+        self.plugin += other.plugin;
+        for (kind, num) in &other.dropped {
+            *self.dropped.entry(kind.clone()).or_default() += num;
+        }
+        self.paranoid += other.paranoid;
+    }
+
+    /// The counts of the kinds with reports, keyed by `Report::level_keys`,
+    /// where the kinds only reported as possibly are counted as possibly.
+    pub fn counts(&self) -> Vec<(&'static str, PossibilityCounts)> {
+        let possibly = |possibly| PossibilityCounts {
+            probably: 0,
+            possibly,
+        };
+        [
+            ("double_lock", self.double_lock),
+            ("conflict_lock", self.conflict_lock),
+            ("condvar", self.condvar_deadlock),
+            (
+                "condvar_multiple_mutexes",
+                possibly(self.condvar_multiple_mutexes),
+            ),
+            ("await_holding_lock", possibly(self.await_holding_lock)),
+            (
+                "block_on_holding_lock",
+                possibly(self.block_on_holding_lock),
+            ),
+            ("pool_self_join", possibly(self.pool_self_join)),
+            ("rayon_deadlock", possibly(self.rayon_deadlock)),
+            ("rendezvous_deadlock", possibly(self.rendezvous_deadlock)),
+            ("once_reentrancy", possibly(self.once_reentrancy)),
+            ("once_init_deadlock", possibly(self.once_init_deadlock)),
+            ("lock_guard_leaked", possibly(self.lock_guard_leaked)),
+            ("double_unlock", possibly(self.double_unlock)),
+            ("use_after_unlock", possibly(self.use_after_unlock)),
+            (
+                "implicit_release_order",
+                possibly(self.implicit_release_order),
+            ),
+            ("send_hazard", possibly(self.send_hazard)),
+            ("hot_path_lock", possibly(self.hot_path_lock)),
+            ("discarded_guard", possibly(self.discarded_guard)),
+            ("lock_coverage", possibly(self.lock_coverage)),
+            ("atomicity_violation", possibly(self.atomicity_violation)),
+            ("invalid_free", possibly(self.invalid_free)),
+            ("use_after_free", possibly(self.use_after_free)),
+            ("double_free", possibly(self.double_free)),
+            (
+                "panic_in_drop",
+                PossibilityCounts {
+                    probably: self.panic_in_drop,
+                    possibly: 0,
+                },
+            ),
+            ("panic_holding_lock", possibly(self.panic_holding_lock)),
+            ("refcell_double_borrow", self.refcell_double_borrow),
+            ("panic_site", possibly(self.panic_site)),
+            ("plugin", possibly(self.plugin)),
+        ]
+        .into_iter()
+        .filter(|(_, counts)| counts.probably + counts.possibly > 0)
+        .collect()
+    }
+
+    pub fn record_dropped(&mut self, dropped: &[Report]) {
+        for report in dropped {
+            *self
+                .dropped
+                .entry(report.level_keys()[0].to_owned())
+                .or_default() += 1;
+        }
+    }
+}
+
+/// The JSON Schema of `ReportEnvelope`.
+pub fn schema() -> RootSchema {
+    schemars::schema_for!(ReportEnvelope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn doublelock(first: &str, second: &str) -> Report {
+        Report::DoubleLock(ReportContent::new(
+            "DoubleLock".to_owned(),
+            "Probably".to_owned(),
+            DeadlockDiagnosis::new(
+                "StdMutex(i32)".to_owned(),
+                first.to_owned(),
+                "StdMutex(i32)".to_owned(),
+                second.to_owned(),
+                Vec::new(),
+            ),
+            "The first lock is not released when acquiring the second lock".to_owned(),
+        ))
+    }
+
+    #[test]
+    fn test_span_key() {
+        assert_eq!(
+            span_key("src/main.rs:9:13: 9:15 (#0)"),
+            ("src/main.rs", 9, 13)
+        );
+        assert_eq!(
+            span_key("Escape to Global: Raw ptr _1 at src/lib.rs:10:5: 10:9 (#0) escapes"),
+            ("src/lib.rs", 10, 5)
+        );
+        assert_eq!(span_key("no span"), ("no span", 0, 0));
+    }
+
+    #[test]
+    fn test_sort_reports() {
+        let reports = || {
+            vec![
+                Report::UseAfterFree(ReportContent::new(
+                    "UseAfterFree".to_owned(),
+                    "Possibly".to_owned(),
+                    "Raw ptr _1 at src/main.rs:1:1: 1:2 (#0)".to_owned(),
+                    String::new(),
+                )),
+                doublelock("src/main.rs:10:5: 10:9 (#0)", "src/main.rs:12:5: 12:9 (#0)"),
+                doublelock("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:20:5: 20:9 (#0)"),
+                doublelock("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:11:5: 11:9 (#0)"),
+                doublelock("src/lib.rs:30:5: 30:9 (#0)", "src/lib.rs:31:5: 31:9 (#0)"),
+            ]
+        };
+        let mut sorted = reports();
+        sort_reports(&mut sorted);
+        let spans = sorted.iter().map(Report::spans).collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec![
+                ("src/lib.rs:30:5: 30:9 (#0)", "src/lib.rs:31:5: 31:9 (#0)"),
+                ("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:11:5: 11:9 (#0)"),
+                ("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:20:5: 20:9 (#0)"),
+                ("src/main.rs:10:5: 10:9 (#0)", "src/main.rs:12:5: 12:9 (#0)"),
+                ("Raw ptr _1 at src/main.rs:1:1: 1:2 (#0)", ""),
+            ]
+        );
+        // The order of the input does not matter.
+        let mut reversed = reports();
+        reversed.reverse();
+        sort_reports(&mut reversed);
+        assert_eq!(
+            serde_json::to_string(&sorted).unwrap(),
+            serde_json::to_string(&reversed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_sort_reports_rotates_conflictlock_cycle() {
+        let diagnosis = |first: &str, second: &str| match doublelock(first, second) {
+            Report::DoubleLock(content) => content.diagnosis,
+            _ => unreachable!(),
+        };
+        let mut reports = vec![Report::ConflictLock(ReportContent::new(
+            "ConflictLock".to_owned(),
+            "Possibly".to_owned(),
+            vec![
+                diagnosis("src/main.rs:20:5: 20:9 (#0)", "src/main.rs:8:5: 8:9 (#0)"),
+                diagnosis("src/main.rs:7:5: 7:9 (#0)", "src/main.rs:21:5: 21:9 (#0)"),
+            ],
+            String::new(),
+        ))];
+        sort_reports(&mut reports);
+        assert_eq!(
+            reports[0].spans(),
+            ("src/main.rs:7:5: 7:9 (#0)", "src/main.rs:21:5: 21:9 (#0)")
+        );
+    }
+
+    #[test]
+    fn test_truncate_reports() {
+        // 1000 reports: every 100th is deny-level, every 10th is probably.
+        let mut reports = (0..1000)
+            .map(|i| {
+                let mut report = if i % 2 == 0 {
+                    doublelock(
+                        &format!("src/main.rs:{}:5: {}:9 (#0)", i, i),
+                        "src/main.rs:1:5: 1:9 (#0)",
+                    )
+                } else {
+                    Report::UseAfterFree(ReportContent::new(
+                        "UseAfterFree".to_owned(),
+                        "Possibly".to_owned(),
+                        format!("Raw ptr _1 at src/main.rs:{}:1: {}:2 (#0)", i, i),
+                        String::new(),
+                    ))
+                };
+                if let Report::DoubleLock(content) = &mut report {
+                    if i % 10 != 0 {
+                        content.possibility = "Possibly".to_owned();
+                    }
+                }
+                if i % 100 == 0 {
+                    report.set_level(Level::Deny);
+                }
+                report
+            })
+            .collect::<Vec<_>>();
+        sort_reports(&mut reports);
+        let dropped = truncate_reports(&mut reports, 50);
+        assert_eq!(reports.len(), 50);
+        assert_eq!(dropped.len(), 950);
+        // 10 deny-level, then the 40 smallest spans of the other 90 probably.
+        assert_eq!(
+            reports
+                .iter()
+                .filter(|report| report.level() == Level::Deny)
+                .count(),
+            10
+        );
+        assert!(reports
+            .iter()
+            .all(|report| report.possibility() == "Probably"));
+        assert!(dropped.iter().all(|report| report.level() == Level::Warn));
+        let mut lines = reports
+            .iter()
+            .map(|report| span_key(report.spans().0).1)
+            .collect::<Vec<_>>();
+        lines.sort_unstable();
+        let mut expected = (0..1000).step_by(100).collect::<Vec<_>>();
+        expected.extend((0..1000).step_by(10).filter(|i| i % 100 != 0).take(40));
+        expected.sort_unstable();
+        assert_eq!(lines, expected);
+        // Both are in the order of `sort_reports`.
+        let mut sorted = std::mem::take(&mut reports);
+        let json = serde_json::to_string(&sorted).unwrap();
+        sort_reports(&mut sorted);
+        assert_eq!(serde_json::to_string(&sorted).unwrap(), json);
+        let mut summary = Summary::new(&sorted);
+        summary.record_dropped(&dropped);
+        assert_eq!(summary.double_lock.probably, 50);
+        assert_eq!(
+            summary.dropped,
+            BTreeMap::from([
+                ("double_lock".to_owned(), 450),
+                ("use_after_free".to_owned(), 500)
+            ])
+        );
+        // Nothing is dropped under the limit.
+        assert!(truncate_reports(&mut sorted, 50).is_empty());
+        assert_eq!(sorted.len(), 50);
+    }
+
+    #[test]
+    fn test_truncate_reports_by_confidence() {
+        let with_confidence = |line: usize, confidence: Option<u8>| {
+            let mut report = doublelock(&format!("src/main.rs:{}:5: {}:9 (#0)", line, line), "");
+            if let Report::DoubleLock(content) = &mut report {
+                content.confidence = confidence;
+            }
+            report
+        };
+        let mut reports = vec![
+            with_confidence(1, Some(40)),
+            with_confidence(2, None),
+            with_confidence(3, Some(95)),
+        ];
+        sort_reports(&mut reports);
+        // The more confident of the same possibility are kept, and the unscored are dropped first.
+        let dropped = truncate_reports(&mut reports, 2);
+        assert_eq!(
+            reports.iter().map(Report::confidence).collect::<Vec<_>>(),
+            vec![Some(40), Some(95)]
+        );
+        assert_eq!(dropped[0].confidence(), None);
+        let dropped = truncate_reports(&mut reports, 1);
+        assert_eq!(reports[0].confidence(), Some(95));
+        assert_eq!(dropped[0].confidence(), Some(40));
+    }
+
+    #[test]
+    fn test_truncate_reports_by_lock_extent() {
+        let mut reports = vec![
+            doublelock("src/main.rs:1:5: 1:9 (#0)", "src/main.rs:30:5: 30:9 (#0)"),
+            doublelock("src/main.rs:2:5: 2:9 (#0)", "src/lib.rs:3:5: 3:9 (#0)"),
+            doublelock("src/main.rs:3:5: 3:9 (#0)", "src/main.rs:5:5: 5:9 (#0)"),
+        ];
+        assert_eq!(
+            reports.iter().map(Report::lock_extent).collect::<Vec<_>>(),
+            vec![Some(30), None, Some(3)]
+        );
+        sort_reports(&mut reports);
+        // Of the same possibility and confidence, the shorter extents are kept, and the unknown dropped first.
+        let dropped = truncate_reports(&mut reports, 2);
+        assert_eq!(
+            reports.iter().map(Report::lock_extent).collect::<Vec<_>>(),
+            vec![Some(30), Some(3)]
+        );
+        assert_eq!(dropped[0].lock_extent(), None);
+        let dropped = truncate_reports(&mut reports, 1);
+        assert_eq!(reports[0].lock_extent(), Some(3));
+        assert_eq!(dropped[0].lock_extent(), Some(30));
+    }
+
+    #[test]
+    fn test_span_lines() {
+        assert_eq!(
+            span_lines("Raw ptr is used at src/main.rs:14:13: 14:16 (#0) after dropped at src/lib.rs:10:37: 10:38 (#0)"),
+            vec![("src/main.rs", 14), ("src/lib.rs", 10)]
+        );
+        assert_eq!(span_lines("no span in main.rs: here"), vec![]);
+    }
+
+    #[test]
+    fn test_span_range() {
+        assert_eq!(
+            span_range("src/main.rs:9:5: 10:20 (#0)"),
+            Some(("src/main.rs", (9, 5), (10, 20)))
+        );
+        assert_eq!(span_range("unknown"), None);
+        assert_eq!(span_range("src/main.rs:9:5"), None);
+    }
+
+    #[test]
+    fn test_group_by_lock() {
+        let class = |id: usize, name: &str| LockClass {
+            id,
+            name: name.to_owned(),
+            declaration_span: format!("src/main.rs:{}:5: {}:20 (#0)", id + 1, id + 1),
+            acquisitions: 2,
+        };
+        let classes = vec![class(0, "Foo.a"), class(1, "Foo.b"), class(2, "Foo.c")];
+        let with_class = |mut report: Report, lock_class: Option<usize>| {
+            if let Report::DoubleLock(content) = &mut report {
+                content.diagnosis.lock_class = lock_class;
+            }
+            report
+        };
+        let reports = vec![
+            with_class(doublelock("src/main.rs:9:13: 9:15 (#0)", ""), Some(2)),
+            with_class(doublelock("src/main.rs:10:13: 10:15 (#0)", ""), None),
+            with_class(doublelock("src/main.rs:11:13: 11:15 (#0)", ""), Some(0)),
+            with_class(doublelock("src/main.rs:12:13: 12:15 (#0)", ""), Some(2)),
+        ];
+        let groups = group_by_lock(&reports, &classes);
+        assert_eq!(
+            groups,
+            vec![
+                LockGroup {
+                    lock: class(0, "Foo.a"),
+                    reports: vec![2],
+                },
+                LockGroup {
+                    lock: class(2, "Foo.c"),
+                    reports: vec![0, 3],
+                },
+            ]
+        );
+        assert!(group_by_lock(&reports, &[]).is_empty());
+        // The locks of the reports include the second locks.
+        assert_eq!(
+            referenced_locks(&reports[..1], &classes),
+            vec![class(2, "Foo.c")]
+        );
+        let mut reports = reports;
+        if let Report::DoubleLock(content) = &mut reports[1] {
+            content.diagnosis.second_lock_class = Some(1);
+        }
+        assert_eq!(referenced_locks(&reports, &classes), classes);
+    }
+
+    #[test]
+    fn test_compute_fingerprint() {
+        // Locate the lines relative to a fn starting at line `start`.
+        let locate =
+            |start: usize| move |file: &str, line: usize| format!("{}:f+{}", file, line - start);
+        let report = doublelock("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:11:5: 11:9 (#0)");
+        let fingerprint = report.compute_fingerprint(locate(5));
+        assert_eq!(fingerprint.len(), 16);
+        // Moving the fn down 10 lines and changing the columns keep the fingerprint.
+        let moved = doublelock(
+            "src/main.rs:19:9: 19:13 (#0)",
+            "src/main.rs:21:9: 21:13 (#0)",
+        );
+        assert_eq!(moved.compute_fingerprint(locate(15)), fingerprint);
+        // Swapping the locks does not.
+        let swapped = doublelock("src/main.rs:11:5: 11:9 (#0)", "src/main.rs:9:5: 9:9 (#0)");
+        assert_ne!(swapped.compute_fingerprint(locate(5)), fingerprint);
+        // Neither does the kind.
+        let uaf = Report::UseAfterFree(ReportContent::new(
+            "UseAfterFree".to_owned(),
+            "Possibly".to_owned(),
+            "Raw ptr is used at src/main.rs:9:5: 9:9 (#0) after dropped at src/main.rs:11:5: 11:9 (#0)".to_owned(),
+            String::new(),
+        ));
+        assert_ne!(uaf.compute_fingerprint(locate(5)), fingerprint);
+    }
+
+    #[test]
+    fn test_compute_fingerprint_conflictlock_cycle() {
+        let diagnosis = |first: &str, second: &str| match doublelock(first, second) {
+            Report::DoubleLock(content) => content.diagnosis,
+            _ => unreachable!(),
+        };
+        let conflictlock = |diagnoses| {
+            Report::ConflictLock(ReportContent::new(
+                "ConflictLock".to_owned(),
+                "Possibly".to_owned(),
+                diagnoses,
+                String::new(),
+            ))
+        };
+        let a = conflictlock(vec![
+            diagnosis("src/main.rs:8:5: 8:9 (#0)", "src/main.rs:9:5: 9:9 (#0)"),
+            diagnosis("src/main.rs:20:5: 20:9 (#0)", "src/main.rs:21:5: 21:9 (#0)"),
+        ]);
+        let b = conflictlock(vec![
+            diagnosis("src/main.rs:20:5: 20:9 (#0)", "src/main.rs:21:5: 21:9 (#0)"),
+            diagnosis("src/main.rs:8:5: 8:9 (#0)", "src/main.rs:9:5: 9:9 (#0)"),
+        ]);
+        let locate = |file: &str, line: usize| format!("{}:{}", file, line);
+        assert_eq!(a.compute_fingerprint(locate), b.compute_fingerprint(locate));
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let mut report = doublelock("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:11:5: 11:9 (#0)");
+        report.set_level(Level::Deny);
+        let envelope = ReportEnvelope::new("inter".to_owned(), vec![report]);
+        assert_eq!(envelope.summary.double_lock.probably, 1);
+        let json = serde_json::to_string_pretty(&envelope).unwrap();
+        let parsed: ReportEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.version, SCHEMA_VERSION);
+        assert_eq!(parsed.summary, envelope.summary);
+        assert_eq!(parsed.reports[0].level(), Level::Deny);
+        assert_eq!(serde_json::to_string_pretty(&parsed).unwrap(), json);
+    }
+
+    fn metadata() -> AnalysisMetadata {
+        AnalysisMetadata {
+            lockbud_version: "0.1.0".to_owned(),
+            analyzed_at: "2024-01-31T12:00:00Z".to_owned(),
+            git_commit: Some("0123abcd".to_owned()),
+            options_hash: "4567ef014567ef01".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_envelope_metadata_round_trip() {
+        let mut envelope = ReportEnvelope::new("inter".to_owned(), Vec::new());
+        envelope.metadata = Some(metadata());
+        let json = serde_json::to_string(&envelope).unwrap();
+        let parsed: ReportEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.metadata, envelope.metadata);
+        assert_eq!(
+            parsed.metadata.unwrap().to_string(),
+            "lockbud 0.1.0 at 2024-01-31T12:00:00Z on commit 0123abcd with options 4567ef014567ef01"
+        );
+        // The envelopes of older lockbud have no metadata.
+        let json = r#"{"version": 1, "crate_name": "inter", "summary": {}, "reports": []}"#;
+        let parsed: ReportEnvelope = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.metadata, None);
+        assert!(!serde_json::to_string(&parsed).unwrap().contains("metadata"));
+    }
+
+    #[test]
+    fn test_envelope_skipped_functions_round_trip() {
+        let mut envelope = ReportEnvelope::new("inter".to_owned(), Vec::new());
+        assert!(!serde_json::to_string(&envelope)
+            .unwrap()
+            .contains("skipped_functions"));
+        envelope.skipped_functions = vec![SkippedFunction {
+            def_path: "generated::huge".to_owned(),
+            analysis: "points-to".to_owned(),
+            reason: "20001 constraint graph nodes exceeded the budget of 20000".to_owned(),
+        }];
+        let json = serde_json::to_string(&envelope).unwrap();
+        let parsed: ReportEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.skipped_functions, envelope.skipped_functions);
+    }
+
+    #[test]
+    fn test_envelope_alias_stats_round_trip() {
+        let mut envelope = ReportEnvelope::new("inter".to_owned(), Vec::new());
+        assert!(!serde_json::to_string(&envelope)
+            .unwrap()
+            .contains("alias_stats"));
+        let mut alias_stats = AliasStats::default();
+        alias_stats
+            .queries
+            .entry(AliasQueryContext::Deadlock)
+            .or_default()
+            .alias
+            .probably = 1;
+        alias_stats.points_to_cache.insert(
+            AliasQueryContext::Deadlock,
+            PointsToCacheCounters {
+                hits: 2,
+                cross_detector_hits: 0,
+                misses: 1,
+            },
+        );
+        envelope.alias_stats = Some(alias_stats);
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert!(json.contains(r#""points_to_cache":{"Deadlock":{"hits":2,"#));
+        let parsed: ReportEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.alias_stats, envelope.alias_stats);
+    }
+
+    #[test]
+    fn test_baseline_round_trip() {
+        let baseline = Baseline {
+            metadata: Some(metadata()),
+            fingerprints: ["b", "a"].into_iter().map(str::to_owned).collect(),
+        };
+        let json = serde_json::to_string(&baseline).unwrap();
+        assert_eq!(serde_json::from_str::<Baseline>(&json).unwrap(), baseline);
+        // The plain lists of older lockbud.
+        let parsed: Baseline = serde_json::from_str(r#"["a", "b"]"#).unwrap();
+        assert_eq!(parsed.fingerprints, baseline.fingerprints);
+        assert_eq!(parsed.metadata, None);
+        assert!(serde_json::from_str::<Baseline>("{}").is_err());
+    }
+
+    #[test]
+    fn test_summary_paranoid() {
+        let mut paranoid = doublelock("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:11:5: 11:9 (#0)");
+        if let Report::DoubleLock(content) = &mut paranoid {
+            content.diagnosis.alias_confidence = Some(ALIAS_CONFIDENCE_UNKNOWN.to_owned());
+        }
+        let reports = vec![
+            doublelock("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:12:5: 12:9 (#0)"),
+            paranoid,
+        ];
+        assert!(!reports[0].is_paranoid());
+        assert!(reports[1].is_paranoid());
+        let summary = Summary::new(&reports);
+        assert_eq!(summary.double_lock.probably, 2);
+        assert_eq!(summary.paranoid, 1);
+        let json = serde_json::to_string(&reports[1]).unwrap();
+        assert!(json.contains(r#""alias_confidence":"unknown""#));
+        assert!(!serde_json::to_string(&Summary::new(&reports[..1]))
+            .unwrap()
+            .contains("paranoid"));
+    }
+
+    #[test]
+    fn test_plugin_report() {
+        let report = Report::Plugin(ReportContent::new(
+            "ForbiddenLock".to_owned(),
+            "Probably".to_owned(),
+            PluginDiagnosis {
+                plugin: "forbidden_lock".to_owned(),
+                subject: "app::CONFIG".to_owned(),
+                span: "src/handlers.rs:9:5: 9:9 (#0)".to_owned(),
+                callchains: Vec::new(),
+            },
+            String::new(),
+        ));
+        assert_eq!(report.spans(), ("src/handlers.rs:9:5: 9:9 (#0)", ""));
+        let summary = Summary::new(&[report]);
+        assert_eq!(summary.plugin, 1);
+        assert!(!serde_json::to_string(&Summary::new(&[]))
+            .unwrap()
+            .contains("plugin"));
+    }
+
+    #[test]
+    fn test_implicit_release_order_truncated_first() {
+        let order = |name: &str, span: &str, lock_types: [&str; 2]| lock::ReleaseOrder {
+            name: name.to_owned(),
+            holder: true,
+            span: span.to_owned(),
+            lock_types: lock_types.map(str::to_owned).to_vec(),
+            lock_spans: Vec::new(),
+        };
+        let release_order = Report::ImplicitReleaseOrder(ReportContent::new(
+            "ImplicitReleaseOrder".to_owned(),
+            "Possibly".to_owned(),
+            ImplicitReleaseOrderDiagnosis {
+                first: order("Transfer", "src/main.rs:3:1: 3:20 (#0)", ["A", "B"]),
+                second: order("Audit", "src/main.rs:8:1: 8:17 (#0)", ["B", "A"]),
+            },
+            String::new(),
+        ));
+        let mut possibly = doublelock(
+            "src/main.rs:20:9: 20:11 (#0)",
+            "src/main.rs:21:9: 21:11 (#0)",
+        );
+        if let Report::DoubleLock(content) = &mut possibly {
+            content.possibility = "Possibly".to_owned();
+        }
+        let mut reports = vec![release_order, possibly];
+        sort_reports(&mut reports);
+        let dropped = truncate_reports(&mut reports, 1);
+        assert!(matches!(reports[..], [Report::DoubleLock(_)]));
+        assert!(matches!(dropped[..], [Report::ImplicitReleaseOrder(_)]));
+        assert_eq!(Summary::new(&dropped).implicit_release_order, 1);
+    }
+
+    #[test]
+    fn test_send_hazard_report() {
+        let report = Report::SendHazard(ReportContent::new(
+            "SendHazard".to_owned(),
+            "Possibly".to_owned(),
+            SendHazardDiagnosis::new(
+                "worker::{closure#0}".to_owned(),
+                "StdMutex(i32)".to_owned(),
+                "src/main.rs:9:13: 9:18 (#0)".to_owned(),
+                "tokio::task::spawn_local".to_owned(),
+                "src/main.rs:7:5: 12:7 (#0)".to_owned(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            ("src/main.rs:9:13: 9:18 (#0)", "src/main.rs:7:5: 12:7 (#0)")
+        );
+        assert_eq!(report.level_keys(), ["send_hazard", "deadlock"]);
+        let summary = Summary::new(&[report]);
+        assert_eq!(summary.counts()[0].0, "send_hazard");
+        assert!(!serde_json::to_string(&Summary::new(&[]))
+            .unwrap()
+            .contains("send_hazard"));
+    }
+
+    #[test]
+    fn test_hot_path_lock_report() {
+        let lock = |name: &str, span: &str| lock::HotPathLock {
+            name: name.to_owned(),
+            lock_type: "StdMutex(i32)".to_owned(),
+            declaration_span: span.to_owned(),
+            acquisitions: 1,
+        };
+        let report = |may_acquire| {
+            Report::HotPathLock(ReportContent::new(
+                "HotPathLock".to_owned(),
+                "Possibly".to_owned(),
+                HotPathLockDiagnosis {
+                    entry_fn: "handle".to_owned(),
+                    entry_span: "src/main.rs:9:1: 9:30 (#0)".to_owned(),
+                    must_acquire: vec![lock("GLOBAL", "src/main.rs:3:1: 3:40 (#0)")],
+                    may_acquire,
+                },
+                String::new(),
+            ))
+        };
+        let first = report(Vec::new());
+        assert_eq!(
+            first.spans(),
+            ("src/main.rs:9:1: 9:30 (#0)", "src/main.rs:3:1: 3:40 (#0)")
+        );
+        assert_eq!(first.level_keys(), ["hot_path_lock", "deadlock"]);
+        // The locks only acquired on some paths do not change the fingerprint.
+        let second = report(vec![lock("CACHE", "src/main.rs:4:1: 4:40 (#0)")]);
+        let locate = |file: &str, line: usize| format!("{}:{}", file, line);
+        assert_eq!(
+            first.compute_fingerprint(locate),
+            second.compute_fingerprint(locate)
+        );
+        assert_eq!(Summary::new(&[first]).counts()[0].0, "hot_path_lock");
+    }
+
+    #[test]
+    fn test_lock_guard_leaked_report() {
+        let report = Report::LockGuardLeaked(ReportContent::new(
+            "LockGuardLeaked".to_owned(),
+            "Possibly".to_owned(),
+            LockGuardLeakedDiagnosis::new(
+                "pause".to_owned(),
+                "StdMutex(i32)".to_owned(),
+                "src/main.rs:9:13: 9:31 (#0)".to_owned(),
+                "std::mem::forget".to_owned(),
+                "src/main.rs:10:5: 10:25 (#0)".to_owned(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            (
+                "src/main.rs:9:13: 9:31 (#0)",
+                "src/main.rs:10:5: 10:25 (#0)"
+            )
+        );
+        assert_eq!(report.level_keys(), ["lock_guard_leaked", "deadlock"]);
+        let summary = Summary::new(&[report]);
+        assert_eq!(summary.counts()[0].0, "lock_guard_leaked");
+        assert!(!serde_json::to_string(&Summary::new(&[]))
+            .unwrap()
+            .contains("lock_guard_leaked"));
+    }
+
+    #[test]
+    fn test_block_on_holding_lock_report() {
+        let report = Report::BlockOnHoldingLock(ReportContent::new(
+            "BlockOnHoldingLock".to_owned(),
+            "Possibly".to_owned(),
+            BlockOnHoldingLockDiagnosis::new(
+                "flush".to_owned(),
+                "StdMutex(i32)".to_owned(),
+                "src/main.rs:9:13: 9:21 (#0)".to_owned(),
+                "futures::executor::block_on".to_owned(),
+                "src/main.rs:10:5: 10:40 (#0)".to_owned(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            (
+                "src/main.rs:9:13: 9:21 (#0)",
+                "src/main.rs:10:5: 10:40 (#0)"
+            )
+        );
+        assert_eq!(report.level_keys(), ["block_on_holding_lock", "deadlock"]);
+        assert_eq!(Summary::new(&[report]).block_on_holding_lock, 1);
+    }
+
+    #[test]
+    fn test_rendezvous_deadlock_report() {
+        let report = Report::RendezvousDeadlock(ReportContent::new(
+            "RendezvousDeadlock".to_owned(),
+            "Possibly".to_owned(),
+            RendezvousDeadlockDiagnosis::new(
+                "StdMutex(i32)".to_owned(),
+                "src/main.rs:12:17: 12:28 (#0)".to_owned(),
+                "std::sync::Barrier::wait".to_owned(),
+                "src/main.rs:13:9: 13:23 (#0)".to_owned(),
+                "src/main.rs:8:22: 8:33 (#0)".to_owned(),
+                "src/main.rs:7:9: 10:11 (#0)".to_owned(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            (
+                "src/main.rs:13:9: 13:23 (#0)",
+                "src/main.rs:8:22: 8:33 (#0)"
+            )
+        );
+        assert_eq!(report.level_keys(), ["rendezvous_deadlock", "deadlock"]);
+        assert_eq!(Summary::new(&[report]).rendezvous_deadlock, 1);
+    }
+
+    #[test]
+    fn test_once_reports() {
+        let reentrancy = Report::OnceReentrancy(ReportContent::new(
+            "OnceReentrancy".to_owned(),
+            "Possibly".to_owned(),
+            OnceReentrancyDiagnosis::new(
+                "std::sync::OnceLock::get_or_init".to_owned(),
+                "src/main.rs:9:6: 9:48 (#0)".to_owned(),
+                "src/main.rs:9:6: 9:48 (#0)".to_owned(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(reentrancy.level_keys(), ["once_reentrancy", "deadlock"]);
+        assert!(!reentrancy.is_paranoid());
+        let mut diagnosis = OnceInitDeadlockDiagnosis::new(
+            "StdMutex(Vec<u32>)".to_owned(),
+            "src/main.rs:21:9: 21:21 (#0)".to_owned(),
+            "std::sync::OnceLock::get_or_init".to_owned(),
+            "src/main.rs:17:6: 17:66 (#0)".to_owned(),
+            "src/main.rs:17:28: 17:52 (#0)".to_owned(),
+        );
+        diagnosis.alias_confidence = Some(ALIAS_CONFIDENCE_UNKNOWN.to_owned());
+        let init_deadlock = Report::OnceInitDeadlock(ReportContent::new(
+            "OnceInitDeadlock".to_owned(),
+            "Possibly".to_owned(),
+            diagnosis,
+            String::new(),
+        ));
+        assert_eq!(
+            init_deadlock.spans(),
+            (
+                "src/main.rs:21:9: 21:21 (#0)",
+                "src/main.rs:17:28: 17:52 (#0)"
+            )
+        );
+        assert_eq!(
+            init_deadlock.level_keys(),
+            ["once_init_deadlock", "deadlock"]
+        );
+        assert!(init_deadlock.is_paranoid());
+        let summary = Summary::new(&[reentrancy, init_deadlock]);
+        assert_eq!(
+            (summary.once_reentrancy, summary.once_init_deadlock),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn test_panic_in_drop_report() {
+        let report = Report::PanicInDrop(ReportContent::new(
+            "PanicInDrop".to_owned(),
+            "Probably".to_owned(),
+            PanicInDropDiagnosis::new(
+                "Pool".to_owned(),
+                "src/main.rs:10:5: 10:22 (#0)".to_owned(),
+                "std::result::Result::unwrap".to_owned(),
+                "src/main.rs:11:9: 11:34 (#0)".to_owned(),
+                Vec::new(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            (
+                "src/main.rs:10:5: 10:22 (#0)",
+                "src/main.rs:11:9: 11:34 (#0)"
+            )
+        );
+        assert_eq!(report.level_keys(), ["panic_in_drop", "panic"]);
+        let summary = Summary::new(&[report]);
+        assert_eq!(
+            summary.counts(),
+            [(
+                "panic_in_drop",
+                PossibilityCounts {
+                    probably: 1,
+                    possibly: 0
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_panic_holding_lock_report() {
+        let report = Report::PanicHoldingLock(ReportContent::new(
+            "PanicHoldingLock".to_owned(),
+            "Possibly".to_owned(),
+            PanicHoldingLockDiagnosis::new(
+                "StdMutex(std::vec::Vec<u32>)".to_owned(),
+                "src/main.rs:8:17: 8:29 (#0)".to_owned(),
+                "std::ops::Index::index".to_owned(),
+                "src/main.rs:9:13: 9:23 (#0)".to_owned(),
+                Vec::new(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            ("src/main.rs:8:17: 8:29 (#0)", "src/main.rs:9:13: 9:23 (#0)")
+        );
+        assert_eq!(report.level_keys(), ["panic_holding_lock", "panic"]);
+        let summary = Summary::new(&[report]);
+        assert_eq!(
+            summary.counts(),
+            [(
+                "panic_holding_lock",
+                PossibilityCounts {
+                    probably: 0,
+                    possibly: 1
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_refcell_double_borrow_report() {
+        let report = Report::RefCellDoubleBorrow(ReportContent::new(
+            "RefCellDoubleBorrow".to_owned(),
+            "Probably".to_owned(),
+            DeadlockDiagnosis::new(
+                "RefCellRef(i32)".to_owned(),
+                "src/main.rs:5:9: 5:10 (#0)".to_owned(),
+                "RefCellRefMut(i32)".to_owned(),
+                "src/main.rs:6:9: 6:10 (#0)".to_owned(),
+                Vec::new(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            ("src/main.rs:5:9: 5:10 (#0)", "src/main.rs:6:9: 6:10 (#0)")
+        );
+        assert_eq!(report.level_keys(), ["refcell_double_borrow", "panic"]);
+        let summary = Summary::new(&[report]);
+        assert_eq!(
+            summary.counts(),
+            [(
+                "refcell_double_borrow",
+                PossibilityCounts {
+                    probably: 1,
+                    possibly: 0
+                }
+            )]
+        );
+        let json = serde_json::to_value(&summary).unwrap();
+        assert!(Summary::default().refcell_double_borrow.is_zero());
+        assert_eq!(json["refcell_double_borrow"]["probably"], 1);
+    }
+
+    fn panic_site(panic_api: &str, line: usize, in_test: bool) -> Report {
+        Report::Panic(ReportContent::new(
+            "Panic".to_owned(),
+            "Possibly".to_owned(),
+            PanicDiagnosis::new(
+                "parse".to_owned(),
+                panic_api.to_owned(),
+                format!("src/main.rs:{}:13: {}:25 (#0)", line, line),
+                "src/main.rs:8:1: 12:2 (#0)".to_owned(),
+                in_test,
+            ),
+            String::new(),
+        ))
+    }
+
+    #[test]
+    fn test_panic_report() {
+        let report = panic_site("OptionUnwrap", 9, false);
+        assert_eq!(
+            report.spans(),
+            ("src/main.rs:9:13: 9:25 (#0)", "src/main.rs:8:1: 12:2 (#0)")
+        );
+        assert_eq!(report.level_keys(), ["panic_site", "panic"]);
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.starts_with(r#"{"Panic":{"bug_kind":"Panic""#));
+        assert!(json.contains(r#""panic_api":"OptionUnwrap""#));
+        let parsed: Report = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        let mut summary = Summary::new(&[report, panic_site("ResultExpect", 10, false)]);
+        summary.merge(&Summary::new(&[
+            panic_site("OptionUnwrap", 11, true),
+            panic_site("Todo", 12, false),
+        ]));
+        assert_eq!(summary.panic_site, 4);
+        assert_eq!(
+            summary.panic_apis.into_iter().collect::<Vec<_>>(),
+            [
+                ("OptionUnwrap".to_owned(), 2),
+                ("ResultExpect".to_owned(), 1),
+                ("Todo".to_owned(), 1)
+            ]
+        );
+        assert_eq!(
+            summary.panic_apis_in_tests.into_iter().collect::<Vec<_>>(),
+            [("OptionUnwrap".to_owned(), 1)]
+        );
+        // The reports before `in_test` are not in the test code.
+        let legacy = json.replace(r#","in_test":false"#, "");
+        assert_ne!(legacy, json);
+        assert!(serde_json::from_str::<Report>(&legacy).is_ok());
+        assert!(!serde_json::to_string(&Summary::new(&[]))
+            .unwrap()
+            .contains("panic_apis"));
+    }
+
+    #[test]
+    fn test_rayon_deadlock_report() {
+        let mut diagnosis = RayonDeadlockDiagnosis::new(
+            "StdMutex(u64)".to_owned(),
+            "src/main.rs:11:13: 11:19 (#0)".to_owned(),
+            "rayon::iter::ParallelIterator::for_each".to_owned(),
+            "src/main.rs:12:9: 14:11 (#0)".to_owned(),
+            "src/main.rs:13:14: 13:40 (#0)".to_owned(),
+        );
+        diagnosis.alias_confidence = Some(ALIAS_CONFIDENCE_UNKNOWN.to_owned());
+        let report = Report::RayonDeadlock(ReportContent::new(
+            "RayonDeadlock".to_owned(),
+            "Possibly".to_owned(),
+            diagnosis,
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            (
+                "src/main.rs:11:13: 11:19 (#0)",
+                "src/main.rs:13:14: 13:40 (#0)"
+            )
+        );
+        assert_eq!(report.level_keys(), ["rayon_deadlock", "deadlock"]);
+        assert!(report.is_paranoid());
+        assert_eq!(Summary::new(&[report]).rayon_deadlock, 1);
+    }
+
+    #[test]
+    fn test_force_unlock_reports() {
+        let diagnosis = || {
+            ForceUnlockDiagnosis::new(
+                "unlock".to_owned(),
+                "ParkingLotMutex(i32)".to_owned(),
+                "src/main.rs:9:17: 9:25 (#0)".to_owned(),
+                "lock_api::Mutex::<R, T>::force_unlock".to_owned(),
+                "src/main.rs:10:14: 10:30 (#0)".to_owned(),
+                "src/main.rs:11:1: 11:2 (#0)".to_owned(),
+            )
+        };
+        let double_unlock = Report::DoubleUnlock(ReportContent::new(
+            "DoubleUnlock".to_owned(),
+            "Probably".to_owned(),
+            diagnosis(),
+            String::new(),
+        ));
+        let use_after_unlock = Report::UseAfterUnlock(ReportContent::new(
+            "UseAfterUnlock".to_owned(),
+            "Probably".to_owned(),
+            diagnosis(),
+            String::new(),
+        ));
+        assert_eq!(
+            double_unlock.spans(),
+            (
+                "src/main.rs:10:14: 10:30 (#0)",
+                "src/main.rs:11:1: 11:2 (#0)"
+            )
+        );
+        assert_eq!(double_unlock.level_keys(), ["double_unlock", "deadlock"]);
+        assert_eq!(
+            use_after_unlock.level_keys(),
+            ["use_after_unlock", "deadlock"]
+        );
+        let summary = Summary::new(&[double_unlock, use_after_unlock]);
+        assert_eq!((summary.double_unlock, summary.use_after_unlock), (1, 1));
+    }
+
+    #[test]
+    fn test_discarded_guard_report() {
+        let report = Report::DiscardedGuard(ReportContent::new(
+            "DiscardedGuard".to_owned(),
+            "Possibly".to_owned(),
+            DiscardedGuardDiagnosis::new(
+                "reset".to_owned(),
+                "StdMutex(i32)".to_owned(),
+                "src/main.rs:9:13: 9:31 (#0)".to_owned(),
+            ),
+            String::new(),
+        ));
+        assert_eq!(report.spans(), ("src/main.rs:9:13: 9:31 (#0)", ""));
+        assert_eq!(report.level_keys(), ["discarded_guard", "deadlock"]);
+        // Informational, so kept after the deadlocks under truncation.
+        assert!(report.priority() > doublelock("src/main.rs:1:1: 1:2 (#0)", "").priority());
+        let summary = Summary::new(&[report]);
+        assert_eq!(summary.counts()[0].0, "discarded_guard");
+        assert!(!serde_json::to_string(&Summary::new(&[]))
+            .unwrap()
+            .contains("discarded_guard"));
+    }
+
+    #[test]
+    fn test_lock_coverage_report() {
+        let report = Report::LockCoverage(ReportContent::new(
+            "LockCoverage".to_owned(),
+            "Possibly".to_owned(),
+            LockCoverageDiagnosis::new(
+                "Stats.hits".to_owned(),
+                "src/main.rs:4:5: 4:14 (#0)".to_owned(),
+                "Stats.mu".to_owned(),
+                "StdMutex(())".to_owned(),
+                4,
+                5,
+                vec!["src/main.rs:30:9: 30:18 (#0)".to_owned()],
+            ),
+            String::new(),
+        ));
+        assert_eq!(
+            report.spans(),
+            ("src/main.rs:30:9: 30:18 (#0)", "src/main.rs:4:5: 4:14 (#0)")
+        );
+        assert_eq!(report.level_keys(), ["lock_coverage", "deadlock"]);
+        let summary = Summary::new(&[report]);
+        assert_eq!(summary.counts()[0].0, "lock_coverage");
+        assert!(!serde_json::to_string(&Summary::new(&[]))
+            .unwrap()
+            .contains("lock_coverage"));
+    }
+
+    #[test]
+    fn test_merge_summaries() {
+        let first = Summary::new(&[
+            doublelock("src/main.rs:9:5: 9:9 (#0)", "src/main.rs:10:5: 10:9 (#0)"),
+            doublelock("src/main.rs:19:5: 19:9 (#0)", "src/main.rs:20:5: 20:9 (#0)"),
+        ]);
+        let mut second = Summary::new(&[Report::UseAfterFree(ReportContent::new(
+            "UseAfterFree".to_owned(),
+            "Possibly".to_owned(),
+            "src/lib.rs:3:5: 3:9 (#0)".to_owned(),
+            String::new(),
+        ))]);
+        second.dropped.insert("use_after_free".to_owned(), 2);
+        let mut total = Summary::default();
+        total.merge(&first);
+        total.merge(&second);
+        total.merge(&second);
+        assert_eq!(total.double_lock.probably, 2);
+        assert_eq!(total.use_after_free, 2);
+        assert_eq!(total.dropped["use_after_free"], 4);
+        assert_eq!(
+            total.counts(),
+            [
+                (
+                    "double_lock",
+                    PossibilityCounts {
+                        probably: 2,
+                        possibly: 0
+                    }
+                ),
+                (
+                    "use_after_free",
+                    PossibilityCounts {
+                        probably: 0,
+                        possibly: 2
+                    }
+                ),
+            ]
+        );
+    }
+
+    /// Run with `UPDATE_SCHEMA=1` to regenerate the schema after changing the types.
+    #[test]
+    fn test_schema_up_to_date() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema/report.schema.json");
+        let schema = serde_json::to_string_pretty(&schema()).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(&path, &schema).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            schema,
+            "run `UPDATE_SCHEMA=1 cargo test -p lockbud-report-types` to regenerate {}",
+            path.display()
+        );
+    }
+}
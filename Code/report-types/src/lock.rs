@@ -0,0 +1,707 @@
+//! The diagnoses of deadlock reports.
+//! e.g., doublelock diagnosis contains one deadlock diagnosis,
+//！while conflictlock diagnosis contanis a vector of deadlock diagnosis.
+//! Deadlock diagnosis consists of the first & second locks' type and span (a.k.a. src code location),
+//! and the callchains from first to second lock, limited by `--max-callchains` and `--max-callchain-depth`.
+//! Truncated callchains end with `[["...truncated"]]`.
+//! The diagnoses carry the ids of the `LockClass`es of their locks, listed in `ReportEnvelope::locks`,
+//! so the reports on the same lock under different spans can be told apart from the reports on different locks.
+//! The doublelocks in the monomorphized instances of the same generic fns are reported once,
+//! with the number of instantiations (omitted if 1).
+//! Under `--paranoid`, the diagnoses relying on the unknown alias results are marked by `alias_confidence`.
+//! The diagnoses whose second lock is only reachable through the heuristic callgraph edges are marked by `heuristic`.
+//! The relations of conflictlocks retried in the closures of `catch_unwind` after a panic are marked by `inversion`.
+//! The locks are also named after their source expressions (e.g., `self.inner.state_mu`) if known.
+//! The raw spans of the locks are kept in-process for the rustc diagnostics, but never serialized.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DeadlockDiagnosis {
+    pub first_lock_type: String,
+    pub first_lock_span: String,
+    pub second_lock_type: String,
+    pub second_lock_span: String,
+    /// The source expression of the first lock, e.g., `self.inner.state_mu` or a variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_lock_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub second_lock_name: Option<String>,
+    pub callchains: Vec<Vec<Vec<String>>>,
+    /// Spawn callsites (or "main") of the threads from which the first lock is reachable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_roots: Option<Vec<String>>,
+    /// The `LockClass` id of the first lock.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_class: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub second_lock_class: Option<usize>,
+    /// The number of the instantiations of the generic fns with the same doublelock.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub instantiations: usize,
+    /// `unknown` if the locks are only reported under `--paranoid`, see `ALIAS_CONFIDENCE_UNKNOWN`,
+    /// or `same_owner` if the locks are only fields of the same struct type, see `ALIAS_CONFIDENCE_SAME_OWNER`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_confidence: Option<String>,
+    /// How the alias of the locks is obtained, e.g., `points_to_intersection` in the same fn,
+    /// `same_constant`, `closure_upvar`, `element_index` for the elements of the same collection,
+    /// `callsite` for the args at the callsites under `--context-sensitive-alias`,
+    /// or `param_type_heuristic` if only the params of the same type are matched
+    /// (per relation for conflictlock, of the second lock and the first lock of the next relation).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_provenance: Option<String>,
+    /// `closure_arg` if the second lock is only reachable through the closures passed to generic fns without MIR,
+    /// see `HEURISTIC_CLOSURE_ARG`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heuristic: Option<String>,
+    /// `panic_retry` if the relation is formed in a closure of `catch_unwind`
+    /// and inverts a relation formed before the panic, see `INVERSION_PANIC_RETRY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inversion: Option<String>,
+    /// The source lines of the spans with one line of context, keyed by the span strings, under `--with-snippets`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub snippet: BTreeMap<String, String>,
+    /// The raw spans of the first and second locks, only used in-process.
+    #[serde(skip)]
+    pub raw_spans: Option<(RawSpan, RawSpan)>,
+}
+
+/// The byte range of a span in the source map of the compilation session.
+/// Unlike the span strings, it locates the span in-process without parsing,
+/// so it is meaningless outside the session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawSpan {
+    pub lo: u32,
+    pub hi: u32,
+}
+
+fn one() -> usize {
+    1
+}
+
+fn is_one(n: &usize) -> bool {
+    *n == 1
+}
+
+impl DeadlockDiagnosis {
+    pub fn new(
+        first_lock_type: String,
+        first_lock_span: String,
+        second_lock_type: String,
+        second_lock_span: String,
+        callchains: Vec<Vec<Vec<String>>>,
+    ) -> Self {
+        Self {
+            first_lock_type,
+            first_lock_span,
+            second_lock_type,
+            second_lock_span,
+            first_lock_name: None,
+            second_lock_name: None,
+            callchains,
+            thread_roots: None,
+            lock_class: None,
+            second_lock_class: None,
+            instantiations: 1,
+            alias_confidence: None,
+            alias_provenance: None,
+            heuristic: None,
+            inversion: None,
+            snippet: BTreeMap::new(),
+            raw_spans: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WaitNotifyLocks {
+    pub wait_lock_type: String,
+    pub wait_lock_span: String,
+    pub notify_lock_type: String,
+    pub notify_lock_span: String,
+    /// The source expressions of the locks, see `DeadlockDiagnosis::first_lock_name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_lock_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify_lock_name: Option<String>,
+    /// The `LockClass` ids of the locks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_lock_class: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify_lock_class: Option<usize>,
+}
+
+impl WaitNotifyLocks {
+    pub fn new(
+        wait_lock_type: String,
+        wait_lock_span: String,
+        notify_lock_type: String,
+        notify_lock_span: String,
+    ) -> Self {
+        Self {
+            wait_lock_type,
+            wait_lock_span,
+            notify_lock_type,
+            notify_lock_span,
+            wait_lock_name: None,
+            notify_lock_name: None,
+            wait_lock_class: None,
+            notify_lock_class: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CondvarDeadlockDiagnosis {
+    pub condvar_wait_type: String,
+    pub condvar_wait_callsite_span: String,
+    pub condvar_notify_type: String,
+    pub condvar_notify_callsite_span: String,
+    pub deadlocks: Vec<WaitNotifyLocks>,
+    /// The `LockClass` id of the first wait lock.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_class: Option<usize>,
+    /// `unknown` if the condvars or locks are only reported under `--paranoid`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_confidence: Option<String>,
+    /// The source lines of the spans with one line of context, keyed by the span strings, under `--with-snippets`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub snippet: BTreeMap<String, String>,
+}
+
+impl CondvarDeadlockDiagnosis {
+    pub fn new(
+        condvar_wait_type: String,
+        condvar_wait_callsite_span: String,
+        condvar_notify_type: String,
+        condvar_notify_callsite_span: String,
+        deadlocks: Vec<WaitNotifyLocks>,
+    ) -> Self {
+        Self {
+            condvar_wait_type,
+            condvar_wait_callsite_span,
+            condvar_notify_type,
+            condvar_notify_callsite_span,
+            deadlocks,
+            lock_class: None,
+            alias_confidence: None,
+            snippet: BTreeMap::new(),
+        }
+    }
+}
+
+/// An alias class of locks, i.e., the lockguards probably aliasing each other,
+/// named after the declaration of the lock (a static, a field, or a variable),
+/// with the number of lockguards of the class in the crate.
+/// The ids are assigned in the order of the names and declaration spans, stable within a run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LockClass {
+    pub id: usize,
+    pub name: String,
+    pub declaration_span: String,
+    pub acquisitions: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AwaitHoldingLockDiagnosis {
+    pub fn_name: String,
+    pub lock_type: String,
+    pub lock_span: String,
+}
+
+impl AwaitHoldingLockDiagnosis {
+    pub fn new(fn_name: String, lock_type: String, lock_span: String) -> Self {
+        Self {
+            fn_name,
+            lock_type,
+            lock_span,
+        }
+    }
+}
+
+/// A non-Send guard held across an await in a future spawned by a local spawn API, e.g., `tokio::task::spawn_local`,
+/// which compiles but fails to once the future is spawned by a Send-requiring API, e.g., `tokio::spawn`.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct SendHazardDiagnosis {
+    pub fn_name: String,
+    pub guard_type: String,
+    pub guard_span: String,
+    pub spawn_api: String,
+    pub spawn_span: String,
+}
+
+impl SendHazardDiagnosis {
+    pub fn new(
+        fn_name: String,
+        guard_type: String,
+        guard_span: String,
+        spawn_api: String,
+        spawn_span: String,
+    ) -> Self {
+        Self {
+            fn_name,
+            guard_type,
+            guard_span,
+            spawn_api,
+            spawn_span,
+        }
+    }
+}
+
+/// The locks acquired in the calls from an entry point designated by `--hot-path-fn`, e.g., a request handler.
+/// `must_acquire` are acquired on every path through the entry point, serializing all the requests,
+/// and `may_acquire` are only acquired on some paths.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HotPathLockDiagnosis {
+    pub entry_fn: String,
+    pub entry_span: String,
+    pub must_acquire: Vec<HotPathLock>,
+    pub may_acquire: Vec<HotPathLock>,
+}
+
+/// A lock named after its declaration (a static, a field, or a variable) like `LockClass`,
+/// with the number of its acquisition sites reachable from the entry point.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HotPathLock {
+    pub name: String,
+    pub lock_type: String,
+    pub declaration_span: String,
+    pub acquisitions: usize,
+}
+
+/// A lock acquired by `let _ = ..`, which drops the lockguard (or the `LockResult` of it) at once,
+/// i.e., the lock is released right after the acquisition, unlike `let _guard = ..` holding it to the end of the scope.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DiscardedGuardDiagnosis {
+    pub fn_name: String,
+    pub lock_type: String,
+    pub lock_span: String,
+}
+
+impl DiscardedGuardDiagnosis {
+    pub fn new(fn_name: String, lock_type: String, lock_span: String) -> Self {
+        Self {
+            fn_name,
+            lock_type,
+            lock_span,
+        }
+    }
+}
+
+/// A field of a struct accessed with the same lock held at most of its accesses (at least the threshold of them),
+/// but without it at some accesses reachable from multiple threads, which probably race with the others.
+/// The lock is named after its declaration like `LockClass`, e.g., `Stats.mu`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LockCoverageDiagnosis {
+    /// The field, e.g., `Stats.hits`.
+    pub field: String,
+    pub field_span: String,
+    pub lock: String,
+    pub lock_type: String,
+    /// The accesses with the lock held and all the accesses, counted by spans.
+    pub protected_accesses: usize,
+    pub accesses: usize,
+    /// The spans of the accesses without the lock.
+    pub unprotected_spans: Vec<String>,
+}
+
+impl LockCoverageDiagnosis {
+    pub fn new(
+        field: String,
+        field_span: String,
+        lock: String,
+        lock_type: String,
+        protected_accesses: usize,
+        accesses: usize,
+        unprotected_spans: Vec<String>,
+    ) -> Self {
+        Self {
+            field,
+            field_span,
+            lock,
+            lock_type,
+            protected_accesses,
+            accesses,
+            unprotected_spans,
+        }
+    }
+}
+
+/// A mutex used with the condvar, and the wait callsites with its guards.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CondvarMutex {
+    pub mutex: String,
+    pub declaration_span: String,
+    pub wait_spans: Vec<String>,
+}
+
+impl CondvarMutex {
+    pub fn new(mutex: String, declaration_span: String, wait_spans: Vec<String>) -> Self {
+        Self {
+            mutex,
+            declaration_span,
+            wait_spans,
+        }
+    }
+}
+
+/// A condvar waited with the guards of more than one mutex, which std (panicking at runtime)
+/// and parking_lot forbid.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CondvarMultipleMutexesDiagnosis {
+    pub condvar_wait_type: String,
+    pub mutexes: Vec<CondvarMutex>,
+}
+
+impl CondvarMultipleMutexesDiagnosis {
+    pub fn new(condvar_wait_type: String, mutexes: Vec<CondvarMutex>) -> Self {
+        Self {
+            condvar_wait_type,
+            mutexes,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct PoolSelfJoinDiagnosis {
+    pub pool: String,
+    pub install_span: String,
+    pub wait_api: String,
+    pub wait_span: String,
+}
+
+impl PoolSelfJoinDiagnosis {
+    pub fn new(pool: String, install_span: String, wait_api: String, wait_span: String) -> Self {
+        Self {
+            pool,
+            install_span,
+            wait_api,
+            wait_span,
+        }
+    }
+}
+
+/// A lock held while blocking the current thread on a future by an executor API, e.g., `futures::executor::block_on`,
+/// which deadlocks if the future (or a task it waits for) needs the lock, and at least stalls the thread.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct BlockOnHoldingLockDiagnosis {
+    pub fn_name: String,
+    pub lock_type: String,
+    pub lock_span: String,
+    pub block_on_api: String,
+    pub block_on_span: String,
+}
+
+impl BlockOnHoldingLockDiagnosis {
+    pub fn new(
+        fn_name: String,
+        lock_type: String,
+        lock_span: String,
+        block_on_api: String,
+        block_on_span: String,
+    ) -> Self {
+        Self {
+            fn_name,
+            lock_type,
+            lock_span,
+            block_on_api,
+            block_on_span,
+        }
+    }
+}
+
+/// A lock held by the caller of a rayon API (e.g., `par_iter().for_each`, `rayon::join`, or `rayon::scope`)
+/// and acquired again by a closure run on the pool, while the caller blocks on the closure.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct RayonDeadlockDiagnosis {
+    pub lock_type: String,
+    pub outer_lock_span: String,
+    pub rayon_api: String,
+    pub rayon_span: String,
+    pub inner_lock_span: String,
+    /// `unknown` if the locks are only reported under `--paranoid`, see `ALIAS_CONFIDENCE_UNKNOWN`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_confidence: Option<String>,
+}
+
+impl RayonDeadlockDiagnosis {
+    pub fn new(
+        lock_type: String,
+        outer_lock_span: String,
+        rayon_api: String,
+        rayon_span: String,
+        inner_lock_span: String,
+    ) -> Self {
+        Self {
+            lock_type,
+            outer_lock_span,
+            rayon_api,
+            rayon_span,
+            inner_lock_span,
+            alias_confidence: None,
+        }
+    }
+}
+
+/// A lock held while blocking on a rendezvous with a peer thread, e.g., `Barrier::wait` or `SyncSender::send`
+/// on a zero-capacity channel, and acquired by the peer before it reaches the matching API,
+/// so neither thread proceeds.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct RendezvousDeadlockDiagnosis {
+    pub lock_type: String,
+    pub lock_span: String,
+    pub rendezvous_api: String,
+    pub rendezvous_span: String,
+    pub peer_lock_span: String,
+    /// The spawn callsite (or "main") of the peer thread.
+    pub peer_thread: String,
+    /// `unknown` if the locks are only reported under `--paranoid`, see `ALIAS_CONFIDENCE_UNKNOWN`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_confidence: Option<String>,
+}
+
+impl RendezvousDeadlockDiagnosis {
+    pub fn new(
+        lock_type: String,
+        lock_span: String,
+        rendezvous_api: String,
+        rendezvous_span: String,
+        peer_lock_span: String,
+        peer_thread: String,
+    ) -> Self {
+        Self {
+            lock_type,
+            lock_span,
+            rendezvous_api,
+            rendezvous_span,
+            peer_lock_span,
+            peer_thread,
+            alias_confidence: None,
+        }
+    }
+}
+
+/// An initializer of `Once::call_once`, `OnceLock::get_or_init`, or a `lazy_static`
+/// reaching the same API on the same receiver again, which blocks forever on the initialization in progress.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct OnceReentrancyDiagnosis {
+    pub once_api: String,
+    /// The callsite of the API running the initializer.
+    pub outer_span: String,
+    /// The callsite of the same API reached from the initializer.
+    pub inner_span: String,
+    /// `unknown` if the receivers are only reported under `--paranoid`, see `ALIAS_CONFIDENCE_UNKNOWN`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_confidence: Option<String>,
+}
+
+impl OnceReentrancyDiagnosis {
+    pub fn new(once_api: String, outer_span: String, inner_span: String) -> Self {
+        Self {
+            once_api,
+            outer_span,
+            inner_span,
+            alias_confidence: None,
+        }
+    }
+}
+
+/// A lock held by the caller of `Once::call_once`, `OnceLock::get_or_init`, or a `lazy_static`
+/// and acquired again by the initializer, which the caller (or a thread waiting for the initialization) blocks on.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct OnceInitDeadlockDiagnosis {
+    pub lock_type: String,
+    pub outer_lock_span: String,
+    pub once_api: String,
+    pub init_span: String,
+    pub inner_lock_span: String,
+    /// `unknown` if the locks are only reported under `--paranoid`, see `ALIAS_CONFIDENCE_UNKNOWN`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_confidence: Option<String>,
+}
+
+impl OnceInitDeadlockDiagnosis {
+    pub fn new(
+        lock_type: String,
+        outer_lock_span: String,
+        once_api: String,
+        init_span: String,
+        inner_lock_span: String,
+    ) -> Self {
+        Self {
+            lock_type,
+            outer_lock_span,
+            once_api,
+            init_span,
+            inner_lock_span,
+            alias_confidence: None,
+        }
+    }
+}
+
+/// A lockguard never dropped since it is moved into an API leaking it, e.g., `std::mem::forget`,
+/// so the lock is never released and every later acquisition of it blocks forever.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct LockGuardLeakedDiagnosis {
+    pub fn_name: String,
+    pub lock_type: String,
+    pub lock_span: String,
+    pub leak_api: String,
+    pub leak_span: String,
+}
+
+impl LockGuardLeakedDiagnosis {
+    pub fn new(
+        fn_name: String,
+        lock_type: String,
+        lock_span: String,
+        leak_api: String,
+        leak_span: String,
+    ) -> Self {
+        Self {
+            fn_name,
+            lock_type,
+            lock_span,
+            leak_api,
+            leak_span,
+        }
+    }
+}
+
+/// A lock unlocked by a raw unlock API, e.g., parking_lot's `force_unlock`, while its lockguard is still alive:
+/// the automatic drop of the lockguard unlocks it again (`DoubleUnlock`),
+/// or the lockguard accesses the data no longer protected (`UseAfterUnlock`).
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct ForceUnlockDiagnosis {
+    pub fn_name: String,
+    pub lock_type: String,
+    pub lock_span: String,
+    pub unlock_api: String,
+    pub unlock_span: String,
+    /// The drop of the lockguard for `DoubleUnlock`, or the use of it for `UseAfterUnlock`.
+    pub later_span: String,
+}
+
+impl ForceUnlockDiagnosis {
+    pub fn new(
+        fn_name: String,
+        lock_type: String,
+        lock_span: String,
+        unlock_api: String,
+        unlock_span: String,
+        later_span: String,
+    ) -> Self {
+        Self {
+            fn_name,
+            lock_type,
+            lock_span,
+            unlock_api,
+            unlock_span,
+            later_span,
+        }
+    }
+}
+
+/// The order in which two locks are released,
+/// implied by a guard holder (a struct dropping its guard fields in the order of declaration)
+/// or by an inline acquisition sequence (dropping the lockguards in a fn).
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct ReleaseOrder {
+    /// The guard holder struct, or the fn of the inline acquisition sequence.
+    pub name: String,
+    pub holder: bool,
+    /// The definition of the struct or the fn.
+    pub span: String,
+    /// The lock types in the order of release.
+    pub lock_types: Vec<String>,
+    /// The spans of the lockguards in the order of release.
+    pub lock_spans: Vec<String>,
+}
+
+/// Two release orders over the same locks opposite to each other, where the first is implied by a guard holder.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ImplicitReleaseOrderDiagnosis {
+    pub first: ReleaseOrder,
+    pub second: ReleaseOrder,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReportContent;
+
+    #[test]
+    fn test_deadlock_diagnosis() {
+        let d = DeadlockDiagnosis::new(
+            "ParkingLotRead(loader::ModuleCache)".to_owned(),
+            "language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)".to_owned(),
+            "ParkingLotRead(loader::ModuleCache)".to_owned(),
+            "language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)".to_owned(),
+            vec![vec![vec![
+                "language/move-vm/runtime/src/loader.rs:518:13: 518:55 (#0)".to_owned(),
+            ]]],
+        );
+        assert_eq!(
+            format!("{:?}", d),
+            r#"DeadlockDiagnosis { first_lock_type: "ParkingLotRead(loader::ModuleCache)", first_lock_span: "language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)", second_lock_type: "ParkingLotRead(loader::ModuleCache)", second_lock_span: "language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)", first_lock_name: None, second_lock_name: None, callchains: [[["language/move-vm/runtime/src/loader.rs:518:13: 518:55 (#0)"]]], thread_roots: None, lock_class: None, second_lock_class: None, instantiations: 1, alias_confidence: None, alias_provenance: None, heuristic: None, inversion: None, snippet: {}, raw_spans: None }"#
+        )
+    }
+
+    #[test]
+    fn test_report_content() {
+        let d = DeadlockDiagnosis::new(
+            "ParkingLotRead(loader::ModuleCache)".to_owned(),
+            "language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)".to_owned(),
+            "ParkingLotRead(loader::ModuleCache)".to_owned(),
+            "language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)".to_owned(),
+            vec![vec![vec![
+                "language/move-vm/runtime/src/loader.rs:518:13: 518:55 (#0)".to_owned(),
+            ]]],
+        );
+        let report_content = ReportContent::new(
+            "DoubleLock".to_owned(),
+            "Possibly".to_owned(),
+            format!("{:?}", d),
+            "The first lock is not released when acquiring the second lock".to_owned(),
+        );
+        assert_eq!(
+            format!("{:?}", report_content),
+            r#"ReportContent { bug_kind: "DoubleLock", possibility: "Possibly", diagnosis: "DeadlockDiagnosis { first_lock_type: \"ParkingLotRead(loader::ModuleCache)\", first_lock_span: \"language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)\", second_lock_type: \"ParkingLotRead(loader::ModuleCache)\", second_lock_span: \"language/move-vm/runtime/src/loader.rs:510:13: 510:18 (#0)\", first_lock_name: None, second_lock_name: None, callchains: [[[\"language/move-vm/runtime/src/loader.rs:518:13: 518:55 (#0)\"]]], thread_roots: None, lock_class: None, second_lock_class: None, instantiations: 1, alias_confidence: None, alias_provenance: None, heuristic: None, inversion: None, snippet: {}, raw_spans: None }", explanation: "The first lock is not released when acquiring the second lock", level: Warn, fingerprint: "", confidence: None }"#
+        );
+    }
+
+    #[test]
+    fn test_deadlock_diagnosis_serialize_without_thread_roots() {
+        let d = DeadlockDiagnosis::new(
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:17:17: 17:30 (#0)".to_owned(),
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:18:10: 18:23 (#0)".to_owned(),
+            vec![],
+        );
+        assert_eq!(
+            serde_json::to_string(&d).unwrap(),
+            r#"{"first_lock_type":"StdMutex(i32)","first_lock_span":"src/main.rs:17:17: 17:30 (#0)","second_lock_type":"StdMutex(i32)","second_lock_span":"src/main.rs:18:10: 18:23 (#0)","callchains":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_deadlock_diagnosis_serialize_with_thread_roots() {
+        let mut d = DeadlockDiagnosis::new(
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:17:17: 17:30 (#0)".to_owned(),
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:18:10: 18:23 (#0)".to_owned(),
+            vec![],
+        );
+        d.thread_roots = Some(vec![
+            "main".to_owned(),
+            "src/main.rs:40:14: 42:7 (#0)".to_owned(),
+        ]);
+        assert_eq!(
+            serde_json::to_string(&d).unwrap(),
+            r#"{"first_lock_type":"StdMutex(i32)","first_lock_span":"src/main.rs:17:17: 17:30 (#0)","second_lock_type":"StdMutex(i32)","second_lock_span":"src/main.rs:18:10: 18:23 (#0)","callchains":[],"thread_roots":["main","src/main.rs:40:14: 42:7 (#0)"]}"#
+        );
+    }
+}
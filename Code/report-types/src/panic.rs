@@ -0,0 +1,102 @@
+//! The diagnosis of panic reports.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A callsite of an API that may panic, e.g., `Option::unwrap` or `core::panicking::panic_fmt`.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct PanicDiagnosis {
+    /// The def path of the fn containing the callsite.
+    pub def_path: String,
+    /// The panic API, e.g., `ResultUnwrap` or `Todo`.
+    pub panic_api: String,
+    pub callsite_span: String,
+    /// The outermost span of the fn containing the callsite.
+    pub outermost_span: String,
+    /// Whether the fn is a `#[test]` fn or gated by `#[cfg(test)]`, including its parents.
+    #[serde(default)]
+    pub in_test: bool,
+    /// The span of the asserted condition if the panic site is an `Assert` terminator, e.g., a bounds check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition_span: Option<String>,
+}
+
+impl PanicDiagnosis {
+    pub fn new(
+        def_path: String,
+        panic_api: String,
+        callsite_span: String,
+        outermost_span: String,
+        in_test: bool,
+    ) -> Self {
+        Self {
+            def_path,
+            panic_api,
+            callsite_span,
+            outermost_span,
+            in_test,
+            condition_span: None,
+        }
+    }
+}
+
+/// A panic site in a `Drop::drop` impl of the crate, or in a fn only reachable from such impls,
+/// which aborts the process if the value is dropped while unwinding from another panic.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct PanicInDropDiagnosis {
+    /// The self type of the `Drop` impl.
+    pub drop_type: String,
+    pub drop_span: String,
+    pub panic_api: String,
+    pub panic_span: String,
+    /// The callchains from `drop` to the fn of the panic site, empty if the panic site is in `drop`,
+    /// see `DeadlockDiagnosis::callchains`.
+    pub callchains: Vec<Vec<Vec<String>>>,
+}
+
+impl PanicInDropDiagnosis {
+    pub fn new(
+        drop_type: String,
+        drop_span: String,
+        panic_api: String,
+        panic_span: String,
+        callchains: Vec<Vec<Vec<String>>>,
+    ) -> Self {
+        Self {
+            drop_type,
+            drop_span,
+            panic_api,
+            panic_span,
+            callchains,
+        }
+    }
+}
+
+/// A panic site reached while a lockguard poisoning its lock on panic, e.g., `std::sync::MutexGuard`, is live,
+/// so that every later `lock().unwrap()` of the lock panics too.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct PanicHoldingLockDiagnosis {
+    pub lock_type: String,
+    pub guard_span: String,
+    pub panic_api: String,
+    pub panic_span: String,
+    /// The callchains from the fn holding the guard to the fn of the panic site, empty if they are the same fn.
+    pub callchains: Vec<Vec<Vec<String>>>,
+}
+
+impl PanicHoldingLockDiagnosis {
+    pub fn new(
+        lock_type: String,
+        guard_span: String,
+        panic_api: String,
+        panic_span: String,
+        callchains: Vec<Vec<Vec<String>>>,
+    ) -> Self {
+        Self {
+            lock_type,
+            guard_span,
+            panic_api,
+            panic_span,
+            callchains,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! The diagnosis of the reports by the plugins, i.e., the custom checks compiled into lockbud.
+//! The bug kind of the report is chosen by the plugin, e.g., `ForbiddenLock`.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PluginDiagnosis {
+    /// The name of the plugin, e.g., `forbidden_lock`.
+    pub plugin: String,
+    /// What the report is about, e.g., the name of the forbidden lock.
+    pub subject: String,
+    pub span: String,
+    /// The callchains leading to `span`, in the format of `DeadlockDiagnosis::callchains`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub callchains: Vec<Vec<Vec<String>>>,
+}
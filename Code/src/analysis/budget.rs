@@ -0,0 +1,148 @@
+//! The analysis budget per function.
+//! A single enormous function (e.g., generated code) may make an analysis run for a very long time.
+//! When analyzing a function exceeds the budget, the analysis gives up the function gracefully
+//! and records it as skipped, so that users know the coverage is reduced.
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub use lockbud_report_types::SkippedFunction;
+
+/// Check the elapsed time once per this many steps.
+const STEPS_PER_TIME_CHECK: usize = 256;
+
+/// The budget of analyzing a function. Zero means unlimited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnalysisBudget {
+    pub time: Duration,
+    /// The maximal number of nodes in the constraint graph of points-to analysis.
+    pub max_nodes: usize,
+}
+
+impl AnalysisBudget {
+    pub fn unlimited() -> Self {
+        Self {
+            time: Duration::ZERO,
+            max_nodes: 0,
+        }
+    }
+
+    pub fn check_nodes(&self, nodes: usize) -> Result<(), BudgetExceeded> {
+        if self.max_nodes != 0 && nodes > self.max_nodes {
+            Err(BudgetExceeded::Nodes {
+                nodes,
+                max_nodes: self.max_nodes,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Start timing an analysis.
+    pub fn start(&self) -> BudgetTimer {
+        BudgetTimer {
+            start: Instant::now(),
+            time: self.time,
+            steps: 0,
+        }
+    }
+}
+
+/// Times an analysis, checking the elapsed time every `STEPS_PER_TIME_CHECK` steps.
+pub struct BudgetTimer {
+    start: Instant,
+    time: Duration,
+    steps: usize,
+}
+
+impl BudgetTimer {
+    /// Count a step of the analysis, e.g., processing a node in the worklist.
+    pub fn step(&mut self) -> Result<(), BudgetExceeded> {
+        self.steps += 1;
+        if self.time.is_zero() || self.steps % STEPS_PER_TIME_CHECK != 0 {
+            return Ok(());
+        }
+        if self.start.elapsed() > self.time {
+            Err(BudgetExceeded::Time { time: self.time })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    Time { time: Duration },
+    Nodes { nodes: usize, max_nodes: usize },
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetExceeded::Time { time } => {
+                write!(f, "exceeded the time budget of {:?}", time)
+            }
+            BudgetExceeded::Nodes { nodes, max_nodes } => write!(
+                f,
+                "{} constraint graph nodes exceeded the budget of {}",
+                nodes, max_nodes
+            ),
+        }
+    }
+}
+
+impl BudgetExceeded {
+    /// The function skipped by the analysis, listed in the `skipped_functions` section of the output.
+    pub fn skipped(self, def_path: String, analysis: &str) -> SkippedFunction {
+        SkippedFunction {
+            def_path,
+            analysis: analysis.to_owned(),
+            reason: self.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_nodes() {
+        let budget = AnalysisBudget {
+            time: Duration::ZERO,
+            max_nodes: 10,
+        };
+        assert_eq!(budget.check_nodes(10), Ok(()));
+        assert_eq!(
+            budget.check_nodes(11),
+            Err(BudgetExceeded::Nodes {
+                nodes: 11,
+                max_nodes: 10
+            })
+        );
+        assert_eq!(AnalysisBudget::unlimited().check_nodes(usize::MAX), Ok(()));
+    }
+
+    #[test]
+    fn test_timer_step() {
+        let budget = AnalysisBudget {
+            time: Duration::from_nanos(1),
+            max_nodes: 0,
+        };
+        let mut timer = budget.start();
+        std::thread::sleep(Duration::from_millis(1));
+        // The time is only checked every `STEPS_PER_TIME_CHECK` steps.
+        for _ in 1..STEPS_PER_TIME_CHECK {
+            assert_eq!(timer.step(), Ok(()));
+        }
+        assert_eq!(
+            timer.step(),
+            Err(BudgetExceeded::Time {
+                time: Duration::from_nanos(1)
+            })
+        );
+        let mut timer = AnalysisBudget::unlimited().start();
+        for _ in 0..STEPS_PER_TIME_CHECK {
+            assert_eq!(timer.step(), Ok(()));
+        }
+    }
+}
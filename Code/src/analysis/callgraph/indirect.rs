@@ -0,0 +1,332 @@
+//! Resolve indirect calls through fn ptrs with points-to results.
+//! A fn ptr called in an instance may point to
+//! 1. the fn items flowing into it in the same instance, e.g.,
+//! ```ignore
+//! _9 = log_count as fn(&Counter) (PointerCoercion(ReifyFnPointer));
+//! _14 = _9;
+//! _13 = move _14(_15);
+//! ```
+//! 2. the fn items stored into the same struct field in any instance if it is read from a field, e.g.,
+//! ```ignore
+//! // in main
+//! _6 = log_count as fn(&Counter) (PointerCoercion(ReifyFnPointer));
+//! _4 = Counter { count: move _5, on_change: move _6 };
+//! // in Counter::incr
+//! _9 = ((*_1).1: fn(&Counter));
+//! _8 = move _9(_1);
+//! ```
+//! Only the instances calling fn ptrs or reifying fn items are analyzed.
+//!
+//! A generic fn without body, e.g., `with_retry<F: Fn() -> Result<()>>(f: F)` from a dependency without MIR,
+//! may call the closures passed as its generic args through their `Fn*` bounds, e.g.,
+//! ```ignore
+//! _4 = with_retry::<[closure@src/main.rs:18:20: 18:22]>(move _5);
+//! ```
+//! so a ClosureArg edge is added from the callsite to each closure defined in the caller.
+extern crate rustc_hash;
+extern crate rustc_hir;
+extern crate rustc_target;
+
+use petgraph::visit::EdgeRef;
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{
+    AggregateKind, Body, CastKind, Local, Operand, Place, ProjectionElem, Rvalue, StatementKind,
+    TerminatorKind,
+};
+use rustc_middle::ty::adjustment::PointerCoercion;
+use rustc_middle::ty::{
+    EarlyBinder, GenericArgsRef, Instance, InstanceDef, ParamEnv, TyCtxt, TyKind,
+};
+use rustc_target::abi::{FieldIdx, FIRST_VARIANT};
+
+use super::{CallGraph, CallGraphNode, CallSiteLocation, InstanceId};
+use crate::analysis::pointsto::{Andersen, ConstraintNode, PointsToMap};
+
+/// A struct field identified by the struct's DefId and the field index.
+type StructField = (DefId, FieldIdx);
+
+/// A fn item with its generic args.
+type FnItem<'tcx> = (DefId, GenericArgsRef<'tcx>);
+
+impl<'tcx> CallGraph<'tcx> {
+    /// Add Indirect edges from the callers of fn ptrs to the fn items they may point to.
+    pub(super) fn resolve_indirect_calls(&mut self, tcx: TyCtxt<'tcx>, param_env: ParamEnv<'tcx>) {
+        let candidates = self
+            .graph
+            .node_indices()
+            .filter_map(|idx| match self.graph[idx] {
+                CallGraphNode::WithBody(instance) => Some((idx, instance)),
+                CallGraphNode::WithoutBody(_) => None,
+            })
+            .filter(|(_, instance)| {
+                let body = tcx.instance_mir(instance.def);
+                body.source.promoted.is_none() && calls_or_reifies_fn_ptr(body, tcx)
+            })
+            .map(|(idx, instance)| {
+                let body = tcx.instance_mir(instance.def);
+                let mut andersen = Andersen::new(body, tcx);
+                andersen.analyze();
+                (idx, instance, andersen.finish())
+            })
+            .collect::<Vec<_>>();
+        // Collect the fn items stored into struct fields.
+        let mut field_fn_items: FxHashMap<StructField, FxHashSet<Instance<'tcx>>> =
+            FxHashMap::default();
+        for (_, instance, pts) in candidates.iter() {
+            let body = tcx.instance_mir(instance.def);
+            for (field, fn_item) in collect_field_stores(body, pts, tcx) {
+                if let Some(fn_item) = resolve_fn_item(instance, fn_item, tcx, param_env) {
+                    field_fn_items.entry(field).or_default().insert(fn_item);
+                }
+            }
+        }
+        // Resolve the fn ptrs called.
+        let mut callsites: Vec<(InstanceId, Instance<'tcx>, CallSiteLocation)> = Vec::new();
+        for (caller_idx, caller, pts) in candidates.iter() {
+            let body = tcx.instance_mir(caller.def);
+            for (block, bb_data) in body.basic_blocks.iter_enumerated() {
+                let func = match &bb_data.terminator().kind {
+                    TerminatorKind::Call { func, .. }
+                        if matches!(func.ty(body, tcx).kind(), TyKind::FnPtr(_)) =>
+                    {
+                        func
+                    }
+                    _ => continue,
+                };
+                let location = body.terminator_loc(block);
+                let fn_ptr = match func.place() {
+                    Some(fn_ptr) => fn_ptr,
+                    None => continue,
+                };
+                let mut callees = fn_items(fn_ptr, pts)
+                    .filter_map(|fn_item| resolve_fn_item(caller, fn_item, tcx, param_env))
+                    .collect::<FxHashSet<_>>();
+                if let Some(field) = fn_ptr
+                    .as_local()
+                    .and_then(|fn_ptr| read_from_field(body, fn_ptr, tcx))
+                {
+                    if let Some(fn_items) = field_fn_items.get(&field) {
+                        callees.extend(fn_items.iter().copied());
+                    }
+                }
+                for callee in callees {
+                    callsites.push((*caller_idx, callee, CallSiteLocation::Indirect(location)));
+                }
+            }
+        }
+        for (caller_idx, callee, location) in callsites {
+            self.add_callsite(caller_idx, callee, location);
+        }
+        for (_, instance, pts) in candidates {
+            self.indirect_pts.insert(instance.def_id(), pts);
+        }
+    }
+
+    /// Add ClosureArg edges from the direct callsites of generic fns without body
+    /// to the closures defined in the callers and passed as the generic args bounded by `Fn*`.
+    pub(super) fn resolve_closure_args(&mut self, tcx: TyCtxt<'tcx>, param_env: ParamEnv<'tcx>) {
+        let mut callsites: Vec<(InstanceId, Instance<'tcx>, CallSiteLocation)> = Vec::new();
+        for edge in self.graph.edge_references() {
+            let caller = match self.graph[edge.source()] {
+                CallGraphNode::WithBody(caller) => caller,
+                CallGraphNode::WithoutBody(_) => continue,
+            };
+            let callee = match self.graph[edge.target()] {
+                CallGraphNode::WithoutBody(callee)
+                    if matches!(callee.def, InstanceDef::Item(_)) =>
+                {
+                    callee
+                }
+                _ => continue,
+            };
+            let closures = fn_bounded_closures(&callee, tcx)
+                .into_iter()
+                .filter(|(def_id, _)| {
+                    tcx.typeck_root_def_id(*def_id) == tcx.typeck_root_def_id(caller.def_id())
+                })
+                .filter_map(|(def_id, args)| {
+                    Instance::resolve(tcx, param_env, def_id, args)
+                        .ok()
+                        .flatten()
+                })
+                .collect::<Vec<_>>();
+            for callsite in edge.weight() {
+                if let CallSiteLocation::Direct(location) = callsite {
+                    for closure in closures.iter() {
+                        callsites.push((
+                            edge.source(),
+                            *closure,
+                            CallSiteLocation::ClosureArg(*location),
+                        ));
+                    }
+                }
+            }
+        }
+        for (caller_idx, callee, location) in callsites {
+            self.add_callsite(caller_idx, callee, location);
+        }
+    }
+}
+
+/// The closures in the generic args of the instance that are bounded by `Fn`, `FnMut`, or `FnOnce`.
+fn fn_bounded_closures<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Vec<FnItem<'tcx>> {
+    if !instance
+        .args
+        .types()
+        .any(|ty| matches!(ty.kind(), TyKind::Closure(..)))
+    {
+        return Vec::new();
+    }
+    tcx.predicates_of(instance.def_id())
+        .instantiate(tcx, instance.args)
+        .predicates
+        .into_iter()
+        .filter_map(|clause| {
+            let trait_pred = clause.as_trait_clause()?.skip_binder();
+            tcx.fn_trait_kind_from_def_id(trait_pred.def_id())?;
+            match trait_pred.self_ty().kind() {
+                TyKind::Closure(def_id, args) => Some((*def_id, *args)),
+                _ => None,
+            }
+        })
+        .collect::<FxHashSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Check if the body calls a fn ptr or reifies a fn item to a fn ptr.
+fn calls_or_reifies_fn_ptr<'tcx>(body: &Body<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    body.basic_blocks.iter().any(|bb_data| {
+        let calls_fn_ptr = matches!(
+            &bb_data.terminator().kind,
+            TerminatorKind::Call { func, .. } if matches!(func.ty(body, tcx).kind(), TyKind::FnPtr(_))
+        );
+        calls_fn_ptr
+            || bb_data.statements.iter().any(|stmt| {
+                matches!(
+                    &stmt.kind,
+                    StatementKind::Assign(box (
+                        _,
+                        Rvalue::Cast(
+                            CastKind::PointerCoercion(PointerCoercion::ReifyFnPointer),
+                            _,
+                            _
+                        )
+                    ))
+                )
+            })
+    })
+}
+
+/// The fn items in the points-to set of the place.
+fn fn_items<'a, 'tcx>(
+    place: Place<'tcx>,
+    pts: &'a PointsToMap<'tcx>,
+) -> impl Iterator<Item = FnItem<'tcx>> + 'a {
+    pts.get(&ConstraintNode::Place(place.as_ref()))
+        .into_iter()
+        .flatten()
+        .filter_map(|pte| match pte {
+            ConstraintNode::FnItem(def_id, args) => Some((*def_id, *args)),
+            _ => None,
+        })
+}
+
+/// The fn items of the operand, either a constant fn item or a place pointing to fn items.
+fn operand_fn_items<'tcx>(operand: &Operand<'tcx>, pts: &PointsToMap<'tcx>) -> Vec<FnItem<'tcx>> {
+    match operand {
+        Operand::Copy(place) | Operand::Move(place) => fn_items(*place, pts).collect(),
+        Operand::Constant(constant) => match constant.ty().kind() {
+            TyKind::FnDef(def_id, args) => vec![(*def_id, args)],
+            _ => Vec::new(),
+        },
+    }
+}
+
+/// The struct field of the place if the last projection is a field of a struct.
+fn struct_field<'tcx>(
+    place: Place<'tcx>,
+    body: &Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<StructField> {
+    let (base, elem) = place.as_ref().last_projection()?;
+    match (elem, base.ty(body, tcx).ty.kind()) {
+        (ProjectionElem::Field(field, _), TyKind::Adt(adt_def, _)) if adt_def.is_struct() => {
+            Some((adt_def.did(), field))
+        }
+        _ => None,
+    }
+}
+
+/// Collect the fn items stored into struct fields, i.e.,
+/// `s = S { f: fn_item, .. }` or `s.f = fn_item`.
+fn collect_field_stores<'tcx>(
+    body: &Body<'tcx>,
+    pts: &PointsToMap<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<(StructField, FnItem<'tcx>)> {
+    let mut stores = Vec::new();
+    for bb_data in body.basic_blocks.iter() {
+        for stmt in bb_data.statements.iter() {
+            let (lhs, rvalue) = match &stmt.kind {
+                StatementKind::Assign(box (lhs, rvalue)) => (lhs, rvalue),
+                _ => continue,
+            };
+            match rvalue {
+                Rvalue::Aggregate(box AggregateKind::Adt(def_id, FIRST_VARIANT, ..), operands)
+                    if tcx.adt_def(*def_id).is_struct() =>
+                {
+                    for (field, operand) in operands.iter_enumerated() {
+                        for fn_item in operand_fn_items(operand, pts) {
+                            stores.push(((*def_id, field), fn_item));
+                        }
+                    }
+                }
+                Rvalue::Use(operand) | Rvalue::Cast(_, operand, _) => {
+                    if let Some(field) = struct_field(*lhs, body, tcx) {
+                        for fn_item in operand_fn_items(operand, pts) {
+                            stores.push((field, fn_item));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    stores
+}
+
+/// The struct field from which the fn ptr is read, i.e., `fn_ptr = s.f` or `fn_ptr = (*s).f`.
+fn read_from_field<'tcx>(
+    body: &Body<'tcx>,
+    fn_ptr: Local,
+    tcx: TyCtxt<'tcx>,
+) -> Option<StructField> {
+    body.basic_blocks.iter().find_map(|bb_data| {
+        bb_data.statements.iter().find_map(|stmt| match &stmt.kind {
+            StatementKind::Assign(box (
+                lhs,
+                Rvalue::Use(Operand::Copy(place) | Operand::Move(place)),
+            )) if lhs.as_local() == Some(fn_ptr) => struct_field(*place, body, tcx),
+            _ => None,
+        })
+    })
+}
+
+/// Resolve the fn item in the (monomorphic) instance to an instance.
+fn resolve_fn_item<'tcx>(
+    instance: &Instance<'tcx>,
+    (def_id, args): FnItem<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    param_env: ParamEnv<'tcx>,
+) -> Option<Instance<'tcx>> {
+    let args = instance.instantiate_mir_and_normalize_erasing_regions(
+        tcx,
+        param_env,
+        EarlyBinder::bind(args),
+    );
+    Instance::resolve(tcx, param_env, def_id, args)
+        .ok()
+        .flatten()
+}
@@ -7,38 +7,101 @@
 //! We also track where a closure is defined rather than called
 //! to record the defined function and the parameter of the closure,
 //! which is pointed to by upvars.
+//! A drop of a value with drop glue is a call to its drop glue, whose (shim) body
+//! calls the `Drop::drop` impls of the value and its fields,
+//! e.g., `drop(foo)` --> `drop_in_place::<Foo>` --> `<Foo as Drop>::drop`.
+//! A closure passed to a combinator calling it inline, e.g., `opt.map(|v| ..)`,
+//! is also called directly at the callsite of the combinator,
+//! while a closure passed to a spawn API, e.g., `s.spawn(|| ..)` of `std::thread::scope`,
+//! is spawned rather than called at the callsite.
+//! The analyses can be focused on the instances whose paths contain a pattern by `--focus`,
+//! including the instances they (transitively) call, while the callgraph is still built for the whole crate.
+extern crate rustc_hash;
+extern crate rustc_hir;
+
 use petgraph::algo;
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::NodeIndex;
-use petgraph::visit::IntoNodeReferences;
-use petgraph::Direction::Incoming;
+use petgraph::visit::{Dfs, EdgeFiltered, IntoNodeReferences};
+use petgraph::Direction::Outgoing;
 use petgraph::{Directed, Graph};
 
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hir::def_id::DefId;
+
 use rustc_middle::mir::visit::Visitor;
-use rustc_middle::mir::{Body, Local, LocalDecl, LocalKind, Location, Terminator, TerminatorKind};
-use rustc_middle::ty::{self, EarlyBinder, Instance, ParamEnv, TyCtxt, TyKind};
+use rustc_middle::mir::{
+    Body, Local, LocalDecl, LocalKind, Location, Operand, Terminator, TerminatorKind,
+};
+use rustc_middle::ty::{self, EarlyBinder, Instance, InstanceDef, ParamEnv, TyCtxt, TyKind};
+
+use crate::analysis::pointsto::PointsToMap;
+use crate::interest::concurrency::thread::SpawnApi;
+use crate::progress::Progress;
+
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
+
+mod indirect;
 
 /// The NodeIndex in CallGraph, denoting a unique instance in CallGraph.
 pub type InstanceId = NodeIndex;
 
 /// The location where caller calls callee.
-/// Support direct call for now, where callee resolves to FnDef.
+/// Support direct call, where callee resolves to FnDef,
+/// including the drop of a value, where callee is its drop glue,
+/// and the closure passed to a combinator calling it inline (see `is_inline_combinator`).
 /// Also support tracking the parameter of a closure (pointed to by upvars)
-/// TODO(boqin): Add support for FnPtr.
+/// A call on a trait object (`dyn Trait`) is Virtual,
+/// with an edge to each candidate method of the local impls of the trait.
+/// Since the real callee is unknown, the consumers should lower their confidence on Virtual edges.
+/// A call through a fn ptr is Indirect, with an edge to each fn item the fn ptr points to.
+/// A call into a generic fn without body (e.g., from a dependency without MIR)
+/// is assumed to call the closures passed as its generic args bounded by `Fn*`,
+/// thus a synthetic indirect edge (ClosureArg) from the callsite to each of the closures.
+/// The consumers should mark the findings only reachable through ClosureArg edges as heuristic.
+/// A closure (or future) passed to a spawn API (e.g., `std::thread::Scope::spawn`) is Spawn,
+/// which is not a call since it runs in a new thread, i.e., a thread root (see `ThreadRoots`).
 #[derive(Copy, Clone, Debug)]
 pub enum CallSiteLocation {
     Direct(Location),
     ClosureDef(Local),
-    // Indirect(Location),
+    Virtual(Location),
+    Indirect(Location),
+    ClosureArg(Location),
+    Spawn(Location),
 }
 
 impl CallSiteLocation {
     pub fn location(&self) -> Option<Location> {
         match self {
-            Self::Direct(loc) => Some(*loc),
+            Self::Direct(loc)
+            | Self::Virtual(loc)
+            | Self::Indirect(loc)
+            | Self::ClosureArg(loc) => Some(*loc),
             _ => None,
         }
     }
+
+    pub fn is_virtual(&self) -> bool {
+        matches!(self, Self::Virtual(_))
+    }
+
+    pub fn is_closure_arg(&self) -> bool {
+        matches!(self, Self::ClosureArg(_))
+    }
+
+    pub fn is_spawn(&self) -> bool {
+        matches!(self, Self::Spawn(_))
+    }
+}
+
+/// The limits on the number of paths and the depth (in edges) of each path
+/// when enumerating the callchains between two instances.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CallChainLimits {
+    pub max_paths: usize,
+    pub max_depth: usize,
 }
 
 /// The CallGraph node wrapping an Instance.
@@ -66,8 +129,17 @@ impl<'tcx> CallGraphNode<'tcx> {
 /// The directed edges are CallSite Locations.
 /// e.g., `Instance1--|[CallSite1, CallSite2]|-->Instance2`
 /// denotes `Instance1` calls `Instance2` at locations `Callsite1` and `CallSite2`.
+/// `index` maps each instance to its InstanceId and `callers` records the reverse adjacency,
+/// both kept in sync with `graph` when adding nodes and edges.
+/// Thus `graph` should only be modified by `CallGraph` itself.
 pub struct CallGraph<'tcx> {
     pub graph: Graph<CallGraphNode<'tcx>, Vec<CallSiteLocation>, Directed>,
+    index: FxHashMap<Instance<'tcx>, InstanceId>,
+    callers: FxHashMap<InstanceId, Vec<InstanceId>>,
+    /// The points-to info computed to resolve indirect calls, reused by `AliasAnalysis`.
+    indirect_pts: FxHashMap<DefId, PointsToMap<'tcx>>,
+    /// The instances matching `--focus` and the instances reachable from them, all if None.
+    focus: Option<(FxHashSet<InstanceId>, FxHashSet<InstanceId>)>,
 }
 
 impl<'tcx> CallGraph<'tcx> {
@@ -75,15 +147,63 @@ impl<'tcx> CallGraph<'tcx> {
     pub fn new() -> Self {
         Self {
             graph: Graph::new(),
+            index: Default::default(),
+            callers: Default::default(),
+            indirect_pts: Default::default(),
+            focus: None,
         }
     }
 
+    /// Focus the analyses on the instances whose paths contain `pattern` and the instances reachable from them.
+    pub fn set_focus(&mut self, pattern: &str, tcx: TyCtxt<'tcx>) {
+        let roots = self
+            .graph
+            .node_references()
+            .filter(|(_, node)| tcx.def_path_str(node.instance().def_id()).contains(pattern))
+            .map(|(instance_id, _)| instance_id)
+            .collect::<FxHashSet<_>>();
+        let mut reachable = FxHashSet::default();
+        for root in &roots {
+            let mut dfs = Dfs::new(&self.graph, *root);
+            while let Some(instance_id) = dfs.next(&self.graph) {
+                reachable.insert(instance_id);
+            }
+        }
+        self.focus = Some((roots, reachable));
+    }
+
+    /// Whether the instance is analyzed under `--focus`, i.e., it matches or is reachable from the matching ones.
+    pub fn is_focused(&self, instance_id: InstanceId) -> bool {
+        self.focus
+            .as_ref()
+            .map_or(true, |(_, reachable)| reachable.contains(&instance_id))
+    }
+
+    /// The instances matching `--focus`, empty without it.
+    pub fn focus_roots(&self) -> impl Iterator<Item = InstanceId> + '_ {
+        self.focus
+            .iter()
+            .flat_map(|(roots, _)| roots.iter().copied())
+    }
+
+    /// Take the points-to info computed to resolve indirect calls.
+    pub fn take_points_to(&mut self) -> FxHashMap<DefId, PointsToMap<'tcx>> {
+        std::mem::take(&mut self.indirect_pts)
+    }
+
     /// Search for the InstanceId of a given instance in CallGraph.
     pub fn instance_to_index(&self, instance: &Instance<'tcx>) -> Option<InstanceId> {
-        self.graph
-            .node_references()
-            .find(|(_idx, inst)| inst.match_instance(instance))
-            .map(|(idx, _)| idx)
+        let idx = self.index.get(instance).copied();
+        debug_assert!(idx.map_or(true, |idx| self.graph[idx].match_instance(instance)));
+        idx
+    }
+
+    /// Add a node and index its instance.
+    fn add_node(&mut self, node: CallGraphNode<'tcx>) -> InstanceId {
+        let instance = *node.instance();
+        let idx = self.graph.add_node(node);
+        self.index.insert(instance, idx);
+        idx
     }
 
     /// Get the instance by InstanceId.
@@ -93,20 +213,27 @@ impl<'tcx> CallGraph<'tcx> {
 
     /// Perform callgraph analysis on the given instances.
     /// The instances should be **all** the instances with MIR available in the current crate.
+    /// The drop glues not in the given instances are added with their shim bodies,
+    /// so that the `Drop::drop` impls they call are always reachable from the drops.
     pub fn analyze(
         &mut self,
         instances: Vec<Instance<'tcx>>,
         tcx: TyCtxt<'tcx>,
         param_env: ParamEnv<'tcx>,
+        progress: &Progress,
     ) {
-        let idx_insts = instances
+        let mut total = instances.len();
+        let mut idx_insts = instances
             .into_iter()
             .map(|inst| {
-                let idx = self.graph.add_node(CallGraphNode::WithBody(inst));
+                let idx = self.add_node(CallGraphNode::WithBody(inst));
                 (idx, inst)
             })
-            .collect::<Vec<_>>();
-        for (caller_idx, caller) in idx_insts {
+            .collect::<VecDeque<_>>();
+        let mut processed = 0;
+        while let Some((caller_idx, caller)) = idx_insts.pop_front() {
+            processed += 1;
+            progress.update(processed, total, "instances");
             let body = tcx.instance_mir(caller.def);
             // Skip promoted src
             if body.source.promoted.is_some() {
@@ -115,20 +242,40 @@ impl<'tcx> CallGraph<'tcx> {
             let mut collector = CallSiteCollector::new(caller, body, tcx, param_env);
             collector.visit_body(body);
             for (callee, location) in collector.finish() {
-                let callee_idx = if let Some(callee_idx) = self.instance_to_index(&callee) {
-                    callee_idx
-                } else {
-                    self.graph.add_node(CallGraphNode::WithoutBody(callee))
-                };
-                if let Some(edge_idx) = self.graph.find_edge(caller_idx, callee_idx) {
-                    // Update edge weight.
-                    self.graph.edge_weight_mut(edge_idx).unwrap().push(location);
-                } else {
-                    // Add edge if not exists.
-                    self.graph.add_edge(caller_idx, callee_idx, vec![location]);
+                if matches!(callee.def, InstanceDef::DropGlue(..))
+                    && self.instance_to_index(&callee).is_none()
+                {
+                    let callee_idx = self.add_node(CallGraphNode::WithBody(callee));
+                    idx_insts.push_back((callee_idx, callee));
+                    total += 1;
                 }
+                self.add_callsite(caller_idx, callee, location);
             }
         }
+        self.resolve_indirect_calls(tcx, param_env);
+        self.resolve_closure_args(tcx, param_env);
+    }
+
+    /// Add an edge from caller to callee (add callee if not exists) with callsite location.
+    fn add_callsite(
+        &mut self,
+        caller_idx: InstanceId,
+        callee: Instance<'tcx>,
+        location: CallSiteLocation,
+    ) {
+        let callee_idx = if let Some(callee_idx) = self.instance_to_index(&callee) {
+            callee_idx
+        } else {
+            self.add_node(CallGraphNode::WithoutBody(callee))
+        };
+        if let Some(edge_idx) = self.graph.find_edge(caller_idx, callee_idx) {
+            // Update edge weight.
+            self.graph.edge_weight_mut(edge_idx).unwrap().push(location);
+        } else {
+            // Add edge if not exists.
+            self.graph.add_edge(caller_idx, callee_idx, vec![location]);
+            self.callers.entry(callee_idx).or_default().push(caller_idx);
+        }
     }
 
     /// Find the callsites (weight) on the edge from source to target.
@@ -143,15 +290,115 @@ impl<'tcx> CallGraph<'tcx> {
 
     /// Find all the callers that call target
     pub fn callers(&self, target: InstanceId) -> Vec<InstanceId> {
-        self.graph.neighbors_directed(target, Incoming).collect()
+        self.callers.get(&target).cloned().unwrap_or_default()
     }
 
-    /// Find all simple paths from source to target.
+    /// Find the simple paths from source to target within `limits`.
     /// e.g., for one of the paths, `source --> instance1 --> instance2 --> target`,
     /// the return is [source, instance1, instance2, target].
-    pub fn all_simple_paths(&self, source: InstanceId, target: InstanceId) -> Vec<Vec<InstanceId>> {
-        algo::all_simple_paths::<Vec<_>, _>(&self.graph, source, target, 0, None)
-            .collect::<Vec<_>>()
+    /// If source is target, the paths are the cycles through it.
+    /// Only the instances that can reach target within the remaining depth are explored.
+    /// The returned bool denotes that paths are truncated by `limits`,
+    /// in which case the shortest path is always included if no path is within the depth.
+    pub fn simple_paths(
+        &self,
+        source: InstanceId,
+        target: InstanceId,
+        limits: CallChainLimits,
+    ) -> (Vec<Vec<InstanceId>>, bool) {
+        // The distance (in edges) to target and the next instance on the shortest path to target.
+        let mut dist_next = FxHashMap::default();
+        dist_next.insert(target, (0, target));
+        let mut worklist = VecDeque::from([target]);
+        while let Some(callee) = worklist.pop_front() {
+            let dist = dist_next[&callee].0;
+            for caller in self.callers(callee) {
+                if let Entry::Vacant(e) = dist_next.entry(caller) {
+                    e.insert((dist + 1, callee));
+                    worklist.push_back(caller);
+                }
+            }
+        }
+        // Bounded DFS, where `visited` is the current path without target.
+        let mut paths = Vec::new();
+        let mut truncated = false;
+        let mut visited = vec![source];
+        let mut stack = vec![self.graph.neighbors_directed(source, Outgoing)];
+        while let Some(children) = stack.last_mut() {
+            let child = match children.next() {
+                Some(child) => child,
+                None => {
+                    stack.pop();
+                    visited.pop();
+                    continue;
+                }
+            };
+            let dist = match dist_next.get(&child) {
+                Some((dist, _)) => *dist,
+                None => continue,
+            };
+            // The depth of the path through child to target.
+            if visited.len() + dist > limits.max_depth {
+                truncated = true;
+                continue;
+            }
+            if child == target {
+                if paths.len() == limits.max_paths {
+                    truncated = true;
+                    break;
+                }
+                paths.push(visited.iter().copied().chain(Some(target)).collect());
+            } else if !visited.contains(&child) {
+                visited.push(child);
+                stack.push(self.graph.neighbors_directed(child, Outgoing));
+            }
+        }
+        if paths.is_empty() && truncated {
+            // The shortest path: source --> first --> next --> .. --> target
+            let first = self
+                .graph
+                .neighbors_directed(source, Outgoing)
+                .filter(|child| dist_next.contains_key(child))
+                .min_by_key(|child| dist_next[child].0);
+            if let Some(mut node) = first {
+                let mut path = vec![source, node];
+                while node != target {
+                    node = dist_next[&node].1;
+                    path.push(node);
+                }
+                paths.push(path);
+            }
+        }
+        (paths, truncated)
+    }
+
+    /// Check if target is reachable from source without going through Virtual callsites.
+    pub fn is_reachable_without_virtual(&self, source: InstanceId, target: InstanceId) -> bool {
+        self.is_reachable_through(source, target, |callsite| !callsite.is_virtual())
+    }
+
+    /// Check if target is only reachable from source through the calls including ClosureArg callsites,
+    /// where the definitions of closures (ClosureDef) are not calls.
+    pub fn is_reachable_only_through_closure_args(
+        &self,
+        source: InstanceId,
+        target: InstanceId,
+    ) -> bool {
+        self.is_reachable_through(source, target, |callsite| callsite.location().is_some())
+            && !self.is_reachable_through(source, target, |callsite| {
+                callsite.location().is_some() && !callsite.is_closure_arg()
+            })
+    }
+
+    /// Check if target is reachable from source through the edges with any callsite satisfying `pred`.
+    fn is_reachable_through(
+        &self,
+        source: InstanceId,
+        target: InstanceId,
+        pred: impl Fn(&CallSiteLocation) -> bool,
+    ) -> bool {
+        let graph = EdgeFiltered::from_fn(&self.graph, |edge| edge.weight().iter().any(&pred));
+        algo::has_path_connecting(&graph, source, target, None)
     }
 
     /// Print the callgraph in dot format.
@@ -164,6 +411,61 @@ impl<'tcx> CallGraph<'tcx> {
     }
 }
 
+/// The combinators of `Option`, `Result`, and `Iterator` calling their closure args inline,
+/// e.g., `std::option::Option::<T>::map` or `std::iter::Iterator::for_each`.
+/// The lazy adapters (e.g., `Iterator::map`) are assumed to be consumed right away at their callsites.
+fn is_inline_combinator(fn_path: &str) -> bool {
+    let (ty_path, method) = match fn_path.rsplit_once("::") {
+        Some(split) => split,
+        None => return false,
+    };
+    match ty_path {
+        "std::option::Option::<T>" => matches!(
+            method,
+            "map"
+                | "map_or"
+                | "map_or_else"
+                | "and_then"
+                | "or_else"
+                | "unwrap_or_else"
+                | "ok_or_else"
+                | "filter"
+                | "is_some_and"
+                | "get_or_insert_with"
+        ),
+        "std::result::Result::<T, E>" => matches!(
+            method,
+            "map"
+                | "map_err"
+                | "map_or"
+                | "map_or_else"
+                | "and_then"
+                | "or_else"
+                | "unwrap_or_else"
+                | "is_ok_and"
+                | "is_err_and"
+        ),
+        "std::iter::Iterator" => matches!(
+            method,
+            "for_each"
+                | "try_for_each"
+                | "map"
+                | "filter"
+                | "filter_map"
+                | "flat_map"
+                | "fold"
+                | "try_fold"
+                | "any"
+                | "all"
+                | "find"
+                | "find_map"
+                | "position"
+                | "inspect"
+        ),
+        _ => false,
+    }
+}
+
 /// Visit Terminator and record callsites (callee + location).
 struct CallSiteCollector<'a, 'tcx> {
     caller: Instance<'tcx>,
@@ -189,6 +491,87 @@ impl<'a, 'tcx> CallSiteCollector<'a, 'tcx> {
         }
     }
 
+    /// Find the candidate callees of a virtual call `<dyn Trait as Trait>::method`,
+    /// i.e., the methods of local non-generic impls of `Trait`
+    /// (or the default methods of `Trait` if not overridden).
+    fn virtual_callees(&self, callee: Instance<'tcx>) -> Vec<Instance<'tcx>> {
+        let method_def_id = callee.def_id();
+        let trait_def_id = match self.tcx.trait_of_item(method_def_id) {
+            Some(trait_def_id) => trait_def_id,
+            None => return Vec::new(),
+        };
+        // callee.args = [dyn Trait, trait args.., method args..]
+        let num_trait_args = self.tcx.generics_of(trait_def_id).count();
+        let trait_args = &callee.args[1..num_trait_args];
+        self.tcx
+            .all_impls(trait_def_id)
+            .filter(|impl_def_id| {
+                impl_def_id.is_local() && self.tcx.generics_of(*impl_def_id).count() == 0
+            })
+            .filter_map(|impl_def_id| {
+                let trait_ref = self.tcx.impl_trait_ref(impl_def_id)?.instantiate_identity();
+                if self.tcx.erase_regions(trait_ref.args)[1..] != *trait_args {
+                    return None;
+                }
+                let args = self.tcx.mk_args_from_iter(
+                    std::iter::once(trait_ref.self_ty().into()).chain(callee.args.iter().skip(1)),
+                );
+                Instance::resolve(self.tcx, self.param_env, method_def_id, args)
+                    .ok()
+                    .flatten()
+            })
+            .collect()
+    }
+
+    /// The closures passed (by value or by ref) to the combinator `def_id` if it calls them inline.
+    fn inline_closures(&self, def_id: DefId, args: &[Operand<'tcx>]) -> Vec<Instance<'tcx>> {
+        let closures = args
+            .iter()
+            .filter_map(|arg| {
+                let arg_ty = self.caller.instantiate_mir_and_normalize_erasing_regions(
+                    self.tcx,
+                    self.param_env,
+                    EarlyBinder::bind(arg.ty(self.body, self.tcx)),
+                );
+                match *arg_ty.peel_refs().kind() {
+                    TyKind::Closure(closure_def_id, substs) => Some((closure_def_id, substs)),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        if closures.is_empty() || !is_inline_combinator(&self.tcx.def_path_str(def_id)) {
+            return Vec::new();
+        }
+        closures
+            .into_iter()
+            .filter_map(|(closure_def_id, substs)| {
+                Instance::resolve(self.tcx, self.param_env, closure_def_id, substs)
+                    .ok()
+                    .flatten()
+            })
+            .collect()
+    }
+
+    /// The closures (or futures, or fn items) spawned by the callee if it is a spawn API.
+    fn spawned_instances(&self, callee: &Instance<'tcx>) -> Vec<Instance<'tcx>> {
+        // Most calls have no closure (or fn item) args, so check them before the path of the callee.
+        let has_closure = callee.args.types().any(|ty| {
+            matches!(
+                ty.kind(),
+                TyKind::Closure(..) | TyKind::Generator(..) | TyKind::FnDef(..)
+            )
+        });
+        if !has_closure || SpawnApi::from_instance(callee, self.tcx).is_none() {
+            return Vec::new();
+        }
+        // A spawn API calling another one, e.g., `Scope::spawn` calling `Builder::spawn_scoped`,
+        // spawns no other thread than its caller's.
+        if SpawnApi::from_instance(&self.caller, self.tcx).is_some() {
+            return Vec::new();
+        }
+        SpawnApi::spawned_instances(callee, self.tcx, self.param_env)
+    }
+
     /// Consumes `CallSiteCollector` and returns its callsites when finished visiting.
     fn finish(self) -> impl IntoIterator<Item = (Instance<'tcx>, CallSiteLocation)> {
         self.callsites.into_iter()
@@ -196,10 +579,30 @@ impl<'a, 'tcx> CallSiteCollector<'a, 'tcx> {
 }
 
 impl<'a, 'tcx> Visitor<'tcx> for CallSiteCollector<'a, 'tcx> {
-    /// Resolve direct call.
+    /// Resolve direct call, virtual call, and drop.
     /// Inspired by rustc_mir/src/transform/inline.rs#get_valid_function_call.
     fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
-        if let TerminatorKind::Call { ref func, .. } = terminator.kind {
+        match terminator.kind {
+            // The drops on unwinding (in cleanup blocks) are skipped.
+            TerminatorKind::Drop { ref place, .. } if !self.body[location.block].is_cleanup => {
+                let ty = self.caller.instantiate_mir_and_normalize_erasing_regions(
+                    self.tcx,
+                    self.param_env,
+                    EarlyBinder::bind(place.ty(self.body, self.tcx).ty),
+                );
+                // The drop of a trait object is Virtual and the drop of a value without drop glue is a no-op.
+                let drop_glue = Instance::resolve_drop_in_place(self.tcx, ty);
+                if let InstanceDef::DropGlue(_, Some(_)) = drop_glue.def {
+                    self.callsites
+                        .push((drop_glue, CallSiteLocation::Direct(location)));
+                }
+            }
+            _ => {}
+        }
+        if let TerminatorKind::Call {
+            ref func, ref args, ..
+        } = terminator.kind
+        {
             let func_ty = func.ty(self.body, self.tcx);
             // Only after monomorphizing can Instance::resolve work
             let func_ty = self.caller.instantiate_mir_and_normalize_erasing_regions(
@@ -208,12 +611,26 @@ impl<'a, 'tcx> Visitor<'tcx> for CallSiteCollector<'a, 'tcx> {
                 EarlyBinder::bind(func_ty),
             );
             if let ty::FnDef(def_id, substs) = *func_ty.kind() {
+                for closure in self.inline_closures(def_id, args) {
+                    self.callsites
+                        .push((closure, CallSiteLocation::Direct(location)));
+                }
                 if let Some(callee) = Instance::resolve(self.tcx, self.param_env, def_id, substs)
                     .ok()
                     .flatten()
                 {
                     self.callsites
                         .push((callee, CallSiteLocation::Direct(location)));
+                    for spawned in self.spawned_instances(&callee) {
+                        self.callsites
+                            .push((spawned, CallSiteLocation::Spawn(location)));
+                    }
+                    if let InstanceDef::Virtual(..) = callee.def {
+                        for candidate in self.virtual_callees(callee) {
+                            self.callsites
+                                .push((candidate, CallSiteLocation::Virtual(location)));
+                        }
+                    }
                 }
             }
         }
@@ -227,13 +644,19 @@ impl<'a, 'tcx> Visitor<'tcx> for CallSiteCollector<'a, 'tcx> {
     ///
     /// _20 is of type Closure, but it is actually the arg that captures
     /// the variables in the defining function.
+    ///
+    /// Generators (async blocks and async fns, including the boxed futures
+    /// generated by `async_trait`) are tracked in the same way,
+    /// so that their bodies are reachable from the functions creating them.
     fn visit_local_decl(&mut self, local: Local, local_decl: &LocalDecl<'tcx>) {
         let func_ty = self.caller.instantiate_mir_and_normalize_erasing_regions(
             self.tcx,
             self.param_env,
             EarlyBinder::bind(local_decl.ty),
         );
-        if let TyKind::Closure(def_id, substs) = func_ty.kind() {
+        if let TyKind::Closure(def_id, substs) | TyKind::Generator(def_id, substs, _) =
+            func_ty.kind()
+        {
             match self.body.local_kind(local) {
                 LocalKind::Arg | LocalKind::ReturnPointer => {}
                 _ => {
@@ -251,3 +674,125 @@ impl<'a, 'tcx> Visitor<'tcx> for CallSiteCollector<'a, 'tcx> {
         self.super_local_decl(local, local_decl);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate rustc_hir;
+
+    use super::*;
+    use rustc_hir::def_id::{DefId, DefIndex, LOCAL_CRATE};
+    use rustc_middle::mir::START_BLOCK;
+
+    /// A synthetic instance that needs no TyCtxt.
+    fn synthetic_instance<'tcx>(i: u32) -> Instance<'tcx> {
+        let def_id = DefId {
+            krate: LOCAL_CRATE,
+            index: DefIndex::from_u32(i),
+        };
+        Instance::new(def_id, ty::List::empty())
+    }
+
+    #[test]
+    fn test_instance_to_index_10k_nodes() {
+        const NUM_NODES: u32 = 10_000;
+        let mut callgraph = CallGraph::new();
+        for i in 0..NUM_NODES {
+            callgraph.add_node(CallGraphNode::WithBody(synthetic_instance(i)));
+        }
+        // A chain 0 -> 1 -> .. -> NUM_NODES - 1, and 0 also calls 2.
+        for i in 1..NUM_NODES {
+            let caller = callgraph
+                .instance_to_index(&synthetic_instance(i - 1))
+                .unwrap();
+            let location = CallSiteLocation::Direct(START_BLOCK.start_location());
+            callgraph.add_callsite(caller, synthetic_instance(i), location);
+            if i == 2 {
+                callgraph.add_callsite(InstanceId::new(0), synthetic_instance(i), location);
+            }
+        }
+        // No new nodes for the callees already in the callgraph.
+        assert_eq!(callgraph.graph.node_count(), NUM_NODES as usize);
+        for i in 0..NUM_NODES {
+            let instance = synthetic_instance(i);
+            let idx = callgraph.instance_to_index(&instance).unwrap();
+            assert_eq!(idx.index(), i as usize);
+            assert!(callgraph.graph[idx].match_instance(&instance));
+        }
+        assert!(callgraph
+            .instance_to_index(&synthetic_instance(NUM_NODES))
+            .is_none());
+        // The reverse adjacency agrees with the graph.
+        assert!(callgraph.callers(InstanceId::new(0)).is_empty());
+        assert_eq!(
+            callgraph.callers(InstanceId::new(1)),
+            vec![InstanceId::new(0)]
+        );
+        let mut callers = callgraph.callers(InstanceId::new(2));
+        callers.sort();
+        assert_eq!(callers, vec![InstanceId::new(0), InstanceId::new(1)]);
+        assert_eq!(
+            callgraph
+                .callsites(InstanceId::new(0), InstanceId::new(1))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_simple_paths_limits() {
+        // 0 calls 1 and 2, 2k-1 and 2k call 2k+1 and 2k+2, .., 2n-1 and 2n call 2n+1,
+        // thus 2^n paths from 0 to 2n+1, each of depth n+1.
+        const N: u32 = 12;
+        let mut callgraph = CallGraph::new();
+        callgraph.add_node(CallGraphNode::WithBody(synthetic_instance(0)));
+        let location = CallSiteLocation::Direct(START_BLOCK.start_location());
+        let mut layer = vec![InstanceId::new(0)];
+        for i in 0..N {
+            for caller in layer.iter() {
+                callgraph.add_callsite(*caller, synthetic_instance(2 * i + 1), location);
+                callgraph.add_callsite(*caller, synthetic_instance(2 * i + 2), location);
+            }
+            layer = vec![
+                InstanceId::new(2 * i as usize + 1),
+                InstanceId::new(2 * i as usize + 2),
+            ];
+        }
+        for caller in layer.iter() {
+            callgraph.add_callsite(*caller, synthetic_instance(2 * N + 1), location);
+        }
+        let (source, target) = (InstanceId::new(0), InstanceId::new(2 * N as usize + 1));
+        let limits = CallChainLimits {
+            max_paths: 8,
+            max_depth: 20,
+        };
+        let (paths, truncated) = callgraph.simple_paths(source, target, limits);
+        assert_eq!(paths.len(), 8);
+        assert!(truncated);
+        assert!(paths.iter().all(|path| path.len() == N as usize + 2
+            && path.first() == Some(&source)
+            && path.last() == Some(&target)));
+        // Too shallow to reach target: only the shortest path.
+        let limits = CallChainLimits {
+            max_paths: 8,
+            max_depth: 3,
+        };
+        let (paths, truncated) = callgraph.simple_paths(source, target, limits);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].len(), N as usize + 2);
+        assert!(truncated);
+        // Within limits.
+        let limits = CallChainLimits {
+            max_paths: 8,
+            max_depth: 20,
+        };
+        let (paths, truncated) =
+            callgraph.simple_paths(InstanceId::new(2 * N as usize - 1), target, limits);
+        assert_eq!(paths.len(), 1);
+        assert!(!truncated);
+        // Unreachable.
+        let (paths, truncated) = callgraph.simple_paths(target, source, limits);
+        assert!(paths.is_empty());
+        assert!(!truncated);
+    }
+}
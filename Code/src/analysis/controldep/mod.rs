@@ -2,12 +2,16 @@ extern crate rustc_data_structures;
 extern crate rustc_index;
 extern crate rustc_middle;
 
+use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::fmt;
 
-use rustc_data_structures::fx::FxHashSet;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_index::bit_set::BitSet;
 use rustc_index::Idx;
 use rustc_index::IndexVec;
 use rustc_middle::mir::{BasicBlock, Location};
+use smallvec::SmallVec;
 
 use crate::analysis::postdom::{post_dominators, EndsControlFlowGraph};
 
@@ -21,26 +25,120 @@ pub fn influences(this: Location, other: Location, control_deps: &ControlDeps<Ba
     control_deps.influences(this.block, other.block)
 }
 
-#[derive(Clone, Debug)]
+type ParentSet<N> = SmallVec<[N; 4]>;
+
+/// The control-dependence parents of each node.
+/// Parents are stored as sorted SmallVecs and identical parent sets are interned,
+/// e.g., thousands of match arms share one parent set `{switch}`.
+#[derive(Clone)]
+pub struct ParentSets<N: Idx> {
+    /// node -> index of its parent set in `sets`
+    ids: IndexVec<N, usize>,
+    sets: Vec<ParentSet<N>>,
+}
+
+impl<N: Idx> ParentSets<N> {
+    fn from_sets(parents: IndexVec<N, FxHashSet<N>>) -> Self {
+        let mut interned: FxHashMap<ParentSet<N>, usize> = FxHashMap::default();
+        let mut sets = Vec::new();
+        let ids = parents
+            .into_iter()
+            .map(|set| {
+                let mut set = set.into_iter().collect::<ParentSet<N>>();
+                set.sort_unstable_by_key(|n| n.index());
+                *interned.entry(set).or_insert_with_key(|set| {
+                    sets.push(set.clone());
+                    sets.len() - 1
+                })
+            })
+            .collect();
+        Self { ids, sets }
+    }
+
+    pub fn get(&self, node: N) -> &[N] {
+        &self.sets[self.ids[node]]
+    }
+
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+/// Keeps the format of a list of sets, e.g., `[{0}, {0, 2}]`.
+impl<N: Idx> fmt::Debug for ParentSets<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.ids.iter().map(|id| DebugSet(&self.sets[*id])))
+            .finish()
+    }
+}
+
+struct DebugSet<'a, N>(&'a [N]);
+
+impl<'a, N: fmt::Debug> fmt::Debug for DebugSet<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.iter()).finish()
+    }
+}
+
+#[derive(Clone)]
 pub struct ControlDeps<N: Idx> {
-    parents: IndexVec<N, FxHashSet<N>>,
+    parents: ParentSets<N>,
+    /// The reverse of `parents`, computed lazily.
+    children: RefCell<Option<IndexVec<N, ParentSet<N>>>>,
+    /// influencer -> the nodes it influences, computed lazily and cached
+    /// since the same influencer is usually queried against many influencees.
+    influenced: RefCell<FxHashMap<N, BitSet<N>>>,
+}
+
+impl<N: Idx> fmt::Debug for ControlDeps<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ControlDeps")
+            .field("parents", &self.parents)
+            .finish()
+    }
 }
 
 impl<Node: Idx> ControlDeps<Node> {
+    fn new(parents: IndexVec<Node, FxHashSet<Node>>) -> Self {
+        Self {
+            parents: ParentSets::from_sets(parents),
+            children: RefCell::new(None),
+            influenced: RefCell::new(FxHashMap::default()),
+        }
+    }
+
     pub fn influences(&self, influencer: Node, influencee: Node) -> bool {
-        let mut worklist = VecDeque::from_iter([influencee]);
-        let mut visited = FxHashSet::from_iter([influencee]);
-        while let Some(n) = worklist.pop_front() {
-            if n == influencer {
-                return true;
+        let mut influenced = self.influenced.borrow_mut();
+        influenced
+            .entry(influencer)
+            .or_insert_with(|| self.influenced_by(influencer))
+            .contains(influencee)
+    }
+
+    /// The nodes transitively control-dependent on `influencer`, including itself.
+    fn influenced_by(&self, influencer: Node) -> BitSet<Node> {
+        let mut children = self.children.borrow_mut();
+        let children = children.get_or_insert_with(|| {
+            let mut children = IndexVec::from_elem_n(ParentSet::new(), self.parents.len());
+            for n in (0..self.parents.len()).map(Node::new) {
+                for p in self.parents.get(n) {
+                    children[*p].push(n);
+                }
             }
-            for p in &self.parents[n] {
-                if visited.insert(*p) {
-                    worklist.push_front(*p)
+            children
+        });
+        let mut visited = BitSet::new_empty(self.parents.len());
+        visited.insert(influencer);
+        let mut worklist = VecDeque::from_iter([influencer]);
+        while let Some(n) = worklist.pop_front() {
+            for c in &children[n] {
+                if visited.insert(*c) {
+                    worklist.push_back(*c);
                 }
             }
         }
-        false
+        visited
     }
 }
 
@@ -77,5 +175,5 @@ pub fn control_deps<G: EndsControlFlowGraph>(graph: G) -> ControlDeps<G::Node> {
         parents[c].insert(root);
     }
 
-    ControlDeps { parents }
+    ControlDeps::new(parents)
 }
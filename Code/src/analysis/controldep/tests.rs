@@ -7,6 +7,7 @@ use rustc_data_structures::graph::{
 use std::cmp::max;
 use std::iter;
 use std::slice;
+use std::time::{Duration, Instant};
 
 use super::*;
 
@@ -189,3 +190,34 @@ fn multi_ends_influences2() {
     //     }
     // }
 }
+
+#[test]
+fn wide_switch_influences() {
+    // 0 switches to 1..=N, each arm branches once more and all of them join at N + 1.
+    const N: usize = 4000;
+    let join = N + 1;
+    let mut edges = Vec::new();
+    for arm in 1..=N {
+        let inner = join + arm;
+        edges.push((0, arm));
+        edges.push((arm, inner));
+        edges.push((arm, join));
+        edges.push((inner, join));
+    }
+    edges.push((join, 2 * N + 2));
+    let graph = TestGraph::new(0, &edges);
+    let start = Instant::now();
+    let control_deps = control_deps(graph);
+    // All the arms share one interned parent set.
+    assert_eq!(control_deps.parents.get(1), &[0]);
+    assert_eq!(control_deps.parents.ids[1], control_deps.parents.ids[N]);
+    for arm in (1..=N).step_by(97) {
+        for other in 1..=N {
+            assert!(control_deps.influences(arm, join + other) == (arm == other));
+            assert!(!control_deps.influences(arm, join));
+        }
+        assert!(control_deps.influences(0, arm));
+    }
+    // Generous limit, only to catch quadratic regressions.
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
@@ -20,7 +20,7 @@ use rustc_middle::mir::visit::Visitor;
 use rustc_middle::mir::{Body, Local, Location, Place, Rvalue};
 
 pub fn all_data_dep_on(a: Local, data_deps: &DataDeps) -> FxHashSet<Local> {
-    let mut worklist = VecDeque::from_iter(data_deps.immediate_dep(a).into_iter());
+    let mut worklist = VecDeque::from_iter(data_deps.immediate_dep(a));
     let mut visited = FxHashSet::default();
     while let Some(n) = worklist.pop_front() {
         if !visited.insert(n) {
@@ -1,6 +1,8 @@
+pub mod budget;
 pub mod callgraph;
 pub mod controldep;
 pub mod datadep;
 pub mod defuse;
 pub mod pointsto;
 pub mod postdom;
+pub mod threadroot;
@@ -0,0 +1,233 @@
+//! Dump the constraint graph and the points-to info of the fns matching `--dump-pointsto`,
+//! to debug why two places do (not) alias.
+//! For each matching fn, the constraint graph is written in dot format to `{dir}/{crate}-{fn}.dot`,
+//! and the points-to sets in JSON to `{dir}/{crate}-{fn}.pts.json`.
+//! The nodes are labeled by their kinds and places, with the names of the user variables from `VarDebugInfo`,
+//! e.g., `Place((*_3).0 [guard])`.
+extern crate rustc_hash;
+extern crate rustc_hir;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use log::warn;
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::visit::Visitor;
+use rustc_middle::mir::{Body, Local, Place, PlaceRef, VarDebugInfoContents};
+use rustc_middle::ty::TyCtxt;
+
+use super::{ConstantKey, ConstraintGraphCollector, ConstraintNode, PointsToMap, ReturnSummaries};
+
+pub(super) struct PointsToDump {
+    /// The def path pattern of the dumped fns.
+    pattern: String,
+    dir: PathBuf,
+    /// Each fn is dumped once, when its points-to info is first queried.
+    dumped: FxHashSet<DefId>,
+}
+
+impl PointsToDump {
+    pub(super) fn new(pattern: &str, dir: PathBuf) -> Self {
+        Self {
+            pattern: pattern.to_owned(),
+            dir,
+            dumped: Default::default(),
+        }
+    }
+
+    /// Dump the fn if its def path matches the pattern and it is not dumped yet.
+    pub(super) fn dump<'tcx>(
+        &mut self,
+        def_id: DefId,
+        body: &Body<'tcx>,
+        pts: &PointsToMap<'tcx>,
+        return_summaries: &ReturnSummaries<'tcx>,
+        int_ptr_casts: bool,
+        tcx: TyCtxt<'tcx>,
+    ) {
+        let path = tcx.def_path_str(def_id);
+        if !path.contains(&self.pattern) || !self.dumped.insert(def_id) {
+            return;
+        }
+        let labels = NodeLabels::new(body, tcx);
+        let (dot, _) =
+            constraint_graph_dot(body, &labels, Some(return_summaries), int_ptr_casts, tcx);
+        let stem = format!(
+            "{}-{}",
+            tcx.crate_name(def_id.krate),
+            path.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_")
+        );
+        let written = std::fs::create_dir_all(&self.dir)
+            .and_then(|()| std::fs::write(self.dir.join(format!("{}.dot", stem)), dot))
+            .and_then(|()| {
+                let json = serde_json::to_string_pretty(&points_to_json(pts, &labels))?;
+                std::fs::write(self.dir.join(format!("{}.pts.json", stem)), json)
+            });
+        if let Err(err) = written {
+            warn!(
+                "lockbud: failed to dump the points-to info of {} to {}: {}",
+                path,
+                self.dir.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Label the constraint nodes of a fn.
+struct NodeLabels<'tcx> {
+    /// The names of the user variables.
+    names: FxHashMap<Local, String>,
+    tcx: TyCtxt<'tcx>,
+}
+
+impl<'tcx> NodeLabels<'tcx> {
+    fn new(body: &Body<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
+        let names = body
+            .var_debug_info
+            .iter()
+            .filter_map(|info| match info.value {
+                VarDebugInfoContents::Place(place) => {
+                    place.as_local().map(|local| (local, info.name.to_string()))
+                }
+                _ => None,
+            })
+            .collect();
+        Self { names, tcx }
+    }
+
+    fn label(&self, node: &ConstraintNode<'tcx>) -> String {
+        match node {
+            ConstraintNode::Alloc(place) => format!("Alloc({})", self.place(place)),
+            ConstraintNode::Place(place) => format!("Place({})", self.place(place)),
+            ConstraintNode::Constant(key) => format!("Constant({})", self.constant(key)),
+            ConstraintNode::ConstantDeref(key) => {
+                format!("ConstantDeref({})", self.constant(key))
+            }
+            ConstraintNode::FnItem(def_id, args) => {
+                format!("FnItem({})", self.tcx.def_path_str_with_args(*def_id, args))
+            }
+        }
+    }
+
+    /// `(*_3).0 [guard]`, where `guard` is the name of `_3`.
+    fn place(&self, place: &PlaceRef<'tcx>) -> String {
+        let place = Place {
+            local: place.local,
+            projection: self.tcx.mk_place_elems(place.projection),
+        };
+        match self.names.get(&place.local) {
+            Some(name) => format!("{:?} [{}]", place, name),
+            None => format!("{:?}", place),
+        }
+    }
+
+    fn constant(&self, key: &ConstantKey<'tcx>) -> String {
+        match key {
+            ConstantKey::Static(def_id) => format!("static {}", self.tcx.def_path_str(*def_id)),
+            ConstantKey::Value(kind) => format!("{:?}", kind),
+        }
+    }
+}
+
+/// The constraint graph of the body in dot format and its number of nodes.
+fn constraint_graph_dot<'tcx>(
+    body: &Body<'tcx>,
+    labels: &NodeLabels<'tcx>,
+    return_summaries: Option<&ReturnSummaries<'tcx>>,
+    int_ptr_casts: bool,
+    tcx: TyCtxt<'tcx>,
+) -> (String, usize) {
+    let mut collector = ConstraintGraphCollector::new(body, tcx, return_summaries, int_ptr_casts);
+    collector.visit_body(body);
+    let graph = collector.finish();
+    (
+        graph.dot(|node| labels.label(node)),
+        graph.graph.node_count(),
+    )
+}
+
+/// The points-to sets keyed and sorted by the labels.
+fn points_to_json<'tcx>(
+    pts: &PointsToMap<'tcx>,
+    labels: &NodeLabels<'tcx>,
+) -> BTreeMap<String, BTreeSet<String>> {
+    pts.iter()
+        .map(|(node, pointees)| {
+            (
+                labels.label(node),
+                pointees
+                    .iter()
+                    .map(|pointee| labels.label(pointee))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::callgraph::CallGraph;
+    use crate::analysis::pointsto::AliasAnalysis;
+    use crate::test_utils::{with_tcx, write_src};
+
+    const DUMP_SRC: &str = r#"
+use std::sync::Mutex;
+
+pub struct Counter {
+    count: Mutex<i32>,
+}
+
+pub fn increment(counter: &Counter) {
+    let mut guard = counter.count.lock().unwrap();
+    *guard += 1;
+}
+"#;
+
+    #[test]
+    fn test_dump_pointsto() {
+        let src = write_src("dump_pointsto", DUMP_SRC);
+        let dir = std::env::temp_dir().join("lockbud-test-dump_pointsto/debug");
+        let _ = std::fs::remove_dir_all(&dir);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(move |tcx| {
+                let def_id = tcx
+                    .mir_keys(())
+                    .iter()
+                    .map(|def_id| def_id.to_def_id())
+                    .find(|def_id| tcx.def_path_str(*def_id) == "increment")
+                    .unwrap();
+                let body = tcx.optimized_mir(def_id);
+                let callgraph = CallGraph::new();
+                let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+                alias_analysis.set_dump_pointsto("increment", dir.clone());
+                let pts_len = alias_analysis.get_or_insert_pts(def_id, body).len();
+                let (_, node_count) =
+                    constraint_graph_dot(body, &NodeLabels::new(body, tcx), None, true, tcx);
+
+                // The nodes in the dot file are the nodes of the constraint graph.
+                let dot = std::fs::read_to_string(dir.join("lib-increment.dot")).unwrap();
+                let node_lines = dot
+                    .lines()
+                    .filter(|line| {
+                        let line = line.trim_start();
+                        let id_len = line.chars().take_while(char::is_ascii_digit).count();
+                        id_len > 0 && line[id_len..].starts_with(" [")
+                    })
+                    .count();
+                assert_eq!(node_lines, node_count);
+                // The locals are labeled by the names of the user variables.
+                assert!(dot.contains("[counter]"), "{}", dot);
+                assert!(dot.contains("[guard]"), "{}", dot);
+
+                let json = std::fs::read_to_string(dir.join("lib-increment.pts.json")).unwrap();
+                let pts: BTreeMap<String, BTreeSet<String>> = serde_json::from_str(&json).unwrap();
+                assert_eq!(pts.len(), pts_len);
+            }),
+        );
+    }
+}
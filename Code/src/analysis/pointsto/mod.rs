@@ -9,31 +9,45 @@
 extern crate rustc_hash;
 extern crate rustc_hir;
 extern crate rustc_index;
+extern crate rustc_target;
+
+mod dump;
+pub mod stats;
+mod summary;
 
 use std::cmp::{Ordering, PartialOrd};
 use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 use rustc_hir::def_id::DefId;
+use rustc_index::IndexSlice;
 use rustc_middle::mir::visit::Visitor;
 use rustc_middle::mir::{
-    Body, ConstOperand, Local, Location, Operand, Place, PlaceElem, PlaceRef, ProjectionElem,
-    Rvalue, Statement, StatementKind, Terminator, TerminatorKind,
+    AggregateKind, Body, CastKind, Local, Location, Operand, Place, PlaceElem, PlaceRef,
+    ProjectionElem, Rvalue, Statement, StatementKind, Terminator, TerminatorKind,
 };
 use rustc_middle::ty::ConstKind;
 
 use rustc_middle::mir::Const;
-use rustc_middle::ty::{Instance, TyCtxt, TyKind};
+use rustc_middle::ty::{GenericArgsRef, Instance, ParamEnv, Ty, TyCtxt, TyKind};
+use rustc_target::abi::FieldIdx;
 
-use petgraph::dot::{Config, Dot};
+use petgraph::algo::tarjan_scc;
+use petgraph::dot::Dot;
 use petgraph::graph::NodeIndex;
-use petgraph::visit::EdgeRef;
+use petgraph::visit::{EdgeFiltered, EdgeRef};
 use petgraph::{Directed, Direction, Graph};
 
+use crate::analysis::budget::{AnalysisBudget, BudgetExceeded, BudgetTimer, SkippedFunction};
 use crate::analysis::callgraph::{CallGraph, CallGraphNode, CallSiteLocation, InstanceId};
 use crate::interest::concurrency::atomic::is_atomic_ptr_store;
 use crate::interest::concurrency::lock::LockGuardId;
-use crate::interest::memory::ownership;
+use crate::interest::memory::ownership::{self, PayloadPassing, RawPtrPassing};
+use dump::PointsToDump;
+use stats::{AliasQueryContext, AliasQueryStats, AliasStats, PointsToCacheStats};
+pub use summary::ReturnSummaries;
 
 /// Field-sensitive intra-procedural Andersen pointer analysis.
 /// <https://helloworld.pub/program-analysis-andersen-pointer-analysis-algorithm-based-on-svf.html>
@@ -47,28 +61,117 @@ use crate::interest::memory::ownership;
 /// 4. Treat special functions by names or signatures (e.g., Arc::clone).
 /// 5. Interproc methods: Use parameters' type info to guide the analysis heuristically (simple but powerful).
 /// 6. Interproc closures: Track the upvars of closures in the functions defining the closures (restricted).
+/// 7. Cycle elimination: The nodes on a cycle of Copy edges have the same points-to set,
+///    so each such cycle is collapsed into one representative node.
+///    Cycles are detected before solving and periodically as Load/Store add Copy edges.
+/// 8. Interproc return values: The calls of local fns returning pointers into their params
+///    are translated by the summaries of the callees if given (see `ReturnSummaries`).
+/// 9. Integer round-trips: The casts between integers and pointers (and transmutes) are copies,
+///    e.g., `q = (p as usize) as *const T` aliases `p`, which can be disabled (see `set_int_ptr_casts`).
 pub struct Andersen<'a, 'tcx> {
     body: &'a Body<'tcx>,
     tcx: TyCtxt<'tcx>,
     pts: PointsToMap<'tcx>,
+    /// Collapse the cycles of Copy edges while solving.
+    collapse_cycles: bool,
+    return_summaries: Option<&'a ReturnSummaries<'tcx>>,
+    /// Track the pointers through the casts between integers and pointers.
+    int_ptr_casts: bool,
 }
 
 pub type PointsToMap<'tcx> = FxHashMap<ConstraintNode<'tcx>, FxHashSet<ConstraintNode<'tcx>>>;
 
+/// Detect the cycles of Copy edges again after this many new Copy edges are added while solving,
+/// or after as many nodes as in the graph are processed if any new Copy edge is added.
+const NEW_COPY_EDGES_TO_COLLAPSE: usize = 128;
+
 impl<'a, 'tcx> Andersen<'a, 'tcx> {
     pub fn new(body: &'a Body<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
         Self {
             body,
             tcx,
             pts: Default::default(),
+            collapse_cycles: true,
+            return_summaries: None,
+            int_ptr_casts: true,
         }
     }
 
+    /// Whether the casts between integers and pointers (and transmutes) are copies, true by default.
+    /// It is unsound towards too many aliases, e.g., an integer merely compared with an address.
+    pub fn set_int_ptr_casts(&mut self, int_ptr_casts: bool) {
+        self.int_ptr_casts = int_ptr_casts;
+    }
+
+    /// Translate the calls of local fns by their return-value summaries.
+    pub fn set_return_summaries(&mut self, return_summaries: &'a ReturnSummaries<'tcx>) {
+        self.return_summaries = Some(return_summaries);
+    }
+
     pub fn analyze(&mut self) {
-        let mut collector = ConstraintGraphCollector::new(self.body, self.tcx);
+        self.analyze_with_budget(AnalysisBudget::unlimited())
+            .expect("the budget is unlimited");
+    }
+
+    /// Analyze within the budget. If the budget is exceeded, the analysis stops
+    /// at the next check (after collecting the constraints or between two steps of solving)
+    /// and the points-to info is incomplete.
+    pub fn analyze_with_budget(&mut self, budget: AnalysisBudget) -> Result<(), BudgetExceeded> {
+        self.pts = self.solver(budget)?.solve(budget)?;
+        Ok(())
+    }
+
+    /// Collect the constraints from MIR into a solver, which does not need `TyCtxt` any more.
+    pub fn solver(&self, budget: AnalysisBudget) -> Result<PointsToSolver<'tcx>, BudgetExceeded> {
+        let mut collector = ConstraintGraphCollector::new(
+            self.body,
+            self.tcx,
+            self.return_summaries,
+            self.int_ptr_casts,
+        );
         collector.visit_body(self.body);
-        let mut graph = collector.finish();
-        let mut worklist = VecDeque::new();
+        // Check before adding the partial copies, which is quadratic in the number of nodes.
+        budget.check_nodes(collector.graph.graph.node_count())?;
+        Ok(PointsToSolver {
+            graph: collector.finish(),
+            pts: Default::default(),
+            collapse_cycles: self.collapse_cycles,
+            merged: Vec::new(),
+        })
+    }
+
+    pub fn finish(self) -> FxHashMap<ConstraintNode<'tcx>, FxHashSet<ConstraintNode<'tcx>>> {
+        self.pts
+    }
+}
+
+/// Solves the constraints collected by `Andersen` with a fixed-point algorithm.
+/// It does not use `TyCtxt`, so the solvers of different fns can run on other threads.
+pub struct PointsToSolver<'tcx> {
+    graph: ConstraintGraph<'tcx>,
+    pts: PointsToMap<'tcx>,
+    /// Collapse the cycles of Copy edges while solving.
+    collapse_cycles: bool,
+    /// (merged node, its representative)
+    merged: Vec<(ConstraintNode<'tcx>, ConstraintNode<'tcx>)>,
+}
+
+impl<'tcx> PointsToSolver<'tcx> {
+    /// Solve within the time budget and return the points-to info.
+    /// If the budget is exceeded, the solving stops between two steps.
+    pub fn solve(mut self, budget: AnalysisBudget) -> Result<PointsToMap<'tcx>, BudgetExceeded> {
+        let mut timer = budget.start();
+        let mut graph = std::mem::take(&mut self.graph);
+        self.solve_graph(&mut graph, &mut timer)?;
+        Ok(self.finish())
+    }
+
+    fn solve_graph(
+        &mut self,
+        graph: &mut ConstraintGraph<'tcx>,
+        timer: &mut BudgetTimer,
+    ) -> Result<(), BudgetExceeded> {
+        let mut worklist = WorkList::default();
         // alloc: place = alloc
         for node in graph.nodes() {
             match node {
@@ -93,27 +196,45 @@ impl<'a, 'tcx> Andersen<'a, 'tcx> {
             }
         }
 
+        self.collapse_cycles(graph, &mut worklist);
+        let mut new_copy_edges = 0;
+        let mut processed = 0;
         while let Some(node) = worklist.pop_front() {
+            timer.step()?;
+            processed += 1;
+            let node = graph.rep(&node);
             if !self.pts.contains_key(&node) {
                 continue;
             }
-            for o in self.pts.get(&node).unwrap() {
+            // Only Copy edges are added below, so the sources and targets stay the same.
+            let store_sources = graph.store_sources(&node);
+            let load_targets = graph.load_targets(&node);
+            let pts = if store_sources.is_empty() && load_targets.is_empty() {
+                None
+            } else {
+                self.pts.get(&node)
+            };
+            for o in pts.into_iter().flatten() {
+                let o_rep = graph.rep(o);
                 // store: *node = source
-                for source in graph.store_sources(&node) {
-                    if graph.insert_edge(source.clone(), o.clone(), ConstraintEdge::Copy) {
-                        worklist.push_back(source);
+                for source in store_sources.iter() {
+                    if graph.insert_edge(source.clone(), o_rep.clone(), ConstraintEdge::Copy) {
+                        new_copy_edges += 1;
+                        worklist.push_back(source.clone());
                     }
                 }
                 // load: target = *node
-                for target in graph.load_targets(&node) {
-                    if graph.insert_edge(o.clone(), target, ConstraintEdge::Copy) {
-                        worklist.push_back(o.clone());
+                for target in load_targets.iter() {
+                    if graph.insert_edge(o_rep.clone(), target.clone(), ConstraintEdge::Copy) {
+                        new_copy_edges += 1;
+                        worklist.push_back(o_rep.clone());
                     }
                 }
             }
             // alias_copy: target = &X; X = ptr::read(node)
             for target in graph.alias_copy_targets(&node) {
-                if graph.insert_edge(node.clone(), target, ConstraintEdge::Copy) {
+                if graph.insert_edge(node.clone(), graph.rep(&target), ConstraintEdge::Copy) {
+                    new_copy_edges += 1;
                     worklist.push_back(node.clone());
                 }
             }
@@ -123,27 +244,86 @@ impl<'a, 'tcx> Andersen<'a, 'tcx> {
                     worklist.push_back(target);
                 }
             }
+            if new_copy_edges >= NEW_COPY_EDGES_TO_COLLAPSE
+                || (new_copy_edges > 0 && processed >= graph.graph.node_count())
+            {
+                new_copy_edges = 0;
+                processed = 0;
+                self.collapse_cycles(graph, &mut worklist);
+            }
+        }
+        Ok(())
+    }
+
+    /// Collapse the cycles of Copy edges in `graph`
+    /// and merge the points-to sets of the merged nodes into their representatives.
+    fn collapse_cycles(
+        &mut self,
+        graph: &mut ConstraintGraph<'tcx>,
+        worklist: &mut WorkList<'tcx>,
+    ) {
+        if !self.collapse_cycles {
+            return;
+        }
+        for (node, rep) in graph.collapse_copy_cycles() {
+            if let Some(node_pts) = self.pts.remove(&node) {
+                self.pts.entry(rep.clone()).or_default().extend(node_pts);
+            }
+            worklist.push_back(rep.clone());
+            self.merged.push((node, rep));
         }
     }
 
     /// pts(target) = pts(target) U pts(source), return true if pts(target) changed
     fn union_pts(&mut self, target: &ConstraintNode<'tcx>, source: &ConstraintNode<'tcx>) -> bool {
         // skip Alloc target
-        if matches!(target, ConstraintNode::Alloc(_)) {
+        if matches!(target, ConstraintNode::Alloc(_)) || target == source {
             return false;
         }
-        let old_len = self.pts.get(target).unwrap().len();
-        let source_pts = self.pts.get(source).unwrap().clone();
+        // Take pts(source) out instead of cloning it.
+        let source_pts = self.pts.remove(source).unwrap();
         let target_pts = self.pts.get_mut(target).unwrap();
-        target_pts.extend(source_pts.into_iter());
-        old_len != target_pts.len()
+        let old_len = target_pts.len();
+        target_pts.extend(source_pts.iter().cloned());
+        let changed = old_len != target_pts.len();
+        self.pts.insert(source.clone(), source_pts);
+        changed
     }
 
-    pub fn finish(self) -> FxHashMap<ConstraintNode<'tcx>, FxHashSet<ConstraintNode<'tcx>>> {
+    fn finish(mut self) -> PointsToMap<'tcx> {
+        // The merged nodes share the points-to sets of their representatives.
+        // A representative may be merged later, so map the nodes back in reverse order.
+        for (node, rep) in self.merged.iter().rev() {
+            if let Some(rep_pts) = self.pts.get(rep) {
+                let rep_pts = rep_pts.clone();
+                self.pts.insert(node.clone(), rep_pts);
+            }
+        }
         self.pts
     }
 }
 
+/// A FIFO worklist ignoring the nodes already in it.
+#[derive(Default)]
+struct WorkList<'tcx> {
+    queue: VecDeque<ConstraintNode<'tcx>>,
+    queued: FxHashSet<ConstraintNode<'tcx>>,
+}
+
+impl<'tcx> WorkList<'tcx> {
+    fn push_back(&mut self, node: ConstraintNode<'tcx>) {
+        if self.queued.insert(node.clone()) {
+            self.queue.push_back(node);
+        }
+    }
+
+    fn pop_front(&mut self) -> Option<ConstraintNode<'tcx>> {
+        let node = self.queue.pop_front()?;
+        self.queued.remove(&node);
+        Some(node)
+    }
+}
+
 /// `ConstraintNode` represents a memory cell, denoted by `Place` in MIR.
 /// A `Place` encompasses `Local` and `[ProjectionElem]`, `ProjectionElem`
 /// can be a `Field`, `Index`, etc.
@@ -155,12 +335,26 @@ impl<'a, 'tcx> Andersen<'a, 'tcx> {
 /// To enable the propagtion of points-to info for `Constant`,
 /// we introduce `ConstantDeref` to denote the points-to node of `Constant`,
 /// namely, forall Constant(c), Constant(c)--|address|-->ConstantDeref(c).
+/// A static is keyed by its DefId (see `ConstantKey`), so that the references to the same static alias
+/// and the references to different statics (even of the same type) do not.
+/// `FnItem` denotes a function item reified to a fn ptr, e.g., `p = foo as fn()`,
+/// namely, FnItem(foo)--|address|-->Place(p), which is used to resolve indirect calls.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ConstraintNode<'tcx> {
     Alloc(PlaceRef<'tcx>),
     Place(PlaceRef<'tcx>),
-    Constant(ConstKind<'tcx>),
-    ConstantDeref(ConstKind<'tcx>),
+    Constant(ConstantKey<'tcx>),
+    ConstantDeref(ConstantKey<'tcx>),
+    FnItem(DefId, GenericArgsRef<'tcx>),
+}
+
+/// The identity of a `Constant`: a reference to a static, e.g., `const {alloc1: &Mutex<i32>}`,
+/// is keyed by the DefId of the static rather than by the value, which differs across the fns,
+/// while a type-level constant is keyed by its value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConstantKey<'tcx> {
+    Static(DefId),
+    Value(ConstKind<'tcx>),
 }
 
 /// The assignments in MIR with default `mir-opt-level` (level 1) are simplified
@@ -193,13 +387,16 @@ enum AccessPattern<'tcx> {
     Ref(PlaceRef<'tcx>),
     Indirect(PlaceRef<'tcx>),
     Direct(PlaceRef<'tcx>),
-    Constant(ConstKind<'tcx>),
+    Constant(ConstantKey<'tcx>),
+    FnItem(DefId, GenericArgsRef<'tcx>),
 }
 
 #[derive(Default)]
 struct ConstraintGraph<'tcx> {
     graph: Graph<ConstraintNode<'tcx>, ConstraintEdge, Directed>,
     node_map: FxHashMap<ConstraintNode<'tcx>, NodeIndex>,
+    /// The nodes merged into their representatives by `collapse_copy_cycles`.
+    reps: FxHashMap<NodeIndex, NodeIndex>,
 }
 
 impl<'tcx> ConstraintGraph<'tcx> {
@@ -217,6 +414,91 @@ impl<'tcx> ConstraintGraph<'tcx> {
         self.node_map.get(node).copied()
     }
 
+    fn rep_idx(&self, mut idx: NodeIndex) -> NodeIndex {
+        while let Some(rep) = self.reps.get(&idx) {
+            idx = *rep;
+        }
+        idx
+    }
+
+    /// The representative of the node if it is merged, otherwise the node itself.
+    fn rep(&self, node: &ConstraintNode<'tcx>) -> ConstraintNode<'tcx> {
+        match self.get_node(node) {
+            Some(idx) if self.reps.contains_key(&idx) => self.graph[self.rep_idx(idx)].clone(),
+            _ => node.clone(),
+        }
+    }
+
+    /// Merge each cycle of Copy edges into the node with the minimal index on it,
+    /// and return the merged nodes with their representatives.
+    /// A node plays two roles: a pointer (Copy/Load/Store edges, the source of AliasCopy edges
+    /// and the target of Address edges) and a pointee (the source of Address edges
+    /// and the target of AliasCopy edges). Only the edges of the pointer role are redirected
+    /// to the representative, so the pointees stay distinct.
+    /// Alloc nodes are skipped because their points-to sets are never updated.
+    fn collapse_copy_cycles(&mut self) -> Vec<(ConstraintNode<'tcx>, ConstraintNode<'tcx>)> {
+        let copy_edges = EdgeFiltered::from_fn(&self.graph, |edge| {
+            *edge.weight() == ConstraintEdge::Copy
+                && !matches!(self.graph[edge.source()], ConstraintNode::Alloc(_))
+                && !matches!(self.graph[edge.target()], ConstraintNode::Alloc(_))
+        });
+        let mut new_reps = FxHashMap::default();
+        for scc in tarjan_scc(&copy_edges) {
+            if scc.len() < 2 {
+                continue;
+            }
+            let rep = *scc.iter().min().unwrap();
+            new_reps.extend(
+                scc.into_iter()
+                    .filter(|idx| *idx != rep)
+                    .map(|idx| (idx, rep)),
+            );
+        }
+        if new_reps.is_empty() {
+            return Vec::new();
+        }
+        let map = |idx: NodeIndex| new_reps.get(&idx).copied().unwrap_or(idx);
+        let mut removed = Vec::new();
+        let mut redirected = Vec::new();
+        for edge in self.graph.edge_references() {
+            let (source, target, weight) = (edge.source(), edge.target(), *edge.weight());
+            let (new_source, new_target) = match weight {
+                ConstraintEdge::Copy | ConstraintEdge::Load | ConstraintEdge::Store => {
+                    (map(source), map(target))
+                }
+                ConstraintEdge::AliasCopy => (map(source), target),
+                ConstraintEdge::Address => continue,
+            };
+            if (new_source, new_target) == (source, target) {
+                continue;
+            }
+            removed.push(edge.id());
+            if weight != ConstraintEdge::Copy || new_source != new_target {
+                redirected.push((new_source, new_target, weight));
+            }
+        }
+        for (source, target, weight) in redirected {
+            if !self
+                .graph
+                .edges_connecting(source, target)
+                .any(|edge| *edge.weight() == weight)
+            {
+                self.graph.add_edge(source, target, weight);
+            }
+        }
+        // Removing an edge moves the last edge to its index, so remove from the last one.
+        removed.sort_unstable_by(|a, b| b.cmp(a));
+        for edge in removed {
+            self.graph.remove_edge(edge);
+        }
+        let merged = new_reps
+            .iter()
+            .map(|(node, rep)| (self.graph[*node].clone(), self.graph[*rep].clone()))
+            .collect();
+        self.reps.extend(new_reps);
+        merged
+    }
+
     fn add_alloc(&mut self, place: PlaceRef<'tcx>) {
         let lhs = ConstraintNode::Place(place);
         let rhs = ConstraintNode::Alloc(place);
@@ -225,7 +507,8 @@ impl<'tcx> ConstraintGraph<'tcx> {
         self.graph.add_edge(rhs, lhs, ConstraintEdge::Address);
     }
 
-    fn add_constant(&mut self, constant: ConstKind<'tcx>) {
+    fn add_constant(&mut self, constant: ConstantKey<'tcx>) {
+        let is_static = matches!(constant, ConstantKey::Static(_));
         let lhs = ConstraintNode::Constant(constant.clone());
         let rhs = ConstraintNode::ConstantDeref(constant);
         let lhs = self.get_or_insert_node(lhs);
@@ -233,7 +516,19 @@ impl<'tcx> ConstraintGraph<'tcx> {
         self.graph.add_edge(rhs, lhs, ConstraintEdge::Address);
         // For a constant C, there may be deref like *C, **C, ***C, ... in a real program.
         // For simplicity, we only track *C, and treat **C, ***C, ... the same as *C.
-        self.graph.add_edge(rhs, rhs, ConstraintEdge::Address);
+        // The memory of a static is not collapsed with the pointers stored in it,
+        // e.g., `static S: &Mutex<i32> = &M;` does not alias `M` with `S`.
+        if !is_static {
+            self.graph.add_edge(rhs, rhs, ConstraintEdge::Address);
+        }
+    }
+
+    fn add_fn_item(&mut self, lhs: PlaceRef<'tcx>, def_id: DefId, args: GenericArgsRef<'tcx>) {
+        let lhs = ConstraintNode::Place(lhs);
+        let rhs = ConstraintNode::FnItem(def_id, args);
+        let lhs = self.get_or_insert_node(lhs);
+        let rhs = self.get_or_insert_node(rhs);
+        self.graph.add_edge(rhs, lhs, ConstraintEdge::Address);
     }
 
     fn add_address(&mut self, lhs: PlaceRef<'tcx>, rhs: PlaceRef<'tcx>) {
@@ -252,7 +547,7 @@ impl<'tcx> ConstraintGraph<'tcx> {
         self.graph.add_edge(rhs, lhs, ConstraintEdge::Copy);
     }
 
-    fn add_copy_constant(&mut self, lhs: PlaceRef<'tcx>, rhs: ConstKind<'tcx>) {
+    fn add_copy_constant(&mut self, lhs: PlaceRef<'tcx>, rhs: ConstantKey<'tcx>) {
         let lhs = ConstraintNode::Place(lhs);
         let rhs = ConstraintNode::Constant(rhs);
         let lhs = self.get_or_insert_node(lhs);
@@ -276,7 +571,7 @@ impl<'tcx> ConstraintGraph<'tcx> {
         self.graph.add_edge(rhs, lhs, ConstraintEdge::Store);
     }
 
-    fn add_store_constant(&mut self, lhs: PlaceRef<'tcx>, rhs: ConstKind<'tcx>) {
+    fn add_store_constant(&mut self, lhs: PlaceRef<'tcx>, rhs: ConstantKey<'tcx>) {
         let lhs = ConstraintNode::Place(lhs);
         let rhs = ConstraintNode::Constant(rhs);
         let lhs = self.get_or_insert_node(lhs);
@@ -391,24 +686,24 @@ impl<'tcx> ConstraintGraph<'tcx> {
     ) -> bool {
         let from = self.get_node(&from).unwrap();
         let to = self.get_node(&to).unwrap();
-        if let Some(edge) = self.graph.find_edge(from, to) {
-            if let Some(w) = self.graph.edge_weight(edge) {
-                if *w == weight {
-                    return false;
-                }
-            }
+        // There may be multiple edges of different weights between two nodes.
+        if self
+            .graph
+            .edges_connecting(from, to)
+            .any(|edge| *edge.weight() == weight)
+        {
+            return false;
         }
         self.graph.add_edge(from, to, weight);
         true
     }
 
-    /// Print the callgraph in dot format.
-    #[allow(dead_code)]
-    pub fn dot(&self) {
-        println!(
-            "{:?}",
-            Dot::with_config(&self.graph, &[Config::GraphContentOnly])
-        );
+    /// The constraint graph in dot format, with the nodes labeled by `label`.
+    fn dot(&self, label: impl Fn(&ConstraintNode<'tcx>) -> String) -> String {
+        let graph = self
+            .graph
+            .map(|_, node| label(node), |_, edge| format!("{:?}", edge));
+        format!("{}", Dot::new(&graph))
     }
 }
 
@@ -417,20 +712,71 @@ struct ConstraintGraphCollector<'a, 'tcx> {
     body: &'a Body<'tcx>,
     tcx: TyCtxt<'tcx>,
     graph: ConstraintGraph<'tcx>,
+    return_summaries: Option<&'a ReturnSummaries<'tcx>>,
+    /// The places the locals assigned only once are copied from, e.g., `_5 -> _1.2` for `_5 = &((*_1).2)`,
+    /// to translate the args by the return-value summaries, see `ref_sources`.
+    ref_sources: FxHashMap<Local, PlaceRef<'tcx>>,
+    /// See `Andersen::set_int_ptr_casts`.
+    int_ptr_casts: bool,
 }
 
 impl<'a, 'tcx> ConstraintGraphCollector<'a, 'tcx> {
-    fn new(body: &'a Body<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
-        Self {
+    fn new(
+        body: &'a Body<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        return_summaries: Option<&'a ReturnSummaries<'tcx>>,
+        int_ptr_casts: bool,
+    ) -> Self {
+        let mut collector = Self {
             body,
             tcx,
             graph: ConstraintGraph::default(),
+            return_summaries,
+            ref_sources: Default::default(),
+            int_ptr_casts,
+        };
+        if return_summaries.is_some() {
+            collector.ref_sources = collector.ref_sources();
+        }
+        collector
+    }
+
+    /// The places the locals assigned only once by `a = &(*b).f` or `a = b` are copied from.
+    fn ref_sources(&self) -> FxHashMap<Local, PlaceRef<'tcx>> {
+        let mut sources = FxHashMap::default();
+        let mut assigned = FxHashSet::default();
+        for statement in self.body.basic_blocks.iter().flat_map(|bb| &bb.statements) {
+            let StatementKind::Assign(box (place, rvalue)) = &statement.kind else {
+                continue;
+            };
+            let Some(local) = place.as_local() else {
+                continue;
+            };
+            if !assigned.insert(local) {
+                sources.remove(&local);
+                continue;
+            }
+            if let Some(AccessPattern::Direct(source)) = self.process_rvalue(rvalue) {
+                sources.insert(local, source);
+            }
+        }
+        // The locals assigned by calls are not copied from a place.
+        for bb in self.body.basic_blocks.iter() {
+            if let TerminatorKind::Call { destination, .. } = &bb.terminator().kind {
+                sources.remove(&destination.local);
+            }
         }
+        sources
     }
 
     fn process_assignment(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>) {
+        // closure = {closure} { upvar0, upvar1, .. } => closure.i = upvar_i
+        if let Rvalue::Aggregate(box AggregateKind::Closure(..), operands) = rvalue {
+            self.process_closure_upvars(place, operands);
+            return;
+        }
         let lhs_pattern = Self::process_place(place.as_ref());
-        let rhs_pattern = Self::process_rvalue(rvalue);
+        let rhs_pattern = self.process_rvalue(rvalue);
         match (lhs_pattern, rhs_pattern) {
             // a = &b
             (AccessPattern::Direct(lhs), Some(AccessPattern::Ref(rhs))) => {
@@ -439,11 +785,23 @@ impl<'a, 'tcx> ConstraintGraphCollector<'a, 'tcx> {
             // a = b
             (AccessPattern::Direct(lhs), Some(AccessPattern::Direct(rhs))) => {
                 self.graph.add_copy(lhs, rhs);
+                // a = (b as Variant).f also copies b, see `variant_base`
+                if let Some(base) = Self::variant_base(rhs) {
+                    self.graph.add_copy(lhs, base);
+                }
+                // a = b[i] also copies b, see `element_base`
+                if let Some(base) = Self::element_base(rhs) {
+                    self.graph.add_copy(lhs, base);
+                }
             }
             // a = Constant
             (AccessPattern::Direct(lhs), Some(AccessPattern::Constant(rhs))) => {
                 self.graph.add_copy_constant(lhs, rhs);
             }
+            // a = fn item
+            (AccessPattern::Direct(lhs), Some(AccessPattern::FnItem(def_id, args))) => {
+                self.graph.add_fn_item(lhs, def_id, args);
+            }
             // a = *b
             (AccessPattern::Direct(lhs), Some(AccessPattern::Indirect(rhs))) => {
                 self.graph.add_load(lhs, rhs);
@@ -460,6 +818,28 @@ impl<'a, 'tcx> ConstraintGraphCollector<'a, 'tcx> {
         }
     }
 
+    /// Copy the captured upvars into the fields of the closure,
+    /// which are tracked to the defsite by the interproc closure alias (see `closure_defsite_upvars`).
+    fn process_closure_upvars(
+        &mut self,
+        place: &Place<'tcx>,
+        operands: &IndexSlice<FieldIdx, Operand<'tcx>>,
+    ) {
+        if place.is_indirect() {
+            return;
+        }
+        for (field, operand) in operands.iter_enumerated() {
+            let upvar = match operand {
+                Operand::Move(upvar) | Operand::Copy(upvar) => upvar,
+                Operand::Constant(_) => continue,
+            };
+            let field_place =
+                self.tcx
+                    .mk_place_field(*place, field, operand.ty(self.body, self.tcx));
+            self.graph.add_copy(field_place.as_ref(), upvar.as_ref());
+        }
+    }
+
     fn process_place(place_ref: PlaceRef<'tcx>) -> AccessPattern<'tcx> {
         match place_ref {
             PlaceRef {
@@ -473,24 +853,80 @@ impl<'a, 'tcx> ConstraintGraphCollector<'a, 'tcx> {
         }
     }
 
-    fn process_rvalue(rvalue: &Rvalue<'tcx>) -> Option<AccessPattern<'tcx>> {
+    /// The enum of `(b as Variant).f`, i.e., the place before the first `Downcast`.
+    /// An enum returned from a call is copied from the args as a whole,
+    /// so its variant fields have no pts of their own.
+    /// e.g., `_4 = Try::branch(move _5); _2 = move ((_4 as Continue).0: MutexGuard<i32>)`
+    /// where `_5 = Mutex::lock(move _6)` from `let _2 = m.lock()?;`.
+    fn variant_base(place_ref: PlaceRef<'tcx>) -> Option<PlaceRef<'tcx>> {
+        let idx = place_ref
+            .projection
+            .iter()
+            .position(|elem| matches!(elem, ProjectionElem::Downcast(..)))?;
+        Some(PlaceRef {
+            local: place_ref.local,
+            projection: &place_ref.projection[..idx],
+        })
+    }
+
+    /// Whether a value of `from_ty` converted to `to_ty` is an integer round-trip of a pointer,
+    /// e.g., `p as usize`, `addr as *const T`, or `transmute::<*const T, usize>(p)`.
+    fn is_int_ptr_conversion(from_ty: Ty<'tcx>, to_ty: Ty<'tcx>) -> bool {
+        let is_ptr = |ty: Ty<'tcx>| ty.is_unsafe_ptr() || ty.is_ref() || ty.is_fn_ptr();
+        (from_ty.is_integral() && is_ptr(to_ty)) || (is_ptr(from_ty) && to_ty.is_integral())
+    }
+
+    /// The collection of `b[i]`, i.e., the place before the first `Index` or `ConstantIndex`.
+    /// The elements of a collection are not distinguished in the points-to sets,
+    /// e.g., `_5 = &(*_1)[_4]` and `_8 = &(*_1)[_7]` both point to `*_1`, see `element_index`.
+    fn element_base(place_ref: PlaceRef<'tcx>) -> Option<PlaceRef<'tcx>> {
+        let idx = place_ref.projection.iter().position(|elem| {
+            matches!(
+                elem,
+                ProjectionElem::Index(..) | ProjectionElem::ConstantIndex { .. }
+            )
+        })?;
+        Some(PlaceRef {
+            local: place_ref.local,
+            projection: &place_ref.projection[..idx],
+        })
+    }
+
+    fn process_rvalue(&self, rvalue: &Rvalue<'tcx>) -> Option<AccessPattern<'tcx>> {
         match rvalue {
+            // Regard `p = addr as *const T` as `p = addr` unless disabled, see `Andersen::set_int_ptr_casts`
+            Rvalue::Cast(
+                CastKind::PointerExposeAddress
+                | CastKind::PointerFromExposedAddress
+                | CastKind::Transmute,
+                operand,
+                ty,
+            ) if !self.int_ptr_casts
+                && Self::is_int_ptr_conversion(operand.ty(self.body, self.tcx), *ty) =>
+            {
+                None
+            }
             Rvalue::Use(operand) | Rvalue::Repeat(operand, _) | Rvalue::Cast(_, operand, _) => {
                 match operand {
                     Operand::Move(place) | Operand::Copy(place) => {
                         Some(AccessPattern::Direct(place.as_ref()))
                     }
-                    Operand::Constant(box ConstOperand {
-                        span: _,
-                        user_ty: _,
-                        const_,
-                    }) => {
-                        if let Const::Ty(const_) = *const_ {
-                            Some(AccessPattern::Constant(const_.kind()))
-                        } else {
-                            None
+                    Operand::Constant(constant) => match constant.const_ {
+                        Const::Ty(const_) => {
+                            Some(AccessPattern::Constant(ConstantKey::Value(const_.kind())))
                         }
-                    }
+                        // e.g., foo as fn() (PointerCoercion(ReifyFnPointer))
+                        Const::Val(_, ty) => match ty.kind() {
+                            TyKind::FnDef(def_id, args) => {
+                                Some(AccessPattern::FnItem(*def_id, args))
+                            }
+                            // e.g., `const {alloc1: &Mutex<i32>}` of `&STATIC`
+                            _ => constant
+                                .check_static_ptr(self.tcx)
+                                .map(|def_id| AccessPattern::Constant(ConstantKey::Static(def_id))),
+                        },
+                        _ => None,
+                    },
                 }
             }
             // Regard `p = &*q` as `p = q`
@@ -504,16 +940,126 @@ impl<'a, 'tcx> ConstraintGraphCollector<'a, 'tcx> {
                 })),
                 _ => Some(AccessPattern::Ref(place.as_ref())),
             },
+            // Regard `p = deref_copy (*q).f` as `p = q.f`, like `p = &(*q).f`
+            Rvalue::CopyForDeref(place) => match place.as_ref() {
+                PlaceRef {
+                    local: l,
+                    projection: [ProjectionElem::Deref, ref remain @ ..],
+                } => Some(AccessPattern::Direct(PlaceRef {
+                    local: l,
+                    projection: remain,
+                })),
+                _ => Some(AccessPattern::Direct(place.as_ref())),
+            },
             _ => None,
         }
     }
 
+    /// dest = f(arg0, ..) where the return value of `f` points to `_1.f` of its params =>
+    /// arg0.f--|copy|-->dest
+    /// An arg copied from a place, e.g., `_5 = &((*_1).2)`, is replaced by the place,
+    /// so that `dest` points to `_1.2.f` and aliases with the other places loaded from `_1.2.f`.
+    /// Return false if any arg in the summary is not a place.
+    fn process_return_summary(
+        &mut self,
+        summary: &[(usize, &'tcx [PlaceElem<'tcx>])],
+        args: &[Operand<'tcx>],
+        dest: PlaceRef<'tcx>,
+    ) -> bool {
+        let mut sources = Vec::with_capacity(summary.len());
+        for (idx, projection) in summary {
+            let arg = match args.get(*idx) {
+                Some(Operand::Move(arg) | Operand::Copy(arg)) if !arg.is_indirect() => arg,
+                _ => return false,
+            };
+            let base = arg
+                .as_local()
+                .and_then(|local| self.ref_sources.get(&local).copied())
+                .unwrap_or_else(|| arg.as_ref());
+            let elems = base
+                .projection
+                .iter()
+                .chain(projection.iter())
+                .copied()
+                .collect::<Vec<_>>();
+            sources.push(PlaceRef {
+                local: base.local,
+                projection: self.tcx.mk_place_elems(&elems),
+            });
+        }
+        for source in sources {
+            self.graph.add_copy(dest, source);
+        }
+        true
+    }
+
     /// dest: *const T = Vec::as_ptr(arg: &Vec<T>) =>
     /// arg--|copy|-->dest
     fn process_call_arg_dest(&mut self, arg: PlaceRef<'tcx>, dest: PlaceRef<'tcx>) {
         self.graph.add_copy(dest, arg);
     }
 
+    /// dest = NonNull::new(arg0) or dest = transmute(arg0) =>
+    /// arg0--|copy|-->dest
+    /// dest = NonNull::as_ref(arg0) =>
+    /// arg0--|load|-->dest
+    /// ptr::write(arg0, arg1) =>
+    /// arg1--|store|-->arg0
+    /// dest = ptr::copy_nonoverlapping(arg0, arg1, n) =>
+    /// arg0--|load|-->dest--|store|-->arg1
+    /// where the unit dest stands for the copied pointee.
+    /// The args of constants, e.g., `ptr::null()`, point to nothing.
+    fn process_raw_ptr_passing(
+        &mut self,
+        passing: RawPtrPassing,
+        args: &[Operand<'tcx>],
+        dest: PlaceRef<'tcx>,
+    ) {
+        let arg = |idx: usize| match args.get(idx) {
+            Some(Operand::Move(arg) | Operand::Copy(arg)) => Some(arg.as_ref()),
+            _ => None,
+        };
+        match passing {
+            RawPtrPassing::Copy => {
+                if let Some(arg0) = arg(0) {
+                    if self.int_ptr_casts
+                        || !Self::is_int_ptr_conversion(
+                            arg0.ty(self.body, self.tcx).ty,
+                            dest.ty(self.body, self.tcx).ty,
+                        )
+                    {
+                        self.graph.add_copy(dest, arg0);
+                    }
+                }
+            }
+            RawPtrPassing::Load => {
+                if let Some(arg0) = arg(0) {
+                    self.graph.add_load(dest, arg0);
+                }
+            }
+            RawPtrPassing::Store => {
+                if let (Some(arg0), Some(arg1)) = (arg(0), arg(1)) {
+                    self.graph.add_store(arg0, arg1);
+                }
+            }
+            RawPtrPassing::CopyPointee { src, dst } => {
+                if let (Some(src), Some(dst)) = (arg(src), arg(dst)) {
+                    self.graph.add_load(dest, src);
+                    self.graph.add_store(dst, dest);
+                }
+            }
+        }
+    }
+
+    /// dest: Option<&T> = Option::as_ref(arg: &Option<T>) =>
+    /// arg--|load|-->dest and
+    /// arg--|copy|-->dest
+    /// since the payload of dest is either loaded from or borrowed from *arg.
+    fn process_payload_borrow(&mut self, arg: PlaceRef<'tcx>, dest: PlaceRef<'tcx>) {
+        self.graph.add_load(dest, arg);
+        self.graph.add_copy(dest, arg);
+    }
+
     /// dest: Arc<T> = Arc::clone(arg: &Arc<T>) or dest: T = ptr::read(arg: *const T) =>
     /// arg--|load|-->dest and
     /// arg--|alias_copy|-->dest
@@ -573,13 +1119,25 @@ impl<'a, 'tcx> Visitor<'tcx> for ConstraintGraphCollector<'a, 'tcx> {
         }
     }
 
+    /// For the local fns with return-value summaries, e.g., destination = S::mu(move arg0),
+    /// destination = copy arg0.f (see `process_return_summary`)
+    /// For the fns on raw ptrs, e.g., ptr::write(move arg0, move arg1),
+    /// *arg0 = copy arg1 (see `process_raw_ptr_passing`)
+    /// For the identity-like combinators of `Option` and `Result`, e.g., destination = Result::expect(move arg0, msg),
+    /// destination = copy arg0, or destination = load and copy arg0 for `as_ref` and `as_mut`
+    /// (see `ownership::option_result_combinator`)
     /// For destination = Arc::clone(move arg0) and destination = ptr::read(move arg0),
     /// destination = alias copy args0
     /// For AtomicPtr::store(move args0, move args1, move args2),
     /// args0 = copy args1
+    /// For destination = index(args0, args1) (and the other element accesses, e.g., `<[T]>::get`), destination = Result::unwrap_or_else(move args0, args1),
+    /// destination = MutexGuard::map(move args0, args1), and destination = DashMap::get(move args0, args1),
+    /// destination = copy args0
     /// For other callsites like `destination = call fn(move args0)`,
     /// heuristically assumes that
     /// destination = copy args0
+    /// The same applies to `destination = call fn(copy args0)`,
+    /// e.g., `RwLock::read(_1)` where `_1: &RwLock<T>` is a param passed without reborrowing.
     fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, _location: Location) {
         if let TerminatorKind::Call {
             func,
@@ -588,8 +1146,35 @@ impl<'a, 'tcx> Visitor<'tcx> for ConstraintGraphCollector<'a, 'tcx> {
             ..
         } = &terminator.kind
         {
+            if let (Some(return_summaries), TyKind::FnDef(def_id, substs)) =
+                (self.return_summaries, func.ty(self.body, self.tcx).kind())
+            {
+                if let Some(summary) = return_summaries.get(*def_id, substs) {
+                    if self.process_return_summary(&summary, args, destination.as_ref()) {
+                        return;
+                    }
+                }
+            }
+            if let TyKind::FnDef(def_id, _) = func.ty(self.body, self.tcx).kind() {
+                if let Some(passing) = ownership::raw_ptr_passing(*def_id, self.tcx) {
+                    return self.process_raw_ptr_passing(passing, args, destination.as_ref());
+                }
+            }
+            if let (TyKind::FnDef(def_id, _), Some(Operand::Move(arg) | Operand::Copy(arg))) =
+                (func.ty(self.body, self.tcx).kind(), args.first())
+            {
+                match ownership::option_result_combinator(*def_id, self.tcx) {
+                    Some(PayloadPassing::Move) => {
+                        return self.process_call_arg_dest(arg.as_ref(), destination.as_ref());
+                    }
+                    Some(PayloadPassing::Borrow) => {
+                        return self.process_payload_borrow(arg.as_ref(), destination.as_ref());
+                    }
+                    None => {}
+                }
+            }
             match (args.as_slice(), destination) {
-                (&[Operand::Move(arg)], dest) => {
+                (&[Operand::Move(arg)] | &[Operand::Copy(arg)], dest) => {
                     let func_ty = func.ty(self.body, self.tcx);
                     if let TyKind::FnDef(def_id, substs) = func_ty.kind() {
                         if ownership::is_arc_or_rc_clone(*def_id, substs, self.tcx)
@@ -600,12 +1185,23 @@ impl<'a, 'tcx> Visitor<'tcx> for ConstraintGraphCollector<'a, 'tcx> {
                     }
                     self.process_call_arg_dest(arg.as_ref(), dest.as_ref());
                 }
-                (&[Operand::Move(arg), _], dest) => {
+                (&[Operand::Move(arg) | Operand::Copy(arg), _], dest) => {
                     let func_ty = func.ty(self.body, self.tcx);
                     if let TyKind::FnDef(def_id, _) = func_ty.kind() {
-                        if ownership::is_index(*def_id, self.tcx) {
-                            // index(arg0, arg1)
-                            // e.g., <String as Index<std::ops::Range<usize>>>::index(move _97, move _98)
+                        // index(arg0, arg1) (and the other element accesses)
+                        // e.g., <String as Index<std::ops::Range<usize>>>::index(move _97, move _98)
+                        // or <Vec<Mutex<i32>> as Index<usize>>::index(_1, const 0_usize)
+                        // or unwrap_or_else(arg0, arg1)
+                        // e.g., Result::unwrap_or_else(move _3, PoisonError::into_inner)
+                        // or map(arg0, arg1)
+                        // e.g., MutexGuard::map(move _4, move _5)
+                        // or the keyed accesses of a sharded lock
+                        // e.g., DashMap::get(move _4, move _5)
+                        if ownership::is_element_access(*def_id, self.tcx)
+                            || ownership::is_result_unwrap_or_else(*def_id, self.tcx)
+                            || ownership::is_lockguard_map(*def_id, self.tcx)
+                            || ownership::is_sharded_lock_access(*def_id, self.tcx)
+                        {
                             return self.process_call_arg_dest(arg.as_ref(), dest.as_ref());
                         }
                     }
@@ -636,6 +1232,52 @@ pub enum ApproximateAliasKind {
     Unknown,
 }
 
+/// How an alias result is obtained, from the most to the least precise evidence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AliasProvenance {
+    /// The same local of the same fn.
+    SameLocal,
+    /// The intersection of the points-to sets in the same fn, see `intraproc_alias`.
+    PointsToIntersection,
+    /// The same constant (e.g., a static) pointed to from different fns.
+    SameConstant,
+    /// The upvars of closures aliasing in their def fns.
+    ClosureUpvar,
+    /// Only the params of the same type and projection of different fns, assumed to be possibly aliasing,
+    /// see `point_to_same_type_param`.
+    /// `different_fields` if both are locals loaded from different fields of the params,
+    /// e.g., `&self.reads` and `&self.writes` of type `&RwLock<u64>`.
+    ParamTypeHeuristic { different_fields: bool },
+    /// The points-to sets in the same fn intersecting at a collection whose elements both locals are derived from,
+    /// see `element_index`. `same_index` if both indices are the same constant, e.g., `shards[0]` twice,
+    /// and `constant` if both are constants, e.g., `shards[0]` and `shards[1]`.
+    ElementIndex { same_index: bool, constant: bool },
+    /// The points-to sets of the args passed to the params at the callsites in the same caller,
+    /// see `AliasAnalysis::callsite_alias`.
+    Callsite,
+    /// None of the above in different fns, i.e., unlikely aliasing.
+    Interproc,
+    /// The points-to info is unknown, e.g., of the fns skipped by the budgets.
+    Unknown,
+}
+
+impl AliasProvenance {
+    /// The name in the diagnoses.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AliasProvenance::SameLocal => "same_local",
+            AliasProvenance::PointsToIntersection => "points_to_intersection",
+            AliasProvenance::SameConstant => "same_constant",
+            AliasProvenance::ClosureUpvar => "closure_upvar",
+            AliasProvenance::ParamTypeHeuristic { .. } => "param_type_heuristic",
+            AliasProvenance::ElementIndex { .. } => "element_index",
+            AliasProvenance::Callsite => "callsite",
+            AliasProvenance::Interproc => "interproc",
+            AliasProvenance::Unknown => "unknown",
+        }
+    }
+}
+
 /// Probably > Possibly > Unlikey > Unknown
 impl PartialOrd for ApproximateAliasKind {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
@@ -655,6 +1297,16 @@ impl PartialOrd for ApproximateAliasKind {
     }
 }
 
+impl ApproximateAliasKind {
+    /// Under `--paranoid`, Unknown (e.g., of the fns skipped by the budgets) is treated as Possibly.
+    pub fn or_paranoid(self, paranoid: bool) -> Self {
+        match self {
+            ApproximateAliasKind::Unknown if paranoid => ApproximateAliasKind::Possibly,
+            _ => self,
+        }
+    }
+}
+
 /// `AliasId` identifies a unique memory cell interprocedurally.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct AliasId {
@@ -679,7 +1331,25 @@ impl std::convert::From<LockGuardId> for AliasId {
 pub struct AliasAnalysis<'a, 'tcx> {
     tcx: TyCtxt<'tcx>,
     callgraph: &'a CallGraph<'tcx>,
-    pts: FxHashMap<DefId, PointsToMap<'tcx>>,
+    /// The points-to cache shared by the detectors, with the detector computing the points-to info.
+    pts: FxHashMap<DefId, (PointsToMap<'tcx>, AliasQueryContext)>,
+    context: AliasQueryContext,
+    stats: AliasQueryStats,
+    cache_stats: PointsToCacheStats,
+    budget: AnalysisBudget,
+    /// The fns whose points-to analysis exceeded the budget.
+    skipped: FxHashMap<DefId, BudgetExceeded>,
+    /// The number of threads solving the points-to constraints in `prefetch_pts`.
+    jobs: usize,
+    /// The fns prefetched but not queried yet, whose first query is counted as a miss.
+    prefetched: FxHashSet<DefId>,
+    /// Dump the points-to info of the fns matching `--dump-pointsto`.
+    dump: Option<PointsToDump>,
+    return_summaries: ReturnSummaries<'tcx>,
+    /// See `Andersen::set_int_ptr_casts`.
+    int_ptr_casts: bool,
+    /// See `set_context_sensitive`.
+    context_sensitive: bool,
 }
 
 impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
@@ -688,13 +1358,122 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
             tcx,
             callgraph,
             pts: Default::default(),
+            context: Default::default(),
+            stats: Default::default(),
+            cache_stats: Default::default(),
+            budget: AnalysisBudget::unlimited(),
+            skipped: Default::default(),
+            jobs: 1,
+            prefetched: Default::default(),
+            dump: None,
+            return_summaries: ReturnSummaries::new(tcx),
+            int_ptr_casts: true,
+            context_sensitive: false,
+        }
+    }
+
+    /// Limit the points-to analysis of each fn.
+    pub fn set_budget(&mut self, budget: AnalysisBudget) {
+        self.budget = budget;
+        self.return_summaries.set_budget(budget);
+    }
+
+    /// Whether the casts between integers and pointers are copies, see `Andersen::set_int_ptr_casts`.
+    /// Set it before `reuse_points_to`.
+    pub fn set_int_ptr_casts(&mut self, int_ptr_casts: bool) {
+        self.int_ptr_casts = int_ptr_casts;
+        self.return_summaries.set_int_ptr_casts(int_ptr_casts);
+    }
+
+    /// Evaluate the aliases of the locals only pointing to the pointees of the params per callsite,
+    /// rather than merging all the callers, e.g., the guards of `m.lock()` in `fn with(&self, m: &Mutex<T>)`
+    /// called with different mutexes. It costs the points-to analysis of the callers, see `callsite_alias`.
+    pub fn set_context_sensitive(&mut self, context_sensitive: bool) {
+        self.context_sensitive = context_sensitive;
+    }
+
+    /// Solve the points-to constraints on `jobs` threads in `prefetch_pts`.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs;
+    }
+
+    /// Write the constraint graph and the points-to info of the fns whose def paths contain `pattern`
+    /// to `dir` when their points-to info is first queried, see `dump.rs`.
+    pub fn set_dump_pointsto(&mut self, pattern: &str, dir: PathBuf) {
+        self.dump = Some(PointsToDump::new(pattern, dir));
+    }
+
+    /// The fns whose points-to analysis exceeded the budget.
+    pub fn skipped_functions(&self) -> Vec<SkippedFunction> {
+        self.skipped
+            .iter()
+            .map(|(def_id, exceeded)| exceeded.skipped(self.tcx.def_path_str(*def_id), "points-to"))
+            .collect()
+    }
+
+    /// Check if the points-to analysis of the instance exceeded the budget.
+    fn is_skipped(&mut self, instance: &Instance<'tcx>) -> bool {
+        // The instances out of `--focus` are not analyzed.
+        if !self
+            .callgraph
+            .instance_to_index(instance)
+            .map_or(true, |instance_id| self.callgraph.is_focused(instance_id))
+        {
+            return true;
         }
+        let body = self.tcx.instance_mir(instance.def);
+        self.get_or_insert_pts(instance.def_id(), body);
+        self.skipped.contains_key(&instance.def_id())
+    }
+
+    /// Tag the following queries with the detector issuing them.
+    pub fn set_context(&mut self, context: AliasQueryContext) {
+        self.context = context;
+    }
+
+    pub fn stats(&self) -> &AliasQueryStats {
+        &self.stats
+    }
+
+    /// The hits and misses of the points-to cache by detectors.
+    pub fn cache_stats(&self) -> &PointsToCacheStats {
+        &self.cache_stats
+    }
+
+    /// The counters of the alias queries and the points-to cache.
+    pub fn alias_stats(&self) -> AliasStats {
+        stats::alias_stats(&self.stats, &self.cache_stats)
+    }
+
+    /// Record the number of reports gated on a Possibly result in current context.
+    pub fn record_reports_gated_on_possibly(&mut self, num: usize) {
+        self.stats
+            .record_reports_gated_on_possibly(self.context, num);
     }
 
     /// Check if two memory cells alias with each other.
     /// If they are from the same func, then perform intraproc alias analysis;
     /// otherwise, perform interproc alias analysis.
     pub fn alias(&mut self, aid1: AliasId, aid2: AliasId) -> ApproximateAliasKind {
+        self.alias_with_provenance(aid1, aid2).0
+    }
+
+    /// `alias` with how the result is obtained, e.g., to score the confidence of the reports.
+    pub fn alias_with_provenance(
+        &mut self,
+        aid1: AliasId,
+        aid2: AliasId,
+    ) -> (ApproximateAliasKind, AliasProvenance) {
+        let (kind, provenance) = self.alias_impl(aid1, aid2);
+        self.stats.record_alias(self.context, kind);
+        (kind, provenance)
+    }
+
+    fn alias_impl(
+        &mut self,
+        aid1: AliasId,
+        aid2: AliasId,
+    ) -> (ApproximateAliasKind, AliasProvenance) {
         let AliasId {
             instance_id: id1,
             local: local1,
@@ -714,21 +1493,66 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
             .map(CallGraphNode::instance);
 
         match (instance1, instance2) {
+            (Some(instance1), Some(instance2))
+                if self.is_skipped(instance1) || self.is_skipped(instance2) =>
+            {
+                (ApproximateAliasKind::Unknown, AliasProvenance::Unknown)
+            }
             (Some(instance1), Some(instance2)) => {
+                if self.context_sensitive && (id1, local1) != (id2, local2) {
+                    if let Some(result) = self.callsite_alias(aid1, aid2) {
+                        return result;
+                    }
+                }
                 let node1 = ConstraintNode::Place(Place::from(local1).as_ref());
                 let node2 = ConstraintNode::Place(Place::from(local2).as_ref());
                 if instance1.def_id() == instance2.def_id() {
                     if local1 == local2 {
-                        return ApproximateAliasKind::Probably;
+                        return (ApproximateAliasKind::Probably, AliasProvenance::SameLocal);
+                    }
+                    match self.intraproc_alias(instance1, &node1, &node2) {
+                        Some(ApproximateAliasKind::Probably) => {
+                            let body = self.tcx.instance_mir(instance1.def);
+                            match (
+                                element_index(local1, body, self.tcx),
+                                element_index(local2, body, self.tcx),
+                            ) {
+                                (
+                                    Some(ElementIndex::Constant(index1)),
+                                    Some(ElementIndex::Constant(index2)),
+                                ) => (
+                                    if index1 == index2 {
+                                        ApproximateAliasKind::Probably
+                                    } else {
+                                        ApproximateAliasKind::Possibly
+                                    },
+                                    AliasProvenance::ElementIndex {
+                                        same_index: index1 == index2,
+                                        constant: true,
+                                    },
+                                ),
+                                (Some(_), Some(_)) => (
+                                    ApproximateAliasKind::Possibly,
+                                    AliasProvenance::ElementIndex {
+                                        same_index: false,
+                                        constant: false,
+                                    },
+                                ),
+                                _ => (
+                                    ApproximateAliasKind::Probably,
+                                    AliasProvenance::PointsToIntersection,
+                                ),
+                            }
+                        }
+                        Some(kind) => (kind, AliasProvenance::PointsToIntersection),
+                        None => (ApproximateAliasKind::Unknown, AliasProvenance::Unknown),
                     }
-                    self.intraproc_alias(instance1, &node1, &node2)
-                        .unwrap_or(ApproximateAliasKind::Unknown)
                 } else {
                     self.interproc_alias(instance1, &node1, instance2, &node2)
-                        .unwrap_or(ApproximateAliasKind::Unknown)
+                        .unwrap_or((ApproximateAliasKind::Unknown, AliasProvenance::Unknown))
                 }
             }
-            _ => ApproximateAliasKind::Unknown,
+            _ => (ApproximateAliasKind::Unknown, AliasProvenance::Unknown),
         }
     }
 
@@ -737,6 +1561,12 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
     /// then check alias between each node in pts(`pointer`) and `pointee`.
     /// Choose the highest alias kind.
     pub fn points_to(&mut self, pointer: AliasId, pointee: AliasId) -> ApproximateAliasKind {
+        let kind = self.points_to_impl(pointer, pointee);
+        self.stats.record_points_to(self.context, kind);
+        kind
+    }
+
+    fn points_to_impl(&mut self, pointer: AliasId, pointee: AliasId) -> ApproximateAliasKind {
         let AliasId {
             instance_id: id1,
             local: local1,
@@ -756,6 +1586,11 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
             .map(CallGraphNode::instance);
 
         match (instance1, instance2) {
+            (Some(instance1), Some(instance2))
+                if self.is_skipped(instance1) || self.is_skipped(instance2) =>
+            {
+                ApproximateAliasKind::Unknown
+            }
             (Some(instance1), Some(instance2)) => {
                 let node1 = ConstraintNode::Place(Place::from(local1).as_ref());
                 let node2 = ConstraintNode::Place(Place::from(local2).as_ref());
@@ -810,7 +1645,7 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
         for local_pointee in set {
             let alias_kind = self
                 .interproc_alias(instance1, local_pointee, instance2, &pointee)
-                .unwrap_or(ApproximateAliasKind::Unknown);
+                .map_or(ApproximateAliasKind::Unknown, |(kind, _)| kind);
             if alias_kind > final_alias_kind {
                 final_alias_kind = alias_kind;
             }
@@ -818,20 +1653,92 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
         final_alias_kind
     }
 
+    /// Add the points-to info computed to resolve indirect calls into cache `pts`.
+    /// It is computed with the integer casts as copies, so it is not reused if they are disabled.
+    pub fn reuse_points_to(&mut self, pts: FxHashMap<DefId, PointsToMap<'tcx>>) {
+        if !self.int_ptr_casts {
+            return;
+        }
+        for (def_id, pts) in pts {
+            self.pts
+                .entry(def_id)
+                .or_insert((pts, AliasQueryContext::IndirectCalls));
+        }
+    }
+
     /// Get the points-to info from cache `pts`.
     /// If not exists, then perform points-to analysis
     /// and add the obtained points-to info to cache.
+    /// If the analysis exceeds the budget, the fn is recorded as skipped
+    /// and its points-to info is empty.
     pub fn get_or_insert_pts(&mut self, def_id: DefId, body: &Body<'tcx>) -> &PointsToMap<'tcx> {
-        if self.pts.contains_key(&def_id) {
-            self.pts.get(&def_id).unwrap()
+        if self.prefetched.remove(&def_id) {
+            self.cache_stats.record_miss(self.context);
+        } else if let Some((_, computed_in)) = self.pts.get(&def_id) {
+            self.cache_stats.record_hit(self.context, *computed_in);
         } else {
+            self.cache_stats.record_miss(self.context);
+            let mut pointer_analysis = Andersen::new(body, self.tcx);
+            pointer_analysis.set_return_summaries(&self.return_summaries);
+            pointer_analysis.set_int_ptr_casts(self.int_ptr_casts);
+            let solved = pointer_analysis
+                .analyze_with_budget(self.budget)
+                .map(|()| pointer_analysis.finish());
+            self.insert_solved(def_id, solved);
+        }
+        let pts = &self.pts[&def_id].0;
+        if let Some(dump) = &mut self.dump {
+            dump.dump(
+                def_id,
+                body,
+                pts,
+                &self.return_summaries,
+                self.int_ptr_casts,
+                self.tcx,
+            );
+        }
+        pts
+    }
+
+    /// Compute the points-to info of the instances not in cache `pts` eagerly if `jobs` > 1.
+    /// `TyCtxt` is not `Sync`, so the constraints are collected on this thread
+    /// and only solved on `jobs` threads.
+    /// The prefetch is not counted in `cache_stats`, where the first query of a prefetched fn is a miss
+    /// as if it were computed on demand, so the counters are the same with any `jobs`.
+    pub fn prefetch_pts(&mut self, instances: impl IntoIterator<Item = Instance<'tcx>>) {
+        if self.jobs <= 1 {
+            return;
+        }
+        let mut solvers = Vec::new();
+        let mut seen = FxHashSet::default();
+        for instance in instances {
+            let def_id = instance.def_id();
+            if self.pts.contains_key(&def_id) || !seen.insert(def_id) {
+                continue;
+            }
+            self.prefetched.insert(def_id);
+            let body = self.tcx.instance_mir(instance.def);
             let mut pointer_analysis = Andersen::new(body, self.tcx);
-            pointer_analysis.analyze();
-            let pts = pointer_analysis.finish();
-            self.pts.entry(def_id).or_insert(pts)
+            pointer_analysis.set_return_summaries(&self.return_summaries);
+            pointer_analysis.set_int_ptr_casts(self.int_ptr_casts);
+            match pointer_analysis.solver(self.budget) {
+                Ok(solver) => solvers.push((def_id, solver)),
+                Err(exceeded) => self.insert_solved(def_id, Err(exceeded)),
+            }
+        }
+        for (def_id, solved) in solve_in_parallel(solvers, self.jobs, self.budget) {
+            self.insert_solved(def_id, solved);
         }
     }
 
+    fn insert_solved(&mut self, def_id: DefId, solved: Result<PointsToMap<'tcx>, BudgetExceeded>) {
+        let pts = solved.unwrap_or_else(|exceeded| {
+            self.skipped.insert(def_id, exceeded);
+            PointsToMap::default()
+        });
+        self.pts.insert(def_id, (pts, self.context));
+    }
+
     /// Check alias of p1 and p2 if they are from the same fn.
     /// if pts(p1) intersect pts(p2) != empty then they probably alias else unlikely
     fn intraproc_alias(
@@ -889,7 +1796,8 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
     /// To avoid interproc analysis, we use heuristic assumption:
     /// p1 and p2 alias if:
     /// 1. they point to the same Constant or
-    /// 2. they point to function parameters with the same type and field or
+    /// 2. they point to function parameters with the same type and field
+    ///    (unlikely if the fields of the params they are loaded from are different) or
     /// 3. they point to upvars of closures and the upvars alias in the def func.
     /// Formally,
     /// if exists a1 in pts(p1) and a1 is Constant(c1) and
@@ -902,8 +1810,8 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
     ///    a1.projection = a2.projection)
     /// then return possible alias
     /// else if p1 or p2 are in closures then
-    ///    if p2 points to defsite_upvar(p1) or
-    ///       p1 points to defsite_upvar(p2) or
+    ///    if p2 points to (or points to the same place as) defsite_upvar(p1) or
+    ///       p1 points to (or points to the same place as) defsite_upvar(p2) or
     ///       upvar(p1) alias with upvar(p2)
     ///    then return possible alias
     /// return unlikely
@@ -913,7 +1821,7 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
         node1: &ConstraintNode<'tcx>,
         instance2: &Instance<'tcx>,
         node2: &ConstraintNode<'tcx>,
-    ) -> Option<ApproximateAliasKind> {
+    ) -> Option<(ApproximateAliasKind, AliasProvenance)> {
         let body1 = self.tcx.instance_mir(instance1.def);
         let body2 = self.tcx.instance_mir(instance2.def);
         let points_to_map1 = self.get_or_insert_pts(instance1.def_id(), body1).clone();
@@ -922,11 +1830,22 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
         let pts2 = points_to_map2.get(node2)?;
         // 1. Check if `node1` and `node2` points to the same Constant.
         if point_to_same_constant(pts1, pts2) {
-            return Some(ApproximateAliasKind::Probably);
+            return Some((
+                ApproximateAliasKind::Probably,
+                AliasProvenance::SameConstant,
+            ));
         }
         // 2. Check if `node1` and `node2` points to func parameters with the same local's type and projection.
-        if point_to_same_type_param(pts1, pts2, body1, body2) {
-            return Some(ApproximateAliasKind::Possibly);
+        let param_match =
+            point_to_same_type_param(pts1, pts2, body1, body2, &points_to_map1, &points_to_map2);
+        if param_match == Some(ParamMatch::SameField) {
+            self.stats.record_same_type_param(self.context);
+            return Some((
+                ApproximateAliasKind::Possibly,
+                AliasProvenance::ParamTypeHeuristic {
+                    different_fields: false,
+                },
+            ));
         }
         // 3. Check if `node1` and `node2` point to upvars of closures and the upvars alias in the def func.
         // 3.1 Get defsite upvars of `node1` then check if `node2` points to the upvar.
@@ -942,9 +1861,11 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
                     if def_inst.def_id() == instance2.def_id() {
                         let alias_kind = self
                             .intraproc_points_to(def_inst, node2.clone(), upvar.clone())
+                            .filter(|alias_kind| *alias_kind > ApproximateAliasKind::Unlikely)
+                            .or_else(|| self.upvar_ref_alias(def_inst, node2, upvar))
                             .unwrap_or(ApproximateAliasKind::Unknown);
                         if alias_kind > ApproximateAliasKind::Unlikely {
-                            return Some(alias_kind);
+                            return Some((alias_kind, AliasProvenance::ClosureUpvar));
                         }
                     }
                 }
@@ -967,9 +1888,11 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
                     if def_inst.def_id() == instance1.def_id() {
                         let alias_kind = self
                             .intraproc_points_to(def_inst, node1.clone(), upvar.clone())
+                            .filter(|alias_kind| *alias_kind > ApproximateAliasKind::Unlikely)
+                            .or_else(|| self.upvar_ref_alias(def_inst, node1, upvar))
                             .unwrap_or(ApproximateAliasKind::Unknown);
                         if alias_kind > ApproximateAliasKind::Unlikely {
-                            return Some(alias_kind);
+                            return Some((alias_kind, AliasProvenance::ClosureUpvar));
                         }
                     }
                 }
@@ -988,13 +1911,154 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
                             .intraproc_alias(instance1, &node1, node2)
                             .unwrap_or(ApproximateAliasKind::Unknown);
                         if alias_kind > ApproximateAliasKind::Unlikely {
-                            return Some(alias_kind);
+                            return Some((alias_kind, AliasProvenance::ClosureUpvar));
                         }
                     }
                 }
             }
         }
-        Some(ApproximateAliasKind::Unlikely)
+        // The params of the same type whose different fields are selected, e.g., `self.a` and `s.b` of `S`.
+        if param_match == Some(ParamMatch::DifferentFields) {
+            return Some((
+                ApproximateAliasKind::Unlikely,
+                AliasProvenance::ParamTypeHeuristic {
+                    different_fields: true,
+                },
+            ));
+        }
+        Some((ApproximateAliasKind::Unlikely, AliasProvenance::Interproc))
+    }
+
+    /// 1-callsite context sensitivity (see `set_context_sensitive`).
+    /// A local only pointing to the pointees of the params of its fn is also mapped to the args
+    /// at each direct callsite of the fn (see `callsite_contexts`).
+    /// Then each pair in the same fn with at least one mapped (at the same callsite if both are mapped from the same instance)
+    /// is checked as `intraproc_alias`, e.g., for the guard of `a.lock()` in `f(a, b)` and of `m.lock()` in `with(m)`,
+    /// the guard of `a` and `b` at `with(b)` in `f` are unlikely aliasing while those of `with(a)` are probably aliasing.
+    /// Return None if no such pair or the points-to info of any caller is unknown (e.g., skipped by the budgets),
+    /// in which case the context-insensitive result is used.
+    fn callsite_alias(
+        &mut self,
+        aid1: AliasId,
+        aid2: AliasId,
+    ) -> Option<(ApproximateAliasKind, AliasProvenance)> {
+        let contexts1 = self.callsite_contexts(aid1)?;
+        let contexts2 = self.callsite_contexts(aid2)?;
+        let mut result = None;
+        for context1 in &contexts1 {
+            for context2 in contexts2.iter().filter(|context2| {
+                context2.instance_id == context1.instance_id
+                    && (context1.callsite.is_some() || context2.callsite.is_some())
+                    && (aid1.instance_id != aid2.instance_id
+                        || context2.callsite == context1.callsite)
+            }) {
+                let caller = self
+                    .callgraph
+                    .index_to_instance(context1.instance_id)?
+                    .instance();
+                let body = self.tcx.instance_mir(caller.def);
+                let points_to_map = self.get_or_insert_pts(caller.def_id(), body);
+                // The args only passed to the calls are not in the constraint graph, aliasing with nothing else.
+                let aliasing = context1.nodes.iter().any(|node1| {
+                    context2.nodes.iter().any(|node2| {
+                        node1 == node2
+                            || points_to_map
+                                .get(node1)
+                                .zip(points_to_map.get(node2))
+                                .map_or(false, |(pts1, pts2)| {
+                                    pts1.intersection(pts2).next().is_some()
+                                })
+                    })
+                });
+                if self.skipped.contains_key(&caller.def_id()) {
+                    return None;
+                }
+                result = Some(if aliasing {
+                    ApproximateAliasKind::Probably
+                } else {
+                    result.unwrap_or(ApproximateAliasKind::Unlikely)
+                });
+            }
+        }
+        result.map(|kind| (kind, AliasProvenance::Callsite))
+    }
+
+    /// The contexts of `aid` in `callsite_alias`: itself,
+    /// and the args at the direct callsites of its instance if it only points to the pointees of the params
+    /// (see `pointee_params`). None if the instance has no body.
+    fn callsite_contexts(&mut self, aid: AliasId) -> Option<Vec<CallsiteContext<'tcx>>> {
+        let Some(CallGraphNode::WithBody(instance)) =
+            self.callgraph.index_to_instance(aid.instance_id)
+        else {
+            return None;
+        };
+        let itself = vec![CallsiteContext {
+            instance_id: aid.instance_id,
+            callsite: None,
+            nodes: vec![ConstraintNode::Place(Place::from(aid.local).as_ref())],
+        }];
+        // The args of closures are tupled.
+        if self.tcx.is_closure(instance.def_id()) {
+            return Some(itself);
+        }
+        let body = self.tcx.instance_mir(instance.def);
+        let points_to_map = self.get_or_insert_pts(instance.def_id(), body);
+        let Some(params) = pointee_params(aid.local, body, points_to_map) else {
+            return Some(itself);
+        };
+        let mut contexts = itself.clone();
+        for caller_id in self.callgraph.callers(aid.instance_id) {
+            let Some(CallGraphNode::WithBody(caller)) = self.callgraph.index_to_instance(caller_id)
+            else {
+                return Some(itself);
+            };
+            let caller_body = self.tcx.instance_mir(caller.def);
+            for callsite in self
+                .callgraph
+                .callsites(caller_id, aid.instance_id)
+                .unwrap_or_default()
+            {
+                // The args of the other callsites (e.g., closure args and spawns) are not passed to the params.
+                let CallSiteLocation::Direct(location) = callsite else {
+                    return Some(itself);
+                };
+                let TerminatorKind::Call { args, .. } =
+                    &caller_body[location.block].terminator().kind
+                else {
+                    return Some(itself);
+                };
+                let mut nodes = Vec::new();
+                for param in &params {
+                    match args.get(param.as_usize() - 1) {
+                        Some(Operand::Move(place) | Operand::Copy(place)) => {
+                            nodes.push(ConstraintNode::Place(place.as_ref()));
+                        }
+                        // e.g., a reference to a static
+                        _ => return Some(itself),
+                    }
+                }
+                contexts.push(CallsiteContext {
+                    instance_id: caller_id,
+                    callsite: Some(location),
+                    nodes,
+                });
+            }
+        }
+        Some(contexts)
+    }
+
+    /// A reference captured by the closure (`upvar = &lock`) is possibly aliased with
+    /// the pointer pointing to the same place in the def fn.
+    fn upvar_ref_alias(
+        &mut self,
+        instance: &Instance<'tcx>,
+        pointer: &ConstraintNode<'tcx>,
+        upvar: &ConstraintNode<'tcx>,
+    ) -> Option<ApproximateAliasKind> {
+        match self.intraproc_alias(instance, pointer, upvar)? {
+            ApproximateAliasKind::Probably => Some(ApproximateAliasKind::Possibly),
+            _ => None,
+        }
     }
 
     /// Suppose _1 is the closure parameter and _9 is the arg in the def fn.
@@ -1030,10 +2094,67 @@ impl<'a, 'tcx> AliasAnalysis<'a, 'tcx> {
 
     #[allow(dead_code)]
     pub fn points_to_map(&self, def_id: DefId) -> Option<&PointsToMap<'tcx>> {
-        self.pts.get(&def_id)
+        self.pts.get(&def_id).map(|(pts, _)| pts)
     }
 }
 
+/// A local in its fn or the args it is mapped to at a callsite of its fn, see `AliasAnalysis::callsite_alias`.
+#[derive(Clone)]
+struct CallsiteContext<'tcx> {
+    instance_id: InstanceId,
+    /// The callsite in `instance_id` if mapped.
+    callsite: Option<Location>,
+    nodes: Vec<ConstraintNode<'tcx>>,
+}
+
+/// The params whose pointees are the only places `local` points to besides the allocs of the locals (e.g., of itself),
+/// e.g., `[_2]` for the guard of `m.lock()` in `fn with(&self, m: &Mutex<T>)`.
+/// None if it points to any other place (e.g., a field of a param, a local, or a static) or to no param.
+fn pointee_params<'tcx>(
+    local: Local,
+    body: &Body<'tcx>,
+    points_to_map: &PointsToMap<'tcx>,
+) -> Option<Vec<Local>> {
+    let node = ConstraintNode::Place(Place::from(local).as_ref());
+    let mut params = Vec::new();
+    for pointee in points_to_map.get(&node)? {
+        match pointee {
+            ConstraintNode::Alloc(place) if place.projection.is_empty() => {
+                if is_parameter(place.local, body) && !params.contains(&place.local) {
+                    params.push(place.local);
+                }
+            }
+            _ => return None,
+        }
+    }
+    params.sort();
+    (!params.is_empty()).then_some(params)
+}
+
+/// Solve the constraints of each fn on `jobs` threads, the larger graphs first.
+fn solve_in_parallel(
+    mut solvers: Vec<(DefId, PointsToSolver<'_>)>,
+    jobs: usize,
+    budget: AnalysisBudget,
+) -> Vec<(DefId, Result<PointsToMap<'_>, BudgetExceeded>)> {
+    solvers.sort_by_key(|(_, solver)| std::cmp::Reverse(solver.graph.graph.node_count()));
+    let queue = Mutex::new(solvers.into_iter());
+    let solved = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap().next();
+                let Some((def_id, solver)) = next else {
+                    break;
+                };
+                let pts = solver.solve(budget);
+                solved.lock().unwrap().push((def_id, pts));
+            });
+        }
+    });
+    solved.into_inner().unwrap()
+}
+
 /// Check if p1 and p2 point to the same Constant.
 /// Return true
 /// if exists a1 in pts(p1) and a1 is Constant(c1) and
@@ -1046,10 +2167,131 @@ fn point_to_same_constant<'tcx>(
     let mut constants1 = pts1
         .iter()
         .filter(|node| matches!(node, &ConstraintNode::ConstantDeref(_)));
-    let mut constants2 = pts2
+    let constants2 = pts2
         .iter()
         .filter(|node| matches!(node, &ConstraintNode::ConstantDeref(_)));
-    constants1.any(|c1| constants2.any(|c2| c2 == c1))
+    constants1.any(|c1| constants2.clone().any(|c2| c2 == c1))
+}
+
+/// The index of an element of a collection, see `element_index`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ElementIndex {
+    Constant(u64),
+    Unknown,
+}
+
+/// The max number of assignments followed back by `element_index`.
+const ELEMENT_INDEX_MAX_DEPTH: usize = 16;
+
+/// The index of the collection element that `local` is derived from, e.g., `Constant(0)` for the guard of `shards[0].lock()`.
+/// It follows the locals assigned only once back through the copies, the borrows, and the first args of the calls
+/// (the same as `dest = copy arg0` in `ConstraintGraphCollector`),
+/// until an element access, i.e., a place projected by an index or a call like `Index::index(arg0, i)`.
+fn element_index<'tcx>(local: Local, body: &Body<'tcx>, tcx: TyCtxt<'tcx>) -> Option<ElementIndex> {
+    let mut local = local;
+    for _ in 0..ELEMENT_INDEX_MAX_DEPTH {
+        let place = match unique_assignment(local, body)? {
+            Assignment::Rvalue(
+                Rvalue::Use(Operand::Move(place) | Operand::Copy(place))
+                | Rvalue::Cast(_, Operand::Move(place) | Operand::Copy(place), _)
+                | Rvalue::Ref(_, _, place)
+                | Rvalue::AddressOf(_, place)
+                | Rvalue::CopyForDeref(place),
+            ) => *place,
+            Assignment::Call(func, args) => {
+                if let TyKind::FnDef(def_id, _) = func.ty(body, tcx).kind() {
+                    if ownership::is_element_access(*def_id, tcx) {
+                        return Some(operand_index(args.get(1)?, body, tcx));
+                    }
+                }
+                match args.first()? {
+                    Operand::Move(place) | Operand::Copy(place) => *place,
+                    Operand::Constant(_) => return None,
+                }
+            }
+            Assignment::Rvalue(_) => return None,
+        };
+        let index = place.projection.iter().rev().find_map(|elem| match elem {
+            ProjectionElem::Index(index) => Some(local_index(index, body, tcx)),
+            ProjectionElem::ConstantIndex {
+                offset,
+                from_end: false,
+                ..
+            } => Some(ElementIndex::Constant(offset)),
+            ProjectionElem::ConstantIndex { from_end: true, .. } => Some(ElementIndex::Unknown),
+            _ => None,
+        });
+        if index.is_some() {
+            return index;
+        }
+        local = place.local;
+    }
+    None
+}
+
+/// The index given by an operand, e.g., `const 0_usize`.
+fn operand_index<'tcx>(
+    operand: &Operand<'tcx>,
+    body: &Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> ElementIndex {
+    match operand {
+        Operand::Constant(constant) => constant
+            .const_
+            .try_eval_target_usize(tcx, ParamEnv::reveal_all())
+            .map_or(ElementIndex::Unknown, ElementIndex::Constant),
+        Operand::Move(place) | Operand::Copy(place) => match place.as_local() {
+            Some(local) => local_index(local, body, tcx),
+            None => ElementIndex::Unknown,
+        },
+    }
+}
+
+/// The index in a local assigned a constant only once, e.g., `_4 = const 0_usize` for `shards[_4]`.
+fn local_index<'tcx>(local: Local, body: &Body<'tcx>, tcx: TyCtxt<'tcx>) -> ElementIndex {
+    match unique_assignment(local, body) {
+        Some(Assignment::Rvalue(Rvalue::Use(operand @ Operand::Constant(_)))) => {
+            operand_index(operand, body, tcx)
+        }
+        _ => ElementIndex::Unknown,
+    }
+}
+
+/// How a local is assigned.
+enum Assignment<'a, 'tcx> {
+    Rvalue(&'a Rvalue<'tcx>),
+    /// `local = func(args)`
+    Call(&'a Operand<'tcx>, &'a [Operand<'tcx>]),
+}
+
+/// The assignment of a local assigned only once (as a whole).
+fn unique_assignment<'a, 'tcx>(local: Local, body: &'a Body<'tcx>) -> Option<Assignment<'a, 'tcx>> {
+    let mut assignments = body.basic_blocks.iter().flat_map(|data| {
+        let statements = data
+            .statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Assign(box (place, rvalue)) if place.local == local => {
+                    Some(place.as_local().map(|_| Assignment::Rvalue(rvalue)))
+                }
+                _ => None,
+            });
+        let call = match &data.terminator().kind {
+            TerminatorKind::Call {
+                func,
+                args,
+                destination,
+                ..
+            } if destination.local == local => {
+                Some(destination.as_local().map(|_| Assignment::Call(func, args)))
+            }
+            _ => None,
+        };
+        statements.chain(call)
+    });
+    // None if a projection of the local is assigned.
+    let assignment = assignments.next()??;
+    assignments.next().is_none().then_some(assignment)
 }
 
 /// Check if `local` is a parameter
@@ -1058,31 +2300,136 @@ fn is_parameter(local: Local, body: &Body<'_>) -> bool {
     body.args_iter().any(|arg| arg == local)
 }
 
+/// How the params pointed to by two pointers match, see `point_to_same_type_param`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamMatch {
+    /// Possibly the same field of the params of the same type.
+    SameField,
+    /// Different fields of the same ADT selected from the params of the same type.
+    DifferentFields,
+}
+
 /// Check if p1 and p2 point to func parameters with the same local's type and projection.
-/// Return true
+/// Return Some
 /// if exists a1 in pts(p1) and a1.local is param and
 ///    exists a2 in pts(p2) and a2.local is param and
 ///    a1.local.ty = a2.local.ty and
 ///    a1.projection = a2.projection
+/// A local loaded from the fields of a param is regarded as a param as well,
+/// e.g., `_7 = deref_copy ((*_1).0)` in `fn next(&mut self)` of an iterator `Drain { queue: &Queue }`,
+/// so that `(*_7).0` matches `(*_1).0` in `fn f(&self)` of `Queue`.
+/// Then the full projection paths from the params (see `param_paths`) are compared:
+/// Return Some(DifferentFields) if all the matches select different fields of the same ADT,
+/// e.g., `(*_3)` with `_3 = &((*_1).0)` and `(*_2)` with `_2 = &((*_1).1)` in two fns of `&self`.
 fn point_to_same_type_param<'tcx>(
     pts1: &FxHashSet<ConstraintNode<'tcx>>,
     pts2: &FxHashSet<ConstraintNode<'tcx>>,
     body1: &Body<'tcx>,
     body2: &Body<'tcx>,
-) -> bool {
-    let mut parameter_places1 = pts1.iter().filter_map(|node| match node {
-        ConstraintNode::Alloc(place) if is_parameter(place.local, body1) => Some(*place),
-        _ => None,
-    });
-    let mut parameter_places2 = pts2.iter().filter_map(|node| match node {
-        ConstraintNode::Alloc(place) if is_parameter(place.local, body2) => Some(*place),
-        _ => None,
-    });
-    parameter_places1.any(|place1| {
-        parameter_places2.any(|place2| {
+    points_to_map1: &PointsToMap<'tcx>,
+    points_to_map2: &PointsToMap<'tcx>,
+) -> Option<ParamMatch> {
+    let parameter_places2 = parameter_places(pts2, body2, points_to_map2).collect::<Vec<_>>();
+    let mut matched = None;
+    for place1 in parameter_places(pts1, body1, points_to_map1) {
+        for place2 in parameter_places2.iter().filter(|place2| {
             body1.local_decls[place1.local].ty == body2.local_decls[place2.local].ty
                 && place1.projection == place2.projection
+        }) {
+            let paths1 = param_paths(place1, body1, points_to_map1);
+            let paths2 = param_paths(*place2, body2, points_to_map2);
+            let same_ty_paths = paths1
+                .iter()
+                .flat_map(|(ty1, path1)| {
+                    paths2
+                        .iter()
+                        .filter(move |(ty2, _)| ty1 == ty2)
+                        .map(move |(_, path2)| (path1, path2))
+                })
+                .collect::<Vec<_>>();
+            if same_ty_paths.is_empty()
+                || !same_ty_paths
+                    .iter()
+                    .all(|(path1, path2)| select_different_fields(path1, path2))
+            {
+                return Some(ParamMatch::SameField);
+            }
+            matched = Some(ParamMatch::DifferentFields);
+        }
+    }
+    matched
+}
+
+/// The full projection paths from the params to the place, with the types of the params,
+/// e.g., `[Deref, Field(0), Deref]` from `_1: &S` for `(*_3)` with `_3 = &((*_1).0)`.
+/// A local loaded from several fields of the params has several paths.
+fn param_paths<'tcx>(
+    place: PlaceRef<'tcx>,
+    body: &Body<'tcx>,
+    points_to_map: &PointsToMap<'tcx>,
+) -> Vec<(Ty<'tcx>, Vec<PlaceElem<'tcx>>)> {
+    if is_parameter(place.local, body) {
+        return vec![(body.local_decls[place.local].ty, place.projection.to_vec())];
+    }
+    let node = ConstraintNode::Place(Place::from(place.local).as_ref());
+    points_to_map
+        .get(&node)
+        .into_iter()
+        .flatten()
+        .filter_map(|node| match node {
+            ConstraintNode::Alloc(field)
+                if !field.projection.is_empty() && is_parameter(field.local, body) =>
+            {
+                let path = field
+                    .projection
+                    .iter()
+                    .chain(place.projection)
+                    .copied()
+                    .collect();
+                Some((body.local_decls[field.local].ty, path))
+            }
+            _ => None,
         })
+        .collect()
+}
+
+/// Check if two projection paths from the params of the same type select different fields of the same ADT,
+/// i.e., they first diverge at the fields of different indices.
+fn select_different_fields<'tcx>(path1: &[PlaceElem<'tcx>], path2: &[PlaceElem<'tcx>]) -> bool {
+    path1
+        .iter()
+        .zip(path2)
+        .find(|(elem1, elem2)| elem1 != elem2)
+        .map_or(false, |(elem1, elem2)| {
+            matches!(
+                (elem1, elem2),
+                (ProjectionElem::Field(field1, _), ProjectionElem::Field(field2, _)) if field1 != field2
+            )
+        })
+}
+
+/// The places in `pts` rooted at a param or at a local loaded from the fields of a param.
+fn parameter_places<'a, 'tcx>(
+    pts: &'a FxHashSet<ConstraintNode<'tcx>>,
+    body: &'a Body<'tcx>,
+    points_to_map: &'a PointsToMap<'tcx>,
+) -> impl Iterator<Item = PlaceRef<'tcx>> + 'a {
+    let is_param_field = |local: Local| {
+        let node = ConstraintNode::Place(Place::from(local).as_ref());
+        points_to_map.get(&node).map_or(false, |pts| {
+            pts.iter().any(|node| {
+                matches!(node, ConstraintNode::Alloc(place)
+                    if !place.projection.is_empty() && is_parameter(place.local, body))
+            })
+        })
+    };
+    pts.iter().filter_map(move |node| match node {
+        ConstraintNode::Alloc(place)
+            if is_parameter(place.local, body) || is_param_field(place.local) =>
+        {
+            Some(*place)
+        }
+        _ => None,
     })
 }
 
@@ -1126,10 +2473,10 @@ type PointsToPath<'tcx> = Vec<(&'tcx [PlaceElem<'tcx>], ConstraintNode<'tcx>)>;
 /// Due to the above reason, pts(_8) does not contain _1.1 and fails to be identified as an upvar.
 /// Thus we need to track the pts-to paths from the given node to the parameter.
 /// If there exists such a path, then the node is an upvar.
-fn points_to_paths_to_param<'a, 'tcx>(
+fn points_to_paths_to_param<'tcx>(
     node: ConstraintNode<'tcx>,
     body: &'tcx Body<'tcx>,
-    points_to_map: &'a PointsToMap<'tcx>,
+    points_to_map: &PointsToMap<'tcx>,
 ) -> Vec<PointsToPath<'tcx>> {
     let mut result = Vec::new();
     let mut path = Vec::new();
@@ -1147,11 +2494,11 @@ fn points_to_paths_to_param<'a, 'tcx>(
 }
 
 /// DFS search for points-to paths from `node` to the parameter.
-fn dfs_paths_recur<'a, 'tcx>(
+fn dfs_paths_recur<'tcx>(
     prev_proj: &'tcx [PlaceElem<'tcx>],
     node: ConstraintNode<'tcx>,
     body: &'tcx Body<'tcx>,
-    points_to_map: &'a PointsToMap<'tcx>,
+    points_to_map: &PointsToMap<'tcx>,
     visited: &mut FxHashSet<ConstraintNode<'tcx>>,
     path: &mut PointsToPath<'tcx>,
     result: &mut Vec<PointsToPath<'tcx>>,
@@ -1199,3 +2546,534 @@ fn dfs_paths_recur<'a, 'tcx>(
     }
     path.pop();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{with_tcx, write_src};
+    use stats::PointsToCacheCounters;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    /// The bodies of the fns and closures in the local crate.
+    fn local_bodies(tcx: TyCtxt<'_>) -> Vec<&Body<'_>> {
+        tcx.mir_keys(())
+            .iter()
+            .filter(|def_id| tcx.def_kind(def_id.to_def_id()).is_fn_like())
+            .map(|def_id| tcx.optimized_mir(def_id.to_def_id()))
+            .collect()
+    }
+
+    /// The sorted points-to sets in Debug format and the time to analyze the body.
+    fn analyze<'tcx>(
+        body: &Body<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        collapse_cycles: bool,
+    ) -> (Vec<String>, Duration) {
+        let start = Instant::now();
+        let mut andersen = Andersen::new(body, tcx);
+        andersen.collapse_cycles = collapse_cycles;
+        andersen.analyze();
+        let pts = andersen.finish();
+        let elapsed = start.elapsed();
+        let mut entries = pts
+            .iter()
+            .map(|(node, pts)| {
+                let mut pts = pts
+                    .iter()
+                    .map(|pte| format!("{:?}", pte))
+                    .collect::<Vec<_>>();
+                pts.sort();
+                format!("{:?}: {:?}", node, pts)
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+        (entries, elapsed)
+    }
+
+    /// Build the dependencies of toys/inter and return the rustc args to link them.
+    fn build_inter_deps() -> Vec<String> {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let target_dir = std::env::temp_dir().join("lockbud-test-inter");
+        let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+            .args(["build", "--offline", "--quiet", "--manifest-path"])
+            .arg(manifest_dir.join("toys/inter/Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let deps_dir = target_dir.join("debug/deps");
+        let mut args = vec![
+            "--edition=2018".to_owned(),
+            "-L".to_owned(),
+            format!("dependency={}", deps_dir.display()),
+        ];
+        for dep in ["parking_lot", "spin"] {
+            let rlib: PathBuf = std::fs::read_dir(&deps_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .find(|path| {
+                    let name = path.file_name().unwrap().to_str().unwrap();
+                    name.starts_with(&format!("lib{}-", dep)) && name.ends_with(".rlib")
+                })
+                .unwrap();
+            args.push("--extern".to_owned());
+            args.push(format!("{}={}", dep, rlib.display()));
+        }
+        args
+    }
+
+    /// The std locks of toys/inter: the guards held across the calls relocking them, and a recursion.
+    /// The `parking_lot` and `spin` locks of the toy are left out, since their crates may not be available offline,
+    /// so the toy itself is only checked by the ignored `test_collapse_cycles_same_pts_on_inter_toy`.
+    const INTER_SRC: &str = r#"use std::sync::{Arc, Mutex, RwLock};
+
+pub struct Foo {
+    mu: Arc<Mutex<i32>>,
+    rw: RwLock<i32>,
+}
+
+impl Foo {
+    pub fn new() -> Self {
+        Self {
+            mu: Arc::new(Mutex::new(1)),
+            rw: RwLock::new(1),
+        }
+    }
+
+    pub fn mutex_1(&self) {
+        match *self.mu.lock().unwrap() {
+            1 => self.recur(),
+            _ => self.mutex_2(),
+        };
+    }
+
+    fn recur(&self) {
+        self.mutex_1();
+    }
+
+    fn mutex_2(&self) {
+        *self.mu.lock().unwrap() += 1;
+    }
+
+    pub fn rwlock_read_1(&self) {
+        match *self.rw.read().unwrap() {
+            1 => self.rwlock_write_2(),
+            _ => self.rwlock_read_2(),
+        };
+    }
+
+    pub fn rwlock_write_1(&self) {
+        match *self.rw.write().unwrap() {
+            1 => self.rwlock_write_2(),
+            _ => self.rwlock_read_2(),
+        };
+    }
+
+    fn rwlock_read_2(&self) {
+        let _ = *self.rw.read().unwrap();
+    }
+
+    fn rwlock_write_2(&self) {
+        *self.rw.write().unwrap() += 1;
+    }
+}
+"#;
+
+    fn assert_collapse_cycles_same_pts(src: &Path, extra_args: &[String]) {
+        with_tcx(
+            src,
+            extra_args,
+            Box::new(|tcx| {
+                let bodies = local_bodies(tcx);
+                assert!(!bodies.is_empty());
+                for body in bodies {
+                    assert_eq!(
+                        analyze(body, tcx, false).0,
+                        analyze(body, tcx, true).0,
+                        "{:?}",
+                        body.source.def_id()
+                    );
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_collapse_cycles_same_pts_on_inter() {
+        assert_collapse_cycles_same_pts(&write_src("inter", INTER_SRC), &[]);
+    }
+
+    /// The same on toys/inter itself, which needs its dependencies in the local cargo registry.
+    #[test]
+    #[ignore]
+    fn test_collapse_cycles_same_pts_on_inter_toy() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("toys/inter/src/main.rs");
+        assert_collapse_cycles_same_pts(&src, &build_inter_deps());
+    }
+
+    /// Generate a fn with `refs` refs on a cycle of copies, i.e., `r0 = r1; r1 = r2; ...; rn = r0;`,
+    /// and `ptrs` refs on a cycle of copies through raw ptrs, i.e., `*q0 = s1; *q1 = s2; ...`,
+    /// which is found only after solving the stores.
+    fn synthetic_fn(name: &str, refs: usize, ptrs: usize) -> String {
+        let pointees = 50;
+        let mut src = format!("pub fn {}() {{\n", name);
+        for i in 0..pointees {
+            src += &format!("let a{i} = {i}i32;\n");
+        }
+        for i in 0..refs {
+            src += &format!("let mut r{i}: &i32 = &a{};\n", i % pointees);
+        }
+        for i in 0..refs {
+            src += &format!("r{} = r{};\n", i, (i + 1) % refs);
+        }
+        for i in 0..ptrs {
+            src += &format!("let mut s{i}: &i32 = &a{};\n", i % pointees);
+            src += &format!("let q{i}: *mut &i32 = std::ptr::addr_of_mut!(s{i});\n");
+        }
+        for i in 0..ptrs {
+            src += &format!("unsafe {{ *q{} = s{}; }}\n", i, (i + 1) % ptrs);
+        }
+        src + "}\n"
+    }
+
+    fn num_statements(body: &Body<'_>) -> usize {
+        body.basic_blocks
+            .iter()
+            .map(|bb_data| bb_data.statements.len())
+            .sum()
+    }
+
+    #[test]
+    fn test_collapse_cycles_on_synthetic_body() {
+        let src = write_src(
+            "synthetic",
+            &(synthetic_fn("small", 100, 150) + &synthetic_fn("large", 1000, 200)),
+        );
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                for body in local_bodies(tcx) {
+                    if tcx.item_name(body.source.def_id()).as_str() == "small" {
+                        assert_eq!(analyze(body, tcx, false).0, analyze(body, tcx, true).0);
+                    } else {
+                        assert!(num_statements(body) >= 5000);
+                        // Without cycle elimination, it takes minutes.
+                        let (_, elapsed) = analyze(body, tcx, true);
+                        assert!(elapsed < Duration::from_secs(10), "{:?}", elapsed);
+                    }
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_budget_exceeded() {
+        let src = write_src("budget", &synthetic_fn("small", 100, 150));
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let body = local_bodies(tcx)[0];
+                let def_id = body.source.def_id();
+                let mut andersen = Andersen::new(body, tcx);
+                let nodes = AnalysisBudget {
+                    time: Duration::ZERO,
+                    max_nodes: 10,
+                };
+                assert!(matches!(
+                    andersen.analyze_with_budget(nodes),
+                    Err(BudgetExceeded::Nodes { max_nodes: 10, .. })
+                ));
+                let mut andersen = Andersen::new(body, tcx);
+                let time = AnalysisBudget {
+                    time: Duration::from_nanos(1),
+                    max_nodes: 0,
+                };
+                assert_eq!(
+                    andersen.analyze_with_budget(time),
+                    Err(BudgetExceeded::Time {
+                        time: Duration::from_nanos(1)
+                    })
+                );
+                // The skipped fn has no points-to info and is listed.
+                let callgraph = CallGraph::new();
+                let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+                alias_analysis.set_budget(nodes);
+                assert!(alias_analysis.get_or_insert_pts(def_id, body).is_empty());
+                let skipped = alias_analysis.skipped_functions();
+                assert_eq!(skipped.len(), 1);
+                assert_eq!(skipped[0].def_path, "small");
+                assert_eq!(skipped[0].analysis, "points-to");
+                assert!(skipped[0]
+                    .reason
+                    .ends_with("constraint graph nodes exceeded the budget of 10"));
+            }),
+        );
+    }
+
+    fn cache_counters(alias_analysis: &AliasAnalysis<'_, '_>) -> PointsToCacheCounters {
+        alias_analysis
+            .cache_stats()
+            .counters(AliasQueryContext::default())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The references to the same static alias across the fns, while the statics of the same type do not.
+    #[test]
+    fn test_static_alias() {
+        let src = write_src(
+            "static-alias",
+            r#"
+use std::sync::Mutex;
+static FIRST: Mutex<i32> = Mutex::new(0);
+static SECOND: Mutex<i32> = Mutex::new(0);
+pub fn first() { *FIRST.lock().unwrap() += 1; }
+pub fn first_again() { *FIRST.lock().unwrap() += 2; }
+pub fn second() { *SECOND.lock().unwrap() += 1; }
+fn main() {}
+"#,
+        );
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let instances = local_bodies(tcx)
+                    .iter()
+                    .map(|body| Instance::mono(tcx, body.source.def_id()))
+                    .collect::<Vec<_>>();
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(
+                    instances.clone(),
+                    tcx,
+                    rustc_middle::ty::ParamEnv::reveal_all(),
+                    &crate::progress::Progress::new(true),
+                );
+                // The local assigned the reference to the static in each fn.
+                let static_ref = |name: &str| {
+                    let instance = instances
+                        .iter()
+                        .find(|instance| tcx.def_path_str(instance.def_id()) == name)
+                        .unwrap();
+                    let body = tcx.instance_mir(instance.def);
+                    let local = body
+                        .basic_blocks
+                        .iter()
+                        .flat_map(|data| &data.statements)
+                        .find_map(|stmt| match &stmt.kind {
+                            StatementKind::Assign(box (
+                                place,
+                                Rvalue::Use(Operand::Constant(constant)),
+                            )) if constant.check_static_ptr(tcx).is_some() => Some(place.local),
+                            _ => None,
+                        })
+                        .unwrap();
+                    AliasId {
+                        instance_id: callgraph.instance_to_index(instance).unwrap(),
+                        local,
+                    }
+                };
+                let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+                assert_eq!(
+                    alias_analysis.alias(static_ref("first"), static_ref("first_again")),
+                    ApproximateAliasKind::Probably
+                );
+                assert_eq!(
+                    alias_analysis.alias(static_ref("first"), static_ref("second")),
+                    ApproximateAliasKind::Unlikely
+                );
+            }),
+        );
+    }
+
+    #[test]
+    fn test_prefetch_pts_in_parallel() {
+        let src = write_src(
+            "prefetch",
+            &(0..6)
+                .map(|i| synthetic_fn(&format!("f{}", i), 50 * (i + 1), 20))
+                .collect::<String>(),
+        );
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let bodies = local_bodies(tcx);
+                let instances = bodies
+                    .iter()
+                    .map(|body| Instance::mono(tcx, body.source.def_id()))
+                    .collect::<Vec<_>>();
+                let callgraph = CallGraph::new();
+                let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+                alias_analysis.set_jobs(3);
+                alias_analysis.prefetch_pts(instances.iter().copied());
+                assert!(alias_analysis
+                    .cache_stats()
+                    .counters(Default::default())
+                    .is_none());
+                for body in &bodies {
+                    let mut andersen = Andersen::new(body, tcx);
+                    andersen.analyze();
+                    let pts = alias_analysis.get_or_insert_pts(body.source.def_id(), body);
+                    assert_eq!(*pts, andersen.finish());
+                }
+                // The first queries of the prefetched fns are misses as with `jobs` = 1.
+                let counters = cache_counters(&alias_analysis);
+                assert_eq!((counters.hits, counters.misses), (0, 6));
+                for body in &bodies {
+                    alias_analysis.get_or_insert_pts(body.source.def_id(), body);
+                }
+                let counters = cache_counters(&alias_analysis);
+                assert_eq!((counters.hits, counters.misses), (6, 6));
+            }),
+        );
+    }
+
+    /// The guards aliasing through the combinators of `Option` and `Result` in the same fn.
+    #[test]
+    fn test_option_result_combinators() {
+        let src = write_src(
+            "option-result-combinators",
+            r#"
+use std::sync::{Mutex, MutexGuard, PoisonError};
+pub fn question_mark() -> Result<(), PoisonError<MutexGuard<'static, i32>>> {
+    let m = Box::leak(Box::new(Mutex::new(1)));
+    let first = m.lock()?;
+    drop(first);
+    let second = m.lock().map_err(|e| e)?;
+    Ok(())
+}
+pub fn expect_ok() {
+    let m = Mutex::new(1);
+    let first = m.lock().expect("poisoned");
+    drop(first);
+    let second = m.lock().ok().unwrap();
+}
+pub fn option_as_ref() {
+    let mut m = Some(Mutex::new(1));
+    let first = m.as_ref().unwrap().lock().unwrap();
+    drop(first);
+    let second = m.as_mut().expect("some").lock().unwrap();
+}
+pub fn different() {
+    let m = Mutex::new(1);
+    let n = Mutex::new(2);
+    let first = m.lock().expect("poisoned");
+    let second = n.lock().expect("poisoned");
+}
+"#,
+        );
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let instances = crate::test_utils::test_instances(tcx);
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(
+                    instances.clone(),
+                    tcx,
+                    rustc_middle::ty::ParamEnv::reveal_all(),
+                    &crate::progress::Progress::new(true),
+                );
+                let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+                // The alias of the guards `first` and `second` in the fn.
+                let mut guards_alias = |name: &str| {
+                    let instance = instances
+                        .iter()
+                        .find(|instance| tcx.def_path_str(instance.def_id()) == name)
+                        .unwrap();
+                    let body = tcx.instance_mir(instance.def);
+                    let var = |name: &str| {
+                        body.var_debug_info
+                            .iter()
+                            .find_map(|info| match info.value {
+                                rustc_middle::mir::VarDebugInfoContents::Place(place)
+                                    if info.name.as_str() == name =>
+                                {
+                                    Some(place.local)
+                                }
+                                _ => None,
+                            })
+                            .unwrap()
+                    };
+                    let instance_id = callgraph.instance_to_index(instance).unwrap();
+                    alias_analysis.alias(
+                        AliasId {
+                            instance_id,
+                            local: var("first"),
+                        },
+                        AliasId {
+                            instance_id,
+                            local: var("second"),
+                        },
+                    )
+                };
+                assert_eq!(
+                    guards_alias("question_mark"),
+                    ApproximateAliasKind::Probably
+                );
+                assert_eq!(guards_alias("expect_ok"), ApproximateAliasKind::Probably);
+                assert_eq!(
+                    guards_alias("option_as_ref"),
+                    ApproximateAliasKind::Probably
+                );
+                assert_eq!(guards_alias("different"), ApproximateAliasKind::Unlikely);
+            }),
+        );
+    }
+
+    /// The pointers through the integer round-trips alias unless disabled.
+    #[test]
+    fn test_int_ptr_casts() {
+        let src = write_src(
+            "int-ptr-casts",
+            r#"
+pub fn roundtrip() -> usize {
+    let x = String::new();
+    let addr = &x as *const String as usize;
+    let p = addr as *const String;
+    let q: *const String = unsafe { std::mem::transmute(addr) };
+    unsafe { (*p).len() + (*q).len() }
+}
+"#,
+        );
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let body = local_bodies(tcx)[0];
+                let var = |name: &str| {
+                    body.var_debug_info
+                        .iter()
+                        .find_map(|info| match info.value {
+                            rustc_middle::mir::VarDebugInfoContents::Place(place)
+                                if info.name.as_str() == name =>
+                            {
+                                Some(place.local)
+                            }
+                            _ => None,
+                        })
+                        .unwrap()
+                };
+                let x = ConstraintNode::Place(Place::from(var("x")).as_ref());
+                for int_ptr_casts in [true, false] {
+                    let mut andersen = Andersen::new(body, tcx);
+                    andersen.set_int_ptr_casts(int_ptr_casts);
+                    andersen.analyze();
+                    let pts = andersen.finish();
+                    for ptr in ["p", "q"] {
+                        let ptr = ConstraintNode::Place(Place::from(var(ptr)).as_ref());
+                        assert_eq!(
+                            pts.get(&ptr).map_or(false, |pts| pts.contains(&x)),
+                            int_ptr_casts
+                        );
+                    }
+                }
+            }),
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Statistics on alias queries.
+//! Count the results of `alias` and `points_to` queries,
+//! the interproc queries resolved by the "same parameter type" heuristic,
+//! and the reports gated on a Possibly result,
+//! broken down by the detector issuing the queries.
+//! Also count the hits and misses of the points-to cache shared by the detectors,
+//! where a cross-detector hit reuses the points-to info computed for another detector.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+pub use lockbud_report_types::{
+    AliasKindCounter, AliasQueryContext, AliasQueryCounters, AliasStats, PointsToCacheCounters,
+};
+
+use super::ApproximateAliasKind;
+
+fn record(counter: &mut AliasKindCounter, kind: ApproximateAliasKind) {
+    match kind {
+        ApproximateAliasKind::Probably => counter.probably += 1,
+        ApproximateAliasKind::Possibly => counter.possibly += 1,
+        ApproximateAliasKind::Unlikely => counter.unlikely += 1,
+        ApproximateAliasKind::Unknown => counter.unknown += 1,
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AliasQueryStats(BTreeMap<AliasQueryContext, AliasQueryCounters>);
+
+impl AliasQueryStats {
+    #[allow(dead_code)]
+    pub fn counters(&self, context: AliasQueryContext) -> Option<&AliasQueryCounters> {
+        self.0.get(&context)
+    }
+
+    fn counters_mut(&mut self, context: AliasQueryContext) -> &mut AliasQueryCounters {
+        self.0.entry(context).or_default()
+    }
+
+    pub fn record_alias(&mut self, context: AliasQueryContext, kind: ApproximateAliasKind) {
+        record(&mut self.counters_mut(context).alias, kind);
+    }
+
+    pub fn record_points_to(&mut self, context: AliasQueryContext, kind: ApproximateAliasKind) {
+        record(&mut self.counters_mut(context).points_to, kind);
+    }
+
+    pub fn record_same_type_param(&mut self, context: AliasQueryContext) {
+        self.counters_mut(context).same_type_param += 1;
+    }
+
+    pub fn record_reports_gated_on_possibly(&mut self, context: AliasQueryContext, num: usize) {
+        self.counters_mut(context).reports_gated_on_possibly += num;
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PointsToCacheStats(BTreeMap<AliasQueryContext, PointsToCacheCounters>);
+
+impl PointsToCacheStats {
+    #[allow(dead_code)]
+    pub fn counters(&self, context: AliasQueryContext) -> Option<&PointsToCacheCounters> {
+        self.0.get(&context)
+    }
+
+    /// Record a hit on the points-to info computed in `computed_in`.
+    pub fn record_hit(&mut self, context: AliasQueryContext, computed_in: AliasQueryContext) {
+        let counters = self.0.entry(context).or_default();
+        counters.hits += 1;
+        if computed_in != context {
+            counters.cross_detector_hits += 1;
+        }
+    }
+
+    pub fn record_miss(&mut self, context: AliasQueryContext) {
+        self.0.entry(context).or_default().misses += 1;
+    }
+
+    /// The total number of cross-detector hits.
+    #[allow(dead_code)]
+    pub fn cross_detector_hits(&self) -> usize {
+        self.0
+            .values()
+            .map(|counters| counters.cross_detector_hits)
+            .sum()
+    }
+}
+
+/// The counters of both for the report envelope.
+pub fn alias_stats(queries: &AliasQueryStats, cache: &PointsToCacheStats) -> AliasStats {
+    AliasStats {
+        queries: queries.0.clone(),
+        points_to_cache: cache.0.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_query_stats_by_context() {
+        let mut stats = AliasQueryStats::default();
+        stats.record_alias(AliasQueryContext::Deadlock, ApproximateAliasKind::Probably);
+        stats.record_alias(AliasQueryContext::Deadlock, ApproximateAliasKind::Possibly);
+        stats.record_alias(AliasQueryContext::Deadlock, ApproximateAliasKind::Unlikely);
+        stats.record_same_type_param(AliasQueryContext::Deadlock);
+        stats.record_reports_gated_on_possibly(AliasQueryContext::Deadlock, 1);
+        stats.record_points_to(
+            AliasQueryContext::UseAfterFree,
+            ApproximateAliasKind::Unknown,
+        );
+        let deadlock = stats.counters(AliasQueryContext::Deadlock).unwrap();
+        assert_eq!(deadlock.alias.total(), 3);
+        assert_eq!(deadlock.alias.possibly, 1);
+        assert_eq!(deadlock.points_to.total(), 0);
+        assert_eq!(deadlock.same_type_param, 1);
+        assert_eq!(deadlock.reports_gated_on_possibly, 1);
+        let uaf = stats.counters(AliasQueryContext::UseAfterFree).unwrap();
+        assert_eq!(uaf.alias.total(), 0);
+        assert_eq!(uaf.points_to.unknown, 1);
+        assert!(stats.counters(AliasQueryContext::InvalidFree).is_none());
+    }
+
+    #[test]
+    fn test_alias_query_stats_serialize() {
+        let mut stats = AliasQueryStats::default();
+        stats.record_alias(AliasQueryContext::Deadlock, ApproximateAliasKind::Probably);
+        assert_eq!(
+            serde_json::to_string(&stats).unwrap(),
+            r#"{"Deadlock":{"alias":{"probably":1,"possibly":0,"unlikely":0,"unknown":0},"points_to":{"probably":0,"possibly":0,"unlikely":0,"unknown":0},"same_type_param":0,"reports_gated_on_possibly":0}}"#
+        );
+    }
+
+    #[test]
+    fn test_points_to_cache_stats() {
+        let mut stats = PointsToCacheStats::default();
+        stats.record_miss(AliasQueryContext::Deadlock);
+        stats.record_hit(AliasQueryContext::Deadlock, AliasQueryContext::Deadlock);
+        stats.record_hit(
+            AliasQueryContext::AtomicityViolation,
+            AliasQueryContext::Deadlock,
+        );
+        stats.record_miss(AliasQueryContext::AtomicityViolation);
+        assert_eq!(
+            stats.counters(AliasQueryContext::Deadlock),
+            Some(&PointsToCacheCounters {
+                hits: 1,
+                cross_detector_hits: 0,
+                misses: 1
+            })
+        );
+        assert_eq!(
+            stats.counters(AliasQueryContext::AtomicityViolation),
+            Some(&PointsToCacheCounters {
+                hits: 1,
+                cross_detector_hits: 1,
+                misses: 1
+            })
+        );
+        assert_eq!(stats.cross_detector_hits(), 1);
+        assert_eq!(
+            serde_json::to_string(&stats).unwrap(),
+            r#"{"Deadlock":{"hits":1,"cross_detector_hits":0,"misses":1},"AtomicityViolation":{"hits":1,"cross_detector_hits":1,"misses":1}}"#
+        );
+    }
+}
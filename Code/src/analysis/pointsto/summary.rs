@@ -0,0 +1,99 @@
+//! Return-value summaries of the local fns with MIR.
+//! By default a call `dest = f(arg0, ..)` is assumed to copy `arg0` into `dest`,
+//! which loses the fields for getters like `fn mu(&self) -> &Mutex<T> { &self.0 }`,
+//! where `dest` should point to `arg0.0` rather than to `arg0`.
+//! The summary of a callee is the places rooted at its params that its return value points to,
+//! computed by the callee's own `Andersen` analysis without summaries,
+//! i.e., only one level of callees is summarized to avoid the fixpoint across the whole program.
+extern crate rustc_hash;
+extern crate rustc_hir;
+
+use std::cell::RefCell;
+
+use rustc_hash::FxHashMap;
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Place, PlaceElem};
+use rustc_middle::ty::{GenericArgsRef, Instance, InstanceDef, ParamEnv, TyCtxt};
+
+use super::{is_parameter, Andersen, ConstraintNode};
+use crate::analysis::budget::AnalysisBudget;
+
+/// The places rooted at the params that the return value points to,
+/// as the indices of the args (from 0) and the projections,
+/// e.g., `[(0, [Field(0)])]` for `fn mu(&self) -> &Mutex<T> { &self.0 }`.
+pub type ReturnSummary<'tcx> = Vec<(usize, &'tcx [PlaceElem<'tcx>])>;
+
+/// The cache of the return-value summaries by the callees.
+pub struct ReturnSummaries<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    /// The budget of the points-to analysis of each callee, without summary if exceeded.
+    budget: AnalysisBudget,
+    /// The constraints are collected on one thread, so a `RefCell` is enough.
+    summaries: RefCell<FxHashMap<DefId, Option<ReturnSummary<'tcx>>>>,
+    /// See `Andersen::set_int_ptr_casts`.
+    int_ptr_casts: bool,
+}
+
+impl<'tcx> ReturnSummaries<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
+        Self {
+            tcx,
+            budget: AnalysisBudget::unlimited(),
+            summaries: Default::default(),
+            int_ptr_casts: true,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: AnalysisBudget) {
+        self.budget = budget;
+    }
+
+    pub fn set_int_ptr_casts(&mut self, int_ptr_casts: bool) {
+        self.int_ptr_casts = int_ptr_casts;
+    }
+
+    /// The summary of the callee `def_id` with `args` if it resolves to a local fn with MIR
+    /// whose return value points into the fields of its params.
+    pub fn get(&self, def_id: DefId, args: GenericArgsRef<'tcx>) -> Option<ReturnSummary<'tcx>> {
+        let instance = Instance::resolve(self.tcx, ParamEnv::reveal_all(), def_id, args)
+            .ok()
+            .flatten()?;
+        let def_id = instance.def_id();
+        if !matches!(instance.def, InstanceDef::Item(_))
+            || !def_id.is_local()
+            || !self.tcx.is_mir_available(def_id)
+        {
+            return None;
+        }
+        if let Some(summary) = self.summaries.borrow().get(&def_id) {
+            return summary.clone();
+        }
+        let summary = self.summarize(instance);
+        self.summaries.borrow_mut().insert(def_id, summary.clone());
+        summary
+    }
+
+    /// The params' places in pts(_0) of the callee.
+    /// Only the summaries with fields are kept, others are no better than copying `arg0`.
+    fn summarize(&self, instance: Instance<'tcx>) -> Option<ReturnSummary<'tcx>> {
+        let body = self.tcx.instance_mir(instance.def);
+        let mut andersen = Andersen::new(body, self.tcx);
+        andersen.set_int_ptr_casts(self.int_ptr_casts);
+        andersen.analyze_with_budget(self.budget).ok()?;
+        let pts = andersen.finish();
+        let summary = pts
+            .get(&ConstraintNode::Place(Place::return_place().as_ref()))?
+            .iter()
+            .filter_map(|node| match node {
+                ConstraintNode::Alloc(place) if is_parameter(place.local, body) => {
+                    Some((place.local.as_usize() - 1, place.projection))
+                }
+                _ => None,
+            })
+            .collect::<ReturnSummary<'tcx>>();
+        summary
+            .iter()
+            .any(|(_, projection)| !projection.is_empty())
+            .then_some(summary)
+    }
+}
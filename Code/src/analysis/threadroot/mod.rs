@@ -0,0 +1,182 @@
+//! Thread-provenance analysis.
+//! Find the threads (denoted by their roots) that may execute each instance in CallGraph.
+//! A thread root is either the crate entry point `main`,
+//! or a spawn site where a closure (or future) is passed to a spawn API,
+//! e.g., `std::thread::spawn`, `rayon::spawn`, `tokio::spawn`, i.e., a Spawn callsite in CallGraph.
+//! Instances reachable from a root without going through another spawn are executed by the thread of the root,
+//! e.g., the closure passed to `std::panic::catch_unwind`, which is not a spawn API.
+//! Instances not reachable from any root (e.g., pub fns in a lib) have no roots,
+//! which means they may be executed by any thread.
+extern crate rustc_hash;
+
+use std::collections::VecDeque;
+
+use petgraph::visit::{EdgeRef, IntoNodeReferences};
+use petgraph::Direction::Outgoing;
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_middle::mir::Location;
+use rustc_middle::ty::TyCtxt;
+
+use crate::analysis::callgraph::{CallGraph, CallSiteLocation, InstanceId};
+use crate::interest::concurrency::thread::SpawnApi;
+
+/// The root of a thread.
+/// `Spawn(caller, location)` denotes the spawn API is called in caller at location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThreadRoot {
+    Main,
+    Spawn(InstanceId, Location),
+}
+
+/// Maps each instance to the roots of the threads that may execute it.
+#[derive(Debug, Default)]
+pub struct ThreadRoots {
+    roots: FxHashMap<InstanceId, FxHashSet<ThreadRoot>>,
+}
+
+impl ThreadRoots {
+    pub fn analyze<'tcx>(callgraph: &CallGraph<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
+        let spawn_apis = callgraph
+            .graph
+            .node_references()
+            .filter(|(_, node)| SpawnApi::from_instance(node.instance(), tcx).is_some())
+            .map(|(instance_id, _)| instance_id)
+            .collect::<FxHashSet<_>>();
+        // entry instance -> thread root
+        let mut entries: Vec<(InstanceId, ThreadRoot)> = callgraph
+            .graph
+            .edge_references()
+            .flat_map(|edge| {
+                edge.weight()
+                    .iter()
+                    .filter_map(move |callsite| match callsite {
+                        CallSiteLocation::Spawn(loc) => {
+                            Some((edge.target(), ThreadRoot::Spawn(edge.source(), *loc)))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect();
+        if let Some((main_def_id, _)) = tcx.entry_fn(()) {
+            if let Some((main, _)) = callgraph
+                .graph
+                .node_references()
+                .find(|(_, node)| node.instance().def_id() == main_def_id)
+            {
+                entries.push((main, ThreadRoot::Main));
+            }
+        }
+        // Do not propagate into spawn APIs or the spawned instances,
+        // which are executed by the spawned threads.
+        let boundaries = entries
+            .iter()
+            .map(|(entry, _)| *entry)
+            .chain(spawn_apis)
+            .collect::<FxHashSet<_>>();
+        let mut roots: FxHashMap<InstanceId, FxHashSet<ThreadRoot>> = FxHashMap::default();
+        for (entry, root) in entries {
+            let mut visited = FxHashSet::default();
+            visited.insert(entry);
+            let mut worklist = VecDeque::from([entry]);
+            while let Some(id) = worklist.pop_front() {
+                roots.entry(id).or_default().insert(root);
+                for callee in callgraph.graph.neighbors_directed(id, Outgoing) {
+                    if !boundaries.contains(&callee) && visited.insert(callee) {
+                        worklist.push_back(callee);
+                    }
+                }
+            }
+        }
+        Self { roots }
+    }
+
+    /// The roots of the threads that may execute the instance.
+    pub fn roots(&self, instance_id: InstanceId) -> Option<&FxHashSet<ThreadRoot>> {
+        self.roots.get(&instance_id)
+    }
+
+    /// The instances executed by each root, i.e., the inverse of `roots`.
+    pub fn instances_by_root(&self) -> FxHashMap<ThreadRoot, Vec<InstanceId>> {
+        let mut instances: FxHashMap<ThreadRoot, Vec<InstanceId>> = FxHashMap::default();
+        for (instance_id, roots) in &self.roots {
+            for root in roots {
+                instances.entry(*root).or_default().push(*instance_id);
+            }
+        }
+        instances
+    }
+
+    /// Two instances are provably executed sequentially in the same thread
+    /// if both of them are only reachable from the same single root.
+    /// Note that a spawn site in a loop is still regarded as one thread.
+    pub fn is_same_single_thread(&self, a: InstanceId, b: InstanceId) -> bool {
+        match (self.roots(a), self.roots(b)) {
+            (Some(a), Some(b)) => a.len() == 1 && a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Progress;
+    use crate::test_utils::{test_instances, with_tcx, write_src};
+    use rustc_middle::ty::ParamEnv;
+
+    const SPAWN_SRC: &str = r#"use std::thread;
+fn worker() {}
+fn scoped_worker() {}
+fn sequential() {}
+fn main() {
+    sequential();
+    thread::spawn(worker).join().unwrap();
+    thread::spawn(|| sequential()).join().unwrap();
+    thread::scope(|s| {
+        s.spawn(scoped_worker);
+    });
+}
+"#;
+
+    #[test]
+    fn test_thread_roots() {
+        let src = write_src("thread-roots", SPAWN_SRC);
+        with_tcx(
+            &src,
+            &["--crate-type=bin".to_owned()],
+            Box::new(|tcx| {
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(
+                    test_instances(tcx),
+                    tcx,
+                    ParamEnv::reveal_all(),
+                    &Progress::new(true),
+                );
+                let thread_roots = ThreadRoots::analyze(&callgraph, tcx);
+                let roots = |name: &str| {
+                    let (instance_id, _) = callgraph
+                        .graph
+                        .node_references()
+                        .find(|(_, node)| tcx.def_path_str(node.instance().def_id()) == name)
+                        .unwrap();
+                    let mut roots = thread_roots
+                        .roots(instance_id)
+                        .into_iter()
+                        .flatten()
+                        .map(|root| match root {
+                            ThreadRoot::Main => 0,
+                            ThreadRoot::Spawn(..) => 1,
+                        })
+                        .collect::<Vec<_>>();
+                    roots.sort_unstable();
+                    roots
+                };
+                // The fn items spawned by `thread::spawn` and `Scope::spawn` run in the spawned threads only.
+                assert_eq!(roots("main"), [0]);
+                assert_eq!(roots("worker"), [1]);
+                assert_eq!(roots("scoped_worker"), [1]);
+                assert_eq!(roots("sequential"), [0, 1]);
+            }),
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Accept the existing reports in a baseline file so that only the new ones are emitted.
+//! A baseline file is a JSON object of the fingerprints of the accepted reports
+//! and the `AnalysisMetadata` of the analysis first writing it (see `Baseline`),
+//! written by `--write-baseline` and read by `--baseline`.
+//! The fingerprint of a report hashes its kind, lock types, and spans (see `Report::compute_fingerprint`),
+//! where each span is located as `{crate}::{def path of the enclosing fn}+{line offset in the fn}`,
+//! so that the edits outside the fn, e.g., moving the fn, keep the fingerprint.
+extern crate rustc_data_structures;
+extern crate rustc_hir;
+
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use rustc_data_structures::flock::Lock;
+use rustc_data_structures::fx::FxHashMap;
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_middle::ty::TyCtxt;
+
+use crate::detector::report::{AnalysisMetadata, Baseline, Report};
+use crate::report_file::lock_path;
+
+/// Locate the lines in the local crate relative to the enclosing fns.
+pub struct SpanLocator {
+    crate_name: String,
+    /// file -> (first line, last line, def path) of the fns and closures in it
+    fns: FxHashMap<String, Vec<(usize, usize, String)>>,
+}
+
+impl SpanLocator {
+    pub fn new(tcx: TyCtxt<'_>) -> Self {
+        let source_map = tcx.sess.source_map();
+        let mut fns: FxHashMap<String, Vec<_>> = FxHashMap::default();
+        for def_id in tcx.hir().body_owners() {
+            if !tcx.def_kind(def_id).is_fn_like() {
+                continue;
+            }
+            let span = tcx.source_span(def_id);
+            let lo = source_map.lookup_char_pos(span.lo());
+            let hi = source_map.lookup_char_pos(span.hi());
+            fns.entry(lo.file.name.prefer_remapped().to_string())
+                .or_default()
+                .push((lo.line, hi.line, tcx.def_path_str(def_id.to_def_id())));
+        }
+        Self {
+            crate_name: tcx.crate_name(LOCAL_CRATE).to_string(),
+            fns,
+        }
+    }
+
+    /// `{crate}::{def path}+{offset}` of the innermost fn containing the line,
+    /// or `{file}:{line}` if no fn in the local crate contains it.
+    pub fn locate(&self, file: &str, line: usize) -> String {
+        self.fns
+            .get(file)
+            .and_then(|fns| {
+                fns.iter()
+                    .filter(|(first, last, _)| *first <= line && line <= *last)
+                    .min_by_key(|(first, last, _)| last - first)
+            })
+            .map_or_else(
+                || format!("{}:{}", file, line),
+                |(first, _, def_path)| {
+                    format!("{}::{}+{}", self.crate_name, def_path, line - first)
+                },
+            )
+    }
+}
+
+/// Set the fingerprints of the reports of the local crate.
+pub fn assign_fingerprints(tcx: TyCtxt<'_>, reports: &mut [Report]) {
+    let locator = SpanLocator::new(tcx);
+    for report in reports.iter_mut() {
+        let fingerprint = report.compute_fingerprint(|file, line| locator.locate(file, line));
+        report.set_fingerprint(fingerprint);
+    }
+}
+
+/// Read the fingerprints in the baseline file.
+pub fn read_baseline(path: &Path) -> Result<Baseline, Box<dyn Error>> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(io::BufReader::new(file))?)
+}
+
+/// Add the fingerprints to the baseline file, creating it with `metadata` if not exists.
+/// cargo may run lockbud on crates in parallel, so the file is updated under the lock on `{path}.lock`.
+pub fn extend_baseline<'a>(
+    path: &Path,
+    metadata: &AnalysisMetadata,
+    fingerprints: impl IntoIterator<Item = &'a str>,
+) -> io::Result<()> {
+    let _lock = Lock::new(&lock_path(path), true, true, true)?;
+    let mut baseline: Baseline = match File::open(path) {
+        Ok(file) => serde_json::from_reader(io::BufReader::new(file))?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Baseline::default(),
+        Err(err) => return Err(err),
+    };
+    // Kept from the first analysis writing the baseline.
+    baseline.metadata.get_or_insert_with(|| metadata.clone());
+    baseline.fingerprints.extend(
+        fingerprints
+            .into_iter()
+            .filter(|fingerprint| !fingerprint.is_empty())
+            .map(str::to_owned),
+    );
+    let j = serde_json::to_string_pretty(&baseline)?;
+    std::fs::write(path, j)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extend_baseline() {
+        let dir = std::env::temp_dir().join("lockbud-test-baseline");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("baseline.json");
+        let metadata = |analyzed_at: &str| AnalysisMetadata {
+            lockbud_version: "0.1.0".to_owned(),
+            analyzed_at: analyzed_at.to_owned(),
+            git_commit: None,
+            options_hash: "0123abcd".to_owned(),
+        };
+        extend_baseline(&path, &metadata("2024-01-31T12:00:00Z"), ["b", "a"]).unwrap();
+        extend_baseline(&path, &metadata("2024-01-31T12:00:05Z"), ["c", "", "a"]).unwrap();
+        let baseline = read_baseline(&path).unwrap();
+        assert_eq!(
+            baseline.fingerprints.into_iter().collect::<Vec<_>>(),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(baseline.metadata, Some(metadata("2024-01-31T12:00:00Z")));
+        // The baselines of older lockbud are extended with the metadata.
+        std::fs::write(&path, r#"["a"]"#).unwrap();
+        extend_baseline(&path, &metadata("2024-02-01T12:00:00Z"), ["b"]).unwrap();
+        let baseline = read_baseline(&path).unwrap();
+        assert_eq!(baseline.fingerprints.len(), 2);
+        assert_eq!(baseline.metadata, Some(metadata("2024-02-01T12:00:00Z")));
+        std::fs::write(&path, "{}").unwrap();
+        assert!(read_baseline(&path).is_err());
+        assert!(extend_baseline(&path, &metadata("2024-02-01T12:00:00Z"), ["a"]).is_err());
+    }
+}
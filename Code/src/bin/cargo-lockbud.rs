@@ -1,30 +1,151 @@
-//! `cargo lockbud $FLAGS $ARGS` calls `cargo build` with RUSTC_WRAPPER set to `lockbud`.
-//! The flags are passed to `lockbud` through env var `LOCKBUD_FLAGS`.
-//! The remainining args are unchanged.
+//! `cargo lockbud $FLAGS -- $ARGS` calls `cargo build $ARGS` with RUSTC_WRAPPER set to `lockbud`,
+//! found next to `cargo-lockbud` if installed together, or else in PATH.
+//! The flags are passed to `lockbud` through env var `LOCKBUD_FLAGS`,
+//! except the cargo flags selecting the packages, targets, and features (see `CARGO_FLAGS`),
+//! which are passed to `cargo build` like the args after `--`.
+//! cargo only runs lockbud on the crates it rebuilds, so the envelope of each analyzed crate is cached
+//! in `LOCKBUD_CACHE_DIR`, i.e., `{target dir}/lockbud/{hash of the flags and the cargo args}`,
+//! and the crates not rebuilt are counted (and written to `--output`) from their cached envelopes after the build,
+//! while cargo replays the warnings, i.e., the reports printed by lockbud, of the crates not rebuilt.
 //! To re-run `cargo lockbud` with different flags on the same crate, please `cargo clean` first.
+//! `--output {path}` is turned into `--report-path` with the absolute path,
+//! so that the reports of all the crates are written to the same file wherever rustc runs.
+//! Likewise for the paths of `--baseline` and `--write-baseline`,
+//! and the baseline file of `--write-baseline` is regenerated from scratch.
+//! lockbud records the crates with deny-level reports in `LOCKBUD_DENIED_PATH` rather than failing their builds,
+//! and `cargo lockbud` exits with 42 if the build succeeds and any crate is recorded.
+//! After the build, `cargo lockbud` prints the total numbers of reports of each detector
+//! in the envelopes of the analyzed and the cached crates.
+//! For tracking the reports over time, lockbud records the `AnalysisMetadata` in the envelopes and the baseline,
+//! with the git commit of the workspace (`GIT_COMMIT`, or else `git rev-parse HEAD`) passed in `LOCKBUD_GIT_COMMIT`,
+//! and the hash of the flags and the args changing the reports (like the cache dir) passed in `LOCKBUD_OPTIONS_HASH`.
+//! `cargo lockbud setup` installs the nightly toolchain pinned by lockbud's `rust-toolchain.toml`
+//! with the components needed by `rustc_private`, which `cargo lockbud` offers to run if the toolchain is missing.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::ffi::OsString;
+use std::hash::{Hash, Hasher};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::SystemTime;
+
+use lockbud_report_types::{AnalysisMetadata, Baseline, Level, ReportEnvelope, Summary};
 
 const CARGO_LOCKBUD_HELP: &str = r#"Statically detect bugs on MIR
 Usage:
-    cargo lockbud [options] [<cargo options>...] [--] [<program/test suite options>...]
+    cargo lockbud [options] [<cargo options>...] [-- <cargo build options>...]
+    cargo lockbud setup      Install the nightly toolchain required by lockbud
 Common options:
     -h, --help               Print this message
     -V, --version            Print version info and exit
     -k, --detector-kind      Choose detector, deadlock
+        --detectors          Enable or (prefixed by -) disable single detectors, e.g., doublelock,-conflictlock
+        --set                Set a parameter of a detector, e.g., deadlock.max_callchain_depth=5
     -b, --blacklist-mode     Use crate-name-list as blacklist, whitelist if not specified
     -l, --crate-name-list    Will not white-or-black list the crates if not specified.
+        --all-crates         Also analyze the dependencies, only the workspace packages (or the ones of -p) by default
+        --no-thread-filter   Report conflictlock even if the locks are only acquired in the same thread
+        --alias-stats        Print statistics on alias queries
+        --analyze-deps-guards  Also collect lockguards in the fns from dependencies
+        --analyze-proc-macros  Also analyze the proc-macro crates, skipped like the build scripts by default
+    -q, --quiet              Do not print progress
+        --max-callchains     The maximal number of callchains in each deadlock diagnosis, 8 by default
+        --max-callchain-depth  The maximal number of calls in each callchain, 20 by default
+        --fn-time-budget     The time budget in seconds of analyzing each fn, 10 by default, 0 for unlimited
+        --fn-node-budget     The maximal number of constraint graph nodes of each fn, 50000 by default, 0 for unlimited
+        --jobs               The number of threads solving the points-to constraints, 1 by default
+        --max-reports        The maximal number of reports of each crate, 0 (unlimited) by default
+        --dropped-reports-dir  The dir to write the reports dropped by --max-reports
+        --output             Write the reports to the file (one JSON envelope per crate and line) rather than stderr
+        --baseline           Suppress the reports whose fingerprints are in the JSON baseline file
+        --write-baseline     Write the fingerprints of the reports to the JSON baseline file
+        --group-by           Group the reports by lock, i.e., the alias classes of their primary locks
+        --message-format     Print the reports as json or diagnostic (rustc warnings), diagnostic on a terminal by default
+        --deny               Deny the reports of a kind (e.g., double_lock) or at least of a possibility (probably or possibly),
+                             making cargo lockbud exit with 42 after the build
+        --paranoid           Report the bugs with unknown alias results (e.g., of the skipped fns) as possibly, for audits
+        --config             The JSON config file, e.g., declaring custom thread pools
+        --guard-newtype      Declare a guard newtype as path=Family, e.g., facade::sync::Locked=ParkingLotMutex
+        --panic-include-tests  Also report the panic sites in #[test] fns and #[cfg(test)] modules
+        --panic-kinds        Only report the panic sites of the kinds, e.g., unwrap,expect
+Cargo options:
+        --workspace          Analyze all the packages in the workspace
+    -p, --package            Analyze the package, may be repeated
+        --tests              Also analyze the tests, e.g., the deadlocks in #[test] fns
+        --benches            Also analyze the benches
+        --examples           Also analyze the examples
+        --all-features       Activate all the features
     
-Other [options] are the same as `cargo build`. Everything after the second "--" verbatim
-to the program.
+The other options after "--" are passed to `cargo build`, e.g., --features or --target.
 Examples:
     # only detect [mycrate1, mycrate2]
     cargo lockbud -k deadlock -l mycrate1,mycrate2
     # skip detecting [mycrate1, mycrate2]
     cargo lockbud -k deadlock -b -l mycrate1,mycrate2
+    # detect all the packages in the workspace, including their tests
+    cargo lockbud -k deadlock --workspace --tests
 "#;
 
+/// The exit code when any deny-level report is emitted, the same as lockbud.
+const EXIT_DENIED: i32 = 42;
+
+/// The toolchain file of lockbud, pinning the nightly required by `rustc_private`.
+const RUST_TOOLCHAIN: &str = include_str!("../../rust-toolchain.toml");
+
+/// The components needed to run lockbud, besides the ones of the minimal profile.
+const REQUIRED_COMPONENTS: [&str; 2] = ["rustc-dev", "llvm-tools-preview"];
+
+/// The flags of `cargo build` selecting the packages, targets, and features, passed to cargo.
+const CARGO_FLAGS: [&str; 5] = [
+    "--workspace",
+    "--tests",
+    "--benches",
+    "--examples",
+    "--all-features",
+];
+
+/// The args of `cargo lockbud`, split for lockbud and cargo.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Args {
+    /// The flags of lockbud except the paths below.
+    flags: Vec<String>,
+    /// The args of `cargo build`.
+    cargo_args: Vec<String>,
+    output: Option<String>,
+    baseline: Option<String>,
+    write_baseline: Option<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                break;
+            }
+            if let Some(path) = flag_value("--output", &arg, &mut args) {
+                parsed.output = Some(path);
+            } else if let Some(path) = flag_value("--baseline", &arg, &mut args) {
+                parsed.baseline = Some(path);
+            } else if let Some(path) = flag_value("--write-baseline", &arg, &mut args) {
+                parsed.write_baseline = Some(path);
+            } else if let Some(package) = flag_value("--package", &arg, &mut args)
+                .or_else(|| flag_value("-p", &arg, &mut args))
+            {
+                parsed.cargo_args.push(format!("--package={}", package));
+            } else if CARGO_FLAGS.contains(&arg.as_str()) {
+                parsed.cargo_args.push(arg);
+            } else {
+                parsed.flags.push(arg);
+            }
+        }
+        parsed.cargo_args.extend(args);
+        parsed
+    }
+}
+
 fn show_help() {
     println!("{}", CARGO_LOCKBUD_HELP);
 }
@@ -43,33 +164,518 @@ fn has_arg_flag(name: &str) -> bool {
     args.any(|val| val == name)
 }
 
+/// The lockbud installed next to `cargo-lockbud` (e.g., both in `~/.cargo/bin`), or else `lockbud` in PATH.
+fn lockbud_wrapper() -> OsString {
+    env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(format!("lockbud{}", env::consts::EXE_SUFFIX)))
+        .filter(|path| path.is_file())
+        .map_or_else(|| OsString::from("lockbud"), PathBuf::into_os_string)
+}
+
+/// The `channel` of a `rust-toolchain.toml`, e.g., `nightly-2023-10-19`.
+fn toolchain_channel(content: &str) -> Option<&str> {
+    content.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "channel").then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// The pinned toolchain of lockbud.
+fn pinned_toolchain() -> &'static str {
+    toolchain_channel(RUST_TOOLCHAIN).expect("no channel in rust-toolchain.toml")
+}
+
+fn has_rustup() -> bool {
+    Command::new("rustup")
+        .arg("--version")
+        .output()
+        .map_or(false, |output| output.status.success())
+}
+
+/// The sysroot of the toolchain if installed with the required components.
+fn toolchain_sysroot(toolchain: &str) -> Option<PathBuf> {
+    let installed = Command::new("rustup")
+        .args(["component", "list", "--installed", "--toolchain", toolchain])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let installed = String::from_utf8_lossy(&installed.stdout);
+    // Listed with the target, and `llvm-tools-preview` is listed as `llvm-tools` by the recent rustup.
+    let has_component = |component: &str| {
+        let prefix = format!("{}-", component.trim_end_matches("-preview"));
+        installed.lines().any(|line| line.starts_with(&prefix))
+    };
+    if !REQUIRED_COMPONENTS
+        .iter()
+        .all(|component| has_component(component))
+    {
+        return None;
+    }
+    let sysroot = Command::new("rustup")
+        .args(["run", toolchain, "rustc", "--print", "sysroot"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let sysroot = PathBuf::from(String::from_utf8_lossy(&sysroot.stdout).trim());
+    sysroot.is_dir().then_some(sysroot)
+}
+
+/// Install the pinned toolchain with the required components, exiting with 1 on failure.
+fn setup() {
+    let toolchain = pinned_toolchain();
+    if !has_rustup() {
+        eprintln!(
+            "lockbud: rustup is not found, please install the toolchain {} with the components {} manually, \
+             and set RUST_SYSROOT to its sysroot when building lockbud",
+            toolchain,
+            REQUIRED_COMPONENTS.join(", ")
+        );
+        std::process::exit(1);
+    }
+    let status = Command::new("rustup")
+        .args([
+            "toolchain",
+            "install",
+            toolchain,
+            "--profile",
+            "minimal",
+            "--no-self-update",
+        ])
+        .arg("--component")
+        .arg(REQUIRED_COMPONENTS.join(","))
+        .status()
+        .expect("could not run rustup");
+    if !status.success() {
+        eprintln!("lockbud: failed to install the toolchain {}", toolchain);
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    match toolchain_sysroot(toolchain) {
+        Some(sysroot) => eprintln!(
+            "lockbud: the toolchain {} is installed at {}",
+            toolchain,
+            sysroot.display()
+        ),
+        None => {
+            eprintln!(
+                "lockbud: the sysroot of the toolchain {} is not found after the installation, \
+                 please check `rustup component list --toolchain {}`, or set RUST_SYSROOT",
+                toolchain, toolchain
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Offer to run `setup` if the pinned toolchain is missing, on a terminal,
+/// or exit with 1 and a hint otherwise.
+/// Without rustup, the toolchain is assumed to be provided by RUST_SYSROOT.
+fn ensure_toolchain() {
+    let toolchain = pinned_toolchain();
+    if !has_rustup() || toolchain_sysroot(toolchain).is_some() {
+        return;
+    }
+    eprintln!(
+        "lockbud: the toolchain {} with the components {} is required but not installed",
+        toolchain,
+        REQUIRED_COMPONENTS.join(", ")
+    );
+    if std::io::stdin().is_terminal() {
+        eprint!("lockbud: run `cargo lockbud setup` to install it now? [y/N] ");
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).is_ok()
+            && matches!(answer.trim(), "y" | "Y" | "yes")
+        {
+            setup();
+            return;
+        }
+    }
+    eprintln!("lockbud: please run `cargo lockbud setup` first");
+    std::process::exit(1);
+}
+
 fn in_cargo_lockbud() {
     // Now we run `cargo build $FLAGS $ARGS`, giving the user the
     // change to add additional arguments. `FLAGS` is set to identify
     // this target. The user gets to control what gets actually passed to lockbud.
     let mut cmd = cargo();
     cmd.arg("build");
-    cmd.env("RUSTC_WRAPPER", "lockbud");
+    cmd.env("RUSTC_WRAPPER", lockbud_wrapper());
     cmd.env("RUST_BACKTRACE", "full");
     cmd.env("LOCKBUD_LOG", "info");
-    let args = std::env::args().skip(2);
-    let mut flags = Vec::new();
-    for arg in args {
-        if arg == "--" {
-            break;
-        }
-        flags.push(arg);
+    // cargo pipes the stderr of lockbud, so tell lockbud whether to print progress.
+    if std::io::stderr().is_terminal() {
+        cmd.env("LOCKBUD_STDERR_IS_TTY", "1");
+    }
+    let Args {
+        mut flags,
+        cargo_args,
+        output,
+        baseline,
+        write_baseline,
+    } = Args::parse(std::env::args().skip(2));
+    let options_hash = options_hash(&flags, &cargo_args, &baseline);
+    let cache_dir = target_dir().join("lockbud").join(&options_hash);
+    cmd.env("LOCKBUD_OPTIONS_HASH", &options_hash);
+    if let Some(git_commit) = git_commit() {
+        cmd.env("LOCKBUD_GIT_COMMIT", git_commit);
+    }
+    cmd.args(cargo_args);
+    let output = output.map(|path| prepare_output(Path::new(&path)));
+    if let Some(output) = &output {
+        let path = output.to_string_lossy();
+        flags.push(format!("--report-path={}", shell_escape::escape(path)));
+    }
+    if let Some(baseline) = &baseline {
+        let path = absolute(Path::new(baseline));
+        let path = path.to_string_lossy();
+        flags.push(format!("--baseline={}", shell_escape::escape(path)));
+    }
+    let write_baseline = write_baseline.map(|path| prepare_output(Path::new(&path)));
+    if let Some(write_baseline) = &write_baseline {
+        let path = write_baseline.to_string_lossy();
+        flags.push(format!("--write-baseline={}", shell_escape::escape(path)));
     }
     let flags = flags.join(" ");
     cmd.env("LOCKBUD_FLAGS", flags);
+    // lockbud records the crates with deny-level reports rather than failing their builds.
+    let denied_path = env::temp_dir().join(format!("lockbud-denied-{}", std::process::id()));
+    let _ = std::fs::remove_file(&denied_path);
+    cmd.env("LOCKBUD_DENIED_PATH", &denied_path);
+    cmd.env("LOCKBUD_CACHE_DIR", &cache_dir);
+    let last_cached = Cache::modified_times(&cache_dir);
     let exit_status = cmd
         .spawn()
         .expect("could not run cargo")
         .wait()
         .expect("failed to wait for cargo?");
+    let cache = Cache::read(&cache_dir, &last_cached);
+    if let Some(output) = &output {
+        cache.append_cached(output);
+        summarize_output(output);
+    }
+    if let Some(write_baseline) = &write_baseline {
+        cache.extend_baseline(write_baseline);
+        summarize_baseline(write_baseline);
+    }
+    cache.summarize();
+    let denied = take_denied(&denied_path) + cache.denied();
     if !exit_status.success() {
         std::process::exit(exit_status.code().unwrap_or(-1))
     };
+    if denied > 0 {
+        eprintln!("lockbud: {} crates contain deny-level bugs", denied);
+        std::process::exit(EXIT_DENIED);
+    }
+}
+
+/// The number of the crates with deny-level reports recorded by lockbud, removing the record.
+fn take_denied(path: &Path) -> usize {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let _ = std::fs::remove_file(path);
+    content.lines().count()
+}
+
+/// The value of `arg` if it is `name=value`, or of the next arg if `arg` is `name`.
+fn flag_value(name: &str, arg: &str, args: &mut impl Iterator<Item = String>) -> Option<String> {
+    if arg == name {
+        args.next()
+    } else {
+        arg.strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+            .map(str::to_owned)
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    env::current_dir()
+        .expect("could not get the current dir")
+        .join(path)
+}
+
+/// The absolute path of an output file (`--output` or `--write-baseline`),
+/// removing the file of the last run or creating its dir.
+fn prepare_output(path: &Path) -> PathBuf {
+    let path = absolute(path);
+    if path.exists() {
+        std::fs::remove_file(&path).expect("could not remove the last output");
+    } else if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("could not create the dir of the output");
+    }
+    path
+}
+
+fn remove_lock_file(path: &Path) {
+    let mut lock_path = OsString::from(path.as_os_str());
+    lock_path.push(".lock");
+    let _ = std::fs::remove_file(lock_path);
+}
+
+/// Print the numbers of reports and crates in `--output`, and remove its lock file after cargo finishes.
+fn summarize_output(path: &Path) {
+    remove_lock_file(path);
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let (mut num_reports, mut num_crates) = (0, 0);
+    for line in content.lines() {
+        match serde_json::from_str::<ReportEnvelope>(line) {
+            Ok(envelope) => {
+                num_reports += envelope.reports.len();
+                num_crates += 1;
+            }
+            Err(err) => eprintln!("lockbud: malformed line in {}: {}", path.display(), err),
+        }
+    }
+    eprintln!(
+        "lockbud: {} reports of {} crates written to {}",
+        num_reports,
+        num_crates,
+        path.display()
+    );
+}
+
+/// The hash of the flags and the args changing the reports, naming the cache dir of the run,
+/// i.e., `{target dir}/lockbud/{hash}`, so that the envelopes cached with different ones are not reported.
+fn options_hash(flags: &[String], cargo_args: &[String], baseline: &Option<String>) -> String {
+    let mut hasher = DefaultHasher::new();
+    (flags, cargo_args, baseline).hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// The git commit of the analyzed workspace, `GIT_COMMIT` (e.g., set by CI) or else the HEAD of the current dir.
+fn git_commit() -> Option<String> {
+    if let Some(commit) = env::var("GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+    {
+        return Some(commit);
+    }
+    Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_owned())
+        .filter(|commit| !commit.is_empty())
+}
+
+/// The target dir of cargo, i.e., `CARGO_TARGET_DIR`, or else the `target_directory` of `cargo metadata`.
+fn target_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+        return absolute(Path::new(&dir));
+    }
+    cargo()
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| serde_json::from_slice::<serde_json::Value>(&output.stdout).ok())
+        .and_then(|metadata| metadata["target_directory"].as_str().map(PathBuf::from))
+        .unwrap_or_else(|| absolute(Path::new("target")))
+}
+
+/// The envelopes in the cache dir, split into the ones of the crates analyzed by this run,
+/// and the ones of the crates not rebuilt, cached by the last runs.
+#[derive(Debug, Default)]
+struct Cache {
+    analyzed: Vec<ReportEnvelope>,
+    cached: Vec<ReportEnvelope>,
+}
+
+impl Cache {
+    /// The modified times of the envelopes in `dir`.
+    fn modified_times(dir: &Path) -> BTreeMap<PathBuf, SystemTime> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return BTreeMap::new();
+        };
+        entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let path = entry.path();
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                (path.extension()? == "json").then_some((path, modified))
+            })
+            .collect()
+    }
+
+    /// Read the envelopes in `dir`, where the ones written since `last_cached`
+    /// (the modified times before the build) are analyzed by this run.
+    fn read(dir: &Path, last_cached: &BTreeMap<PathBuf, SystemTime>) -> Self {
+        let mut cache = Cache::default();
+        for (path, modified) in Self::modified_times(dir) {
+            let envelope = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|content| serde_json::from_str(&content).map_err(|err| err.to_string()));
+            let envelope = match envelope {
+                Ok(envelope) => envelope,
+                Err(err) => {
+                    eprintln!("lockbud: malformed cache {}: {}", path.display(), err);
+                    continue;
+                }
+            };
+            if last_cached.get(&path) != Some(&modified) {
+                cache.analyzed.push(envelope);
+            } else {
+                cache.cached.push(envelope);
+            }
+        }
+        cache
+    }
+
+    /// Append the envelopes of the cached crates to `--output`, where lockbud appends the analyzed ones.
+    fn append_cached(&self, output: &Path) {
+        let appended = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(output)
+            .and_then(|mut file| {
+                self.cached
+                    .iter()
+                    .try_for_each(|envelope| writeln!(file, "{}", serde_json::to_string(envelope)?))
+            });
+        if let Err(err) = appended {
+            eprintln!(
+                "lockbud: failed to write the cached reports to {}: {}",
+                output.display(),
+                err
+            );
+        }
+    }
+
+    /// The metadata of the analyzed crates, or of the cached ones if none is analyzed.
+    fn metadata(&self) -> Option<&AnalysisMetadata> {
+        self.analyzed
+            .iter()
+            .chain(&self.cached)
+            .find_map(|envelope| envelope.metadata.as_ref())
+    }
+
+    /// Add the fingerprints of the cached reports to `--write-baseline`, where lockbud adds the analyzed ones,
+    /// keeping the metadata written by lockbud.
+    fn extend_baseline(&self, path: &Path) {
+        let mut baseline = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Baseline>(&content).ok())
+            .unwrap_or_default();
+        if baseline.metadata.is_none() {
+            baseline.metadata = self.metadata().cloned();
+        }
+        baseline.fingerprints.extend(
+            self.cached
+                .iter()
+                .flat_map(|envelope| &envelope.reports)
+                .map(|report| report.fingerprint().to_owned())
+                .filter(|fingerprint| !fingerprint.is_empty()),
+        );
+        let j = serde_json::to_string_pretty(&baseline).unwrap();
+        if let Err(err) = std::fs::write(path, j) {
+            eprintln!(
+                "lockbud: failed to write the cached fingerprints to {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    /// The number of the cached crates with deny-level reports, which lockbud records for the analyzed ones.
+    fn denied(&self) -> usize {
+        self.cached
+            .iter()
+            .filter(|envelope| {
+                envelope
+                    .reports
+                    .iter()
+                    .any(|report| report.level() == Level::Deny)
+            })
+            .count()
+    }
+
+    /// The commits of the cached crates other than the one of `metadata`, i.e., not analyzed on the current commit.
+    fn stale_commits(&self) -> BTreeSet<&str> {
+        let commit = self
+            .metadata()
+            .and_then(|metadata| metadata.git_commit.as_deref());
+        self.cached
+            .iter()
+            .filter_map(|envelope| envelope.metadata.as_ref()?.git_commit.as_deref())
+            .filter(|cached_commit| Some(*cached_commit) != commit)
+            .collect()
+    }
+
+    /// Print the total numbers of reports of each detector in the analyzed and the cached crates.
+    fn summarize(&self) {
+        let mut total = Summary::default();
+        for envelope in self.analyzed.iter().chain(&self.cached) {
+            total.merge(&envelope.summary);
+        }
+        let num_crates = self.analyzed.len() + self.cached.len();
+        if num_crates == 0 {
+            eprintln!(
+                "lockbud: no crate analyzed, please `cargo clean` to re-analyze the built crates"
+            );
+            return;
+        }
+        let counts = total.counts();
+        let num_reports: usize = counts
+            .iter()
+            .map(|(_, counts)| counts.probably + counts.possibly)
+            .sum();
+        eprintln!(
+            "lockbud: {} reports in {} crates ({} analyzed, {} cached)",
+            num_reports,
+            num_crates,
+            self.analyzed.len(),
+            self.cached.len()
+        );
+        if let Some(metadata) = self.metadata() {
+            eprintln!("lockbud: analyzed by {}", metadata);
+        }
+        for commit in self.stale_commits() {
+            eprintln!(
+                "lockbud: some cached crates were analyzed on commit {}",
+                commit
+            );
+        }
+        for (kind, counts) in counts {
+            eprintln!(
+                "    {}: {} (probably: {}, possibly: {})",
+                kind,
+                counts.probably + counts.possibly,
+                counts.probably,
+                counts.possibly
+            );
+        }
+        for (panic_api, num) in &total.panic_apis {
+            match total.panic_apis_in_tests.get(panic_api) {
+                Some(in_tests) => eprintln!(
+                    "        {}: {} (non-test: {}, test: {})",
+                    panic_api,
+                    num,
+                    num - in_tests,
+                    in_tests
+                ),
+                None => eprintln!("        {}: {}", panic_api, num),
+            }
+        }
+    }
+}
+
+/// Print the number of fingerprints in `--write-baseline`, and remove its lock file after cargo finishes.
+fn summarize_baseline(path: &Path) {
+    remove_lock_file(path);
+    let content = std::fs::read_to_string(path).unwrap_or_else(|_| "[]".to_owned());
+    match serde_json::from_str::<Baseline>(&content) {
+        Ok(baseline) => eprintln!(
+            "lockbud: {} fingerprints written to {}{}",
+            baseline.fingerprints.len(),
+            path.display(),
+            baseline
+                .metadata
+                .map_or_else(String::new, |metadata| format!(" by {}", metadata))
+        ),
+        Err(err) => eprintln!("lockbud: malformed baseline {}: {}", path.display(), err),
+    }
 }
 
 fn main() {
@@ -82,6 +688,85 @@ fn main() {
         return;
     }
     if let Some("lockbud") = std::env::args().nth(1).as_deref() {
+        if let Some("setup") = std::env::args().nth(2).as_deref() {
+            setup();
+            return;
+        }
+        ensure_toolchain();
         in_cargo_lockbud();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args = "-k deadlock --workspace -p core --package=app --tests --output out.json -- --features foo"
+            .split(' ')
+            .map(str::to_owned);
+        let args = Args::parse(args);
+        assert_eq!(args.flags, ["-k", "deadlock"]);
+        assert_eq!(
+            args.cargo_args,
+            [
+                "--workspace",
+                "--package=core",
+                "--package=app",
+                "--tests",
+                "--features",
+                "foo"
+            ]
+        );
+        assert_eq!(args.output.as_deref(), Some("out.json"));
+        assert_eq!(args.baseline, None);
+    }
+
+    #[test]
+    fn test_toolchain_channel() {
+        assert!(pinned_toolchain().starts_with("nightly-"));
+        let content = "[toolchain]\nchannel=\"nightly\"\ncomponents = [\"rustc-dev\"]\n";
+        assert_eq!(toolchain_channel(content), Some("nightly"));
+        assert_eq!(toolchain_channel("[toolchain]\n"), None);
+    }
+
+    #[test]
+    fn test_read_cache() {
+        let dir = env::temp_dir().join("lockbud-test-cargo-cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, level: &str, commit: &str| {
+            let j = format!(
+                r#"{{"version": 1, "crate_name": "{}", "summary": {{}}, "reports": [{{"UseAfterFree": {{"bug_kind": "UseAfterFree", "possibility": "Possibly", "diagnosis": "", "explanation": "", "level": "{}", "fingerprint": "fp-{}"}}}}], "metadata": {{"lockbud_version": "0.1.0", "analyzed_at": "2024-01-31T12:00:00Z", "git_commit": "{}", "options_hash": "0123abcd"}}}}"#,
+                name, level, name, commit
+            );
+            std::fs::write(dir.join(format!("{}-0123abcd.json", name)), j).unwrap();
+        };
+        write("cached", "deny", "c0");
+        let last_cached = Cache::modified_times(&dir);
+        write("analyzed", "warn", "c1");
+        std::fs::write(dir.join("partial-0123abcd.json.tmp"), "{").unwrap();
+        let cache = Cache::read(&dir, &last_cached);
+        assert_eq!(cache.analyzed.len(), 1);
+        assert_eq!(cache.cached[0].crate_name, "cached");
+        assert_eq!(cache.denied(), 1);
+        assert_eq!(cache.metadata().unwrap().git_commit.as_deref(), Some("c1"));
+        assert_eq!(cache.stale_commits(), BTreeSet::from(["c0"]));
+        // The metadata of a baseline without one is taken from the analyzed crates.
+        let baseline = dir.join("baseline.json");
+        std::fs::write(&baseline, r#"["fp-analyzed"]"#).unwrap();
+        cache.extend_baseline(&baseline);
+        let content = std::fs::read_to_string(&baseline).unwrap();
+        let parsed: Baseline = serde_json::from_str(&content).unwrap();
+        assert_eq!(
+            parsed.fingerprints.into_iter().collect::<Vec<_>>(),
+            ["fp-analyzed", "fp-cached"]
+        );
+        assert_eq!(parsed.metadata.as_ref(), cache.metadata());
+        assert_ne!(
+            options_hash(&["-k".to_owned()], &[], &None),
+            options_hash(&["-k".to_owned()], &["--tests".to_owned()], &None)
+        );
+    }
+}
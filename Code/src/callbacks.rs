@@ -1,32 +1,66 @@
-//! The main functionality: callbacks for rustc plugin systems.
+//! The driver of lockbud: callbacks for rustc plugin systems, running `run_analysis` on each crate
+//! and emitting the reports after applying the baseline and the levels.
 //! Inspired by <https://github.com/facebookexperimental/MIRAI/blob/9cf3067309d591894e2d0cd9b1ee6e18d0fdd26c/checker/src/callbacks.rs>
 extern crate rustc_driver;
 extern crate rustc_hir;
 
-use std::path::PathBuf;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-use crate::analysis::pointsto::AliasAnalysis;
-use crate::detector::memory::{InvalidFreeDetector, UseAfterFreeDetector};
-use crate::options::{CrateNameList, DetectorKind, Options};
+use crate::analysis::budget::SkippedFunction;
+use crate::analysis::pointsto::stats::AliasStats;
+use crate::baseline;
+use crate::detector::lock::lock_graph::LockGraph;
+use crate::detector::lock::report::LockClass;
+use crate::detector::report::{
+    group_by_lock, referenced_locks, sort_reports, truncate_reports, AnalysisMetadata, Level,
+    Report, ReportEnvelope, Summary,
+};
+use crate::diagnostic;
+use crate::options::{CrateNameList, MessageFormat, Options};
+use crate::progress;
+use crate::report_file;
+use crate::run::run_analysis;
 use log::{debug, warn};
 use rustc_driver::Compilation;
 use rustc_hir::def_id::LOCAL_CRATE;
 use rustc_interface::interface;
-use rustc_middle::mir::mono::MonoItem;
-use rustc_middle::ty::{Instance, ParamEnv, TyCtxt};
+use rustc_middle::ty::TyCtxt;
+use rustc_session::config::CrateType;
 
-use crate::analysis::callgraph::CallGraph;
+/// The exit code of lockbud when any deny-level report is emitted.
+pub const EXIT_DENIED: i32 = 42;
 
-use crate::detector::atomic::AtomicityViolationDetector;
-use crate::detector::lock::DeadlockDetector;
-use crate::detector::panic::PanicDetector;
-use crate::detector::report::Report;
+/// Set by `cargo lockbud` to the file recording the crates with deny-level reports.
+const DENIED_PATH_ENV: &str = "LOCKBUD_DENIED_PATH";
+
+/// Set by `cargo lockbud` to the dir caching the envelopes of the analyzed crates, see `report_file.rs`.
+const CACHE_DIR_ENV: &str = "LOCKBUD_CACHE_DIR";
+
+/// Set by `cargo lockbud` to the git commit of the analyzed workspace, falling back to `GIT_COMMIT`.
+const GIT_COMMIT_ENV: &str = "LOCKBUD_GIT_COMMIT";
+
+/// Set by `cargo lockbud` to the hash of the options changing the reports,
+/// falling back to the hash of `LOCKBUD_FLAGS`.
+const OPTIONS_HASH_ENV: &str = "LOCKBUD_OPTIONS_HASH";
 
 pub struct LockBudCallbacks {
     options: Options,
     file_name: String,
-    output_directory: PathBuf,
     test_run: bool,
+    /// Whether any deny-level report is emitted.
+    denied: bool,
+    /// The alias classes of the locks in the deadlock reports.
+    lock_classes: Vec<LockClass>,
+    /// The fns skipped by the budgets of the analyses of the crate.
+    skipped_functions: Vec<SkippedFunction>,
+    /// The counters of the alias queries of the crate with `--alias-stats`.
+    alias_stats: Option<AliasStats>,
+    /// Recorded in the envelopes and the baseline.
+    metadata: AnalysisMetadata,
 }
 
 impl LockBudCallbacks {
@@ -34,8 +68,42 @@ impl LockBudCallbacks {
         Self {
             options,
             file_name: String::new(),
-            output_directory: PathBuf::default(),
             test_run: false,
+            denied: false,
+            lock_classes: Vec::new(),
+            skipped_functions: Vec::new(),
+            alias_stats: None,
+            metadata: analysis_metadata(),
+        }
+    }
+
+    /// The exit code after the compilation succeeds.
+    /// Under `cargo lockbud`, the crate is recorded in `LOCKBUD_DENIED_PATH` instead of failing,
+    /// so that cargo goes on building (and analyzing) the dependent crates,
+    /// and `cargo lockbud` exits with `EXIT_DENIED` after the build.
+    pub fn exit_code(&self) -> i32 {
+        if !self.denied {
+            return rustc_driver::EXIT_SUCCESS;
+        }
+        let Some(path) = std::env::var_os(DENIED_PATH_ENV) else {
+            return EXIT_DENIED;
+        };
+        // A single short append, so the crates compiled in parallel do not interleave.
+        let recorded = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{}", self.file_name));
+        match recorded {
+            Ok(()) => rustc_driver::EXIT_SUCCESS,
+            Err(err) => {
+                warn!(
+                    "lockbud: failed to record the denied crate in {}: {}",
+                    PathBuf::from(path).display(),
+                    err
+                );
+                EXIT_DENIED
+            }
         }
     }
 }
@@ -48,13 +116,6 @@ impl rustc_driver::Callbacks for LockBudCallbacks {
             debug!("in test only mode");
             // self.options.test_only = true;
         }
-        match &config.output_dir {
-            None => {
-                self.output_directory = std::env::temp_dir();
-                self.output_directory.pop();
-            }
-            Some(path_buf) => self.output_directory.push(path_buf.as_path()),
-        }
     }
     fn after_analysis<'tcx>(
         &mut self,
@@ -62,15 +123,6 @@ impl rustc_driver::Callbacks for LockBudCallbacks {
         queries: &'tcx rustc_interface::Queries<'tcx>,
     ) -> rustc_driver::Compilation {
         compiler.session().abort_if_errors();
-        if self
-            .output_directory
-            .to_str()
-            .expect("valid string")
-            .contains("/build/")
-        {
-            // No need to analyze a build script, but do generate code.
-            return Compilation::Continue;
-        }
         queries.global_ctxt().unwrap().enter(|tcx| {
             self.analyze_with_lockbud(compiler, tcx);
         });
@@ -85,7 +137,7 @@ impl rustc_driver::Callbacks for LockBudCallbacks {
 }
 
 impl LockBudCallbacks {
-    fn analyze_with_lockbud<'tcx>(&mut self, _compiler: &interface::Compiler, tcx: TyCtxt<'tcx>) {
+    fn analyze_with_lockbud(&mut self, _compiler: &interface::Compiler, tcx: TyCtxt<'_>) {
         // Skip crates by names (white or black list).
         let crate_name = tcx.crate_name(LOCAL_CRATE).to_string();
         match &self.options.crate_name_list {
@@ -95,181 +147,537 @@ impl LockBudCallbacks {
             CrateNameList::Black(crates) if crates.contains(&crate_name) => return,
             _ => {}
         };
+        // No need to analyze the build scripts and the proc-macro crates running at build time,
+        // but do generate code.
+        if is_build_script(&crate_name)
+            || (!self.options.analyze_proc_macros
+                && tcx.crate_types().contains(&CrateType::ProcMacro))
+        {
+            debug!("Skipping build-time crate {}", crate_name);
+            return;
+        }
+        // Skip the dependencies when run by cargo, which are still compiled with their MIR encoded,
+        // so the interprocedural analysis of the primary packages goes into them.
+        if !self.options.all_crates && is_cargo_dependency() {
+            debug!("Skipping non-primary package {}", crate_name);
+            return;
+        }
         if tcx.sess.opts.unstable_opts.no_codegen || !tcx.sess.opts.output_types.should_codegen() {
             return;
         }
-        let cgus = tcx.collect_and_partition_mono_items(()).1;
-        let instances: Vec<Instance<'tcx>> = cgus
-            .iter()
-            .flat_map(|cgu| {
-                cgu.items().iter().filter_map(|(mono_item, _)| {
-                    if let MonoItem::Fn(instance) = mono_item {
-                        Some(*instance)
-                    } else {
-                        None
-                    }
-                })
+        let mut output = run_analysis(tcx, &self.options);
+        if output.suppressed > 0 {
+            warn!(
+                "crate {} suppressed {} reports in source",
+                crate_name, output.suppressed
+            );
+        }
+        if !output.skipped_functions.is_empty() {
+            let j = serde_json::to_string_pretty(&output.skipped_functions).unwrap();
+            warn!("crate {} skipped_functions: {}", crate_name, j);
+        }
+        self.lock_classes = std::mem::take(&mut output.lock_classes);
+        self.skipped_functions = std::mem::take(&mut output.skipped_functions);
+        self.alias_stats = output.alias_stats.take();
+        if let Some(lock_graph) = output.lock_graph.take() {
+            self.emit_lock_graph(&crate_name, &lock_graph);
+        }
+        self.emit_reports(Some(tcx), &crate_name, output.into_reports());
+    }
+}
+
+impl LockBudCallbacks {
+    /// Apply the configured levels to the reports, dropping the allowed ones.
+    /// `--deny` raises the level of the matching reports that are not allowed.
+    /// The reports less confident than `--min-confidence` are dropped as well.
+    fn apply_levels(&mut self, reports: Vec<Report>) -> Vec<Report> {
+        let min_confidence = self.options.min_confidence;
+        let reports = reports
+            .into_iter()
+            .filter(|report| {
+                report
+                    .confidence()
+                    .map_or(true, |confidence| confidence >= min_confidence)
             })
-            .collect();
-        let mut callgraph = CallGraph::new();
-        let param_env = ParamEnv::reveal_all();
-        callgraph.analyze(instances.clone(), tcx, param_env);
-        let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
-        match self.options.detector_kind {
-            DetectorKind::Deadlock => {
-                debug!("Detecting deadlock");
-                let mut deadlock_detector = DeadlockDetector::new(tcx, param_env);
-                let reports = deadlock_detector.detect(&callgraph, &mut alias_analysis);
-                if !reports.is_empty() {
-                    let j = serde_json::to_string_pretty(&reports).unwrap();
-                    warn!("{}", j);
-                    let stats = report_stats(&crate_name, &reports);
-                    warn!("{}", stats);
-                }
-            }
-            DetectorKind::AtomicityViolation => {
-                debug!("Detecting atomicity violation");
-                let mut atomicity_violation_detector = AtomicityViolationDetector::new(tcx);
-                let reports = atomicity_violation_detector.detect(&callgraph, &mut alias_analysis);
-                if !reports.is_empty() {
-                    let j = serde_json::to_string_pretty(&reports).unwrap();
-                    warn!("{}", j);
-                    let stats = report_stats(&crate_name, &reports);
-                    warn!("{}", stats);
-                }
-            }
-            DetectorKind::Memory => {
-                debug!("Detecting memory bugs");
-                let mut reports = {
-                    let invalid_free_detector = InvalidFreeDetector::new(tcx);
-                    invalid_free_detector.detect(&callgraph, &mut alias_analysis)
+            .filter_map(|mut report| {
+                let level = match self.options.config.level_of(&report) {
+                    Level::Allow => Level::Allow,
+                    _ if self.options.deny.matches(&report) => Level::Deny,
+                    level => level,
                 };
-                let reports2 = {
-                    let use_after_free_detector = UseAfterFreeDetector::new(tcx);
-                    use_after_free_detector.detect(&callgraph, &mut alias_analysis)
-                };
-                reports.extend(reports2.into_iter());
-                if !reports.is_empty() {
-                    let j = serde_json::to_string_pretty(&reports).unwrap();
-                    warn!("{}", j);
-                    let stats = report_stats(&crate_name, &reports);
-                    warn!("{}", stats);
-                }
+                report.set_level(level);
+                (level != Level::Allow).then_some(report)
+            })
+            .collect::<Vec<_>>();
+        if reports.iter().any(|report| report.level() == Level::Deny) {
+            self.denied = true;
+        }
+        reports
+    }
+
+    /// Drop the reports in `--baseline` after adding their fingerprints to `--write-baseline`.
+    fn apply_baseline(&self, crate_name: &str, reports: Vec<Report>) -> Vec<Report> {
+        if let Some(path) = &self.options.write_baseline {
+            match baseline::extend_baseline(
+                path,
+                &self.metadata,
+                reports.iter().map(Report::fingerprint),
+            ) {
+                Ok(()) => warn!(
+                    "lockbud: fingerprints of {} reports of crate {} written to {}",
+                    reports.len(),
+                    crate_name,
+                    path.display()
+                ),
+                Err(err) => warn!(
+                    "lockbud: failed to write the baseline of crate {} to {}: {}",
+                    crate_name,
+                    path.display(),
+                    err
+                ),
             }
-            DetectorKind::All => {
-                debug!("Detecting all bugs");
-                let mut reports;
-                {
-                    let mut deadlock_detector = DeadlockDetector::new(tcx, param_env);
-                    reports = deadlock_detector.detect(&callgraph, &mut alias_analysis);
-                }
-                {
-                    let mut atomicity_violation_detector = AtomicityViolationDetector::new(tcx);
-                    reports.extend(
-                        atomicity_violation_detector
-                            .detect(&callgraph, &mut alias_analysis)
-                            .into_iter(),
-                    );
-                }
-                {
-                    let invalid_free_detector = InvalidFreeDetector::new(tcx);
-                    reports.extend(
-                        invalid_free_detector
-                            .detect(&callgraph, &mut alias_analysis)
-                            .into_iter(),
+        }
+        let baseline = &self.options.baseline;
+        if baseline.fingerprints.is_empty() {
+            return reports;
+        }
+        let num_reports = reports.len();
+        let reports = reports
+            .into_iter()
+            .filter(|report| !baseline.fingerprints.contains(report.fingerprint()))
+            .collect::<Vec<_>>();
+        if reports.len() < num_reports {
+            warn!(
+                "crate {} suppressed {} reports in the baseline{}",
+                crate_name,
+                num_reports - reports.len(),
+                baseline
+                    .metadata
+                    .as_ref()
+                    .map_or_else(String::new, |metadata| format!(" written by {}", metadata))
+            );
+        }
+        reports
+    }
+
+    /// Emit the reports as JSON, or as rustc diagnostics if the session (`tcx`) is available.
+    fn emit_reports(&mut self, tcx: Option<TyCtxt<'_>>, crate_name: &str, reports: Vec<Report>) {
+        let reports = self.apply_baseline(crate_name, reports);
+        let mut reports = self.apply_levels(reports);
+        sort_reports(&mut reports);
+        let denied = reports
+            .iter()
+            .filter(|report| report.level() == Level::Deny)
+            .count();
+        let dropped = if self.options.max_reports > 0 {
+            truncate_reports(&mut reports, self.options.max_reports)
+        } else {
+            Vec::new()
+        };
+        let stats = report_stats(crate_name, &reports);
+        let mut envelope = ReportEnvelope::new(crate_name.to_owned(), reports);
+        envelope.metadata = Some(self.metadata.clone());
+        envelope.summary.record_dropped(&dropped);
+        envelope.locks = referenced_locks(&envelope.reports, &self.lock_classes);
+        envelope.skipped_functions = self.skipped_functions.clone();
+        envelope.alias_stats = self.alias_stats.clone();
+        if self.options.group_by_lock {
+            envelope.groups = group_by_lock(&envelope.reports, &self.lock_classes);
+        }
+        if let Some(tcx) = tcx {
+            cache_envelope(tcx, &envelope);
+        }
+        // Every analyzed crate is recorded in the report file, even if it has no reports.
+        let written = self.options.report_path.as_ref().map_or(false, |path| {
+            match report_file::append_envelope(path, &envelope) {
+                Ok(()) => {
+                    warn!(
+                        "lockbud: {} reports ({} deny-level) of crate {} written to {}",
+                        envelope.reports.len(),
+                        denied,
+                        crate_name,
+                        path.display()
                     );
+                    true
                 }
-                {
-                    let use_after_free_detector = UseAfterFreeDetector::new(tcx);
-                    reports.extend(
-                        use_after_free_detector
-                            .detect(&callgraph, &mut alias_analysis)
-                            .into_iter(),
+                Err(err) => {
+                    warn!(
+                        "lockbud: failed to write the reports of crate {} to {}: {}",
+                        crate_name,
+                        path.display(),
+                        err
                     );
-                }
-                if !reports.is_empty() {
-                    let j = serde_json::to_string_pretty(&reports).unwrap();
-                    warn!("{}", j);
-                    let stats = report_stats(&crate_name, &reports);
-                    warn!("{}", stats);
+                    false
                 }
             }
-            DetectorKind::Panic => {
-                debug!("Detecting panic sites");
-                let mut detector = PanicDetector::new(tcx);
-                for instance in instances {
-                    detector.detect(instance);
+        });
+        if !written && !envelope.reports.is_empty() {
+            let message_format = self.options.message_format.unwrap_or_else(|| {
+                if progress::stderr_is_tty() {
+                    MessageFormat::Diagnostic
+                } else {
+                    MessageFormat::Json
                 }
-                for (i, (k, v)) in detector.result().iter().enumerate() {
-                    println!(
-                        "PANIC[{}#{}]: {:?}: span[{:?}], outermost_span[{:?}], {:?}",
-                        tcx.crate_name(LOCAL_CRATE),
-                        i,
-                        k,
-                        v.0,
-                        v.1,
-                        v.2
-                    );
+            });
+            match tcx {
+                Some(tcx) if message_format == MessageFormat::Diagnostic => {
+                    diagnostic::emit_diagnostics(tcx, &envelope.reports)
                 }
-                for (panic_api, cnt) in detector.statistics() {
-                    println!("{}: {:?}: {}", tcx.crate_name(LOCAL_CRATE), panic_api, cnt);
+                _ => {
+                    let j = serde_json::to_string_pretty(&envelope).unwrap();
+                    warn!("{}", j);
                 }
             }
+            warn!("{}", stats);
+            if self.options.group_by_lock {
+                warn!("{}", render_lock_groups(&envelope));
+            }
+            if denied > 0 {
+                warn!("crate {} contains {} deny-level bugs", crate_name, denied);
+            }
+        }
+        if !dropped.is_empty() {
+            self.emit_dropped_reports(crate_name, dropped);
         }
     }
-}
 
-fn report_stats(crate_name: &str, reports: &[Report]) -> String {
-    let (
-        mut doublelock_probably,
-        mut doublelock_possibly,
-        mut conflictlock_probably,
-        mut conflictlock_possibly,
-        mut condvar_deadlock_probably,
-        mut condvar_deadlock_possibly,
-        mut atomicity_violation_possibly,
-        mut invalid_free_possibly,
-        mut use_after_free_possibly,
-    ) = (0, 0, 0, 0, 0, 0, 0, 0, 0);
-    for report in reports {
-        match report {
-            Report::DoubleLock(doublelock) => match doublelock.possibility.as_str() {
-                "Probably" => doublelock_probably += 1,
-                "Possibly" => doublelock_possibly += 1,
-                _ => {}
-            },
-            Report::ConflictLock(conflictlock) => match conflictlock.possibility.as_str() {
-                "Probably" => conflictlock_probably += 1,
-                "Possibly" => conflictlock_possibly += 1,
-                _ => {}
-            },
-            Report::CondvarDeadlock(condvar_deadlock) => {
-                match condvar_deadlock.possibility.as_str() {
-                    "Probably" => condvar_deadlock_probably += 1,
-                    "Possibly" => condvar_deadlock_possibly += 1,
-                    _ => {}
-                }
-            }
-            Report::AtomicityViolation(_) => {
-                atomicity_violation_possibly += 1;
-            }
-            Report::InvalidFree(_) => {
-                invalid_free_possibly += 1;
-            }
-            Report::UseAfterFree(_) => {
-                use_after_free_possibly += 1;
+    /// Write the reports dropped by `--max-reports` to `{crate_name}.dropped.json` in `--dropped-reports-dir`.
+    fn emit_dropped_reports(&self, crate_name: &str, dropped: Vec<Report>) {
+        let num_dropped = dropped.len();
+        let dir = match &self.options.dropped_reports_dir {
+            Some(dir) => dir,
+            None => {
+                warn!(
+                    "crate {} dropped {} reports by --max-reports {}, use --dropped-reports-dir to keep them",
+                    crate_name, num_dropped, self.options.max_reports
+                );
+                return;
             }
+        };
+        let path = dir.join(format!("{}.dropped.json", crate_name));
+        let mut envelope = ReportEnvelope::new(crate_name.to_owned(), dropped);
+        envelope.metadata = Some(self.metadata.clone());
+        let j = serde_json::to_string_pretty(&envelope).unwrap();
+        match std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, j)) {
+            Ok(()) => warn!(
+                "crate {} dropped {} reports by --max-reports {}, written to {}",
+                crate_name,
+                num_dropped,
+                self.options.max_reports,
+                path.display()
+            ),
+            Err(err) => warn!(
+                "crate {} dropped {} reports by --max-reports {}, failed to write {}: {}",
+                crate_name,
+                num_dropped,
+                self.options.max_reports,
+                path.display(),
+                err
+            ),
         }
     }
-    format!("crate {} contains bugs: {{ probably: {}, possibly: {} }}, conflictlock: {{ probably: {}, possibly: {} }}, condvar_deadlock: {{ probably: {}, possibly: {} }}, atomicity_violation: {{ possibly: {} }}, invalid_free: {{ possibly: {} }}, use_after_free: {{ possibly: {} }}", crate_name, doublelock_probably, doublelock_possibly, conflictlock_probably, conflictlock_possibly, condvar_deadlock_probably, condvar_deadlock_possibly, atomicity_violation_possibly, invalid_free_possibly, use_after_free_possibly)
+
+    /// Write the lock order graph to `{crate_name}.lock-graph.dot` and `{crate_name}.lock-graph.json` in `--emit-lock-graph`.
+    fn emit_lock_graph(&self, crate_name: &str, lock_graph: &LockGraph) {
+        let Some(dir) = &self.options.emit_lock_graph else {
+            return;
+        };
+        let dot_path = dir.join(format!("{}.lock-graph.dot", crate_name));
+        let json_path = dir.join(format!("{}.lock-graph.json", crate_name));
+        let j = serde_json::to_string_pretty(lock_graph).unwrap();
+        match std::fs::create_dir_all(dir)
+            .and_then(|_| std::fs::write(&dot_path, lock_graph.dot()))
+            .and_then(|_| std::fs::write(&json_path, j))
+        {
+            Ok(()) => debug!(
+                "crate {} lock graph written to {}",
+                crate_name,
+                dot_path.display()
+            ),
+            Err(err) => warn!(
+                "crate {} failed to write the lock graph to {}: {}",
+                crate_name,
+                dir.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// Whether the crate is a build script compiled by cargo, named `build_script_build`
+/// (or after the file of `package.build`, e.g., `build_script_codegen`).
+fn is_build_script(crate_name: &str) -> bool {
+    crate_name.starts_with("build_script_")
+}
+
+/// Whether the crate is compiled by cargo as a dependency, i.e., not a primary package
+/// (a workspace member or a package selected by `-p`), for which cargo sets `CARGO_PRIMARY_PACKAGE`.
+fn is_cargo_dependency() -> bool {
+    std::env::var_os("CARGO_PKG_NAME").is_some()
+        && std::env::var_os("CARGO_PRIMARY_PACKAGE").is_none()
+}
+
+/// The metadata of the analysis starting now, with the git commit and the options hash given by `cargo lockbud`.
+fn analysis_metadata() -> AnalysisMetadata {
+    let git_commit = [GIT_COMMIT_ENV, "GIT_COMMIT"]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok().filter(|commit| !commit.is_empty()));
+    let options_hash = std::env::var(OPTIONS_HASH_ENV).unwrap_or_else(|_| {
+        let mut hasher = DefaultHasher::new();
+        std::env::var("LOCKBUD_FLAGS")
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    });
+    AnalysisMetadata {
+        lockbud_version: env!("CARGO_PKG_VERSION").to_owned(),
+        analyzed_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        git_commit,
+        options_hash,
+    }
+}
+
+/// Cache the envelope of the crate in `LOCKBUD_CACHE_DIR` under its `-C metadata` given by cargo,
+/// for `cargo lockbud` to report the crate after the build, even if it is not rebuilt by the next run.
+fn cache_envelope(tcx: TyCtxt<'_>, envelope: &ReportEnvelope) {
+    let Some(dir) = std::env::var_os(CACHE_DIR_ENV) else {
+        return;
+    };
+    let metadata = &tcx.sess.opts.cg.metadata;
+    if metadata.is_empty() {
+        return;
+    }
+    if let Err(err) = report_file::write_cache(Path::new(&dir), &metadata.join("-"), envelope) {
+        warn!(
+            "lockbud: failed to cache the reports of crate {} in {}: {}",
+            envelope.crate_name,
+            PathBuf::from(dir).display(),
+            err
+        );
+    }
+}
+
+/// The dir of the debug dumps, e.g., by `--dump-pointsto`: `debug` next to `LOCKBUD_CACHE_DIR`
+/// in `{target dir}/lockbud`, or `target/lockbud/debug` if not run by `cargo lockbud`.
+pub(crate) fn debug_dir() -> PathBuf {
+    std::env::var_os(CACHE_DIR_ENV)
+        .and_then(|dir| {
+            PathBuf::from(dir)
+                .parent()
+                .map(|parent| parent.join("debug"))
+        })
+        .unwrap_or_else(|| PathBuf::from("target/lockbud/debug"))
+}
+
+/// The human-readable view of `--group-by lock`: the reports under each lock, then the number of ungrouped ones.
+fn render_lock_groups(envelope: &ReportEnvelope) -> String {
+    let mut lines = vec![format!(
+        "crate {} reports grouped by lock:",
+        envelope.crate_name
+    )];
+    for group in &envelope.groups {
+        lines.push(format!(
+            "  {} declared at {}: {} acquisitions, {} reports",
+            group.lock.name,
+            group.lock.declaration_span,
+            group.lock.acquisitions,
+            group.reports.len()
+        ));
+        for idx in &group.reports {
+            let report = &envelope.reports[*idx];
+            lines.push(format!(
+                "    {} at {}",
+                report.level_keys()[0],
+                report.spans().0
+            ));
+        }
+    }
+    let grouped = envelope
+        .groups
+        .iter()
+        .map(|group| group.reports.len())
+        .sum::<usize>();
+    lines.push(format!(
+        "  {} reports not grouped by lock",
+        envelope.reports.len() - grouped
+    ));
+    lines.join("\n")
+}
+
+fn report_stats(crate_name: &str, reports: &[Report]) -> String {
+    let summary = Summary::new(reports);
+    format!("crate {} contains bugs: {{ probably: {}, possibly: {} }}, conflictlock: {{ probably: {}, possibly: {} }}, condvar_deadlock: {{ probably: {}, possibly: {} }}, condvar_multiple_mutexes: {{ possibly: {} }}, await_holding_lock: {{ possibly: {} }}, block_on_holding_lock: {{ possibly: {} }}, pool_self_join: {{ possibly: {} }}, rayon_deadlock: {{ possibly: {} }}, rendezvous_deadlock: {{ possibly: {} }}, once_reentrancy: {{ possibly: {} }}, once_init_deadlock: {{ possibly: {} }}, lock_guard_leaked: {{ possibly: {} }}, double_unlock: {{ possibly: {} }}, use_after_unlock: {{ possibly: {} }}, implicit_release_order: {{ possibly: {} }}, send_hazard: {{ possibly: {} }}, hot_path_lock: {{ possibly: {} }}, discarded_guard: {{ possibly: {} }}, lock_coverage: {{ possibly: {} }}, atomicity_violation: {{ possibly: {} }}, invalid_free: {{ possibly: {} }}, use_after_free: {{ possibly: {} }}, double_free: {{ possibly: {} }}, panic_in_drop: {{ probably: {} }}, panic_holding_lock: {{ possibly: {} }}, refcell_double_borrow: {{ probably: {}, possibly: {} }}, panic_site: {{ possibly: {} }}, plugin: {}", crate_name, summary.double_lock.probably, summary.double_lock.possibly, summary.conflict_lock.probably, summary.conflict_lock.possibly, summary.condvar_deadlock.probably, summary.condvar_deadlock.possibly, summary.condvar_multiple_mutexes, summary.await_holding_lock, summary.block_on_holding_lock, summary.pool_self_join, summary.rayon_deadlock, summary.rendezvous_deadlock, summary.once_reentrancy, summary.once_init_deadlock, summary.lock_guard_leaked, summary.double_unlock, summary.use_after_unlock, summary.implicit_release_order, summary.send_hazard, summary.hot_path_lock, summary.discarded_guard, summary.lock_coverage, summary.atomicity_violation, summary.invalid_free, summary.use_after_free, summary.double_free, summary.panic_in_drop, summary.panic_holding_lock, summary.refcell_double_borrow.probably, summary.refcell_double_borrow.possibly, summary.panic_site, summary.plugin)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::config::Config;
+    use crate::detector::lock::report::DeadlockDiagnosis;
+    use crate::detector::report::{Baseline, ReportContent};
+    use crate::options::DenyFilter;
+
+    fn report_content<D: std::fmt::Debug>(bug_kind: &str, diagnosis: D) -> ReportContent<D> {
+        ReportContent::new(
+            bug_kind.to_owned(),
+            "Possibly".to_owned(),
+            diagnosis,
+            String::new(),
+        )
+    }
+
+    fn sample_reports() -> Vec<Report> {
+        let d = DeadlockDiagnosis::new(
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:17:17: 17:30 (#0)".to_owned(),
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:18:10: 18:23 (#0)".to_owned(),
+            Vec::new(),
+        );
+        vec![
+            Report::DoubleLock(report_content("DoubleLock", d)),
+            Report::ConflictLock(report_content("ConflictLock", Vec::new())),
+            Report::InvalidFree(report_content("InvalidFree", String::new())),
+            Report::UseAfterFree(report_content("UseAfterFree", String::new())),
+        ]
+    }
+
+    fn callbacks_with_config(config: &str) -> LockBudCallbacks {
+        let options = Options {
+            config: Config::from_str(config).unwrap(),
+            ..Default::default()
+        };
+        LockBudCallbacks::new(options)
+    }
+
+    #[test]
+    fn test_apply_levels() {
+        let mut callbacks = callbacks_with_config(
+            r#"{"levels": {"double_lock": "deny", "deadlock": "warn", "memory": "allow"}}"#,
+        );
+        let reports = callbacks.apply_levels(sample_reports());
+        let levels = reports
+            .iter()
+            .map(|report| (report.level_keys()[0], report.level()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            levels,
+            vec![("double_lock", Level::Deny), ("conflict_lock", Level::Warn)]
+        );
+        assert!(serde_json::to_string(&reports[0])
+            .unwrap()
+            .contains(r#""level":"deny""#));
+        assert_eq!(callbacks.exit_code(), EXIT_DENIED);
+    }
+
+    #[test]
+    fn test_apply_levels_without_deny() {
+        let mut callbacks = callbacks_with_config(r#"{"levels": {"double_lock": "warn"}}"#);
+        let reports = callbacks.apply_levels(sample_reports());
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|report| report.level() == Level::Warn));
+        assert_eq!(callbacks.exit_code(), rustc_driver::EXIT_SUCCESS);
+        let mut callbacks = callbacks_with_config(r#"{"levels": {"deadlock": "allow"}}"#);
+        let reports = callbacks.apply_levels(sample_reports());
+        assert_eq!(reports.len(), 2);
+        assert_eq!(callbacks.exit_code(), rustc_driver::EXIT_SUCCESS);
+    }
+
+    #[test]
+    fn test_apply_levels_with_deny() {
+        let mut callbacks = LockBudCallbacks::new(Options {
+            deny: DenyFilter {
+                kinds: vec!["deadlock".to_owned()],
+                possibility: Some("Probably".to_owned()),
+            },
+            ..Default::default()
+        });
+        let reports = callbacks.apply_levels(sample_reports());
+        assert!(reports.iter().all(|report| report.level() == Level::Warn));
+        assert_eq!(callbacks.exit_code(), rustc_driver::EXIT_SUCCESS);
+        let mut callbacks = LockBudCallbacks::new(Options {
+            config: Config::from_str(r#"{"levels": {"invalid_free": "allow"}}"#).unwrap(),
+            deny: DenyFilter {
+                kinds: vec!["double_lock".to_owned(), "memory".to_owned()],
+                possibility: None,
+            },
+            ..Default::default()
+        });
+        let reports = callbacks.apply_levels(sample_reports());
+        let levels = reports
+            .iter()
+            .map(|report| (report.level_keys()[0], report.level()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            levels,
+            vec![
+                ("double_lock", Level::Deny),
+                ("conflict_lock", Level::Warn),
+                ("use_after_free", Level::Deny)
+            ]
+        );
+        assert_eq!(callbacks.exit_code(), EXIT_DENIED);
+    }
+
+    #[test]
+    fn test_apply_baseline() {
+        let path = std::env::temp_dir().join("lockbud-test-apply-baseline.json");
+        let _ = std::fs::remove_file(&path);
+        let callbacks = LockBudCallbacks::new(Options {
+            baseline: Baseline {
+                metadata: None,
+                fingerprints: ["fp0", "fp2"].into_iter().map(str::to_owned).collect(),
+            },
+            write_baseline: Some(path.clone()),
+            ..Default::default()
+        });
+        let mut reports = sample_reports();
+        for (i, report) in reports.iter_mut().enumerate() {
+            report.set_fingerprint(format!("fp{}", i));
+        }
+        let reports = callbacks.apply_baseline("dummy", reports);
+        assert_eq!(
+            reports.iter().map(Report::fingerprint).collect::<Vec<_>>(),
+            vec!["fp1", "fp3"]
+        );
+        // The baseline to write includes the suppressed reports.
+        let baseline = baseline::read_baseline(&path).unwrap();
+        assert_eq!(baseline.fingerprints.len(), 4);
+        assert_eq!(baseline.metadata.as_ref(), Some(&callbacks.metadata));
+    }
+
+    #[test]
+    fn test_emit_dropped_reports() {
+        let dir = std::env::temp_dir().join("lockbud-test-dropped-reports");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut callbacks = LockBudCallbacks::new(Options {
+            config: Config::from_str(r#"{"levels": {"use_after_free": "deny"}}"#).unwrap(),
+            max_reports: 1,
+            dropped_reports_dir: Some(dir.clone()),
+            ..Default::default()
+        });
+        callbacks.emit_reports(None, "dummy", sample_reports());
+        let j = std::fs::read_to_string(dir.join("dummy.dropped.json")).unwrap();
+        let envelope: ReportEnvelope = serde_json::from_str(&j).unwrap();
+        // The deny-level use_after_free is kept.
+        assert_eq!(
+            envelope
+                .reports
+                .iter()
+                .map(|report| report.level_keys()[0])
+                .collect::<Vec<_>>(),
+            vec!["double_lock", "conflict_lock", "invalid_free"]
+        );
+        let metadata = envelope.metadata.unwrap();
+        assert_eq!(metadata, callbacks.metadata);
+        assert_eq!(metadata.lockbud_version, env!("CARGO_PKG_VERSION"));
+        assert!(humantime::parse_rfc3339(&metadata.analyzed_at).is_ok());
+        assert_eq!(callbacks.exit_code(), EXIT_DENIED);
+    }
+
     #[test]
     fn test_report_stats() {
-        assert_eq!(report_stats("dummy", &[]), format!("crate {} contains bugs: {{ probably: {}, possibly: {} }}, conflictlock: {{ probably: {}, possibly: {} }}, condvar_deadlock: {{ probably: {}, possibly: {} }}, atomicity_violation: {{ possibly: {} }}, invalid_free: {{ possibly: {} }}, use_after_free: {{ possibly: {} }}", "dummy", 0, 0, 0, 0, 0, 0, 0, 0, 0));
+        assert_eq!(report_stats("dummy", &[]), format!("crate {} contains bugs: {{ probably: {}, possibly: {} }}, conflictlock: {{ probably: {}, possibly: {} }}, condvar_deadlock: {{ probably: {}, possibly: {} }}, condvar_multiple_mutexes: {{ possibly: {} }}, await_holding_lock: {{ possibly: {} }}, block_on_holding_lock: {{ possibly: {} }}, pool_self_join: {{ possibly: {} }}, rayon_deadlock: {{ possibly: {} }}, rendezvous_deadlock: {{ possibly: {} }}, once_reentrancy: {{ possibly: {} }}, once_init_deadlock: {{ possibly: {} }}, lock_guard_leaked: {{ possibly: {} }}, double_unlock: {{ possibly: {} }}, use_after_unlock: {{ possibly: {} }}, implicit_release_order: {{ possibly: {} }}, send_hazard: {{ possibly: {} }}, hot_path_lock: {{ possibly: {} }}, discarded_guard: {{ possibly: {} }}, lock_coverage: {{ possibly: {} }}, atomicity_violation: {{ possibly: {} }}, invalid_free: {{ possibly: {} }}, use_after_free: {{ possibly: {} }}, double_free: {{ possibly: {} }}, panic_in_drop: {{ probably: {} }}, panic_holding_lock: {{ possibly: {} }}, refcell_double_borrow: {{ probably: {}, possibly: {} }}, panic_site: {{ possibly: {} }}, plugin: {}", "dummy", 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0));
     }
 }
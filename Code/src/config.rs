@@ -0,0 +1,173 @@
+//! The config file given by `--config {path}`, in JSON, e.g.,
+//! ```json
+//! {
+//!     "pools": [
+//!         {
+//!             "name": "my_pool::ThreadPool",
+//!             "install": ["my_pool::ThreadPool::install"],
+//!             "wait": ["my_pool::ScopeHandle::wait"],
+//!             "schedules_into_same_pool": true
+//!         }
+//!     ],
+//!     "levels": { "double_lock": "deny", "condvar": "warn", "memory": "allow" },
+//!     "locks": [
+//!         { "name": "MyMutex", "crate": "my_lock", "guard": "MutexGuard", "access": "mutex" }
+//!     ]
+//! }
+//! ```
+//! The API paths are without generic args, and the paths in the crate being detected may omit the crate name.
+//! The `levels` map report kinds to `allow`, `warn` (the default), or `deny`.
+//! A kind is either a report kind (e.g., `conflict_lock`) or a group (`deadlock` or `memory`),
+//! and the report kind takes precedence over its group.
+//! The `locks` declare lockguard patterns in addition to the built-in ones, see `lock_registry.rs`.
+//! The `plugins` configure the custom checks to run, keyed by their names, see `plugin/mod.rs`, e.g.,
+//! `"plugins": { "forbidden_lock": [{ "lock": "app::CONFIG", "from": "app::handlers" }] }`.
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::detector::report::{Level, Report};
+use crate::interest::concurrency::lock_registry::GuardPattern;
+use crate::plugin;
+
+/// The keys allowed in `levels` (and `--deny`).
+pub const LEVEL_KEYS: [&str; 31] = [
+    "double_lock",
+    "conflict_lock",
+    "condvar",
+    "condvar_multiple_mutexes",
+    "await_holding_lock",
+    "block_on_holding_lock",
+    "pool_self_join",
+    "rayon_deadlock",
+    "rendezvous_deadlock",
+    "once_reentrancy",
+    "once_init_deadlock",
+    "lock_guard_leaked",
+    "double_unlock",
+    "use_after_unlock",
+    "implicit_release_order",
+    "send_hazard",
+    "hot_path_lock",
+    "discarded_guard",
+    "lock_coverage",
+    "atomicity_violation",
+    "invalid_free",
+    "use_after_free",
+    "double_free",
+    "panic_in_drop",
+    "panic_holding_lock",
+    "refcell_double_borrow",
+    "panic_site",
+    "plugin",
+    "deadlock",
+    "memory",
+    "panic",
+];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
+    #[serde(default)]
+    pub levels: BTreeMap<String, Level>,
+    /// The lockguard patterns, see `lock_registry.rs`.
+    #[serde(default)]
+    pub locks: Vec<GuardPattern>,
+    /// The settings of the plugins to run, keyed by their names, see `plugin/mod.rs`.
+    #[serde(default)]
+    pub plugins: BTreeMap<String, serde_json::Value>,
+}
+
+/// A custom thread pool.
+/// `install` runs a closure on the pool, and `wait` blocks on the completion of work scheduled into the pool.
+/// If `schedules_into_same_pool`, the work waited for is scheduled into the same pool as the waiting task,
+/// thus waiting in a closure running on the pool deadlocks when the pool is single-threaded.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    pub name: String,
+    #[serde(default)]
+    pub install: Vec<String>,
+    #[serde(default)]
+    pub wait: Vec<String>,
+    #[serde(default)]
+    pub schedules_into_same_pool: bool,
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let s = std::fs::read_to_string(path)?;
+        Self::from_str(&s)
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, Box<dyn Error>> {
+        let config: Self = serde_json::from_str(s)?;
+        if let Some(key) = config
+            .levels
+            .keys()
+            .find(|key| !LEVEL_KEYS.contains(&key.as_str()))
+        {
+            return Err(format!(
+                "unknown kind `{}` in levels, expected one of {}",
+                key,
+                LEVEL_KEYS.join(", ")
+            )
+            .into());
+        }
+        plugin::registered_plugins(&config.plugins)?;
+        Ok(config)
+    }
+
+    /// The effective level of the report.
+    pub fn level_of(&self, report: &Report) -> Level {
+        report
+            .level_keys()
+            .iter()
+            .find_map(|key| self.levels.get(*key).copied())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_str() {
+        let config = Config::from_str(
+            r#"{"pools": [{"name": "my_pool::ThreadPool", "install": ["my_pool::ThreadPool::install"], "wait": ["my_pool::ScopeHandle::wait"], "schedules_into_same_pool": true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.pools.len(), 1);
+        let pool = &config.pools[0];
+        assert_eq!(pool.name, "my_pool::ThreadPool");
+        assert_eq!(
+            pool.install,
+            vec!["my_pool::ThreadPool::install".to_owned()]
+        );
+        assert_eq!(pool.wait, vec!["my_pool::ScopeHandle::wait".to_owned()]);
+        assert!(pool.schedules_into_same_pool);
+        let config = Config::from_str(r#"{"pools": [{"name": "my_pool::ThreadPool"}]}"#).unwrap();
+        assert!(!config.pools[0].schedules_into_same_pool);
+        assert!(Config::from_str("{}").unwrap().pools.is_empty());
+        assert!(Config::from_str(r#"{"pool": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_levels() {
+        let config = Config::from_str(
+            r#"{"levels": {"double_lock": "deny", "deadlock": "warn", "memory": "allow"}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.levels.len(), 3);
+        assert_eq!(config.levels["double_lock"], Level::Deny);
+        assert_eq!(config.levels["deadlock"], Level::Warn);
+        assert_eq!(config.levels["memory"], Level::Allow);
+        assert!(Config::from_str("{}").unwrap().levels.is_empty());
+        assert!(Config::from_str(r#"{"levels": {"doublelock": "deny"}}"#).is_err());
+        assert!(Config::from_str(r#"{"levels": {"double_lock": "error"}}"#).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 //! Detect atomicity violation caused by misuse of atomic variables.
 //! Currently only support the following two patterns:
-//! ```no_run
+//! ```ignore
 //! // atomic::store is control dep on atomic::load
 //! if atomic.load(order) == v1 {
 //!     atomic.store(v2, order);
@@ -24,7 +24,8 @@ use crate::analysis::controldep;
 use crate::analysis::datadep;
 use crate::analysis::defuse;
 use crate::analysis::pointsto::{AliasAnalysis, AliasId, ApproximateAliasKind};
-use crate::detector::report::{Report, ReportContent};
+use crate::detector::config::DetectorConfig;
+use crate::detector::report::{Report, ReportContent, ALIAS_CONFIDENCE_UNKNOWN};
 use crate::interest::concurrency::atomic::AtomicApi;
 use report::AtomicityViolationDiagnosis;
 
@@ -33,11 +34,25 @@ use std::collections::BTreeSet;
 
 pub struct AtomicityViolationDetector<'tcx> {
     tcx: TyCtxt<'tcx>,
+    /// The enabled detectors and their parameters.
+    config: DetectorConfig,
+    /// Report the atomics with unknown alias results as possibly.
+    paranoid: bool,
 }
 
 impl<'tcx> AtomicityViolationDetector<'tcx> {
-    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
-        Self { tcx }
+    pub fn new(tcx: TyCtxt<'tcx>, config: DetectorConfig) -> Self {
+        Self {
+            tcx,
+            config,
+            paranoid: false,
+        }
+    }
+
+    /// Treat the unknown alias results of the atomics as possibly under `--paranoid`,
+    /// marking the diagnoses by `alias_confidence`.
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
     }
 
     /// Collect atomic APIs.
@@ -68,7 +83,8 @@ impl<'tcx> AtomicityViolationDetector<'tcx> {
         let mut atomic_writes = FxHashMap::default();
         let mut atomic_read_writes = FxHashMap::default();
         for (instance_id, atomic_api) in atomic_apis {
-            let callers = callgraph.callers(instance_id);
+            let mut callers = callgraph.callers(instance_id);
+            callers.retain(|caller| callgraph.is_focused(*caller));
             match atomic_api {
                 AtomicApi::Read => atomic_reads.insert(instance_id, callers),
                 AtomicApi::Write => atomic_writes.insert(instance_id, callers),
@@ -108,20 +124,22 @@ impl<'tcx> AtomicityViolationDetector<'tcx> {
                         callsite_locations(callgraph, *caller, *atomic_write).unwrap();
                     for read_callsite in read_callsites {
                         for write_callsite in &write_callsites {
-                            let dep_kind = match atomic_uses_influences(
+                            let (dep_kind, unknown_alias) = match atomic_uses_influences(
                                 read_callsite,
                                 *write_callsite,
                                 (*caller, body),
                                 alias_analysis,
+                                self.paranoid,
                                 &mut uses_cache,
                                 &data_deps,
                                 &control_deps,
                             ) {
-                                Some(dep_kind) => dep_kind,
+                                Some(influence) => influence,
                                 None => {
                                     continue;
                                 }
                             };
+                            // Not paranoid, since the read-write callsites only suppress the reports.
                             if !read_write_callsites.iter().any(|read_write_callsite| {
                                 matches!(
                                     atomic_uses_influences(
@@ -129,11 +147,13 @@ impl<'tcx> AtomicityViolationDetector<'tcx> {
                                         *write_callsite,
                                         (*caller, body),
                                         alias_analysis,
+                                        false,
                                         &mut uses_cache,
                                         &data_deps,
                                         &control_deps,
                                     ),
-                                    Some(DependenceKind::Control) | Some(DependenceKind::Both)
+                                    Some((DependenceKind::Control, _))
+                                        | Some((DependenceKind::Both, _))
                                 )
                             }) {
                                 let fn_name = self.tcx.def_path_str(
@@ -153,6 +173,9 @@ impl<'tcx> AtomicityViolationDetector<'tcx> {
                                     atomic_reader,
                                     atomic_writer,
                                     dep_kind,
+                                    alias_confidence: unknown_alias
+                                        .then(|| ALIAS_CONFIDENCE_UNKNOWN.to_owned()),
+                                    snippet: Default::default(),
                                 };
                                 let report_content = ReportContent::new(
                                     "AtomicityViolation".to_owned(),
@@ -168,6 +191,7 @@ impl<'tcx> AtomicityViolationDetector<'tcx> {
                 }
             }
         }
+        self.config.retain(&mut reports);
         reports
     }
 }
@@ -219,16 +243,18 @@ enum DependenceKind {
 }
 
 /// Returns the uses locations of `influencer` that may data or control influence `influencee`
-/// For
+/// Also returns whether the atomics only alias under `paranoid`, i.e., their alias is Unknown.
+#[allow(clippy::too_many_arguments)]
 fn atomic_uses_influences<'tcx>(
     influencer: Location,
     influencee: Location,
     instance_body: (InstanceId, &Body<'tcx>),
     alias_analysis: &mut AliasAnalysis<'_, 'tcx>,
+    paranoid: bool,
     uses_cache: &mut FxHashMap<Local, BTreeSet<Location>>,
     data_deps: &datadep::DataDeps,
     control_deps: &controldep::ControlDeps<BasicBlock>,
-) -> Option<DependenceKind> {
+) -> Option<(DependenceKind, bool)> {
     let (instance_id, body) = instance_body;
     let (influencer_arg, influencer_dest) = first_arg_and_dest(influencer, body)?;
     let (influencee_arg, value_arg) = first_two_args(influencee, body)?;
@@ -242,9 +268,10 @@ fn atomic_uses_influences<'tcx>(
         local: influencee_arg.local,
     };
     let alias_kind = alias_analysis.alias(influencer_id, influencee_id);
-    if alias_kind < ApproximateAliasKind::Possibly {
+    if alias_kind.or_paranoid(paranoid) < ApproximateAliasKind::Possibly {
         return None;
     }
+    let unknown_alias = alias_kind == ApproximateAliasKind::Unknown;
     let local = influencer_dest.local;
     // locals that is data dep on influencer
     let deps = datadep::all_data_dep_on(local, data_deps);
@@ -266,17 +293,18 @@ fn atomic_uses_influences<'tcx>(
                 .filter(|use_loc| controldep::influences(*use_loc, influencee, control_deps)),
         );
     }
-    if use_locs_influenced.is_empty() {
+    let dep_kind = if use_locs_influenced.is_empty() {
         if is_data_dep {
-            Some(DependenceKind::Data)
+            DependenceKind::Data
         } else {
-            None
+            return None;
         }
     } else if is_data_dep {
-        Some(DependenceKind::Both)
+        DependenceKind::Both
     } else {
-        Some(DependenceKind::Control)
-    }
+        DependenceKind::Control
+    };
+    Some((dep_kind, unknown_alias))
 }
 
 /// CallSite Locations from source to target
@@ -0,0 +1,268 @@
+//! The detectors enabled by `-k` and `--detectors`, and their parameters set by `--set`.
+//! The detectors are named by their report kinds (the keys of `levels` in `config.rs`, e.g., `conflict_lock`),
+//! and a group (`deadlock`, `atomicity_violation`, `memory`, or `panic`) stands for all its detectors.
+//! The underscores in the names may be omitted, e.g., `doublelock`.
+//! `--detectors doublelock,condvar,-conflictlock` enables the detectors without `-`
+//! and disables the ones with `-`, starting from the detectors of `-k`.
+//! `--set detector.key=value` sets a parameter:
+//! `deadlock.max_callchains` and `deadlock.max_callchain_depth`, the limits on the callchains in deadlock diagnoses;
+//! `conflict_lock.thread_filter`, `false` to report conflictlock even if the locks are only acquired in the same thread;
+//! `lock_coverage.threshold`, the ratio (0 to 1) of the accesses of a field with the same lock held
+//! over which its accesses without the lock are reported, 0.8 by default;
+//! `{detector or group}.min_possibility`, `probably` to drop the possibly reports, e.g., of unsure alias results.
+//! The informational detectors in `OPT_IN_DETECTORS` are off by default and only enabled by their names.
+use std::collections::BTreeSet;
+
+use crate::analysis::callgraph::CallChainLimits;
+use crate::detector::report::Report;
+
+/// The detectors and their groups.
+const DETECTORS: [(&str, &str); 28] = [
+    ("double_lock", "deadlock"),
+    ("conflict_lock", "deadlock"),
+    ("condvar", "deadlock"),
+    ("condvar_multiple_mutexes", "deadlock"),
+    ("await_holding_lock", "deadlock"),
+    ("block_on_holding_lock", "deadlock"),
+    ("pool_self_join", "deadlock"),
+    ("rayon_deadlock", "deadlock"),
+    ("rendezvous_deadlock", "deadlock"),
+    ("once_reentrancy", "deadlock"),
+    ("once_init_deadlock", "deadlock"),
+    ("lock_guard_leaked", "deadlock"),
+    ("double_unlock", "deadlock"),
+    ("use_after_unlock", "deadlock"),
+    ("implicit_release_order", "deadlock"),
+    ("send_hazard", "deadlock"),
+    ("hot_path_lock", "deadlock"),
+    ("discarded_guard", "deadlock"),
+    ("lock_coverage", "deadlock"),
+    ("atomicity_violation", "atomicity_violation"),
+    ("invalid_free", "memory"),
+    ("use_after_free", "memory"),
+    ("double_free", "memory"),
+    ("panic_in_drop", "panic"),
+    ("panic_holding_lock", "panic"),
+    ("refcell_double_borrow", "panic"),
+    ("panic_site", "panic"),
+    ("plugin", "deadlock"),
+];
+
+/// The detectors not enabled by their groups, e.g., `--detectors implicit_release_order`.
+const OPT_IN_DETECTORS: [&str; 1] = ["implicit_release_order"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetectorConfig {
+    enabled: BTreeSet<&'static str>,
+    /// The detectors only reporting the probably bugs.
+    probably_only: BTreeSet<&'static str>,
+    /// The limits on the callchains in deadlock diagnoses.
+    pub callchain_limits: CallChainLimits,
+    /// Only report conflictlock when the relations may run on different threads.
+    pub thread_filter: bool,
+    /// The percentage of the accesses of a field with the same lock held over which `lock_coverage` reports.
+    pub lock_coverage_threshold: u32,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self::new(&["deadlock"])
+    }
+}
+
+impl DetectorConfig {
+    /// Enable the detectors of the groups.
+    pub fn new(groups: &[&str]) -> Self {
+        Self {
+            enabled: DETECTORS
+                .iter()
+                .filter(|(detector, group)| {
+                    groups.contains(group) && !OPT_IN_DETECTORS.contains(detector)
+                })
+                .map(|(detector, _)| *detector)
+                .collect(),
+            probably_only: BTreeSet::new(),
+            callchain_limits: CallChainLimits {
+                max_paths: 8,
+                max_depth: 20,
+            },
+            thread_filter: true,
+            lock_coverage_threshold: 80,
+        }
+    }
+
+    /// The detectors named by a detector or a group, where the underscores may be omitted.
+    fn resolve(name: &str) -> Result<Vec<&'static str>, String> {
+        let normalized = name.to_lowercase().replace('_', "");
+        let matches = |key: &str| key.replace('_', "") == normalized;
+        if let Some((detector, _)) = DETECTORS.iter().find(|(detector, _)| matches(detector)) {
+            return Ok(vec![detector]);
+        }
+        let detectors = DETECTORS
+            .iter()
+            .filter(|(_, group)| matches(group))
+            .map(|(detector, _)| *detector)
+            .collect::<Vec<_>>();
+        if detectors.is_empty() {
+            return Err(format!(
+                "UnknownDetector: {}, expected one of {}, deadlock, memory",
+                name,
+                DETECTORS.map(|(detector, _)| detector).join(", ")
+            ));
+        }
+        Ok(detectors)
+    }
+
+    /// Enable the detector (or the group) of a `--detectors` value, or disable it if prefixed by `-`.
+    pub fn toggle(&mut self, value: &str) -> Result<(), String> {
+        let (name, enable) = match value.strip_prefix('-') {
+            Some(name) => (name, false),
+            None => (value.strip_prefix('+').unwrap_or(value), true),
+        };
+        let detectors = Self::resolve(name)?;
+        // A group only enables its detectors not in `OPT_IN_DETECTORS`.
+        let is_group = detectors.len() > 1;
+        for detector in detectors {
+            if enable && is_group && OPT_IN_DETECTORS.contains(&detector) {
+                continue;
+            }
+            if enable {
+                self.enabled.insert(detector);
+            } else {
+                self.enabled.remove(detector);
+            }
+        }
+        Ok(())
+    }
+
+    /// Set a parameter by a `--set` value `detector.key=value`.
+    pub fn set(&mut self, param: &str) -> Result<(), String> {
+        let invalid = || format!("InvalidDetectorParam: {}", param);
+        let (name, value) = param.split_once('=').ok_or_else(invalid)?;
+        let (detector, key) = name.rsplit_once('.').ok_or_else(invalid)?;
+        let detectors = Self::resolve(detector)?;
+        let is_group = |group: &str| detector.to_lowercase() == group;
+        match key {
+            "max_callchains" if is_group("deadlock") => {
+                self.callchain_limits.max_paths = value.parse().map_err(|_| invalid())?;
+            }
+            "max_callchain_depth" if is_group("deadlock") => {
+                self.callchain_limits.max_depth = value.parse().map_err(|_| invalid())?;
+            }
+            "thread_filter" if detectors == ["conflict_lock"] => {
+                self.thread_filter = value.parse().map_err(|_| invalid())?;
+            }
+            "threshold" if detectors == ["lock_coverage"] => {
+                let ratio = value.parse::<f64>().map_err(|_| invalid())?;
+                if !(0.0..=1.0).contains(&ratio) {
+                    return Err(invalid());
+                }
+                self.lock_coverage_threshold = (ratio * 100.0).round() as u32;
+            }
+            "min_possibility" => match value.to_lowercase().as_str() {
+                "probably" => self.probably_only.extend(detectors),
+                "possibly" => {
+                    for detector in detectors {
+                        self.probably_only.remove(detector);
+                    }
+                }
+                _ => return Err(invalid()),
+            },
+            _ => return Err(format!("UnknownDetectorParam: {}", name)),
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, detector: &str) -> bool {
+        self.enabled.contains(detector)
+    }
+
+    /// Whether any detector of the group is enabled.
+    pub fn is_group_enabled(&self, group: &str) -> bool {
+        DETECTORS
+            .iter()
+            .any(|(detector, g)| *g == group && self.is_enabled(detector))
+    }
+
+    /// Drop the reports of the disabled detectors,
+    /// and the possibly reports of the detectors with `min_possibility` probably.
+    pub fn retain(&self, reports: &mut Vec<Report>) {
+        reports.retain(|report| {
+            let detector = report.level_keys()[0];
+            self.is_enabled(detector)
+                && (!self.probably_only.contains(detector) || report.possibility() == "Probably")
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_detectors() {
+        let mut config = DetectorConfig::default();
+        assert!(config.is_enabled("conflict_lock"));
+        assert!(!config.is_group_enabled("memory"));
+        for value in ["doublelock", "-conflictlock", "atomicity_violation"] {
+            config.toggle(value).unwrap();
+        }
+        assert!(config.is_enabled("double_lock"));
+        assert!(!config.is_enabled("conflict_lock"));
+        assert!(config.is_enabled("condvar"));
+        assert!(config.is_enabled("atomicity_violation"));
+        assert!(!config.is_enabled("implicit_release_order"));
+        config.toggle("implicitreleaseorder").unwrap();
+        assert!(config.is_enabled("implicit_release_order"));
+        config.toggle("-deadlock").unwrap();
+        assert!(!config.is_group_enabled("deadlock"));
+        config.toggle("deadlock").unwrap();
+        assert!(config.is_enabled("double_lock") && !config.is_enabled("implicit_release_order"));
+        config.toggle("-deadlock").unwrap();
+        config.toggle("+memory").unwrap();
+        assert!(
+            config.is_enabled("invalid_free")
+                && config.is_enabled("use_after_free")
+                && config.is_enabled("double_free")
+        );
+        let err = config.toggle("-livelock").unwrap_err();
+        assert!(err.starts_with("UnknownDetector: livelock, expected one of double_lock"));
+    }
+
+    #[test]
+    fn test_set_params() {
+        let mut config = DetectorConfig::new(&[]);
+        assert!(!config.is_group_enabled("deadlock"));
+        config.set("deadlock.max_callchains=2").unwrap();
+        config.set("deadlock.max_callchain_depth=5").unwrap();
+        assert_eq!(
+            config.callchain_limits,
+            CallChainLimits {
+                max_paths: 2,
+                max_depth: 5
+            }
+        );
+        config.set("conflictlock.thread_filter=false").unwrap();
+        assert!(!config.thread_filter);
+        assert_eq!(config.lock_coverage_threshold, 80);
+        config.set("lockcoverage.threshold=0.9").unwrap();
+        assert_eq!(config.lock_coverage_threshold, 90);
+        assert!(config.set("lock_coverage.threshold=90").is_err());
+        config.set("deadlock.min_possibility=probably").unwrap();
+        config.set("condvar.min_possibility=possibly").unwrap();
+        assert!(config.probably_only.contains("double_lock"));
+        assert!(!config.probably_only.contains("condvar"));
+        assert!(config.set("deadlock.max_callchains=many").is_err());
+        assert!(config.set("deadlock.max_callchains").is_err());
+        assert!(config.set("doublelock.thread_filter=false").is_err());
+        assert!(config.set("memory.min_possibility=unknown").is_err());
+        assert_eq!(
+            config.set("doublelock.max_depth=1"),
+            Err("UnknownDetectorParam: doublelock.max_depth".to_owned())
+        );
+        assert!(config
+            .set("livelock.min_possibility=probably")
+            .unwrap_err()
+            .starts_with("UnknownDetector: livelock"));
+    }
+}
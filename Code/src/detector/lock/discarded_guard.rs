@@ -0,0 +1,97 @@
+//! Report the locks acquired by `let _ = ..`, i.e., `DiscardedGuard`.
+//! The wildcard pattern binds nothing, so the lockguard (or the `LockResult` of it) is dropped at the end of the statement
+//! and the lock is released right after the acquisition, unlike `let _guard = ..` holding it to the end of the scope.
+//! Both spellings look alike in MIR (a temporary lockguard vs. a user variable killed by the drop at the end of its scope),
+//! so the `let` statements are found in HIR like rustc's `let_underscore_lock`,
+//! which only covers the std locks while the lockguards here are those of `LockRegistry` and the guard newtypes.
+//! The gen/kill of the lockguards is not affected: the discarded lockguard is killed at its statement.
+//! The `RefCell` borrows discarded by `let _ = ..` are harmless, so they are not reported.
+//! The reports are informational.
+extern crate rustc_hir;
+extern crate rustc_span;
+
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_hir::{Local, PatKind};
+use rustc_middle::ty::{self, TyCtxt, TypeckResults};
+use rustc_span::{sym, Span};
+
+use super::report::DiscardedGuardDiagnosis;
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::{GuardNewtypes, LockGuardTy};
+
+struct DiscardedGuardVisitor<'a, 'tcx> {
+    guard_newtypes: &'a GuardNewtypes,
+    typeck_results: &'tcx TypeckResults<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    /// (lockguard type, span of the initializer)
+    discarded: Vec<(LockGuardTy<'tcx>, Span)>,
+}
+
+impl<'a, 'tcx> DiscardedGuardVisitor<'a, 'tcx> {
+    /// The lockguard type of `ty`, or of the first type arg of `ty` if it is a `Result` or an `Option`,
+    /// e.g., `LockResult<MutexGuard<T>>` of `let _ = m.lock();` or `Option<MutexGuard<T>>` of `try_lock`.
+    fn lockguard_ty(&self, ty: ty::Ty<'tcx>) -> Option<LockGuardTy<'tcx>> {
+        if let Some(lockguard_ty) = self.guard_newtypes.lockguard_ty(ty, self.tcx) {
+            return Some(lockguard_ty);
+        }
+        match ty.kind() {
+            ty::TyKind::Adt(adt_def, substs)
+                if self.tcx.is_diagnostic_item(sym::Result, adt_def.did())
+                    || self.tcx.is_diagnostic_item(sym::Option, adt_def.did()) =>
+            {
+                self.guard_newtypes
+                    .lockguard_ty(substs.types().next()?, self.tcx)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a, 'tcx> Visitor<'tcx> for DiscardedGuardVisitor<'a, 'tcx> {
+    fn visit_local(&mut self, local: &'tcx Local<'tcx>) {
+        if let (PatKind::Wild, Some(init)) = (&local.pat.kind, local.init) {
+            let init_ty = self.typeck_results.expr_ty(init);
+            match self.lockguard_ty(init_ty) {
+                Some(lockguard_ty) if !lockguard_ty.is_borrow() => {
+                    self.discarded.push((lockguard_ty, init.span));
+                }
+                _ => {}
+            }
+        }
+        intravisit::walk_local(self, local);
+    }
+}
+
+/// Find the `let _ = ..` discarding lockguards in the bodies of the local crate.
+/// The closures are bodies of their own, named after their enclosing fns.
+pub fn detect_discarded_guards(guard_newtypes: &GuardNewtypes, tcx: TyCtxt<'_>) -> Vec<Report> {
+    let mut reports = Vec::new();
+    for def_id in tcx.hir().body_owners() {
+        let body = tcx.hir().body(tcx.hir().body_owned_by(def_id));
+        let mut visitor = DiscardedGuardVisitor {
+            guard_newtypes,
+            typeck_results: tcx.typeck(def_id),
+            tcx,
+            discarded: Vec::new(),
+        };
+        visitor.visit_body(body);
+        let fn_name = tcx.def_path_str(tcx.typeck_root_def_id(def_id.to_def_id()));
+        for (lockguard_ty, span) in visitor.discarded {
+            let diagnosis = DiscardedGuardDiagnosis::new(
+                fn_name.clone(),
+                format!("{:?}", lockguard_ty),
+                format!("{:?}", span),
+            );
+            reports.push(Report::DiscardedGuard(ReportContent::new(
+                "DiscardedGuard".to_owned(),
+                "Possibly".to_owned(),
+                diagnosis,
+                "The lock is acquired and immediately released since `let _ = ..` drops the lockguard at once; did you mean to hold it? \
+                 Bind the lockguard to a named variable (e.g., `let _guard = ..`) to hold the lock to the end of the scope, \
+                 or write `drop(..)` if releasing it at once is intended"
+                    .to_owned(),
+            )));
+        }
+    }
+    reports
+}
@@ -0,0 +1,209 @@
+//! Report the misuses of the raw unlock APIs, e.g., parking_lot's `force_unlock`, with a live lockguard of the same lock:
+//! `DoubleUnlock` if the automatic drop of the lockguard is reachable from the unlock, unlocking the lock again,
+//! and `UseAfterUnlock` if a use of the lockguard (or of the locals it is moved into, e.g., a `ManuallyDrop`) is,
+//! accessing the data no longer protected by the lock.
+//! A hand-rolled guard adapter moving the lockguard into a `ManuallyDrop` (or `mem::forget`) drops nothing,
+//! so it is fine as long as the data is not touched after the unlock.
+//! The receiver of the unlock is connected to the lockguards of the same fn by AliasAnalysis,
+//! since a lockguard points to its lock (see `pointsto`).
+extern crate rustc_hash;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_middle::mir::visit::{MutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{Body, Local, Location, Place, TerminatorKind};
+use rustc_middle::ty::{TyCtxt, TyKind};
+
+use super::guard_leak::moved_lockguard;
+use super::report::ForceUnlockDiagnosis;
+use crate::analysis::callgraph::{CallGraph, InstanceId};
+use crate::analysis::pointsto::{AliasAnalysis, AliasId, ApproximateAliasKind};
+use crate::detector::memory::{collect_callsite_args0, is_reachable};
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::{LockGuardId, LockGuardMap};
+use crate::interest::memory::ownership;
+
+/// The callsites of the raw unlock APIs and their receivers (the locks) in each caller.
+pub type ForceUnlocks<'tcx> = FxHashMap<InstanceId, Vec<(Location, Place<'tcx>)>>;
+
+/// `force_unlock`, `force_unlock_fair`, `force_unlock_read`, `force_unlock_write`, etc. of lock_api,
+/// which unlock the lock without a lockguard.
+fn is_force_unlock(instance_name: &str) -> bool {
+    instance_name.starts_with("force_unlock")
+}
+
+pub fn collect_force_unlocks<'tcx>(
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> ForceUnlocks<'tcx> {
+    collect_callsite_args0(callgraph, tcx, |instance| {
+        tcx.opt_item_name(instance.def_id())
+            .map_or(false, |name| is_force_unlock(name.as_str()))
+    })
+}
+
+/// Whether the lockguard may be of the lock unlocked by the receiver.
+pub fn unlocks_lockguard(
+    caller_id: InstanceId,
+    receiver: Place<'_>,
+    lockguard_id: LockGuardId,
+    alias_analysis: &mut AliasAnalysis<'_, '_>,
+) -> ApproximateAliasKind {
+    let receiver = AliasId {
+        instance_id: caller_id,
+        local: receiver.local,
+    };
+    alias_analysis.alias(lockguard_id.into(), receiver)
+}
+
+pub fn detect_force_unlock_misuse<'a, 'tcx>(
+    force_unlocks: &ForceUnlocks<'tcx>,
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &'a CallGraph<'tcx>,
+    alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<Report> {
+    let mut callers = force_unlocks.iter().collect::<Vec<_>>();
+    callers.sort_by_key(|(caller_id, _)| **caller_id);
+    // The diagnoses of the monomorphized instances of the same generic fns are the same.
+    let mut diagnoses = Vec::new();
+    for (caller_id, unlocks) in callers {
+        let body = match callgraph.index_to_instance(*caller_id) {
+            Some(caller) => tcx.instance_mir(caller.instance().def),
+            None => continue,
+        };
+        let mut caller_lockguards = lockguards
+            .iter()
+            .filter(|(id, _)| id.instance_id == *caller_id)
+            .collect::<Vec<_>>();
+        caller_lockguards.sort_by_key(|(id, _)| id.local);
+        for (lockguard_id, info) in caller_lockguards {
+            let holders = holders(body, lockguard_id.local);
+            let mut holder_uses = HolderUses::new(&holders, body, tcx);
+            holder_uses.visit_body(body);
+            for (unlock_loc, receiver) in unlocks {
+                // The lockguard acquired before the unlock.
+                if !info.gen_locs.iter().any(|gen_loc| {
+                    gen_loc != unlock_loc && is_reachable(*gen_loc, *unlock_loc, body)
+                }) {
+                    continue;
+                }
+                let possibility =
+                    match unlocks_lockguard(*caller_id, *receiver, *lockguard_id, alias_analysis) {
+                        ApproximateAliasKind::Probably => "Probably",
+                        ApproximateAliasKind::Possibly => "Possibly",
+                        _ => continue,
+                    };
+                let after_unlock =
+                    |loc: &&Location| *loc != unlock_loc && is_reachable(*unlock_loc, **loc, body);
+                let diagnosis = |later_loc: &Location| {
+                    let unlock_api = match &body[unlock_loc.block].terminator().kind {
+                        TerminatorKind::Call { func, .. } => match func.ty(body, tcx).kind() {
+                            TyKind::FnDef(def_id, _) => tcx.def_path_str(*def_id),
+                            _ => String::new(),
+                        },
+                        _ => String::new(),
+                    };
+                    ForceUnlockDiagnosis::new(
+                        tcx.def_path_str(tcx.typeck_root_def_id(body.source.def_id())),
+                        format!("{:?}", info.lockguard_ty),
+                        format!("{:?}", info.span),
+                        unlock_api,
+                        format!("{:?}", body.source_info(*unlock_loc).span),
+                        format!("{:?}", body.source_info(*later_loc).span),
+                    )
+                };
+                if let Some(drop_loc) = holder_uses.drops.iter().find(after_unlock) {
+                    diagnoses.push((true, possibility, diagnosis(drop_loc)));
+                }
+                if let Some(use_loc) = holder_uses.uses.iter().find(after_unlock) {
+                    diagnoses.push((false, possibility, diagnosis(use_loc)));
+                }
+            }
+        }
+    }
+    let mut reports = Vec::new();
+    let mut seen = FxHashSet::default();
+    for (double_unlock, possibility, diagnosis) in diagnoses {
+        if !seen.insert((
+            double_unlock,
+            diagnosis.later_span.clone(),
+            diagnosis.unlock_span.clone(),
+        )) {
+            continue;
+        }
+        reports.push(if double_unlock {
+            Report::DoubleUnlock(ReportContent::new(
+                "DoubleUnlock".to_owned(),
+                possibility.to_owned(),
+                diagnosis,
+                "The lock is unlocked without its lockguard, which unlocks it again when dropped; move the lockguard into `ManuallyDrop` or `mem::forget` it before unlocking".to_owned(),
+            ))
+        } else {
+            Report::UseAfterUnlock(ReportContent::new(
+                "UseAfterUnlock".to_owned(),
+                possibility.to_owned(),
+                diagnosis,
+                "The lockguard is used after the lock is unlocked without it, accessing the data no longer protected by the lock".to_owned(),
+            ))
+        });
+    }
+    reports
+}
+
+/// The lockguard and the locals it is moved into, e.g., `ManuallyDrop::new(guard)` or `Box::new(guard)`.
+pub fn holders(body: &Body<'_>, lockguard: Local) -> FxHashSet<Local> {
+    body.local_decls
+        .indices()
+        .filter(|local| moved_lockguard(body, *local, |local| local == lockguard).is_some())
+        .collect()
+}
+
+/// The (non-cleanup) drops and the uses of the holders of a lockguard, in the order of locations.
+/// The moves into `mem::forget` or `ManuallyDrop::new` touch nothing, so they are not uses.
+struct HolderUses<'a, 'b, 'tcx> {
+    holders: &'a FxHashSet<Local>,
+    body: &'b Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    drops: Vec<Location>,
+    uses: Vec<Location>,
+}
+
+impl<'a, 'b, 'tcx> HolderUses<'a, 'b, 'tcx> {
+    fn new(holders: &'a FxHashSet<Local>, body: &'b Body<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
+        Self {
+            holders,
+            body,
+            tcx,
+            drops: Vec::new(),
+            uses: Vec::new(),
+        }
+    }
+
+    fn is_leak(&self, location: Location) -> bool {
+        match &self.body[location.block].terminator().kind {
+            TerminatorKind::Call { func, .. }
+                if location == self.body.terminator_loc(location.block) =>
+            {
+                match func.ty(self.body, self.tcx).kind() {
+                    TyKind::FnDef(def_id, _) => ownership::is_leak(*def_id, self.tcx),
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a, 'b, 'tcx> Visitor<'tcx> for HolderUses<'a, 'b, 'tcx> {
+    fn visit_local(&mut self, local: Local, context: PlaceContext, location: Location) {
+        if !self.holders.contains(&local) || self.body[location.block].is_cleanup {
+            return;
+        }
+        match context {
+            PlaceContext::NonUse(_) => {}
+            PlaceContext::MutatingUse(MutatingUseContext::Drop) => self.drops.push(location),
+            _ if self.is_leak(location) => {}
+            _ => self.uses.push(location),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Report the lockguards leaked by `std::mem::forget`, `ManuallyDrop::new`, or `Box::leak`, i.e., `LockGuardLeaked`.
+//! A leaked lockguard is never dropped, so the lock is never released and every later acquisition of it blocks forever.
+//! The callsites of the leaking APIs are collected like the manual drops of the memory detectors (see `collect_callsite_args0`),
+//! and the lockguard leaked by a callsite is traced back from its first arg through the moves in the caller,
+//! e.g., `Box::leak(Box::new(guard))` or `mem::forget(Holder { guard })`.
+//! A fn returning a `ManuallyDrop` hands the lockguard to its caller on purpose (e.g., a raw-lock pattern
+//! unlocking it manually later), so its `ManuallyDrop::new` is not reported.
+//! Nor the leaks of the lockguards whose locks are unlocked by a raw unlock API in the same fn, e.g.,
+//! `mem::forget(guard)` followed by `force_unlock` (see `force_unlock.rs`).
+//! Nor the leaks of the lockguards already released by `ptr::drop_in_place` or `ManuallyDrop::drop` in the same fn,
+//! e.g., to unlock them early (see `toys/early-unlock`).
+//! Neither are the leaks in a fn returning a lockguard, e.g., `MutexGuard::map` or `RwLockWriteGuard::downgrade`
+//! of a custom lock, which forgets the old lockguard to hand the lock over to the returned one.
+extern crate rustc_hash;
+
+use rustc_hash::FxHashSet;
+use rustc_middle::mir::{
+    Body, Local, Operand, Rvalue, StatementKind, TerminatorKind, RETURN_PLACE,
+};
+use rustc_middle::ty::{GenericArgKind, TyCtxt, TyKind};
+
+use super::force_unlock::{holders, unlocks_lockguard, ForceUnlocks};
+use super::report::LockGuardLeakedDiagnosis;
+use crate::analysis::callgraph::CallGraph;
+use crate::analysis::pointsto::{AliasAnalysis, ApproximateAliasKind};
+use crate::detector::memory::collect_callsite_args0;
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::{GuardNewtypes, LockGuardId, LockGuardMap};
+use crate::interest::memory::ownership;
+
+pub fn detect_leaked_lockguards<'a, 'tcx>(
+    lockguards: &LockGuardMap<'tcx>,
+    guard_newtypes: &GuardNewtypes,
+    force_unlocks: &ForceUnlocks<'tcx>,
+    callgraph: &'a CallGraph<'tcx>,
+    alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<Report> {
+    let leaks = collect_callsite_args0(callgraph, tcx, |instance| {
+        ownership::is_leak(instance.def_id(), tcx)
+    });
+    let mut leaks = leaks.into_iter().collect::<Vec<_>>();
+    leaks.sort_by_key(|(caller_id, _)| *caller_id);
+    // The diagnoses of the monomorphized instances of the same generic fns are the same.
+    let mut diagnoses = Vec::new();
+    for (caller_id, callsites) in leaks {
+        let body = match callgraph.index_to_instance(caller_id) {
+            Some(caller) => tcx.instance_mir(caller.instance().def),
+            None => continue,
+        };
+        let return_tys = body.local_decls[RETURN_PLACE]
+            .ty
+            .walk()
+            .filter_map(|arg| match arg.unpack() {
+                GenericArgKind::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if return_tys
+            .iter()
+            .any(|ty| guard_newtypes.lockguard_ty(*ty, tcx).is_some())
+        {
+            continue;
+        }
+        let returns_manually_drop = return_tys.iter().any(|ty| {
+            ty.ty_adt_def()
+                .map_or(false, |adt_def| adt_def.is_manually_drop())
+        });
+        for (location, arg0) in callsites {
+            let leak_api = match &body[location.block].terminator().kind {
+                TerminatorKind::Call { func, .. } => match func.ty(body, tcx).kind() {
+                    TyKind::FnDef(def_id, _) => tcx.def_path_str(*def_id),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if returns_manually_drop && leak_api.starts_with("std::mem::ManuallyDrop") {
+                continue;
+            }
+            let is_lockguard = |local| lockguards.contains_key(&LockGuardId::new(caller_id, local));
+            let lockguard = arg0
+                .as_local()
+                .and_then(|local| moved_lockguard(body, local, is_lockguard))
+                .map(|local| original_lockguard(body, local, is_lockguard));
+            let lockguard_id = match lockguard {
+                Some(local) => LockGuardId::new(caller_id, local),
+                None => continue,
+            };
+            if is_explicitly_dropped(body, lockguard_id.local, tcx) {
+                continue;
+            }
+            let unlocked =
+                force_unlocks
+                    .get(&caller_id)
+                    .into_iter()
+                    .flatten()
+                    .any(|(_, receiver)| {
+                        matches!(
+                            unlocks_lockguard(caller_id, *receiver, lockguard_id, alias_analysis),
+                            ApproximateAliasKind::Probably | ApproximateAliasKind::Possibly
+                        )
+                    });
+            if unlocked {
+                continue;
+            }
+            let info = &lockguards[&lockguard_id];
+            let diagnosis = LockGuardLeakedDiagnosis::new(
+                tcx.def_path_str(tcx.typeck_root_def_id(body.source.def_id())),
+                format!("{:?}", info.lockguard_ty),
+                format!("{:?}", info.span),
+                leak_api,
+                format!("{:?}", body.source_info(location).span),
+            );
+            if !diagnoses.contains(&diagnosis) {
+                diagnoses.push(diagnosis);
+            }
+        }
+    }
+    diagnoses
+        .into_iter()
+        .map(|diagnosis| {
+            Report::LockGuardLeaked(ReportContent::new(
+                "LockGuardLeaked".to_owned(),
+                "Possibly".to_owned(),
+                diagnosis,
+                "The lockguard is never dropped, so the lock is never released and every later acquisition of it blocks forever"
+                    .to_owned(),
+            ))
+        })
+        .collect()
+}
+
+/// The lockguard the lockguard `local` is moved from, e.g., `guard` of `mem::forget(guard)`
+/// rather than the temporary lockguard moved into `forget`.
+fn original_lockguard(
+    body: &Body<'_>,
+    local: Local,
+    is_lockguard: impl Fn(Local) -> bool,
+) -> Local {
+    let mut local = local;
+    let mut visited = FxHashSet::default();
+    while visited.insert(local) {
+        let from = body
+            .basic_blocks
+            .iter()
+            .flat_map(|bb_data| &bb_data.statements)
+            .find_map(|stmt| match &stmt.kind {
+                StatementKind::Assign(box (lhs, Rvalue::Use(Operand::Move(from))))
+                    if lhs.as_local() == Some(local) =>
+                {
+                    from.as_local()
+                }
+                _ => None,
+            });
+        match from {
+            Some(from) if is_lockguard(from) => local = from,
+            _ => break,
+        }
+    }
+    local
+}
+
+/// Whether a holder of the lockguard is dropped in place by `ptr::drop_in_place` or `ManuallyDrop::drop`,
+/// which releases the lock before the holder is leaked.
+fn is_explicitly_dropped<'tcx>(body: &Body<'tcx>, lockguard: Local, tcx: TyCtxt<'tcx>) -> bool {
+    let holders = holders(body, lockguard);
+    body.basic_blocks
+        .iter_enumerated()
+        .any(|(bb, bb_data)| match &bb_data.terminator().kind {
+            TerminatorKind::Call { func, args, .. } => {
+                match (func.ty(body, tcx).kind(), args.as_slice()) {
+                    (TyKind::FnDef(def_id, _), [Operand::Move(arg) | Operand::Copy(arg)])
+                        if ownership::is_explicit_drop(*def_id, tcx) =>
+                    {
+                        arg.as_local()
+                            .and_then(|ptr| {
+                                ownership::pointee_place(body, ptr, body.terminator_loc(bb))
+                            })
+                            .map_or(false, |pointee| holders.contains(&pointee.local))
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        })
+}
+
+/// The lockguard moved into `local` (or `local` itself), traced back through the assignments `local = move x`,
+/// the aggregates `local = S { f: move x, .. }`, and the calls moving their first args `local = f(move x, ..)`.
+pub fn moved_lockguard(
+    body: &Body<'_>,
+    local: Local,
+    is_lockguard: impl Fn(Local) -> bool,
+) -> Option<Local> {
+    let moved = |operand: &Operand<'_>| match operand {
+        Operand::Move(place) => Some(place.local),
+        _ => None,
+    };
+    let mut worklist = vec![local];
+    let mut visited = FxHashSet::default();
+    while let Some(local) = worklist.pop() {
+        if !visited.insert(local) {
+            continue;
+        }
+        if is_lockguard(local) {
+            return Some(local);
+        }
+        for bb_data in body.basic_blocks.iter() {
+            for stmt in &bb_data.statements {
+                match &stmt.kind {
+                    StatementKind::Assign(box (lhs, rvalue)) if lhs.as_local() == Some(local) => {
+                        match rvalue {
+                            Rvalue::Use(operand) => worklist.extend(moved(operand)),
+                            Rvalue::Aggregate(_, operands) => {
+                                worklist.extend(operands.iter().filter_map(moved))
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let TerminatorKind::Call {
+                args, destination, ..
+            } = &bb_data.terminator().kind
+            {
+                if destination.as_local() == Some(local) {
+                    worklist.extend(args.get(0).and_then(moved));
+                }
+            }
+        }
+    }
+    None
+}
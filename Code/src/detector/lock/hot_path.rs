@@ -0,0 +1,322 @@
+//! Report the locks acquired in the calls from the entry points designated by `--hot-path-fn`, i.e., `HotPathLock`,
+//! since a lock acquired on every path through, e.g., a request handler serializes all the requests even without deadlocks.
+//! The locks are named after their declarations like `--group-by lock` (see `inventory::lock_declaration`),
+//! or after the lockguards acquiring them if the declarations are not found.
+//! A fn must acquire a lock if it is acquired on every path from the entry to a return of the fn,
+//! by a lockguard or by a callsite whose callees all must acquire it,
+//! which is a forward must-dataflow over the CFG (ignoring the cleanup blocks), run on CallGraph bottom-up by SCCs.
+//! The callees in the same SCC (recursion) and the closures only assumed to be called (`ClosureArg`) acquire nothing,
+//! so the must-acquire locks are under-approximated. The other locks reachable in CallGraph may be acquired.
+//! The `RefCell` borrows serialize nothing, so they are not locks here.
+//! The reports are informational and only emitted for the entry points given.
+extern crate rustc_hash;
+extern crate rustc_index;
+extern crate rustc_span;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use petgraph::algo::tarjan_scc;
+use petgraph::visit::{EdgeRef, IntoNodeReferences};
+use petgraph::Direction;
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_index::IndexVec;
+use rustc_middle::mir::{BasicBlock, Location, TerminatorKind, START_BLOCK};
+use rustc_middle::ty::{Instance, TyCtxt};
+use rustc_span::Span;
+
+use super::inventory::lock_declaration;
+use super::report::{HotPathLock, HotPathLockDiagnosis};
+use crate::analysis::callgraph::{CallGraph, CallGraphNode, CallSiteLocation, InstanceId};
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::LockGuardMap;
+
+/// A lock acquired by a lockguard of an instance.
+struct Acquisition {
+    /// The index of the lock in `locks`.
+    lock: usize,
+    location: Location,
+    span: Span,
+}
+
+/// The locks (name, type, declaration span) and their acquisitions in each instance.
+struct Locks {
+    locks: Vec<(String, String, String)>,
+    acquisitions: FxHashMap<InstanceId, Vec<Acquisition>>,
+}
+
+impl Locks {
+    fn new<'tcx>(
+        lockguards: &LockGuardMap<'tcx>,
+        callgraph: &CallGraph<'tcx>,
+        tcx: TyCtxt<'tcx>,
+    ) -> Self {
+        let mut lockguards = lockguards.iter().collect::<Vec<_>>();
+        lockguards.sort_by_key(|(id, _)| (id.instance_id, id.local));
+        let mut locks = Vec::new();
+        let mut indices = FxHashMap::default();
+        let mut acquisitions = FxHashMap::<_, Vec<_>>::default();
+        for (lockguard_id, info) in lockguards {
+            if info.lockguard_ty.is_borrow() {
+                continue;
+            }
+            // The moves of lockguards, e.g., returned by lock wrappers, continue the acquisitions elsewhere.
+            let locations = info
+                .gen_locs
+                .iter()
+                .filter(|loc| !info.move_gen_locs.contains(loc))
+                .collect::<Vec<_>>();
+            if locations.is_empty() {
+                continue;
+            }
+            let (name, declaration_span) = lock_declaration(*lockguard_id, callgraph, tcx)
+                .unwrap_or_else(|| {
+                    (
+                        info.lock_name
+                            .clone()
+                            .unwrap_or_else(|| format!("{:?}", info.lockguard_ty)),
+                        format!("{:?}", info.span),
+                    )
+                });
+            let lock = *indices
+                .entry((name.clone(), declaration_span.clone()))
+                .or_insert_with(|| {
+                    locks.push((name, format!("{:?}", info.lockguard_ty), declaration_span));
+                    locks.len() - 1
+                });
+            acquisitions
+                .entry(lockguard_id.instance_id)
+                .or_default()
+                .extend(locations.into_iter().map(|location| Acquisition {
+                    lock,
+                    location: *location,
+                    span: info.span,
+                }));
+        }
+        Self {
+            locks,
+            acquisitions,
+        }
+    }
+}
+
+pub fn detect_hot_path_locks<'tcx>(
+    hot_path_fns: &[String],
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<Report> {
+    let mut entries = callgraph
+        .graph
+        .node_references()
+        .filter_map(|(instance_id, node)| match node {
+            CallGraphNode::WithBody(instance) => {
+                let path = tcx.def_path_str(instance.def_id());
+                hot_path_fns
+                    .iter()
+                    .any(|hot_path_fn| {
+                        path == *hot_path_fn || path.ends_with(&format!("::{}", hot_path_fn))
+                    })
+                    .then_some((path, instance_id))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return Vec::new();
+    }
+    entries.sort();
+    let locks = Locks::new(lockguards, callgraph, tcx);
+    let must = must_acquire_locks(&locks, callgraph, tcx);
+    let mut reported = BTreeSet::new();
+    let mut reports = Vec::new();
+    for (entry_fn, entry) in entries {
+        let entry_span = format!(
+            "{:?}",
+            tcx.def_span(
+                callgraph
+                    .index_to_instance(entry)
+                    .unwrap()
+                    .instance()
+                    .def_id()
+            )
+        );
+        // The instantiations of the same generic entry point are reported once.
+        if !reported.insert((entry_fn.clone(), entry_span.clone())) {
+            continue;
+        }
+        // The acquisition spans of each lock reachable from the entry point.
+        let mut acquired = BTreeMap::<_, FxHashSet<_>>::new();
+        let mut visited = FxHashSet::from_iter([entry]);
+        let mut worklist = VecDeque::from([entry]);
+        while let Some(id) = worklist.pop_front() {
+            for acquisition in locks.acquisitions.get(&id).into_iter().flatten() {
+                acquired
+                    .entry(acquisition.lock)
+                    .or_default()
+                    .insert(acquisition.span);
+            }
+            for callee in callgraph.graph.neighbors_directed(id, Direction::Outgoing) {
+                if visited.insert(callee) {
+                    worklist.push_back(callee);
+                }
+            }
+        }
+        if acquired.is_empty() {
+            continue;
+        }
+        let entry_must = must.get(&entry).cloned().unwrap_or_default();
+        let (mut must_acquire, mut may_acquire) = (Vec::new(), Vec::new());
+        for (lock, spans) in acquired {
+            let (name, lock_type, declaration_span) = locks.locks[lock].clone();
+            let hot_path_lock = HotPathLock {
+                name,
+                lock_type,
+                declaration_span,
+                acquisitions: spans.len(),
+            };
+            if entry_must.contains(&lock) {
+                must_acquire.push(hot_path_lock);
+            } else {
+                may_acquire.push(hot_path_lock);
+            }
+        }
+        for hot_path_locks in [&mut must_acquire, &mut may_acquire] {
+            hot_path_locks.sort_by(|a, b| {
+                (&a.name, &a.declaration_span).cmp(&(&b.name, &b.declaration_span))
+            });
+        }
+        let explanation = if must_acquire.is_empty() {
+            format!(
+                "The locks are only acquired on some paths through `{}`",
+                entry_fn
+            )
+        } else {
+            format!(
+                "The locks acquired on every path through `{}` serialize the concurrent calls of it even without deadlocks",
+                entry_fn
+            )
+        };
+        reports.push(Report::HotPathLock(ReportContent::new(
+            "HotPathLock".to_owned(),
+            "Possibly".to_owned(),
+            HotPathLockDiagnosis {
+                entry_fn,
+                entry_span,
+                must_acquire,
+                may_acquire,
+            },
+            explanation,
+        )));
+    }
+    reports
+}
+
+/// The locks each instance reaching some lock in CallGraph must acquire, bottom-up by SCCs.
+fn must_acquire_locks<'tcx>(
+    locks: &Locks,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> FxHashMap<InstanceId, FxHashSet<usize>> {
+    let mut reaching = FxHashSet::default();
+    let mut must = FxHashMap::default();
+    // The SCCs are in the reverse topological order, i.e., the callees first.
+    for scc in tarjan_scc(&callgraph.graph) {
+        let reaches = scc.iter().any(|id| {
+            locks.acquisitions.contains_key(id)
+                || callgraph
+                    .graph
+                    .neighbors_directed(*id, Direction::Outgoing)
+                    .any(|callee| reaching.contains(&callee))
+        });
+        if !reaches {
+            continue;
+        }
+        reaching.extend(scc.iter().copied());
+        for id in scc {
+            if let Some(CallGraphNode::WithBody(instance)) = callgraph.index_to_instance(id) {
+                let locks = intraproc_must_acquire(id, *instance, locks, &must, callgraph, tcx);
+                must.insert(id, locks);
+            }
+        }
+    }
+    must
+}
+
+/// The locks acquired on every path from the entry to a return of the instance,
+/// empty if the instance never returns.
+fn intraproc_must_acquire<'tcx>(
+    id: InstanceId,
+    instance: Instance<'tcx>,
+    locks: &Locks,
+    must: &FxHashMap<InstanceId, FxHashSet<usize>>,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> FxHashSet<usize> {
+    let body = tcx.instance_mir(instance.def);
+    let mut gen = FxHashMap::<BasicBlock, FxHashSet<usize>>::default();
+    for acquisition in locks.acquisitions.get(&id).into_iter().flatten() {
+        gen.entry(acquisition.location.block)
+            .or_default()
+            .insert(acquisition.lock);
+    }
+    let mut callees = FxHashMap::<Location, Vec<InstanceId>>::default();
+    for edge in callgraph.graph.edges_directed(id, Direction::Outgoing) {
+        for callsite in edge.weight() {
+            if let CallSiteLocation::Direct(loc)
+            | CallSiteLocation::Virtual(loc)
+            | CallSiteLocation::Indirect(loc) = callsite
+            {
+                callees.entry(*loc).or_default().push(edge.target());
+            }
+        }
+    }
+    // A callsite must acquire the locks all its callees must acquire.
+    for (loc, callees) in callees {
+        let common = callees
+            .iter()
+            .map(|callee| must.get(callee).cloned().unwrap_or_default())
+            .reduce(|common, locks| &common & &locks)
+            .unwrap_or_default();
+        gen.entry(loc.block).or_default().extend(common);
+    }
+    // None for the blocks not reached yet, i.e., all the locks.
+    let mut outs: IndexVec<BasicBlock, Option<FxHashSet<usize>>> =
+        IndexVec::from_elem(None, &body.basic_blocks);
+    let predecessors = body.basic_blocks.predecessors();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &bb in body.basic_blocks.reverse_postorder() {
+            if body.basic_blocks[bb].is_cleanup {
+                continue;
+            }
+            let mut state = if bb == START_BLOCK {
+                FxHashSet::default()
+            } else {
+                match predecessors[bb]
+                    .iter()
+                    .filter_map(|pred| outs[*pred].as_ref())
+                    .fold(None, |state: Option<FxHashSet<usize>>, out| {
+                        Some(match state {
+                            Some(state) => &state & out,
+                            None => out.clone(),
+                        })
+                    }) {
+                    Some(state) => state,
+                    None => continue,
+                }
+            };
+            state.extend(gen.get(&bb).into_iter().flatten());
+            if outs[bb].as_ref() != Some(&state) {
+                outs[bb] = Some(state);
+                changed = true;
+            }
+        }
+    }
+    body.basic_blocks
+        .iter_enumerated()
+        .filter(|(_, data)| matches!(data.terminator().kind, TerminatorKind::Return))
+        .filter_map(|(bb, _)| outs[bb].clone())
+        .reduce(|common, locks| &common & &locks)
+        .unwrap_or_default()
+}
@@ -0,0 +1,370 @@
+//! The inventory of the locks in the deadlock reports, listed in the envelopes and grouped by with `--group-by lock`,
+//! also bucketing the locks of the lock order graph, see `lock_graph.rs`.
+//! The lockguards of the locks in the deadlock reports are bucketed into alias classes,
+//! where the lockguards probably aliasing each other (or acquired at the same span) are in the same class.
+//! Each class is named after the declaration of its lock, found by tracking back the receiver of the lock call,
+//! e.g., `_3 = &((*_1).0: Mutex<i32>); _2 = Mutex::lock(move _3)` is named after the field `.0`,
+//! and the classes of the same declaration are merged.
+//! The acquisitions of a class are the lockguards in the crate of the same declaration or probably aliasing it.
+extern crate rustc_hash;
+extern crate rustc_hir;
+
+use std::collections::BTreeMap;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{
+    Body, Local, Operand, Place, PlaceElem, Rvalue, StatementKind, TerminatorKind,
+    VarDebugInfoContents,
+};
+use rustc_middle::ty::{TyCtxt, TyKind};
+
+use super::report::LockClass;
+use crate::analysis::callgraph::CallGraph;
+use crate::analysis::pointsto::{AliasAnalysis, ApproximateAliasKind};
+use crate::detector::report::Report;
+use crate::interest::concurrency::lock::{LockGuardId, LockGuardMap};
+
+/// The maximal number of assignments tracked back from a lockguard to its lock.
+const MAX_TRACK_DEPTH: usize = 16;
+
+/// Bucket the locks in the reports into `LockClass`es and set the class ids of the locks in the reports.
+/// The classes are sorted by name and declaration span.
+pub fn classify_locks<'a, 'tcx>(
+    reports: &mut [Report],
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &'a CallGraph<'tcx>,
+    alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<LockClass> {
+    let spans = reports
+        .iter()
+        .flat_map(lock_spans)
+        .collect::<FxHashSet<_>>();
+    let mut members = lockguards
+        .iter()
+        .map(|(id, info)| (format!("{:?}", info.span), *id))
+        .filter(|(span, _)| spans.contains(span.as_str()))
+        .collect::<Vec<_>>();
+    members.sort_by_key(|(span, id)| (span.clone(), id.instance_id, id.local));
+    let mut declarations = FxHashMap::default();
+    let declared = alias_classes(
+        &members,
+        lockguards,
+        callgraph,
+        alias_analysis,
+        &mut declarations,
+        tcx,
+    );
+    let mut all_lockguards = lockguards.keys().copied().collect::<Vec<_>>();
+    all_lockguards.sort_by_key(|id| (id.instance_id, id.local));
+    let mut inventory = Vec::new();
+    for ((name, declaration_span), (ids, spans)) in declared {
+        let representative = ids[0];
+        let ids = ids.into_iter().collect::<FxHashSet<_>>();
+        let mut acquisitions = 0;
+        for id in &all_lockguards {
+            if ids.contains(id)
+                || declaration_of(*id, &mut declarations, callgraph, tcx).as_ref()
+                    == Some(&(name.clone(), declaration_span.clone()))
+                || alias_analysis.alias(representative.into(), (*id).into())
+                    == ApproximateAliasKind::Probably
+            {
+                acquisitions += 1;
+            }
+        }
+        let class = LockClass {
+            id: inventory.len(),
+            name,
+            declaration_span,
+            acquisitions,
+        };
+        inventory.push((class, spans));
+    }
+    let mut span_to_class = FxHashMap::default();
+    for (class, spans) in inventory.iter() {
+        span_to_class.extend(spans.iter().map(|span| (span.clone(), class.id)));
+    }
+    let class_of = |span: &String| span_to_class.get(span).copied();
+    for report in reports.iter_mut() {
+        match report {
+            Report::DoubleLock(content) => {
+                let diagnosis = &mut content.diagnosis;
+                diagnosis.lock_class = class_of(&diagnosis.first_lock_span);
+                diagnosis.second_lock_class = class_of(&diagnosis.second_lock_span);
+            }
+            Report::ConflictLock(content) => {
+                for diagnosis in content.diagnosis.iter_mut() {
+                    diagnosis.lock_class = class_of(&diagnosis.first_lock_span);
+                    diagnosis.second_lock_class = class_of(&diagnosis.second_lock_span);
+                }
+            }
+            Report::CondvarDeadlock(content) => {
+                let diagnosis = &mut content.diagnosis;
+                for locks in diagnosis.deadlocks.iter_mut() {
+                    locks.wait_lock_class = class_of(&locks.wait_lock_span);
+                    locks.notify_lock_class = class_of(&locks.notify_lock_span);
+                }
+                diagnosis.lock_class = diagnosis
+                    .deadlocks
+                    .first()
+                    .and_then(|locks| locks.wait_lock_class);
+            }
+            _ => {}
+        }
+    }
+    inventory.into_iter().map(|(class, _)| class).collect()
+}
+
+/// Bucket the lockguards (with their spans) into alias classes, keyed by the names and declaration spans of their locks.
+/// The lockguards probably aliasing each other or acquired at the same span are in the same class,
+/// and the classes of the same declaration are merged.
+pub(super) fn alias_classes<'a, 'tcx>(
+    members: &[(String, LockGuardId)],
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &'a CallGraph<'tcx>,
+    alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    declarations: &mut FxHashMap<LockGuardId, Option<(String, String)>>,
+    tcx: TyCtxt<'tcx>,
+) -> BTreeMap<(String, String), (Vec<LockGuardId>, Vec<String>)> {
+    // Union-find on the members.
+    let mut parents = (0..members.len()).collect::<Vec<_>>();
+    fn find(parents: &mut [usize], mut idx: usize) -> usize {
+        while parents[idx] != idx {
+            parents[idx] = parents[parents[idx]];
+            idx = parents[idx];
+        }
+        idx
+    }
+    for i in 0..members.len() {
+        for j in i + 1..members.len() {
+            let (ri, rj) = (find(&mut parents, i), find(&mut parents, j));
+            if ri == rj {
+                continue;
+            }
+            if members[i].0 == members[j].0
+                || alias_analysis.alias(members[i].1.into(), members[j].1.into())
+                    == ApproximateAliasKind::Probably
+            {
+                parents[rj] = ri;
+            }
+        }
+    }
+    let mut classes: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+    for idx in 0..members.len() {
+        classes
+            .entry(find(&mut parents, idx))
+            .or_default()
+            .push(idx);
+    }
+    // The alias classes of the same declaration, e.g., a field locked via `&self` in different fns,
+    // are merged because the params only possibly alias.
+    let mut declared: BTreeMap<(String, String), (Vec<LockGuardId>, Vec<String>)> = BTreeMap::new();
+    for indices in classes.into_values() {
+        let declaration = indices
+            .iter()
+            .find_map(|idx| declaration_of(members[*idx].1, declarations, callgraph, tcx))
+            .unwrap_or_else(|| {
+                let info = &lockguards[&members[indices[0]].1];
+                (
+                    format!("{:?}", info.lockguard_ty),
+                    format!("{:?}", info.span),
+                )
+            });
+        let (ids, spans) = declared.entry(declaration).or_default();
+        ids.extend(indices.iter().map(|idx| members[*idx].1));
+        spans.extend(indices.iter().map(|idx| members[*idx].0.clone()));
+    }
+    declared
+}
+
+/// The spans of the lockguards in the report.
+fn lock_spans(report: &Report) -> Vec<&str> {
+    match report {
+        Report::DoubleLock(content) => vec![
+            &content.diagnosis.first_lock_span,
+            &content.diagnosis.second_lock_span,
+        ],
+        Report::ConflictLock(content) => content
+            .diagnosis
+            .iter()
+            .flat_map(|diagnosis| [&diagnosis.first_lock_span, &diagnosis.second_lock_span])
+            .map(String::as_str)
+            .collect(),
+        Report::CondvarDeadlock(content) => content
+            .diagnosis
+            .deadlocks
+            .iter()
+            .flat_map(|locks| [&locks.wait_lock_span, &locks.notify_lock_span])
+            .map(String::as_str)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `lock_declaration` cached in `declarations`.
+fn declaration_of<'tcx>(
+    lockguard_id: LockGuardId,
+    declarations: &mut FxHashMap<LockGuardId, Option<(String, String)>>,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<(String, String)> {
+    declarations
+        .entry(lockguard_id)
+        .or_insert_with(|| lock_declaration(lockguard_id, callgraph, tcx))
+        .clone()
+}
+
+/// The name and declaration span of the lock of the lockguard (or a local moved from it):
+/// a static, a struct field, or a named variable (including params).
+/// The variables are only named after the lock call so that the guards are not mistaken for the locks.
+pub fn lock_declaration<'tcx>(
+    lockguard_id: LockGuardId,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<(String, String)> {
+    let instance = callgraph.index_to_instance(lockguard_id.instance_id)?;
+    let body = tcx.instance_mir(instance.instance().def);
+    let mut local = lockguard_id.local;
+    let mut after_call = false;
+    for _ in 0..MAX_TRACK_DEPTH {
+        let place = match definition(body, local, tcx) {
+            // `lockguard = lock(move receiver)`
+            Some(Definition::Call(Some(arg))) => {
+                after_call = true;
+                arg
+            }
+            // `receiver = &lock` or `receiver = move lock`
+            Some(Definition::Place(place)) => place,
+            Some(Definition::Static(def_id)) => {
+                return Some((
+                    tcx.def_path_str(def_id),
+                    format!("{:?}", tcx.def_span(def_id)),
+                ))
+            }
+            // A param or a lock not tracked.
+            Some(Definition::Call(None)) | None => {
+                return after_call
+                    .then(|| variable_declaration(body, local))
+                    .flatten()
+            }
+        };
+        if let Some(declaration) = field_declaration(body, place, tcx) {
+            return Some(declaration);
+        }
+        if after_call && place.projection.is_empty() {
+            if let Some(declaration) = variable_declaration(body, place.local) {
+                return Some(declaration);
+            }
+        }
+        local = place.local;
+    }
+    None
+}
+
+/// The local referenced by the local, e.g., `_4` of `_10 = &mut _4`.
+pub fn referent(body: &Body<'_>, local: Local) -> Option<Local> {
+    body.basic_blocks
+        .iter()
+        .flat_map(|bb_data| &bb_data.statements)
+        .find_map(|stmt| match &stmt.kind {
+            StatementKind::Assign(box (lhs, Rvalue::Ref(_, _, place)))
+                if lhs.as_local() == Some(local) =>
+            {
+                place.as_local()
+            }
+            _ => None,
+        })
+}
+
+enum Definition<'tcx> {
+    /// The first arg of the call defining the local.
+    Call(Option<Place<'tcx>>),
+    /// The place referenced or moved (copied) to the local.
+    Place(Place<'tcx>),
+    Static(DefId),
+}
+
+/// The first assignment to the local in the body.
+fn definition<'tcx>(
+    body: &Body<'tcx>,
+    local: Local,
+    tcx: TyCtxt<'tcx>,
+) -> Option<Definition<'tcx>> {
+    for bb_data in body.basic_blocks.iter() {
+        for stmt in &bb_data.statements {
+            let rvalue = match &stmt.kind {
+                StatementKind::Assign(box (lhs, rvalue)) if lhs.as_local() == Some(local) => rvalue,
+                _ => continue,
+            };
+            return match rvalue {
+                Rvalue::Ref(_, _, place)
+                | Rvalue::AddressOf(_, place)
+                | Rvalue::CopyForDeref(place)
+                | Rvalue::Use(Operand::Copy(place) | Operand::Move(place))
+                | Rvalue::Cast(_, Operand::Copy(place) | Operand::Move(place), _) => {
+                    Some(Definition::Place(*place))
+                }
+                Rvalue::Use(Operand::Constant(constant)) => {
+                    constant.check_static_ptr(tcx).map(Definition::Static)
+                }
+                _ => None,
+            };
+        }
+        if let Some(terminator) = &bb_data.terminator {
+            if let TerminatorKind::Call {
+                args, destination, ..
+            } = &terminator.kind
+            {
+                if destination.as_local() == Some(local) {
+                    return Some(Definition::Call(args.get(0).and_then(Operand::place)));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// `Struct.field` if the place is a field of a struct.
+fn field_declaration<'tcx>(
+    body: &Body<'tcx>,
+    place: Place<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<(String, String)> {
+    let (base, elem) = place.as_ref().last_projection()?;
+    let field = match elem {
+        PlaceElem::Field(field, _) => field,
+        _ => return None,
+    };
+    // The fields of enum variants are the payloads rather than the declared locks.
+    if base
+        .projection
+        .iter()
+        .any(|elem| matches!(elem, PlaceElem::Downcast(..)))
+    {
+        return None;
+    }
+    match base.ty(body, tcx).ty.kind() {
+        TyKind::Adt(adt_def, _) if adt_def.is_struct() => {
+            let field_def = &adt_def.non_enum_variant().fields[field];
+            Some((
+                format!("{}.{}", tcx.def_path_str(adt_def.did()), field_def.name),
+                format!("{:?}", tcx.def_span(field_def.did)),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The name and span of the user variable (or param).
+fn variable_declaration(body: &Body<'_>, local: Local) -> Option<(String, String)> {
+    body.var_debug_info
+        .iter()
+        .find_map(|info| match info.value {
+            VarDebugInfoContents::Place(place) if place.as_local() == Some(local) => Some((
+                info.name.to_string(),
+                format!("{:?}", body.local_decls[local].source_info.span),
+            )),
+            _ => None,
+        })
+}
@@ -0,0 +1,277 @@
+//! Report the fields of `self` accessed with the same lock held at most of their accesses
+//! but without it at some accesses reachable from multiple threads, i.e., `LockCoverage`.
+//! E.g., `self.hits` accessed at 9 sites while the lockguard of `self.mu` is live and at 1 site without,
+//! the odd one out probably races with the others.
+//! The field accesses are the places projecting a field of `self` in the local methods,
+//! and the lockguards live at them are collected in the fixed-point of `DeadlockDetector::detect`,
+//! including the lockguards held by the callers.
+//! The locks are classified by their declarations like `LockClass`, e.g., `Stats.mu`,
+//! or by the alias of the lockguards if undeclared, while the `RefCell` borrows are not locks here.
+//! The accesses are counted by spans so that the monomorphized instances of the same generic fns count once,
+//! and an access is protected only if the lock is held at all its locations.
+//! A field is reported if the ratio of its protected accesses reaches `lock_coverage.threshold`, 0.8 by default.
+extern crate rustc_hash;
+extern crate rustc_span;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use petgraph::visit::IntoNodeReferences;
+use rustc_hash::FxHashMap;
+use rustc_middle::mir::visit::{PlaceContext, Visitor};
+use rustc_middle::mir::{Body, Local, Location, Place, ProjectionElem};
+use rustc_middle::ty::{TyCtxt, TyKind};
+use rustc_span::Span;
+
+use super::report::LockCoverageDiagnosis;
+use super::{inventory, LockGuardsBeforeCallSites};
+use crate::analysis::callgraph::{CallGraph, CallGraphNode, InstanceId};
+use crate::analysis::pointsto::{AliasAnalysis, ApproximateAliasKind};
+use crate::analysis::threadroot::ThreadRoots;
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::{LockGuardId, LockGuardMap};
+
+/// A field of `self`, named like the lock declarations, e.g., `Stats.hits`, and its declaration span.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct Field {
+    name: String,
+    span: String,
+}
+
+/// The accesses to the fields of `self` in each instance.
+pub(super) type FieldAccesses = FxHashMap<InstanceId, Vec<(Location, Span, Field)>>;
+
+/// The names and the lockguard types of the locks held at an access.
+type HeldLocks = BTreeSet<(String, String)>;
+
+struct FieldAccessCollector<'a, 'tcx> {
+    body: &'a Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    accesses: Vec<(Location, Span, Field)>,
+}
+
+impl<'a, 'tcx> FieldAccessCollector<'a, 'tcx> {
+    /// The struct field projected from `self` (or `*self`) by the place.
+    fn self_field(&self, place: &Place<'tcx>) -> Option<Field> {
+        if place.local != Local::from_usize(1) {
+            return None;
+        }
+        let field_idx = match place.projection.as_ref() {
+            [ProjectionElem::Deref, ProjectionElem::Field(field, _), ..]
+            | [ProjectionElem::Field(field, _), ..] => *field,
+            _ => return None,
+        };
+        let deref = matches!(place.projection[0], ProjectionElem::Deref);
+        let mut base_ty = self.body.local_decls[place.local].ty;
+        if deref {
+            base_ty = base_ty.builtin_deref(true)?.ty;
+        }
+        match base_ty.kind() {
+            TyKind::Adt(adt_def, _) if adt_def.is_struct() => {
+                let field_def = &adt_def.non_enum_variant().fields[field_idx];
+                Some(Field {
+                    name: format!(
+                        "{}.{}",
+                        self.tcx.def_path_str(adt_def.did()),
+                        field_def.name
+                    ),
+                    span: format!("{:?}", self.tcx.def_span(field_def.did)),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a, 'tcx> Visitor<'tcx> for FieldAccessCollector<'a, 'tcx> {
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        if matches!(context, PlaceContext::NonUse(_)) || self.body[location.block].is_cleanup {
+            return;
+        }
+        if let Some(field) = self.self_field(place) {
+            let span = self.body.source_info(location).span;
+            self.accesses.push((location, span, field));
+        }
+    }
+}
+
+/// Collect the accesses to the fields of `self` in the local methods.
+pub(super) fn collect_field_accesses<'tcx>(
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> FieldAccesses {
+    callgraph
+        .graph
+        .node_references()
+        .filter_map(|(instance_id, node)| {
+            let instance = match node {
+                CallGraphNode::WithBody(instance) if callgraph.is_focused(instance_id) => instance,
+                _ => return None,
+            };
+            let def_id = instance.def_id();
+            let is_method = def_id.is_local()
+                && tcx
+                    .opt_associated_item(def_id)
+                    .map_or(false, |item| item.fn_has_self_parameter);
+            if !is_method {
+                return None;
+            }
+            let body = tcx.instance_mir(instance.def);
+            let mut collector = FieldAccessCollector {
+                body,
+                tcx,
+                accesses: Vec::new(),
+            };
+            collector.visit_body(body);
+            (!collector.accesses.is_empty()).then_some((instance_id, collector.accesses))
+        })
+        .collect()
+}
+
+/// The lock classes of the lockguards: their declarations, or the first alias of the undeclared ones.
+struct LockClasses<'b, 'a, 'tcx> {
+    lockguards: &'b LockGuardMap<'tcx>,
+    callgraph: &'a CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    classes: FxHashMap<LockGuardId, (String, String)>,
+    undeclared: Vec<LockGuardId>,
+}
+
+impl<'b, 'a, 'tcx> LockClasses<'b, 'a, 'tcx> {
+    /// The name and the lockguard type of the lock class.
+    fn class_of(
+        &mut self,
+        lockguard_id: LockGuardId,
+        alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    ) -> (String, String) {
+        if let Some(class) = self.classes.get(&lockguard_id) {
+            return class.clone();
+        }
+        let info = &self.lockguards[&lockguard_id];
+        let lock_type = format!("{:?}", info.lockguard_ty);
+        let name = match inventory::lock_declaration(lockguard_id, self.callgraph, self.tcx) {
+            Some((name, _)) => name,
+            None => {
+                let representative = self
+                    .undeclared
+                    .iter()
+                    .copied()
+                    .find(|other| {
+                        alias_analysis.alias(lockguard_id.into(), (*other).into())
+                            == ApproximateAliasKind::Probably
+                    })
+                    .unwrap_or_else(|| {
+                        self.undeclared.push(lockguard_id);
+                        lockguard_id
+                    });
+                format!("{:?}", self.lockguards[&representative].span)
+            }
+        };
+        self.classes
+            .insert(lockguard_id, (name.clone(), lock_type.clone()));
+        (name, lock_type)
+    }
+}
+
+pub(super) fn detect_lock_coverage<'a, 'tcx>(
+    lockguards_before_field_accesses: &LockGuardsBeforeCallSites,
+    field_accesses: &FieldAccesses,
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &'a CallGraph<'tcx>,
+    alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    threshold: u32,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<Report> {
+    let mut lock_classes = LockClasses {
+        lockguards,
+        callgraph,
+        tcx,
+        classes: FxHashMap::default(),
+        undeclared: Vec::new(),
+    };
+    // field -> access span -> (the instances, the locks held at each location)
+    let mut fields: BTreeMap<Field, BTreeMap<String, Vec<(InstanceId, HeldLocks)>>> =
+        BTreeMap::new();
+    for (instance_id, accesses) in field_accesses {
+        for (loc, span, field) in accesses {
+            let locks = lockguards_before_field_accesses
+                .get(&(*instance_id, *loc))
+                .into_iter()
+                .flat_map(|live| live.raw_lockguard_ids())
+                .filter(|lockguard_id| !lockguards[lockguard_id].lockguard_ty.is_borrow())
+                .map(|lockguard_id| lock_classes.class_of(*lockguard_id, alias_analysis))
+                .collect();
+            fields
+                .entry(field.clone())
+                .or_default()
+                .entry(format!("{:?}", span))
+                .or_default()
+                .push((*instance_id, locks));
+        }
+    }
+    let thread_roots = ThreadRoots::analyze(callgraph, tcx);
+    let mut reports = Vec::new();
+    for (field, accesses) in fields {
+        // The lock held at the most accesses, where an access holds the lock at all its locations.
+        let mut protected: BTreeMap<&(String, String), usize> = BTreeMap::new();
+        for occurrences in accesses.values() {
+            let (_, first) = &occurrences[0];
+            for lock in first {
+                if occurrences.iter().all(|(_, locks)| locks.contains(lock)) {
+                    *protected.entry(lock).or_default() += 1;
+                }
+            }
+        }
+        let Some(((lock, lock_type), protected_accesses)) = protected
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .filter(|((lock, _), _)| *lock != field.name)
+        else {
+            continue;
+        };
+        if protected_accesses == accesses.len()
+            || (protected_accesses as u64) * 100 < u64::from(threshold) * accesses.len() as u64
+        {
+            continue;
+        }
+        let is_unprotected = |occurrences: &Vec<(InstanceId, HeldLocks)>| {
+            occurrences
+                .iter()
+                .any(|(_, locks)| !locks.contains(&(lock.clone(), lock_type.clone())))
+        };
+        // An unprotected access races only if some access may run in another thread.
+        let racy = accesses
+            .values()
+            .filter(|occurrences| is_unprotected(occurrences))
+            .flatten()
+            .any(|(unprotected, _)| {
+                accesses
+                    .values()
+                    .flatten()
+                    .any(|(other, _)| !thread_roots.is_same_single_thread(*unprotected, *other))
+            });
+        if !racy {
+            continue;
+        }
+        let unprotected_spans = accesses
+            .iter()
+            .filter(|(_, occurrences)| is_unprotected(occurrences))
+            .map(|(span, _)| span.clone())
+            .collect();
+        let diagnosis = LockCoverageDiagnosis::new(
+            field.name,
+            field.span,
+            lock.clone(),
+            lock_type.clone(),
+            protected_accesses,
+            accesses.len(),
+            unprotected_spans,
+        );
+        reports.push(Report::LockCoverage(ReportContent::new(
+            "LockCoverage".to_owned(),
+            "Possibly".to_owned(),
+            diagnosis,
+            "The field is accessed with the lock held at most of its accesses, so the accesses without it may race with the others".to_owned(),
+        )));
+    }
+    reports
+}
@@ -0,0 +1,162 @@
+//! The lock order graph for `--emit-lock-graph`, to review the lock hierarchy of a crate.
+//! The nodes are the locks: the lockguards in `lockguard_relations` bucketed into alias classes,
+//! named after the declarations of their locks, see `inventory::alias_classes`.
+//! The edges are "acquired while holding" relations between the locks,
+//! e.g., `mu -> rw1` if `rw1` is locked while the guard of `mu` is live,
+//! with the spans of a representative relation.
+//! A cycle in the graph is a potential conflictlock, even if not reported, e.g., with the alias unknown.
+extern crate rustc_hash;
+
+use std::collections::BTreeMap;
+
+use petgraph::algo::tarjan_scc;
+use petgraph::dot::Dot;
+use petgraph::Graph;
+use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_middle::ty::TyCtxt;
+use serde::Serialize;
+
+use super::inventory;
+use crate::analysis::callgraph::CallGraph;
+use crate::analysis::pointsto::AliasAnalysis;
+use crate::interest::concurrency::lock::{LockGuardId, LockGuardMap};
+
+/// A lock, i.e., an alias class of lockguards.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LockNode {
+    pub id: usize,
+    /// The declaration of the lock, e.g., `MyStruct.mu`, or the lockguard type if unknown.
+    pub name: String,
+    pub declaration_span: String,
+    /// The spans of the acquisitions of the lock in the relations, sorted.
+    pub acquisition_spans: Vec<String>,
+}
+
+/// `to` is acquired while holding `from`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LockOrderEdge {
+    pub from: usize,
+    pub to: usize,
+    /// The spans of the first relation in order, of `from` and `to` respectively.
+    pub first_lock_span: String,
+    pub second_lock_span: String,
+    /// The number of the relations of the lockguards.
+    pub relations: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct LockGraph {
+    pub locks: Vec<LockNode>,
+    pub edges: Vec<LockOrderEdge>,
+    /// The ids of the locks in each cycle (strongly connected component), sorted.
+    pub cycles: Vec<Vec<usize>>,
+}
+
+impl LockGraph {
+    /// Bucket the lockguards in the relations into locks and add an edge for each pair of distinct locks in order.
+    /// A relation in the same lock (e.g., a recursive read) is left to the doublelock detection.
+    pub(crate) fn new<'a, 'tcx>(
+        relations: &FxHashSet<(LockGuardId, LockGuardId)>,
+        lockguards: &LockGuardMap<'tcx>,
+        callgraph: &'a CallGraph<'tcx>,
+        alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+        tcx: TyCtxt<'tcx>,
+    ) -> Self {
+        let mut members = relations
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .collect::<FxHashSet<_>>()
+            .into_iter()
+            .map(|id| (format!("{:?}", lockguards[&id].span), id))
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(span, id)| (span.clone(), id.instance_id, id.local));
+        let declared = inventory::alias_classes(
+            &members,
+            lockguards,
+            callgraph,
+            alias_analysis,
+            &mut FxHashMap::default(),
+            tcx,
+        );
+        let mut lock_of = FxHashMap::default();
+        let mut locks = Vec::new();
+        for ((name, declaration_span), (ids, mut spans)) in declared {
+            let id = locks.len();
+            lock_of.extend(ids.into_iter().map(|lockguard_id| (lockguard_id, id)));
+            spans.sort();
+            spans.dedup();
+            locks.push(LockNode {
+                id,
+                name,
+                declaration_span,
+                acquisition_spans: spans,
+            });
+        }
+        let mut edges: BTreeMap<(usize, usize), Vec<(String, String)>> = BTreeMap::new();
+        for (a, b) in relations {
+            let (from, to) = (lock_of[a], lock_of[b]);
+            if from != to {
+                edges.entry((from, to)).or_default().push((
+                    format!("{:?}", lockguards[a].span),
+                    format!("{:?}", lockguards[b].span),
+                ));
+            }
+        }
+        let edges = edges
+            .into_iter()
+            .map(|((from, to), spans)| {
+                let (first_lock_span, second_lock_span) = spans.iter().min().cloned().unwrap();
+                LockOrderEdge {
+                    from,
+                    to,
+                    first_lock_span,
+                    second_lock_span,
+                    relations: spans.len(),
+                }
+            })
+            .collect();
+        let mut graph = Self {
+            locks,
+            edges,
+            cycles: Vec::new(),
+        };
+        let mut cycles = tarjan_scc(&graph.graph())
+            .into_iter()
+            .filter(|scc| scc.len() > 1)
+            .map(|scc| {
+                let mut ids = scc.into_iter().map(|node| node.index()).collect::<Vec<_>>();
+                ids.sort();
+                ids
+            })
+            .collect::<Vec<_>>();
+        cycles.sort();
+        graph.cycles = cycles;
+        graph
+    }
+
+    /// The graph with the locks and the edges labeled by their names and spans.
+    fn graph(&self) -> Graph<String, String> {
+        let mut graph = Graph::new();
+        let nodes = self
+            .locks
+            .iter()
+            .map(|lock| graph.add_node(format!("{}\n{}", lock.name, lock.declaration_span)))
+            .collect::<Vec<_>>();
+        for edge in &self.edges {
+            graph.add_edge(
+                nodes[edge.from],
+                nodes[edge.to],
+                format!(
+                    "{} -> {} ({})",
+                    edge.first_lock_span, edge.second_lock_span, edge.relations
+                ),
+            );
+        }
+        graph
+    }
+
+    /// The graph in dot format.
+    pub fn dot(&self) -> String {
+        format!("{}", Dot::new(&self.graph()))
+    }
+}
@@ -1,32 +1,72 @@
 //! DeadlockDetector: detects doublelock and conflictlock.
 extern crate rustc_data_structures;
 extern crate rustc_hash;
+extern crate rustc_span;
 
+pub mod discarded_guard;
+pub mod force_unlock;
+pub mod guard_leak;
+pub mod hot_path;
+pub mod inventory;
+pub mod lock_coverage;
+pub mod lock_graph;
+pub mod poison;
+pub mod release_order;
 pub mod report;
-use super::report::{Report, ReportContent};
+use super::report::{
+    Report, ReportContent, ALIAS_CONFIDENCE_SAME_OWNER, ALIAS_CONFIDENCE_UNKNOWN,
+    HEURISTIC_CLOSURE_ARG, INVERSION_PANIC_RETRY,
+};
+use lock_graph::LockGraph;
 use report::DeadlockDiagnosis;
 
-use crate::analysis::callgraph::{CallGraph, CallGraphNode, InstanceId};
-use crate::analysis::pointsto::{AliasAnalysis, AliasId, ApproximateAliasKind};
+use crate::analysis::budget::{AnalysisBudget, BudgetExceeded, SkippedFunction};
+use crate::analysis::callgraph::{
+    CallChainLimits, CallGraph, CallGraphNode, CallSiteLocation, InstanceId,
+};
+use crate::analysis::pointsto::{AliasAnalysis, AliasId, AliasProvenance, ApproximateAliasKind};
+use crate::analysis::threadroot::{ThreadRoot, ThreadRoots};
+use crate::config::PoolConfig;
+use crate::detector::config::DetectorConfig;
+use crate::diagnostic::raw_span;
 use crate::interest::concurrency::condvar::{CondvarApi, ParkingLotCondvarApi, StdCondvarApi};
+use crate::interest::concurrency::executor::is_block_on_api;
 use crate::interest::concurrency::lock::{
-    DeadlockPossibility, LockGuardCollector, LockGuardId, LockGuardMap, LockGuardTy,
+    DeadlockPossibility, GuardHolder, GuardNewtypes, LockGuardCollector, LockGuardId, LockGuardMap,
 };
+use crate::interest::concurrency::lock_registry::LockAccess;
+use crate::interest::concurrency::once::OnceApi;
+use crate::interest::concurrency::pool::PoolApi;
+use crate::interest::concurrency::rayon::RayonApi;
+use crate::interest::concurrency::rendezvous::{is_sync_channel, RendezvousApi};
+use crate::interest::concurrency::thread::{
+    api_path, catch_unwind_instances, is_local_spawn_api, SpawnApi,
+};
+use crate::plugin::{Plugin, PluginContext};
+use crate::progress::Progress;
 
 use petgraph::algo;
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::NodeIndex;
 
-use petgraph::visit::{depth_first_search, Control, DfsEvent, EdgeRef, IntoNodeReferences};
+use petgraph::visit::{EdgeRef, IntoNodeReferences, NodeFiltered};
 use petgraph::{Directed, Direction, Graph};
 
+use log::warn;
 use rustc_hash::{FxHashMap, FxHashSet};
-use rustc_middle::mir::{Body, Location, Operand, TerminatorKind};
-use rustc_middle::ty::{ParamEnv, TyCtxt};
+use rustc_middle::mir::{Body, Location, Operand, TerminatorKind, RETURN_PLACE};
+use rustc_middle::ty::{EarlyBinder, Instance, ParamEnv, Ty, TyCtxt};
+use rustc_span::Span;
 
 use std::collections::VecDeque;
+use std::iter;
 
-use self::report::{CondvarDeadlockDiagnosis, WaitNotifyLocks};
+use self::report::{
+    AwaitHoldingLockDiagnosis, BlockOnHoldingLockDiagnosis, CondvarDeadlockDiagnosis,
+    CondvarMultipleMutexesDiagnosis, CondvarMutex, LockClass, OnceInitDeadlockDiagnosis,
+    OnceReentrancyDiagnosis, PoolSelfJoinDiagnosis, RayonDeadlockDiagnosis,
+    RendezvousDeadlockDiagnosis, SendHazardDiagnosis, WaitNotifyLocks,
+};
 
 #[derive(Clone, Debug, Default)]
 struct LiveLockGuards(FxHashSet<LockGuardId>);
@@ -49,52 +89,162 @@ impl LiveLockGuards {
     // self = self U other, if changed return true
     fn union_in_place(&mut self, other: Self) -> bool {
         let old_len = self.0.len();
-        self.0.extend(other.0.into_iter());
+        self.0.extend(other.0);
         old_len != self.0.len()
     }
 }
 
 type LockGuardsBeforeCallSites = FxHashMap<(InstanceId, Location), LiveLockGuards>;
 
+/// The lockguards generated, transferred, and killed at each location.
+#[derive(Default)]
+struct GenKillMaps {
+    gen: FxHashMap<Location, LiveLockGuards>,
+    transfer: FxHashMap<Location, LiveLockGuards>,
+    kill: FxHashMap<Location, LiveLockGuards>,
+}
+
 /// Detect doublelock and conflictlock.
 pub struct DeadlockDetector<'tcx> {
     tcx: TyCtxt<'tcx>,
     param_env: ParamEnv<'tcx>,
     pub lockguard_relations: FxHashSet<(LockGuardId, LockGuardId)>,
+    /// The enabled detectors and their parameters.
+    config: DetectorConfig,
+    /// Also collect lockguards in non-local instances with MIR available.
+    analyze_deps_guards: bool,
+    /// The declared and detected guard newtypes.
+    guard_newtypes: GuardNewtypes,
+    /// The custom thread pools declared in the config.
+    pools: Vec<PoolConfig>,
+    /// The budget of each run of intraproc gen/kill.
+    budget: AnalysisBudget,
+    /// The fns whose gen/kill exceeded the budget.
+    pub skipped_functions: Vec<SkippedFunction>,
+    /// The alias classes of the locks in the reports.
+    pub lock_classes: Vec<LockClass>,
+    /// Report the locks and condvars with unknown alias results as possibly.
+    paranoid: bool,
+    /// The custom checks run after the detection.
+    plugins: Vec<Box<dyn Plugin>>,
+    /// The paths of the entry points given by `--hot-path-fn`.
+    hot_path_fns: Vec<String>,
+    /// Print the MIR of the focused fns with the live lockguards, see `dump_mir`.
+    dump_mir: bool,
+    /// Build the lock order graph of the lockguard relations.
+    emit_lock_graph: bool,
+    /// The lock order graph if `emit_lock_graph`, see `lock_graph.rs`.
+    pub lock_graph: Option<LockGraph>,
 }
 
 impl<'tcx> DeadlockDetector<'tcx> {
-    pub fn new(tcx: TyCtxt<'tcx>, param_env: ParamEnv<'tcx>) -> Self {
+    pub fn new(
+        tcx: TyCtxt<'tcx>,
+        param_env: ParamEnv<'tcx>,
+        config: DetectorConfig,
+        analyze_deps_guards: bool,
+        guard_newtypes: GuardNewtypes,
+        pools: Vec<PoolConfig>,
+    ) -> Self {
         Self {
             tcx,
             param_env,
             lockguard_relations: Default::default(),
+            config,
+            analyze_deps_guards,
+            guard_newtypes,
+            pools,
+            budget: AnalysisBudget::unlimited(),
+            skipped_functions: Vec::new(),
+            lock_classes: Vec::new(),
+            paranoid: false,
+            plugins: Vec::new(),
+            hot_path_fns: Vec::new(),
+            dump_mir: false,
+            emit_lock_graph: false,
+            lock_graph: None,
         }
     }
 
+    /// Limit each run of intraproc gen/kill.
+    pub fn set_budget(&mut self, budget: AnalysisBudget) {
+        self.budget = budget;
+    }
+
+    /// Treat the unknown alias results (e.g., of the fns skipped by the budgets) as possibly under `--paranoid`,
+    /// marking the diagnoses by `alias_confidence`.
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
+    }
+
+    /// Run the plugins on the lockguards and their relations after the detection.
+    pub fn set_plugins(&mut self, plugins: Vec<Box<dyn Plugin>>) {
+        self.plugins = plugins;
+    }
+
+    /// Report the locks acquired on the paths through the entry points, see `hot_path.rs`.
+    pub fn set_hot_path_fns(&mut self, hot_path_fns: Vec<String>) {
+        self.hot_path_fns = hot_path_fns;
+    }
+
+    /// Print the MIR of the fns matching `--focus` with the live lockguards before each location.
+    pub fn set_dump_mir(&mut self, dump_mir: bool) {
+        self.dump_mir = dump_mir;
+    }
+
+    /// Build the lock order graph of the lockguard relations into `lock_graph`.
+    pub fn set_emit_lock_graph(&mut self, emit_lock_graph: bool) {
+        self.emit_lock_graph = emit_lock_graph;
+    }
+
+    /// Collect the lockguards of each instance and the guard holders.
     fn collect_lockguards(
         &self,
         callgraph: &CallGraph<'tcx>,
-    ) -> FxHashMap<InstanceId, LockGuardMap<'tcx>> {
+        progress: &Progress,
+    ) -> (FxHashMap<InstanceId, LockGuardMap<'tcx>>, Vec<GuardHolder>) {
         let mut lockguards = FxHashMap::default();
-        for (instance_id, node) in callgraph.graph.node_references() {
+        let mut holders = Vec::new();
+        let total = callgraph.graph.node_count();
+        for (processed, (instance_id, node)) in callgraph.graph.node_references().enumerate() {
+            progress.update(processed + 1, total, "instances");
             let instance = match node {
-                CallGraphNode::WithBody(instance) => instance,
+                CallGraphNode::WithBody(instance) if callgraph.is_focused(instance_id) => instance,
                 _ => continue,
             };
-            // Only analyze local fn with body
-            if !instance.def_id().is_local() {
+            // Only analyze local fn with body,
+            // or non-local fn (e.g., generic lock wrappers from deps) if `analyze_deps_guards` is on.
+            // The lock APIs in std are skipped because they are modeled by `LockRegistry`.
+            let def_id = instance.def_id();
+            let is_local = def_id.is_local();
+            let analyze_dep = self.analyze_deps_guards
+                && self.tcx.is_mir_available(def_id)
+                && !is_std_crate(&self.tcx.crate_name(def_id.krate).to_string());
+            if !is_local && !analyze_dep {
                 continue;
             }
             let body = self.tcx.instance_mir(instance.def);
-            let mut lockguard_collector =
-                LockGuardCollector::new(instance_id, instance, body, self.tcx, self.param_env);
+            let mut lockguard_collector = LockGuardCollector::new(
+                instance_id,
+                instance,
+                body,
+                self.tcx,
+                self.param_env,
+                &self.guard_newtypes,
+            );
             lockguard_collector.analyze();
+            if !is_local {
+                // The returned lockguard of a non-local fn (e.g., `Mutex::lock`) is tracked in its caller.
+                lockguard_collector
+                    .lockguards
+                    .retain(|lockguard_id, _| lockguard_id.local != RETURN_PLACE);
+            }
+            holders.extend(lockguard_collector.holders);
             if !lockguard_collector.lockguards.is_empty() {
                 lockguards.insert(instance_id, lockguard_collector.lockguards);
             }
         }
-        lockguards
+        (lockguards, holders)
     }
 
     /// Collect condvar APIs.
@@ -110,19 +260,155 @@ impl<'tcx> DeadlockDetector<'tcx> {
             .collect()
     }
 
+    /// Collect the closures called by each instance of `catch_unwind`.
+    fn collect_catch_unwind_closures(
+        &self,
+        callgraph: &CallGraph<'tcx>,
+    ) -> FxHashMap<InstanceId, Vec<InstanceId>> {
+        callgraph
+            .graph
+            .node_references()
+            .filter_map(|(instance_id, node)| {
+                let closures = catch_unwind_instances(node.instance(), self.tcx, self.param_env)?
+                    .into_iter()
+                    .filter_map(|instance| callgraph.instance_to_index(&instance))
+                    .collect();
+                Some((instance_id, closures))
+            })
+            .collect()
+    }
+
+    /// Collect the closures run on the pool by each instance of the rayon APIs.
+    fn collect_rayon_closures(
+        &self,
+        callgraph: &CallGraph<'tcx>,
+    ) -> FxHashMap<InstanceId, Vec<InstanceId>> {
+        callgraph
+            .graph
+            .node_references()
+            .filter(|(_, node)| RayonApi::from_instance(node.instance(), self.tcx).is_some())
+            .map(|(instance_id, node)| {
+                let closures =
+                    RayonApi::closure_instances(node.instance(), self.tcx, self.param_env)
+                        .into_iter()
+                        .filter_map(|instance| callgraph.instance_to_index(&instance))
+                        .collect();
+                (instance_id, closures)
+            })
+            .collect()
+    }
+
+    /// Collect the once API and the initializers of each instance of the once APIs.
+    fn collect_once_initializers(
+        &self,
+        callgraph: &CallGraph<'tcx>,
+    ) -> FxHashMap<InstanceId, (OnceApi, Vec<InstanceId>)> {
+        callgraph
+            .graph
+            .node_references()
+            .filter_map(|(instance_id, node)| {
+                let once_api = OnceApi::from_instance(node.instance(), self.tcx)?;
+                let initializers =
+                    OnceApi::initializer_instances(node.instance(), self.tcx, self.param_env)
+                        .into_iter()
+                        .filter_map(|instance| callgraph.instance_to_index(&instance))
+                        .collect();
+                Some((instance_id, (once_api, initializers)))
+            })
+            .collect()
+    }
+
     /// Detect deadlock inter-procedurally and returns bug report.
     pub fn detect<'a>(
         &mut self,
         callgraph: &'a CallGraph<'tcx>,
         alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+        progress: &Progress,
     ) -> Vec<Report> {
-        let lockguards = self.collect_lockguards(callgraph);
+        progress.phase("lockguard collection");
+        self.guard_newtypes
+            .detect(callgraph, self.tcx, self.param_env);
+        let (lockguards, holders) = self.collect_lockguards(callgraph, progress);
+        // The alias queries on lockguards need the points-to info of the instances holding them.
+        alias_analysis.prefetch_pts(lockguards.keys().filter_map(|instance_id| {
+            match callgraph.index_to_instance(*instance_id) {
+                Some(CallGraphNode::WithBody(instance)) => Some(*instance),
+                _ => None,
+            }
+        }));
         let condvar_apis = self.collect_condvars(callgraph);
         let mut lockguards_before_condvar_apis: FxHashMap<InstanceId, LockGuardsBeforeCallSites> =
             condvar_apis
                 .keys()
                 .map(|instance_id| (*instance_id, FxHashMap::default()))
                 .collect();
+        // The executor APIs blocking on futures and the rendezvous APIs blocking on the peer threads,
+        // checked with the lockguards live at their callsites like condvars.
+        let block_on_apis = callgraph
+            .graph
+            .node_references()
+            .filter(|(_, node)| is_block_on_api(node.instance(), self.tcx))
+            .map(|(instance_id, _)| instance_id)
+            .collect::<FxHashSet<_>>();
+        let rendezvous_apis = callgraph
+            .graph
+            .node_references()
+            .filter_map(|(instance_id, node)| {
+                RendezvousApi::from_instance(node.instance(), self.tcx)
+                    .map(|rendezvous_api| (instance_id, rendezvous_api))
+            })
+            .collect::<FxHashMap<_, _>>();
+        let blocking_apis = block_on_apis
+            .iter()
+            .copied()
+            .chain(
+                rendezvous_apis
+                    .iter()
+                    .filter(|(_, rendezvous_api)| rendezvous_api.is_blocking())
+                    .map(|(instance_id, _)| *instance_id),
+            )
+            .collect::<FxHashSet<_>>();
+        let mut lockguards_before_blocking_apis: FxHashMap<InstanceId, LockGuardsBeforeCallSites> =
+            FxHashMap::default();
+        // The closures of `catch_unwind` run with the lockguards live at its callsites.
+        let catch_unwind_closures = self.collect_catch_unwind_closures(callgraph);
+        // The spawn APIs run the closures (or futures) in new threads without the caller's lockguards,
+        // so they are not propagated into like `ThreadRoots`.
+        let spawn_apis = callgraph
+            .graph
+            .node_references()
+            .filter(|(_, node)| SpawnApi::from_instance(node.instance(), self.tcx).is_some())
+            .map(|(instance_id, _)| instance_id)
+            .collect::<FxHashSet<_>>();
+        // Nor do the rayon APIs run their closures with the caller's lockguards,
+        // but the caller blocks on the closures, so the lockguards at the callsites are checked against them.
+        let rayon_apis = self.collect_rayon_closures(callgraph);
+        let rayon_closures = rayon_apis.values().flatten().collect::<FxHashSet<_>>();
+        let mut lockguards_before_rayon_apis: FxHashMap<InstanceId, LockGuardsBeforeCallSites> =
+            FxHashMap::default();
+        // Nor do the once APIs, whose initializers may run in another thread holding the initialization,
+        // but the caller blocks on the initialization, so the lockguards at the callsites are checked against them.
+        let once_apis = self.collect_once_initializers(callgraph);
+        let once_initializers = once_apis
+            .values()
+            .flat_map(|(_, initializers)| initializers)
+            .collect::<FxHashSet<_>>();
+        let mut lockguards_before_once_apis: FxHashMap<InstanceId, LockGuardsBeforeCallSites> =
+            FxHashMap::default();
+        // The panic sites poisoning the lockguards live at them, see `poison.rs`.
+        let panic_sites = if self.config.is_enabled("panic_holding_lock") {
+            poison::collect_panic_sites(callgraph, self.tcx)
+        } else {
+            FxHashMap::default()
+        };
+        let mut lockguards_before_panics: LockGuardsBeforeCallSites = FxHashMap::default();
+        // The accesses to the fields of `self` checked against their usual locks, see `lock_coverage.rs`.
+        let field_accesses = if self.config.is_enabled("lock_coverage") {
+            lock_coverage::collect_field_accesses(callgraph, self.tcx)
+        } else {
+            FxHashMap::default()
+        };
+        let mut lockguards_before_field_accesses: LockGuardsBeforeCallSites = FxHashMap::default();
         // Init `worklist` with all the `InstanceId`s
         let mut worklist = callgraph
             .graph
@@ -135,30 +421,85 @@ impl<'tcx> DeadlockDetector<'tcx> {
             .copied()
             .map(|id| (id, LiveLockGuards::default()))
             .collect::<FxHashMap<_, _>>();
+        // The instances whose gen/kill exceeded the budget are treated as if without lockguards.
+        let mut skipped = FxHashSet::default();
         // The fixed-point algorithm
+        progress.phase("fixpoint");
         while let Some(id) = worklist.pop_front() {
-            if let Some(lockguard_info) = lockguards.get(&id) {
+            if let Some(lockguard_info) = lockguards.get(&id).filter(|_| !skipped.contains(&id)) {
                 let instance = match callgraph.index_to_instance(id).unwrap() {
                     CallGraphNode::WithBody(instance) => instance,
                     _ => continue,
                 };
                 let body = self.tcx.instance_mir(instance.def);
                 let context = contexts[&id].clone();
-                let states = self.intraproc_gen_kill(body, &context, lockguard_info);
+                let states = match self.intraproc_gen_kill(body, &context, lockguard_info) {
+                    Ok(states) => states,
+                    Err(exceeded) => {
+                        self.skipped_functions.push(exceeded.skipped(
+                            self.tcx.def_path_str(instance.def_id()),
+                            "lockguard gen/kill",
+                        ));
+                        skipped.insert(id);
+                        worklist.push_front(id);
+                        continue;
+                    }
+                };
+                for (loc, _, _) in panic_sites.get(&id).into_iter().flatten() {
+                    lockguards_before_panics
+                        .entry((id, *loc))
+                        .or_default()
+                        .union_in_place(states[loc].clone());
+                }
+                for (loc, _, _) in field_accesses.get(&id).into_iter().flatten() {
+                    lockguards_before_field_accesses
+                        .entry((id, *loc))
+                        .or_default()
+                        .union_in_place(states[loc].clone());
+                }
                 for edge in callgraph.graph.edges_directed(id, Direction::Outgoing) {
                     let callee = edge.target();
+                    if spawn_apis.contains(&callee) {
+                        continue;
+                    }
+                    if rayon_apis.contains_key(&callee) {
+                        for loc in edge.weight().iter().filter_map(CallSiteLocation::location) {
+                            lockguards_before_rayon_apis
+                                .entry(callee)
+                                .or_default()
+                                .entry((id, loc))
+                                .or_default()
+                                .union_in_place(states[&loc].clone());
+                        }
+                        continue;
+                    }
+                    if once_apis.contains_key(&callee) {
+                        for loc in edge.weight().iter().filter_map(CallSiteLocation::location) {
+                            lockguards_before_once_apis
+                                .entry(callee)
+                                .or_default()
+                                .entry((id, loc))
+                                .or_default()
+                                .union_in_place(states[&loc].clone());
+                        }
+                        continue;
+                    }
                     for callsite in edge.weight() {
                         let loc = match callsite.location() {
                             Some(loc) => loc,
                             None => continue,
                         };
-                        let callsite_state = states[&loc].clone();
-                        let changed = contexts
-                            .get_mut(&callee)
-                            .unwrap()
-                            .union_in_place(callsite_state);
-                        if changed {
-                            worklist.push_back(callee);
+                        for target in iter::once(&callee)
+                            .chain(catch_unwind_closures.get(&callee).into_iter().flatten())
+                        {
+                            let callsite_state = states[&loc].clone();
+                            let changed = contexts
+                                .get_mut(target)
+                                .unwrap()
+                                .union_in_place(callsite_state);
+                            if changed {
+                                worklist.push_back(*target);
+                            }
                         }
                         if condvar_apis.contains_key(&callee) {
                             lockguards_before_condvar_apis
@@ -168,15 +509,75 @@ impl<'tcx> DeadlockDetector<'tcx> {
                                 .or_default()
                                 .union_in_place(states[&loc].clone());
                         }
+                        if blocking_apis.contains(&callee) {
+                            lockguards_before_blocking_apis
+                                .entry(callee)
+                                .or_default()
+                                .entry((id, loc))
+                                .or_default()
+                                .union_in_place(states[&loc].clone());
+                        }
                     }
                 }
             } else {
+                for (loc, _, _) in panic_sites.get(&id).into_iter().flatten() {
+                    lockguards_before_panics
+                        .entry((id, *loc))
+                        .or_default()
+                        .union_in_place(contexts[&id].clone());
+                }
+                for (loc, _, _) in field_accesses.get(&id).into_iter().flatten() {
+                    lockguards_before_field_accesses
+                        .entry((id, *loc))
+                        .or_default()
+                        .union_in_place(contexts[&id].clone());
+                }
                 for edge in callgraph.graph.edges_directed(id, Direction::Outgoing) {
                     let callee = edge.target();
-                    let context = contexts[&id].clone();
-                    let changed = contexts.get_mut(&callee).unwrap().union_in_place(context);
-                    if changed {
-                        worklist.push_back(callee);
+                    // Nor are the closures (or futures) spawned, run on the pool, or run as the initializers,
+                    // which are only defined in the caller.
+                    let only_defined = edge
+                        .weight()
+                        .iter()
+                        .all(|callsite| callsite.location().is_none());
+                    if spawn_apis.contains(&callee)
+                        || only_defined
+                            && (edge.weight().iter().any(CallSiteLocation::is_spawn)
+                                || rayon_closures.contains(&callee)
+                                || once_initializers.contains(&callee))
+                    {
+                        continue;
+                    }
+                    if rayon_apis.contains_key(&callee) {
+                        for loc in edge.weight().iter().filter_map(CallSiteLocation::location) {
+                            lockguards_before_rayon_apis
+                                .entry(callee)
+                                .or_default()
+                                .entry((id, loc))
+                                .or_default()
+                                .union_in_place(contexts[&id].clone());
+                        }
+                        continue;
+                    }
+                    if once_apis.contains_key(&callee) {
+                        for loc in edge.weight().iter().filter_map(CallSiteLocation::location) {
+                            lockguards_before_once_apis
+                                .entry(callee)
+                                .or_default()
+                                .entry((id, loc))
+                                .or_default()
+                                .union_in_place(contexts[&id].clone());
+                        }
+                        continue;
+                    }
+                    for target in iter::once(&callee)
+                        .chain(catch_unwind_closures.get(&callee).into_iter().flatten())
+                    {
+                        let context = contexts[&id].clone();
+                        let changed = contexts.get_mut(target).unwrap().union_in_place(context);
+                        if changed {
+                            worklist.push_back(*target);
+                        }
                     }
                     if condvar_apis.contains_key(&callee) {
                         for callsite in edge.weight() {
@@ -190,123 +591,1016 @@ impl<'tcx> DeadlockDetector<'tcx> {
                             }
                         }
                     }
+                    if blocking_apis.contains(&callee) {
+                        for loc in edge.weight().iter().filter_map(CallSiteLocation::location) {
+                            lockguards_before_blocking_apis
+                                .entry(callee)
+                                .or_default()
+                                .entry((id, loc))
+                                .or_default()
+                                .union_in_place(contexts[&id].clone());
+                        }
+                    }
                 }
             }
         }
 
+        if self.dump_mir {
+            self.dump_focused_mir(callgraph, &lockguards, &contexts);
+        }
+
         // Get lockguard info
         let mut info = FxHashMap::default();
         for (_, map) in lockguards.into_iter() {
             info.extend(map.into_iter());
         }
 
-        let mut reports = self.detect_deadlock(&info, callgraph, alias_analysis);
-        if !lockguards_before_condvar_apis.is_empty() {
-            reports.extend(
-                self.detect_condvar_misuse(
-                    &lockguards_before_condvar_apis,
-                    &condvar_apis,
+        let mut reports = self.detect_deadlock(&info, callgraph, alias_analysis, progress);
+        progress.phase("condvar");
+        if !lockguards_before_condvar_apis.is_empty()
+            && (self.config.is_enabled("condvar")
+                || self.config.is_enabled("condvar_multiple_mutexes"))
+        {
+            reports.extend(self.detect_condvar_misuse(
+                &lockguards_before_condvar_apis,
+                &condvar_apis,
+                &info,
+                callgraph,
+                alias_analysis,
+            ));
+        }
+        if !lockguards_before_rayon_apis.is_empty() && self.config.is_enabled("rayon_deadlock") {
+            progress.phase("rayon");
+            reports.extend(self.detect_rayon_deadlock(
+                &lockguards_before_rayon_apis,
+                &rayon_apis,
+                &info,
+                callgraph,
+                alias_analysis,
+            ));
+        }
+        if !once_apis.is_empty()
+            && (self.config.is_enabled("once_reentrancy")
+                || self.config.is_enabled("once_init_deadlock"))
+        {
+            progress.phase("once");
+            if self.config.is_enabled("once_reentrancy") {
+                reports.extend(self.detect_once_reentrancy(&once_apis, callgraph, alias_analysis));
+            }
+            if self.config.is_enabled("once_init_deadlock") {
+                reports.extend(self.detect_once_init_deadlock(
+                    &lockguards_before_once_apis,
+                    &once_apis,
                     &info,
                     callgraph,
                     alias_analysis,
-                )
-                .into_iter(),
+                ));
+            }
+        }
+        if self.config.is_enabled("await_holding_lock") {
+            progress.phase("await holding lock");
+            reports.extend(self.detect_await_holding(callgraph));
+        }
+        if !block_on_apis.is_empty() && self.config.is_enabled("block_on_holding_lock") {
+            progress.phase("block on holding lock");
+            reports.extend(self.detect_block_on_holding_lock(
+                &lockguards_before_blocking_apis,
+                &block_on_apis,
+                &info,
+                callgraph,
+            ));
+        }
+        if !rendezvous_apis.is_empty() && self.config.is_enabled("rendezvous_deadlock") {
+            progress.phase("rendezvous");
+            reports.extend(self.detect_rendezvous_deadlock(
+                &lockguards_before_blocking_apis,
+                &rendezvous_apis,
+                &info,
+                callgraph,
+                alias_analysis,
+            ));
+        }
+        if self.config.is_enabled("pool_self_join") {
+            progress.phase("pool self-join");
+            reports.extend(self.detect_pool_self_join(callgraph));
+        }
+        let force_unlocks = force_unlock::collect_force_unlocks(callgraph, self.tcx);
+        if self.config.is_enabled("lock_guard_leaked") {
+            progress.phase("leaked lockguards");
+            reports.extend(guard_leak::detect_leaked_lockguards(
+                &info,
+                &self.guard_newtypes,
+                &force_unlocks,
+                callgraph,
+                alias_analysis,
+                self.tcx,
+            ));
+        }
+        if !force_unlocks.is_empty()
+            && (self.config.is_enabled("double_unlock")
+                || self.config.is_enabled("use_after_unlock"))
+        {
+            progress.phase("force unlock");
+            reports.extend(force_unlock::detect_force_unlock_misuse(
+                &force_unlocks,
+                &info,
+                callgraph,
+                alias_analysis,
+                self.tcx,
+            ));
+        }
+        if self.config.is_enabled("send_hazard") {
+            progress.phase("send hazard");
+            reports.extend(self.detect_send_hazard(callgraph));
+        }
+        if self.config.is_enabled("implicit_release_order") {
+            progress.phase("release order");
+            reports.extend(release_order::detect_implicit_release_order(
+                &holders,
+                &self.lockguard_relations,
+                &info,
+                callgraph,
+                alias_analysis,
+                self.tcx,
+            ));
+        }
+        if self.config.is_enabled("hot_path_lock") && !self.hot_path_fns.is_empty() {
+            progress.phase("hot path");
+            reports.extend(hot_path::detect_hot_path_locks(
+                &self.hot_path_fns,
+                &info,
+                callgraph,
+                self.tcx,
+            ));
+        }
+        if self.config.is_enabled("discarded_guard") {
+            progress.phase("discarded guards");
+            reports.extend(discarded_guard::detect_discarded_guards(
+                &self.guard_newtypes,
+                self.tcx,
+            ));
+        }
+        if !field_accesses.is_empty() {
+            progress.phase("lock coverage");
+            reports.extend(lock_coverage::detect_lock_coverage(
+                &lockguards_before_field_accesses,
+                &field_accesses,
+                &info,
+                callgraph,
+                alias_analysis,
+                self.config.lock_coverage_threshold,
+                self.tcx,
+            ));
+        }
+        if !lockguards_before_panics.is_empty() {
+            progress.phase("panic holding lock");
+            reports.extend(poison::detect_panics_holding_lock(
+                &lockguards_before_panics,
+                &panic_sites,
+                &info,
+                callgraph,
+                self.config.callchain_limits,
+                self.tcx,
+            ));
+        }
+        if self.config.is_enabled("plugin") && !self.plugins.is_empty() {
+            progress.phase("plugins");
+            let mut cx = PluginContext::new(
+                self.tcx,
+                callgraph,
+                &info,
+                &self.lockguard_relations,
+                alias_analysis,
+                self.config.callchain_limits,
             );
+            for plugin in &self.plugins {
+                plugin.check(&mut cx);
+            }
+            reports.extend(cx.into_reports());
+        }
+        // Dropped before classifying the locks so that the lock classes only cover the reported ones.
+        self.config.retain(&mut reports);
+        self.lock_classes =
+            inventory::classify_locks(&mut reports, &info, callgraph, alias_analysis, self.tcx);
+        if self.emit_lock_graph {
+            progress.phase("lock graph");
+            self.lock_graph = Some(LockGraph::new(
+                &self.lockguard_relations,
+                &info,
+                callgraph,
+                alias_analysis,
+                self.tcx,
+            ));
         }
         reports
     }
 
-    /// Detect condvar misuse.
-    /// First collect Condvar APIs info: callsites to (Condvar, MutexGuard)
-    /// - std::sync::Condvar::wait(&Condvar, MutexGuard) -> MutexGuard
-    /// - std::sync::Condvar::notify(&Condvar)
-    /// - parking_lot::Condvar::wait(&Condvar, &mut MutexGuard)
-    /// - parking_lot::Condvar::notify(&Condvar)
-    /// Then match `wait` and `notify` if their Condvars alias with each other.
-    /// Finally check LiveLockGuards before `wait` and `notify`:
-    /// if they have aliasing LockGuards that are not aliased with MutexGuard in `wait` then possibly deadlock
-    /// else if LiveLockGuards before `notify` does not contain a LockGuard aliasing with
-    /// the MutexLock in `wait`, then possibly missing lock before notify.
-    /// Note that the `missing lock` warning is imprecise because the rule rigidly follows the
-    /// Condvar example code. In fact, it is correct as long as there is a LockGuard aliasing with
-    /// the MutexGuard in `wait` that dominates `notify`.
-    /// TODO(boqin): check if an aliased MutexGuard dominates `notify` instead.
-    fn detect_condvar_misuse<'a>(
+    /// Detect the tasks running on a pool blocking on the completion of work scheduled into the same pool,
+    /// e.g., `pool.install(|| pool.scope(|s| ..).wait())`, for the pools declared `schedules_into_same_pool`.
+    /// The closure passed to an install API runs on the pool,
+    /// so a wait API of the same pool reachable from the closure in CallGraph is a self-join.
+    fn detect_pool_self_join(&self, callgraph: &CallGraph<'tcx>) -> Vec<Report> {
+        let mut install_apis = Vec::new();
+        let mut wait_apis = FxHashMap::default();
+        for (instance_id, node) in callgraph.graph.node_references() {
+            match PoolApi::from_instance(node.instance(), &self.pools, self.tcx) {
+                Some(PoolApi::Install(pool)) if self.pools[pool].schedules_into_same_pool => {
+                    install_apis.push((instance_id, pool))
+                }
+                Some(PoolApi::Wait(pool)) => {
+                    wait_apis.insert(instance_id, pool);
+                }
+                _ => {}
+            }
+        }
+        let callsite_spans = |callee: InstanceId| self.callsite_spans(callee, callgraph);
+        let mut diagnoses = FxHashSet::default();
+        for (install_api, pool) in install_apis {
+            let closures = SpawnApi::spawned_instances(
+                callgraph.index_to_instance(install_api).unwrap().instance(),
+                self.tcx,
+                self.param_env,
+            )
+            .into_iter()
+            .filter_map(|instance| callgraph.instance_to_index(&instance))
+            .collect::<Vec<_>>();
+            // The instances running on the pool.
+            let mut on_pool = closures.iter().copied().collect::<FxHashSet<_>>();
+            let mut worklist = closures;
+            while let Some(id) = worklist.pop() {
+                for callee in callgraph.graph.neighbors_directed(id, Direction::Outgoing) {
+                    if on_pool.insert(callee) {
+                        worklist.push(callee);
+                    }
+                }
+            }
+            let waits = on_pool
+                .iter()
+                .filter(|id| wait_apis.get(id) == Some(&pool))
+                .flat_map(|wait_api| {
+                    let wait_api_path = api_path(
+                        callgraph.index_to_instance(*wait_api).unwrap().instance(),
+                        self.tcx,
+                    );
+                    callsite_spans(*wait_api)
+                        .into_iter()
+                        .filter(|(caller, _)| on_pool.contains(caller))
+                        .map(move |(_, span)| (wait_api_path.clone(), span))
+                })
+                .collect::<Vec<_>>();
+            for (_, install_span) in callsite_spans(install_api) {
+                for (wait_api, wait_span) in waits.iter() {
+                    diagnoses.insert(PoolSelfJoinDiagnosis::new(
+                        self.pools[pool].name.clone(),
+                        install_span.clone(),
+                        wait_api.clone(),
+                        wait_span.clone(),
+                    ));
+                }
+            }
+        }
+        diagnoses
+            .into_iter()
+            .map(|diagnosis| {
+                Report::PoolSelfJoin(ReportContent::new(
+                    "PoolSelfJoin".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "A task running on the pool blocks on the work scheduled into the same pool, which deadlocks if the pool is single-threaded".to_owned(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Detect the locks held by the callers of the rayon APIs and acquired again by the closures run on the pool,
+    /// e.g., `let _g = m.lock(); v.par_iter().for_each(|_| { m.lock(); })`.
+    /// The caller blocks until the closures complete, which never acquire the lock held by the caller.
+    /// Like `detect_pool_self_join`, the instances reachable from the closures in CallGraph run on the pool.
+    fn detect_rayon_deadlock<'a>(
         &self,
-        lockguards_before_condvar_apis: &FxHashMap<InstanceId, LockGuardsBeforeCallSites>,
-        condvar_apis: &FxHashMap<InstanceId, CondvarApi>,
+        lockguards_before_rayon_apis: &FxHashMap<InstanceId, LockGuardsBeforeCallSites>,
+        rayon_apis: &FxHashMap<InstanceId, Vec<InstanceId>>,
         lockguards: &LockGuardMap<'tcx>,
         callgraph: &'a CallGraph<'tcx>,
         alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
     ) -> Vec<Report> {
-        let mut reports = Vec::new();
-        // Collect Condvar API info
-        // callee_instance -> (Caller, Location, Callee) -> (&Condvar, MutexGuard)
-        let mut std_notify = FxHashMap::default();
-        // callee_instance -> (Caller, Location, Callee) -> &Condvar
-        let mut std_wait = FxHashMap::default();
-        // callee_instance -> (Caller, Location, Callee) -> (&Condvar, &mut MutexGuard)
-        let mut parking_lot_notify = FxHashMap::default();
-        // callee_instance -> (Caller, Location, Callee) -> &Condvar
-        let mut parking_lot_wait = FxHashMap::default();
-        for (callee_id, callsite_lockguards) in lockguards_before_condvar_apis {
-            let condvar_api = condvar_apis.get(callee_id).unwrap();
-            for (caller_id, loc) in callsite_lockguards.keys() {
-                let body = self.tcx.instance_mir(
+        let mut instance_lockguards: FxHashMap<InstanceId, Vec<LockGuardId>> = FxHashMap::default();
+        for lockguard_id in lockguards.keys() {
+            instance_lockguards
+                .entry(lockguard_id.instance_id)
+                .or_default()
+                .push(*lockguard_id);
+        }
+        let mut diagnoses = FxHashSet::default();
+        for (rayon_api, callsite_lockguards) in lockguards_before_rayon_apis {
+            let rayon_api_path = api_path(
+                callgraph.index_to_instance(*rayon_api).unwrap().instance(),
+                self.tcx,
+            );
+            let on_pool = reachable_instances(rayon_apis[rayon_api].clone(), callgraph);
+            let inner_lockguards = on_pool
+                .iter()
+                .filter_map(|id| instance_lockguards.get(id))
+                .flatten()
+                .collect::<Vec<_>>();
+            for ((caller_id, loc), outer_lockguards) in callsite_lockguards {
+                let caller_body = self.tcx.instance_mir(
                     callgraph
                         .index_to_instance(*caller_id)
                         .unwrap()
                         .instance()
                         .def,
                 );
-                let term = body[loc.block].terminator();
-                let args = match &term.kind {
-                    TerminatorKind::Call { func: _, args, .. } => args.clone(),
-                    _ => continue,
-                };
-                match condvar_api {
-                    CondvarApi::Std(StdCondvarApi::Wait(_)) => {
-                        if let (Operand::Move(condvar_ref), Operand::Move(mutex_guard)) =
-                            (&args[0], &args[1])
-                        {
-                            // callsite -> (&Condvar, MutexGuard)
-                            std_wait.insert(
-                                (*caller_id, *loc, *callee_id),
-                                (
-                                    AliasId {
-                                        instance_id: *caller_id,
-                                        local: condvar_ref.local,
-                                    },
-                                    AliasId {
-                                        instance_id: *caller_id,
-                                        local: mutex_guard.local,
-                                    },
-                                ),
-                            );
-                        }
-                    }
-                    CondvarApi::ParkingLot(ParkingLotCondvarApi::Wait(_)) => {
-                        if let (Operand::Move(condvar_ref), Operand::Move(mutex_guard_ref)) =
-                            (&args[0], &args[1])
-                        {
-                            // callsite -> (&Condvar, &mut MutexGuard)
-                            parking_lot_wait.insert(
-                                (*caller_id, *loc, *callee_id),
-                                (
-                                    AliasId {
-                                        instance_id: *caller_id,
-                                        local: condvar_ref.local,
-                                    },
-                                    AliasId {
-                                        instance_id: *caller_id,
-                                        local: mutex_guard_ref.local,
-                                    },
-                                ),
+                let rayon_span = format!("{:?}", caller_body.source_info(*loc).span);
+                for a in outer_lockguards.raw_lockguard_ids() {
+                    for b in inner_lockguards.iter() {
+                        let possibility =
+                            deadlock_possibility(a, b, lockguards, alias_analysis, false).0;
+                        if possibility.or_paranoid(self.paranoid) > DeadlockPossibility::Unlikely {
+                            let mut diagnosis = RayonDeadlockDiagnosis::new(
+                                format!("{:?}", lockguards[a].lockguard_ty),
+                                format!("{:?}", lockguards[a].span),
+                                rayon_api_path.clone(),
+                                rayon_span.clone(),
+                                format!("{:?}", lockguards[*b].span),
+                            );
+                            diagnosis.alias_confidence =
+                                alias_confidence(possibility == DeadlockPossibility::Unknown);
+                            diagnoses.insert(diagnosis);
+                        }
+                    }
+                }
+            }
+        }
+        diagnoses
+            .into_iter()
+            .map(|diagnosis| {
+                Report::RayonDeadlock(ReportContent::new(
+                    "RayonDeadlock".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "The lock held by the caller of the rayon API is acquired again by a closure run on the pool, while the caller blocks on the closure".to_owned(),
+                ))
+            })
+            .collect()
+    }
+
+    /// The (caller, span) of each callsite of `callee` in CallGraph.
+    fn callsite_spans(
+        &self,
+        callee: InstanceId,
+        callgraph: &CallGraph<'tcx>,
+    ) -> Vec<(InstanceId, String)> {
+        callgraph
+            .graph
+            .edges_directed(callee, Direction::Incoming)
+            .flat_map(|edge| {
+                let caller = callgraph.index_to_instance(edge.source()).unwrap();
+                let body = self.tcx.instance_mir(caller.instance().def);
+                edge.weight()
+                    .iter()
+                    .filter_map(|callsite| callsite.location())
+                    .map(|loc| (edge.source(), format!("{:?}", body.source_info(loc).span)))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// The types and spans of the locals living across await points in a local async fn/block,
+    /// i.e., the fields saved in its generator layout.
+    fn saved_across_awaits(&self, instance: &Instance<'tcx>) -> Vec<(Ty<'tcx>, Span)> {
+        let def_id = instance.def_id();
+        if !def_id.is_local() || self.tcx.generator_kind(def_id).is_none() {
+            return Vec::new();
+        }
+        let body = self.tcx.instance_mir(instance.def);
+        let Some(layout) = body.generator_layout() else {
+            return Vec::new();
+        };
+        layout
+            .field_tys
+            .iter()
+            .map(|saved_ty| {
+                let ty = instance.instantiate_mir_and_normalize_erasing_regions(
+                    self.tcx,
+                    self.param_env,
+                    EarlyBinder::bind(saved_ty.ty),
+                );
+                let span = saved_ty.source_info.span;
+                // Spans inside `async_trait` expansions point to the macro, use the callsite instead.
+                let span = if span.from_expansion() {
+                    span.source_callsite()
+                } else {
+                    span
+                };
+                (ty, span)
+            })
+            .collect()
+    }
+
+    /// Detect the non-Send guards held across await points in the futures spawned by local spawn APIs,
+    /// e.g., `tokio::task::spawn_local`, which compile but fail to once the futures are spawned by `tokio::spawn`.
+    /// The async fns/blocks reachable from the spawned futures in CallGraph (not through other spawn APIs,
+    /// local or not) run on the local task, so the lockguards that are not Send
+    /// and the `RefCell` borrows saved in their layouts are reported.
+    fn detect_send_hazard(&self, callgraph: &CallGraph<'tcx>) -> Vec<Report> {
+        // The local spawn APIs and their futures.
+        let mut local_spawns = Vec::new();
+        // Do not propagate into the spawn APIs (local or not) or the futures spawned by them like `ThreadRoots`,
+        // since a future is reachable from the body creating it, e.g., the one passed to `LocalSet::block_on`.
+        let mut boundaries = FxHashSet::default();
+        for (instance_id, node) in callgraph.graph.node_references() {
+            let local = is_local_spawn_api(node.instance(), self.tcx);
+            if !local && SpawnApi::from_instance(node.instance(), self.tcx).is_none() {
+                continue;
+            }
+            let futures = SpawnApi::spawned_instances(node.instance(), self.tcx, self.param_env)
+                .into_iter()
+                .filter_map(|instance| callgraph.instance_to_index(&instance))
+                .collect::<Vec<_>>();
+            boundaries.insert(instance_id);
+            boundaries.extend(futures.iter().copied());
+            if local {
+                local_spawns.push((instance_id, futures));
+            }
+        }
+        let mut diagnoses = FxHashSet::default();
+        for (spawn_api, futures) in local_spawns {
+            // The instances running on the local task.
+            let mut on_task = futures.iter().copied().collect::<FxHashSet<_>>();
+            let mut worklist = futures;
+            while let Some(id) = worklist.pop() {
+                for callee in callgraph.graph.neighbors_directed(id, Direction::Outgoing) {
+                    if !boundaries.contains(&callee) && on_task.insert(callee) {
+                        worklist.push(callee);
+                    }
+                }
+            }
+            let node = callgraph.index_to_instance(spawn_api).unwrap();
+            let spawn_api_path = api_path(node.instance(), self.tcx);
+            // The local spawn APIs called by each other in tokio, e.g., `LocalSet::block_on` calling `run_until`,
+            // are only reported at the callsites in the crate.
+            let spawn_spans = self
+                .callsite_spans(spawn_api, callgraph)
+                .into_iter()
+                .filter(|(caller, _)| {
+                    let caller = callgraph.index_to_instance(*caller).unwrap().instance();
+                    caller.def_id().is_local()
+                })
+                .collect::<Vec<_>>();
+            for id in on_task {
+                let instance = match callgraph.index_to_instance(id) {
+                    Some(CallGraphNode::WithBody(instance)) => instance,
+                    _ => continue,
+                };
+                for (ty, span) in self.saved_across_awaits(instance) {
+                    let Some(guard_type) = self.non_send_guard(ty) else {
+                        continue;
+                    };
+                    let fn_name = self
+                        .tcx
+                        .def_path_str(self.tcx.typeck_root_def_id(instance.def_id()));
+                    for (_, spawn_span) in spawn_spans.iter() {
+                        diagnoses.insert(SendHazardDiagnosis::new(
+                            fn_name.clone(),
+                            guard_type.clone(),
+                            format!("{:?}", span),
+                            spawn_api_path.clone(),
+                            spawn_span.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+        diagnoses
+            .into_iter()
+            .map(|diagnosis| {
+                Report::SendHazard(ReportContent::new(
+                    "SendHazard".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "The guard is not Send but held across an await point in a future spawned locally, which fails to compile if the future is spawned by a Send-requiring API, e.g., `tokio::spawn`".to_owned(),
+                ))
+            })
+            .collect()
+    }
+
+    /// The type of a guard that is not Send, i.e., a lockguard except the async and spin ones,
+    /// including a `RefCell` borrow (`Ref` and `RefMut`).
+    fn non_send_guard(&self, ty: Ty<'tcx>) -> Option<String> {
+        let lockguard_ty = self.guard_newtypes.lockguard_ty(ty, self.tcx)?;
+        (!lockguard_ty.is_send()).then(|| format!("{:?}", lockguard_ty))
+    }
+
+    /// Detect lockguards held across await points, except the `RefCell` borrows, which never block.
+    /// A local living across a yield point is saved in the generator layout,
+    /// so any lockguard among the saved fields is held across an await.
+    /// This covers plain async fns/blocks as well as the boxed futures generated by `async_trait`,
+    /// whose generator bodies are reported under the original method.
+    fn detect_await_holding(&self, callgraph: &CallGraph<'tcx>) -> Vec<Report> {
+        let mut reports = Vec::new();
+        for (_, node) in callgraph.graph.node_references() {
+            let instance = match node {
+                CallGraphNode::WithBody(instance) => instance,
+                _ => continue,
+            };
+            for (ty, span) in self.saved_across_awaits(instance) {
+                let lockguard_ty = match self.guard_newtypes.lockguard_ty(ty, self.tcx) {
+                    Some(lockguard_ty) if !lockguard_ty.is_async() && !lockguard_ty.is_borrow() => {
+                        lockguard_ty
+                    }
+                    _ => continue,
+                };
+                let fn_name = self
+                    .tcx
+                    .def_path_str(self.tcx.typeck_root_def_id(instance.def_id()));
+                let diagnosis = AwaitHoldingLockDiagnosis::new(
+                    fn_name,
+                    format!("{:?}", lockguard_ty),
+                    format!("{:?}", span),
+                );
+                reports.push(Report::AwaitHoldingLock(ReportContent::new(
+                    "AwaitHoldingLock".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "The lock is held across an await point, which may block the executor thread"
+                        .to_owned(),
+                )));
+            }
+        }
+        reports
+    }
+
+    /// Detect the lockguards live at the callsites of the executor APIs blocking on futures,
+    /// e.g., `let _g = m.lock(); futures::executor::block_on(fut)`.
+    /// The guards of async locks are skipped, since the future may acquire the async lock and yield instead of blocking,
+    /// and so are the `RefCell` borrows, which never block.
+    fn detect_block_on_holding_lock(
+        &self,
+        lockguards_before_blocking_apis: &FxHashMap<InstanceId, LockGuardsBeforeCallSites>,
+        block_on_apis: &FxHashSet<InstanceId>,
+        lockguards: &LockGuardMap<'tcx>,
+        callgraph: &CallGraph<'tcx>,
+    ) -> Vec<Report> {
+        let mut diagnoses = FxHashSet::default();
+        for (block_on_api, callsite_lockguards) in lockguards_before_blocking_apis
+            .iter()
+            .filter(|(callee, _)| block_on_apis.contains(callee))
+        {
+            let block_on_api_path = api_path(
+                callgraph
+                    .index_to_instance(*block_on_api)
+                    .unwrap()
+                    .instance(),
+                self.tcx,
+            );
+            for ((caller_id, loc), live) in callsite_lockguards {
+                let caller = callgraph.index_to_instance(*caller_id).unwrap().instance();
+                let caller_body = self.tcx.instance_mir(caller.def);
+                let fn_name = self
+                    .tcx
+                    .def_path_str(self.tcx.typeck_root_def_id(caller.def_id()));
+                for lockguard_id in live.raw_lockguard_ids() {
+                    let info = &lockguards[lockguard_id];
+                    if info.lockguard_ty.is_async() || info.lockguard_ty.is_borrow() {
+                        continue;
+                    }
+                    diagnoses.insert(BlockOnHoldingLockDiagnosis::new(
+                        fn_name.clone(),
+                        format!("{:?}", info.lockguard_ty),
+                        format!("{:?}", info.span),
+                        block_on_api_path.clone(),
+                        format!("{:?}", caller_body.source_info(*loc).span),
+                    ));
+                }
+            }
+        }
+        diagnoses
+            .into_iter()
+            .map(|diagnosis| {
+                Report::BlockOnHoldingLock(ReportContent::new(
+                    "BlockOnHoldingLock".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "The lock is held while blocking on a future, which deadlocks if the future needs the lock and stalls the thread otherwise".to_owned(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Detect the initializers of the once APIs reaching the same API on the same receiver,
+    /// e.g., `CONFIG.get_or_init(|| load())` where `load` calls `CONFIG.get_or_init(..)` again.
+    /// The receivers (i.e., the first args) are compared by `AliasAnalysis`,
+    /// except for `lazy_static`, whose API instance is unique to each lazy static by its initializer.
+    /// Only the callsites in the crate are checked, since the once APIs call each other in the deps.
+    fn detect_once_reentrancy<'a>(
+        &self,
+        once_apis: &FxHashMap<InstanceId, (OnceApi, Vec<InstanceId>)>,
+        callgraph: &'a CallGraph<'tcx>,
+        alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    ) -> Vec<Report> {
+        // (once API, caller, span, receiver) of each callsite in the crate.
+        let mut callsites = Vec::new();
+        for once_api in once_apis.keys() {
+            for edge in callgraph
+                .graph
+                .edges_directed(*once_api, Direction::Incoming)
+            {
+                let caller = callgraph
+                    .index_to_instance(edge.source())
+                    .unwrap()
+                    .instance();
+                if !caller.def_id().is_local() {
+                    continue;
+                }
+                let body = self.tcx.instance_mir(caller.def);
+                for loc in edge.weight().iter().filter_map(CallSiteLocation::location) {
+                    let receiver = match &body[loc.block].terminator().kind {
+                        TerminatorKind::Call { args, .. } => match args.get(0) {
+                            Some(Operand::Move(place) | Operand::Copy(place)) => Some(place.local),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    let span = format!("{:?}", once_callsite_span(body.source_info(loc).span));
+                    callsites.push((*once_api, edge.source(), span, receiver));
+                }
+            }
+        }
+        let mut diagnoses = FxHashSet::default();
+        for (outer_api, outer_caller, outer_span, outer_receiver) in &callsites {
+            let (once_api, initializers) = &once_apis[outer_api];
+            let initialized = reachable_instances(initializers.clone(), callgraph);
+            let once_api_path = api_path(
+                callgraph.index_to_instance(*outer_api).unwrap().instance(),
+                self.tcx,
+            );
+            for (inner_api, inner_caller, inner_span, inner_receiver) in &callsites {
+                if !initialized.contains(inner_caller) || once_apis[inner_api].0 != *once_api {
+                    continue;
+                }
+                let alias = match (once_api, outer_receiver, inner_receiver) {
+                    (OnceApi::LazyStatic, _, _) if outer_api == inner_api => {
+                        ApproximateAliasKind::Probably
+                    }
+                    (OnceApi::LazyStatic, _, _) => continue,
+                    (_, Some(outer_receiver), Some(inner_receiver)) => alias_analysis.alias(
+                        AliasId {
+                            instance_id: *outer_caller,
+                            local: *outer_receiver,
+                        },
+                        AliasId {
+                            instance_id: *inner_caller,
+                            local: *inner_receiver,
+                        },
+                    ),
+                    _ => ApproximateAliasKind::Unknown,
+                };
+                match alias.or_paranoid(self.paranoid) {
+                    ApproximateAliasKind::Probably | ApproximateAliasKind::Possibly => {
+                        let mut diagnosis = OnceReentrancyDiagnosis::new(
+                            once_api_path.clone(),
+                            outer_span.clone(),
+                            inner_span.clone(),
+                        );
+                        diagnosis.alias_confidence =
+                            alias_confidence(alias == ApproximateAliasKind::Unknown);
+                        diagnoses.insert(diagnosis);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        diagnoses
+            .into_iter()
+            .map(|diagnosis| {
+                Report::OnceReentrancy(ReportContent::new(
+                    "OnceReentrancy".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "The initializer reaches the same initialization again, which blocks forever on the initialization in progress".to_owned(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Detect the locks held by the callers of the once APIs and acquired again by the initializers,
+    /// e.g., `let _g = m.lock(); CONFIG.get_or_init(|| { m.lock(); .. })`.
+    /// The caller blocks until the initialization completes, whether it runs the initializer or waits for another thread.
+    /// Like `detect_rayon_deadlock`, the instances reachable from the initializers in CallGraph run the initialization.
+    fn detect_once_init_deadlock<'a>(
+        &self,
+        lockguards_before_once_apis: &FxHashMap<InstanceId, LockGuardsBeforeCallSites>,
+        once_apis: &FxHashMap<InstanceId, (OnceApi, Vec<InstanceId>)>,
+        lockguards: &LockGuardMap<'tcx>,
+        callgraph: &'a CallGraph<'tcx>,
+        alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    ) -> Vec<Report> {
+        let mut instance_lockguards: FxHashMap<InstanceId, Vec<LockGuardId>> = FxHashMap::default();
+        for lockguard_id in lockguards.keys() {
+            instance_lockguards
+                .entry(lockguard_id.instance_id)
+                .or_default()
+                .push(*lockguard_id);
+        }
+        let mut diagnoses = FxHashSet::default();
+        for (once_api, callsite_lockguards) in lockguards_before_once_apis {
+            let once_api_path = api_path(
+                callgraph.index_to_instance(*once_api).unwrap().instance(),
+                self.tcx,
+            );
+            let initialized = reachable_instances(once_apis[once_api].1.clone(), callgraph);
+            let inner_lockguards = initialized
+                .iter()
+                .filter_map(|id| instance_lockguards.get(id))
+                .flatten()
+                .collect::<Vec<_>>();
+            for ((caller_id, loc), outer_lockguards) in callsite_lockguards {
+                let caller_body = self.tcx.instance_mir(
+                    callgraph
+                        .index_to_instance(*caller_id)
+                        .unwrap()
+                        .instance()
+                        .def,
+                );
+                let init_span = format!(
+                    "{:?}",
+                    once_callsite_span(caller_body.source_info(*loc).span)
+                );
+                for a in outer_lockguards.raw_lockguard_ids() {
+                    for b in inner_lockguards.iter() {
+                        let possibility =
+                            deadlock_possibility(a, b, lockguards, alias_analysis, false).0;
+                        if possibility.or_paranoid(self.paranoid) > DeadlockPossibility::Unlikely {
+                            let mut diagnosis = OnceInitDeadlockDiagnosis::new(
+                                format!("{:?}", lockguards[a].lockguard_ty),
+                                format!("{:?}", lockguards[a].span),
+                                once_api_path.clone(),
+                                init_span.clone(),
+                                format!("{:?}", lockguards[*b].span),
+                            );
+                            diagnosis.alias_confidence =
+                                alias_confidence(possibility == DeadlockPossibility::Unknown);
+                            diagnoses.insert(diagnosis);
+                        }
+                    }
+                }
+            }
+        }
+        diagnoses
+            .into_iter()
+            .map(|diagnosis| {
+                Report::OnceInitDeadlock(ReportContent::new(
+                    "OnceInitDeadlock".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "The lock is held while waiting for the initialization, whose initializer acquires the lock again".to_owned(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Detect the locks held while blocking on a rendezvous with the peer threads,
+    /// e.g., `barrier.wait()` or `tx.send(..)` on a zero-capacity channel,
+    /// and acquired by a peer thread reaching the matching API, e.g., `barrier.wait()` or `rx.recv()`,
+    /// which may block on the lock and never release the caller.
+    /// The peer threads are the roots in `ThreadRoots` other than those of the caller (any root if unknown).
+    fn detect_rendezvous_deadlock<'a>(
+        &self,
+        lockguards_before_blocking_apis: &FxHashMap<InstanceId, LockGuardsBeforeCallSites>,
+        rendezvous_apis: &FxHashMap<InstanceId, RendezvousApi>,
+        lockguards: &LockGuardMap<'tcx>,
+        callgraph: &'a CallGraph<'tcx>,
+        alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    ) -> Vec<Report> {
+        let thread_roots = ThreadRoots::analyze(callgraph, self.tcx);
+        let instances_by_root = thread_roots.instances_by_root();
+        let mut instance_lockguards: FxHashMap<InstanceId, Vec<LockGuardId>> = FxHashMap::default();
+        for lockguard_id in lockguards.keys() {
+            instance_lockguards
+                .entry(lockguard_id.instance_id)
+                .or_default()
+                .push(*lockguard_id);
+        }
+        let mut diagnoses = FxHashSet::default();
+        for (rendezvous_api_id, callsite_lockguards) in lockguards_before_blocking_apis {
+            let rendezvous_api = match rendezvous_apis.get(rendezvous_api_id) {
+                Some(rendezvous_api) => *rendezvous_api,
+                None => continue,
+            };
+            let instance = callgraph
+                .index_to_instance(*rendezvous_api_id)
+                .unwrap()
+                .instance();
+            if rendezvous_api == RendezvousApi::SyncSend
+                && !self.may_be_zero_capacity(instance, callgraph)
+            {
+                continue;
+            }
+            let rendezvous_api_path = api_path(instance, self.tcx);
+            // The threads reaching the matching API, which release the caller.
+            let peers = instances_by_root
+                .iter()
+                .filter(|(_, instances)| {
+                    instances
+                        .iter()
+                        .any(|id| rendezvous_apis.get(id) == Some(&rendezvous_api.peer()))
+                })
+                .collect::<Vec<_>>();
+            for ((caller_id, loc), live) in callsite_lockguards {
+                let caller_body = self.tcx.instance_mir(
+                    callgraph
+                        .index_to_instance(*caller_id)
+                        .unwrap()
+                        .instance()
+                        .def,
+                );
+                let rendezvous_span = format!("{:?}", caller_body.source_info(*loc).span);
+                let caller_roots = thread_roots.roots(*caller_id);
+                for (root, instances) in peers.iter() {
+                    if caller_roots.map_or(false, |roots| roots.contains(*root)) {
+                        continue;
+                    }
+                    let peer_thread = diagnose_thread_root(root, callgraph, self.tcx);
+                    let peer_lockguards = instances
+                        .iter()
+                        .filter_map(|id| instance_lockguards.get(id))
+                        .flatten();
+                    for b in peer_lockguards {
+                        for a in live.raw_lockguard_ids() {
+                            if lockguards[a].lockguard_ty.is_async() {
+                                continue;
+                            }
+                            let possibility =
+                                deadlock_possibility(a, b, lockguards, alias_analysis, false).0;
+                            if possibility.or_paranoid(self.paranoid)
+                                > DeadlockPossibility::Unlikely
+                            {
+                                let mut diagnosis = RendezvousDeadlockDiagnosis::new(
+                                    format!("{:?}", lockguards[a].lockguard_ty),
+                                    format!("{:?}", lockguards[a].span),
+                                    rendezvous_api_path.clone(),
+                                    rendezvous_span.clone(),
+                                    format!("{:?}", lockguards[b].span),
+                                    peer_thread.clone(),
+                                );
+                                diagnosis.alias_confidence =
+                                    alias_confidence(possibility == DeadlockPossibility::Unknown);
+                                diagnoses.insert(diagnosis);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        diagnoses
+            .into_iter()
+            .map(|diagnosis| {
+                Report::RendezvousDeadlock(ReportContent::new(
+                    "RendezvousDeadlock".to_owned(),
+                    "Possibly".to_owned(),
+                    diagnosis,
+                    "The lock is held while waiting for the peer thread, which acquires the lock before releasing the waiter".to_owned(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Whether `SyncSender::send` may send on a zero-capacity channel,
+    /// i.e., unless all the `sync_channel` calls with the same message type have nonzero constant capacities.
+    /// The channels created out of CallGraph are assumed to be zero-capacity.
+    fn may_be_zero_capacity(&self, send: &Instance<'tcx>, callgraph: &CallGraph<'tcx>) -> bool {
+        let message_ty = send.args.type_at(0);
+        let mut capacities = callgraph
+            .graph
+            .node_references()
+            .filter(|(_, node)| {
+                is_sync_channel(node.instance(), self.tcx)
+                    && node.instance().args.type_at(0) == message_ty
+            })
+            .flat_map(|(sync_channel, _)| {
+                callgraph
+                    .graph
+                    .edges_directed(sync_channel, Direction::Incoming)
+                    .flat_map(|edge| {
+                        let caller = callgraph.index_to_instance(edge.source()).unwrap();
+                        let body = self.tcx.instance_mir(caller.instance().def);
+                        edge.weight()
+                            .iter()
+                            .filter_map(CallSiteLocation::location)
+                            .map(|loc| match &body[loc.block].terminator().kind {
+                                TerminatorKind::Call { args, .. } => match args.get(0) {
+                                    Some(Operand::Constant(constant)) => constant
+                                        .const_
+                                        .try_eval_target_usize(self.tcx, self.param_env),
+                                    _ => None,
+                                },
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+            })
+            .peekable();
+        capacities.peek().is_none() || capacities.any(|capacity| capacity.unwrap_or(0) == 0)
+    }
+
+    /// Detect condvar misuse.
+    /// First collect Condvar APIs info: callsites to (Condvar, MutexGuard)
+    /// - std::sync::Condvar::wait(&Condvar, MutexGuard) -> MutexGuard
+    /// - std::sync::Condvar::notify(&Condvar)
+    /// - parking_lot::Condvar::wait(&Condvar, &mut MutexGuard)
+    /// - parking_lot::Condvar::notify(&Condvar)
+    /// Then match `wait` and `notify` if their Condvars alias with each other.
+    /// Finally check LiveLockGuards before `wait` and `notify`:
+    /// if they have aliasing LockGuards that are not aliased with MutexGuard in `wait` then possibly deadlock
+    /// else if LiveLockGuards before `notify` does not contain a LockGuard aliasing with
+    /// the MutexLock in `wait`, then possibly missing lock before notify.
+    /// Note that the `missing lock` warning is imprecise because the rule rigidly follows the
+    /// Condvar example code. In fact, it is correct as long as there is a LockGuard aliasing with
+    /// the MutexGuard in `wait` that dominates `notify`.
+    /// TODO(boqin): check if an aliased MutexGuard dominates `notify` instead.
+    fn detect_condvar_misuse<'a>(
+        &self,
+        lockguards_before_condvar_apis: &FxHashMap<InstanceId, LockGuardsBeforeCallSites>,
+        condvar_apis: &FxHashMap<InstanceId, CondvarApi>,
+        lockguards: &LockGuardMap<'tcx>,
+        callgraph: &'a CallGraph<'tcx>,
+        alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    ) -> Vec<Report> {
+        let mut reports = Vec::new();
+        // Collect Condvar API info
+        // callee_instance -> (Caller, Location, Callee) -> (&Condvar, MutexGuard)
+        let mut std_notify = FxHashMap::default();
+        // callee_instance -> (Caller, Location, Callee) -> &Condvar
+        let mut std_wait = FxHashMap::default();
+        // callee_instance -> (Caller, Location, Callee) -> (&Condvar, &mut MutexGuard)
+        let mut parking_lot_notify = FxHashMap::default();
+        // callee_instance -> (Caller, Location, Callee) -> &Condvar
+        let mut parking_lot_wait = FxHashMap::default();
+        for (callee_id, callsite_lockguards) in lockguards_before_condvar_apis {
+            let condvar_api = condvar_apis.get(callee_id).unwrap();
+            for (caller_id, loc) in callsite_lockguards.keys() {
+                let body = self.tcx.instance_mir(
+                    callgraph
+                        .index_to_instance(*caller_id)
+                        .unwrap()
+                        .instance()
+                        .def,
+                );
+                let term = body[loc.block].terminator();
+                let args = match &term.kind {
+                    TerminatorKind::Call { func: _, args, .. } => args.clone(),
+                    _ => continue,
+                };
+                match condvar_api {
+                    CondvarApi::Std(StdCondvarApi::Wait(_)) => {
+                        // `&Condvar` is moved or copied.
+                        if let (
+                            Operand::Move(condvar_ref) | Operand::Copy(condvar_ref),
+                            Operand::Move(mutex_guard),
+                        ) = (&args[0], &args[1])
+                        {
+                            // callsite -> (&Condvar, MutexGuard)
+                            std_wait.insert(
+                                (*caller_id, *loc, *callee_id),
+                                (
+                                    AliasId {
+                                        instance_id: *caller_id,
+                                        local: condvar_ref.local,
+                                    },
+                                    AliasId {
+                                        instance_id: *caller_id,
+                                        local: mutex_guard.local,
+                                    },
+                                ),
+                            );
+                        }
+                    }
+                    CondvarApi::ParkingLot(ParkingLotCondvarApi::Wait(_)) => {
+                        // `&mut MutexGuard` is reborrowed or copied.
+                        if let (
+                            Operand::Move(condvar_ref) | Operand::Copy(condvar_ref),
+                            Operand::Move(mutex_guard_ref) | Operand::Copy(mutex_guard_ref),
+                        ) = (&args[0], &args[1])
+                        {
+                            // callsite -> (&Condvar, &mut MutexGuard)
+                            parking_lot_wait.insert(
+                                (*caller_id, *loc, *callee_id),
+                                (
+                                    AliasId {
+                                        instance_id: *caller_id,
+                                        local: condvar_ref.local,
+                                    },
+                                    AliasId {
+                                        instance_id: *caller_id,
+                                        local: mutex_guard_ref.local,
+                                    },
+                                ),
                             );
                         }
                     }
                     CondvarApi::Std(StdCondvarApi::Notify(_)) => {
-                        if let Operand::Move(condvar_ref) = args[0] {
+                        if let Operand::Move(condvar_ref) | Operand::Copy(condvar_ref) = args[0] {
                             // callsite -> &Condvar
                             std_notify.insert(
                                 (*caller_id, *loc, *callee_id),
@@ -318,7 +1612,7 @@ impl<'tcx> DeadlockDetector<'tcx> {
                         }
                     }
                     CondvarApi::ParkingLot(ParkingLotCondvarApi::Notify(_)) => {
-                        if let Operand::Move(condvar_ref) = args[0] {
+                        if let Operand::Move(condvar_ref) | Operand::Copy(condvar_ref) = args[0] {
                             // callsite -> &Condvar
                             parking_lot_notify.insert(
                                 (*caller_id, *loc, *callee_id),
@@ -336,7 +1630,8 @@ impl<'tcx> DeadlockDetector<'tcx> {
         for ((caller_id1, loc1, callee_id1), (condvar_ref1, mutex_guard1)) in std_wait.iter() {
             for ((caller_id2, loc2, callee_id2), condvar_ref2) in std_notify.iter() {
                 let res = alias_analysis.alias(*condvar_ref1, *condvar_ref2);
-                match res {
+                let unknown_condvar = res == ApproximateAliasKind::Unknown;
+                match res.or_paranoid(self.paranoid) {
                     ApproximateAliasKind::Possibly | ApproximateAliasKind::Probably => {
                         // live1: LiveLockGuards before `wait`
                         // live2: LiveLockGuards before `notify`
@@ -354,28 +1649,37 @@ impl<'tcx> DeadlockDetector<'tcx> {
                                 let cartesian_product =
                                     live2.flat_map(|g2| live1.clone().map(move |g1| (*g1, *g2)));
                                 let aliased_pairs = cartesian_product
-                                    .filter(|(g1, g2)| {
-                                        alias_analysis.alias((*g1).into(), (*g2).into())
+                                    .filter_map(|(g1, g2)| {
+                                        let alias = alias_analysis.alias(g1.into(), g2.into());
+                                        let possibility = deadlock_possibility(
+                                            &g1,
+                                            &g2,
+                                            lockguards,
+                                            alias_analysis,
+                                            false,
+                                        )
+                                        .0;
+                                        let unknown_alias = alias == ApproximateAliasKind::Unknown
+                                            || possibility == DeadlockPossibility::Unknown;
+                                        (alias.or_paranoid(self.paranoid)
                                             > ApproximateAliasKind::Unlikely
-                                            && deadlock_possibility(
-                                                g1,
-                                                g2,
-                                                lockguards,
-                                                alias_analysis,
-                                            )
-                                            .0 > DeadlockPossibility::Unlikely
+                                            && possibility.or_paranoid(self.paranoid)
+                                                > DeadlockPossibility::Unlikely)
+                                            .then_some((g1, g2, unknown_alias))
                                     })
                                     .collect::<Vec<_>>();
                                 // exists (g1, g2) in aliased_pairs: alias(g2, mutex_guard1)
                                 // LockGuard pairs that do not alias with MutexGuard in `wait`
                                 let mut no_mutex_guards = Vec::new();
-                                for (g1, g2) in aliased_pairs.iter() {
+                                let mut unknown_alias = unknown_condvar;
+                                for (g1, g2, unknown_pair) in aliased_pairs.iter() {
                                     if AliasId::from(*g1) != *mutex_guard1 {
                                         no_mutex_guards.push((g1, g2));
+                                        unknown_alias |= *unknown_pair;
                                     }
                                 }
                                 if !no_mutex_guards.is_empty() {
-                                    let diagnosis = diagnose_condvar_deadlock(
+                                    let mut diagnosis = diagnose_condvar_deadlock(
                                         (*caller_id1, *loc1),
                                         (*caller_id2, *loc2),
                                         true,
@@ -384,6 +1688,7 @@ impl<'tcx> DeadlockDetector<'tcx> {
                                         callgraph,
                                         self.tcx,
                                     );
+                                    diagnosis.alias_confidence = alias_confidence(unknown_alias);
                                     let content = ReportContent::new(
                                         "Deadlock before Condvar::wait and notify".to_owned(),
                                         "Possibly".to_owned(),
@@ -411,7 +1716,8 @@ impl<'tcx> DeadlockDetector<'tcx> {
         {
             for ((caller_id2, loc2, callee_id2), condvar_ref2) in parking_lot_notify.iter() {
                 let res = alias_analysis.alias(*condvar_ref1, *condvar_ref2);
-                match res {
+                let unknown_condvar = res == ApproximateAliasKind::Unknown;
+                match res.or_paranoid(self.paranoid) {
                     ApproximateAliasKind::Possibly | ApproximateAliasKind::Probably => {
                         // live1: LiveLockGuards before `wait`
                         // live2: LiveLockGuards before `notify`
@@ -429,32 +1735,41 @@ impl<'tcx> DeadlockDetector<'tcx> {
                                 let cartesian_product =
                                     live2.flat_map(|g2| live1.clone().map(move |g1| (*g1, *g2)));
                                 let aliased_pairs = cartesian_product
-                                    .filter(|(g1, g2)| {
-                                        alias_analysis.alias((*g1).into(), (*g2).into())
+                                    .filter_map(|(g1, g2)| {
+                                        let alias = alias_analysis.alias(g1.into(), g2.into());
+                                        let possibility = deadlock_possibility(
+                                            &g1,
+                                            &g2,
+                                            lockguards,
+                                            alias_analysis,
+                                            false,
+                                        )
+                                        .0;
+                                        let unknown_alias = alias == ApproximateAliasKind::Unknown
+                                            || possibility == DeadlockPossibility::Unknown;
+                                        (alias.or_paranoid(self.paranoid)
                                             > ApproximateAliasKind::Unlikely
-                                            && deadlock_possibility(
-                                                g1,
-                                                g2,
-                                                lockguards,
-                                                alias_analysis,
-                                            )
-                                            .0 > DeadlockPossibility::Unlikely
+                                            && possibility.or_paranoid(self.paranoid)
+                                                > DeadlockPossibility::Unlikely)
+                                            .then_some((g1, g2, unknown_alias))
                                     })
                                     .collect::<Vec<_>>();
                                 // exists (g1, g2) in aliased_pairs: alias(g2, mutex_guard1)
                                 // LockGuard pairs that do not alias with MutexGuard in `wait`
                                 let mut no_mutex_guards = Vec::new();
-                                for (g1, g2) in aliased_pairs.iter() {
+                                let mut unknown_alias = unknown_condvar;
+                                for (g1, g2, unknown_pair) in aliased_pairs.iter() {
                                     if !matches!(
                                         alias_analysis.points_to(*mutex_guard1, AliasId::from(*g1)),
                                         ApproximateAliasKind::Possibly
                                             | ApproximateAliasKind::Probably
                                     ) {
                                         no_mutex_guards.push((g1, g2));
+                                        unknown_alias |= *unknown_pair;
                                     }
                                 }
                                 if !no_mutex_guards.is_empty() {
-                                    let diagnosis = diagnose_condvar_deadlock(
+                                    let mut diagnosis = diagnose_condvar_deadlock(
                                         (*caller_id1, *loc1),
                                         (*caller_id2, *loc2),
                                         false,
@@ -463,6 +1778,7 @@ impl<'tcx> DeadlockDetector<'tcx> {
                                         callgraph,
                                         self.tcx,
                                     );
+                                    diagnosis.alias_confidence = alias_confidence(unknown_alias);
                                     let content = ReportContent::new(
                                         "Deadlock before Condvar::wait and notify".to_owned(),
                                         "Possibly".to_owned(),
@@ -484,42 +1800,173 @@ impl<'tcx> DeadlockDetector<'tcx> {
                 }
             }
         }
+        reports.extend(self.detect_condvar_multiple_mutexes(
+            &std_wait,
+            true,
+            callgraph,
+            alias_analysis,
+        ));
+        reports.extend(self.detect_condvar_multiple_mutexes(
+            &parking_lot_wait,
+            false,
+            callgraph,
+            alias_analysis,
+        ));
         reports
     }
 
-    /// Collect gen/kill info for related locations.
-    fn gen_kill_locations(
-        lockguard_map: &LockGuardMap<'tcx>,
-    ) -> (
-        FxHashMap<Location, LiveLockGuards>,
-        FxHashMap<Location, LiveLockGuards>,
-    ) {
-        let mut gen_map: FxHashMap<Location, LiveLockGuards> = Default::default();
-        let mut kill_map: FxHashMap<Location, LiveLockGuards> = Default::default();
-        for (id, info) in lockguard_map {
-            for loc in &info.gen_locs {
-                gen_map.entry(*loc).or_default().insert(*id);
-            }
-            for loc in &info.kill_locs {
-                kill_map.entry(*loc).or_default().insert(*id);
-            }
-        }
-        (gen_map, kill_map)
+    /// Detect the condvars waited with the guards of different mutexes,
+    /// which std forbids by panicking at runtime and parking_lot by documentation.
+    /// The wait callsites are bucketed by the condvars (of the same declaration or probably aliasing),
+    /// then the guards in each bucket by the mutexes (of the same declaration or possibly aliasing).
+    fn detect_condvar_multiple_mutexes<'a>(
+        &self,
+        waits: &FxHashMap<(InstanceId, Location, InstanceId), (AliasId, AliasId)>,
+        is_std_condvar: bool,
+        callgraph: &'a CallGraph<'tcx>,
+        alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    ) -> Vec<Report> {
+        // (wait span, &Condvar, MutexGuard, condvar declaration, mutex declaration)
+        let mut waits = waits
+            .iter()
+            .filter_map(|((caller_id, loc, _), (condvar_ref, mutex_guard))| {
+                let body = self
+                    .tcx
+                    .instance_mir(callgraph.index_to_instance(*caller_id)?.instance().def);
+                // parking_lot waits with `&mut MutexGuard`.
+                let mutex_guard = if is_std_condvar {
+                    *mutex_guard
+                } else {
+                    AliasId {
+                        instance_id: *caller_id,
+                        local: inventory::referent(body, mutex_guard.local)?,
+                    }
+                };
+                let declaration = |id: AliasId| {
+                    inventory::lock_declaration(
+                        LockGuardId::new(id.instance_id, id.local),
+                        callgraph,
+                        self.tcx,
+                    )
+                };
+                Some((
+                    format!("{:?}", body.source_info(*loc).span),
+                    *condvar_ref,
+                    mutex_guard,
+                    declaration(*condvar_ref),
+                    declaration(mutex_guard),
+                ))
+            })
+            .collect::<Vec<_>>();
+        waits.sort_by(|a, b| a.0.cmp(&b.0));
+        // The condvars (mutexes) across fns only possibly alias,
+        // so they are first matched by their declarations (fields or statics), then by alias.
+        let mut same = |a: (AliasId, &Option<(String, String)>),
+                        b: (AliasId, &Option<(String, String)>),
+                        kind: ApproximateAliasKind| match (a.1, b.1) {
+            (Some(a), Some(b)) => a == b,
+            _ => alias_analysis.alias(a.0, b.0) >= kind,
+        };
+        let mut reports = Vec::new();
+        let mut bucketed = vec![false; waits.len()];
+        for i in 0..waits.len() {
+            if bucketed[i] {
+                continue;
+            }
+            let mut bucket = vec![i];
+            for j in i + 1..waits.len() {
+                if !bucketed[j]
+                    && same(
+                        (waits[i].1, &waits[i].3),
+                        (waits[j].1, &waits[j].3),
+                        ApproximateAliasKind::Probably,
+                    )
+                {
+                    bucket.push(j);
+                }
+            }
+            // (the first wait, waits) of each mutex
+            let mut mutexes: Vec<(usize, Vec<usize>)> = Vec::new();
+            for j in bucket {
+                bucketed[j] = true;
+                match mutexes.iter_mut().find(|(first, _)| {
+                    same(
+                        (waits[*first].2, &waits[*first].4),
+                        (waits[j].2, &waits[j].4),
+                        ApproximateAliasKind::Possibly,
+                    )
+                }) {
+                    Some((_, same_mutex)) => same_mutex.push(j),
+                    None => mutexes.push((j, vec![j])),
+                }
+            }
+            if mutexes.len() < 2 {
+                continue;
+            }
+            let mutexes = mutexes
+                .into_iter()
+                .map(|(first, same_mutex)| {
+                    let wait_spans = same_mutex
+                        .iter()
+                        .map(|j| waits[*j].0.clone())
+                        .collect::<Vec<_>>();
+                    let (mutex, declaration_span) = waits[first]
+                        .4
+                        .clone()
+                        .unwrap_or_else(|| ("unknown".to_owned(), wait_spans[0].clone()));
+                    CondvarMutex::new(mutex, declaration_span, wait_spans)
+                })
+                .collect::<Vec<_>>();
+            let condvar_wait_type = if is_std_condvar {
+                "std::sync::Condvar::wait"
+            } else {
+                "parking_lot::Condvar::wait"
+            };
+            reports.push(Report::CondvarMultipleMutexes(ReportContent::new(
+                "CondvarMultipleMutexes".to_owned(),
+                "Possibly".to_owned(),
+                CondvarMultipleMutexesDiagnosis::new(condvar_wait_type.to_owned(), mutexes),
+                "The condvar is waited with the guards of different mutexes".to_owned(),
+            )));
+        }
+        reports
+    }
+
+    /// Collect gen/kill info for related locations.
+    /// The transfer gens (see `LockGuardInfo::transfer_gen_locs`) are collected apart from the other gens.
+    fn gen_kill_locations(lockguard_map: &LockGuardMap<'tcx>) -> GenKillMaps {
+        let mut maps = GenKillMaps::default();
+        for (id, info) in lockguard_map {
+            for loc in &info.gen_locs {
+                let map = if info.transfer_gen_locs.contains(loc) {
+                    &mut maps.transfer
+                } else {
+                    &mut maps.gen
+                };
+                map.entry(*loc).or_default().insert(*id);
+            }
+            for loc in &info.kill_locs {
+                maps.kill.entry(*loc).or_default().insert(*id);
+            }
+        }
+        maps
     }
 
-    /// state' = state \ kill U gen
+    /// state' = state \ kill U gen U transfer
     /// return lockguard relation(a, b) where a is still live when b becomes live.
+    /// A transfer gen continues the live range of the lockguard moved into it,
+    /// which is related to the live lockguards when acquired, so it forms no relations.
     fn apply_gen_kill(
         state: &mut LiveLockGuards,
-        gen: Option<&LiveLockGuards>,
-        kill: Option<&LiveLockGuards>,
+        maps: &GenKillMaps,
+        loc: Location,
     ) -> FxHashSet<(LockGuardId, LockGuardId)> {
         // First kill, then gen
-        if let Some(kill) = kill {
+        if let Some(kill) = maps.kill.get(&loc) {
             state.difference_in_place(kill);
         }
         let mut relations = FxHashSet::default();
-        if let Some(gen) = gen {
+        if let Some(gen) = maps.gen.get(&loc) {
             for s in state.raw_lockguard_ids() {
                 for g in gen.raw_lockguard_ids() {
                     relations.insert((*s, *g));
@@ -527,17 +1974,22 @@ impl<'tcx> DeadlockDetector<'tcx> {
             }
             state.union_in_place(gen.clone());
         }
+        if let Some(transfer) = maps.transfer.get(&loc) {
+            state.union_in_place(transfer.clone());
+        }
         relations
     }
 
     /// Apply Gen/Kill to get live lockguards for each location in the same fn.
+    /// Stop if the budget is exceeded.
     fn intraproc_gen_kill(
         &mut self,
         body: &'tcx Body<'tcx>,
         context: &LiveLockGuards,
         lockguard_info: &LockGuardMap<'tcx>,
-    ) -> FxHashMap<Location, LiveLockGuards> {
-        let (gen_map, kill_map) = Self::gen_kill_locations(lockguard_info);
+    ) -> Result<FxHashMap<Location, LiveLockGuards>, BudgetExceeded> {
+        let mut timer = self.budget.start();
+        let maps = Self::gen_kill_locations(lockguard_info);
         let mut worklist: VecDeque<Location> = Default::default();
         for (bb, bb_data) in body.basic_blocks.iter_enumerated() {
             for stmt_idx in 0..bb_data.statements.len() + 1 {
@@ -554,8 +2006,9 @@ impl<'tcx> DeadlockDetector<'tcx> {
             .collect();
         *states.get_mut(&Location::START).unwrap() = context.clone();
         while let Some(loc) = worklist.pop_front() {
+            timer.step()?;
             let mut after = states[&loc].clone();
-            let relation = Self::apply_gen_kill(&mut after, gen_map.get(&loc), kill_map.get(&loc));
+            let relation = Self::apply_gen_kill(&mut after, &maps, loc);
             self.lockguard_relations.extend(relation.into_iter());
             let term_loc = body.terminator_loc(loc.block);
             if loc != term_loc {
@@ -582,7 +2035,73 @@ impl<'tcx> DeadlockDetector<'tcx> {
                 }
             }
         }
-        states
+        Ok(states)
+    }
+
+    /// Print the MIR of the fns matching `--focus` to stderr, where each location is followed by
+    /// the lockguards live before it, named by their locals (prefixed by the fns holding them if in the callers) and spans.
+    fn dump_focused_mir(
+        &mut self,
+        callgraph: &CallGraph<'tcx>,
+        lockguards: &FxHashMap<InstanceId, LockGuardMap<'tcx>>,
+        contexts: &FxHashMap<InstanceId, LiveLockGuards>,
+    ) {
+        let no_lockguards = LockGuardMap::default();
+        let mut roots = callgraph.focus_roots().collect::<Vec<_>>();
+        roots.sort();
+        for id in roots {
+            let Some(CallGraphNode::WithBody(instance)) = callgraph.index_to_instance(id) else {
+                continue;
+            };
+            let body = self.tcx.instance_mir(instance.def);
+            let lockguard_info = lockguards.get(&id).unwrap_or(&no_lockguards);
+            let Ok(states) = self.intraproc_gen_kill(body, &contexts[&id], lockguard_info) else {
+                continue;
+            };
+            let live = |loc: Location| {
+                let mut live = states[&loc]
+                    .raw_lockguard_ids()
+                    .iter()
+                    .map(|lockguard_id| {
+                        let span = lockguards[&lockguard_id.instance_id][lockguard_id].span;
+                        let holder = if lockguard_id.instance_id == id {
+                            String::new()
+                        } else {
+                            let holder = callgraph.index_to_instance(lockguard_id.instance_id);
+                            format!(
+                                "{}::",
+                                self.tcx.def_path_str(holder.unwrap().instance().def_id())
+                            )
+                        };
+                        format!("{}{:?} ({:?})", holder, lockguard_id.local, span)
+                    })
+                    .collect::<Vec<_>>();
+                live.sort();
+                live.join(", ")
+            };
+            eprintln!(
+                "// {}",
+                self.tcx
+                    .def_path_str_with_args(instance.def_id(), instance.args)
+            );
+            for (bb, bb_data) in body.basic_blocks.iter_enumerated() {
+                eprintln!("{:?}: {{", bb);
+                for (statement_index, statement) in bb_data.statements.iter().enumerate() {
+                    let loc = Location {
+                        block: bb,
+                        statement_index,
+                    };
+                    eprintln!("    {:?}; // live: [{}]", statement, live(loc));
+                }
+                let loc = body.terminator_loc(bb);
+                eprintln!(
+                    "    {:?}; // live: [{}]",
+                    bb_data.terminator().kind,
+                    live(loc)
+                );
+                eprintln!("}}");
+            }
+        }
     }
 
     /// First detect doublelock on each relation(a, b),
@@ -593,27 +2112,101 @@ impl<'tcx> DeadlockDetector<'tcx> {
         lockguards: &LockGuardMap<'tcx>,
         callgraph: &'a CallGraph<'tcx>,
         alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+        progress: &Progress,
     ) -> Vec<Report> {
         let mut reports = Vec::new();
         let mut conflictlock_graph = ConflictLockGraph::new();
         let mut relation_to_nodes = FxHashMap::default();
+        // The doublelocks keyed by the fns (rather than instances), spans, and possibility,
+        // so that the monomorphized instances of the same generic fns with the same verdict are reported once.
+        let mut doublelocks = FxHashMap::default();
+        let mut evidences = FxHashMap::default();
         // Detect doublelock:
         // forall relation(a, b): deadlock(a, b) => doublelock(a, b)
-        for (a, b) in &self.lockguard_relations {
-            let (possibility, reason) = deadlock_possibility(a, b, lockguards, alias_analysis);
+        progress.phase("doublelock");
+        let total = self.lockguard_relations.len();
+        for (processed, (a, b)) in self.lockguard_relations.iter().enumerate() {
+            progress.update(processed + 1, total, "relations");
+            let (possibility, reason, evidence) =
+                deadlock_possibility_with_evidence(a, b, lockguards, alias_analysis, true);
+            if let Some(evidence) = evidence {
+                evidences.insert((*a, *b), evidence);
+            }
+            let unknown_alias = possibility == DeadlockPossibility::Unknown;
+            let mut possibility = possibility.or_paranoid(self.paranoid);
+            // The elements of a collection with different (or unknown) indices, e.g., `shards[0]` and `shards[1]`,
+            // are left to conflictlock unless `--paranoid`.
+            if possibility == DeadlockPossibility::Possibly
+                && !self.paranoid
+                && evidence.map_or(false, |evidence| {
+                    matches!(
+                        evidence.provenance,
+                        AliasProvenance::ElementIndex {
+                            same_index: false,
+                            ..
+                        }
+                    )
+                })
+            {
+                possibility = DeadlockPossibility::Unlikely;
+            }
+            // The callees of virtual calls are only candidates,
+            // so lower the possibility if b is only reachable through virtual calls.
+            if possibility == DeadlockPossibility::Probably
+                && !callgraph.is_reachable_without_virtual(a.instance_id, b.instance_id)
+            {
+                possibility = DeadlockPossibility::Possibly;
+            }
+            // Likewise if b is only reachable through the closures assumed to be called by generic fns without MIR.
+            let heuristic = heuristic(a, b, callgraph);
+            if possibility == DeadlockPossibility::Probably && heuristic.is_some() {
+                possibility = DeadlockPossibility::Possibly;
+            }
+            // The callee of a virtual call (e.g., a plugin) may lock the caller's struct again
+            // through a handle untracked by the alias analysis.
+            let mut owner_ty = None;
+            if matches!(
+                possibility,
+                DeadlockPossibility::Unlikely | DeadlockPossibility::Unknown
+            ) && reason == NotDeadlockReason::TrueDeadlock
+            {
+                owner_ty = same_owner_through_virtual(a, b, lockguards, callgraph);
+                if owner_ty.is_some() {
+                    possibility = DeadlockPossibility::Possibly;
+                }
+            }
             match possibility {
                 DeadlockPossibility::Probably | DeadlockPossibility::Possibly => {
-                    let diagnosis = diagnose_doublelock(a, b, lockguards, callgraph, self.tcx);
-                    let report = Report::DoubleLock(ReportContent::new(
-                        "DoubleLock".to_owned(),
-                        format!("{:?}", possibility),
-                        diagnosis,
-                        "The first lock is not released when acquiring the second lock".to_owned(),
-                    ));
-                    reports.push(report);
+                    let key = (
+                        callgraph
+                            .index_to_instance(a.instance_id)
+                            .unwrap()
+                            .instance()
+                            .def_id(),
+                        callgraph
+                            .index_to_instance(b.instance_id)
+                            .unwrap()
+                            .instance()
+                            .def_id(),
+                        lockguards[a].span,
+                        lockguards[b].span,
+                        possibility,
+                        unknown_alias && owner_ty.is_none(),
+                        owner_ty,
+                        heuristic,
+                    );
+                    doublelocks
+                        .entry(key)
+                        .or_insert_with(Vec::new)
+                        .push((*a, *b));
                 }
-                _ if NotDeadlockReason::RecursiveRead != reason
-                    && NotDeadlockReason::SameSpan != reason =>
+                _ if !matches!(
+                    reason,
+                    NotDeadlockReason::RecursiveRead
+                        | NotDeadlockReason::SameSpan
+                        | NotDeadlockReason::Reentrant
+                        | NotDeadlockReason::TryBorrow
+                ) =>
                 {
                     // if unlikely doublelock, add the pair into graph to check conflictlock
                     // when the lockguards are gen by call rather than move
@@ -626,93 +2219,452 @@ impl<'tcx> DeadlockDetector<'tcx> {
                 _ => {}
             }
         }
+        for ((_, _, _, _, possibility, unknown_alias, owner_ty, heuristic), mut relations) in
+            doublelocks
+        {
+            relations.sort_by_key(|(a, b)| (a.instance_id, a.local, b.instance_id, b.local));
+            let (a, b) = relations[0];
+            let mut diagnosis = diagnose_doublelock(
+                &a,
+                &b,
+                lockguards,
+                callgraph,
+                self.config.callchain_limits,
+                self.tcx,
+            );
+            diagnosis.instantiations = relations
+                .iter()
+                .map(|(a, b)| (a.instance_id, b.instance_id))
+                .collect::<FxHashSet<_>>()
+                .len();
+            diagnosis.alias_confidence = alias_confidence(unknown_alias);
+            let mut explanation =
+                "The first lock is not released when acquiring the second lock".to_owned();
+            if heuristic.is_some() {
+                explanation.push_str(
+                    " in a closure assumed to be called by the generic fn without MIR it is passed to",
+                );
+            }
+            let feasible = heuristic.is_none()
+                && callgraph.is_reachable_without_virtual(a.instance_id, b.instance_id);
+            let evidence: Option<&DeadlockEvidence> = evidences.get(&(a, b));
+            let confidence = evidence.map_or(0, |evidence| evidence.confidence(feasible));
+            diagnosis.alias_provenance =
+                evidence.map(|evidence| evidence.provenance.as_str().to_owned());
+            diagnosis.heuristic = heuristic;
+            if let Some(owner_ty) = owner_ty {
+                diagnosis.alias_confidence = Some(ALIAS_CONFIDENCE_SAME_OWNER.to_owned());
+                explanation.push_str(&format!(
+                    ", which is only reachable through virtual calls and is a field of the same struct type `{}` as the first lock",
+                    owner_ty
+                ));
+            }
+            // The conflicting borrows of a `RefCell` panic rather than block.
+            let report = if lockguards[&a].lockguard_ty.is_borrow() {
+                let mut content = ReportContent::new(
+                    "RefCellDoubleBorrow".to_owned(),
+                    format!("{:?}", possibility),
+                    diagnosis,
+                    "The first borrow is still live when borrowing the RefCell again, which panics with `BorrowMutError` (or `BorrowError`)".to_owned(),
+                );
+                content.confidence = Some(confidence);
+                Report::RefCellDoubleBorrow(content)
+            } else {
+                let mut content = ReportContent::new(
+                    "DoubleLock".to_owned(),
+                    format!("{:?}", possibility),
+                    diagnosis,
+                    explanation,
+                );
+                content.confidence = Some(confidence);
+                Report::DoubleLock(content)
+            };
+            reports.push(report);
+        }
         // Detect conflictlock:
         // forall relation(a, b), relation(c, d): deadlock(b, c) and deadlock(d, a) => conflictlock((a, b), (c, d))
         // forall relation(a, b), relation(c, d), relation(e, f): deadlock(b, c) and deadlock(d, e) and deadlock(f, a) => conflictlock((a, b), (c, d), (e, f))
         // ...
         // In general, forall relation(a, b), relation(c, d): deadlock(b, c) => edge(relation(a, b), relation(c, d))
         // if exists a cycle, i.e., edge(r1, r2), edge(r2, r3), ..., edge(rn, r1) then conflictlock((r1, r2, r3, ..., rn))
-        for ((_, a), node1) in relation_to_nodes.iter() {
+        // Relations are annotated with the thread roots of the instance holding the first lock.
+        // Relations executed sequentially in the same single thread cannot interleave, so no edge between them,
+        // unless one of them is retried in a closure of `catch_unwind` after a panic in the other (a panic-retry inversion).
+        if !self.config.is_enabled("conflict_lock") {
+            return reports;
+        }
+        progress.phase("conflictlock");
+        let thread_roots = ThreadRoots::analyze(callgraph, self.tcx);
+        let panic_retry =
+            PanicRetryContexts::new(&self.collect_catch_unwind_closures(callgraph), callgraph);
+        let total = relation_to_nodes.len() * relation_to_nodes.len();
+        let mut processed = 0;
+        let mut edge_evidences = FxHashMap::default();
+        for ((a0, a), node1) in relation_to_nodes.iter() {
             for ((b, _), node2) in relation_to_nodes.iter() {
-                let (possibility, _) = deadlock_possibility(a, b, lockguards, alias_analysis);
+                processed += 1;
+                progress.update(processed, total, "relation pairs");
+                if self.config.thread_filter
+                    && thread_roots.is_same_single_thread(a0.instance_id, b.instance_id)
+                    && !panic_retry.is_inversion(a0.instance_id, b.instance_id)
+                {
+                    continue;
+                }
+                let (possibility, _, evidence) =
+                    deadlock_possibility_with_evidence(a, b, lockguards, alias_analysis, false);
+                // The same relation in two threads, e.g., `shards[0]` then `shards[1]`,
+                // locks the elements of different constant indices in the same order.
+                if node1 == node2
+                    && evidence.map_or(false, |evidence| {
+                        evidence.provenance
+                            == AliasProvenance::ElementIndex {
+                                same_index: false,
+                                constant: true,
+                            }
+                    })
+                {
+                    continue;
+                }
                 match possibility {
                     DeadlockPossibility::Probably | DeadlockPossibility::Possibly => {
                         conflictlock_graph.add_edge(*node1, *node2, possibility);
                     }
-                    _ => {}
+                    // Kept as Unknown to mark the diagnoses.
+                    DeadlockPossibility::Unknown if self.paranoid => {
+                        conflictlock_graph.add_edge(*node1, *node2, possibility);
+                    }
+                    _ => continue,
                 };
+                if let Some(evidence) = evidence {
+                    edge_evidences.insert((*node1, *node2), evidence);
+                }
             }
         }
-        let cycle_paths = conflictlock_graph.cycle_paths();
+        let (cycle_paths, truncated) = conflictlock_graph.cycle_paths(MAX_CONFLICTLOCK_CYCLES);
+        if truncated {
+            warn!(
+                "ConflictLock cycles are truncated to the first {}",
+                MAX_CONFLICTLOCK_CYCLES
+            );
+        }
         for path in cycle_paths {
+            let first_locks = path
+                .iter()
+                .map(|relation_id| conflictlock_graph.node_weight(*relation_id).unwrap().0)
+                .collect::<Vec<_>>();
             let diagnosis = path
-                .into_iter()
-                .map(|relation_id| {
+                .iter()
+                .zip(path.iter().cycle().skip(1))
+                .map(|(&relation_id, &next)| {
                     let (a, b) = conflictlock_graph.node_weight(relation_id).unwrap();
-                    diagnose_one_relation(a, b, lockguards, callgraph, self.tcx)
+                    let mut diagnosis = diagnose_one_relation(
+                        a,
+                        b,
+                        lockguards,
+                        callgraph,
+                        self.config.callchain_limits,
+                        self.tcx,
+                    );
+                    diagnosis.thread_roots =
+                        diagnose_thread_roots(a.instance_id, &thread_roots, callgraph, self.tcx);
+                    // The second lock deadlocks with the first lock of the next relation.
+                    diagnosis.alias_confidence = alias_confidence(
+                        conflictlock_graph.edge_weight(relation_id, next)
+                            == Some(DeadlockPossibility::Unknown),
+                    );
+                    diagnosis.alias_provenance = edge_evidences
+                        .get(&(relation_id, next))
+                        .map(|evidence: &DeadlockEvidence| evidence.provenance.as_str().to_owned());
+                    diagnosis.heuristic = heuristic(a, b, callgraph);
+                    diagnosis.inversion = first_locks
+                        .iter()
+                        .any(|other| panic_retry.is_retry_of(a.instance_id, other.instance_id))
+                        .then(|| INVERSION_PANIC_RETRY.to_owned());
+                    diagnosis
                 })
                 .collect::<Vec<_>>();
-            let report = Report::ConflictLock(ReportContent::new(
+            // A cycle is as confident as its least confident edge.
+            let confidence = path
+                .iter()
+                .zip(path.iter().cycle().skip(1))
+                .map(|(&relation_id, &next)| {
+                    let (a, b) = conflictlock_graph.node_weight(relation_id).unwrap();
+                    let feasible = heuristic(a, b, callgraph).is_none()
+                        && callgraph.is_reachable_without_virtual(a.instance_id, b.instance_id);
+                    edge_evidences
+                        .get(&(relation_id, next))
+                        .map_or(0, |evidence: &DeadlockEvidence| {
+                            evidence.confidence(feasible)
+                        })
+                })
+                .min()
+                .unwrap_or(0);
+            let mut explanation = "Locks mutually wait for each other to form a cycle".to_owned();
+            if diagnosis
+                .iter()
+                .any(|diagnosis| diagnosis.inversion.is_some())
+            {
+                explanation.push_str(
+                    ", where the locks retried in the closure of `catch_unwind` after a panic are acquired in the opposite order of the attempt before the panic",
+                );
+            }
+            let mut content = ReportContent::new(
                 "ConflictLock".to_owned(),
                 "Possibly".to_owned(),
                 diagnosis,
-                "Locks mutually wait for each other to form a cycle".to_owned(),
-            ));
-            reports.push(report);
+                explanation,
+            );
+            content.confidence = Some(confidence);
+            reports.push(Report::ConflictLock(content));
         }
         reports
     }
 }
 
+#[inline]
+fn is_std_crate(crate_name: &str) -> bool {
+    matches!(crate_name, "std" | "core" | "alloc")
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum NotDeadlockReason {
     TrueDeadlock,
     RecursiveRead,
     SameSpan,
+    /// The same reentrant lock acquired again by the thread holding it.
+    Reentrant,
+    /// The `RefCell` borrowed again by `try_borrow*` and matched rather than unwrapped,
+    /// which fails without panicking.
+    TryBorrow,
     // TODO,
 }
 
+/// The evidence of a deadlock(a, b) to score the confidence of the reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DeadlockEvidence {
+    alias: ApproximateAliasKind,
+    provenance: AliasProvenance,
+    /// The possibility by the lockguard types alone, e.g., write-write is Probably, read-write is Possibly.
+    type_possibility: DeadlockPossibility,
+}
+
+impl DeadlockEvidence {
+    /// The confidence in 0..=100, summing the weights of the alias kind, the alias provenance,
+    /// the type pairing, and whether the second lock is reachable from the first one
+    /// without virtual calls or heuristics (`feasible_callchain`).
+    fn confidence(&self, feasible_callchain: bool) -> u8 {
+        let alias = match self.alias {
+            ApproximateAliasKind::Probably => 40,
+            ApproximateAliasKind::Possibly => 25,
+            ApproximateAliasKind::Unlikely => 5,
+            ApproximateAliasKind::Unknown => 0,
+        };
+        let provenance = match self.provenance {
+            AliasProvenance::SameLocal => 25,
+            AliasProvenance::PointsToIntersection
+            | AliasProvenance::SameConstant
+            | AliasProvenance::Callsite
+            | AliasProvenance::ElementIndex {
+                same_index: true, ..
+            } => 20,
+            AliasProvenance::ClosureUpvar => 10,
+            AliasProvenance::ElementIndex {
+                same_index: false, ..
+            } => 0,
+            AliasProvenance::ParamTypeHeuristic { .. } | AliasProvenance::Interproc => 0,
+            AliasProvenance::Unknown => 0,
+        };
+        let type_possibility = match self.type_possibility {
+            DeadlockPossibility::Probably => 20,
+            DeadlockPossibility::Possibly => 10,
+            _ => 0,
+        };
+        let callchain = if feasible_callchain { 15 } else { 0 };
+        alias + provenance + type_possibility + callchain
+    }
+}
+
 /// Check deadlock possibility.
 /// for two lockguards, first check if their types may deadlock;
 /// if so, then check if they may alias.
-fn deadlock_possibility<'tcx>(
+/// `same_thread` if both lockguards are held by the same thread, e.g., in a relation,
+/// otherwise a reentrant lock excludes the other thread, e.g., across the relations of a conflictlock.
+fn deadlock_possibility(
     a: &LockGuardId,
     b: &LockGuardId,
-    lockguards: &LockGuardMap<'tcx>,
+    lockguards: &LockGuardMap<'_>,
     alias_analysis: &mut AliasAnalysis,
+    same_thread: bool,
 ) -> (DeadlockPossibility, NotDeadlockReason) {
+    let (possibility, reason, _) =
+        deadlock_possibility_with_evidence(a, b, lockguards, alias_analysis, same_thread);
+    (possibility, reason)
+}
+
+/// `deadlock_possibility` with the evidence if the alias of the lockguards is checked.
+fn deadlock_possibility_with_evidence(
+    a: &LockGuardId,
+    b: &LockGuardId,
+    lockguards: &LockGuardMap<'_>,
+    alias_analysis: &mut AliasAnalysis,
+    same_thread: bool,
+) -> (
+    DeadlockPossibility,
+    NotDeadlockReason,
+    Option<DeadlockEvidence>,
+) {
     let a_ty = &lockguards[a].lockguard_ty;
     let b_ty = &lockguards[b].lockguard_ty;
-    if let (LockGuardTy::ParkingLotRead(_), LockGuardTy::ParkingLotRead(_)) = (a_ty, b_ty) {
-        if lockguards[b].is_gen_only_by_recursive() {
-            return (
-                DeadlockPossibility::Unlikely,
-                NotDeadlockReason::RecursiveRead,
-            );
-        }
+    // A relation of two different reentrant locks may still be in a conflictlock.
+    if same_thread && a_ty.is_reentrant_with(b_ty) {
+        let reason = match alias_analysis.alias((*a).into(), (*b).into()) {
+            ApproximateAliasKind::Probably | ApproximateAliasKind::Possibly => {
+                NotDeadlockReason::Reentrant
+            }
+            _ => NotDeadlockReason::TrueDeadlock,
+        };
+        return (DeadlockPossibility::Unlikely, reason, None);
+    }
+    if (a_ty.access(), b_ty.access()) == (LockAccess::Read, LockAccess::Read)
+        && lockguards[b].is_gen_only_by_recursive()
+    {
+        return (
+            DeadlockPossibility::Unlikely,
+            NotDeadlockReason::RecursiveRead,
+            None,
+        );
     }
     // Assume that a lock in a loop or recursive functions will not deadlock with itself,
     // in which case the lock spans of the two locks are the same.
     // This may miss some bugs but can reduce many FPs.
     if lockguards[a].span == lockguards[b].span {
-        return (DeadlockPossibility::Unlikely, NotDeadlockReason::SameSpan);
-    }
-    let possibility = match a_ty.deadlock_with(b_ty) {
-        DeadlockPossibility::Probably => match alias_analysis.alias((*a).into(), (*b).into()) {
-            ApproximateAliasKind::Probably => DeadlockPossibility::Probably,
-            ApproximateAliasKind::Possibly => DeadlockPossibility::Possibly,
-            ApproximateAliasKind::Unlikely => DeadlockPossibility::Unlikely,
-            ApproximateAliasKind::Unknown => DeadlockPossibility::Unknown,
-        },
-        DeadlockPossibility::Possibly => match alias_analysis.alias((*a).into(), (*b).into()) {
-            ApproximateAliasKind::Probably => DeadlockPossibility::Possibly,
-            ApproximateAliasKind::Possibly => DeadlockPossibility::Possibly,
-            ApproximateAliasKind::Unlikely => DeadlockPossibility::Unlikely,
-            ApproximateAliasKind::Unknown => DeadlockPossibility::Unknown,
-        },
-        _ => DeadlockPossibility::Unlikely,
+        return (
+            DeadlockPossibility::Unlikely,
+            NotDeadlockReason::SameSpan,
+            None,
+        );
+    }
+    if same_thread && b_ty.is_borrow() && lockguards[b].is_gen_only_by_move() {
+        return (
+            DeadlockPossibility::Unlikely,
+            NotDeadlockReason::TryBorrow,
+            None,
+        );
+    }
+    let ty_possibility = if same_thread {
+        a_ty.deadlock_with(b_ty)
+    } else {
+        a_ty.deadlock_across_threads_with(b_ty)
+    };
+    if !matches!(
+        ty_possibility,
+        DeadlockPossibility::Probably | DeadlockPossibility::Possibly
+    ) {
+        return (
+            DeadlockPossibility::Unlikely,
+            NotDeadlockReason::TrueDeadlock,
+            None,
+        );
+    }
+    let (alias, provenance) = alias_analysis.alias_with_provenance((*a).into(), (*b).into());
+    let possibility = match (ty_possibility, alias) {
+        (DeadlockPossibility::Probably, ApproximateAliasKind::Probably) => {
+            DeadlockPossibility::Probably
+        }
+        (_, ApproximateAliasKind::Probably | ApproximateAliasKind::Possibly) => {
+            DeadlockPossibility::Possibly
+        }
+        (_, ApproximateAliasKind::Unlikely) => DeadlockPossibility::Unlikely,
+        (_, ApproximateAliasKind::Unknown) => DeadlockPossibility::Unknown,
+    };
+    let evidence = DeadlockEvidence {
+        alias,
+        provenance,
+        type_possibility: ty_possibility,
     };
-    (possibility, NotDeadlockReason::TrueDeadlock)
+    (possibility, NotDeadlockReason::TrueDeadlock, Some(evidence))
+}
+
+/// The owner type of the locks if the second lock is only reachable through virtual calls,
+/// and the locks (that may deadlock) are fields of the same struct type,
+/// e.g., a plugin called with the read guard of the service navigates back to the service and writes the same field.
+fn same_owner_through_virtual<'tcx>(
+    a: &LockGuardId,
+    b: &LockGuardId,
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &CallGraph<'tcx>,
+) -> Option<Ty<'tcx>> {
+    let (a_info, b_info) = (&lockguards[a], &lockguards[b]);
+    let owner_ty = a_info.owner_ty?;
+    (b_info.owner_ty == Some(owner_ty)
+        && a_info.lockguard_ty.deadlock_with(&b_info.lockguard_ty) != DeadlockPossibility::Unlikely
+        && !callgraph.is_reachable_without_virtual(a.instance_id, b.instance_id))
+    .then_some(owner_ty)
+}
+
+/// The closures of `catch_unwind` as additional execution contexts in the thread of their callers.
+/// A relation formed in such a closure (e.g., a retry after recovering a poisoned lock)
+/// may run after a panic unwinds to the catch from a relation formed by the callers before,
+/// so the two relations are paired even if they are in the same single thread.
+struct PanicRetryContexts {
+    /// closure -> the callers of `catch_unwind` with the closure
+    callers: FxHashMap<InstanceId, Vec<InstanceId>>,
+    /// The instances reachable from each caller, where the relations before the panic are formed.
+    reachable: FxHashMap<InstanceId, FxHashSet<InstanceId>>,
+}
+
+impl PanicRetryContexts {
+    fn new(
+        catch_unwind_closures: &FxHashMap<InstanceId, Vec<InstanceId>>,
+        callgraph: &CallGraph<'_>,
+    ) -> Self {
+        let mut callers: FxHashMap<InstanceId, Vec<InstanceId>> = FxHashMap::default();
+        let mut reachable = FxHashMap::default();
+        for (catch_unwind, closures) in catch_unwind_closures {
+            for caller in callgraph.callers(*catch_unwind) {
+                reachable.entry(caller).or_insert_with(|| {
+                    let mut dfs = petgraph::visit::Dfs::new(&callgraph.graph, caller);
+                    let mut reachable = FxHashSet::default();
+                    while let Some(instance_id) = dfs.next(&callgraph.graph) {
+                        reachable.insert(instance_id);
+                    }
+                    reachable
+                });
+                for closure in closures {
+                    callers.entry(*closure).or_default().push(caller);
+                }
+            }
+        }
+        Self { callers, reachable }
+    }
+
+    /// Check if the relation with the first lock in `retry` is formed in a closure of `catch_unwind`,
+    /// and the one in `attempt` is formed before the panic, i.e., from a caller of `catch_unwind`.
+    fn is_retry_of(&self, retry: InstanceId, attempt: InstanceId) -> bool {
+        retry != attempt
+            && self.callers.get(&retry).map_or(false, |callers| {
+                callers
+                    .iter()
+                    .any(|caller| self.reachable[caller].contains(&attempt))
+            })
+    }
+
+    fn is_inversion(&self, a: InstanceId, b: InstanceId) -> bool {
+        self.is_retry_of(a, b) || self.is_retry_of(b, a)
+    }
+}
+
+/// `HEURISTIC_CLOSURE_ARG` if the second lock is only reachable from the first one
+/// through the closures passed to generic fns without MIR, see `CallSiteLocation::ClosureArg`.
+fn heuristic(a: &LockGuardId, b: &LockGuardId, callgraph: &CallGraph<'_>) -> Option<String> {
+    callgraph
+        .is_reachable_only_through_closure_args(a.instance_id, b.instance_id)
+        .then(|| HEURISTIC_CLOSURE_ARG.to_owned())
+}
+
+/// `ALIAS_CONFIDENCE_UNKNOWN` if the diagnosis relies on unknown alias results, see `set_paranoid`.
+fn alias_confidence(unknown_alias: bool) -> Option<String> {
+    unknown_alias.then(|| ALIAS_CONFIDENCE_UNKNOWN.to_owned())
 }
 
 /// Generate doublelock diagnosis.
@@ -721,23 +2673,29 @@ fn diagnose_doublelock<'tcx>(
     b: &LockGuardId,
     lockguards: &LockGuardMap<'tcx>,
     callgraph: &CallGraph<'tcx>,
+    limits: CallChainLimits,
     tcx: TyCtxt<'tcx>,
 ) -> report::DeadlockDiagnosis {
-    diagnose_one_relation(a, b, lockguards, callgraph, tcx)
+    diagnose_one_relation(a, b, lockguards, callgraph, limits, tcx)
 }
 
-/// Find all the callchains: source -> target
+/// The marker of truncated callchains.
+const TRUNCATED: &str = "...truncated";
+
+/// Find the callchains within `limits`: source -> target
+/// If truncated by `limits`, the last callchain is `[["...truncated"]]`.
 // e.g., for one path: source --|callsites1|--> medium --|callsites2|--> target,
 // first extract callsite locations on edge, namely, [callsites1, callsites2],
 // then map locations to spans [spans1, spans2].
-fn track_callchains<'tcx>(
+pub fn track_callchains<'tcx>(
     source: InstanceId,
     target: InstanceId,
     callgraph: &CallGraph<'tcx>,
+    limits: CallChainLimits,
     tcx: TyCtxt<'tcx>,
 ) -> Vec<Vec<Vec<String>>> {
-    let paths = callgraph.all_simple_paths(source, target);
-    paths
+    let (paths, truncated) = callgraph.simple_paths(source, target, limits);
+    let mut callchains = paths
         .into_iter()
         .map(|vec| {
             vec.windows(2)
@@ -760,7 +2718,11 @@ fn track_callchains<'tcx>(
                 })
                 .collect::<Vec<_>>()
         })
-        .collect::<Vec<_>>()
+        .collect::<Vec<_>>();
+    if truncated {
+        callchains.push(vec![vec![TRUNCATED.to_owned()]]);
+    }
+    callchains
 }
 
 // Find the diagnosis info for relation(a, b), including a's ty & span, b's ty & span, and callchains.
@@ -769,6 +2731,7 @@ fn diagnose_one_relation<'tcx>(
     b: &LockGuardId,
     lockguards: &LockGuardMap<'tcx>,
     callgraph: &CallGraph<'tcx>,
+    limits: CallChainLimits,
     tcx: TyCtxt<'tcx>,
 ) -> DeadlockDiagnosis {
     let a_info = &lockguards[a];
@@ -781,14 +2744,76 @@ fn diagnose_one_relation<'tcx>(
         format!("{:?}", b_info.lockguard_ty),
         format!("{:?}", b_info.span),
     );
-    let callchains = track_callchains(a.instance_id, b.instance_id, callgraph, tcx);
-    DeadlockDiagnosis::new(
+    let callchains = track_callchains(a.instance_id, b.instance_id, callgraph, limits, tcx);
+    let mut diagnosis = DeadlockDiagnosis::new(
         first_lock.0,
         first_lock.1,
         second_lock.0,
         second_lock.1,
         callchains,
-    )
+    );
+    diagnosis.first_lock_name = a_info.lock_name.clone();
+    diagnosis.second_lock_name = b_info.lock_name.clone();
+    diagnosis.raw_spans = Some((raw_span(a_info.span), raw_span(b_info.span)));
+    diagnosis
+}
+
+/// Find the thread roots from which the instance is reachable:
+/// the spans of the spawn callsites, or "main" for the crate entry point.
+/// Returns None if the instance is not reachable from any known root.
+fn diagnose_thread_roots<'tcx>(
+    instance_id: InstanceId,
+    thread_roots: &ThreadRoots,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<Vec<String>> {
+    let mut roots = thread_roots
+        .roots(instance_id)?
+        .iter()
+        .map(|root| diagnose_thread_root(root, callgraph, tcx))
+        .collect::<Vec<_>>();
+    roots.sort();
+    Some(roots)
+}
+
+/// The callsites of `Lazy::get` are inside the `lazy_static!` expansions pointing to the macro,
+/// use the callsite of the macro instead.
+fn once_callsite_span(span: Span) -> Span {
+    if span.from_expansion() {
+        span.source_callsite()
+    } else {
+        span
+    }
+}
+
+/// The instances reachable from `roots` in CallGraph, including `roots`.
+fn reachable_instances(roots: Vec<InstanceId>, callgraph: &CallGraph<'_>) -> FxHashSet<InstanceId> {
+    let mut reachable = roots.iter().copied().collect::<FxHashSet<_>>();
+    let mut worklist = roots;
+    while let Some(id) = worklist.pop() {
+        for callee in callgraph.graph.neighbors_directed(id, Direction::Outgoing) {
+            if reachable.insert(callee) {
+                worklist.push(callee);
+            }
+        }
+    }
+    reachable
+}
+
+/// The span of the spawn callsite of the thread root, or "main" for the entry point.
+fn diagnose_thread_root<'tcx>(
+    root: &ThreadRoot,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> String {
+    match root {
+        ThreadRoot::Main => "main".to_owned(),
+        ThreadRoot::Spawn(caller, loc) => {
+            let caller_body =
+                tcx.instance_mir(callgraph.index_to_instance(*caller).unwrap().instance().def);
+            format!("{:?}", caller_body.source_info(*loc).span)
+        }
+    }
 }
 
 fn diagnose_condvar_deadlock<'tcx>(
@@ -823,12 +2848,15 @@ fn diagnose_condvar_deadlock<'tcx>(
         .map(|(a, b)| {
             let a_info = &lockguards[a];
             let b_info = &lockguards[b];
-            WaitNotifyLocks::new(
+            let mut locks = WaitNotifyLocks::new(
                 format!("{:?}", a_info.lockguard_ty),
                 format!("{:?}", a_info.span),
                 format!("{:?}", b_info.lockguard_ty),
                 format!("{:?}", b_info.span),
-            )
+            );
+            locks.wait_lock_name = a_info.lock_name.clone();
+            locks.notify_lock_name = b_info.lock_name.clone();
+            locks
         })
         .collect::<Vec<_>>();
     if is_std_condvar {
@@ -850,6 +2878,9 @@ fn diagnose_condvar_deadlock<'tcx>(
     }
 }
 
+/// The maximal number of cycles enumerated in ConflictLockGraph.
+const MAX_CONFLICTLOCK_CYCLES: usize = 1024;
+
 /// The NodeIndex in ConflictLockGraph, denoting a unique relation(a, b) in ConflictLockGraph,
 /// where a and b are LockGuardId.
 type RelationId = NodeIndex;
@@ -879,53 +2910,74 @@ impl ConflictLockGraph {
         self.graph.node_weight(a)
     }
 
-    /// Find all the back-edges in the graph.
-    fn back_edges(&self) -> Vec<(RelationId, RelationId)> {
-        let mut back_edges = Vec::new();
-        let nodes = self.graph.node_indices();
-        for start in nodes {
-            depth_first_search(&self.graph, Some(start), |event| {
-                match event {
-                    DfsEvent::BackEdge(u, v) => {
-                        if !back_edges.contains(&(u, v)) && !back_edges.contains(&(v, u)) {
-                            back_edges.push((u, v));
+    fn edge_weight(&self, a: RelationId, b: RelationId) -> Option<DeadlockPossibility> {
+        let edge = self.graph.find_edge(a, b)?;
+        self.graph.edge_weight(edge).copied()
+    }
+
+    /// Find the elementary cycles in the graph with Johnson's algorithm, at most `max_cycles`.
+    /// Each cycle is found exactly once, beginning with its least node.
+    /// The returned bool denotes that the cycles are truncated by `max_cycles`.
+    fn cycle_paths(&self, max_cycles: usize) -> (Vec<Vec<RelationId>>, bool) {
+        let mut cycles = Vec::new();
+        for start in self.graph.node_indices() {
+            // The strongly connected component containing `start` in the subgraph of nodes >= `start`.
+            let subgraph = NodeFiltered::from_fn(&self.graph, |node| node >= start);
+            let scc = match algo::tarjan_scc(&subgraph)
+                .into_iter()
+                .find(|scc| scc.contains(&start))
+            {
+                Some(scc) => scc.into_iter().collect::<FxHashSet<_>>(),
+                None => continue,
+            };
+            let successors = |node: RelationId| {
+                self.graph
+                    .neighbors(node)
+                    .filter(|succ| scc.contains(succ))
+                    .collect::<FxHashSet<_>>()
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            };
+            let mut path = vec![start];
+            let mut blocked = FxHashSet::from_iter([start]);
+            let mut blocked_by: FxHashMap<RelationId, FxHashSet<RelationId>> = FxHashMap::default();
+            let mut closed = FxHashSet::default();
+            let mut stack = vec![(start, successors(start))];
+            while let Some((node, succs)) = stack.last_mut() {
+                let node = *node;
+                if let Some(succ) = succs.pop() {
+                    if succ == start {
+                        if cycles.len() == max_cycles {
+                            return (cycles, true);
                         }
+                        cycles.push(path.clone());
+                        closed.extend(path.iter().copied());
+                    } else if !blocked.contains(&succ) {
+                        path.push(succ);
+                        closed.remove(&succ);
+                        blocked.insert(succ);
+                        stack.push((succ, successors(succ)));
                     }
-                    DfsEvent::Finish(_, _) => {
-                        return Control::Break(());
+                    continue;
+                }
+                if closed.contains(&node) {
+                    // Unblock node and the nodes blocked by it recursively.
+                    let mut worklist = vec![node];
+                    while let Some(node) = worklist.pop() {
+                        if blocked.remove(&node) {
+                            worklist.extend(blocked_by.remove(&node).into_iter().flatten());
+                        }
+                    }
+                } else {
+                    for succ in successors(node) {
+                        blocked_by.entry(succ).or_default().insert(node);
                     }
-                    _ => {}
-                };
-                Control::Continue
-            });
-        }
-        back_edges
-    }
-
-    /// Find all the cycles in the graph.
-    fn cycle_paths(&self) -> Vec<Vec<RelationId>> {
-        let mut dedup = Vec::new();
-        let mut edge_sets = Vec::new();
-        for (src, target) in self.back_edges() {
-            let cycle_paths =
-                algo::all_simple_paths::<Vec<_>, _>(&self.graph, target, src, 0, None)
-                    .collect::<Vec<_>>();
-            for path in cycle_paths {
-                // `path` forms a cycle, where adjacent nodes are directly connected and last_node connects to first_node.
-                // Different `path`s beginning with different nodes may denote the same cycle if their edges are the same.
-                // Thus we use `edge_sets` to deduplicate the cycle paths.
-                let set = path
-                    .iter()
-                    .zip(path.iter().skip(1).chain(path.get(0)))
-                    .map(|(a, b)| (*a, *b))
-                    .collect::<FxHashSet<_>>();
-                if !edge_sets.contains(&set) {
-                    edge_sets.push(set);
-                    dedup.push(path);
                 }
+                stack.pop();
+                path.pop();
             }
         }
-        dedup
+        (cycles, false)
     }
 
     /// Print the ConflictGraph in dot format.
@@ -937,3 +2989,3156 @@ impl ConflictLockGraph {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::detector::report::{sort_reports, ReportEnvelope};
+    use crate::interest::concurrency::lock_registry::LockRegistry;
+    use crate::plugin::registered_plugins;
+    use crate::test_utils::{test_instances, with_tcx, write_src};
+    use rustc_middle::mir::Local;
+    use rustc_middle::ty::Instance;
+
+    fn relation(i: u32) -> (LockGuardId, LockGuardId) {
+        let a = LockGuardId::new(InstanceId::new(0), Local::from_u32(i));
+        let b = LockGuardId::new(InstanceId::new(1), Local::from_u32(i));
+        (a, b)
+    }
+
+    #[test]
+    fn test_cycle_paths() {
+        // 0 <-> 1, 1 -> 2 -> 0, 3 -> 3, 4 (no cycle)
+        let mut graph = ConflictLockGraph::new();
+        let nodes = (0..5)
+            .map(|i| graph.add_node(relation(i)))
+            .collect::<Vec<_>>();
+        for (a, b) in [(0, 1), (1, 0), (1, 2), (2, 0), (3, 3), (4, 0)] {
+            graph.add_edge(nodes[a], nodes[b], DeadlockPossibility::Possibly);
+        }
+        let (mut cycles, truncated) = graph.cycle_paths(MAX_CONFLICTLOCK_CYCLES);
+        assert!(!truncated);
+        cycles.sort();
+        assert_eq!(
+            cycles,
+            vec![
+                vec![nodes[0], nodes[1]],
+                vec![nodes[0], nodes[1], nodes[2]],
+                vec![nodes[3]],
+            ]
+        );
+        let (cycles, truncated) = graph.cycle_paths(2);
+        assert_eq!(cycles.len(), 2);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_cycle_paths_complete_graph() {
+        // A complete graph of n nodes without self-loops has sum_{k=2}^{n} C(n, k) * (k - 1)! cycles.
+        let mut graph = ConflictLockGraph::new();
+        let nodes = (0..5)
+            .map(|i| graph.add_node(relation(i)))
+            .collect::<Vec<_>>();
+        for a in nodes.iter() {
+            for b in nodes.iter() {
+                if a != b {
+                    graph.add_edge(*a, *b, DeadlockPossibility::Possibly);
+                }
+            }
+        }
+        let (cycles, truncated) = graph.cycle_paths(MAX_CONFLICTLOCK_CYCLES);
+        assert!(!truncated);
+        // 10 * 1 + 10 * 2 + 5 * 6 + 1 * 24
+        assert_eq!(cycles.len(), 84);
+        let dedup = cycles.iter().collect::<FxHashSet<_>>();
+        assert_eq!(dedup.len(), cycles.len());
+    }
+
+    const CUSTOM_LOCK_SRC: &str = r#"
+pub struct Lock<T>(std::cell::UnsafeCell<T>);
+pub struct Locked<'a, T>(&'a Lock<T>);
+impl<T> Lock<T> {
+    #[inline(never)]
+    pub fn acquire(&self) -> Locked<'_, T> {
+        Locked(self)
+    }
+}
+impl<'a, T> Drop for Locked<'a, T> {
+    fn drop(&mut self) {}
+}
+pub fn double_lock(lock: &Lock<i32>) {
+    let _a = lock.acquire();
+    let _b = lock.acquire();
+}
+"#;
+
+    const DOWNGRADE_SRC: &str = r#"
+pub struct RwLock<T>(std::cell::UnsafeCell<T>);
+pub struct ReadGuard<'a, T>(&'a RwLock<T>);
+pub struct WriteGuard<'a, T>(&'a RwLock<T>);
+impl<T> RwLock<T> {
+    #[inline(never)]
+    pub fn read(&self) -> ReadGuard<'_, T> {
+        ReadGuard(self)
+    }
+    #[inline(never)]
+    pub fn write(&self) -> WriteGuard<'_, T> {
+        WriteGuard(self)
+    }
+}
+impl<'a, T> WriteGuard<'a, T> {
+    #[inline(never)]
+    pub fn downgrade(this: Self) -> ReadGuard<'a, T> {
+        let lock = this.0;
+        std::mem::forget(this);
+        ReadGuard(lock)
+    }
+}
+impl<'a, T> Drop for ReadGuard<'a, T> {
+    fn drop(&mut self) {}
+}
+impl<'a, T> Drop for WriteGuard<'a, T> {
+    fn drop(&mut self) {}
+}
+pub fn downgrade_then_read(lock: &RwLock<i32>) {
+    let w = lock.write();
+    let _r = WriteGuard::downgrade(w);
+    let _r2 = lock.read();
+}
+pub fn downgrade_then_write(lock: &RwLock<i32>) {
+    let w = lock.write();
+    let _r = WriteGuard::downgrade(w); // first
+    let _w2 = lock.write();
+}
+"#;
+
+    const MAPPED_GUARD_SRC: &str = r#"
+pub struct Lock<T>(std::cell::UnsafeCell<T>);
+pub struct Guard<'a, T>(&'a Lock<T>);
+pub struct MappedGuard<'a, U>(&'a mut U);
+impl<T> Lock<T> {
+    #[inline(never)]
+    pub fn acquire(&self) -> Guard<'_, T> {
+        Guard(self)
+    }
+}
+impl<'a, T> Guard<'a, T> {
+    #[inline(never)]
+    pub fn map<U, F: FnOnce(&mut T) -> &mut U>(this: Self, f: F) -> MappedGuard<'a, U> {
+        let data = unsafe { &mut *this.0 .0.get() };
+        std::mem::forget(this);
+        MappedGuard(f(data))
+    }
+    #[inline(never)]
+    pub fn try_map<U, F: FnOnce(&mut T) -> Option<&mut U>>(
+        this: Self,
+        f: F,
+    ) -> Result<MappedGuard<'a, U>, Self> {
+        let data = unsafe { &mut *this.0 .0.get() };
+        match f(data) {
+            Some(data) => {
+                std::mem::forget(this);
+                Ok(MappedGuard(data))
+            }
+            None => Err(this),
+        }
+    }
+}
+impl<'a, T> Drop for Guard<'a, T> {
+    fn drop(&mut self) {}
+}
+impl<'a, U> Drop for MappedGuard<'a, U> {
+    fn drop(&mut self) {}
+}
+pub fn map_then_lock(lock: &Lock<(i32, bool)>) {
+    let _first = Guard::map(lock.acquire(), |pair| &mut pair.0); // first
+    let _pair = lock.acquire();
+}
+pub fn try_map_then_lock(lock: &Lock<(i32, bool)>) {
+    if let Ok(_first) = Guard::try_map(lock.acquire(), |pair| Some(&mut pair.1)) { // first
+        let _pair = lock.acquire();
+    }
+}
+pub fn map_then_unlock(lock: &Lock<(i32, bool)>) {
+    {
+        let _first = Guard::map(lock.acquire(), |pair| &mut pair.0);
+    }
+    let _pair = lock.acquire();
+}
+"#;
+
+    const REENTRANT_CONFLICT_SRC: &str = r#"
+use std::sync::Mutex;
+pub struct Lock<T>(std::cell::UnsafeCell<T>);
+unsafe impl<T> Sync for Lock<T> {}
+pub struct Locked<'a, T>(&'a Lock<T>);
+impl<T> Lock<T> {
+    #[inline(never)]
+    pub fn acquire(&self) -> Locked<'_, T> {
+        Locked(self)
+    }
+}
+impl<'a, T> Drop for Locked<'a, T> {
+    fn drop(&mut self) {}
+}
+pub fn nested(lock: &Lock<i32>) {
+    let _outer = lock.acquire();
+    let _inner = lock.acquire();
+}
+pub fn reentrant_then_mutex(lock: &Lock<i32>, mutex: &Mutex<bool>) {
+    let _l = lock.acquire();
+    let _m = mutex.lock().unwrap();
+}
+pub fn mutex_then_reentrant(lock: &Lock<i32>, mutex: &Mutex<bool>) {
+    let _m = mutex.lock().unwrap();
+    let _l = lock.acquire();
+}
+"#;
+
+    /// Run the deadlock detector on the non-generic fns in the local crate
+    /// and the instantiations of the generic fns collected for codegen.
+    fn detect_deadlock(tcx: TyCtxt<'_>, registry: LockRegistry) -> Vec<Report> {
+        run_deadlock_detector(tcx, registry).0
+    }
+
+    /// Run the deadlock detector, returning the reports and the lock classes.
+    fn run_deadlock_detector(
+        tcx: TyCtxt<'_>,
+        registry: LockRegistry,
+    ) -> (Vec<Report>, Vec<LockClass>) {
+        run_deadlock_detector_with(tcx, registry, |_, _| {})
+    }
+
+    /// Run the deadlock detector after `configure` sets up the detector and the alias analysis.
+    fn run_deadlock_detector_with<'tcx>(
+        tcx: TyCtxt<'tcx>,
+        registry: LockRegistry,
+        configure: impl FnOnce(&mut DeadlockDetector<'tcx>, &mut AliasAnalysis<'_, 'tcx>),
+    ) -> (Vec<Report>, Vec<LockClass>) {
+        let (reports, detector) =
+            run_deadlock_detector_on(tcx, test_instances(tcx), registry, configure);
+        (reports, detector.lock_classes)
+    }
+
+    /// Run the deadlock detector on the callgraph of the given instances with body,
+    /// returning the reports and the detector with its results.
+    fn run_deadlock_detector_on<'tcx>(
+        tcx: TyCtxt<'tcx>,
+        instances: Vec<Instance<'tcx>>,
+        registry: LockRegistry,
+        configure: impl FnOnce(&mut DeadlockDetector<'tcx>, &mut AliasAnalysis<'_, 'tcx>),
+    ) -> (Vec<Report>, DeadlockDetector<'tcx>) {
+        let progress = Progress::new(true);
+        let param_env = ParamEnv::reveal_all();
+        let mut callgraph = CallGraph::new();
+        callgraph.analyze(instances, tcx, param_env, &progress);
+        let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+        let mut detector = DeadlockDetector::new(
+            tcx,
+            param_env,
+            DetectorConfig::default(),
+            false,
+            GuardNewtypes::new(registry, Vec::new()),
+            Vec::new(),
+        );
+        configure(&mut detector, &mut alias_analysis);
+        let reports = detector.detect(&callgraph, &mut alias_analysis, &progress);
+        (reports, detector)
+    }
+
+    const PARANOID_SRC: &str = r#"
+use std::sync::Mutex;
+
+pub fn relock(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap(); // first
+    let _b = m.lock().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_paranoid_reports_unknown_alias() {
+        let src = write_src("paranoid", PARANOID_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // The points-to analysis of every fn exceeds the budget, so the aliases are unknown.
+                let tiny_budget = AnalysisBudget {
+                    time: std::time::Duration::ZERO,
+                    max_nodes: 1,
+                };
+                let run = |paranoid| {
+                    run_deadlock_detector_with(
+                        tcx,
+                        LockRegistry::default(),
+                        |detector, alias_analysis| {
+                            alias_analysis.set_budget(tiny_budget);
+                            detector.set_paranoid(paranoid);
+                        },
+                    )
+                    .0
+                };
+                assert!(run(false).is_empty());
+                let reports = run(true);
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(PARANOID_SRC)
+                );
+                assert_eq!(reports[0].possibility(), "Possibly");
+                assert!(reports[0].is_paranoid());
+                assert_eq!(
+                    ReportEnvelope::new("paranoid".to_owned(), reports)
+                        .summary
+                        .paranoid,
+                    1
+                );
+                // The reports with known aliases are not marked.
+                let reports =
+                    run_deadlock_detector_with(tcx, LockRegistry::default(), |detector, _| {
+                        detector.set_paranoid(true)
+                    })
+                    .0;
+                assert_eq!(reports.len(), 1);
+                assert_eq!(reports[0].possibility(), "Probably");
+                assert!(!reports[0].is_paranoid());
+            }),
+        );
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let src = write_src("downgrade", DOWNGRADE_SRC);
+        with_tcx(
+            &src,
+            &["--crate-name=my_lock".to_owned()],
+            Box::new(|tcx| {
+                let config = Config::from_str(
+                    r#"{"locks": [
+                        {"name": "MyRead", "lock": "MyRwLock", "crate": "my_lock", "guard": "ReadGuard", "access": "read", "recursive_read": true},
+                        {"name": "MyWrite", "lock": "MyRwLock", "crate": "my_lock", "guard": "WriteGuard", "access": "write"}
+                    ]}"#,
+                )
+                .unwrap();
+                // The write guard moved into `downgrade` is released,
+                // and the read guard returned acquires the same lock.
+                let reports = detect_deadlock(tcx, LockRegistry::new(config.locks));
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(DOWNGRADE_SRC)
+                );
+                match &reports[0] {
+                    Report::DoubleLock(content) => {
+                        assert_eq!(content.diagnosis.first_lock_type, "MyRead(i32)");
+                        assert_eq!(content.diagnosis.second_lock_type, "MyWrite(i32)");
+                    }
+                    report => panic!("unexpected report {:?}", report),
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_mapped_guard() {
+        let src = write_src("mapped-guard", MAPPED_GUARD_SRC);
+        with_tcx(
+            &src,
+            &["--crate-name=my_lock".to_owned()],
+            Box::new(|tcx| {
+                let config = Config::from_str(
+                    r#"{"locks": [{"name": "MyLock", "crate": "my_lock", "guard": "Guard", "access": "mutex"}]}"#,
+                )
+                .unwrap();
+                // The mapped guards still guard the pairs they are mapped from.
+                let reports = detect_deadlock(tcx, LockRegistry::new(config.locks));
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(MAPPED_GUARD_SRC)
+                );
+                for report in &reports {
+                    match report {
+                        Report::DoubleLock(content) => assert_eq!(
+                            content.diagnosis.first_lock_type,
+                            content.diagnosis.second_lock_type
+                        ),
+                        report => panic!("unexpected report {:?}", report),
+                    }
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_reentrant_lock_in_conflictlock() {
+        let src = write_src("reentrant-conflict", REENTRANT_CONFLICT_SRC);
+        with_tcx(
+            &src,
+            &["--crate-name=my_lock".to_owned()],
+            Box::new(|tcx| {
+                let config = Config::from_str(
+                    r#"{"locks": [{"name": "MyLock", "crate": "my_lock", "guard": "Locked", "access": "mutex", "reentrant": true}]}"#,
+                )
+                .unwrap();
+                // The nested acquisition of the reentrant lock is no doublelock,
+                // but the reentrant lock held by one thread still blocks the other in the cycle with the mutex.
+                let reports = detect_deadlock(tcx, LockRegistry::new(config.locks));
+                assert_eq!(reports.len(), 1, "{:?}", reports);
+                match &reports[0] {
+                    Report::ConflictLock(content) => {
+                        let mut lock_types = content
+                            .diagnosis
+                            .iter()
+                            .map(|diagnosis| {
+                                (
+                                    diagnosis.first_lock_type.as_str(),
+                                    diagnosis.second_lock_type.as_str(),
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        lock_types.sort();
+                        assert_eq!(
+                            lock_types,
+                            [
+                                ("MyLock(i32)", "StdMutex(bool)"),
+                                ("StdMutex(bool)", "MyLock(i32)")
+                            ]
+                        );
+                    }
+                    report => panic!("unexpected report {:?}", report),
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_custom_lock_from_config() {
+        let src = write_src("custom-lock", CUSTOM_LOCK_SRC);
+        with_tcx(
+            &src,
+            &["--crate-name=my_lock".to_owned()],
+            Box::new(|tcx| {
+                assert!(detect_deadlock(tcx, LockRegistry::default()).is_empty());
+                let config = Config::from_str(
+                    r#"{"locks": [{"name": "MyLock", "crate": "my_lock", "guard": "Locked", "access": "mutex"}]}"#,
+                )
+                .unwrap();
+                let reports = detect_deadlock(tcx, LockRegistry::new(config.locks));
+                assert_eq!(reports.len(), 1);
+                match &reports[0] {
+                    Report::DoubleLock(content) => {
+                        assert_eq!(content.diagnosis.first_lock_type, "MyLock(i32)");
+                        assert_eq!(content.diagnosis.second_lock_type, "MyLock(i32)");
+                    }
+                    report => panic!("unexpected report {:?}", report),
+                }
+                // A reentrant lock can be acquired again by the same thread.
+                let config = Config::from_str(
+                    r#"{"locks": [{"name": "MyLock", "crate": "my_lock", "guard": "Locked", "access": "mutex", "reentrant": true}]}"#,
+                )
+                .unwrap();
+                assert!(detect_deadlock(tcx, LockRegistry::new(config.locks)).is_empty());
+            }),
+        );
+    }
+
+    /// The config locks marked `// forbidden` are reachable from the handlers.
+    const FORBIDDEN_LOCK_SRC: &str = r#"
+use std::sync::Mutex;
+pub static CONFIG: Mutex<i32> = Mutex::new(0);
+pub struct State {
+    pub config: Mutex<i32>,
+}
+pub mod handlers {
+    pub fn handle() -> i32 {
+        *super::CONFIG.lock().unwrap() // forbidden
+    }
+    pub fn handle_indirect() -> i32 {
+        super::read_config()
+    }
+    pub fn handle_state(state: &super::State) -> i32 {
+        *state.config.lock().unwrap() // forbidden
+    }
+}
+pub fn read_config() -> i32 {
+    *CONFIG.lock().unwrap() // forbidden
+}
+pub fn startup(state: &State) -> i32 {
+    *CONFIG.lock().unwrap() + *state.config.lock().unwrap()
+}
+"#;
+
+    #[test]
+    fn test_forbidden_lock_plugin() {
+        let src = write_src("forbidden-lock", FORBIDDEN_LOCK_SRC);
+        with_tcx(
+            &src,
+            &["--crate-name=app".to_owned()],
+            Box::new(|tcx| {
+                let config = Config::from_str(
+                    r#"{"plugins": {"forbidden_lock": [
+                        {"lock": "app::CONFIG", "from": "handlers"},
+                        {"lock": "State.config", "from": "app::handlers::handle_state"}
+                    ]}}"#,
+                )
+                .unwrap();
+                let reports =
+                    run_deadlock_detector_with(tcx, LockRegistry::default(), |detector, _| {
+                        detector.set_plugins(registered_plugins(&config.plugins).unwrap())
+                    })
+                    .0;
+                let mut lines = reports
+                    .iter()
+                    .map(|report| match report {
+                        Report::Plugin(content) => {
+                            let span = &content.diagnosis.span;
+                            span.split(':').nth(1).unwrap().parse::<usize>().unwrap()
+                        }
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                lines.sort_unstable();
+                let expected = FORBIDDEN_LOCK_SRC
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| line.ends_with("// forbidden"))
+                    .map(|(idx, _)| idx + 1)
+                    .collect::<Vec<_>>();
+                assert_eq!(lines, expected);
+                // The lock in the callee of the handler is reported with the callchain.
+                let callchains = reports.iter().find_map(|report| match report {
+                    Report::Plugin(content) if !content.diagnosis.callchains.is_empty() => {
+                        Some(&content.diagnosis.callchains)
+                    }
+                    _ => None,
+                });
+                assert_eq!(callchains.map(Vec::len), Some(1));
+                assert!(Config::from_str(
+                    r#"{"plugins": {"forbidden_lock": [{"lock": "CONFIG"}]}}"#
+                )
+                .is_err());
+                assert!(Config::from_str(r#"{"plugins": {"no_such_plugin": []}}"#).is_err());
+            }),
+        );
+    }
+
+    const RELEASE_ORDER_SRC: &str = r#"
+use std::sync::{Mutex, MutexGuard};
+pub struct Bank {
+    pub accounts: Mutex<u64>,
+    pub ledger: Mutex<Vec<u64>>,
+}
+pub struct Transfer<'a> {
+    pub accounts: MutexGuard<'a, u64>,
+    pub ledger: MutexGuard<'a, Vec<u64>>,
+}
+pub struct Audit<'a> {
+    pub ledger: MutexGuard<'a, Vec<u64>>,
+    pub accounts: MutexGuard<'a, u64>,
+}
+pub struct Deposit<'a> {
+    pub accounts: MutexGuard<'a, u64>,
+    pub ledger: MutexGuard<'a, Vec<u64>>,
+}
+pub fn transfer(bank: &Bank) -> Transfer<'_> {
+    Transfer { accounts: bank.accounts.lock().unwrap(), ledger: bank.ledger.lock().unwrap() }
+}
+pub fn audit(bank: &Bank) -> Audit<'_> {
+    Audit { ledger: bank.ledger.lock().unwrap(), accounts: bank.accounts.lock().unwrap() }
+}
+pub fn deposit(bank: &Bank) -> Deposit<'_> {
+    Deposit { accounts: bank.accounts.lock().unwrap(), ledger: bank.ledger.lock().unwrap() }
+}
+pub fn close(bank: &Bank) {
+    let accounts = bank.accounts.lock().unwrap();
+    let ledger = bank.ledger.lock().unwrap();
+    drop(ledger);
+    drop(accounts);
+}
+"#;
+
+    #[test]
+    fn test_implicit_release_order() {
+        let src = write_src("release-order", RELEASE_ORDER_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // Off by default, while `audit` acquiring the locks in the opposite order conflicts with the others.
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                assert_eq!(reports.len(), 3);
+                assert!(reports
+                    .iter()
+                    .all(|report| matches!(report, Report::ConflictLock(_))));
+                let reports =
+                    run_deadlock_detector_with(tcx, LockRegistry::default(), |detector, _| {
+                        detector.config.toggle("implicit_release_order").unwrap()
+                    })
+                    .0;
+                let mut pairs = reports
+                    .iter()
+                    .filter_map(|report| match report {
+                        Report::ImplicitReleaseOrder(content) => {
+                            let (first, second) =
+                                (&content.diagnosis.first, &content.diagnosis.second);
+                            assert!(first.holder);
+                            assert_eq!(
+                                first.lock_types.iter().rev().collect::<Vec<_>>(),
+                                second.lock_types.iter().collect::<Vec<_>>()
+                            );
+                            Some((first.name.as_str(), second.name.as_str(), second.holder))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                pairs.sort_unstable();
+                // `Transfer` and `Deposit` release the locks in the same order.
+                assert_eq!(
+                    pairs,
+                    [
+                        ("Audit", "Deposit", true),
+                        ("Deposit", "close", false),
+                        ("Transfer", "Audit", true),
+                        ("Transfer", "close", false),
+                    ]
+                );
+            }),
+        );
+    }
+
+    /// `handle` acquires `REQUESTS` through a call and `STATS` on both branches, but `CACHE` only on one.
+    const HOT_PATH_SRC: &str = r#"
+use std::sync::Mutex;
+static REQUESTS: Mutex<u64> = Mutex::new(0);
+static STATS: Mutex<u64> = Mutex::new(0);
+static CACHE: Mutex<u64> = Mutex::new(0);
+fn count() {
+    *REQUESTS.lock().unwrap() += 1;
+}
+pub fn handle(cached: bool) -> u64 {
+    count();
+    if cached {
+        *STATS.lock().unwrap() += 1;
+        return 0;
+    }
+    let value = *CACHE.lock().unwrap();
+    *STATS.lock().unwrap() += value;
+    value
+}
+pub fn other() -> u64 {
+    *CACHE.lock().unwrap()
+}
+"#;
+
+    #[test]
+    fn test_hot_path_lock() {
+        let src = write_src("hot-path", HOT_PATH_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // Nothing is reported without entry points.
+                assert!(detect_deadlock(tcx, LockRegistry::default()).is_empty());
+                let reports =
+                    run_deadlock_detector_with(tcx, LockRegistry::default(), |detector, _| {
+                        detector.set_hot_path_fns(vec!["handle".to_owned()])
+                    })
+                    .0;
+                let diagnosis = match &reports[..] {
+                    [Report::HotPathLock(content)] => &content.diagnosis,
+                    _ => panic!("{:?}", reports),
+                };
+                assert_eq!(diagnosis.entry_fn, "handle");
+                let names = |locks: &[report::HotPathLock]| {
+                    locks
+                        .iter()
+                        .map(|lock| (lock.name.clone(), lock.acquisitions))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(
+                    names(&diagnosis.must_acquire),
+                    [("REQUESTS".to_owned(), 1), ("STATS".to_owned(), 2)]
+                );
+                assert_eq!(names(&diagnosis.may_acquire), [("CACHE".to_owned(), 1)]);
+            }),
+        );
+    }
+
+    /// The temporary guards of one-liners, tail expressions, and match scrutinees.
+    const TEMP_GUARD_SRC: &str = r#"
+use std::sync::Mutex;
+pub fn one_liners(m: &Mutex<u64>, v: &Mutex<Vec<u64>>) -> u64 {
+    *m.lock().unwrap() += 1;
+    *m.lock().unwrap() += 1;
+    v.lock().unwrap().push(*m.lock().unwrap());
+    let _ = m.lock();
+    let n = std::mem::take(&mut *m.lock().unwrap());
+    n + *m.lock().unwrap()
+}
+pub fn tail_temps(m: &Mutex<u64>, cond: bool) {
+    let x = if cond { *m.lock().unwrap() } else { 0 };
+    *m.lock().unwrap() += x;
+}
+pub fn match_scrutinee(m: &Mutex<u64>) {
+    match *m.lock().unwrap() { // first
+        1 => {}
+        _ => *m.lock().unwrap() += 1,
+    }
+}
+"#;
+
+    #[test]
+    fn test_temp_guards() {
+        let src = write_src("temp-guard", TEMP_GUARD_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // The temporary guards are dropped at the end of their statements,
+                // except those in the match scrutinee, which live until the end of the match.
+                let (discarded, reports): (Vec<_>, Vec<_>) =
+                    detect_deadlock(tcx, LockRegistry::default())
+                        .into_iter()
+                        .partition(|report| matches!(report, Report::DiscardedGuard(_)));
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(TEMP_GUARD_SRC)
+                );
+                assert_eq!(discarded.len(), 1);
+            }),
+        );
+    }
+
+    const GUARD_LEAK_SRC: &str = r#"
+use std::mem::ManuallyDrop;
+use std::sync::{Mutex, MutexGuard};
+pub struct Holder<'a> {
+    _guard: MutexGuard<'a, i32>,
+}
+pub fn forget(m: &Mutex<i32>) {
+    let g = m.lock().unwrap(); // leaked
+    std::mem::forget(g);
+}
+pub fn manually_drop(m: &Mutex<i32>) {
+    let _g = ManuallyDrop::new(m.lock().unwrap()); // leaked
+}
+pub fn box_leak(m: &'static Mutex<i32>) {
+    **Box::leak(Box::new(m.lock().unwrap())) += 1; // leaked
+}
+pub fn holder(m: &Mutex<i32>) {
+    std::mem::forget(Holder { _guard: m.lock().unwrap() }); // leaked
+}
+pub fn raw_lock(m: &Mutex<i32>) -> ManuallyDrop<MutexGuard<'_, i32>> {
+    ManuallyDrop::new(m.lock().unwrap())
+}
+pub fn not_a_guard(m: &Mutex<i32>, v: Vec<i32>) {
+    let _g = m.lock().unwrap();
+    std::mem::forget(v);
+}
+pub fn drop_in_place_unlock(m: &Mutex<i32>) {
+    let mut g = m.lock().unwrap();
+    unsafe { std::ptr::drop_in_place(&mut g as *mut _) };
+    *m.lock().unwrap() += 1;
+    std::mem::forget(g);
+}
+pub fn manually_drop_unlock(m: &Mutex<i32>) {
+    let mut g = ManuallyDrop::new(m.lock().unwrap());
+    **g += 1;
+    unsafe { ManuallyDrop::drop(&mut g) };
+}
+pub fn forget_borrowed(m: &Mutex<i32>) {
+    let mut g = m.lock().unwrap(); // leaked
+    let _p = &mut g as *mut MutexGuard<'_, i32>;
+    std::mem::forget(g);
+}
+"#;
+
+    #[test]
+    fn test_guard_leak() {
+        let src = write_src("guard-leak", GUARD_LEAK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let mut leaked = detect_deadlock(tcx, LockRegistry::default())
+                    .iter()
+                    .filter_map(|report| match report {
+                        Report::LockGuardLeaked(content) => {
+                            let line = content.diagnosis.lock_span.split(':').nth(1).unwrap();
+                            Some(line.parse::<usize>().unwrap())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                leaked.sort_unstable();
+                let marked = GUARD_LEAK_SRC
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| line.ends_with("// leaked"))
+                    .map(|(idx, _)| idx + 1)
+                    .collect::<Vec<_>>();
+                assert_eq!(leaked, marked);
+            }),
+        );
+    }
+
+    const FORCE_UNLOCK_SRC: &str = r#"
+use std::mem::ManuallyDrop;
+use std::sync::Mutex;
+pub trait ForceUnlock {
+    unsafe fn force_unlock(&self);
+}
+impl ForceUnlock for Mutex<i32> {
+    unsafe fn force_unlock(&self) {}
+}
+pub fn double_unlock(m: &Mutex<i32>) {
+    let mut g = m.lock().unwrap();
+    *g += 1;
+    unsafe { m.force_unlock() };
+} // double unlock
+pub fn use_after_unlock(m: &Mutex<i32>) {
+    let mut g = ManuallyDrop::new(m.lock().unwrap());
+    unsafe { m.force_unlock() };
+    **g += 1; // use after unlock
+}
+pub fn adapter(m: &Mutex<i32>) {
+    let mut g = ManuallyDrop::new(m.lock().unwrap());
+    **g += 1;
+    unsafe { m.force_unlock() };
+}
+pub fn forget(m: &Mutex<i32>) {
+    let g = m.lock().unwrap();
+    std::mem::forget(g);
+    unsafe { m.force_unlock() };
+}
+"#;
+
+    #[test]
+    fn test_force_unlock() {
+        let src = write_src("force-unlock", FORCE_UNLOCK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut misuses = detect_deadlock(tcx, LockRegistry::default())
+                    .iter()
+                    .filter_map(|report| match report {
+                        Report::DoubleUnlock(content) => {
+                            Some((line(&content.diagnosis.later_span), "double unlock"))
+                        }
+                        Report::UseAfterUnlock(content) => {
+                            Some((line(&content.diagnosis.later_span), "use after unlock"))
+                        }
+                        Report::LockGuardLeaked(content) => {
+                            Some((line(&content.diagnosis.leak_span), "leaked"))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                misuses.sort_unstable();
+                assert_eq!(misuses, [(14, "double unlock"), (18, "use after unlock")]);
+            }),
+        );
+    }
+
+    const REFCELL_SRC: &str = r#"
+use std::cell::RefCell;
+pub fn borrow_then_borrow_mut(c: &RefCell<i32>) {
+    let g = c.borrow(); // first
+    *c.borrow_mut() += *g;
+}
+pub fn borrow_twice(c: &RefCell<i32>) -> i32 {
+    let g1 = c.borrow();
+    let g2 = c.borrow();
+    *g1 + *g2
+}
+pub fn try_borrow_mut(c: &RefCell<i32>) {
+    let _g = c.borrow();
+    if let Ok(mut g) = c.try_borrow_mut() {
+        *g += 1;
+    };
+}
+pub fn try_borrow_mut_unwrap(c: &RefCell<i32>) {
+    let _g = c.borrow_mut(); // first
+    *c.try_borrow_mut().unwrap() += 1;
+}
+"#;
+
+    #[test]
+    fn test_refcell_double_borrow() {
+        let src = write_src("refcell", REFCELL_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // Only the conflicting borrows panic, and `try_borrow_mut` only if unwrapped.
+                let (reports, _) =
+                    run_deadlock_detector_with(tcx, LockRegistry::default(), |detector, _| {
+                        detector.config.toggle("refcell_double_borrow").unwrap()
+                    });
+                assert!(reports
+                    .iter()
+                    .all(|report| !matches!(report, Report::DoubleLock(_))));
+                let mut lines = reports
+                    .iter()
+                    .filter_map(|report| match report {
+                        Report::RefCellDoubleBorrow(content) => {
+                            let line = content.diagnosis.first_lock_span.split(':').nth(1)?;
+                            Some(line.parse::<usize>().unwrap())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                lines.sort_unstable();
+                assert_eq!(lines, marked_first_lines(REFCELL_SRC));
+            }),
+        );
+    }
+
+    const LOCK_COVERAGE_SRC: &str = r#"
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+pub struct Stats {
+    mu: Mutex<()>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+impl Stats {
+    pub fn hit(&self) {
+        let _g = self.mu.lock().unwrap();
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+    pub fn hit_twice(&self) {
+        let _g = self.mu.lock().unwrap();
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn get(&self) -> usize {
+        let _g = self.mu.lock().unwrap();
+        self.hits.load(Ordering::Relaxed)
+    }
+    pub fn fast_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed); // unprotected
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+pub fn run(stats: Arc<Stats>) {
+    let s = stats.clone();
+    let t = thread::spawn(move || {
+        s.hit();
+        s.hit_twice();
+        s.fast_hit();
+    });
+    stats.get();
+    t.join().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_lock_coverage() {
+        let src = write_src("lock-coverage", LOCK_COVERAGE_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // `misses` is accessed with `mu` held at only half of its accesses, below the threshold.
+                let coverage = detect_deadlock(tcx, LockRegistry::default())
+                    .into_iter()
+                    .filter_map(|report| match report {
+                        Report::LockCoverage(content) => Some(content.diagnosis),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(coverage.len(), 1);
+                let diagnosis = &coverage[0];
+                assert_eq!(
+                    (diagnosis.field.as_str(), diagnosis.lock.as_str()),
+                    ("Stats.hits", "Stats.mu")
+                );
+                assert_eq!((diagnosis.protected_accesses, diagnosis.accesses), (4, 5));
+                let lines = diagnosis
+                    .unprotected_spans
+                    .iter()
+                    .map(|span| span.split(':').nth(1).unwrap().parse::<usize>().unwrap())
+                    .collect::<Vec<_>>();
+                assert_eq!(lines, [26]);
+            }),
+        );
+    }
+
+    const DISCARDED_GUARD_SRC: &str = r#"
+#![allow(let_underscore_lock)]
+use std::sync::Mutex;
+pub fn discard(m: &Mutex<i32>) {
+    let _ = m.lock(); // discarded
+    let _ = m.lock().unwrap(); // discarded
+    let _: Option<_> = m.try_lock().ok(); // discarded
+    *m.lock().unwrap() += 1;
+}
+pub fn hold(m: &Mutex<i32>) {
+    let _g = m.lock().unwrap(); // first
+    let _ = *m.lock().unwrap();
+}
+pub fn closure(m: &Mutex<i32>) -> impl Fn() + '_ {
+    move || {
+        let _ = m.lock(); // discarded
+    }
+}
+"#;
+
+    #[test]
+    fn test_discarded_guard() {
+        let src = write_src("discarded-guard", DISCARDED_GUARD_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // `let _ =` releases the lock at once, so only `let _g =` deadlocks with the later lock.
+                let (discarded, reports): (Vec<_>, Vec<_>) =
+                    detect_deadlock(tcx, LockRegistry::default())
+                        .into_iter()
+                        .partition(|report| matches!(report, Report::DiscardedGuard(_)));
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(DISCARDED_GUARD_SRC)
+                );
+                let mut discarded = discarded
+                    .iter()
+                    .map(|report| match report {
+                        Report::DiscardedGuard(content) => {
+                            let line = content.diagnosis.lock_span.split(':').nth(1).unwrap();
+                            (
+                                line.parse::<usize>().unwrap(),
+                                content.diagnosis.fn_name.as_str(),
+                            )
+                        }
+                        _ => unreachable!(),
+                    })
+                    .collect::<Vec<_>>();
+                discarded.sort_unstable();
+                assert_eq!(
+                    discarded,
+                    [
+                        (5, "discard"),
+                        (6, "discard"),
+                        (7, "discard"),
+                        (16, "closure")
+                    ]
+                );
+            }),
+        );
+    }
+
+    /// The first guard in each fn is held across the early returns of `?`.
+    const QUESTION_MARK_SRC: &str = r#"
+use std::sync::Mutex;
+pub struct MyError;
+impl<T> From<std::sync::PoisonError<T>> for MyError {
+    fn from(_: std::sync::PoisonError<T>) -> Self { MyError }
+}
+impl From<std::num::ParseIntError> for MyError {
+    fn from(_: std::num::ParseIntError) -> Self { MyError }
+}
+fn helper() -> Result<(), MyError> { Ok(()) }
+fn helper_opt() -> Option<i32> { Some(1) }
+pub fn result(m: &Mutex<i32>) -> Result<(), MyError> {
+    let _a = m.lock().unwrap(); // first
+    helper()?;
+    let _b = m.lock().unwrap();
+    Ok(())
+}
+pub fn result_from(m: &Mutex<i32>, s: &str) -> Result<i32, MyError> {
+    let _a = m.lock().unwrap(); // first
+    let n: i32 = s.parse()?;
+    helper()?;
+    let _b = m.lock().unwrap();
+    Ok(n)
+}
+pub fn option(m: &Mutex<i32>) -> Option<i32> {
+    let _a = m.lock().unwrap(); // first
+    let x = helper_opt()?;
+    let y = helper_opt()?;
+    let _b = m.lock().unwrap();
+    Some(x + y)
+}
+pub fn lock_result(m: &Mutex<i32>) -> Result<(), MyError> {
+    let _a = m.lock()?; // first
+    helper()?;
+    let _b = m.lock()?;
+    Ok(())
+}
+pub fn lock_option(m: &Mutex<i32>) -> Option<i32> {
+    let a = m.lock().ok()?; // first
+    let x = helper_opt()?;
+    let b = m.lock().ok()?;
+    Some(*a + *b + x)
+}
+"#;
+
+    /// The sorted lines of the first locks of the doublelocks.
+    fn doublelock_first_lines(reports: &[Report]) -> Vec<usize> {
+        let mut first_lines = reports
+            .iter()
+            .map(|report| match report {
+                Report::DoubleLock(content) => {
+                    let span = &content.diagnosis.first_lock_span;
+                    span.split(':').nth(1).unwrap().parse::<usize>().unwrap()
+                }
+                report => panic!("unexpected report {:?}", report),
+            })
+            .collect::<Vec<_>>();
+        first_lines.sort_unstable();
+        first_lines.dedup();
+        first_lines
+    }
+
+    /// The lines ending with `// first` in `src`.
+    fn marked_first_lines(src: &str) -> Vec<usize> {
+        src.lines()
+            .enumerate()
+            .filter(|(_, line)| line.ends_with("// first"))
+            .map(|(idx, _)| idx + 1)
+            .collect()
+    }
+
+    #[test]
+    fn test_doublelock_across_question_mark() {
+        let src = write_src("question-mark", QUESTION_MARK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let first_lines =
+                    doublelock_first_lines(&detect_deadlock(tcx, LockRegistry::default()));
+                let expected = marked_first_lines(QUESTION_MARK_SRC);
+                assert_eq!(expected.len(), 5);
+                assert_eq!(first_lines, expected);
+            }),
+        );
+    }
+
+    /// `Handle::drop` locks the count again when a `Handle` is dropped in the critical sections marked `// first`.
+    const DROP_IMPL_SRC: &str = r#"
+use std::sync::Mutex;
+static COUNT: Mutex<i32> = Mutex::new(0);
+pub struct Handle;
+impl Drop for Handle {
+    fn drop(&mut self) {
+        *COUNT.lock().unwrap() -= 1;
+    }
+}
+pub struct Owner {
+    _handle: Handle,
+}
+pub fn explicit_drop(h: Handle) {
+    let mut count = COUNT.lock().unwrap(); // first
+    *count += 1;
+    drop(h);
+}
+pub fn scope_end(h: Handle) {
+    let _count = COUNT.lock().unwrap(); // first
+    let _h = h;
+}
+pub fn field_drop(o: Owner) {
+    let _count = COUNT.lock().unwrap(); // first
+    drop(o);
+}
+pub fn replace(h: &mut Handle) {
+    let _count = COUNT.lock().unwrap(); // first
+    *h = Handle;
+}
+pub fn released_before_drop(h: Handle) {
+    *COUNT.lock().unwrap() += 1;
+    drop(h);
+}
+pub fn dropped_after_release(h: Handle) {
+    let count = COUNT.lock().unwrap();
+    drop(count);
+    drop(h);
+}
+"#;
+
+    #[test]
+    fn test_doublelock_in_drop_impl() {
+        let src = write_src("drop-impl", DROP_IMPL_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let first_lines =
+                    doublelock_first_lines(&detect_deadlock(tcx, LockRegistry::default()));
+                let expected = marked_first_lines(DROP_IMPL_SRC);
+                assert_eq!(expected.len(), 4);
+                assert_eq!(first_lines, expected);
+            }),
+        );
+    }
+
+    /// The closures passed to the combinators lock `hits` again in the fns marked `// first`.
+    const INLINE_CLOSURE_SRC: &str = r#"
+use std::sync::Mutex;
+pub struct Cache {
+    values: Vec<u64>,
+    hits: Mutex<u64>,
+}
+impl Cache {
+    pub fn get(&self, idx: usize) -> Option<u64> {
+        let _hits = self.hits.lock().unwrap(); // first
+        self.values.get(idx).map(|v| *v + *self.hits.lock().unwrap())
+    }
+    pub fn get_or_hits(&self, idx: usize) -> u64 {
+        let _hits = self.hits.lock().unwrap(); // first
+        self.values.get(idx).copied().unwrap_or_else(|| *self.hits.lock().unwrap())
+    }
+    pub fn count(&self) {
+        let _hits = self.hits.lock().unwrap(); // first
+        self.values.iter().for_each(|_| *self.hits.lock().unwrap() += 1);
+    }
+    pub fn released(&self, idx: usize) -> Option<u64> {
+        *self.hits.lock().unwrap() += 1;
+        self.values.get(idx).map(|v| *v + *self.hits.lock().unwrap())
+    }
+}
+"#;
+
+    #[test]
+    fn test_doublelock_in_inline_closures() {
+        let src = write_src("inline-closure", INLINE_CLOSURE_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                let first_lines = doublelock_first_lines(&reports);
+                let expected = marked_first_lines(INLINE_CLOSURE_SRC);
+                assert_eq!(expected.len(), 3);
+                assert_eq!(first_lines, expected);
+                // The closures are called directly at the callsites of the combinators.
+                for report in &reports {
+                    if let Report::DoubleLock(content) = report {
+                        assert!(content
+                            .diagnosis
+                            .callchains
+                            .iter()
+                            .any(|callchain| callchain.len() == 1 && callchain[0].len() == 1));
+                    }
+                }
+            }),
+        );
+    }
+
+    /// The queue is locked in the fns marked `// first` when `Drain::next` locks it again.
+    const ITERATOR_NEXT_SRC: &str = r#"
+use std::sync::Mutex;
+pub struct Queue {
+    items: Mutex<Vec<i32>>,
+}
+pub struct Drain<'a> {
+    queue: &'a Queue,
+}
+impl<'a> Iterator for Drain<'a> {
+    type Item = i32;
+    fn next(&mut self) -> Option<i32> {
+        self.queue.items.lock().unwrap().pop()
+    }
+}
+impl<'a> IntoIterator for &'a Queue {
+    type Item = i32;
+    type IntoIter = Drain<'a>;
+    fn into_iter(self) -> Drain<'a> {
+        Drain { queue: self }
+    }
+}
+impl Queue {
+    pub fn drain(&self) -> Drain<'_> {
+        Drain { queue: self }
+    }
+    pub fn sum_under_lock(&self) -> i32 {
+        let items = self.items.lock().unwrap(); // first
+        let mut sum = items.len() as i32;
+        for item in self.drain() {
+            sum += item;
+        }
+        sum
+    }
+    pub fn sum_into_iter_under_lock(&self) -> i32 {
+        let items = self.items.lock().unwrap(); // first
+        let mut sum = items.len() as i32;
+        for item in self {
+            sum += item;
+        }
+        sum
+    }
+    pub fn sum_after_unlock(&self) -> i32 {
+        let mut sum = self.items.lock().unwrap().len() as i32;
+        for item in self.drain() {
+            sum += item;
+        }
+        sum
+    }
+}
+"#;
+
+    #[test]
+    fn test_doublelock_in_iterator_next() {
+        let src = write_src("iterator-next", ITERATOR_NEXT_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let first_lines =
+                    doublelock_first_lines(&detect_deadlock(tcx, LockRegistry::default()));
+                let expected = marked_first_lines(ITERATOR_NEXT_SRC);
+                assert_eq!(expected.len(), 2);
+                assert_eq!(first_lines, expected);
+            }),
+        );
+    }
+
+    /// The plugins are called with the read guard of `Service::state`,
+    /// and `Recorder` looks up the service by id to write `state`, untracked by the alias analysis.
+    const DYN_PLUGIN_SRC: &str = r#"
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+pub trait Plugin: Send + Sync {
+    fn process(&self, state: &Vec<i32>);
+}
+pub struct Service {
+    state: RwLock<Vec<i32>>,
+    plugins: Vec<Box<dyn Plugin>>,
+}
+pub struct Audit {
+    state: RwLock<Vec<i32>>,
+}
+static SERVICES: OnceLock<Mutex<HashMap<u32, Arc<Service>>>> = OnceLock::new();
+pub struct Recorder {
+    id: u32,
+}
+impl Plugin for Recorder {
+    fn process(&self, state: &Vec<i32>) {
+        let service = SERVICES.get().unwrap().lock().unwrap().get(&self.id).cloned().unwrap();
+        service.state.write().unwrap().push(state.len() as i32);
+    }
+}
+pub struct Auditor {
+    audit: Arc<Audit>,
+}
+impl Plugin for Auditor {
+    fn process(&self, state: &Vec<i32>) {
+        self.audit.state.write().unwrap().extend(state);
+    }
+}
+impl Service {
+    pub fn new(audit: Arc<Audit>) -> Self {
+        let plugins: Vec<Box<dyn Plugin>> = vec![Box::new(Recorder { id: 1 }), Box::new(Auditor { audit })];
+        Self { state: RwLock::new(Vec::new()), plugins }
+    }
+    pub fn run(&self) {
+        for plugin in &self.plugins {
+            plugin.process(&self.state.read().unwrap()); // first
+        }
+    }
+}
+"#;
+
+    #[test]
+    fn test_doublelock_through_dyn_plugin_by_owner() {
+        let src = write_src("dyn-plugin", DYN_PLUGIN_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                // Not `Audit::state` of the same lock type.
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(DYN_PLUGIN_SRC)
+                );
+                assert_eq!(reports.len(), 1);
+                let Report::DoubleLock(content) = &reports[0] else {
+                    unreachable!()
+                };
+                assert_eq!(content.possibility, "Possibly");
+                assert_eq!(
+                    content.diagnosis.alias_confidence.as_deref(),
+                    Some(ALIAS_CONFIDENCE_SAME_OWNER)
+                );
+                assert!(content.explanation.ends_with("`Service` as the first lock"));
+            }),
+        );
+    }
+
+    /// The guards are carried out of the spinning loops by labeled breaks.
+    const SPIN_ACQUIRE_SRC: &str = r#"
+use std::sync::Mutex;
+pub fn spin_then_relock(m: &Mutex<i32>) {
+    let g = 'outer: loop { // first
+        if let Ok(g) = m.try_lock() {
+            break 'outer g;
+        }
+        std::hint::spin_loop();
+    };
+    let _g2 = m.lock().unwrap();
+    drop(g);
+}
+pub fn nested_spin_then_relock(m: &Mutex<i32>) {
+    let g = 'outer: loop { // first
+        for _ in 0..100 {
+            if let Ok(g) = m.try_lock() {
+                break 'outer g;
+            }
+        }
+        std::thread::yield_now();
+    };
+    let _g2 = m.lock().unwrap();
+    drop(g);
+}
+pub fn spin_then_unlock(m: &Mutex<i32>) {
+    let g = 'outer: loop {
+        if let Ok(g) = m.try_lock() {
+            break 'outer g;
+        }
+    };
+    drop(g);
+    let _g2 = m.lock().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_doublelock_after_labeled_break() {
+        let src = write_src("spin-acquire", SPIN_ACQUIRE_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                // Moving the held guard into `drop` is not another acquisition.
+                assert_eq!(reports.len(), 2);
+                let expected = marked_first_lines(SPIN_ACQUIRE_SRC);
+                assert_eq!(doublelock_first_lines(&reports), expected);
+            }),
+        );
+    }
+
+    #[test]
+    fn test_deterministic_reports() {
+        let src = write_src("deterministic", QUESTION_MARK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let json = || {
+                    let mut reports = detect_deadlock(tcx, LockRegistry::default());
+                    sort_reports(&mut reports);
+                    serde_json::to_string_pretty(&reports).unwrap()
+                };
+                let first = json();
+                assert!(first.contains("DoubleLock"));
+                assert_eq!(first, json());
+            }),
+        );
+    }
+
+    /// The fingerprints and the primary spans of the deadlock reports on `src`.
+    fn fingerprints(test_name: &str, src: &str) -> Vec<(String, String)> {
+        let src = write_src(test_name, src);
+        let fingerprints = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let result = fingerprints.clone();
+        with_tcx(
+            &src,
+            &[],
+            Box::new(move |tcx| {
+                let mut reports = detect_deadlock(tcx, LockRegistry::default());
+                crate::baseline::assign_fingerprints(tcx, &mut reports);
+                *result.lock().unwrap() = reports
+                    .iter()
+                    .map(|report| (report.fingerprint().to_owned(), report.spans().0.to_owned()))
+                    .collect::<Vec<_>>();
+            }),
+        );
+        let mut fingerprints = std::mem::take(&mut *fingerprints.lock().unwrap());
+        fingerprints.sort();
+        fingerprints
+    }
+
+    #[test]
+    fn test_fingerprints_stable_when_fn_moves() {
+        let original = fingerprints("fingerprint-original", QUESTION_MARK_SRC);
+        assert!(!original.is_empty());
+        // Edit a fn without reports and move `option` down 10 lines.
+        let (before, after) = QUESTION_MARK_SRC.split_once("pub fn option").unwrap();
+        let moved_src = format!(
+            "{}{}pub fn option{}",
+            before.replace(
+                "fn helper_opt() -> Option<i32> { Some(1) }",
+                "fn helper_opt() -> Option<i32> {\n    Some(1)\n}"
+            ),
+            "// padding\n".repeat(10),
+            after
+        );
+        let moved = fingerprints("fingerprint-moved", &moved_src);
+        let split = |fingerprints: Vec<(String, String)>| -> (Vec<String>, Vec<String>) {
+            fingerprints.into_iter().unzip()
+        };
+        let (original, original_spans) = split(original);
+        let (moved, moved_spans) = split(moved);
+        assert_eq!(original, moved);
+        // The reports in different fns are told apart,
+        // while the ones differing only in the columns share the fingerprint.
+        assert_eq!(original.iter().collect::<FxHashSet<_>>().len(), 5);
+        assert!(moved_spans
+            .iter()
+            .all(|span| !original_spans.contains(span)));
+    }
+
+    #[test]
+    fn test_report_envelope_round_trip() {
+        let src = write_src("envelope-round-trip", QUESTION_MARK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let mut reports = detect_deadlock(tcx, LockRegistry::default());
+                sort_reports(&mut reports);
+                let envelope = ReportEnvelope::new("question_mark".to_owned(), reports);
+                let json = serde_json::to_string_pretty(&envelope).unwrap();
+                let parsed: lockbud_report_types::ReportEnvelope =
+                    serde_json::from_str(&json).unwrap();
+                assert_eq!(parsed.summary, envelope.summary);
+                assert_eq!(
+                    parsed.summary.double_lock.probably + parsed.summary.double_lock.possibly,
+                    parsed.reports.len()
+                );
+                assert_eq!(serde_json::to_string_pretty(&parsed).unwrap(), json);
+            }),
+        );
+    }
+
+    /// toys/conflict with a doublelock on each lock.
+    const GROUP_BY_LOCK_SRC: &str = r#"
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+pub struct MyStruct {
+    mu: Mutex<bool>,
+    rw1: RwLock<i32>,
+    rw2: RwLock<u8>,
+}
+
+impl MyStruct {
+    pub fn new() -> Self {
+        Self {
+            mu: Mutex::new(true),
+            rw1: RwLock::new(1),
+            rw2: RwLock::new(1),
+        }
+    }
+
+    pub fn mu_rw1(&self) -> i32 {
+        let mu = self.mu.lock().unwrap();
+        let ret = match *mu {
+            true => *self.rw1.read().unwrap(),
+            false => 0,
+        };
+        ret
+    }
+
+    pub fn rw1_rw2(&self) -> u8 {
+        let mut rw1 = self.rw1.write().unwrap();
+        *rw1 += 1;
+        let ret = *self.rw2.read().unwrap();
+        ret
+    }
+
+    pub fn rw2_mu(&self) -> bool {
+        let mut rw2 = self.rw2.write().unwrap();
+        *rw2 += 1;
+        let ret = *self.mu.lock().unwrap();
+        ret
+    }
+
+    pub fn mu_mu(&self) {
+        let _a = self.mu.lock().unwrap();
+        let _b = self.mu.lock().unwrap();
+    }
+
+    pub fn rw1_rw1(&self) {
+        let _a = self.rw1.write().unwrap();
+        let _b = self.rw1.read().unwrap();
+    }
+
+    pub fn rw2_rw2(&self) {
+        let _a = self.rw2.read().unwrap();
+        let _b = self.rw2.write().unwrap();
+    }
+}
+
+pub fn run() {
+    let my_struct = Arc::new(MyStruct::new());
+    let clone1 = Arc::clone(&my_struct);
+    let clone2 = Arc::clone(&my_struct);
+    let th1 = thread::spawn(move || {
+        clone1.mu_rw1();
+    });
+    let th2 = thread::spawn(move || {
+        clone2.rw1_rw2();
+    });
+    my_struct.rw2_mu();
+    th1.join().unwrap();
+    th2.join().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_deadlock_confidence() {
+        let evidence = |alias, provenance, type_possibility| DeadlockEvidence {
+            alias,
+            provenance,
+            type_possibility,
+        };
+        let same_local = evidence(
+            ApproximateAliasKind::Probably,
+            AliasProvenance::SameLocal,
+            DeadlockPossibility::Probably,
+        );
+        assert_eq!(same_local.confidence(true), 100);
+        assert_eq!(same_local.confidence(false), 85);
+        let unknown = evidence(
+            ApproximateAliasKind::Unknown,
+            AliasProvenance::Unknown,
+            DeadlockPossibility::Possibly,
+        );
+        assert_eq!(unknown.confidence(false), 10);
+        // A write-write of the locks aliasing in the same fn on a callchain is more confident
+        // than a read-write of the locks only assumed to alias by their param types.
+        let intraproc = evidence(
+            ApproximateAliasKind::Probably,
+            AliasProvenance::PointsToIntersection,
+            DeadlockPossibility::Probably,
+        );
+        let same_type_param = evidence(
+            ApproximateAliasKind::Possibly,
+            AliasProvenance::ParamTypeHeuristic {
+                different_fields: false,
+            },
+            DeadlockPossibility::Possibly,
+        );
+        assert_eq!(intraproc.confidence(true), 95);
+        assert_eq!(same_type_param.confidence(true), 50);
+        let finding = |line: usize, confidence: u8| {
+            let span = format!("src/lib.rs:{}:5: {}:9 (#0)", line, line);
+            let diagnosis = DeadlockDiagnosis::new(
+                "StdMutexGuard(i32)".to_owned(),
+                span.clone(),
+                "StdMutexGuard(i32)".to_owned(),
+                span,
+                Vec::new(),
+            );
+            let mut content = ReportContent::new(
+                "DoubleLock".to_owned(),
+                "Possibly".to_owned(),
+                diagnosis,
+                String::new(),
+            );
+            content.confidence = Some(confidence);
+            Report::DoubleLock(content)
+        };
+        let mut reports = vec![
+            finding(1, same_type_param.confidence(true)),
+            finding(2, intraproc.confidence(true)),
+        ];
+        sort_reports(&mut reports);
+        lockbud_report_types::truncate_reports(&mut reports, 1);
+        assert_eq!(reports[0].confidence(), Some(95));
+    }
+
+    #[test]
+    fn test_group_by_lock() {
+        let src = write_src("group-by-lock", GROUP_BY_LOCK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let (mut reports, classes) = run_deadlock_detector(tcx, LockRegistry::default());
+                sort_reports(&mut reports);
+                // The doublelocks in the same fns are more confident than the conflictlock across the fns.
+                assert_eq!(
+                    reports.iter().map(Report::confidence).collect::<Vec<_>>(),
+                    vec![Some(95), Some(95), Some(95), Some(60)]
+                );
+                let names = classes
+                    .iter()
+                    .map(|class| class.name.as_str())
+                    .collect::<Vec<_>>();
+                assert_eq!(names, vec!["MyStruct.mu", "MyStruct.rw1", "MyStruct.rw2"]);
+                assert!(classes.iter().all(|class| class.acquisitions >= 3));
+                let groups = crate::detector::report::group_by_lock(&reports, &classes);
+                assert_eq!(groups.len(), 3);
+                assert_eq!(
+                    groups
+                        .iter()
+                        .map(|group| group.reports.len())
+                        .sum::<usize>(),
+                    reports.len()
+                );
+                // Each lock owns its doublelock, and the conflictlock is owned by its first lock.
+                let kinds = |group: &lockbud_report_types::LockGroup| {
+                    group
+                        .reports
+                        .iter()
+                        .map(|idx| reports[*idx].level_keys()[0])
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(kinds(&groups[0]), vec!["double_lock", "conflict_lock"]);
+                assert_eq!(kinds(&groups[1]), vec!["double_lock"]);
+                assert_eq!(kinds(&groups[2]), vec!["double_lock"]);
+                // Each lock in the reports refers to its class: a doublelock to one class,
+                // and the conflictlock to the three classes in its cycle.
+                let lock_classes = |idx: usize| {
+                    let mut lock_classes = reports[idx].lock_classes();
+                    lock_classes.sort();
+                    lock_classes.dedup();
+                    lock_classes
+                };
+                assert_eq!(lock_classes(groups[1].reports[0]), vec![1]);
+                assert_eq!(lock_classes(groups[0].reports[1]), vec![0, 1, 2]);
+                assert_eq!(
+                    crate::detector::report::referenced_locks(&reports, &classes),
+                    classes
+                );
+            }),
+        );
+    }
+
+    #[test]
+    fn test_lock_graph() {
+        let src = write_src("lock-graph", GROUP_BY_LOCK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let (_, detector) = run_deadlock_detector_on(
+                    tcx,
+                    test_instances(tcx),
+                    LockRegistry::default(),
+                    |detector, _| detector.set_emit_lock_graph(true),
+                );
+                let lock_graph = detector.lock_graph.unwrap();
+                let names = lock_graph
+                    .locks
+                    .iter()
+                    .map(|lock| lock.name.as_str())
+                    .collect::<Vec<_>>();
+                assert_eq!(names, vec!["MyStruct.mu", "MyStruct.rw1", "MyStruct.rw2"]);
+                // mu -> rw1 -> rw2 -> mu
+                let edges = lock_graph
+                    .edges
+                    .iter()
+                    .map(|edge| (edge.from, edge.to))
+                    .collect::<Vec<_>>();
+                assert_eq!(edges, vec![(0, 1), (1, 2), (2, 0)]);
+                assert_eq!(lock_graph.cycles, vec![vec![0, 1, 2]]);
+                let dot = lock_graph.dot();
+                assert_eq!(dot.matches(" -> ").count(), 3 * 2, "{}", dot);
+                // Off by default.
+                let (_, detector) = run_deadlock_detector_on(
+                    tcx,
+                    test_instances(tcx),
+                    LockRegistry::default(),
+                    |_, _| {},
+                );
+                assert!(detector.lock_graph.is_none());
+            }),
+        );
+    }
+
+    /// toys/suppression without main.
+    const SUPPRESSION_SRC: &str = r#"
+use std::sync::Mutex;
+
+/// Reported: no suppression.
+pub fn reported(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap(); // first
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the attribute on the fn, a no-op without lockbud.
+#[cfg_attr(lockbud, allow(lockbud::double_lock))]
+pub fn allowed_fn(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the family `deadlock` allowed on the enclosing module.
+#[cfg_attr(lockbud, allow(lockbud::deadlock))]
+mod wrapper {
+    use std::sync::Mutex;
+
+    pub fn allowed_in_mod(m: &Mutex<i32>) {
+        let _a = m.lock().unwrap();
+        let _b = m.lock().unwrap();
+    }
+}
+
+/// Reported: the attribute allows another kind.
+#[cfg_attr(lockbud, allow(lockbud::conflict_lock))]
+pub fn allowed_other_kind(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap(); // first
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the comment in the macro.
+macro_rules! lock_twice {
+    ($m:expr) => {{
+        let _a = $m.lock().unwrap(); // lockbud:ignore(double_lock)
+        let _b = $m.lock().unwrap();
+    }};
+}
+
+/// Suppressed by the comment on the lock line, where an attribute can't be placed.
+pub fn ignored_on_line(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap(); // lockbud:ignore(double_lock)
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the comment alone on the line above the lock.
+pub fn ignored_above(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    // lockbud:ignore(double_lock): guarded by a timeout in the test
+    let _b = m.lock().unwrap();
+}
+
+/// Reported: the comment ignores another kind.
+pub fn ignored_other_kind(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap(); // lockbud:ignore(use_after_free) // first
+    let _b = m.lock().unwrap();
+}
+
+pub fn lock_twice_in_macro(m: &Mutex<i32>) {
+    lock_twice!(m);
+}
+"#;
+
+    #[test]
+    fn test_suppression_in_source() {
+        let src = write_src("suppression", SUPPRESSION_SRC);
+        let args = crate::suppression::RUSTC_ARGS.map(str::to_owned);
+        with_tcx(
+            &src,
+            &args,
+            Box::new(|tcx| {
+                let mut reports = detect_deadlock(tcx, LockRegistry::default());
+                assert_eq!(reports.len(), 8);
+                let suppressed = crate::suppression::apply_suppressions(tcx, &mut reports);
+                assert_eq!(suppressed, 5);
+                let expected = marked_first_lines(SUPPRESSION_SRC);
+                assert_eq!(expected.len(), 3);
+                assert_eq!(doublelock_first_lines(&reports), expected);
+            }),
+        );
+    }
+
+    const MONOMORPHIZATION_SRC: &str = r#"
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+
+pub fn with_lock<T>(m: &Mutex<T>) {
+    let _a = m.lock().unwrap();
+    let _b = m.lock().unwrap();
+}
+
+pub trait Acquire {
+    type Guard<'a>
+    where
+        Self: 'a;
+    fn acquire(&self) -> Self::Guard<'_>;
+}
+
+impl Acquire for Mutex<i32> {
+    type Guard<'a> = MutexGuard<'a, i32>;
+    fn acquire(&self) -> MutexGuard<'_, i32> {
+        self.lock().unwrap()
+    }
+}
+
+impl Acquire for RwLock<i32> {
+    type Guard<'a> = RwLockReadGuard<'a, i32>;
+    fn acquire(&self) -> RwLockReadGuard<'_, i32> {
+        self.read().unwrap()
+    }
+}
+
+pub fn acquire_twice<L: Acquire>(l: &L) {
+    let _a = l.acquire();
+    let _b = l.acquire();
+}
+
+pub fn run() {
+    with_lock(&Mutex::new(1i32));
+    with_lock(&Mutex::new(String::new()));
+    with_lock(&Mutex::new(1u8));
+    acquire_twice(&Mutex::new(1));
+    acquire_twice(&RwLock::new(1));
+}
+"#;
+
+    #[test]
+    fn test_dedup_monomorphizations() {
+        let src = write_src("monomorphization", MONOMORPHIZATION_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let mut reports = detect_deadlock(tcx, LockRegistry::default())
+                    .into_iter()
+                    .map(|report| match report {
+                        Report::DoubleLock(content) => (
+                            content
+                                .diagnosis
+                                .first_lock_span
+                                .split(':')
+                                .nth(1)
+                                .unwrap()
+                                .parse::<usize>()
+                                .unwrap(),
+                            content.possibility,
+                            content.diagnosis.instantiations,
+                        ),
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                reports.sort();
+                // `with_lock` is reported once for the three instantiations,
+                // while the Mutex and RwLock instantiations of `acquire_twice` differ in possibility.
+                assert_eq!(
+                    reports,
+                    vec![
+                        (5, "Probably".to_owned(), 3),
+                        (31, "Possibly".to_owned(), 1),
+                        (31, "Probably".to_owned(), 1),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const CONDVAR_MULTIPLE_MUTEXES_SRC: &str = r#"use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+pub struct Shared {
+    ready: Mutex<bool>,
+    done: Mutex<bool>,
+    cvar: Condvar,
+}
+
+impl Shared {
+    pub fn wait_ready(&self) {
+        let mut ready = self.ready.lock().unwrap();
+        while !*ready {
+            ready = self.cvar.wait(ready).unwrap();
+        }
+    }
+
+    pub fn wait_done(&self) {
+        let mut done = self.done.lock().unwrap();
+        while !*done {
+            done = self.cvar.wait(done).unwrap();
+        }
+    }
+}
+
+pub struct Pair {
+    lock: Mutex<bool>,
+    cvar: Condvar,
+}
+
+impl Pair {
+    pub fn wait(&self) {
+        let mut started = self.lock.lock().unwrap();
+        while !*started {
+            started = self.cvar.wait(started).unwrap();
+        }
+    }
+
+    pub fn wait_again(&self) {
+        let started = self.lock.lock().unwrap();
+        let _started = self.cvar.wait_while(started, |started| !*started).unwrap();
+    }
+}
+
+pub fn run(shared: Arc<Shared>, pair: Arc<Pair>) {
+    let shared1 = shared.clone();
+    let th = thread::spawn(move || shared1.wait_ready());
+    shared.wait_done();
+    pair.wait();
+    pair.wait_again();
+    th.join().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_condvar_multiple_mutexes() {
+        let src = write_src("condvar-multiple-mutexes", CONDVAR_MULTIPLE_MUTEXES_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default())
+                    .into_iter()
+                    .filter_map(|report| match report {
+                        Report::CondvarMultipleMutexes(content) => Some(content.diagnosis),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                // `Pair` waits its only mutex twice, which is not reported.
+                assert_eq!(reports.len(), 1);
+                assert_eq!(reports[0].condvar_wait_type, "std::sync::Condvar::wait");
+                let mutexes = reports[0]
+                    .mutexes
+                    .iter()
+                    .map(|mutex| (mutex.mutex.as_str(), mutex.wait_spans.len()))
+                    .collect::<Vec<_>>();
+                assert_eq!(mutexes, vec![("Shared.ready", 1), ("Shared.done", 1)]);
+            }),
+        );
+    }
+
+    const CONDVAR_DEADLOCK_SRC: &str = r#"use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+pub fn deadlock_wait() {
+    let mu1 = Arc::new(Mutex::new(1));
+    let mu2 = mu1.clone();
+    let pair1 = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = pair1.clone();
+    let th1 = thread::spawn(move || {
+        let _i = mu1.lock().unwrap();
+        let (lock, cvar) = &*pair1;
+        let mut started = lock.lock().unwrap();
+        while !*started {
+            started = cvar.wait(started).unwrap();
+        }
+    });
+    let th2 = thread::spawn(move || {
+        let _i = mu2.lock().unwrap();
+        let (lock, cvar) = &*pair2;
+        let mut started = lock.lock().unwrap();
+        *started = true;
+        cvar.notify_one();
+    });
+    th1.join().unwrap();
+    th2.join().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_condvar_deadlock() {
+        let src = write_src("condvar-deadlock", CONDVAR_DEADLOCK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // The `&Condvar`s bound by the patterns are copied into `wait` and `notify_one`.
+                let reports = detect_deadlock(tcx, LockRegistry::default())
+                    .into_iter()
+                    .filter_map(|report| match report {
+                        Report::CondvarDeadlock(content) => Some(content.diagnosis),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(reports.len(), 1);
+                assert_eq!(reports[0].condvar_wait_type, "std::sync::Condvar::wait");
+                assert!(reports[0]
+                    .condvar_wait_callsite_span
+                    .contains("lib.rs:14:23"));
+            }),
+        );
+    }
+
+    const BUILDER_CATCH_UNWIND_SRC: &str = r#"use std::panic;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub fn builder_conflict() {
+    let lock_a1 = Arc::new(Mutex::new(1));
+    let lock_a2 = lock_a1.clone();
+    let lock_b1 = Arc::new(Mutex::new(true));
+    let lock_b2 = lock_b1.clone();
+    {
+        let _b = lock_b1.lock().unwrap();
+        let _a = lock_a1.lock().unwrap();
+    }
+    let th = thread::Builder::new()
+        .name("worker".to_owned())
+        .spawn(move || {
+            let _a = lock_a2.lock().unwrap();
+            let _b = lock_b2.lock().unwrap();
+        })
+        .unwrap();
+    th.join().unwrap();
+}
+
+pub fn catch_unwind_holding_lock() {
+    let lock_a = Mutex::new(1);
+    let _a = lock_a.lock().unwrap(); // first
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let _a = lock_a.lock().unwrap();
+    }));
+}
+
+pub fn catch_unwind_after_unlock() {
+    let lock_a = Mutex::new(1);
+    {
+        let _a = lock_a.lock().unwrap();
+    }
+    let _ = panic::catch_unwind(|| {
+        let _a = lock_a.lock().unwrap();
+    });
+}
+
+pub fn scoped_builder_conflict() {
+    let lock_a = Mutex::new(1);
+    let lock_b = Mutex::new(true);
+    thread::scope(|s| {
+        thread::Builder::new()
+            .spawn_scoped(s, || {
+                let _b = lock_b.lock().unwrap();
+                let _a = lock_a.lock().unwrap();
+            })
+            .unwrap();
+        thread::Builder::new()
+            .spawn_scoped(s, || {
+                let _a = lock_a.lock().unwrap();
+                let _b = lock_b.lock().unwrap();
+            })
+            .unwrap();
+    });
+}
+
+pub fn main() {
+    builder_conflict();
+    catch_unwind_holding_lock();
+    catch_unwind_after_unlock();
+    scoped_builder_conflict();
+}
+"#;
+
+    #[test]
+    fn test_builder_spawn_and_catch_unwind() {
+        let src = write_src("builder-catch-unwind", BUILDER_CATCH_UNWIND_SRC);
+        // Built as a bin, so the fns called by `main` run in the main thread.
+        with_tcx(
+            &src,
+            &["--crate-type=bin".to_owned()],
+            Box::new(|tcx| {
+                let (doublelocks, conflictlocks): (Vec<_>, Vec<_>) =
+                    detect_deadlock(tcx, LockRegistry::default())
+                        .into_iter()
+                        .partition(|report| matches!(report, Report::DoubleLock(_)));
+                // The closure of `catch_unwind` runs with the lockguards live at the callsite.
+                assert_eq!(
+                    doublelock_first_lines(&doublelocks),
+                    marked_first_lines(BUILDER_CATCH_UNWIND_SRC)
+                );
+                // The closure spawned by `Builder::spawn` runs in the thread spawned at line 14,
+                // and those spawned by `Builder::spawn_scoped` in the threads spawned at lines 46 and 52.
+                let mut roots = conflictlocks
+                    .iter()
+                    .map(|report| {
+                        let mut roots = match report {
+                            Report::ConflictLock(content) => content
+                                .diagnosis
+                                .iter()
+                                .flat_map(|diagnosis| {
+                                    diagnosis.thread_roots.clone().unwrap_or_default()
+                                })
+                                .map(|root| match root.split(':').nth(1) {
+                                    Some(line) => line.to_owned(),
+                                    None => root,
+                                })
+                                .collect::<Vec<_>>(),
+                            report => panic!("unexpected report {:?}", report),
+                        };
+                        roots.sort();
+                        roots.dedup();
+                        roots
+                    })
+                    .collect::<Vec<_>>();
+                roots.sort();
+                assert_eq!(roots, [["14", "main"], ["46", "52"]]);
+            }),
+        );
+    }
+
+    const SCOPED_SPAWN_SRC: &str = r#"use std::sync::Mutex;
+use std::thread;
+
+pub struct Accounts {
+    a: Mutex<u64>,
+    b: Mutex<u64>,
+}
+
+pub fn transfer(accounts: &Accounts) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            let _b = accounts.b.lock().unwrap();
+            let _a = accounts.a.lock().unwrap();
+        });
+        let _a = accounts.a.lock().unwrap();
+        let _b = accounts.b.lock().unwrap();
+    });
+}
+
+pub fn hold_and_spawn(accounts: &Accounts) {
+    let _a = accounts.a.lock().unwrap();
+    spawn_child(accounts);
+}
+
+fn spawn_child(accounts: &Accounts) {
+    thread::scope(|s| {
+        s.spawn(|| *accounts.a.lock().unwrap() += 1);
+    });
+}
+"#;
+
+    #[test]
+    fn test_scoped_spawn() {
+        let src = write_src("scoped-spawn", SCOPED_SPAWN_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                // The closure spawned by `Scope::spawn` runs in the thread spawned at line 11,
+                // without the lockguards of `hold_and_spawn`.
+                assert_eq!(reports.len(), 1);
+                let roots = match &reports[0] {
+                    Report::ConflictLock(content) => content
+                        .diagnosis
+                        .iter()
+                        .flat_map(|diagnosis| diagnosis.thread_roots.clone().unwrap_or_default())
+                        .collect::<Vec<_>>(),
+                    report => panic!("unexpected report {:?}", report),
+                };
+                assert!(roots.iter().any(|root| root.contains("lib.rs:11:")));
+            }),
+        );
+    }
+
+    const RAYON_SRC: &str = r#"
+use std::sync::Mutex;
+pub mod rayon {
+    pub fn join<A: FnOnce() -> RA, B: FnOnce() -> RB, RA, RB>(a: A, b: B) -> (RA, RB) {
+        (a(), b())
+    }
+    pub fn scope<OP: FnOnce(&()) -> R, R>(op: OP) -> R {
+        op(&())
+    }
+    pub mod iter {
+        pub trait ParallelIterator: Iterator + Sized {
+            fn for_each<OP: Fn(Self::Item)>(self, op: OP) {
+                Iterator::for_each(self, op)
+            }
+        }
+        impl<I: Iterator> ParallelIterator for I {}
+    }
+}
+use rayon::iter::ParallelIterator;
+pub fn add_all(total: &Mutex<u64>, items: &[u64]) {
+    let _total = total.lock().unwrap();
+    ParallelIterator::for_each(items.iter(), |item| {
+        *total.lock().unwrap() += item;
+    });
+}
+pub fn add_pair(total: &Mutex<u64>, a: u64, b: u64) {
+    let _total = total.lock().unwrap();
+    rayon::join(
+        || *total.lock().unwrap() += a,
+        || *total.lock().unwrap() += b,
+    );
+}
+pub fn add_scoped(total: &Mutex<u64>) {
+    let _total = total.lock().unwrap();
+    rayon::scope(|_| *total.lock().unwrap() += 1);
+}
+pub fn add_released(total: &Mutex<u64>, items: &[u64]) {
+    let sum = *total.lock().unwrap();
+    ParallelIterator::for_each(items.iter(), |item| {
+        *total.lock().unwrap() += item + sum;
+    });
+}
+"#;
+
+    #[test]
+    fn test_rayon_deadlock() {
+        let src = write_src("rayon-deadlock", RAYON_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut relocks = detect_deadlock(tcx, LockRegistry::default())
+                    .iter()
+                    .map(|report| match report {
+                        Report::RayonDeadlock(content) => (
+                            line(&content.diagnosis.outer_lock_span),
+                            content.diagnosis.rayon_api.clone(),
+                            line(&content.diagnosis.inner_lock_span),
+                        ),
+                        // The closures never run with the caller's lockguards.
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                relocks.sort_unstable();
+                assert_eq!(
+                    relocks,
+                    [
+                        (21, "rayon::iter::ParallelIterator::for_each".to_owned(), 23),
+                        (27, "rayon::join".to_owned(), 29),
+                        (27, "rayon::join".to_owned(), 30),
+                        (34, "rayon::scope".to_owned(), 35),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const BLOCK_ON_SRC: &str = r#"
+use std::sync::Mutex;
+pub mod futures {
+    pub mod executor {
+        pub fn block_on<F: std::future::Future>(_f: F) -> F::Output {
+            unimplemented!()
+        }
+    }
+}
+pub fn holding(m: &Mutex<i32>) {
+    let _g = m.lock().unwrap();
+    futures::executor::block_on(async {});
+}
+pub fn released(m: &Mutex<i32>) {
+    *m.lock().unwrap() += 1;
+    futures::executor::block_on(async {});
+}
+fn flush() {
+    futures::executor::block_on(async {});
+}
+pub fn holding_caller(m: &Mutex<i32>) {
+    let _g = m.lock().unwrap();
+    flush();
+}
+"#;
+
+    #[test]
+    fn test_block_on_holding_lock() {
+        let src = write_src("block-on-holding-lock", BLOCK_ON_SRC);
+        with_tcx(
+            &src,
+            &["--edition=2021".to_owned()],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut blocks = detect_deadlock(tcx, LockRegistry::default())
+                    .iter()
+                    .map(|report| match report {
+                        Report::BlockOnHoldingLock(content) => (
+                            line(&content.diagnosis.lock_span),
+                            line(&content.diagnosis.block_on_span),
+                            content.diagnosis.fn_name.clone(),
+                        ),
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                blocks.sort_unstable();
+                assert_eq!(
+                    blocks,
+                    [(11, 12, "holding".to_owned()), (22, 19, "flush".to_owned())]
+                );
+            }),
+        );
+    }
+
+    const RENDEZVOUS_SRC: &str = r#"
+use std::sync::mpsc;
+use std::sync::{Barrier, Mutex};
+use std::thread;
+pub fn barrier(m: &Mutex<i32>) {
+    let barrier = Barrier::new(2);
+    thread::scope(|s| {
+        s.spawn(|| {
+            *m.lock().unwrap() += 1;
+            barrier.wait();
+        });
+        let _g = m.lock().unwrap();
+        barrier.wait();
+    });
+}
+pub fn rendezvous(m: &Mutex<i32>) {
+    let (tx, rx) = mpsc::sync_channel(0);
+    thread::scope(|s| {
+        s.spawn(move || {
+            let _g = m.lock().unwrap();
+            rx.recv().unwrap();
+        });
+        let _g = m.lock().unwrap();
+        tx.send(1).unwrap();
+    });
+}
+pub fn buffered(m: &Mutex<u8>) {
+    let (tx, rx) = mpsc::sync_channel(1);
+    thread::scope(|s| {
+        s.spawn(move || {
+            let _g = m.lock().unwrap();
+            rx.recv().unwrap();
+        });
+        let _g = m.lock().unwrap();
+        tx.send(1u8).unwrap();
+    });
+}
+"#;
+
+    #[test]
+    fn test_rendezvous_deadlock() {
+        let src = write_src("rendezvous-deadlock", RENDEZVOUS_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut rendezvous = detect_deadlock(tcx, LockRegistry::default())
+                    .iter()
+                    .map(|report| match report {
+                        Report::RendezvousDeadlock(content) => (
+                            line(&content.diagnosis.lock_span),
+                            content.diagnosis.rendezvous_api.clone(),
+                            line(&content.diagnosis.peer_lock_span),
+                        ),
+                        // The buffered channel never blocks the sender on the receiver.
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                rendezvous.sort_unstable();
+                assert_eq!(
+                    rendezvous,
+                    [
+                        (12, "std::sync::Barrier::wait".to_owned(), 9),
+                        (23, "std::sync::mpsc::SyncSender::send".to_owned(), 20),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const ONCE_SRC: &str = r#"
+use std::sync::{Mutex, Once, OnceLock};
+pub mod lazy {
+    pub struct Lazy<T>(pub Option<T>);
+    impl<T> Lazy<T> {
+        pub fn get<F: FnOnce() -> T>(&'static self, f: F) -> &T {
+            let _ = f();
+            self.0.as_ref().unwrap()
+        }
+    }
+}
+static CONFIG: OnceLock<u32> = OnceLock::new();
+pub fn config() -> u32 {
+    *CONFIG.get_or_init(|| config() + 1)
+}
+static FIRST: Once = Once::new();
+static SECOND: Once = Once::new();
+pub fn first() {
+    FIRST.call_once(|| SECOND.call_once(|| {}));
+}
+pub struct Table;
+impl std::ops::Deref for Table {
+    type Target = Vec<u32>;
+    fn deref(&self) -> &Vec<u32> {
+        fn __static_ref_initialize() -> Vec<u32> {
+            Table.to_vec()
+        }
+        static LAZY: lazy::Lazy<Vec<u32>> = lazy::Lazy(None);
+        LAZY.get(__static_ref_initialize)
+    }
+}
+static REGISTRY: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static SIZE: OnceLock<usize> = OnceLock::new();
+pub fn register(id: usize) {
+    let mut registry = REGISTRY.lock().unwrap();
+    SIZE.get_or_init(|| REGISTRY.lock().unwrap().len());
+    registry.push(id);
+}
+"#;
+
+    #[test]
+    fn test_once_deadlock() {
+        let src = write_src("once-deadlock", ONCE_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut reentrancies = Vec::new();
+                let mut init_deadlocks = Vec::new();
+                for report in detect_deadlock(tcx, LockRegistry::default()) {
+                    match report {
+                        Report::OnceReentrancy(content) => reentrancies.push((
+                            line(&content.diagnosis.outer_span),
+                            content.diagnosis.once_api,
+                            line(&content.diagnosis.inner_span),
+                        )),
+                        Report::OnceInitDeadlock(content) => init_deadlocks.push((
+                            line(&content.diagnosis.outer_lock_span),
+                            line(&content.diagnosis.init_span),
+                            line(&content.diagnosis.inner_lock_span),
+                        )),
+                        // The initializer of `SIZE` never runs with the caller's lockguards.
+                        report => panic!("unexpected report {:?}", report),
+                    }
+                }
+                reentrancies.sort_unstable();
+                // `SECOND` is initialized by the initializer of `FIRST` rather than `FIRST` itself.
+                assert_eq!(
+                    reentrancies,
+                    [
+                        (14, "std::sync::OnceLock::get_or_init".to_owned(), 14),
+                        (29, "lazy::Lazy::get".to_owned(), 29),
+                    ]
+                );
+                assert_eq!(init_deadlocks, [(35, 36, 36)]);
+            }),
+        );
+    }
+
+    const PANIC_HOLDING_LOCK_SRC: &str = r#"use std::sync::{Mutex, RwLock};
+
+pub fn get(values: &Mutex<Vec<u32>>, i: usize) -> u32 {
+    let values = values.lock().unwrap();
+    values[i]
+}
+
+pub fn bump(values: &Mutex<[u32; 4]>, i: usize) {
+    let mut values = values.lock().unwrap();
+    bump_at(&mut values, i);
+}
+
+fn bump_at(values: &mut [u32; 4], i: usize) {
+    values[i] += 1;
+}
+
+pub fn first(values: &RwLock<Vec<u32>>) -> u32 {
+    let values = values.read().unwrap();
+    *values.first().unwrap()
+}
+
+pub fn len(values: &Mutex<Vec<u32>>, other: &Mutex<u32>) -> usize {
+    let values = values.lock().unwrap();
+    let _other = other.lock().unwrap();
+    values.len()
+}
+"#;
+
+    #[test]
+    fn test_panic_holding_lock() {
+        let src = write_src("panic-holding-lock", PANIC_HOLDING_LOCK_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let (reports, _) =
+                    run_deadlock_detector_with(tcx, LockRegistry::default(), |detector, _| {
+                        detector.config = DetectorConfig::new(&["panic"]);
+                    });
+                let mut panics = reports
+                    .into_iter()
+                    .map(|report| match report {
+                        Report::PanicHoldingLock(content) => (
+                            line(&content.diagnosis.guard_span),
+                            content.diagnosis.panic_api,
+                            line(&content.diagnosis.panic_span),
+                            content.diagnosis.callchains.len(),
+                        ),
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                panics.sort_unstable();
+                // The read guard does not poison the `RwLock`, nor does `lock().unwrap()` panic by itself.
+                assert_eq!(
+                    panics,
+                    [
+                        (4, "std::ops::Index::index".to_owned(), 5, 0),
+                        (9, "index out of bounds".to_owned(), 14, 1),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const PANIC_RETRY_SRC: &str = r#"use std::panic;
+use std::sync::{Mutex, PoisonError};
+
+pub fn transfer(accounts: &Mutex<i32>, journal: &Mutex<i32>) {
+    let _a = accounts.lock().unwrap();
+    let _j = journal.lock().unwrap();
+}
+
+pub fn transfer_with_retry(accounts: &Mutex<i32>, journal: &Mutex<i32>) {
+    if panic::catch_unwind(|| transfer(accounts, journal)).is_ok() {
+        return;
+    }
+    let _ = panic::catch_unwind(|| {
+        let _j = journal.lock().unwrap_or_else(PoisonError::into_inner);
+        let _a = accounts.lock().unwrap_or_else(PoisonError::into_inner);
+    });
+}
+"#;
+
+    #[test]
+    fn test_panic_retry_inversion() {
+        let src = write_src("panic-retry", PANIC_RETRY_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                assert_eq!(reports.len(), 1);
+                let content = match &reports[0] {
+                    Report::ConflictLock(content) => content,
+                    report => panic!("unexpected report {:?}", report),
+                };
+                // Only the relation retried in the closure of `catch_unwind` is marked.
+                let inversions = content
+                    .diagnosis
+                    .iter()
+                    .map(|diagnosis| {
+                        (
+                            diagnosis
+                                .first_lock_span
+                                .split(':')
+                                .nth(1)
+                                .unwrap()
+                                .to_owned(),
+                            diagnosis.inversion.clone(),
+                        )
+                    })
+                    .collect::<FxHashSet<_>>();
+                assert_eq!(
+                    inversions,
+                    FxHashSet::from_iter([
+                        ("5".to_owned(), None),
+                        ("14".to_owned(), Some(INVERSION_PANIC_RETRY.to_owned())),
+                    ])
+                );
+                assert!(content.explanation.contains("catch_unwind"));
+            }),
+        );
+    }
+
+    const CLOSURE_RETRY_SRC: &str = r#"use std::sync::Mutex;
+pub fn with_retry<F: Fn() -> Result<(), ()>>(f: F) -> Result<(), ()> {
+    for _ in 0..3 {
+        if f().is_ok() {
+            return Ok(());
+        }
+    }
+    Err(())
+}
+pub struct Store {
+    state: Mutex<i32>,
+}
+impl Store {
+    fn do_locked_thing(&self) -> Result<(), ()> {
+        *self.state.lock().unwrap() += 1;
+        Ok(())
+    }
+    pub fn retry_under_lock(&self) -> Result<(), ()> {
+        let _state = self.state.lock().unwrap(); // first
+        with_retry(|| self.do_locked_thing())
+    }
+}
+"#;
+
+    /// The closure passed to `with_retry` is reached through a ClosureArg edge if `with_retry` has no body.
+    #[test]
+    fn test_doublelock_through_closure_arg() {
+        let src = write_src("closure-retry", CLOSURE_RETRY_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // Monomorphized with body: reached directly.
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(CLOSURE_RETRY_SRC)
+                );
+                let Report::DoubleLock(content) = &reports[0] else {
+                    unreachable!()
+                };
+                assert!(content.diagnosis.heuristic.is_none());
+                // As if `with_retry` came from a dependency without MIR.
+                let instances = test_instances(tcx)
+                    .into_iter()
+                    .filter(|instance| !tcx.def_path_str(instance.def_id()).ends_with("with_retry"))
+                    .collect();
+                let reports =
+                    run_deadlock_detector_on(tcx, instances, LockRegistry::default(), |_, _| {}).0;
+                assert_eq!(
+                    doublelock_first_lines(&reports),
+                    marked_first_lines(CLOSURE_RETRY_SRC)
+                );
+                let Report::DoubleLock(content) = &reports[0] else {
+                    unreachable!()
+                };
+                assert_eq!(content.possibility, "Possibly");
+                assert_eq!(
+                    content.diagnosis.heuristic.as_deref(),
+                    Some(HEURISTIC_CLOSURE_ARG)
+                );
+                assert!(content.explanation.ends_with("without MIR it is passed to"));
+            }),
+        );
+    }
+
+    const LOCK_NAMES_SRC: &str = r#"use std::sync::{Arc, Mutex, RwLock};
+pub struct Inner {
+    state_mu: Mutex<i32>,
+    other_mu: Mutex<i32>,
+}
+pub struct Service {
+    inner: Arc<Inner>,
+    rw: RwLock<i32>,
+}
+impl Service {
+    pub fn nested(&self) {
+        let _a = self.inner.state_mu.lock().unwrap();
+        let _b = self.inner.state_mu.lock().unwrap();
+    }
+    pub fn rw(&self) {
+        let _a = self.rw.read().unwrap();
+        let _b = self.rw.write().unwrap();
+    }
+}
+pub fn variable(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    let r = m.lock();
+    let _b = r.unwrap();
+}
+pub fn state_other(s: &Service) {
+    let _a = s.inner.state_mu.lock().unwrap();
+    let _b = s.inner.other_mu.lock().unwrap();
+}
+pub fn other_state(s: &Service) {
+    let _b = s.inner.other_mu.lock().unwrap();
+    let _a = s.inner.state_mu.lock().unwrap();
+}
+"#;
+
+    /// The locks in the deadlock diagnoses are named after the fields (through `Arc`), variables, and params.
+    #[test]
+    fn test_lock_names() {
+        let src = write_src("lock-names", LOCK_NAMES_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                let line = |span: &str| -> usize {
+                    let (_, rest) = span.split_once("lib.rs:").unwrap();
+                    rest.split(':').next().unwrap().parse().unwrap()
+                };
+                let mut names = std::collections::BTreeMap::new();
+                let mut insert = |diagnosis: &DeadlockDiagnosis| {
+                    for (span, name) in [
+                        (&diagnosis.first_lock_span, &diagnosis.first_lock_name),
+                        (&diagnosis.second_lock_span, &diagnosis.second_lock_name),
+                    ] {
+                        names.insert(line(span), name.clone().unwrap_or_default());
+                    }
+                };
+                for report in reports.iter() {
+                    match report {
+                        Report::DoubleLock(content) => insert(&content.diagnosis),
+                        Report::ConflictLock(content) => {
+                            content.diagnosis.iter().for_each(&mut insert)
+                        }
+                        _ => {}
+                    }
+                }
+                let expected = [
+                    (12, "self.inner.state_mu"),
+                    (13, "self.inner.state_mu"),
+                    (16, "self.rw"),
+                    (17, "self.rw"),
+                    (21, "m"),
+                    (23, "m"),
+                    (26, "s.inner.state_mu"),
+                    (27, "s.inner.other_mu"),
+                    (30, "s.inner.other_mu"),
+                    (31, "s.inner.state_mu"),
+                ];
+                assert_eq!(
+                    names,
+                    expected
+                        .into_iter()
+                        .map(|(line, name)| (line, name.to_owned()))
+                        .collect()
+                );
+            }),
+        );
+    }
+
+    /// `toys/conflict-inter` with two more `RwLock<u64>` fields locked in the opposite orders through `self`.
+    const ALIAS_PROVENANCE_SRC: &str = r#"use std::sync::{Arc, Mutex, RwLock};
+pub struct Foo {
+    mu1: Arc<Mutex<i32>>,
+    rw1: RwLock<i32>,
+    reads: RwLock<u64>,
+    writes: RwLock<u64>,
+}
+impl Foo {
+    pub fn std_mutex_1(&self) {
+        match *self.mu1.lock().unwrap() {
+            1 => {}
+            _ => self.std_rw_2(),
+        };
+    }
+    fn std_rw_2(&self) {
+        *self.rw1.write().unwrap() += 1;
+    }
+    pub fn std_rw_1(&self) {
+        match *self.rw1.read().unwrap() {
+            1 => {}
+            _ => self.std_mutex_2(),
+        }
+    }
+    fn std_mutex_2(&self) {
+        *self.mu1.lock().unwrap() += 1;
+    }
+    pub fn record_read(&self) {
+        let _reads = self.reads.write().unwrap();
+        self.record_write();
+    }
+    fn record_write(&self) {
+        *self.writes.write().unwrap() += 1;
+    }
+}
+"#;
+
+    #[test]
+    fn test_alias_provenance() {
+        let src = write_src("alias-provenance", ALIAS_PROVENANCE_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                // `self.reads` and `self.writes` are only matched by the types of `self`
+                // but loaded from different fields, so no doublelock.
+                let [Report::ConflictLock(content)] = &reports[..] else {
+                    panic!("expected a conflictlock, got {:?}", reports);
+                };
+                let provenances = content
+                    .diagnosis
+                    .iter()
+                    .map(|diagnosis| diagnosis.alias_provenance.as_deref())
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    provenances,
+                    vec![Some("param_type_heuristic"), Some("param_type_heuristic")]
+                );
+            }),
+        );
+    }
+
+    /// `toys/same-type-fields`.
+    const SAME_TYPE_FIELDS_SRC: &str = r#"use std::sync::Mutex;
+pub struct S {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+}
+impl S {
+    pub fn f(&self) {
+        let _a = self.a.lock().unwrap();
+        g(self);
+    }
+    pub fn h(&self) {
+        let _a = self.a.lock().unwrap();
+        k(self);
+    }
+}
+fn g(s: &S) {
+    *s.b.lock().unwrap() += 1;
+}
+fn k(s: &S) {
+    *s.a.lock().unwrap() += 1;
+}
+"#;
+
+    #[test]
+    fn test_same_type_fields() {
+        let src = write_src("same-type-fields", SAME_TYPE_FIELDS_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                // `self.a` in `f` and `s.b` in `g` select different fields of `S`, so only `h` and `k` doublelock.
+                let [Report::DoubleLock(content)] = &reports[..] else {
+                    panic!("expected a doublelock, got {:?}", reports);
+                };
+                let diagnosis = &content.diagnosis;
+                assert_eq!(diagnosis.first_lock_name.as_deref(), Some("self.a"));
+                assert_eq!(diagnosis.second_lock_name.as_deref(), Some("s.a"));
+                assert_eq!(
+                    diagnosis.alias_provenance.as_deref(),
+                    Some("param_type_heuristic")
+                );
+            }),
+        );
+    }
+
+    /// `toys/getter`.
+    const GETTER_SRC: &str = r#"use std::sync::Mutex;
+pub struct Inner {
+    mu: Mutex<i32>,
+}
+impl Inner {
+    fn mu(&self) -> &Mutex<i32> {
+        &self.mu
+    }
+}
+pub struct S {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+    inner: Inner,
+}
+impl S {
+    fn a(&self) -> &Mutex<i32> {
+        &self.a
+    }
+    fn b(&self) -> &Mutex<i32> {
+        &self.b
+    }
+    pub fn a_then_b(&self) {
+        let _a = self.a().lock().unwrap();
+        let _b = self.b().lock().unwrap();
+    }
+    pub fn a_then_a(&self) {
+        let _a = self.a().lock().unwrap();
+        *self.a.lock().unwrap() += 1;
+    }
+    pub fn inner_then_inner(&self) {
+        let _a = self.inner.mu().lock().unwrap();
+        self.lock_inner();
+    }
+    fn lock_inner(&self) {
+        *self.inner.mu.lock().unwrap() += 1;
+    }
+}
+"#;
+
+    #[test]
+    fn test_getter() {
+        let src = write_src("getter", GETTER_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                // The guards of `self.a()` and `self.b()` point to different fields of `self`
+                // by the return-value summaries of the getters, so `a_then_b` is not a doublelock.
+                let line = |span: &str| -> usize {
+                    let (_, rest) = span.split_once("lib.rs:").unwrap();
+                    rest.split(':').next().unwrap().parse().unwrap()
+                };
+                let doublelocks = reports
+                    .iter()
+                    .map(|report| {
+                        let Report::DoubleLock(content) = report else {
+                            panic!("expected a doublelock, got {:?}", report);
+                        };
+                        (
+                            line(&content.diagnosis.first_lock_span),
+                            line(&content.diagnosis.second_lock_span),
+                            content.possibility.as_str(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    doublelocks,
+                    vec![(27, 28, "Probably"), (31, 35, "Possibly")]
+                );
+            }),
+        );
+    }
+
+    /// `toys/sharded-locks`.
+    const SHARDED_LOCKS_SRC: &str = r#"use std::sync::Mutex;
+pub fn vec_same_shard(shards: &Vec<Mutex<i32>>) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[0].lock().unwrap();
+}
+pub fn vec_different_shards(shards: &Vec<Mutex<i32>>) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[1].lock().unwrap();
+}
+pub fn array_same_shard(shards: &[Mutex<i32>; 2]) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[0].lock().unwrap();
+}
+pub fn array_different_shards(shards: &[Mutex<i32>; 2]) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[1].lock().unwrap();
+}
+pub fn get_same_shard(shards: &[Mutex<i32>]) {
+    let _first = shards.get(0).unwrap().lock().unwrap();
+    let _second = shards.get(0).unwrap().lock().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_sharded_locks() {
+        let src = write_src("sharded-locks", SHARDED_LOCKS_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                // `shards[0]` and `shards[1]` are only Possibly aliased, which is filtered from doublelock.
+                let line = |span: &str| -> usize {
+                    let (_, rest) = span.split_once("lib.rs:").unwrap();
+                    rest.split(':').next().unwrap().parse().unwrap()
+                };
+                let mut doublelocks = reports
+                    .iter()
+                    .map(|report| {
+                        let Report::DoubleLock(content) = report else {
+                            panic!("expected a doublelock, got {:?}", report);
+                        };
+                        (
+                            line(&content.diagnosis.first_lock_span),
+                            content.possibility.as_str(),
+                            content.diagnosis.alias_provenance.as_deref(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                doublelocks.sort();
+                assert_eq!(
+                    doublelocks,
+                    vec![
+                        (3, "Probably", Some("element_index")),
+                        (11, "Probably", Some("element_index")),
+                        (19, "Probably", Some("element_index")),
+                    ]
+                );
+            }),
+        );
+    }
+
+    /// `toys/shared-helper`.
+    const SHARED_HELPER_SRC: &str = r#"use std::sync::Mutex;
+pub struct Registry {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+}
+impl Registry {
+    fn with(&self, m: &Mutex<i32>) -> i32 {
+        let value = m.lock().unwrap();
+        *value
+    }
+    pub fn a_then_b(&self) -> i32 {
+        let a = self.a.lock().unwrap();
+        *a + self.with(&self.b)
+    }
+    pub fn a_then_a(&self) -> i32 {
+        let a = self.a.lock().unwrap();
+        *a + self.with(&self.a)
+    }
+}
+"#;
+
+    #[test]
+    fn test_context_sensitive_alias() {
+        let src = write_src("shared-helper", SHARED_HELPER_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| -> usize {
+                    let (_, rest) = span.split_once("lib.rs:").unwrap();
+                    rest.split(':').next().unwrap().parse().unwrap()
+                };
+                let doublelocks = |reports: Vec<Report>| {
+                    let mut doublelocks = reports
+                        .iter()
+                        .map(|report| {
+                            let Report::DoubleLock(content) = report else {
+                                panic!("expected a doublelock, got {:?}", report);
+                            };
+                            (
+                                line(&content.diagnosis.first_lock_span),
+                                content.possibility.clone(),
+                                content.diagnosis.alias_provenance.clone().unwrap(),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    doublelocks.sort();
+                    doublelocks
+                };
+                // The guards of `self.a` and `m` are only matched by the param types of `&Mutex<i32>`.
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                assert_eq!(
+                    doublelocks(reports),
+                    vec![
+                        (12, "Possibly".to_owned(), "param_type_heuristic".to_owned()),
+                        (16, "Possibly".to_owned(), "param_type_heuristic".to_owned()),
+                    ]
+                );
+                // `m` is `&self.b` at the callsite in `a_then_b` and `&self.a` in `a_then_a`.
+                let reports = run_deadlock_detector_with(
+                    tcx,
+                    LockRegistry::default(),
+                    |_, alias_analysis| alias_analysis.set_context_sensitive(true),
+                )
+                .0;
+                assert_eq!(
+                    doublelocks(reports),
+                    vec![(16, "Probably".to_owned(), "callsite".to_owned())]
+                );
+            }),
+        );
+    }
+
+    /// A default method of a trait called through a trait object, whose instance is a virtual shim.
+    const TRAIT_OBJECT_SHIM_SRC: &str = r#"use std::sync::Mutex;
+pub trait Subscriber {
+    fn event_enabled(&self) -> bool {
+        true
+    }
+}
+pub fn notify(subscriber: &dyn Subscriber, m: &Mutex<i32>) {
+    let _g = m.lock().unwrap();
+    if subscriber.event_enabled() {
+        *m.lock().unwrap() += 1;
+    }
+}
+"#;
+
+    #[test]
+    fn test_guard_newtypes_skip_shims() {
+        let src = write_src("trait-object-shim", TRAIT_OBJECT_SHIM_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // Detecting the guard newtypes does not query the MIR of the shims.
+                let reports = detect_deadlock(tcx, LockRegistry::default());
+                let [Report::DoubleLock(content)] = &reports[..] else {
+                    panic!("expected a doublelock, got {:?}", reports);
+                };
+                assert_eq!(content.possibility, "Probably");
+            }),
+        );
+    }
+}
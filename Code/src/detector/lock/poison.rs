@@ -0,0 +1,106 @@
+//! Report the panic sites reached while the lockguards poisoning their locks on panic are live, i.e., `PanicHoldingLock`.
+//! A panic while a std `MutexGuard` (or `RwLockWriteGuard`) is live poisons the lock,
+//! so that every later `lock().unwrap()` of the lock panics too, cascading the panic to the other threads.
+//! The panic sites are those of `PanicDetector` and the indexing, see `poisoning_panic_sites`,
+//! and the lockguards live at them are collected in the fixed-point of `DeadlockDetector::detect`,
+//! including the lockguards held by the callers.
+//! The `lock().unwrap()` acquiring a lockguard is not a panic site, since it only panics on an already poisoned lock.
+//! The reports may be noisy, so their level can be lowered by `panic_holding_lock` in `levels`.
+extern crate rustc_hash;
+extern crate rustc_span;
+
+use std::collections::BTreeSet;
+
+use petgraph::visit::IntoNodeReferences;
+use rustc_hash::FxHashMap;
+use rustc_middle::mir::Location;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+
+use super::{track_callchains, LockGuardsBeforeCallSites};
+use crate::analysis::callgraph::{CallChainLimits, CallGraph, CallGraphNode, InstanceId};
+use crate::detector::panic::poisoning_panic_sites;
+use crate::detector::panic::report::PanicHoldingLockDiagnosis;
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::LockGuardMap;
+
+/// The panic sites of the instances, see `poisoning_panic_sites`.
+pub(super) type PanicSites = FxHashMap<InstanceId, Vec<(Location, Span, String)>>;
+
+pub(super) fn collect_panic_sites<'tcx>(
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> PanicSites {
+    callgraph
+        .graph
+        .node_references()
+        .filter_map(|(instance_id, node)| match node {
+            CallGraphNode::WithBody(instance) => {
+                let sites = poisoning_panic_sites(*instance, tcx);
+                (!sites.is_empty()).then_some((instance_id, sites))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub(super) fn detect_panics_holding_lock<'tcx>(
+    lockguards_before_panics: &LockGuardsBeforeCallSites,
+    panic_sites: &PanicSites,
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &CallGraph<'tcx>,
+    limits: CallChainLimits,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<Report> {
+    // The monomorphized instances of the same generic fns are reported once.
+    let mut diagnoses = BTreeSet::new();
+    for (instance_id, sites) in panic_sites {
+        for (loc, span, api) in sites {
+            let live = match lockguards_before_panics.get(&(*instance_id, *loc)) {
+                Some(live) => live,
+                None => continue,
+            };
+            for lockguard_id in live.raw_lockguard_ids() {
+                let info = &lockguards[lockguard_id];
+                if !info.lockguard_ty.poisons_on_panic() {
+                    continue;
+                }
+                diagnoses.insert((
+                    format!("{:?}", info.lockguard_ty),
+                    format!("{:?}", info.span),
+                    api.clone(),
+                    format!("{:?}", span),
+                    lockguard_id.instance_id,
+                    *instance_id,
+                ));
+            }
+        }
+    }
+    let mut reported = BTreeSet::new();
+    diagnoses
+        .into_iter()
+        .filter(|(lock_type, guard_span, api, panic_span, _, _)| {
+            reported.insert((
+                lock_type.clone(),
+                guard_span.clone(),
+                api.clone(),
+                panic_span.clone(),
+            ))
+        })
+        .map(|(lock_type, guard_span, api, panic_span, holder, target)| {
+            let callchains = if holder == target {
+                Vec::new()
+            } else {
+                track_callchains(holder, target, callgraph, limits, tcx)
+            };
+            let diagnosis =
+                PanicHoldingLockDiagnosis::new(lock_type, guard_span, api, panic_span, callchains);
+            Report::PanicHoldingLock(ReportContent::new(
+                "PanicHoldingLock".to_owned(),
+                "Possibly".to_owned(),
+                diagnosis,
+                "A panic while the lockguard is live poisons the lock, so that every later `lock().unwrap()` of it panics too".to_owned(),
+            ))
+        })
+        .collect()
+}
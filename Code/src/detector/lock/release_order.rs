@@ -0,0 +1,175 @@
+//! Detect the guard holders releasing locks in the opposite order to other guard holders
+//! or to inline acquisition sequences, i.e., `ImplicitReleaseOrder`.
+//! A guard holder drops its guard fields in the order of declaration (see `GuardHolder`),
+//! so swapping the fields silently changes the order of releasing the locks.
+//! An inline acquisition sequence of two lockguards in a fn releases them in the order of dropping,
+//! which is known if the (non-cleanup) kills of one lockguard precede the other's but not vice versa.
+//! Two release orders are opposite if the first lock of each aliases the second lock of the other.
+//! The reports are informational, so the detector is off by default, see `detector/config.rs`.
+extern crate rustc_hash;
+
+use std::collections::BTreeSet;
+
+use rustc_hash::FxHashSet;
+use rustc_middle::mir::{Body, Location};
+use rustc_middle::ty::TyCtxt;
+
+use super::report::{ImplicitReleaseOrderDiagnosis, ReleaseOrder};
+use crate::analysis::callgraph::{CallGraph, CallGraphNode};
+use crate::analysis::pointsto::{AliasAnalysis, ApproximateAliasKind};
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::{GuardHolder, LockGuardId, LockGuardMap};
+
+/// Two lockguards in the order of release, implied by a guard holder or an inline acquisition sequence.
+struct Release {
+    /// The index of the guard holder in `holders`, or None for an inline acquisition sequence.
+    holder: Option<usize>,
+    first: LockGuardId,
+    second: LockGuardId,
+}
+
+pub fn detect_implicit_release_order<'a, 'tcx>(
+    holders: &[GuardHolder],
+    relations: &FxHashSet<(LockGuardId, LockGuardId)>,
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &'a CallGraph<'tcx>,
+    alias_analysis: &mut AliasAnalysis<'a, 'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<Report> {
+    if holders.is_empty() {
+        return Vec::new();
+    }
+    let mut releases = Vec::new();
+    for (idx, holder) in holders.iter().enumerate() {
+        for (i, first) in holder.lockguards.iter().enumerate() {
+            for second in &holder.lockguards[i + 1..] {
+                releases.push(Release {
+                    holder: Some(idx),
+                    first: *first,
+                    second: *second,
+                });
+            }
+        }
+    }
+    // The lockguards moved into the holders are released by the holders.
+    let held = holders
+        .iter()
+        .flat_map(|holder| holder.lockguards.iter().copied())
+        .collect::<FxHashSet<_>>();
+    let mut inline_relations = relations
+        .iter()
+        .filter(|(a, b)| {
+            a.instance_id == b.instance_id
+                && !held.contains(a)
+                && !held.contains(b)
+                && !lockguards[a].is_gen_only_by_move()
+                && !lockguards[b].is_gen_only_by_move()
+        })
+        .collect::<Vec<_>>();
+    inline_relations.sort_by_key(|(a, b)| (a.instance_id, a.local, b.local));
+    for (a, b) in inline_relations {
+        let body = match callgraph.index_to_instance(a.instance_id) {
+            Some(CallGraphNode::WithBody(instance)) => tcx.instance_mir(instance.def),
+            _ => continue,
+        };
+        let (a_kills, b_kills) = (&lockguards[a].kill_locs, &lockguards[b].kill_locs);
+        let (first, second) = match (
+            released_before(body, a_kills, b_kills),
+            released_before(body, b_kills, a_kills),
+        ) {
+            (true, false) => (*a, *b),
+            (false, true) => (*b, *a),
+            _ => continue,
+        };
+        releases.push(Release {
+            holder: None,
+            first,
+            second,
+        });
+    }
+    let mut same_lock = |a: &LockGuardId, b: &LockGuardId| {
+        lockguards[a]
+            .lockguard_ty
+            .is_same_lock(&lockguards[b].lockguard_ty)
+            && matches!(
+                alias_analysis.alias((*a).into(), (*b).into()),
+                ApproximateAliasKind::Probably | ApproximateAliasKind::Possibly
+            )
+    };
+    let mut reported = BTreeSet::new();
+    let mut reports = Vec::new();
+    for (i, x) in releases.iter().enumerate() {
+        if x.holder.is_none() || same_lock(&x.first, &x.second) {
+            continue;
+        }
+        for y in &releases[i + 1..] {
+            if x.holder == y.holder
+                || !same_lock(&x.first, &y.second)
+                || !same_lock(&x.second, &y.first)
+            {
+                continue;
+            }
+            let first = release_order(x, holders, lockguards, callgraph, tcx);
+            let second = release_order(y, holders, lockguards, callgraph, tcx);
+            // The instantiations of the same generic holders or fns are reported once.
+            if !reported.insert((
+                first.name.clone(),
+                first.span.clone(),
+                second.name.clone(),
+                second.span.clone(),
+            )) {
+                continue;
+            }
+            let explanation = format!(
+                "The fields of `{}` are dropped in the order of declaration, releasing the locks in the opposite order to `{}`",
+                first.name, second.name
+            );
+            reports.push(Report::ImplicitReleaseOrder(ReportContent::new(
+                "ImplicitReleaseOrder".to_owned(),
+                "Possibly".to_owned(),
+                ImplicitReleaseOrderDiagnosis { first, second },
+                explanation,
+            )));
+        }
+    }
+    reports
+}
+
+/// Whether a kill of the first lockguard precedes a kill of the second one, ignoring the cleanup blocks.
+fn released_before(body: &Body<'_>, first: &[Location], second: &[Location]) -> bool {
+    let normal = |loc: &&Location| !body.basic_blocks[loc.block].is_cleanup;
+    first.iter().filter(normal).any(|first| {
+        second
+            .iter()
+            .filter(normal)
+            .any(|second| first.is_predecessor_of(*second, body))
+    })
+}
+
+fn release_order<'tcx>(
+    release: &Release,
+    holders: &[GuardHolder],
+    lockguards: &LockGuardMap<'tcx>,
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> ReleaseOrder {
+    let def_id = match release.holder {
+        Some(idx) => holders[idx].def_id,
+        None => callgraph
+            .index_to_instance(release.first.instance_id)
+            .unwrap()
+            .instance()
+            .def_id(),
+    };
+    let (first, second) = (&lockguards[&release.first], &lockguards[&release.second]);
+    ReleaseOrder {
+        name: tcx.def_path_str(def_id),
+        holder: release.holder.is_some(),
+        span: format!("{:?}", tcx.def_span(def_id)),
+        lock_types: vec![
+            format!("{:?}", first.lockguard_ty),
+            format!("{:?}", second.lockguard_ty),
+        ],
+        lock_spans: vec![format!("{:?}", first.span), format!("{:?}", second.span)],
+    }
+}
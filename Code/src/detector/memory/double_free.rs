@@ -0,0 +1,353 @@
+//! Detect Double Free
+//! For `p = Box::into_raw(b)` (or `Rc::into_raw`, `Arc::into_raw`, `Vec::into_raw_parts`):
+//! 1. find the reconstructions `Box::from_raw(q)` (or `Rc::from_raw`, `Vec::from_raw_parts`, etc.)
+//!    where q originates from p, including the calls to the fns reconstructing their args
+//! 2. if a reconstruction reaches another one on an aliasing ptr
+//!    without an intervening `into_raw` of the ptr, then report double-free
+//! 3. if a reconstruction reaches a use of q (or its copies)
+//!    without an intervening `into_raw` of the ptr, then report use-after-reconstruction
+extern crate rustc_data_structures;
+extern crate rustc_hir;
+extern crate rustc_middle;
+
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{Body, Local, Location, Operand, TerminatorKind};
+use rustc_middle::ty::{Instance, TyCtxt, TyKind};
+
+use petgraph::visit::IntoNodeReferences;
+
+use super::{copy_sources, is_reachable, raw_ptr_copies};
+use crate::analysis::callgraph::{CallGraph, CallGraphNode, InstanceId};
+use crate::analysis::defuse::find_uses;
+use crate::analysis::pointsto::{AliasAnalysis, AliasId, ApproximateAliasKind, ConstraintNode};
+use crate::detector::config::DetectorConfig;
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::memory::ownership;
+use crate::progress::Progress;
+
+/// The fns reconstructing their args, e.g., `fn release(p: *mut T) { drop(Box::from_raw(p)) }`,
+/// mapped to the indices of the args and the reconstruction APIs.
+type ReconstructingFns = FxHashMap<DefId, Vec<(usize, &'static str)>>;
+
+/// A leak or a reconstruction of the ownership of a raw ptr.
+struct OwnershipTransfer {
+    location: Location,
+    /// The raw ptr leaked to or reconstructed from.
+    ptr: Local,
+    /// The API, e.g., `Box::into_raw`.
+    api: &'static str,
+    /// The fn calling the API with the raw ptr as its arg.
+    via: Option<DefId>,
+}
+
+impl OwnershipTransfer {
+    fn describe(&self, tcx: TyCtxt<'_>) -> String {
+        match self.via {
+            Some(via) => format!("`{}` calling `{}`", tcx.def_path_str(via), self.api),
+            None => format!("`{}`", self.api),
+        }
+    }
+}
+
+pub struct DoubleFreeDetector<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    /// The enabled detectors and their parameters.
+    config: DetectorConfig,
+}
+
+impl<'tcx> DoubleFreeDetector<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>, config: DetectorConfig) -> Self {
+        Self { tcx, config }
+    }
+
+    pub fn detect(
+        &self,
+        callgraph: &CallGraph<'tcx>,
+        alias_analysis: &mut AliasAnalysis<'_, 'tcx>,
+        progress: &Progress,
+    ) -> Vec<Report> {
+        let reconstructing_fns = self.collect_reconstructing_fns(callgraph);
+        let mut reports = Vec::new();
+        let total = callgraph.graph.node_count();
+        for (processed, (instance_id, node)) in callgraph.graph.node_references().enumerate() {
+            progress.update(processed + 1, total, "instances");
+            let instance = match node {
+                CallGraphNode::WithBody(instance) if callgraph.is_focused(instance_id) => instance,
+                _ => continue,
+            };
+            reports.extend(self.detect_instance(
+                instance_id,
+                instance,
+                &reconstructing_fns,
+                alias_analysis,
+            ));
+        }
+        self.config.retain(&mut reports);
+        reports
+    }
+
+    /// Collect the fns reconstructing their args directly or by calling other reconstructing fns
+    /// until a fixed point.
+    fn collect_reconstructing_fns(&self, callgraph: &CallGraph<'tcx>) -> ReconstructingFns {
+        let mut reconstructing_fns = ReconstructingFns::default();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (_, node) in callgraph.graph.node_references() {
+                let instance = match node {
+                    CallGraphNode::WithBody(instance) => instance,
+                    CallGraphNode::WithoutBody(_) => continue,
+                };
+                let body = self.tcx.instance_mir(instance.def);
+                for reconstruction in self.collect_reconstructions(body, &reconstructing_fns) {
+                    let sources = copy_sources(body, reconstruction.ptr);
+                    for arg in body.args_iter().filter(|arg| sources.contains(arg)) {
+                        let entry = reconstructing_fns.entry(instance.def_id()).or_default();
+                        let arg_api = (arg.index() - 1, reconstruction.api);
+                        if !entry.contains(&arg_api) {
+                            entry.push(arg_api);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        reconstructing_fns
+    }
+
+    /// `p = Box::into_raw(b)`, etc. and `Rc::increment_strong_count(p)`, etc.
+    fn collect_leaks(&self, body: &Body<'tcx>) -> Vec<OwnershipTransfer> {
+        let mut leaks = Vec::new();
+        for (block, data) in body.basic_blocks.iter_enumerated() {
+            if data.is_cleanup {
+                continue;
+            }
+            if let TerminatorKind::Call {
+                func,
+                args,
+                destination,
+                ..
+            } = &data.terminator().kind
+            {
+                let api = match func.ty(body, self.tcx).kind() {
+                    TyKind::FnDef(def_id, _) => ownership::leak_to_raw(*def_id, self.tcx),
+                    _ => None,
+                };
+                let api = match api {
+                    Some(api) => api,
+                    None => continue,
+                };
+                let ptr = if api.ends_with("increment_strong_count") {
+                    arg_local(args, 0)
+                } else {
+                    destination.as_local()
+                };
+                if let Some(ptr) = ptr {
+                    leaks.push(OwnershipTransfer {
+                        location: body.terminator_loc(block),
+                        ptr,
+                        api,
+                        via: None,
+                    });
+                }
+            }
+        }
+        leaks
+    }
+
+    /// `b = Box::from_raw(p)`, etc. and the calls to the fns reconstructing their args.
+    fn collect_reconstructions(
+        &self,
+        body: &Body<'tcx>,
+        reconstructing_fns: &ReconstructingFns,
+    ) -> Vec<OwnershipTransfer> {
+        let mut reconstructions = Vec::new();
+        for (block, data) in body.basic_blocks.iter_enumerated() {
+            if data.is_cleanup {
+                continue;
+            }
+            let (func, args) = match &data.terminator().kind {
+                TerminatorKind::Call { func, args, .. } => (func, args),
+                _ => continue,
+            };
+            let def_id = match func.ty(body, self.tcx).kind() {
+                TyKind::FnDef(def_id, _) => *def_id,
+                _ => continue,
+            };
+            let location = body.terminator_loc(block);
+            if let Some(api) = ownership::reconstruct_from_raw(def_id, self.tcx) {
+                if let Some(ptr) = arg_local(args, 0) {
+                    reconstructions.push(OwnershipTransfer {
+                        location,
+                        ptr,
+                        api,
+                        via: None,
+                    });
+                }
+            } else if let Some(indices) = reconstructing_fns.get(&def_id) {
+                for (idx, api) in indices {
+                    if let Some(ptr) = arg_local(args, *idx) {
+                        reconstructions.push(OwnershipTransfer {
+                            location,
+                            ptr,
+                            api,
+                            via: Some(def_id),
+                        });
+                    }
+                }
+            }
+        }
+        reconstructions
+    }
+
+    fn detect_instance(
+        &self,
+        instance_id: InstanceId,
+        instance: &Instance<'tcx>,
+        reconstructing_fns: &ReconstructingFns,
+        alias_analysis: &mut AliasAnalysis<'_, 'tcx>,
+    ) -> Vec<Report> {
+        let body = self.tcx.instance_mir(instance.def);
+        let reconstructions = self.collect_reconstructions(body, reconstructing_fns);
+        if reconstructions.is_empty() {
+            return Vec::new();
+        }
+        let leaks = self.collect_leaks(body);
+        // The raw ptrs leaked in the current fn, i.e., Alloc(p) in pts(q) for the leak p, are tracked.
+        let pts = alias_analysis.get_or_insert_pts(instance.def_id(), body);
+        let reconstructions = reconstructions
+            .into_iter()
+            .filter_map(|reconstruction| {
+                let ptes = pts.get(&ConstraintNode::Place(reconstruction.ptr.into()))?;
+                let origin = leaks.iter().find(|leak| {
+                    !leak.api.ends_with("increment_strong_count")
+                        && ptes.iter().any(|pte| {
+                            matches!(pte, ConstraintNode::Alloc(place) if place.local == leak.ptr)
+                        })
+                })?;
+                Some((reconstruction, origin))
+            })
+            .collect::<Vec<_>>();
+        let mut double_free_diagnosis_set = FxHashSet::default();
+        let mut use_diagnosis_set = FxHashSet::default();
+        let mut reported_pairs = FxHashSet::default();
+        for (idx1, (first, origin)) in reconstructions.iter().enumerate() {
+            for (idx2, (second, _)) in reconstructions.iter().enumerate() {
+                if first.location == second.location
+                    || !is_reachable(first.location, second.location, body)
+                    || !reported_pairs.insert((idx1.min(idx2), idx1.max(idx2)))
+                    || !aliases(instance_id, first.ptr, second.ptr, alias_analysis)
+                    || is_leaked_between(
+                        first.location,
+                        second.location,
+                        second.ptr,
+                        &leaks,
+                        instance_id,
+                        body,
+                        alias_analysis,
+                    )
+                {
+                    continue;
+                }
+                let diagnosis = format!(
+                    "Raw ptr leaked by {} at {:?} is reconstructed by {} at {:?} and again by {} at {:?}",
+                    origin.describe(self.tcx),
+                    body.source_info(origin.location).span,
+                    first.describe(self.tcx),
+                    body.source_info(first.location).span,
+                    second.describe(self.tcx),
+                    body.source_info(second.location).span
+                );
+                double_free_diagnosis_set.insert(diagnosis);
+            }
+            let use_locations = raw_ptr_copies(body, first.ptr)
+                .into_iter()
+                .flat_map(|local| {
+                    find_uses(body, local)
+                        .into_iter()
+                        .map(move |location| (location, local))
+                })
+                .collect::<Vec<_>>();
+            for (use_loc, used) in use_locations {
+                let is_transfer = use_loc == first.location
+                    || reconstructions
+                        .iter()
+                        .any(|(reconstruction, _)| reconstruction.location == use_loc)
+                    || leaks.iter().any(|leak| leak.location == use_loc);
+                if is_transfer
+                    || !is_reachable(first.location, use_loc, body)
+                    || is_leaked_between(
+                        first.location,
+                        use_loc,
+                        used,
+                        &leaks,
+                        instance_id,
+                        body,
+                        alias_analysis,
+                    )
+                {
+                    continue;
+                }
+                let diagnosis = format!(
+                    "Raw ptr leaked by {} at {:?} is used at {:?} after reconstructed by {} at {:?}",
+                    origin.describe(self.tcx),
+                    body.source_info(origin.location).span,
+                    body.source_info(use_loc).span,
+                    first.describe(self.tcx),
+                    body.source_info(first.location).span
+                );
+                use_diagnosis_set.insert(diagnosis);
+            }
+        }
+        let mut reports = double_free_diagnosis_set.into_iter().map(|diagnosis| Report::DoubleFree(ReportContent::new("DoubleFree".to_owned(), "Possibly".to_owned(), diagnosis, "The ownership leaked to a raw ptr is reconstructed twice, which frees the pointee twice".to_owned()))).collect::<Vec<_>>();
+        reports.extend(use_diagnosis_set.into_iter().map(|diagnosis| Report::DoubleFree(ReportContent::new("DoubleFree".to_owned(), "Possibly".to_owned(), diagnosis, "The raw ptr is used after its ownership is reconstructed, and the reconstructed owner may have freed the pointee".to_owned()))));
+        reports
+    }
+}
+
+/// Whether the two raw ptrs in the instance alias with each other.
+fn aliases(
+    instance_id: InstanceId,
+    ptr1: Local,
+    ptr2: Local,
+    alias_analysis: &mut AliasAnalysis<'_, '_>,
+) -> bool {
+    let aid1 = AliasId {
+        instance_id,
+        local: ptr1,
+    };
+    let aid2 = AliasId {
+        instance_id,
+        local: ptr2,
+    };
+    alias_analysis.alias(aid1, aid2) > ApproximateAliasKind::Unlikely
+}
+
+/// Whether the ownership of `ptr` is leaked again between `from` and `to`,
+/// e.g., `let b = Box::from_raw(p); p = Box::into_raw(b);` in a loop.
+/// `increment_strong_count` before `to` leaks another ownership even if it is before `from`.
+fn is_leaked_between(
+    from: Location,
+    to: Location,
+    ptr: Local,
+    leaks: &[OwnershipTransfer],
+    instance_id: InstanceId,
+    body: &Body<'_>,
+    alias_analysis: &mut AliasAnalysis<'_, '_>,
+) -> bool {
+    leaks.iter().any(|leak| {
+        leak.location != from
+            && (leak.api.ends_with("increment_strong_count")
+                || is_reachable(from, leak.location, body))
+            && is_reachable(leak.location, to, body)
+            && aliases(instance_id, leak.ptr, ptr, alias_analysis)
+    })
+}
+
+/// The local of the `idx`th arg of a call.
+fn arg_local(args: &[Operand<'_>], idx: usize) -> Option<Local> {
+    args.get(idx)
+        .and_then(|arg| arg.place())
+        .and_then(|place| place.as_local())
+}
@@ -4,20 +4,34 @@
 //! 2. and there exists `drop(a)`
 //! 3. then report invalid-free
 //!
-//! For `b = MaybeUninit::uninit()` and `c = assume_init(b)`:
+//! For `a = mem::zeroed()`:
+//! 1. if `a` is of a type with a component invalid when zeroed, e.g., a reference or a `Box`
+//! 2. then report invalid-value
+//!
+//! For `b = MaybeUninit::zeroed()` and `c = assume_init(b)`:
 //! 1. if there is no `write(b)` in between
+//! 2. and `c` is of a type with a component invalid when zeroed
+//! 3. then report invalid-value
+//!
+//! For `b = MaybeUninit::uninit()` and `c = assume_init(b)`:
+//! 1. if there is no `write(b)` in between,
+//!    nor `ptr::write(p)` of the whole value or of every field through `p = b.as_mut_ptr()`
 //! 2. and `c` is of a type that is not simple
 //! 3. and there exists `drop(c)`
 //! 4. then report invalid-free
 extern crate rustc_data_structures;
 extern crate rustc_index;
 extern crate rustc_middle;
+extern crate rustc_target;
 
 use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 use rustc_index::bit_set::BitSet;
 use rustc_middle::mir::visit::Visitor;
-use rustc_middle::mir::{BasicBlock, Body, Location, Place, TerminatorKind};
-use rustc_middle::ty::{self, EarlyBinder, Instance, Ty, TyCtxt};
+use rustc_middle::mir::{
+    BasicBlock, Body, Local, Location, Place, ProjectionElem, Rvalue, StatementKind, TerminatorKind,
+};
+use rustc_middle::ty::{self, EarlyBinder, Instance, Ty, TyCtxt, TyKind};
+use rustc_target::abi::FieldIdx;
 
 use petgraph::visit::IntoNodeReferences;
 
@@ -25,28 +39,38 @@ use super::{collect_manual_drop, dest_args0, AutoDropCollector};
 use crate::analysis::callgraph::{CallSiteLocation, InstanceId};
 use crate::analysis::pointsto::{AliasId, ApproximateAliasKind};
 use crate::analysis::{callgraph::CallGraph, pointsto::AliasAnalysis};
+use crate::detector::config::DetectorConfig;
 use crate::detector::report::{Report, ReportContent};
-use crate::interest::memory::uninit::UninitApi;
+use crate::interest::memory::uninit::{non_zeroable_component, UninitApi};
+use crate::progress::Progress;
+
+const UNINIT_EXPLANATION: &str = "Call mem::uninitialized() or MaybeUninit::uninit() followed by assume_init() without actually write on not simple types";
+const ZEROED_EXPLANATION: &str = "Call mem::zeroed() or MaybeUninit::zeroed() followed by assume_init() on types invalid when zeroed, which is instant undefined behavior";
 
 pub struct InvalidFreeDetector<'tcx> {
     tcx: TyCtxt<'tcx>,
+    /// The enabled detectors and their parameters.
+    config: DetectorConfig,
 }
 
 impl<'tcx> InvalidFreeDetector<'tcx> {
-    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
-        Self { tcx }
+    pub fn new(tcx: TyCtxt<'tcx>, config: DetectorConfig) -> Self {
+        Self { tcx, config }
     }
 
     pub fn detect(
         &self,
         callgraph: &CallGraph<'tcx>,
         alias_analysis: &mut AliasAnalysis,
+        progress: &Progress,
     ) -> Vec<Report> {
         let uninits = self.collect_uninit(callgraph);
         let caller_callsites = self.collect_caller_callsites(uninits, callgraph);
         let manual_drops = collect_manual_drop(callgraph, self.tcx);
         let mut reports = Vec::new();
-        for (caller_id, callsites) in caller_callsites {
+        let total = caller_callsites.len();
+        for (processed, (caller_id, callsites)) in caller_callsites.into_iter().enumerate() {
+            progress.update(processed + 1, total, "callers of uninit APIs");
             let manual_drops = match manual_drops.get(&caller_id) {
                 Some(v) => v.clone(),
                 None => Vec::new(),
@@ -58,12 +82,18 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
                 alias_analysis,
                 &manual_drops,
             ) {
-                for d in diagnosis {
-                    let content = ReportContent::new("InvalidFree".to_owned(), "Possibly".to_owned(), d, "Call mem::uninitialized() or MaybeUninit::uninit() followed by assume_init() without actually write on not simple types".to_owned());
+                for (d, explanation) in diagnosis {
+                    let content = ReportContent::new(
+                        "InvalidFree".to_owned(),
+                        "Possibly".to_owned(),
+                        d,
+                        explanation.to_owned(),
+                    );
                     reports.push(Report::InvalidFree(content));
                 }
             }
         }
+        self.config.retain(&mut reports);
         reports
     }
 
@@ -89,6 +119,9 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
         for (callee, uninit_api) in uninits.iter() {
             let callers = callgraph.callers(*callee);
             for caller in callers {
+                if !callgraph.is_focused(caller) {
+                    continue;
+                }
                 if let Some(callsites) = callgraph.callsites(caller, *callee) {
                     let entry = caller_callsites.entry(caller).or_default();
                     for callsite in callsites {
@@ -103,6 +136,7 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
     }
 
     /// Detect each caller that calls Uninit APIs.
+    /// Returns the diagnoses and their explanations.
     fn detect_caller_callsites(
         &self,
         caller_id: InstanceId,
@@ -110,7 +144,7 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
         callgraph: &CallGraph<'tcx>,
         alias_analysis: &mut AliasAnalysis,
         manual_drops: &[(Location, Place<'tcx>)],
-    ) -> Option<Vec<String>> {
+    ) -> Option<Vec<(String, &'static str)>> {
         let mut diagnosis_vec = Vec::new();
         let caller = callgraph
             .index_to_instance(caller_id)
@@ -119,6 +153,7 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
         let mut maybe_uninits = Vec::new();
         let mut assume_inits = Vec::new();
         let mut writes = Vec::new();
+        let mut ptr_writes = Vec::new();
         let mut auto_drop_collector = AutoDropCollector::new();
         auto_drop_collector.visit_body(body);
         let mut drops = auto_drop_collector.finish();
@@ -135,19 +170,39 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
                         &drops,
                         alias_analysis,
                     ) {
-                        diagnosis_vec.push(diagnosis);
+                        diagnosis_vec.push((diagnosis, UNINIT_EXPLANATION));
+                    }
+                }
+                UninitApi::Zeroed => {
+                    if let Some(diagnosis) = self.detect_zeroed(caller, body, loc) {
+                        diagnosis_vec.push((diagnosis, ZEROED_EXPLANATION));
                     }
                 }
                 UninitApi::MaybeUninit => {
                     if let Some((dest, _)) = dest_args0(body, loc) {
-                        maybe_uninits.push((loc, dest));
+                        maybe_uninits.push((loc, dest, false));
                     }
                 }
-                UninitApi::MaybeUninitWrite | UninitApi::PtrWrite => {
+                UninitApi::MaybeUninitZeroed => {
+                    if let Some((dest, _)) = dest_args0(body, loc) {
+                        maybe_uninits.push((loc, dest, true));
+                    }
+                }
+                UninitApi::MaybeUninitWrite => {
                     if let Some((_, Some(place0))) = dest_args0(body, loc) {
                         writes.push((loc, place0));
                     }
                 }
+                // The ptrs returned by as_mut_ptr are resolved by points-to at the ptr writes.
+                UninitApi::AsMutPtr => {}
+                UninitApi::PtrWrite => {
+                    if let Some((_, Some(place0))) = dest_args0(body, loc) {
+                        if let Some(local) = place0.as_local() {
+                            let (ptr, field) = ptr_write_target(body, local);
+                            ptr_writes.push((loc, ptr, field));
+                        }
+                    }
+                }
                 UninitApi::AssumeInit => {
                     if let Some((dest, Some(place0))) = dest_args0(body, loc) {
                         assume_inits.push((loc, dest, place0));
@@ -157,13 +212,22 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
         }
         // candidates = MaybeUninits X AssumeInits X Dest(MaybeUninits)
         let mut candidates = Vec::new();
-        for (loc1, dest1) in maybe_uninits {
+        for (loc1, dest1, zeroed) in maybe_uninits {
             for (loc2, dest2, first_arg) in &assume_inits {
                 // let obj: T = assume_init();
                 // T should not be simple types like bool, i32, etc.
                 // T should be complex types like Vec, MyStruct, etc.
+                // For zeroed, T should contain a component invalid when zeroed.
                 let dest2_ty = self.monomorphize(caller, dest2.ty(body, self.tcx).ty);
-                if dest2_ty.is_simple_ty() {
+                let non_zeroable = if zeroed {
+                    match non_zeroable_component(dest2_ty, self.tcx) {
+                        Some(component) => Some(component),
+                        None => continue,
+                    }
+                } else {
+                    None
+                };
+                if !zeroed && dest2_ty.is_simple_ty() {
                     continue;
                 }
                 let dest1_ty = self.monomorphize(caller, dest1.ty(body, self.tcx).ty);
@@ -202,8 +266,9 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
                         break;
                     }
                 }
-                if is_aliased && is_dropped {
-                    candidates.push((loc1, *loc2, dest1));
+                // The zeroed value is invalid as soon as assume_init returns, dropped or not.
+                if is_aliased && (is_dropped || zeroed) {
+                    candidates.push((loc1, *loc2, dest1, non_zeroable));
                 }
             }
         }
@@ -211,7 +276,8 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
         // uninit -> write? -> assume_init
         // If there is a write in between, and first_arg(write) points to dest(uninit)
         // then we conservatively consider it not a bug, otherwise diagnosis the bug.
-        for (loc1, loc2, dest1) in candidates {
+        for (loc1, loc2, dest1, non_zeroable) in candidates {
+            let num_fields = num_fields(self.monomorphize(caller, dest1.ty(body, self.tcx).ty));
             let paths = self.paths_from_to(loc1, loc2, body);
             for path in paths {
                 let mut write_in_between = false;
@@ -231,6 +297,31 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
                         }
                     }
                 }
+                // Written through the ptr returned by as_mut_ptr as a whole or field by field
+                let mut written_fields = FxHashSet::default();
+                for (loc3, ptr, field) in &ptr_writes {
+                    if write_in_between || !path.contains(&loc3.block) {
+                        continue;
+                    }
+                    let aid1 = AliasId {
+                        instance_id: caller_id,
+                        local: dest1.local,
+                    };
+                    let aid2 = AliasId {
+                        instance_id: caller_id,
+                        local: *ptr,
+                    };
+                    if alias_analysis.points_to(aid2, aid1) <= ApproximateAliasKind::Unlikely {
+                        continue;
+                    }
+                    match field {
+                        Some(field) => {
+                            written_fields.insert(*field);
+                            write_in_between = num_fields > 0 && written_fields.len() == num_fields;
+                        }
+                        None => write_in_between = true,
+                    }
+                }
                 if !write_in_between {
                     let span1 = body.source_info(loc1).span;
                     let span_str1 = format!("{span1:?}");
@@ -249,11 +340,22 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
                         continue;
                     }
                     let ty = self.monomorphize(caller, dest1.ty(body, self.tcx).ty);
-                    let diagnosis = format!(
-                        "{:?} = uninit at {:?}, assume_init at {:?}",
-                        ty, span1, span2
-                    );
-                    diagnosis_vec.push(diagnosis);
+                    match non_zeroable {
+                        Some(component) => {
+                            let diagnosis = format!(
+                                "{:?} = zeroed at {:?}, assume_init at {:?}, but {:?} is invalid when zeroed",
+                                ty, span1, span2, component
+                            );
+                            diagnosis_vec.push((diagnosis, ZEROED_EXPLANATION));
+                        }
+                        None => {
+                            let diagnosis = format!(
+                                "{:?} = uninit at {:?}, assume_init at {:?}",
+                                ty, span1, span2
+                            );
+                            diagnosis_vec.push((diagnosis, UNINIT_EXPLANATION));
+                        }
+                    }
                 }
             }
         }
@@ -315,6 +417,30 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
         None
     }
 
+    /// Detect mem::zeroed() on types invalid when zeroed
+    fn detect_zeroed(
+        &self,
+        instance: &Instance<'tcx>,
+        body: &Body<'tcx>,
+        loc: Location,
+    ) -> Option<String> {
+        if let TerminatorKind::Call { destination, .. } = &body[loc.block].terminator().kind {
+            let ty = self.monomorphize(instance, destination.ty(body, self.tcx).ty);
+            let component = non_zeroable_component(ty, self.tcx)?;
+            let span = body.source_info(loc).span;
+            let span_str = format!("{span:?}");
+            // skip std lib
+            if !(span_str.contains(".rustup/toolchains")
+                && span_str.contains("lib/rustlib/src/rust/library"))
+            {
+                return Some(format!(
+                    "{ty:?} = mem::zeroed() at {span_str}, but {component:?} is invalid when zeroed"
+                ));
+            }
+        }
+        None
+    }
+
     /// Find all the paths from loc1 to loc2
     fn paths_from_to(
         &self,
@@ -337,10 +463,65 @@ impl<'tcx> InvalidFreeDetector<'tcx> {
     }
 }
 
-fn find_path_recursive<'tcx>(
+/// The ptr and the field written by `ptr::write(arg, ..)`:
+/// (p, Some(f)) for `arg = &raw mut (*p).f` (maybe copied), or (arg, None) for the whole pointee.
+fn ptr_write_target(body: &Body<'_>, arg: Local) -> (Local, Option<FieldIdx>) {
+    let mut local = arg;
+    // Bounded by the number of locals in case of a cycle.
+    for _ in 0..body.local_decls.len() {
+        let mut rvalues = body.basic_blocks.iter().flat_map(|data| {
+            data.statements
+                .iter()
+                .filter_map(|statement| match &statement.kind {
+                    StatementKind::Assign(box (lhs, rvalue)) if lhs.as_local() == Some(local) => {
+                        Some(rvalue)
+                    }
+                    _ => None,
+                })
+        });
+        let rvalue = match (rvalues.next(), rvalues.next()) {
+            (Some(rvalue), None) => rvalue,
+            _ => break,
+        };
+        match rvalue {
+            Rvalue::AddressOf(_, place) => {
+                if let [ProjectionElem::Deref, ProjectionElem::Field(field, _)] =
+                    place.projection.as_slice()
+                {
+                    return (place.local, Some(*field));
+                }
+                break;
+            }
+            Rvalue::Use(operand) => match operand.place().and_then(|place| place.as_local()) {
+                Some(from) => local = from,
+                None => break,
+            },
+            _ => break,
+        }
+    }
+    (local, None)
+}
+
+/// The number of the fields of T in `MaybeUninit<T>` if T is a struct or a tuple, otherwise 0.
+fn num_fields(maybe_uninit_ty: Ty<'_>) -> usize {
+    let ty = match maybe_uninit_ty.kind() {
+        TyKind::Adt(_, substs) => match substs.get(0).and_then(|arg| arg.as_type()) {
+            Some(ty) => ty,
+            None => return 0,
+        },
+        _ => return 0,
+    };
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) if adt_def.is_struct() => adt_def.non_enum_variant().fields.len(),
+        TyKind::Tuple(tys) => tys.len(),
+        _ => 0,
+    }
+}
+
+fn find_path_recursive(
     u: BasicBlock,
     d: BasicBlock,
-    body: &Body<'tcx>,
+    body: &Body<'_>,
     path: &mut Vec<BasicBlock>,
     visited: &mut BitSet<BasicBlock>,
     paths: &mut Vec<Vec<BasicBlock>>,
@@ -3,16 +3,21 @@ extern crate rustc_middle;
 
 use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 use rustc_middle::mir::visit::Visitor;
-use rustc_middle::mir::{Body, Location, Place, Terminator, TerminatorKind};
-use rustc_middle::ty::TyCtxt;
+use rustc_middle::mir::{
+    BasicBlock, Body, Local, Location, Place, Rvalue, StatementKind, Terminator, TerminatorKind,
+};
+use rustc_middle::ty::{Instance, TyCtxt, TyKind};
 
 use petgraph::visit::IntoNodeReferences;
 
 use crate::analysis::callgraph::{CallGraph, InstanceId};
+use crate::interest::memory::ownership;
 
+mod double_free;
 mod invalid_free;
 mod use_after_free;
 
+pub use double_free::DoubleFreeDetector;
 pub use invalid_free::InvalidFreeDetector;
 pub use use_after_free::UseAfterFreeDetector;
 
@@ -38,15 +43,29 @@ fn collect_manual_drop<'tcx>(
     callgraph: &CallGraph<'tcx>,
     tcx: TyCtxt<'tcx>,
 ) -> FxHashMap<InstanceId, Vec<(Location, Place<'tcx>)>> {
-    let mut manual_drops: FxHashMap<InstanceId, Vec<_>> = FxHashMap::default();
+    collect_callsite_args0(callgraph, tcx, |instance| {
+        let path = tcx.def_path_str_with_args(instance.def_id(), instance.args);
+        path.starts_with("std::mem::drop") || path.starts_with("core::mem::drop")
+    })
+}
+
+/// The callsites of the callees satisfying `is_callee` in CallGraph and their first args, grouped by the callers,
+/// e.g., `std::mem::drop(place)` or `std::mem::forget(place)`.
+pub fn collect_callsite_args0<'tcx>(
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    is_callee: impl Fn(Instance<'tcx>) -> bool,
+) -> FxHashMap<InstanceId, Vec<(Location, Place<'tcx>)>> {
+    let mut callsite_args0: FxHashMap<InstanceId, Vec<_>> = FxHashMap::default();
     for (callee_id, node) in callgraph.graph.node_references() {
-        let instance = node.instance();
-        let path = tcx.def_path_str_with_args(instance.def_id(), &*instance.args);
-        if !path.starts_with("std::mem::drop") && !path.starts_with("core::mem::drop") {
+        if !is_callee(*node.instance()) {
             continue;
         }
         let caller_ids = callgraph.callers(callee_id);
-        for caller_id in caller_ids {
+        for caller_id in caller_ids
+            .into_iter()
+            .filter(|id| callgraph.is_focused(*id))
+        {
             let callsites = match callgraph.callsites(caller_id, callee_id) {
                 Some(callsites) => callsites,
                 None => continue,
@@ -66,14 +85,41 @@ fn collect_manual_drop<'tcx>(
                     Some((_, Some(places0))) => places0,
                     _ => continue,
                 };
-                manual_drops
+                callsite_args0
                     .entry(caller_id)
                     .or_default()
                     .push((loc, places0));
             }
         }
     }
-    manual_drops
+    callsite_args0
+}
+
+/// std::ptr::drop_in_place(ptr) or ManuallyDrop::drop(ptr);
+/// the pointee of ptr is dropped in place.
+fn collect_explicit_drop<'tcx>(
+    body: &Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<(Location, Place<'tcx>)> {
+    let mut explicit_drops = Vec::new();
+    for (block, bb_data) in body.basic_blocks.iter_enumerated() {
+        if let TerminatorKind::Call { func, args, .. } = &bb_data.terminator().kind {
+            match func.ty(body, tcx).kind() {
+                TyKind::FnDef(def_id, _) if ownership::is_explicit_drop(*def_id, tcx) => {}
+                _ => continue,
+            }
+            let location = body.terminator_loc(block);
+            if let Some(pointee) = args
+                .get(0)
+                .and_then(|arg| arg.place())
+                .and_then(|arg| arg.as_local())
+                .and_then(|ptr| ownership::pointee_place(body, ptr, location))
+            {
+                explicit_drops.push((location, pointee));
+            }
+        }
+    }
+    explicit_drops
 }
 
 /// Collect TerminatorKind::Drop
@@ -101,7 +147,8 @@ impl<'tcx> Visitor<'tcx> for AutoDropCollector<'tcx> {
     }
 }
 
-fn is_reachable(from: Location, to: Location, body: &Body<'_>) -> bool {
+/// Whether `to` is reachable from `from` in the CFG, regardless of the loops in the same block.
+pub fn is_reachable(from: Location, to: Location, body: &Body<'_>) -> bool {
     if from.block == to.block {
         return from.statement_index <= to.statement_index;
     }
@@ -124,3 +171,767 @@ fn is_reachable(from: Location, to: Location, body: &Body<'_>) -> bool {
     }
     false
 }
+
+/// Whether `to` is reachable from `from` in the CFG without passing any location in `avoid`,
+/// i.e., `to` is not reached after the locations in `avoid` on every path.
+pub fn is_reachable_avoiding(
+    from: Location,
+    to: Location,
+    avoid: &[Location],
+    body: &Body<'_>,
+) -> bool {
+    let avoided = |block: BasicBlock, after: Option<usize>, before: Option<usize>| {
+        avoid.iter().any(|loc| {
+            loc.block == block
+                && after.map_or(true, |after| loc.statement_index > after)
+                && before.map_or(true, |before| loc.statement_index < before)
+        })
+    };
+    if from.block == to.block && from.statement_index <= to.statement_index {
+        return !avoided(
+            from.block,
+            Some(from.statement_index),
+            Some(to.statement_index),
+        );
+    }
+    if avoided(from.block, Some(from.statement_index), None) {
+        return false;
+    }
+    let mut worklist = body.basic_blocks[from.block]
+        .terminator()
+        .successors()
+        .collect::<Vec<_>>();
+    let mut visited = worklist.iter().copied().collect::<FxHashSet<_>>();
+    while let Some(curr) = worklist.pop() {
+        if curr == to.block {
+            if !avoided(curr, None, Some(to.statement_index)) {
+                return true;
+            }
+            continue;
+        }
+        if avoided(curr, None, None) {
+            continue;
+        }
+        for succ in body.basic_blocks[curr].terminator().successors() {
+            if visited.insert(succ) {
+                worklist.push(succ);
+            }
+        }
+    }
+    false
+}
+
+/// The local and the raw ptrs copied (or cast) from it in the body.
+fn raw_ptr_copies(body: &Body<'_>, raw_ptr: Local) -> FxHashSet<Local> {
+    let mut copies = FxHashSet::default();
+    copies.insert(raw_ptr);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for data in body.basic_blocks.iter() {
+            for statement in &data.statements {
+                let (lhs, operand) = match &statement.kind {
+                    StatementKind::Assign(box (
+                        lhs,
+                        Rvalue::Use(operand) | Rvalue::Cast(_, operand, _),
+                    )) => (lhs, operand),
+                    _ => continue,
+                };
+                let from_copy = operand
+                    .place()
+                    .and_then(|place| place.as_local())
+                    .map_or(false, |local| copies.contains(&local));
+                if from_copy {
+                    if let Some(lhs) = lhs.as_local() {
+                        changed |= copies.insert(lhs);
+                    }
+                }
+            }
+        }
+    }
+    copies
+}
+
+/// The local and the locals it is copied (or cast) from in the body.
+fn copy_sources(body: &Body<'_>, local: Local) -> FxHashSet<Local> {
+    let mut sources = FxHashSet::default();
+    sources.insert(local);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for data in body.basic_blocks.iter() {
+            for statement in &data.statements {
+                let (lhs, operand) = match &statement.kind {
+                    StatementKind::Assign(box (
+                        lhs,
+                        Rvalue::Use(operand) | Rvalue::Cast(_, operand, _),
+                    )) => (lhs, operand),
+                    _ => continue,
+                };
+                let is_source = lhs.as_local().map_or(false, |lhs| sources.contains(&lhs));
+                if is_source {
+                    if let Some(rhs) = operand.place().and_then(|place| place.as_local()) {
+                        changed |= sources.insert(rhs);
+                    }
+                }
+            }
+        }
+    }
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::pointsto::AliasAnalysis;
+    use crate::detector::config::DetectorConfig;
+    use crate::detector::report::Report;
+    use crate::progress::Progress;
+    use crate::test_utils::{with_tcx, write_src};
+    use rustc_middle::ty::{Instance, ParamEnv};
+
+    const REBORROW_SRC: &str = r#"
+pub fn reborrow_mutate() -> i32 {
+    let mut x = vec![1];
+    let r = &mut x;
+    let p = r.as_mut_ptr();
+    r.push(2);
+    drop(r);
+    unsafe { *p }
+}
+pub fn reborrow_mutate_copy() -> i32 {
+    let mut x = [1, 2];
+    let r = &mut x;
+    let p = r.as_mut_ptr();
+    let t = &mut r[1];
+    *t += 1;
+    drop(t);
+    drop(x);
+    unsafe { *p }
+}
+pub fn drop_at_scope_end() -> i32 {
+    let p = {
+        let mut v = vec![1];
+        v.as_mut_ptr()
+    };
+    unsafe { *p }
+}
+pub fn drop_manually() -> i32 {
+    let x = vec![1];
+    let p = x.as_ptr();
+    drop(x);
+    unsafe { *p }
+}
+"#;
+
+    /// Run the use-after-free detector on the fns in the local crate.
+    fn detect_use_after_free(tcx: TyCtxt<'_>) -> Vec<Report> {
+        let instances = tcx
+            .mir_keys(())
+            .iter()
+            .map(|def_id| def_id.to_def_id())
+            .filter(|def_id| tcx.def_kind(def_id).is_fn_like())
+            .map(|def_id| Instance::mono(tcx, def_id))
+            .collect::<Vec<_>>();
+        let progress = Progress::new(true);
+        let mut callgraph = CallGraph::new();
+        callgraph.analyze(instances, tcx, ParamEnv::reveal_all(), &progress);
+        let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+        UseAfterFreeDetector::new(tcx, DetectorConfig::new(&["memory"])).detect(
+            &callgraph,
+            &mut alias_analysis,
+            &progress,
+        )
+    }
+
+    #[test]
+    fn test_use_after_free_reborrow_mutate() {
+        let src = write_src("use-after-free-reborrow", REBORROW_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                // The reallocation by `push` in `reborrow_mutate` is checked by `test_use_after_realloc`.
+                let mut drop_lines = detect_use_after_free(tcx)
+                    .iter()
+                    .filter_map(|report| {
+                        let diagnosis = match report {
+                            Report::UseAfterFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        let (_, drop_span) = diagnosis.split_once("dropped at ")?;
+                        drop_span.split(':').nth(1)?.parse::<usize>().ok()
+                    })
+                    .collect::<Vec<_>>();
+                drop_lines.sort();
+                drop_lines.dedup();
+                // Neither the reborrows nor the array of a `Copy` type are freed.
+                assert_eq!(drop_lines, vec![24, 30]);
+            }),
+        );
+    }
+
+    const RAW_PTR_FNS_SRC: &str = r#"use std::ptr::{self, NonNull};
+pub fn write() -> i32 {
+    let mut slot: *const i32 = ptr::null();
+    let v = vec![1];
+    unsafe { ptr::write(&mut slot, v.as_ptr()) };
+    drop(v);
+    unsafe { *slot }
+}
+pub fn copy_nonoverlapping() -> i32 {
+    let mut slot: *const i32 = ptr::null();
+    let v = vec![1];
+    let src = v.as_ptr();
+    unsafe { ptr::copy_nonoverlapping(&src, &mut slot, 1) };
+    drop(v);
+    unsafe { *slot }
+}
+pub fn nonnull_as_ref() -> i32 {
+    let mut v = vec![1];
+    let p = NonNull::new(v.as_mut_ptr()).unwrap();
+    drop(v);
+    let q: *const i32 = unsafe { p.as_ref() };
+    unsafe { *q }
+}
+pub fn write_null() -> i32 {
+    let mut slot: *const i32 = &1;
+    let v = vec![1];
+    unsafe { ptr::write(&mut slot, ptr::null()) };
+    drop(v);
+    unsafe { *slot }
+}
+"#;
+
+    #[test]
+    fn test_use_after_free_through_raw_ptr_fns() {
+        let src = write_src("use-after-free-raw-ptr-fns", RAW_PTR_FNS_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let mut drop_lines = detect_use_after_free(tcx)
+                    .iter()
+                    .filter_map(|report| {
+                        let diagnosis = match report {
+                            Report::UseAfterFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        let (_, drop_span) = diagnosis.split_once("dropped at ")?;
+                        drop_span.split(':').nth(1)?.parse::<usize>().ok()
+                    })
+                    .collect::<Vec<_>>();
+                drop_lines.sort();
+                drop_lines.dedup();
+                // The null ptr written over the slot points to nothing.
+                assert_eq!(drop_lines, vec![6, 14, 20]);
+            }),
+        );
+    }
+
+    const REALLOC_SRC: &str = r#"
+pub fn push_after_as_mut_ptr(x: u32) -> u32 {
+    let mut v = vec![1];
+    let p = v.as_mut_ptr();
+    v.push(x);
+    unsafe { *p }
+}
+pub fn preallocated(x: u32) -> u32 {
+    let mut v = Vec::with_capacity(4);
+    v.push(1);
+    let p = v.as_slice().as_ptr();
+    v.push(x);
+    unsafe { *p }
+}
+pub fn push_before_as_ptr(x: u32) -> u32 {
+    let mut v = vec![1];
+    v.push(x);
+    let p = v.as_ptr();
+    unsafe { *p }
+}
+pub fn push_other(x: u32) -> u32 {
+    let v = vec![1];
+    let mut w = vec![2];
+    let p = v.as_ptr();
+    w.push(x);
+    unsafe { *p }
+}
+pub fn push_str_after_as_ptr(s: &mut String) -> u8 {
+    let p = s.as_ptr();
+    s.push_str("tail");
+    unsafe { *p }
+}
+"#;
+
+    #[test]
+    fn test_use_after_realloc() {
+        let src = write_src("use-after-realloc", REALLOC_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut reports = detect_use_after_free(tcx)
+                    .iter()
+                    .map(|report| {
+                        let diagnosis = match report {
+                            Report::UseAfterFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        let (derive_span, rest) = diagnosis
+                            .strip_prefix("Raw ptr derived at ")
+                            .and_then(|rest| rest.split_once(" is used at "))
+                            .unwrap();
+                        let (use_span, rest) = rest.split_once(" after `").unwrap();
+                        let (method, rest) = rest.split_once("` at ").unwrap();
+                        (
+                            line(derive_span),
+                            line(use_span),
+                            method.to_owned(),
+                            line(rest),
+                            rest.contains("with_capacity"),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                reports.sort();
+                reports.dedup();
+                // Neither the push before deriving the raw ptr nor the push to another Vec invalidates it.
+                assert_eq!(
+                    reports,
+                    [
+                        (4, 6, "push".to_owned(), 5, false),
+                        (11, 13, "push".to_owned(), 12, true),
+                        (29, 31, "push_str".to_owned(), 30, false),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const CSTRING_SRC: &str = r#"use std::ffi::CString;
+use std::os::raw::c_char;
+extern "C" { fn puts(s: *const c_char) -> i32; }
+pub fn temporary(s: &str) -> i32 {
+    let p = CString::new(s).unwrap().as_ptr();
+    unsafe { puts(p) }
+}
+pub fn escape_to_return(s: &str) -> *const c_char {
+    CString::new(s).unwrap().as_ptr()
+}
+pub fn named(s: &str) -> i32 {
+    let c = CString::new(s).unwrap();
+    let p = c.as_c_str().as_ptr();
+    unsafe { puts(p) }
+}
+pub fn ffi_arg(s: &str) -> i32 {
+    unsafe { puts(CString::new(s).unwrap().as_ptr()) }
+}
+pub fn fmt_time(year: u32) -> *const c_char {
+    let time_str = format!("{:4}\n\0", year);
+    time_str[0..4].as_ptr() as _
+}
+"#;
+
+    #[test]
+    fn test_cstring_as_ptr_after_drop() {
+        let src = write_src("cstring-as-ptr", CSTRING_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut reports = detect_use_after_free(tcx)
+                    .iter()
+                    .filter_map(|report| {
+                        let diagnosis = match report {
+                            Report::UseAfterFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        let (derive_span, rest) = diagnosis
+                            .strip_prefix("Raw ptr from `as_ptr` at ")?
+                            .split_once(") ")?;
+                        let (used, rest) = rest.split_once(" at ")?;
+                        let (use_span, rest) = rest.split_once(" after ")?;
+                        let (dropped, drop_span) = rest.split_once(" is dropped at ")?;
+                        Some((
+                            line(derive_span),
+                            used.to_owned(),
+                            line(use_span),
+                            dropped.to_owned(),
+                            line(drop_span),
+                        ))
+                    })
+                    .collect::<Vec<_>>();
+                reports.sort();
+                reports.dedup();
+                // The named CString outlives the use of the raw ptr,
+                // and the temporary passed directly to the FFI call outlives the call.
+                assert_eq!(
+                    reports,
+                    [
+                        (
+                            5,
+                            "is used".to_owned(),
+                            6,
+                            "the temporary `std::ffi::CString`".to_owned(),
+                            5,
+                        ),
+                        (
+                            9,
+                            "escapes to the return value".to_owned(),
+                            10,
+                            "the temporary `std::ffi::CString`".to_owned(),
+                            10,
+                        ),
+                        (
+                            21,
+                            "escapes to the return value".to_owned(),
+                            22,
+                            "`std::string::String`".to_owned(),
+                            22,
+                        ),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const GUARD_SRC: &str = r#"use std::sync::Mutex;
+
+pub fn read_after_drop(m: &Mutex<u64>) -> u64 {
+    let guard = m.lock().unwrap();
+    let p: *const u64 = &*guard;
+    drop(guard);
+    unsafe { *p }
+}
+
+pub fn field_after_drop(m: &Mutex<(u64, u64)>) -> u64 {
+    let mut guard = m.lock().unwrap();
+    let p: *mut u64 = &mut guard.1;
+    drop(guard);
+    unsafe { *p }
+}
+
+pub fn relock(m: &Mutex<u64>) -> u64 {
+    let guard = m.lock().unwrap();
+    let mut p: *const u64 = &*guard;
+    let n = unsafe { *p };
+    drop(guard);
+    let guard = m.lock().unwrap();
+    p = &*guard;
+    let m = unsafe { *p };
+    drop(guard);
+    n + m
+}
+"#;
+
+    #[test]
+    fn test_use_after_unlock() {
+        let src = write_src("use-after-unlock", GUARD_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut reports = detect_use_after_free(tcx)
+                    .iter()
+                    .filter_map(|report| {
+                        let diagnosis = match report {
+                            Report::UseAfterFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        let (derive_span, rest) = diagnosis
+                            .strip_prefix("Raw ptr derived from the lockguard at ")?
+                            .split_once(") is used at ")?;
+                        let (use_span, unlock_span) =
+                            rest.split_once(" after the lockguard is unlocked at ")?;
+                        Some((line(derive_span), line(use_span), line(unlock_span)))
+                    })
+                    .collect::<Vec<_>>();
+                reports.sort();
+                // The raw ptr re-derived from the second lockguard is valid at its use.
+                assert_eq!(reports, [(5, 7, 6), (12, 14, 13)]);
+            }),
+        );
+    }
+
+    const DOUBLE_FREE_SRC: &str = r#"#![feature(vec_into_raw_parts)]
+use std::rc::Rc;
+pub fn double_from_raw(x: u32, fail: bool) -> u32 {
+    let p = Box::into_raw(Box::new(x));
+    if fail {
+        unsafe { drop(Box::from_raw(p)) };
+    }
+    unsafe { *Box::from_raw(p) }
+}
+pub fn single_from_raw(x: u32, fail: bool) -> u32 {
+    let p = Box::into_raw(Box::new(x));
+    if fail {
+        unsafe { drop(Box::from_raw(p)) };
+        return 0;
+    }
+    unsafe { *Box::from_raw(p) }
+}
+pub fn releak(x: u32, n: u32) -> u32 {
+    let mut p = Box::into_raw(Box::new(x));
+    for _ in 0..n {
+        let mut b = unsafe { Box::from_raw(p) };
+        *b += 1;
+        p = Box::into_raw(b);
+    }
+    unsafe { *Box::from_raw(p) }
+}
+pub fn rc_increment(x: u32) {
+    let p = Rc::into_raw(Rc::new(x));
+    unsafe {
+        Rc::increment_strong_count(p);
+        drop(Rc::from_raw(p));
+        drop(Rc::from_raw(p));
+    }
+}
+fn release(p: *mut u32) {
+    unsafe { drop(Box::from_raw(p)) };
+}
+pub fn cross_function(x: u32) -> u32 {
+    let p = Box::into_raw(Box::new(x));
+    release(p);
+    unsafe { *p }
+}
+pub fn vec_from_raw_parts(v: Vec<u32>) {
+    let (p, len, cap) = v.into_raw_parts();
+    unsafe { drop(Vec::from_raw_parts(p, len, cap)) };
+    release(p);
+}
+"#;
+
+    /// Run the double-free detector on the fns in the local crate.
+    fn detect_double_free(tcx: TyCtxt<'_>) -> Vec<Report> {
+        let instances = tcx
+            .mir_keys(())
+            .iter()
+            .map(|def_id| def_id.to_def_id())
+            .filter(|def_id| tcx.def_kind(def_id).is_fn_like())
+            .map(|def_id| Instance::mono(tcx, def_id))
+            .collect::<Vec<_>>();
+        let progress = Progress::new(true);
+        let mut callgraph = CallGraph::new();
+        callgraph.analyze(instances, tcx, ParamEnv::reveal_all(), &progress);
+        let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+        DoubleFreeDetector::new(tcx, DetectorConfig::new(&["memory"])).detect(
+            &callgraph,
+            &mut alias_analysis,
+            &progress,
+        )
+    }
+
+    #[test]
+    fn test_double_free() {
+        let src = write_src("double-free", DOUBLE_FREE_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut reports = detect_double_free(tcx)
+                    .iter()
+                    .map(|report| {
+                        let diagnosis = match report {
+                            Report::DoubleFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        let spans = diagnosis
+                            .split(" at ")
+                            .skip(1)
+                            .map(line)
+                            .collect::<Vec<_>>();
+                        (diagnosis.contains(" is used at "), spans)
+                    })
+                    .collect::<Vec<_>>();
+                reports.sort();
+                // Neither the reconstructions on exclusive paths, nor the reconstructions of
+                // the ownership leaked again by `into_raw` or `increment_strong_count` are double frees.
+                assert_eq!(
+                    reports,
+                    [
+                        (false, vec![4, 6, 8]),
+                        (false, vec![44, 45, 46]),
+                        (true, vec![39, 41, 40]),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const INVALID_FREE_SRC: &str = r#"use std::mem::{self, MaybeUninit};
+use std::ptr::addr_of_mut;
+pub struct Obj {
+    a: Vec<i32>,
+    b: bool,
+}
+pub fn uninit() {
+    #[allow(invalid_value, deprecated)]
+    let _obj: Vec<i32> = unsafe { mem::uninitialized() };
+}
+pub fn assume() {
+    let uninit = MaybeUninit::<Vec<i32>>::uninit();
+    unsafe { uninit.assume_init() };
+}
+pub fn assume_write() {
+    let mut uninit = MaybeUninit::<Vec<i32>>::uninit();
+    uninit.write(Vec::new());
+    unsafe { uninit.assume_init() };
+}
+pub fn assume_ptr_write_fields() {
+    let mut uninit = MaybeUninit::<Obj>::uninit();
+    unsafe {
+        let ptr = uninit.as_mut_ptr();
+        addr_of_mut!((*ptr).a).write(Vec::new());
+        std::ptr::write(addr_of_mut!((*ptr).b), true);
+        uninit.assume_init();
+    }
+}
+pub fn assume_ptr_write_whole() {
+    let mut uninit = MaybeUninit::<Obj>::uninit();
+    unsafe {
+        uninit.as_mut_ptr().write(Obj { a: Vec::new(), b: true });
+        uninit.assume_init();
+    }
+}
+pub fn assume_ptr_write_partial() {
+    let mut uninit = MaybeUninit::<Obj>::uninit();
+    unsafe {
+        let ptr = uninit.as_mut_ptr();
+        addr_of_mut!((*ptr).b).write(true);
+        uninit.assume_init();
+    }
+}
+pub fn assume_ptr_write_one_path(c: bool) {
+    let mut uninit = MaybeUninit::<Obj>::uninit();
+    unsafe {
+        let ptr = uninit.as_mut_ptr();
+        addr_of_mut!((*ptr).a).write(Vec::new());
+        if c {
+            addr_of_mut!((*ptr).b).write(true);
+        }
+        uninit.assume_init();
+    }
+}
+"#;
+
+    #[test]
+    fn test_invalid_free_field_writes() {
+        let src = write_src("invalid-free-field-writes", INVALID_FREE_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let instances = tcx
+                    .mir_keys(())
+                    .iter()
+                    .map(|def_id| def_id.to_def_id())
+                    .filter(|def_id| tcx.def_kind(def_id).is_fn_like())
+                    .map(|def_id| Instance::mono(tcx, def_id))
+                    .collect::<Vec<_>>();
+                let progress = Progress::new(true);
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(instances, tcx, ParamEnv::reveal_all(), &progress);
+                let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+                let reports = InvalidFreeDetector::new(tcx, DetectorConfig::new(&["memory"]))
+                    .detect(&callgraph, &mut alias_analysis, &progress);
+                let mut lines = reports
+                    .iter()
+                    .map(|report| {
+                        let diagnosis = match report {
+                            Report::InvalidFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        let (_, span) = diagnosis.split_once(" at ").unwrap();
+                        span.split(':').nth(1).unwrap().parse::<usize>().unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                lines.sort();
+                lines.dedup();
+                // Writing every field or the whole value through `as_mut_ptr` initializes it,
+                // but writing one field (on a path) does not.
+                assert_eq!(lines, vec![9, 12, 37, 45]);
+            }),
+        );
+    }
+
+    const ZEROED_SRC: &str = r#"use std::mem::{self, MaybeUninit};
+use std::ptr::NonNull;
+
+pub fn zeroed_usize() -> usize {
+    unsafe { mem::zeroed::<usize>() }
+}
+
+pub fn zeroed_ref() -> &'static i32 {
+    unsafe { mem::zeroed::<&'static i32>() }
+}
+
+pub struct Inner {
+    pub n: usize,
+    pub b: Box<i32>,
+}
+
+pub struct Outer {
+    pub inner: Inner,
+}
+
+pub fn zeroed_nested_box() -> Outer {
+    unsafe { mem::zeroed::<Outer>() }
+}
+
+pub fn maybe_uninit_zeroed_box() -> Box<i32> {
+    unsafe { MaybeUninit::<Box<i32>>::zeroed().assume_init() }
+}
+
+pub fn zeroed_option_ref() -> Option<&'static i32> {
+    unsafe { mem::zeroed::<Option<&'static i32>>() }
+}
+
+pub fn zeroed_option_non_null() -> Option<NonNull<u8>> {
+    unsafe { MaybeUninit::<Option<NonNull<u8>>>::zeroed().assume_init() }
+}
+"#;
+
+    #[test]
+    fn test_invalid_free_zeroed() {
+        let src = write_src("invalid-free-zeroed", ZEROED_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let instances = tcx
+                    .mir_keys(())
+                    .iter()
+                    .map(|def_id| def_id.to_def_id())
+                    .filter(|def_id| tcx.def_kind(def_id).is_fn_like())
+                    .map(|def_id| Instance::mono(tcx, def_id))
+                    .collect::<Vec<_>>();
+                let progress = Progress::new(true);
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(instances, tcx, ParamEnv::reveal_all(), &progress);
+                let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+                let reports = InvalidFreeDetector::new(tcx, DetectorConfig::new(&["memory"]))
+                    .detect(&callgraph, &mut alias_analysis, &progress);
+                let mut lines = reports
+                    .iter()
+                    .map(|report| {
+                        let diagnosis = match report {
+                            Report::InvalidFree(content) => &content.diagnosis,
+                            _ => panic!("unexpected report {:?}", report),
+                        };
+                        assert!(diagnosis.contains("is invalid when zeroed"), "{diagnosis}");
+                        let (_, span) = diagnosis.split_once(" at ").unwrap();
+                        span.split(':').nth(1).unwrap().parse::<usize>().unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                lines.sort();
+                lines.dedup();
+                // Integers and niche-optimized Options are valid when zeroed.
+                assert_eq!(lines, vec![9, 22, 26]);
+            }),
+        );
+    }
+}
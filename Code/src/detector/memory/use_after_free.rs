@@ -3,57 +3,81 @@
 /// raw ptr assigned to other place
 /// drop(place)
 /// after drop, raw ptr or its assignee is used
+/// raw ptr to the buffer of a Vec or String is used after the buffer may be reallocated, e.g., by push
+/// raw ptr derived from the data of a lockguard is used after the lockguard is unlocked
 extern crate rustc_data_structures;
 extern crate rustc_index;
 extern crate rustc_middle;
 
-use rustc_data_structures::fx::FxHashSet;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 use rustc_index::Idx;
 use rustc_middle::mir::visit::Visitor;
-use rustc_middle::mir::{Body, HasLocalDecls, Local, Location, Place};
-use rustc_middle::ty::{Instance, TyCtxt};
+use rustc_middle::mir::{
+    Body, HasLocalDecls, Local, Location, Operand, Place, PlaceRef, Rvalue, StatementKind,
+    TerminatorKind, VarDebugInfoContents,
+};
+use rustc_middle::ty::{Instance, ParamEnv, TyCtxt, TyKind};
 
 use petgraph::visit::IntoNodeReferences;
 
-use super::{collect_manual_drop, is_reachable, AutoDropCollector};
-use crate::analysis::callgraph::CallGraphNode;
+use super::{
+    collect_explicit_drop, collect_manual_drop, copy_sources, is_reachable, is_reachable_avoiding,
+    raw_ptr_copies, AutoDropCollector,
+};
+use crate::analysis::callgraph::{CallGraphNode, InstanceId};
 use crate::analysis::defuse::find_uses;
-use crate::analysis::pointsto::{ConstraintNode, PointsToMap};
+use crate::analysis::pointsto::{AliasId, ApproximateAliasKind, ConstraintNode, PointsToMap};
 use crate::analysis::{callgraph::CallGraph, pointsto::AliasAnalysis};
+use crate::detector::config::DetectorConfig;
 use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::lock::{GuardNewtypes, LockGuardCollector, LockGuardTy};
+use crate::interest::memory::ownership;
+use crate::progress::Progress;
 
 pub struct UseAfterFreeDetector<'tcx> {
     tcx: TyCtxt<'tcx>,
+    /// The enabled detectors and their parameters.
+    config: DetectorConfig,
 }
 
 impl<'tcx> UseAfterFreeDetector<'tcx> {
-    pub fn new(tcx: TyCtxt<'tcx>) -> Self {
-        Self { tcx }
+    pub fn new(tcx: TyCtxt<'tcx>, config: DetectorConfig) -> Self {
+        Self { tcx, config }
     }
 
     pub fn detect(
         &self,
         callgraph: &CallGraph<'tcx>,
         alias_analysis: &mut AliasAnalysis<'_, 'tcx>,
+        progress: &Progress,
     ) -> Vec<Report> {
         let mut reports = Vec::new();
         let manual_drops = collect_manual_drop(callgraph, self.tcx);
-        for (instance_id, node) in callgraph.graph.node_references() {
+        let total = callgraph.graph.node_count();
+        for (processed, (instance_id, node)) in callgraph.graph.node_references().enumerate() {
+            progress.update(processed + 1, total, "instances");
             let instance = match node {
-                CallGraphNode::WithBody(instance) => instance,
-                CallGraphNode::WithoutBody(_) => continue,
+                CallGraphNode::WithBody(instance) if callgraph.is_focused(instance_id) => instance,
+                _ => continue,
             };
             let local_manual_drops = manual_drops
                 .get(&instance_id)
                 .map(Vec::as_slice)
                 .unwrap_or(&[]);
-            reports.extend(self.detect_instance(instance, alias_analysis, local_manual_drops));
+            reports.extend(self.detect_instance(
+                instance_id,
+                instance,
+                alias_analysis,
+                local_manual_drops,
+            ));
         }
+        self.config.retain(&mut reports);
         reports
     }
 
     fn detect_instance(
         &self,
+        instance_id: InstanceId,
         instance: &Instance<'tcx>,
         alias_analysis: &mut AliasAnalysis<'_, 'tcx>,
         manual_drops: &[(Location, Place<'tcx>)],
@@ -70,8 +94,30 @@ impl<'tcx> UseAfterFreeDetector<'tcx> {
         diagnosis_set.extend(detect_escape_to_return_or_param(
             pts, &drops, body, self.tcx,
         ));
-        diagnosis_set.extend(detect_use_after_drop(&raw_ptrs, pts, &drops, body));
-        diagnosis_set.into_iter().map(|diagnosis| Report::UseAfterFree(ReportContent::new("UseAfterFree".to_owned(), "Possibly".to_owned(), diagnosis, "Raw ptr is used or escapes the current function after the pointed value is dropped".to_owned()))).collect::<Vec<_>>()
+        diagnosis_set.extend(detect_use_after_drop(
+            &raw_ptrs, pts, &drops, body, self.tcx,
+        ));
+        let mut reports = diagnosis_set.into_iter().map(|diagnosis| Report::UseAfterFree(ReportContent::new("UseAfterFree".to_owned(), "Possibly".to_owned(), diagnosis, "Raw ptr is used or escapes the current function after the pointed value is dropped".to_owned()))).collect::<Vec<_>>();
+        let realloc_diagnosis_set =
+            detect_use_after_realloc(instance_id, instance, body, alias_analysis, self.tcx);
+        let as_ptr_diagnosis_set = detect_as_ptr_after_drop(
+            alias_analysis.get_or_insert_pts(instance.def_id(), body),
+            &drops,
+            body,
+            self.tcx,
+        );
+        reports.extend(as_ptr_diagnosis_set.into_iter().map(|diagnosis| Report::UseAfterFree(ReportContent::new("UseAfterFree".to_owned(), "Possibly".to_owned(), diagnosis, "Raw ptr to the C string of a CString or the buffer of a Vec or String is used or escapes the current function after the owner is dropped".to_owned()))));
+        reports.extend(realloc_diagnosis_set.into_iter().map(|diagnosis| Report::UseAfterFree(ReportContent::new("UseAfterFree".to_owned(), "Possibly".to_owned(), diagnosis, "Raw ptr to the buffer of a Vec or String is used after a call that may reallocate the buffer or drop its elements".to_owned()))));
+        let unlock_diagnosis_set = detect_use_after_unlock(
+            instance_id,
+            instance,
+            &raw_ptrs,
+            alias_analysis.get_or_insert_pts(instance.def_id(), body),
+            body,
+            self.tcx,
+        );
+        reports.extend(unlock_diagnosis_set.into_iter().map(|diagnosis| Report::UseAfterFree(ReportContent::new("UseAfterFree".to_owned(), "Possibly".to_owned(), diagnosis, "Raw ptr to the data protected by a lock is used after the lockguard is unlocked, racing with the other holders of the lock".to_owned()))));
+        reports
     }
 
     fn collect_raw_ptrs(&self, body: &Body<'tcx>) -> FxHashSet<Local> {
@@ -96,10 +142,33 @@ impl<'tcx> UseAfterFreeDetector<'tcx> {
         collector.visit_body(body);
         let mut drops = collector.finish();
         drops.extend(manual_drops.iter().cloned());
+        drops.extend(collect_explicit_drop(body, self.tcx));
         drops
     }
 }
 
+/// Whether dropping `drop_place` frees `pointee`.
+/// The dropped value is `pointee` itself or moved from it,
+/// i.e., Alloc(pointee) is in pts(drop_place), rather than merely aliasing `pointee`
+/// through an intermediate copy, e.g., `drop(r)` for `r = &mut pointee`.
+/// Dropping a value of a `Copy` type or a reference frees nothing.
+fn drops_pointee<'tcx>(
+    drop_place: &Place<'tcx>,
+    pointee: PlaceRef<'tcx>,
+    pts: &PointsToMap<'tcx>,
+    body: &Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> bool {
+    let ty = drop_place.ty(body, tcx).ty;
+    if ty.is_ref() || ty.is_copy_modulo_regions(tcx, ParamEnv::reveal_all()) {
+        return false;
+    }
+    drop_place.as_ref() == pointee
+        || pts
+            .get(&ConstraintNode::Place(drop_place.as_ref()))
+            .map_or(false, |ptes| ptes.contains(&ConstraintNode::Alloc(pointee)))
+}
+
 /// Collect raw ptrs escaping to Globals.
 /// Alloc(ptr) pointed to by ConstantDeref implies Place(ptr) escapes to Global.
 /// 1. forall c is ConstantDeref, collect pts(c) into S
@@ -151,7 +220,7 @@ fn detect_escape_to_global<'tcx>(
                 _ => continue,
             };
             for (location, drop) in drops.iter() {
-                if drop.as_ref() == *place {
+                if drops_pointee(drop, *place, pts, body, tcx) {
                     let escape_span = match escape {
                         ConstraintNode::Place(ptr) => body.local_decls[ptr.local].source_info.span,
                         _ => continue,
@@ -221,7 +290,7 @@ fn detect_escape_to_return_or_param<'tcx>(
                     if body.basic_blocks[location.block].is_cleanup {
                         continue;
                     }
-                    if drop_place.as_ref() == *pte_place {
+                    if drops_pointee(drop_place, *pte_place, pts, body, tcx) {
                         let ptr_span = body.local_decls[ptr.local].source_info.span;
                         let diagnosis = format!("Escape to Param/Return: Raw ptr {:?} at {:?} escapes to {:?} but pointee is dropped at {:?}", ptr, ptr_span, alias_with_params, body.source_info(*location).span);
                         diagnosis_set.insert(diagnosis);
@@ -240,6 +309,7 @@ fn detect_use_after_drop<'tcx>(
     pts: &PointsToMap<'tcx>,
     drops: &[(Location, Place<'tcx>)],
     body: &Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
 ) -> FxHashSet<String> {
     let mut diagnosis_set = FxHashSet::default();
     for raw_ptr in raw_ptrs {
@@ -255,11 +325,17 @@ fn detect_use_after_drop<'tcx>(
                 _ => continue,
             };
             for (drop_loc, drop_place) in drops {
-                if drop_place.as_ref() != *pte {
+                if !drops_pointee(drop_place, *pte, pts, body, tcx) {
+                    continue;
+                }
+                // Dropping a lockguard unlocks the lock rather than frees the data,
+                // see `detect_use_after_unlock`.
+                if LockGuardTy::from_local_ty(drop_place.ty(body, tcx).ty, tcx).is_some() {
                     continue;
                 }
                 for use_loc in &raw_ptr_use_locations {
-                    if is_reachable(*drop_loc, *use_loc, body) {
+                    // e.g., drop_in_place(raw_ptr) uses raw_ptr to drop its pointee
+                    if use_loc != drop_loc && is_reachable(*drop_loc, *use_loc, body) {
                         let diagnosis = format!(
                             "Raw ptr is used at {:?} after dropped at {:?}",
                             body.source_info(*use_loc).span,
@@ -273,3 +349,443 @@ fn detect_use_after_drop<'tcx>(
     }
     diagnosis_set
 }
+
+// p = &*guard: p -> guard
+// drop(guard) reaches use(p) without re-deriving p in between
+fn detect_use_after_unlock<'tcx>(
+    instance_id: InstanceId,
+    instance: &Instance<'tcx>,
+    raw_ptrs: &FxHashSet<Local>,
+    pts: &PointsToMap<'tcx>,
+    body: &Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> FxHashSet<String> {
+    let mut diagnosis_set = FxHashSet::default();
+    let guard_newtypes = GuardNewtypes::default();
+    let mut collector = LockGuardCollector::new(
+        instance_id,
+        instance,
+        body,
+        tcx,
+        ParamEnv::reveal_all(),
+        &guard_newtypes,
+    );
+    collector.analyze();
+    if collector.lockguards.is_empty() {
+        return diagnosis_set;
+    }
+    // raw_ptr -> the assignments of the raw ptr and the locals it is copied from
+    let assignments = raw_ptrs
+        .iter()
+        .map(|raw_ptr| {
+            let sources = copy_sources(body, *raw_ptr);
+            (*raw_ptr, assignment_locations(body, &sources))
+        })
+        .collect::<FxHashMap<_, _>>();
+    for (lockguard_id, info) in &collector.lockguards {
+        // The lockguard and the lockguards it is moved from, e.g., `drop(guard)` drops a temp moved from `guard`.
+        let lockguards = copy_sources(body, lockguard_id.local);
+        for unlock_loc in &info.kill_locs {
+            if !is_unlock(body, *unlock_loc, tcx) {
+                continue;
+            }
+            for raw_ptr in raw_ptrs {
+                if !points_into(pts, *raw_ptr, &lockguards) {
+                    continue;
+                }
+                let assignments = &assignments[raw_ptr];
+                // The borrow of the data before the unlock, e.g., `&*guard` or `&mut guard.field`.
+                let derivation = assignments.iter().rev().find(|loc| {
+                    is_derivation(body, **loc, pts, &lockguards)
+                        && is_reachable(**loc, *unlock_loc, body)
+                });
+                let derivation = match derivation {
+                    Some(derivation) => derivation,
+                    None => continue,
+                };
+                for use_loc in find_uses(body, *raw_ptr) {
+                    // A re-derivation in between, e.g., from a second lockguard, makes the raw ptr valid again.
+                    if use_loc != *unlock_loc
+                        && is_reachable_avoiding(*unlock_loc, use_loc, assignments, body)
+                    {
+                        diagnosis_set.insert(format!(
+                            "Raw ptr derived from the lockguard at {:?} is used at {:?} after the lockguard is unlocked at {:?}",
+                            body.source_info(*derivation).span,
+                            body.source_info(use_loc).span,
+                            body.source_info(*unlock_loc).span
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    diagnosis_set
+}
+
+/// The locations assigning the locals, sorted.
+fn assignment_locations(body: &Body<'_>, locals: &FxHashSet<Local>) -> Vec<Location> {
+    let mut locations = Vec::new();
+    for (block, data) in body.basic_blocks.iter_enumerated() {
+        for (statement_index, statement) in data.statements.iter().enumerate() {
+            if let StatementKind::Assign(box (lhs, _)) = &statement.kind {
+                if lhs.as_local().map_or(false, |lhs| locals.contains(&lhs)) {
+                    locations.push(Location {
+                        block,
+                        statement_index,
+                    });
+                }
+            }
+        }
+        if let TerminatorKind::Call { destination, .. } = &data.terminator().kind {
+            if destination
+                .as_local()
+                .map_or(false, |dest| locals.contains(&dest))
+            {
+                locations.push(body.terminator_loc(block));
+            }
+        }
+    }
+    locations
+}
+
+/// Whether the assignment at `location` borrows (or derefs) the data of the lockguards.
+fn is_derivation<'tcx>(
+    body: &Body<'tcx>,
+    location: Location,
+    pts: &PointsToMap<'tcx>,
+    lockguards: &FxHashSet<Local>,
+) -> bool {
+    let data = &body.basic_blocks[location.block];
+    let lhs = match data.statements.get(location.statement_index) {
+        Some(statement) => match &statement.kind {
+            StatementKind::Assign(box (lhs, Rvalue::Ref(..) | Rvalue::AddressOf(..))) => lhs,
+            _ => return false,
+        },
+        None => match &data.terminator().kind {
+            TerminatorKind::Call { destination, .. } => destination,
+            _ => return false,
+        },
+    };
+    points_into(pts, lhs.local, lockguards)
+}
+
+/// Whether the ptr points to the lockguards or into their data, e.g., `(*_6).0` for `_6 = deref_mut(&mut guard)`.
+fn points_into(pts: &PointsToMap<'_>, ptr: Local, lockguards: &FxHashSet<Local>) -> bool {
+    let points_to = |ptr: Local| {
+        pts.get(&ConstraintNode::Place(Place::from(ptr).as_ref()))
+            .into_iter()
+            .flatten()
+    };
+    points_to(ptr).any(|pte| match pte {
+        ConstraintNode::Place(pte) | ConstraintNode::Alloc(pte) => {
+            lockguards.contains(&pte.local)
+                || (!pte.projection.is_empty()
+                    && points_to(pte.local).any(|pte| {
+                        matches!(pte, ConstraintNode::Place(pte) | ConstraintNode::Alloc(pte) if lockguards.contains(&pte.local))
+                    }))
+        }
+        _ => false,
+    })
+}
+
+/// Whether the lockguard is unlocked at `location`, i.e., dropped automatically or explicitly,
+/// rather than moved into another lockguard or a `ManuallyDrop`.
+fn is_unlock<'tcx>(body: &Body<'tcx>, location: Location, tcx: TyCtxt<'tcx>) -> bool {
+    if location != body.terminator_loc(location.block) {
+        return false;
+    }
+    match &body[location.block].terminator().kind {
+        TerminatorKind::Drop { .. } => true,
+        TerminatorKind::Call { func, .. } => match func.ty(body, tcx).kind() {
+            TyKind::FnDef(def_id, _) => {
+                let path = tcx.def_path_str(*def_id);
+                path == "std::mem::drop"
+                    || path == "core::mem::drop"
+                    || ownership::is_explicit_drop(*def_id, tcx)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The local of the first arg of a call, e.g., the receiver `r` of `Vec::push(r, x)`.
+fn arg0_local(args: &[Operand<'_>]) -> Option<Local> {
+    args.get(0)
+        .and_then(|arg| arg.place())
+        .and_then(|place| place.as_local())
+}
+
+/// Raw ptr to the buffer of a Vec or String is used after a call that may reallocate the buffer
+/// (or drop its elements), e.g., `let p = v.as_mut_ptr(); v.push(x); unsafe { *p }`.
+/// 1. Find p = as_ptr(r1), where r1 is the receiver or is borrowed from it, e.g., by `as_slice` or `deref`,
+///    and the owners pointed to by the receiver.
+/// 2. Find the invalidating calls, e.g., push(r2, x), where r2 points to an owner (or aliases the receiver).
+/// 3. The invalidating call is reachable from the derivation, and a use of p (or its copies) from the call.
+///
+/// An owner preallocated by `with_capacity` is still reported, since the capacity may be exceeded.
+fn detect_use_after_realloc<'tcx>(
+    instance_id: InstanceId,
+    instance: &Instance<'tcx>,
+    body: &Body<'tcx>,
+    alias_analysis: &mut AliasAnalysis<'_, 'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> FxHashSet<String> {
+    let mut diagnosis_set = FxHashSet::default();
+    // (location, raw ptr, receiver)
+    let mut derivations = Vec::new();
+    // (location, method, receiver)
+    let mut invalidations = Vec::new();
+    // borrow -> the receiver borrowed from
+    let mut borrows = FxHashMap::default();
+    let mut preallocated = FxHashSet::default();
+    for (block, data) in body.basic_blocks.iter_enumerated() {
+        if data.is_cleanup {
+            continue;
+        }
+        let (func, args, destination) = match &data.terminator().kind {
+            TerminatorKind::Call {
+                func,
+                args,
+                destination,
+                ..
+            } => (func, args, destination),
+            _ => continue,
+        };
+        let (def_id, substs) = match func.ty(body, tcx).kind() {
+            TyKind::FnDef(def_id, substs) => (*def_id, *substs),
+            _ => continue,
+        };
+        let location = body.terminator_loc(block);
+        let dest = match destination.as_local() {
+            Some(dest) => dest,
+            None => continue,
+        };
+        if ownership::is_with_capacity(def_id, tcx) {
+            preallocated.insert(dest);
+            continue;
+        }
+        let receiver = match arg0_local(args) {
+            Some(receiver) => receiver,
+            None => continue,
+        };
+        if ownership::is_buffer_as_ptr(def_id, tcx) {
+            derivations.push((location, dest, receiver));
+        } else if ownership::is_buffer_borrow(def_id, substs, tcx) {
+            borrows.insert(dest, receiver);
+        } else if let Some(method) = ownership::buffer_invalidation(def_id, substs, tcx) {
+            invalidations.push((location, method, receiver));
+        }
+    }
+    if derivations.is_empty() || invalidations.is_empty() {
+        return diagnosis_set;
+    }
+    for (derive_loc, raw_ptr, receiver) in derivations {
+        let mut receiver = receiver;
+        // Bounded by the number of borrows in case of a cycle.
+        for _ in 0..borrows.len() {
+            match borrows.get(&receiver) {
+                Some(borrowed_from) => receiver = *borrowed_from,
+                None => break,
+            }
+        }
+        let owners = alias_analysis
+            .get_or_insert_pts(instance.def_id(), body)
+            .get(&ConstraintNode::Place(Place::from(receiver).as_ref()))
+            .map(|ptes| {
+                ptes.iter()
+                    .filter_map(|pte| match pte {
+                        ConstraintNode::Place(place) if place.projection.is_empty() => {
+                            Some(place.local)
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let use_locations = raw_ptr_copies(body, raw_ptr)
+            .into_iter()
+            .flat_map(|local| find_uses(body, local))
+            .collect::<FxHashSet<_>>();
+        for (invalidate_loc, method, invalidated) in &invalidations {
+            if *invalidate_loc == derive_loc || !is_reachable(derive_loc, *invalidate_loc, body) {
+                continue;
+            }
+            let aid_invalidated = AliasId {
+                instance_id,
+                local: *invalidated,
+            };
+            let aliased_owners = owners
+                .iter()
+                .filter(|owner| {
+                    let aid_owner = AliasId {
+                        instance_id,
+                        local: **owner,
+                    };
+                    alias_analysis.points_to(aid_invalidated, aid_owner)
+                        > ApproximateAliasKind::Unlikely
+                })
+                .collect::<Vec<_>>();
+            // The owners behind references, e.g., `s: &mut String`, are not locals,
+            // so the receivers are checked to alias with each other instead.
+            let aid_receiver = AliasId {
+                instance_id,
+                local: receiver,
+            };
+            if aliased_owners.is_empty()
+                && alias_analysis.alias(aid_receiver, aid_invalidated)
+                    <= ApproximateAliasKind::Unlikely
+            {
+                continue;
+            }
+            let caveat = if aliased_owners
+                .iter()
+                .any(|owner| preallocated.contains(*owner))
+            {
+                ", though the buffer is preallocated by `with_capacity` and only reallocated if the capacity is exceeded"
+            } else {
+                ""
+            };
+            for use_loc in &use_locations {
+                if use_loc == invalidate_loc || !is_reachable(*invalidate_loc, *use_loc, body) {
+                    continue;
+                }
+                let diagnosis = format!(
+                    "Raw ptr derived at {:?} is used at {:?} after `{}` at {:?} may reallocate the buffer{}",
+                    body.source_info(derive_loc).span,
+                    body.source_info(*use_loc).span,
+                    method,
+                    body.source_info(*invalidate_loc).span,
+                    caveat
+                );
+                diagnosis_set.insert(diagnosis);
+            }
+        }
+    }
+    diagnosis_set
+}
+
+/// Raw ptr to the C string of a CString (or the buffer of a Vec or String) is used after the owner is dropped,
+/// e.g., `let p = CString::new(s).unwrap().as_ptr(); unsafe { puts(p) }`,
+/// where the temporary CString is dropped at the end of the statement.
+/// 1. Find p = CStr::as_ptr(r), where r is borrowed from the CString, e.g., by `deref` or `as_c_str`,
+///    (or p = str::as_ptr(r), etc.) and the owners pointed to by the borrowed receiver.
+/// 2. Find the drop of an owner reachable from the derivation.
+/// 3. A use of p (or its copies) is reachable from the drop, including passing it to an FFI call
+///    and returning it.
+fn detect_as_ptr_after_drop<'tcx>(
+    pts: &PointsToMap<'tcx>,
+    drops: &[(Location, Place<'tcx>)],
+    body: &Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> FxHashSet<String> {
+    let mut diagnosis_set = FxHashSet::default();
+    // (location, raw ptr, receiver)
+    let mut derivations = Vec::new();
+    // borrow -> the receiver borrowed from
+    let mut borrows = FxHashMap::default();
+    for (block, data) in body.basic_blocks.iter_enumerated() {
+        if data.is_cleanup {
+            continue;
+        }
+        let (func, args, destination) = match &data.terminator().kind {
+            TerminatorKind::Call {
+                func,
+                args,
+                destination,
+                ..
+            } => (func, args, destination),
+            _ => continue,
+        };
+        let (def_id, substs) = match func.ty(body, tcx).kind() {
+            TyKind::FnDef(def_id, substs) => (*def_id, *substs),
+            _ => continue,
+        };
+        let (dest, receiver) = match (destination.as_local(), arg0_local(args)) {
+            (Some(dest), Some(receiver)) => (dest, receiver),
+            _ => continue,
+        };
+        if ownership::is_cstr_as_ptr(def_id, tcx) || ownership::is_buffer_as_ptr(def_id, tcx) {
+            derivations.push((body.terminator_loc(block), dest, receiver));
+        } else if ownership::is_cstring_borrow(def_id, substs, tcx)
+            || ownership::is_buffer_borrow(def_id, substs, tcx)
+        {
+            borrows.insert(dest, receiver);
+        }
+    }
+    for (derive_loc, raw_ptr, receiver) in derivations {
+        let mut receiver = receiver;
+        // Bounded by the number of borrows in case of a cycle.
+        for _ in 0..borrows.len() {
+            match borrows.get(&receiver) {
+                Some(borrowed_from) => receiver = *borrowed_from,
+                None => break,
+            }
+        }
+        let owners = match pts.get(&ConstraintNode::Place(Place::from(receiver).as_ref())) {
+            Some(ptes) => ptes
+                .iter()
+                .filter_map(|pte| match pte {
+                    ConstraintNode::Place(place) if place.projection.is_empty() => Some(*place),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            None => continue,
+        };
+        let use_locations = raw_ptr_copies(body, raw_ptr)
+            .into_iter()
+            .flat_map(|local| find_uses(body, local))
+            .collect::<FxHashSet<_>>();
+        for (drop_loc, drop_place) in drops {
+            if body.basic_blocks[drop_loc.block].is_cleanup
+                || !is_reachable(derive_loc, *drop_loc, body)
+            {
+                continue;
+            }
+            let owner = match owners
+                .iter()
+                .find(|owner| drops_pointee(drop_place, **owner, pts, body, tcx))
+            {
+                Some(owner) => owner,
+                None => continue,
+            };
+            // `local_info` is unavailable for cross-crate bodies, so the user variables are
+            // told apart from the temporaries by their debug info.
+            let is_user_variable = body.var_debug_info.iter().any(|info| {
+                matches!(info.value, VarDebugInfoContents::Place(place) if place.local == owner.local)
+            });
+            let owner_ty = body.local_decls[owner.local].ty;
+            let dropped = if is_user_variable {
+                format!("`{}` is dropped", owner_ty)
+            } else {
+                format!("the temporary `{}` is dropped", owner_ty)
+            };
+            for use_loc in &use_locations {
+                if use_loc == drop_loc || !is_reachable(*drop_loc, *use_loc, body) {
+                    continue;
+                }
+                let is_return = use_loc.statement_index
+                    == body.basic_blocks[use_loc.block].statements.len()
+                    && matches!(
+                        body.basic_blocks[use_loc.block].terminator().kind,
+                        TerminatorKind::Return
+                    );
+                let used = if is_return {
+                    "escapes to the return value"
+                } else {
+                    "is used"
+                };
+                let diagnosis = format!(
+                    "Raw ptr from `as_ptr` at {:?} {} at {:?} after {} at {:?}",
+                    body.source_info(derive_loc).span,
+                    used,
+                    body.source_info(*use_loc).span,
+                    dropped,
+                    body.source_info(*drop_loc).span
+                );
+                diagnosis_set.insert(diagnosis);
+            }
+        }
+    }
+    diagnosis_set
+}
@@ -1,4 +1,5 @@
 pub mod atomic;
+pub mod config;
 pub mod lock;
 pub mod memory;
 pub mod panic;
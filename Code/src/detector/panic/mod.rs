@@ -1,16 +1,33 @@
+extern crate rustc_data_structures;
 extern crate rustc_hir;
 extern crate rustc_span;
 
+pub mod report;
+
 use once_cell::sync::Lazy;
 use regex::Regex;
 use rustc_hir::def_id::{DefId, LOCAL_CRATE};
 use rustc_middle::mir::visit::Visitor;
-use rustc_middle::mir::{Body, Location, Terminator, TerminatorKind, OUTERMOST_SOURCE_SCOPE};
+use rustc_middle::mir::{
+    AssertKind, BasicBlock, BinOp, Body, Local, Location, Operand, Rvalue, Statement,
+    StatementKind, Terminator, TerminatorKind, OUTERMOST_SOURCE_SCOPE,
+};
 use rustc_middle::ty::EarlyBinder;
 use rustc_middle::ty::{self, TyCtxt, TyKind};
 use rustc_middle::ty::{Instance, InstanceDef};
-use rustc_span::Span;
-use std::collections::HashMap;
+use rustc_span::hygiene::{ExpnKind, MacroKind};
+use rustc_span::{sym, Span};
+use std::collections::{HashMap, HashSet};
+use std::iter;
+
+use petgraph::visit::IntoNodeReferences;
+use rustc_data_structures::fx::FxHashSet;
+
+use crate::analysis::callgraph::{CallChainLimits, CallGraph, CallGraphNode, InstanceId};
+use crate::detector::lock::track_callchains;
+use crate::detector::report::{Report, ReportContent};
+use crate::interest::concurrency::thread::api_path;
+use report::{PanicDiagnosis, PanicInDropDiagnosis};
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum PanicAPI {
@@ -21,8 +38,50 @@ pub enum PanicAPI {
     PanicFmt,
     AssertFailed,
     Panic,
+    Unreachable,
+    Todo,
+    Unimplemented,
+    BoundsCheck,
+    DivisionByZero,
+    RemainderByZero,
+    Overflow,
 }
 
+/// The kinds of the panic APIs selected by `--panic-kinds`.
+/// `overflow` is only reported if selected, since every arithmetic op checks the overflow in debug builds.
+pub const PANIC_KINDS: [&str; 10] = [
+    "unwrap",
+    "expect",
+    "panic",
+    "assert",
+    "unreachable",
+    "todo",
+    "unimplemented",
+    "index",
+    "division",
+    "overflow",
+];
+
+impl PanicAPI {
+    /// The kind of the API in `PANIC_KINDS`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PanicAPI::ResultUnwrap | PanicAPI::OptionUnwrap => "unwrap",
+            PanicAPI::ResultExpect | PanicAPI::OptionExpect => "expect",
+            PanicAPI::PanicFmt | PanicAPI::Panic => "panic",
+            PanicAPI::AssertFailed => "assert",
+            PanicAPI::Unreachable => "unreachable",
+            PanicAPI::Todo => "todo",
+            PanicAPI::Unimplemented => "unimplemented",
+            PanicAPI::BoundsCheck => "index",
+            PanicAPI::DivisionByZero | PanicAPI::RemainderByZero => "division",
+            PanicAPI::Overflow => "overflow",
+        }
+    }
+}
+
+/// `todo!` and `unimplemented!` (and `unreachable!` without args) call `panic` like `panic!`,
+/// so they are told apart by the std macros expanded to the callsites, see `PanicInstance::refine_by_macro`.
 static PANIC_API_REGEX: Lazy<HashMap<PanicAPI, Regex>> = Lazy::new(|| {
     let mut m = HashMap::new();
     m.insert(
@@ -46,6 +105,10 @@ static PANIC_API_REGEX: Lazy<HashMap<PanicAPI, Regex>> = Lazy::new(|| {
         PanicAPI::AssertFailed,
         Regex::new(r"assert_failed").unwrap(),
     );
+    m.insert(
+        PanicAPI::Unreachable,
+        Regex::new(r"panicking::unreachable").unwrap(),
+    );
     m.insert(PanicAPI::Panic, Regex::new(r"panic").unwrap());
     m
 });
@@ -53,6 +116,9 @@ static PANIC_API_REGEX: Lazy<HashMap<PanicAPI, Regex>> = Lazy::new(|| {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::detector::config::DetectorConfig;
+    use crate::progress::Progress;
+    use crate::test_utils::{test_instances, with_tcx, write_src};
     #[test]
     fn test_panic_api_regex() {
         assert!(PANIC_API_REGEX[&PanicAPI::ResultUnwrap].is_match("Result::<i32, String>::unwrap"));
@@ -62,6 +128,236 @@ mod tests {
         assert!(PANIC_API_REGEX[&PanicAPI::PanicFmt].is_match("core::panicking::panic_fmt"));
         assert!(PANIC_API_REGEX[&PanicAPI::AssertFailed].is_match("core::panicking::assert_failed"));
         assert!(PANIC_API_REGEX[&PanicAPI::Panic].is_match("core::panicking::panic"));
+        assert!(PANIC_API_REGEX[&PanicAPI::Unreachable]
+            .is_match("core::panicking::unreachable_display::<&str>"));
+        assert!(
+            !PANIC_API_REGEX[&PanicAPI::Unreachable].is_match("std::hint::unreachable_unchecked")
+        );
+    }
+
+    const PANIC_IN_DROP_SRC: &str = r#"use std::sync::Mutex;
+
+pub struct Registry {
+    entries: Mutex<Vec<u32>>,
+    last: Option<u32>,
+}
+
+fn flush(last: Option<u32>) {
+    let _ = last.expect("flushed twice");
+}
+
+pub fn check(last: Option<u32>) -> u32 {
+    last.unwrap()
+}
+
+impl Drop for Registry {
+    fn drop(&mut self) {
+        self.entries.lock().unwrap().clear();
+        flush(self.last);
+        check(self.last);
+    }
+}
+"#;
+
+    #[test]
+    fn test_panic_in_drop() {
+        let src = write_src("panic-in-drop", PANIC_IN_DROP_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let progress = Progress::new(true);
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(
+                    test_instances(tcx),
+                    tcx,
+                    ty::ParamEnv::reveal_all(),
+                    &progress,
+                );
+                let mut reports = PanicDetector::new(tcx)
+                    .detect_panic_in_drop(&callgraph, DetectorConfig::default().callchain_limits)
+                    .into_iter()
+                    .map(|report| match report {
+                        Report::PanicInDrop(content) => (
+                            line(&content.diagnosis.panic_span),
+                            content.diagnosis.drop_type,
+                            content.diagnosis.callchains.len(),
+                            content.explanation.starts_with("`lock().unwrap()`"),
+                        ),
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                reports.sort_unstable();
+                // `check` is also reachable from outside of the crate.
+                assert_eq!(
+                    reports,
+                    [
+                        (9, "Registry".to_owned(), 1, false),
+                        (18, "Registry".to_owned(), 0, true),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const PANIC_KINDS_SRC: &str = r#"pub fn parse(s: &str, mode: u8) -> u32 {
+    match mode {
+        0 => s.parse().unwrap(),
+        1 => todo!(),
+        2 => unimplemented!("mode {}", mode),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_ok() {
+        assert_eq!(super::parse("1", 0), 1);
+        "2".parse::<u32>().expect("a number");
+    }
+}
+"#;
+
+    #[test]
+    fn test_panic_kinds_and_tests() {
+        let src = write_src("panic-kinds", PANIC_KINDS_SRC);
+        with_tcx(
+            &src,
+            &["--test".to_owned()],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(
+                    test_instances(tcx),
+                    tcx,
+                    ty::ParamEnv::reveal_all(),
+                    &Progress::new(true),
+                );
+                let panic_sites = |include_tests: bool, kinds: &[&str]| {
+                    let mut panic_detector = PanicDetector::new(tcx);
+                    panic_detector.set_filter(
+                        include_tests,
+                        kinds.iter().map(|kind| kind.to_string()).collect(),
+                    );
+                    let mut sites = panic_detector
+                        .detect_panic_sites(&callgraph)
+                        .into_iter()
+                        .map(|report| match report {
+                            Report::Panic(content) => (
+                                line(&content.diagnosis.callsite_span),
+                                content.diagnosis.panic_api,
+                                content.diagnosis.in_test,
+                            ),
+                            report => panic!("unexpected report {:?}", report),
+                        })
+                        .collect::<Vec<_>>();
+                    sites.sort_unstable();
+                    sites
+                };
+                let site = |line: usize, api: &str, in_test: bool| (line, api.to_owned(), in_test);
+                assert_eq!(
+                    panic_sites(false, &[]),
+                    [
+                        site(3, "ResultUnwrap", false),
+                        site(4, "Todo", false),
+                        site(5, "Unimplemented", false),
+                        site(6, "Unreachable", false),
+                    ]
+                );
+                assert_eq!(
+                    panic_sites(true, &["unwrap", "expect"]),
+                    [
+                        site(3, "ResultUnwrap", false),
+                        site(15, "ResultExpect", true),
+                    ]
+                );
+            }),
+        );
+    }
+
+    const PANIC_ASSERTS_SRC: &str = r#"pub fn unguarded(v: &[u32], i: usize) -> u32 {
+    v[i]
+}
+
+pub fn guarded(v: &[u32], i: usize) -> u32 {
+    if i < v.len() {
+        v[i]
+    } else {
+        0
+    }
+}
+
+pub fn early_return(v: &[u32], i: usize) -> u32 {
+    if i >= v.len() {
+        return 0;
+    }
+    v[i]
+}
+
+pub fn guarded_by_other(v: &[u32], i: usize, j: usize) -> u32 {
+    if j < v.len() {
+        v[i]
+    } else {
+        0
+    }
+}
+
+pub fn ratio(n: u32, d: u32) -> u32 {
+    let r = n % d;
+    if d != 0 {
+        n / d + r
+    } else {
+        0
+    }
+}
+"#;
+
+    #[test]
+    fn test_panic_asserts() {
+        let src = write_src("panic-asserts", PANIC_ASSERTS_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let mut callgraph = CallGraph::new();
+                callgraph.analyze(
+                    test_instances(tcx),
+                    tcx,
+                    ty::ParamEnv::reveal_all(),
+                    &Progress::new(true),
+                );
+                let mut sites = PanicDetector::new(tcx)
+                    .detect_panic_sites(&callgraph)
+                    .into_iter()
+                    .map(|report| match report {
+                        Report::Panic(content) => (
+                            line(&content.diagnosis.callsite_span),
+                            content.diagnosis.panic_api,
+                            content.diagnosis.condition_span.as_deref().map(line),
+                        ),
+                        report => panic!("unexpected report {:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                sites.sort_unstable();
+                // The overflow checks are not reported by default.
+                assert_eq!(
+                    sites,
+                    [
+                        (2, "BoundsCheck".to_owned(), Some(2)),
+                        (22, "BoundsCheck".to_owned(), Some(22)),
+                        (29, "RemainderByZero".to_owned(), Some(29)),
+                    ]
+                );
+                let mut panic_detector = PanicDetector::new(tcx);
+                panic_detector.set_filter(false, vec!["overflow".to_owned()]);
+                let overflows = panic_detector.detect_panic_sites(&callgraph);
+                assert_eq!(overflows.len(), 1);
+                assert_eq!(overflows[0].spans().0.split(':').nth(1), Some("31"));
+            }),
+        );
     }
 }
 
@@ -74,11 +370,19 @@ pub enum PanicInstance<'tcx> {
     PanicFmt(Instance<'tcx>),
     AssertFailed(Instance<'tcx>),
     Panic(Instance<'tcx>),
+    Unreachable(Instance<'tcx>),
+    Todo(Instance<'tcx>),
+    Unimplemented(Instance<'tcx>),
+    /// The `Assert` terminators with the spans of the asserted conditions, e.g., the bounds checks of arrays and slices.
+    BoundsCheck(Span),
+    DivisionByZero(Span),
+    RemainderByZero(Span),
+    Overflow(Span),
 }
 
 impl<'tcx> PanicInstance<'tcx> {
-    fn new(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
-        let def_path_str = tcx.def_path_str_with_args(instance.def_id(), &*instance.args);
+    pub fn new(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
+        let def_path_str = tcx.def_path_str_with_args(instance.def_id(), instance.args);
         if PANIC_API_REGEX[&PanicAPI::ResultUnwrap].is_match(&def_path_str) {
             Some(PanicInstance::ResultUnwrap(instance))
         } else if PANIC_API_REGEX[&PanicAPI::ResultExpect].is_match(&def_path_str) {
@@ -91,6 +395,8 @@ impl<'tcx> PanicInstance<'tcx> {
             Some(PanicInstance::PanicFmt(instance))
         } else if PANIC_API_REGEX[&PanicAPI::AssertFailed].is_match(&def_path_str) {
             Some(PanicInstance::AssertFailed(instance))
+        } else if PANIC_API_REGEX[&PanicAPI::Unreachable].is_match(&def_path_str) {
+            Some(PanicInstance::Unreachable(instance))
         } else if PANIC_API_REGEX[&PanicAPI::Panic].is_match(&def_path_str) {
             Some(PanicInstance::Panic(instance))
         } else {
@@ -98,6 +404,104 @@ impl<'tcx> PanicInstance<'tcx> {
         }
     }
 
+    /// The panic of an `Assert` terminator, with the span of the asserted condition.
+    fn from_assert(msg: &AssertKind<Operand<'tcx>>, condition_span: Span) -> Option<Self> {
+        match msg {
+            AssertKind::BoundsCheck { .. } => Some(PanicInstance::BoundsCheck(condition_span)),
+            AssertKind::DivisionByZero(_) => Some(PanicInstance::DivisionByZero(condition_span)),
+            AssertKind::RemainderByZero(_) => Some(PanicInstance::RemainderByZero(condition_span)),
+            AssertKind::Overflow(..) | AssertKind::OverflowNeg(_) => {
+                Some(PanicInstance::Overflow(condition_span))
+            }
+            _ => None,
+        }
+    }
+
+    /// The callee of the panic API, `None` for the `Assert` terminators.
+    pub fn instance(&self) -> Option<&Instance<'tcx>> {
+        match self {
+            PanicInstance::ResultUnwrap(instance)
+            | PanicInstance::ResultExpect(instance)
+            | PanicInstance::OptionUnwrap(instance)
+            | PanicInstance::OptionExpect(instance)
+            | PanicInstance::PanicFmt(instance)
+            | PanicInstance::AssertFailed(instance)
+            | PanicInstance::Panic(instance)
+            | PanicInstance::Unreachable(instance)
+            | PanicInstance::Todo(instance)
+            | PanicInstance::Unimplemented(instance) => Some(instance),
+            PanicInstance::BoundsCheck(_)
+            | PanicInstance::DivisionByZero(_)
+            | PanicInstance::RemainderByZero(_)
+            | PanicInstance::Overflow(_) => None,
+        }
+    }
+
+    /// The span of the asserted condition of an `Assert` terminator.
+    fn condition_span(&self) -> Option<Span> {
+        match self {
+            PanicInstance::BoundsCheck(span)
+            | PanicInstance::DivisionByZero(span)
+            | PanicInstance::RemainderByZero(span)
+            | PanicInstance::Overflow(span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// The path of the callee, or the panic message of an `Assert` terminator, e.g., `index out of bounds`.
+    fn api_path(&self, tcx: TyCtxt<'tcx>) -> String {
+        match self {
+            PanicInstance::BoundsCheck(_) => "index out of bounds".to_owned(),
+            PanicInstance::DivisionByZero(_) => "attempt to divide by zero".to_owned(),
+            PanicInstance::RemainderByZero(_) => {
+                "attempt to calculate the remainder with a divisor of zero".to_owned()
+            }
+            PanicInstance::Overflow(_) => "attempt to compute with overflow".to_owned(),
+            _ => api_path(self.instance().unwrap(), tcx),
+        }
+    }
+
+    /// Refine `Panic` and `PanicFmt` by the std macro expanded to the callsite,
+    /// i.e., `unreachable!`, `todo!`, or `unimplemented!`.
+    fn refine_by_macro(self, span: Span) -> Self {
+        let instance = match self {
+            PanicInstance::Panic(instance) | PanicInstance::PanicFmt(instance) => instance,
+            _ => return self,
+        };
+        for expn_data in span.macro_backtrace() {
+            if expn_data
+                .macro_def_id
+                .map_or(true, |macro_def_id| macro_def_id.is_local())
+            {
+                continue;
+            }
+            if let ExpnKind::Macro(MacroKind::Bang, name) = expn_data.kind {
+                match name.as_str() {
+                    "unreachable" => return PanicInstance::Unreachable(instance),
+                    "todo" => return PanicInstance::Todo(instance),
+                    "unimplemented" => return PanicInstance::Unimplemented(instance),
+                    _ => {}
+                }
+            }
+        }
+        self
+    }
+
+    /// `Result::<_, PoisonError<_>>::unwrap/expect`, e.g., `Mutex::lock().unwrap()`.
+    fn unwraps_poison_error(&self, tcx: TyCtxt<'tcx>) -> bool {
+        match self {
+            PanicInstance::ResultUnwrap(instance) | PanicInstance::ResultExpect(instance) => {
+                match instance.args.types().nth(1).map(|ty| ty.kind()) {
+                    Some(TyKind::Adt(adt_def, _)) => {
+                        tcx.def_path_str(adt_def.did()) == "std::sync::PoisonError"
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
     fn to_panic_api(&self) -> PanicAPI {
         match self {
             PanicInstance::ResultUnwrap(_) => PanicAPI::ResultUnwrap,
@@ -107,13 +511,28 @@ impl<'tcx> PanicInstance<'tcx> {
             PanicInstance::PanicFmt(_) => PanicAPI::PanicFmt,
             PanicInstance::AssertFailed(_) => PanicAPI::AssertFailed,
             PanicInstance::Panic(_) => PanicAPI::Panic,
+            PanicInstance::Unreachable(_) => PanicAPI::Unreachable,
+            PanicInstance::Todo(_) => PanicAPI::Todo,
+            PanicInstance::Unimplemented(_) => PanicAPI::Unimplemented,
+            PanicInstance::BoundsCheck(_) => PanicAPI::BoundsCheck,
+            PanicInstance::DivisionByZero(_) => PanicAPI::DivisionByZero,
+            PanicInstance::RemainderByZero(_) => PanicAPI::RemainderByZero,
+            PanicInstance::Overflow(_) => PanicAPI::Overflow,
         }
     }
 }
 
+/// The panic sites keyed by the fns and the locations,
+/// with the callsite span, the outermost span of the fn, the panic API, and whether in the test code.
+type PanicSites<'tcx> = HashMap<(DefId, Location), (Span, Span, PanicInstance<'tcx>, bool)>;
+
 pub struct PanicDetector<'tcx> {
     tcx: TyCtxt<'tcx>,
-    result: HashMap<(DefId, Location), (Span, Span, PanicInstance<'tcx>)>,
+    result: PanicSites<'tcx>,
+    /// Report the panic sites in the test code by `detect_panic_sites`.
+    include_tests: bool,
+    /// The kinds in `PANIC_KINDS` reported by `detect_panic_sites`, all if empty.
+    kinds: Vec<String>,
 }
 
 impl<'tcx> PanicDetector<'tcx> {
@@ -121,22 +540,220 @@ impl<'tcx> PanicDetector<'tcx> {
         Self {
             tcx,
             result: Default::default(),
+            include_tests: false,
+            kinds: Vec::new(),
         }
     }
+
+    /// Filter the panic sites reported by `detect_panic_sites`, see `--panic-include-tests` and `--panic-kinds`.
+    pub fn set_filter(&mut self, include_tests: bool, kinds: Vec<String>) {
+        self.include_tests = include_tests;
+        self.kinds = kinds;
+    }
+
     pub fn detect(&mut self, instance: Instance<'tcx>) {
         if let Some(mut panic_finder) = PanicFinder::new(instance, self.tcx) {
-            self.result.extend(panic_finder.detect().into_iter());
+            self.result.extend(panic_finder.detect());
         }
     }
-    pub fn result(&self) -> &HashMap<(DefId, Location), (Span, Span, PanicInstance<'tcx>)> {
-        &self.result
+
+    /// Report the panic sites in the instances of the callgraph with the kinds of their APIs.
+    pub fn detect_panic_sites(&mut self, callgraph: &CallGraph<'tcx>) -> Vec<Report> {
+        for (instance_id, node) in callgraph.graph.node_references() {
+            if let CallGraphNode::WithBody(instance) = node {
+                if callgraph.is_focused(instance_id) {
+                    self.detect(*instance);
+                }
+            }
+        }
+        self.result
+            .iter()
+            .filter(|(_, (_, _, panic_instance, in_test))| {
+                let kind = panic_instance.to_panic_api().kind();
+                let selected = if self.kinds.is_empty() {
+                    kind != "overflow"
+                } else {
+                    self.kinds.iter().any(|k| k == kind)
+                };
+                (self.include_tests || !in_test) && selected
+            })
+            .map(
+                |((def_id, _), (span, outermost_span, panic_instance, in_test))| {
+                    let panic_api = panic_instance.to_panic_api();
+                    let mut diagnosis = PanicDiagnosis::new(
+                        self.tcx.def_path_str(*def_id),
+                        format!("{:?}", panic_api),
+                        format!("{:?}", span),
+                        format!("{:?}", outermost_span),
+                        *in_test,
+                    );
+                    diagnosis.condition_span = panic_instance
+                        .condition_span()
+                        .map(|span| format!("{:?}", span));
+                    let explanation = match panic_api {
+                        PanicAPI::Unreachable | PanicAPI::Todo | PanicAPI::Unimplemented => {
+                            format!("`{}!` panics if reached", panic_api.kind())
+                        }
+                        PanicAPI::BoundsCheck
+                        | PanicAPI::DivisionByZero
+                        | PanicAPI::RemainderByZero
+                        | PanicAPI::Overflow => format!(
+                            "Panics with `{}` unless guarded",
+                            panic_instance.api_path(self.tcx)
+                        ),
+                        _ => format!("`{}` may panic", panic_instance.api_path(self.tcx)),
+                    };
+                    Report::Panic(ReportContent::new(
+                        "Panic".to_owned(),
+                        "Possibly".to_owned(),
+                        diagnosis,
+                        explanation,
+                    ))
+                },
+            )
+            .collect()
     }
-    pub fn statistics(&self) -> HashMap<PanicAPI, usize> {
-        let mut tally: HashMap<PanicAPI, usize> = HashMap::new();
-        for (_, (_, _, panic_instance)) in &self.result {
-            *tally.entry(panic_instance.to_panic_api()).or_default() += 1;
+
+    /// Report the panic sites in the `Drop::drop` impls of the crate and in the fns only reachable from them.
+    /// A panic in `drop` while unwinding from another panic aborts the process.
+    pub fn detect_panic_in_drop(
+        &mut self,
+        callgraph: &CallGraph<'tcx>,
+        limits: CallChainLimits,
+    ) -> Vec<Report> {
+        let mut instances_by_def_id: HashMap<DefId, Vec<InstanceId>> = HashMap::new();
+        let mut drop_impls = FxHashSet::default();
+        let mut exported = FxHashSet::default();
+        for (instance_id, node) in callgraph.graph.node_references() {
+            let instance = match node {
+                CallGraphNode::WithBody(instance) if callgraph.is_focused(instance_id) => *instance,
+                _ => continue,
+            };
+            self.detect(instance);
+            instances_by_def_id
+                .entry(instance.def_id())
+                .or_default()
+                .push(instance_id);
+            if self.is_local_drop_impl(instance.def_id()) {
+                drop_impls.insert(instance_id);
+            } else if instance.def_id().as_local().map_or(false, |local_def_id| {
+                self.tcx
+                    .effective_visibilities(())
+                    .is_exported(local_def_id)
+            }) {
+                exported.insert(instance_id);
+            }
+        }
+        if drop_impls.is_empty() {
+            return Vec::new();
+        }
+        let mut diagnosis_set = HashSet::new();
+        for ((def_id, _), (span, _, panic_instance, _)) in self.result.iter() {
+            // The overflow checks of debug builds are too many to report.
+            if let PanicInstance::Overflow(_) = panic_instance {
+                continue;
+            }
+            let Some(instance_ids) = instances_by_def_id.get(def_id) else {
+                continue;
+            };
+            for &target in instance_ids {
+                let Some(drops) = only_reachable_from(target, &drop_impls, &exported, callgraph)
+                else {
+                    continue;
+                };
+                for drop in drops {
+                    let drop_instance = callgraph.index_to_instance(drop).unwrap().instance();
+                    let drop_def_id = drop_instance.def_id();
+                    let impl_def_id = self.tcx.impl_of_method(drop_def_id).unwrap();
+                    let drop_type = self
+                        .tcx
+                        .type_of(impl_def_id)
+                        .instantiate_identity()
+                        .to_string();
+                    let callchains = if drop == target {
+                        Vec::new()
+                    } else {
+                        track_callchains(drop, target, callgraph, limits, self.tcx)
+                    };
+                    let diagnosis = PanicInDropDiagnosis::new(
+                        drop_type,
+                        format!("{:?}", self.tcx.def_span(drop_def_id)),
+                        panic_instance.api_path(self.tcx),
+                        format!("{:?}", span),
+                        callchains,
+                    );
+                    diagnosis_set
+                        .insert((diagnosis, panic_instance.unwraps_poison_error(self.tcx)));
+                }
+            }
+        }
+        diagnosis_set
+            .into_iter()
+            .map(|(diagnosis, unwraps_poison_error)| {
+                let explanation = if unwraps_poison_error {
+                    "`lock().unwrap()` in `Drop::drop` panics on a poisoned lock, which is poisoned by the thread panicking while holding it, so dropping the value while unwinding from that panic aborts the process".to_owned()
+                } else {
+                    "A panic in `Drop::drop` aborts the process if the value is dropped while unwinding from another panic".to_owned()
+                };
+                Report::PanicInDrop(ReportContent::new(
+                    "PanicInDrop".to_owned(),
+                    "Probably".to_owned(),
+                    diagnosis,
+                    explanation,
+                ))
+            })
+            .collect()
+    }
+
+    fn is_local_drop_impl(&self, def_id: DefId) -> bool {
+        def_id.is_local()
+            && self
+                .tcx
+                .impl_of_method(def_id)
+                .and_then(|impl_def_id| self.tcx.trait_id_of_impl(impl_def_id))
+                .map_or(false, |trait_def_id| {
+                    Some(trait_def_id) == self.tcx.lang_items().drop_trait()
+                })
+    }
+}
+
+/// The drop impls `target` is only reachable from, i.e., every callchain reaching `target` passes one of them.
+/// Returns `None` if `target` is reachable without a drop impl from a root of the callgraph, e.g., `main`,
+/// or from a fn exported by the crate.
+fn only_reachable_from(
+    target: InstanceId,
+    drop_impls: &FxHashSet<InstanceId>,
+    exported: &FxHashSet<InstanceId>,
+    callgraph: &CallGraph<'_>,
+) -> Option<Vec<InstanceId>> {
+    if drop_impls.contains(&target) {
+        return Some(vec![target]);
+    }
+    let mut drops = Vec::new();
+    let mut visited = FxHashSet::default();
+    visited.insert(target);
+    let mut worklist = vec![target];
+    while let Some(id) = worklist.pop() {
+        let callers = callgraph.callers(id);
+        if callers.is_empty() || exported.contains(&id) {
+            return None;
+        }
+        for caller in callers {
+            if !visited.insert(caller) {
+                continue;
+            }
+            if drop_impls.contains(&caller) {
+                drops.push(caller);
+            } else {
+                worklist.push(caller);
+            }
         }
-        tally
+    }
+    if drops.is_empty() {
+        None
+    } else {
+        drops.sort();
+        Some(drops)
     }
 }
 
@@ -165,44 +782,315 @@ impl<'tcx> PanicFinder<'tcx> {
         })
     }
 
-    fn detect(&mut self) -> HashMap<(DefId, Location), (Span, Span, PanicInstance<'tcx>)> {
+    fn detect(&mut self) -> PanicSites<'tcx> {
         self.visit_body(self.body);
+        let def_id = self.instance.def_id();
+        let in_test = is_test_code(def_id, self.tcx);
         self.callsites
             .iter()
             .map(|(loc, instance)| {
-                let def_id = self.instance.def_id();
-                let span = self.body.source_info(*loc).span;
+                let span = callsite_out_of_foreign_macros(self.body.source_info(*loc).span);
                 let outermost_span = self.body.source_scopes[OUTERMOST_SOURCE_SCOPE].span;
-                ((def_id, *loc), (span, outermost_span, instance.clone()))
+                (
+                    (def_id, *loc),
+                    (span, outermost_span, instance.clone(), in_test),
+                )
             })
             .collect::<_>()
     }
+
+    fn resolve_callee(&self, func: &Operand<'tcx>) -> Option<Instance<'tcx>> {
+        let func_ty = func.ty(self.body, self.tcx);
+        let func_ty = self.instance.instantiate_mir_and_normalize_erasing_regions(
+            self.tcx,
+            ty::ParamEnv::reveal_all(),
+            EarlyBinder::bind(func_ty),
+        );
+        if let TyKind::FnDef(def_id, subst_ref) = func_ty.kind() {
+            Instance::resolve(self.tcx, ty::ParamEnv::reveal_all(), *def_id, subst_ref)
+                .ok()
+                .flatten()
+        } else {
+            None
+        }
+    }
+
+    /// The local of a place operand, e.g., `_5` of `move _5`.
+    fn assigned_local(&self, operand: &Operand<'tcx>) -> Option<Local> {
+        operand.place().and_then(|place| place.as_local())
+    }
+
+    /// The only assignment to the local in the body, with its rvalue.
+    fn assignment(&self, local: Local) -> Option<(&'tcx Statement<'tcx>, &'tcx Rvalue<'tcx>)> {
+        let mut assignments = self.body.basic_blocks.iter().flat_map(|data| {
+            data.statements
+                .iter()
+                .filter_map(move |statement| match &statement.kind {
+                    StatementKind::Assign(box (place, rvalue))
+                        if place.as_local() == Some(local) =>
+                    {
+                        Some((statement, rvalue))
+                    }
+                    _ => None,
+                })
+        });
+        let assignment = assignments.next()?;
+        assignments.next().is_none().then_some(assignment)
+    }
+
+    /// The local the operand is copied from through the temps assigned once, e.g., the variable `i` of `v[i]`.
+    fn copy_root(&self, operand: &Operand<'tcx>) -> Option<Local> {
+        let mut local = self.assigned_local(operand)?;
+        for _ in 0..self.body.local_decls.len() {
+            match self.assignment(local) {
+                Some((_, Rvalue::Use(operand))) => match self.assigned_local(operand) {
+                    Some(source) => local = source,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        Some(local)
+    }
+
+    /// Whether the bounds check (or the check of a zero divisor) is trivially dominated by an explicit check,
+    /// e.g., `if i < v.len() { v[i] }`, `if i >= v.len() { return; }`, or `if d != 0 { n / d }`:
+    /// a `switchInt` on the comparison of the same variable branches to a block only entered from the switch,
+    /// which dominates the `Assert` terminator. The bound is not checked against the indexed length.
+    fn is_guarded(
+        &self,
+        cond: &Operand<'tcx>,
+        msg: &AssertKind<Operand<'tcx>>,
+        block: BasicBlock,
+    ) -> bool {
+        let is_zero = |operand: &Operand<'tcx>| {
+            operand
+                .constant()
+                .and_then(|constant| constant.const_.try_to_scalar_int())
+                .map_or(false, |scalar| scalar.is_null())
+        };
+        let (checked, nonzero) = match msg {
+            AssertKind::BoundsCheck { index, .. } => (index, false),
+            // The operand of the message is the dividend, and the divisor is in the condition `Eq(d, 0)`.
+            AssertKind::DivisionByZero(_) | AssertKind::RemainderByZero(_) => {
+                let divisor = self
+                    .assigned_local(cond)
+                    .and_then(|local| self.assignment(local))
+                    .and_then(|(_, rvalue)| match rvalue {
+                        Rvalue::BinaryOp(BinOp::Eq, box (lhs, rhs)) if is_zero(rhs) => Some(lhs),
+                        _ => None,
+                    });
+                match divisor {
+                    Some(divisor) => (divisor, true),
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        let Some(root) = self.copy_root(checked) else {
+            return false;
+        };
+        let is_root = |operand: &Operand<'tcx>| self.copy_root(operand) == Some(root);
+        // Whether the comparison taking the branch implies the check.
+        let implies = |op: BinOp, lhs: &Operand<'tcx>, rhs: &Operand<'tcx>, taken: bool| {
+            if nonzero {
+                let (var, other) = if is_root(lhs) { (lhs, rhs) } else { (rhs, lhs) };
+                is_root(var)
+                    && is_zero(other)
+                    && matches!(
+                        (op, taken),
+                        (BinOp::Ne, true) | (BinOp::Eq, false) | (BinOp::Gt, true)
+                    )
+            } else {
+                matches!((op, taken), (BinOp::Lt, true) | (BinOp::Ge, false)) && is_root(lhs)
+                    || matches!((op, taken), (BinOp::Gt, true) | (BinOp::Le, false)) && is_root(rhs)
+            }
+        };
+        let dominators = self.body.basic_blocks.dominators();
+        let predecessors = self.body.basic_blocks.predecessors();
+        self.body.basic_blocks.iter().any(|data| {
+            let TerminatorKind::SwitchInt { discr, targets } = &data.terminator().kind else {
+                return false;
+            };
+            // Only the switches on a bool or on zero, i.e., `[0: false, otherwise: true]`.
+            let mut values = targets.iter();
+            let Some((0, zero_target)) = values.next() else {
+                return false;
+            };
+            if values.next().is_some() {
+                return false;
+            }
+            let comparison = self
+                .assigned_local(discr)
+                .and_then(|local| self.assignment(local))
+                .and_then(|(_, rvalue)| match rvalue {
+                    Rvalue::BinaryOp(op, box (lhs, rhs)) => Some((*op, lhs, rhs)),
+                    _ => None,
+                });
+            [(zero_target, false), (targets.otherwise(), true)]
+                .into_iter()
+                .any(|(target, taken)| {
+                    predecessors[target].len() == 1
+                        && dominators.dominates(target, block)
+                        && match comparison {
+                            Some((op, lhs, rhs)) => implies(op, lhs, rhs, taken),
+                            // `if d != 0` is simplified to the switch on `d`.
+                            None => nonzero && taken && is_root(discr),
+                        }
+                })
+        })
+    }
 }
 
 impl<'tcx> Visitor<'tcx> for PanicFinder<'tcx> {
     fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
-        if let TerminatorKind::Call { ref func, .. } = terminator.kind {
-            let func_ty = func.ty(self.body, self.tcx);
-            let func_ty = self.instance.instantiate_mir_and_normalize_erasing_regions(
-                self.tcx,
-                ty::ParamEnv::reveal_all(),
-                EarlyBinder::bind(func_ty),
-            );
-            if let TyKind::FnDef(def_id, subst_ref) = func_ty.kind() {
-                if let Some(callee_instance) =
-                    Instance::resolve(self.tcx, ty::ParamEnv::reveal_all(), *def_id, subst_ref)
-                        .ok()
-                        .flatten()
-                {
+        match terminator.kind {
+            TerminatorKind::Call { ref func, .. } => {
+                if let Some(callee_instance) = self.resolve_callee(func) {
                     if let Some(panic_instance) = PanicInstance::new(callee_instance, self.tcx) {
+                        let panic_instance =
+                            panic_instance.refine_by_macro(terminator.source_info.span);
                         self.callsites.insert(location, panic_instance);
                     }
                 }
             }
+            TerminatorKind::Assert {
+                ref cond, ref msg, ..
+            } => {
+                // The condition is assigned in the block of the `Assert` terminator.
+                let condition_span = self
+                    .assigned_local(cond)
+                    .and_then(|local| {
+                        self.body.basic_blocks[location.block]
+                            .statements
+                            .iter()
+                            .rev()
+                            .find(|statement| match &statement.kind {
+                                StatementKind::Assign(box (place, _)) => {
+                                    place.as_local() == Some(local)
+                                }
+                                _ => false,
+                            })
+                    })
+                    .map_or(terminator.source_info.span, |statement| {
+                        statement.source_info.span
+                    });
+                if let Some(panic_instance) = PanicInstance::from_assert(msg, condition_span) {
+                    if !self.is_guarded(cond, msg, location.block) {
+                        self.callsites.insert(location, panic_instance);
+                    }
+                }
+            }
+            _ => {}
         }
     }
 }
 
+/// The panic sites poisoning the live lockguards, see `panic_holding_lock`:
+/// the panic callsites except the `unwrap`/`expect` of `PoisonError`, e.g., `lock().unwrap()`,
+/// which only panics on an already poisoned lock, and the callees matched by `panic` but returning, e.g., `catch_unwind`;
+/// plus the indexing, i.e., `Index::index` or `IndexMut::index_mut` of the foreign types, e.g., `Vec`,
+/// and the bounds checks of arrays and slices.
+/// Returns the (location, span, api) of the sites not in the cleanup blocks.
+pub fn poisoning_panic_sites<'tcx>(
+    instance: Instance<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<(Location, Span, String)> {
+    let panic_finder = match PanicFinder::new(instance, tcx) {
+        Some(panic_finder) => panic_finder,
+        None => return Vec::new(),
+    };
+    let body = panic_finder.body;
+    let index_traits = [
+        tcx.lang_items().index_trait(),
+        tcx.lang_items().index_mut_trait(),
+    ];
+    let mut sites = Vec::new();
+    for (block, data) in body.basic_blocks.iter_enumerated() {
+        if data.is_cleanup {
+            continue;
+        }
+        let terminator = data.terminator();
+        let location = body.terminator_loc(block);
+        let span = terminator.source_info.span;
+        match &terminator.kind {
+            TerminatorKind::Call { func, .. } => {
+                let callee = match panic_finder.resolve_callee(func) {
+                    Some(callee) => callee,
+                    None => continue,
+                };
+                if let Some(panic_instance) = PanicInstance::new(callee, tcx) {
+                    let diverges = tcx
+                        .fn_sig(callee.def_id())
+                        .skip_binder()
+                        .output()
+                        .skip_binder()
+                        .is_never();
+                    if panic_instance.unwraps_poison_error(tcx)
+                        || matches!(panic_instance, PanicInstance::Panic(_)) && !diverges
+                    {
+                        continue;
+                    }
+                    sites.push((location, span, api_path(&callee, tcx)));
+                } else if let Some(index_trait) = tcx
+                    .impl_of_method(callee.def_id())
+                    .and_then(|impl_def_id| tcx.trait_id_of_impl(impl_def_id))
+                    .filter(|trait_def_id| index_traits.contains(&Some(*trait_def_id)))
+                {
+                    if !callee.def_id().is_local() {
+                        let api = format!(
+                            "{}::{}",
+                            tcx.def_path_str(index_trait),
+                            tcx.item_name(callee.def_id())
+                        );
+                        sites.push((location, span, api));
+                    }
+                }
+            }
+            TerminatorKind::Assert { msg, .. }
+                if matches!(**msg, AssertKind::BoundsCheck { .. }) =>
+            {
+                sites.push((location, span, "index out of bounds".to_owned()));
+            }
+            _ => {}
+        }
+    }
+    sites
+}
+
+/// The callsite of the outermost macro from the other crates expanded to the span, e.g., the callsite of `todo!()`,
+/// whose expansion is in std.
+fn callsite_out_of_foreign_macros(mut span: Span) -> Span {
+    loop {
+        let expn_data = span.ctxt().outer_expn_data();
+        match expn_data.macro_def_id {
+            Some(macro_def_id) if !macro_def_id.is_local() => span = expn_data.call_site,
+            _ => return span,
+        }
+    }
+}
+
+/// Whether the fn or any of its parents (e.g., the fn of a closure or a module) is a `#[test]` fn
+/// or gated by `#[cfg(test)]`.
+fn is_test_code(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    iter::successors(Some(def_id), |def_id| tcx.opt_parent(*def_id)).any(|def_id| {
+        let local_def_id = match def_id.as_local() {
+            Some(local_def_id) => local_def_id,
+            None => return false,
+        };
+        tcx.hir()
+            .attrs(tcx.local_def_id_to_hir_id(local_def_id))
+            .iter()
+            .any(|attr| {
+                attr.has_name(sym::rustc_test_marker)
+                    || attr.has_name(sym::cfg)
+                        && attr.meta_item_list().map_or(false, |items| {
+                            items.iter().any(|item| item.has_name(sym::test))
+                        })
+            })
+    })
+}
+
 fn skip_detecting<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
     if let InstanceDef::Item(_) = instance.def {
         !tcx.is_mir_available(instance.def_id())
@@ -0,0 +1,3 @@
+pub use lockbud_report_types::panic::{
+    PanicDiagnosis, PanicHoldingLockDiagnosis, PanicInDropDiagnosis,
+};
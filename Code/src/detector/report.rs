@@ -1,41 +1,22 @@
-//! Reports for different kinds of bugs.
+//! Reports for different kinds of bugs, defined in the `lockbud-report-types` crate.
 //! ReportContent includes bug kind, possibility, diagnosis, and explanation.
 //! The diagnosis for different kinds of bugs may be different.
 //! e.g., doublelock diagnosis contains one deadlock diagnosis,
 //！while conflictlock diagnosis contanis a vector of deadlock diagnosis.
 //! Deadlock diagnosis consists of the first & second locks' type and span (a.k.a. src code location),
 //! and **all** possible callchains from first to second lock.
-use serde::Serialize;
-
-use crate::detector::atomic::report::AtomicityViolationDiagnosis;
-use crate::detector::lock::report::{CondvarDeadlockDiagnosis, DeadlockDiagnosis};
-
-#[allow(dead_code)]
-#[derive(Debug, Serialize)]
-pub struct ReportContent<D> {
-    pub bug_kind: String,
-    pub possibility: String,
-    pub diagnosis: D,
-    pub explanation: String,
-}
-
-impl<D: std::fmt::Debug> ReportContent<D> {
-    pub fn new(bug_kind: String, possibility: String, diagnosis: D, explanation: String) -> Self {
-        Self {
-            bug_kind,
-            possibility,
-            diagnosis,
-            explanation,
-        }
-    }
-}
-
-#[derive(Debug, Serialize)]
-pub enum Report {
-    DoubleLock(ReportContent<DeadlockDiagnosis>),
-    ConflictLock(ReportContent<Vec<DeadlockDiagnosis>>),
-    CondvarDeadlock(ReportContent<CondvarDeadlockDiagnosis>),
-    AtomicityViolation(ReportContent<AtomicityViolationDiagnosis>),
-    InvalidFree(ReportContent<String>),
-    UseAfterFree(ReportContent<String>),
-}
+//! Each report carries its effective level, configured per kind by `levels` in the config file.
+//! The reports are sorted by `sort_reports` before emission, so that the output is deterministic.
+//! The reports of a crate are emitted in a versioned `ReportEnvelope` with a `Summary` and the `AnalysisMetadata`.
+//! With `--max-reports`, the most actionable reports are kept by `truncate_reports`.
+//! The alias classes of the locks in the reports are listed in the envelope by `referenced_locks`,
+//! and with `--group-by lock`, the reports are grouped by the alias classes of their primary locks by `group_by_lock`.
+//! With `--paranoid`, the reports relying on unknown alias results are marked by `ALIAS_CONFIDENCE_UNKNOWN`.
+//! The doublelocks through virtual calls reported by the owner types of the locks are marked by `ALIAS_CONFIDENCE_SAME_OWNER`.
+//! The deadlocks only reachable through the closures passed to generic fns without MIR are marked by `HEURISTIC_CLOSURE_ARG`.
+//! The conflictlock relations retried in the closures of `catch_unwind` after a panic are marked by `INVERSION_PANIC_RETRY`.
+pub use lockbud_report_types::{
+    group_by_lock, referenced_locks, sort_reports, truncate_reports, AnalysisMetadata, Baseline,
+    Level, LockGroup, Report, ReportContent, ReportEnvelope, Summary, ALIAS_CONFIDENCE_SAME_OWNER,
+    ALIAS_CONFIDENCE_UNKNOWN, HEURISTIC_CLOSURE_ARG, INVERSION_PANIC_RETRY,
+};
@@ -0,0 +1,784 @@
+//! Emit the reports as rustc diagnostics (`--message-format diagnostic`, the default when stderr is a terminal),
+//! so that they look like compiler warnings: the source snippets underlined at the locks,
+//! a note at the callchain, and the explanation as help.
+//! e.g., a doublelock has the primary span at the second lock and a label at the first lock.
+//! The spans of the locks in `DeadlockDiagnosis` are located by their raw spans,
+//! and the other spans by parsing the span strings against the source map.
+//! The spans not in the source map (e.g., `unknown`) are skipped.
+extern crate rustc_errors;
+extern crate rustc_span;
+
+use rustc_errors::MultiSpan;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::{BytePos, Span};
+
+use crate::detector::lock::report::{DeadlockDiagnosis, HotPathLock, RawSpan, ReleaseOrder};
+use crate::detector::report::Report;
+use lockbud_report_types::span_range;
+
+/// The marker of truncated callchains, see `track_callchains`.
+const TRUNCATED: &str = "...truncated";
+
+pub fn raw_span(span: Span) -> RawSpan {
+    let data = span.data();
+    RawSpan {
+        lo: data.lo.0,
+        hi: data.hi.0,
+    }
+}
+
+/// A span string with its raw span if known.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SpanRef<'a> {
+    text: &'a str,
+    raw: Option<RawSpan>,
+}
+
+impl<'a> SpanRef<'a> {
+    fn text(text: &'a str) -> Self {
+        Self { text, raw: None }
+    }
+}
+
+/// The parts of the diagnostic of a report, independent of the session.
+#[derive(Debug)]
+struct ReportDiagnostic<'a> {
+    message: String,
+    primary: (SpanRef<'a>, String),
+    labels: Vec<(SpanRef<'a>, String)>,
+    /// The callsites of the first callchain from the first to the second lock.
+    callchain: Vec<SpanRef<'a>>,
+    notes: Vec<String>,
+    help: &'a str,
+}
+
+/// Emit each report as a warning of the session.
+pub fn emit_diagnostics(tcx: TyCtxt<'_>, reports: &[Report]) {
+    for report in reports {
+        let diagnostic = report_diagnostic(report);
+        let primary = resolve(tcx, diagnostic.primary.0);
+        let mut builder = match primary {
+            Some(span) => {
+                let mut builder = tcx.sess.struct_span_warn(span, diagnostic.message);
+                builder.span_label(span, diagnostic.primary.1);
+                builder
+            }
+            None => tcx.sess.struct_warn(diagnostic.message),
+        };
+        for (span, label) in diagnostic.labels {
+            if let Some(span) = resolve(tcx, span) {
+                builder.span_label(span, label);
+            }
+        }
+        let callchain = diagnostic
+            .callchain
+            .into_iter()
+            .filter_map(|span| resolve(tcx, span))
+            .collect::<Vec<_>>();
+        if !callchain.is_empty() {
+            builder.span_note(
+                MultiSpan::from_spans(callchain),
+                match report {
+                    Report::Plugin(_) => "the lock is acquired through these calls",
+                    _ => "the second lock is acquired through these calls",
+                },
+            );
+        }
+        for note in diagnostic.notes {
+            builder.note(note);
+        }
+        builder.help(diagnostic.help.to_owned());
+        builder.emit();
+    }
+}
+
+/// Locate the span in the source map of the session.
+fn resolve(tcx: TyCtxt<'_>, span: SpanRef<'_>) -> Option<Span> {
+    if let Some(raw) = span.raw {
+        return Some(Span::with_root_ctxt(BytePos(raw.lo), BytePos(raw.hi)));
+    }
+    locate_span_text(tcx, span.text)
+}
+
+/// Locate a span string like `src/main.rs:9:5: 9:20 (#0)` in the source map of the session.
+pub fn locate_span_text(tcx: TyCtxt<'_>, text: &str) -> Option<Span> {
+    let (file, lo, hi) = span_range(text)?;
+    let source_map = tcx.sess.source_map();
+    let files = source_map.files();
+    let source_file = files
+        .iter()
+        .find(|source_file| source_file.name.prefer_remapped().to_string() == file)?;
+    // The columns count chars from 1.
+    let pos = |(line, col): (usize, usize)| {
+        let text = source_file.get_line(line.checked_sub(1)?)?;
+        let offset = text
+            .char_indices()
+            .map(|(idx, _)| idx)
+            .chain(std::iter::once(text.len()))
+            .nth(col.checked_sub(1)?)?;
+        let start = source_file.line_bounds(line - 1).start;
+        Some(start + BytePos(offset as u32))
+    };
+    Some(Span::with_root_ctxt(pos(lo)?, pos(hi)?))
+}
+
+fn report_diagnostic<'a>(report: &'a Report) -> ReportDiagnostic<'a> {
+    // The reports of the plugins are named after the plugins.
+    let kind = match report {
+        Report::Plugin(content) => &content.diagnosis.plugin,
+        _ => report.level_keys()[0],
+    };
+    let message = format!(
+        "lockbud: {} {}",
+        report.possibility().to_lowercase(),
+        kind.replace('_', " ")
+    );
+    let mut notes = Vec::new();
+    if !report.fingerprint().is_empty() {
+        notes.push(format!("fingerprint: {}", report.fingerprint()));
+    }
+    let diagnostic = |primary, labels, callchain, help| ReportDiagnostic {
+        message: message.clone(),
+        primary,
+        labels,
+        callchain,
+        notes: notes.clone(),
+        help,
+    };
+    match report {
+        Report::DoubleLock(content) => {
+            let (first, second) = lock_spans(&content.diagnosis);
+            diagnostic(
+                (
+                    second,
+                    format!(
+                        "second lock {} acquired here",
+                        lock_label(
+                            &content.diagnosis.second_lock_name,
+                            &content.diagnosis.second_lock_type
+                        )
+                    ),
+                ),
+                vec![(
+                    first,
+                    format!(
+                        "first lock {} acquired here",
+                        lock_label(
+                            &content.diagnosis.first_lock_name,
+                            &content.diagnosis.first_lock_type
+                        )
+                    ),
+                )],
+                first_callchain(&content.diagnosis.callchains),
+                &content.explanation,
+            )
+        }
+        Report::ConflictLock(content) => {
+            let mut labels = content
+                .diagnosis
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, diagnosis)| {
+                    let (first, second) = lock_spans(diagnosis);
+                    [
+                        (
+                            first,
+                            format!(
+                                "#{} {} acquired here",
+                                idx,
+                                lock_label(&diagnosis.first_lock_name, &diagnosis.first_lock_type)
+                            ),
+                        ),
+                        (
+                            second,
+                            format!(
+                                "#{} then {} acquired here",
+                                idx,
+                                lock_label(
+                                    &diagnosis.second_lock_name,
+                                    &diagnosis.second_lock_type
+                                )
+                            ),
+                        ),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let primary = if labels.len() > 1 {
+                labels.remove(1)
+            } else {
+                (SpanRef::text(""), String::new())
+            };
+            let callchain = content
+                .diagnosis
+                .first()
+                .map(|diagnosis| first_callchain(&diagnosis.callchains))
+                .unwrap_or_default();
+            diagnostic(primary, labels, callchain, &content.explanation)
+        }
+        Report::CondvarDeadlock(content) => {
+            let diagnosis = &content.diagnosis;
+            let mut labels = vec![(
+                SpanRef::text(&diagnosis.condvar_notify_callsite_span),
+                format!("`{}` here", diagnosis.condvar_notify_type),
+            )];
+            for locks in &diagnosis.deadlocks {
+                labels.push((
+                    SpanRef::text(&locks.wait_lock_span),
+                    format!(
+                        "{} held while waiting",
+                        lock_label(&locks.wait_lock_name, &locks.wait_lock_type)
+                    ),
+                ));
+                labels.push((
+                    SpanRef::text(&locks.notify_lock_span),
+                    format!(
+                        "{} acquired before notifying",
+                        lock_label(&locks.notify_lock_name, &locks.notify_lock_type)
+                    ),
+                ));
+            }
+            diagnostic(
+                (
+                    SpanRef::text(&diagnosis.condvar_wait_callsite_span),
+                    format!("`{}` here", diagnosis.condvar_wait_type),
+                ),
+                labels,
+                Vec::new(),
+                &content.explanation,
+            )
+        }
+        Report::CondvarMultipleMutexes(content) => {
+            let mut labels = content
+                .diagnosis
+                .mutexes
+                .iter()
+                .flat_map(|mutex| {
+                    mutex
+                        .wait_spans
+                        .iter()
+                        .map(|span| {
+                            (
+                                SpanRef::text(span),
+                                format!("waited with the guard of `{}`", mutex.mutex),
+                            )
+                        })
+                        .chain([(
+                            SpanRef::text(&mutex.declaration_span),
+                            format!("`{}` declared here", mutex.mutex),
+                        )])
+                })
+                .collect::<Vec<_>>();
+            let primary = if labels.is_empty() {
+                (SpanRef::text(""), String::new())
+            } else {
+                labels.remove(0)
+            };
+            diagnostic(primary, labels, Vec::new(), &content.explanation)
+        }
+        Report::AwaitHoldingLock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.lock_span),
+                format!(
+                    "`{}` held across an await in `{}`",
+                    content.diagnosis.lock_type, content.diagnosis.fn_name
+                ),
+            ),
+            Vec::new(),
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::BlockOnHoldingLock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.block_on_span),
+                format!(
+                    "`{}` blocks on a future in `{}`",
+                    content.diagnosis.block_on_api, content.diagnosis.fn_name
+                ),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.lock_span),
+                format!("`{}` held here", content.diagnosis.lock_type),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::PoolSelfJoin(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.wait_span),
+                format!("`{}` waits here", content.diagnosis.wait_api),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.install_span),
+                format!("running on `{}` installed here", content.diagnosis.pool),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::RayonDeadlock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.inner_lock_span),
+                format!(
+                    "`{}` acquired again in a closure run on the pool",
+                    content.diagnosis.lock_type
+                ),
+            ),
+            vec![
+                (
+                    SpanRef::text(&content.diagnosis.outer_lock_span),
+                    "first acquired here".to_owned(),
+                ),
+                (
+                    SpanRef::text(&content.diagnosis.rayon_span),
+                    format!(
+                        "the caller blocks on `{}` here",
+                        content.diagnosis.rayon_api
+                    ),
+                ),
+            ],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::RendezvousDeadlock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.rendezvous_span),
+                format!(
+                    "blocks on `{}` holding `{}`",
+                    content.diagnosis.rendezvous_api, content.diagnosis.lock_type
+                ),
+            ),
+            vec![
+                (
+                    SpanRef::text(&content.diagnosis.lock_span),
+                    "acquired here".to_owned(),
+                ),
+                (
+                    SpanRef::text(&content.diagnosis.peer_lock_span),
+                    format!(
+                        "acquired by the peer thread rooted at {}",
+                        content.diagnosis.peer_thread
+                    ),
+                ),
+            ],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::OnceReentrancy(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.inner_span),
+                format!(
+                    "`{}` reached again by its initializer",
+                    content.diagnosis.once_api
+                ),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.outer_span),
+                "the initialization starts here".to_owned(),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::OnceInitDeadlock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.inner_lock_span),
+                format!(
+                    "`{}` acquired again by the initializer",
+                    content.diagnosis.lock_type
+                ),
+            ),
+            vec![
+                (
+                    SpanRef::text(&content.diagnosis.outer_lock_span),
+                    "first acquired here".to_owned(),
+                ),
+                (
+                    SpanRef::text(&content.diagnosis.init_span),
+                    format!("the caller blocks on `{}` here", content.diagnosis.once_api),
+                ),
+            ],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::LockGuardLeaked(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.leak_span),
+                format!(
+                    "`{}` leaked by `{}` here, never released",
+                    content.diagnosis.lock_type, content.diagnosis.leak_api
+                ),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.lock_span),
+                "acquired here".to_owned(),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::DoubleUnlock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.later_span),
+                format!("`{}` unlocked again here", content.diagnosis.lock_type),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.unlock_span),
+                format!(
+                    "already unlocked by `{}` here",
+                    content.diagnosis.unlock_api
+                ),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::UseAfterUnlock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.later_span),
+                format!("`{}` used after unlocked", content.diagnosis.lock_type),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.unlock_span),
+                format!("unlocked by `{}` here", content.diagnosis.unlock_api),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::ImplicitReleaseOrder(content) => {
+            let release = |order: &'a ReleaseOrder| {
+                (
+                    SpanRef::text(&order.span),
+                    format!(
+                        "`{}` releases `{}`",
+                        order.name,
+                        order.lock_types.join("` before `")
+                    ),
+                )
+            };
+            diagnostic(
+                release(&content.diagnosis.first),
+                vec![release(&content.diagnosis.second)],
+                Vec::new(),
+                &content.explanation,
+            )
+        }
+        Report::SendHazard(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.guard_span),
+                format!(
+                    "`{}` held across an await in `{}`",
+                    content.diagnosis.guard_type, content.diagnosis.fn_name
+                ),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.spawn_span),
+                format!("spawned by `{}` here", content.diagnosis.spawn_api),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::HotPathLock(content) => {
+            let diagnosis = &content.diagnosis;
+            let label = |lock: &'a HotPathLock, paths: &str| {
+                (
+                    SpanRef::text(&lock.declaration_span),
+                    format!(
+                        "`{}` (`{}`) acquired on {} paths, acquisitions: {}",
+                        lock.name, lock.lock_type, paths, lock.acquisitions
+                    ),
+                )
+            };
+            diagnostic(
+                (
+                    SpanRef::text(&diagnosis.entry_span),
+                    format!(
+                        "`{}` acquires {} lock(s) on every path",
+                        diagnosis.entry_fn,
+                        diagnosis.must_acquire.len()
+                    ),
+                ),
+                diagnosis
+                    .must_acquire
+                    .iter()
+                    .map(|lock| label(lock, "all"))
+                    .chain(diagnosis.may_acquire.iter().map(|lock| label(lock, "some")))
+                    .collect(),
+                Vec::new(),
+                &content.explanation,
+            )
+        }
+        Report::DiscardedGuard(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.lock_span),
+                format!(
+                    "`{}` acquired and immediately released in `{}`",
+                    content.diagnosis.lock_type, content.diagnosis.fn_name
+                ),
+            ),
+            Vec::new(),
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::LockCoverage(content) => {
+            let diagnosis = &content.diagnosis;
+            let mut unprotected = diagnosis.unprotected_spans.iter();
+            let primary = unprotected.next().map_or("", String::as_str);
+            diagnostic(
+                (
+                    SpanRef::text(primary),
+                    format!(
+                        "`{}` accessed without `{}` (`{}`), held at {} of its {} accesses",
+                        diagnosis.field,
+                        diagnosis.lock,
+                        diagnosis.lock_type,
+                        diagnosis.protected_accesses,
+                        diagnosis.accesses
+                    ),
+                ),
+                unprotected
+                    .map(|span| {
+                        (
+                            SpanRef::text(span),
+                            "also accessed without the lock".to_owned(),
+                        )
+                    })
+                    .chain(std::iter::once((
+                        SpanRef::text(&diagnosis.field_span),
+                        format!("`{}` declared here", diagnosis.field),
+                    )))
+                    .collect(),
+                Vec::new(),
+                &content.explanation,
+            )
+        }
+        Report::AtomicityViolation(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.atomic_writer),
+                format!(
+                    "atomic writer {} dependent on the reader in `{}`",
+                    content.diagnosis.dep_kind, content.diagnosis.fn_name
+                ),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.atomic_reader),
+                "atomic reader".to_owned(),
+            )],
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::InvalidFree(content) => diagnostic(
+            (SpanRef::text(report.spans().0), content.diagnosis.clone()),
+            Vec::new(),
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::UseAfterFree(content) => diagnostic(
+            (SpanRef::text(report.spans().0), content.diagnosis.clone()),
+            Vec::new(),
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::DoubleFree(content) => diagnostic(
+            (SpanRef::text(report.spans().0), content.diagnosis.clone()),
+            Vec::new(),
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::PanicInDrop(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.panic_span),
+                format!(
+                    "`{}` may panic while dropping `{}`",
+                    content.diagnosis.panic_api, content.diagnosis.drop_type
+                ),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.drop_span),
+                "the drop impl".to_owned(),
+            )],
+            first_callchain(&content.diagnosis.callchains),
+            &content.explanation,
+        ),
+        Report::PanicHoldingLock(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.panic_span),
+                format!(
+                    "`{}` may panic while holding `{}`",
+                    content.diagnosis.panic_api, content.diagnosis.lock_type
+                ),
+            ),
+            vec![(
+                SpanRef::text(&content.diagnosis.guard_span),
+                "the lock is acquired here and poisoned by the panic".to_owned(),
+            )],
+            first_callchain(&content.diagnosis.callchains),
+            &content.explanation,
+        ),
+        Report::RefCellDoubleBorrow(content) => {
+            let (first, second) = lock_spans(&content.diagnosis);
+            diagnostic(
+                (
+                    second,
+                    format!(
+                        "`{}` borrowed again here",
+                        content.diagnosis.second_lock_type
+                    ),
+                ),
+                vec![(
+                    first,
+                    format!("`{}` still live here", content.diagnosis.first_lock_type),
+                )],
+                first_callchain(&content.diagnosis.callchains),
+                &content.explanation,
+            )
+        }
+        Report::Panic(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.callsite_span),
+                format!(
+                    "`{}` may panic in `{}`",
+                    content.diagnosis.panic_api, content.diagnosis.def_path
+                ),
+            ),
+            Vec::new(),
+            Vec::new(),
+            &content.explanation,
+        ),
+        Report::Plugin(content) => diagnostic(
+            (
+                SpanRef::text(&content.diagnosis.span),
+                format!("`{}` here", content.diagnosis.subject),
+            ),
+            Vec::new(),
+            first_callchain(&content.diagnosis.callchains),
+            &content.explanation,
+        ),
+    }
+}
+
+/// `` `name` (`type`) `` if the lock is named, otherwise `` `type` ``.
+fn lock_label(name: &Option<String>, lock_type: &str) -> String {
+    match name {
+        Some(name) => format!("`{}` (`{}`)", name, lock_type),
+        None => format!("`{}`", lock_type),
+    }
+}
+
+/// The (first, second) lock spans.
+fn lock_spans(diagnosis: &DeadlockDiagnosis) -> (SpanRef<'_>, SpanRef<'_>) {
+    let (first_raw, second_raw) = diagnosis.raw_spans.unzip();
+    (
+        SpanRef {
+            text: &diagnosis.first_lock_span,
+            raw: first_raw,
+        },
+        SpanRef {
+            text: &diagnosis.second_lock_span,
+            raw: second_raw,
+        },
+    )
+}
+
+/// The first callsite of each call in the first callchain.
+fn first_callchain(callchains: &[Vec<Vec<String>>]) -> Vec<SpanRef<'_>> {
+    callchains
+        .first()
+        .into_iter()
+        .flatten()
+        .filter_map(|callsites| callsites.first())
+        .filter(|callsite| callsite.as_str() != TRUNCATED)
+        .map(|callsite| SpanRef::text(callsite))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::report::ReportContent;
+    use crate::test_utils::{with_tcx, write_src};
+    use rustc_span::SyntaxContext;
+
+    #[test]
+    fn test_doublelock_diagnostic() {
+        let mut diagnosis = DeadlockDiagnosis::new(
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:17:17: 17:30 (#0)".to_owned(),
+            "StdMutex(i32)".to_owned(),
+            "src/main.rs:20:10: 20:23 (#0)".to_owned(),
+            vec![vec![
+                vec!["src/main.rs:18:5: 18:10 (#0)".to_owned()],
+                vec![TRUNCATED.to_owned()],
+            ]],
+        );
+        let (first, second) = (RawSpan { lo: 1, hi: 2 }, RawSpan { lo: 3, hi: 4 });
+        diagnosis.raw_spans = Some((first, second));
+        diagnosis.first_lock_name = Some("self.state".to_owned());
+        let report = Report::DoubleLock(ReportContent::new(
+            "DoubleLock".to_owned(),
+            "Possibly".to_owned(),
+            diagnosis,
+            "The first lock is not released when acquiring the second lock".to_owned(),
+        ));
+        let diagnostic = report_diagnostic(&report);
+        assert_eq!(diagnostic.message, "lockbud: possibly double lock");
+        assert_eq!(diagnostic.primary.0.raw, Some(second));
+        assert_eq!(
+            diagnostic.primary.1,
+            "second lock `StdMutex(i32)` acquired here"
+        );
+        assert_eq!(diagnostic.labels.len(), 1);
+        assert_eq!(diagnostic.labels[0].0.raw, Some(first));
+        assert_eq!(
+            diagnostic.labels[0].1,
+            "first lock `self.state` (`StdMutex(i32)`) acquired here"
+        );
+        assert_eq!(
+            diagnostic.callchain,
+            vec![SpanRef::text("src/main.rs:18:5: 18:10 (#0)")]
+        );
+        assert!(diagnostic.notes.is_empty());
+        assert_eq!(
+            diagnostic.help,
+            "The first lock is not released when acquiring the second lock"
+        );
+    }
+
+    #[test]
+    fn test_resolve_span_string() {
+        let src = write_src(
+            "resolve-span",
+            "pub fn f() {\n    let s = \"ünïcode\"; let _t = s.len();\n}\n",
+        );
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let body = tcx.optimized_mir(tcx.hir().body_owners().next().unwrap());
+                let spans = body
+                    .basic_blocks
+                    .iter()
+                    .flat_map(|bb| bb.statements.iter().map(|stmt| stmt.source_info.span))
+                    .chain(body.local_decls.iter().map(|decl| decl.source_info.span))
+                    .filter(|span| !span.is_dummy())
+                    .collect::<Vec<_>>();
+                assert!(!spans.is_empty());
+                for span in spans {
+                    let text = format!("{:?}", span);
+                    assert_eq!(
+                        resolve(tcx, SpanRef::text(&text)),
+                        Some(span.with_ctxt(SyntaxContext::root())),
+                        "{}",
+                        text
+                    );
+                    assert_eq!(
+                        resolve(
+                            tcx,
+                            SpanRef {
+                                text: "",
+                                raw: Some(raw_span(span))
+                            }
+                        ),
+                        Some(span.with_ctxt(SyntaxContext::root()))
+                    );
+                }
+                assert_eq!(resolve(tcx, SpanRef::text("unknown")), None);
+            }),
+        );
+    }
+}
@@ -1,6 +1,6 @@
 //! Denotes Condvar APIs in std and parking_lot.
 //!
-//! 1. std::Condvar::wait.*(&Condvar, MutexGuard,.*) -> MutexGuard
+//! 1. std::Condvar::wait.*(&Condvar, MutexGuard,.*) -> MutexGuard, the same for loom::sync::Condvar
 //! 2. std::Condvar::notify.*(&Condvar)
 //! 3. parking_lot::Condvar::wait.*(&Condvar, &mut MutexGuard,.*)
 //! 4. parking_lot::Condvar::notify.*(&Condvar)
@@ -14,10 +14,12 @@ pub enum CondvarApi {
 
 impl CondvarApi {
     pub fn from_instance<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
-        let path = tcx.def_path_str_with_args(instance.def_id(), &*instance.args);
-        let std_condvar = "std::sync::Condvar::";
+        let path = tcx.def_path_str_with_args(instance.def_id(), instance.args);
+        let std_condvar = ["std::sync::Condvar::", "loom::sync::Condvar::"]
+            .into_iter()
+            .find(|std_condvar| path.starts_with(std_condvar));
         let parking_lot_condvar = "parking_lot::Condvar::";
-        if path.starts_with(std_condvar) {
+        if let Some(std_condvar) = std_condvar {
             let tail = &path.as_bytes()[std_condvar.len()..];
             let std_condvar_api = if tail.starts_with("wait::".as_bytes()) {
                 StdCondvarApi::Wait(StdWait::Wait)
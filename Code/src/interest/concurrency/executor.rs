@@ -0,0 +1,22 @@
+//! Denotes the executor APIs blocking the current thread on a future.
+//!
+//! 1. futures::executor::block_on(Future)
+//! 2. tokio::runtime::Runtime::block_on(&Runtime, Future), tokio::runtime::Handle::block_on(&Handle, Future)
+//!
+//! The future may need a lock held by the blocked thread to make progress,
+//! e.g., a task of the runtime waiting for the lock, so blocking on it in a critical section may deadlock.
+use rustc_middle::ty::{Instance, TyCtxt};
+
+use super::thread::api_path;
+
+/// Whether the instance is an executor API blocking on a future.
+pub fn is_block_on_api<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    matches!(
+        api_path(instance, tcx).as_str(),
+        "futures::executor::block_on"
+            | "futures_executor::block_on"
+            | "futures_executor::local_pool::block_on"
+            | "tokio::runtime::Runtime::block_on"
+            | "tokio::runtime::Handle::block_on"
+    )
+}
@@ -1,18 +1,31 @@
 //! Collect LockGuard info.
 extern crate rustc_hash;
+extern crate rustc_hir;
 extern crate rustc_span;
+extern crate rustc_target;
 
 use smallvec::SmallVec;
 use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
 
 use rustc_hash::FxHashMap;
+use rustc_hir::def_id::DefId;
 use rustc_middle::mir::visit::{MutatingUseContext, NonMutatingUseContext, PlaceContext, Visitor};
-use rustc_middle::mir::{Body, Local, Location, TerminatorKind};
+use rustc_middle::mir::{
+    AggregateKind, Body, Local, Location, Operand, Place, PlaceRef, ProjectionElem, Rvalue,
+    StatementKind, Terminator, TerminatorKind, VarDebugInfoContents, RETURN_PLACE,
+};
 use rustc_middle::ty::EarlyBinder;
-use rustc_middle::ty::{self, Instance, ParamEnv, TyCtxt};
+use rustc_middle::ty::{self, Instance, InstanceDef, ParamEnv, TyCtxt};
 use rustc_span::Span;
+use rustc_target::abi::FieldIdx;
 
-use crate::analysis::callgraph::InstanceId;
+use petgraph::visit::IntoNodeReferences;
+
+use super::lock_registry::{GuardPattern, LockAccess, LockRegistry};
+use crate::analysis::callgraph::{CallGraph, InstanceId};
+use crate::interest::memory::ownership;
 
 /// Uniquely identify a LockGuard in a crate.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -28,7 +41,7 @@ impl LockGuardId {
 }
 
 /// The possibility of deadlock.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum DeadlockPossibility {
     Probably,
     Possibly,
@@ -54,128 +67,257 @@ impl PartialOrd for DeadlockPossibility {
     }
 }
 
-/// LockGuardKind, DataTy
-#[derive(Clone, Debug)]
-pub enum LockGuardTy<'tcx> {
-    StdMutex(ty::Ty<'tcx>),
-    ParkingLotMutex(ty::Ty<'tcx>),
-    SpinMutex(ty::Ty<'tcx>),
-    StdRwLockRead(ty::Ty<'tcx>),
-    StdRwLockWrite(ty::Ty<'tcx>),
-    ParkingLotRead(ty::Ty<'tcx>),
-    ParkingLotWrite(ty::Ty<'tcx>),
-    SpinRead(ty::Ty<'tcx>),
-    SpinWrite(ty::Ty<'tcx>),
+impl DeadlockPossibility {
+    /// Under `--paranoid`, Unknown (from the unknown alias results) is treated as Possibly.
+    pub fn or_paranoid(self, paranoid: bool) -> Self {
+        match self {
+            DeadlockPossibility::Unknown if paranoid => DeadlockPossibility::Possibly,
+            _ => self,
+        }
+    }
+}
+
+/// The lockguard family and the data type it protects, e.g., `StdMutex(i32)`.
+/// The families are declared by the patterns in `LockRegistry`.
+#[derive(Clone)]
+pub struct LockGuardTy<'tcx> {
+    pattern: Arc<GuardPattern>,
+    data_ty: ty::Ty<'tcx>,
+}
+
+impl<'tcx> fmt::Debug for LockGuardTy<'tcx> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({:?})", self.pattern.name, self.data_ty)
+    }
 }
 
 impl<'tcx> LockGuardTy<'tcx> {
+    pub fn new(pattern: Arc<GuardPattern>, data_ty: ty::Ty<'tcx>) -> Self {
+        Self { pattern, data_ty }
+    }
+
+    /// Get the LockGuardTy of `local_ty` with the built-in patterns.
     pub fn from_local_ty(local_ty: ty::Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
-        // e.g.
-        // extract i32 from
-        // sync: MutexGuard<i32, Poison>
-        // spin: MutexGuard<i32>
-        // parking_lot: MutexGuard<RawMutex, i32>
-        // async, tokio, future: currently Unsupported
-        if let ty::TyKind::Adt(adt_def, substs) = local_ty.kind() {
-            let path = tcx.def_path_str_with_args(adt_def.did(), &*substs);
-            // quick fail
-            if !path.contains("MutexGuard")
-                && !path.contains("RwLockReadGuard")
-                && !path.contains("RwLockWriteGuard")
+        LockRegistry::builtin().lockguard_ty(local_ty, tcx)
+    }
+
+    pub fn access(&self) -> LockAccess {
+        self.pattern.access
+    }
+
+    /// The guard of an async lock is meant to be held across await points.
+    pub fn is_async(&self) -> bool {
+        self.pattern.asynchronous
+    }
+
+    /// Whether the guard can be sent to another thread, e.g., by a future spawned by `tokio::spawn`.
+    /// The guards of async locks and spin are Send, while those of std (and their wrappers),
+    /// lock_api, and the user patterns are regarded as not.
+    pub fn is_send(&self) -> bool {
+        self.pattern.asynchronous || matches!(self.pattern.lock(), "SpinMutex" | "SpinRwLock")
+    }
+
+    /// Whether a panic while the guard is live poisons the lock, i.e., the std `MutexGuard` and `RwLockWriteGuard`,
+    /// so that the later `lock().unwrap()` of the lock panics too.
+    pub fn poisons_on_panic(&self) -> bool {
+        matches!(self.pattern.name.as_str(), "StdMutex" | "StdRwLockWrite")
+    }
+
+    /// The guard of this family protecting the data of `source`, e.g., a `MappedMutexGuard` of a field
+    /// mapped from a `MutexGuard` of the struct still guards the struct, so it deadlocks with the struct's guards.
+    pub fn with_data_of(&self, source: &Self) -> Self {
+        Self::new(self.pattern.clone(), source.data_ty)
+    }
+
+    /// Whether the guard is a `RefCell` borrow, i.e., `Ref` or `RefMut`,
+    /// whose conflicting borrows panic rather than block.
+    pub fn is_borrow(&self) -> bool {
+        self.pattern.lock() == "RefCell"
+    }
+
+    /// Whether the guards are of the same lock protecting the same data, regardless of the access.
+    pub fn is_same_lock(&self, other: &Self) -> bool {
+        self.pattern.lock() == other.pattern.lock() && self.data_ty == other.data_ty
+    }
+
+    /// Whether the guards are of the same reentrant lock, e.g., `ReentrantMutex`,
+    /// which can be acquired again by the thread holding it.
+    pub fn is_reentrant_with(&self, other: &Self) -> bool {
+        self.is_same_lock(other) && self.pattern.reentrant && other.pattern.reentrant
+    }
+
+    /// Only the guards of the same lock protecting the same data may deadlock.
+    /// Two guards held by the same thread deadlock if either excludes the other,
+    /// except that a reentrant lock can be acquired again by the same thread.
+    /// Two read guards (or a read guard and an upgradable read guard) may deadlock
+    /// if the lock has write priority (see `builtin_patterns`),
+    /// since a write lock from another thread may interleave them.
+    pub fn deadlock_with(&self, other: &Self) -> DeadlockPossibility {
+        if self.is_reentrant_with(other) {
+            return DeadlockPossibility::Unlikely;
+        }
+        self.excludes(other)
+    }
+
+    /// Like `deadlock_with`, but the guards are held by different threads, e.g., in a conflictlock cycle,
+    /// so a reentrant lock held by one thread excludes the other.
+    /// A `RefCell` is not Sync, so its borrows are never held by different threads.
+    pub fn deadlock_across_threads_with(&self, other: &Self) -> DeadlockPossibility {
+        if self.is_borrow() {
+            return DeadlockPossibility::Unlikely;
+        }
+        self.excludes(other)
+    }
+
+    /// Whether either guard excludes the other, regardless of the threads holding them.
+    /// The guards of a sharded lock only possibly lock the same shard.
+    fn excludes(&self, other: &Self) -> DeadlockPossibility {
+        use LockAccess::*;
+        if !self.is_same_lock(other) {
+            return DeadlockPossibility::Unlikely;
+        }
+        match (self.access(), other.access()) {
+            (Read, Read) | (Read, Upgradable) | (Upgradable, Read)
+                if !self.pattern.recursive_read =>
             {
-                return None;
-            }
-            let first_part = path.split('<').next()?;
-            if first_part.contains("MutexGuard") {
-                if first_part.contains("async")
-                    || first_part.contains("tokio")
-                    || first_part.contains("future")
-                    || first_part.contains("loom")
-                {
-                    // Currentlly does not support async lock or loom
-                    None
-                } else if first_part.contains("spin") {
-                    Some(LockGuardTy::SpinMutex(substs.types().next()?))
-                } else if first_part.contains("lock_api") || first_part.contains("parking_lot") {
-                    Some(LockGuardTy::ParkingLotMutex(substs.types().nth(1)?))
-                } else {
-                    // std::sync::Mutex or its wrapper by default
-                    Some(LockGuardTy::StdMutex(substs.types().next()?))
-                }
-            } else if first_part.contains("RwLockReadGuard") {
-                if first_part.contains("async")
-                    || first_part.contains("tokio")
-                    || first_part.contains("future")
-                    || first_part.contains("loom")
-                {
-                    // Currentlly does not support async lock or loom
-                    None
-                } else if first_part.contains("spin") {
-                    Some(LockGuardTy::SpinRead(substs.types().next()?))
-                } else if first_part.contains("lock_api") || first_part.contains("parking_lot") {
-                    Some(LockGuardTy::ParkingLotRead(substs.types().nth(1)?))
-                } else {
-                    // std::sync::RwLockReadGuard or its wrapper by default
-                    Some(LockGuardTy::StdRwLockRead(substs.types().next()?))
-                }
-            } else if first_part.contains("RwLockWriteGuard") {
-                if first_part.contains("async")
-                    || first_part.contains("tokio")
-                    || first_part.contains("future")
-                    || first_part.contains("loom")
-                {
-                    // Currentlly does not support async lock or loom
-                    None
-                } else if first_part.contains("spin") {
-                    Some(LockGuardTy::SpinWrite(substs.types().next()?))
-                } else if first_part.contains("lock_api") || first_part.contains("parking_lot") {
-                    Some(LockGuardTy::ParkingLotWrite(substs.types().nth(1)?))
-                } else {
-                    // std::sync::RwLockReadGuard or its wrapper by default
-                    Some(LockGuardTy::StdRwLockWrite(substs.types().next()?))
-                }
-            } else {
-                None
+                DeadlockPossibility::Possibly
             }
-        } else {
-            None
+            (Read, Read) | (Read, Upgradable) | (Upgradable, Read) => DeadlockPossibility::Unlikely,
+            _ if self.pattern.sharded || other.pattern.sharded => DeadlockPossibility::Possibly,
+            _ => DeadlockPossibility::Probably,
         }
     }
+}
 
-    /// In parking_lot, the read lock is by default non-recursive if not specified.
-    /// if two recursively acquired read locks in one thread are interleaved
-    /// by a write lock from another thread, a deadlock may happen.
-    /// The reason is write lock has higher priority than read lock in parking_lot.
-    /// In std::sync, the implementation of read lock depends on the underlying OS.
-    /// AFAIK, the implementation on Windows and Mac have write priority.
-    /// So read lock in std::sync cannot be acquired recursively on the two systems.
-    /// spin explicitly documents no write priority. So the read lock in spin can
-    /// be acquired recursively.
-    pub fn deadlock_with(&self, other: &Self) -> DeadlockPossibility {
-        use LockGuardTy::*;
-        match (self, other) {
-            (StdMutex(a), StdMutex(b))
-            | (ParkingLotMutex(a), ParkingLotMutex(b))
-            | (SpinMutex(a), SpinMutex(b))
-            | (StdRwLockWrite(a), StdRwLockWrite(b))
-            | (StdRwLockWrite(a), StdRwLockRead(b))
-            | (StdRwLockRead(a), StdRwLockWrite(b))
-            | (ParkingLotWrite(a), ParkingLotWrite(b))
-            | (ParkingLotWrite(a), ParkingLotRead(b))
-            | (ParkingLotRead(a), ParkingLotWrite(b))
-            | (SpinWrite(a), SpinWrite(b))
-            | (SpinWrite(a), SpinRead(b))
-            | (SpinRead(a), SpinWrite(b))
-                if a == b =>
+/// Guard newtypes, i.e., the guards of lock newtypes in facade crates, e.g.,
+/// ```ignore
+/// pub struct Mutex<T>(parking_lot::Mutex<T>);
+/// pub struct Locked<'a, T>(parking_lot::MutexGuard<'a, T>);
+/// impl<T> Mutex<T> {
+///     pub fn lock(&self) -> Locked<'_, T> {
+///         Locked(self.0.lock())
+///     }
+/// }
+/// ```
+/// A guard newtype is either declared with the lock family it acts as,
+/// or detected from a wrapper fn returning it, whose body makes a single underlying lock call.
+/// A detected newtype inherits the identity of the lockguard it wraps.
+#[derive(Debug, Default)]
+pub struct GuardNewtypes {
+    /// The lockguard patterns.
+    registry: LockRegistry,
+    /// (newtype path without generic args, lock family)
+    declared: Vec<(String, String)>,
+    /// newtype -> the field wrapping the lockguard
+    detected: FxHashMap<DefId, FieldIdx>,
+}
+
+impl GuardNewtypes {
+    pub fn new(registry: LockRegistry, declared: Vec<(String, String)>) -> Self {
+        Self {
+            registry,
+            declared,
+            detected: Default::default(),
+        }
+    }
+
+    /// Get the LockGuardTy of `local_ty` if it is a lockguard or a guard newtype.
+    pub fn lockguard_ty<'tcx>(
+        &self,
+        local_ty: ty::Ty<'tcx>,
+        tcx: TyCtxt<'tcx>,
+    ) -> Option<LockGuardTy<'tcx>> {
+        if let Some(lockguard_ty) = self.registry.lockguard_ty(local_ty, tcx) {
+            return Some(lockguard_ty);
+        }
+        let (adt_def, substs) = match local_ty.kind() {
+            ty::TyKind::Adt(adt_def, substs) if adt_def.is_struct() => (adt_def, substs),
+            _ => return None,
+        };
+        if let Some(field) = self.detected.get(&adt_def.did()) {
+            let field_ty = adt_def.non_enum_variant().fields[*field].ty(tcx, substs);
+            return self.lockguard_ty(field_ty, tcx);
+        }
+        if self.declared.is_empty() {
+            return None;
+        }
+        let path = tcx.def_path_str(adt_def.did());
+        // Local paths are printed without the crate name.
+        let crate_path = format!("{}::{}", tcx.crate_name(adt_def.did().krate), path);
+        let (_, family) = self
+            .declared
+            .iter()
+            .find(|(declared, _)| *declared == path || *declared == crate_path)?;
+        // The data is the first type arg, or unit if the newtype has no type args.
+        let data_ty = substs.types().next().unwrap_or(tcx.types.unit);
+        let pattern = self.registry.family(family)?;
+        Some(LockGuardTy::new(pattern.clone(), data_ty))
+    }
+
+    /// Detect the guard newtypes returned by the wrapper fns in the callgraph.
+    /// A wrapper fn forwards to the underlying lock if
+    /// 1. it returns a struct with exactly one field of lockguard, and
+    /// 2. exactly one call in its body returns a lockguard, e.g., `Mutex::lock` or `LockResult::unwrap`.
+    pub fn detect<'tcx>(
+        &mut self,
+        callgraph: &CallGraph<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        param_env: ParamEnv<'tcx>,
+    ) {
+        for (_, node) in callgraph.graph.node_references() {
+            let instance = node.instance();
+            let def_id = instance.def_id();
+            // The virtual calls and the shims, e.g., `<dyn Subscriber>::event_enabled`, are no wrappers.
+            if !matches!(instance.def, InstanceDef::Item(_))
+                || !tcx.is_mir_available(def_id)
+                || matches!(
+                    tcx.crate_name(def_id.krate).as_str(),
+                    "std" | "core" | "alloc"
+                )
             {
-                DeadlockPossibility::Probably
+                continue;
             }
-            (StdRwLockRead(a), StdRwLockRead(b)) | (ParkingLotRead(a), ParkingLotRead(b))
-                if a == b =>
+            let body = tcx.instance_mir(instance.def);
+            let monomorphize = |ty| {
+                instance.instantiate_mir_and_normalize_erasing_regions(
+                    tcx,
+                    param_env,
+                    EarlyBinder::bind(ty),
+                )
+            };
+            let ret_ty = monomorphize(body.local_decls[RETURN_PLACE].ty);
+            let (adt_def, substs) = match ret_ty.kind() {
+                ty::TyKind::Adt(adt_def, substs) if adt_def.is_struct() => (adt_def, substs),
+                _ => continue,
+            };
+            if self.detected.contains_key(&adt_def.did())
+                || self.lockguard_ty(ret_ty, tcx).is_some()
             {
-                DeadlockPossibility::Possibly
+                continue;
+            }
+            let mut guard_fields = adt_def
+                .non_enum_variant()
+                .fields
+                .iter_enumerated()
+                .filter(|(_, field)| self.lockguard_ty(field.ty(tcx, substs), tcx).is_some())
+                .map(|(idx, _)| idx);
+            let field = match (guard_fields.next(), guard_fields.next()) {
+                (Some(field), None) => field,
+                _ => continue,
+            };
+            let lock_calls = body
+                .basic_blocks
+                .iter()
+                .filter(|bb_data| match &bb_data.terminator().kind {
+                    TerminatorKind::Call { destination, .. } => self
+                        .lockguard_ty(monomorphize(destination.ty(body, tcx).ty), tcx)
+                        .is_some(),
+                    _ => false,
+                })
+                .count();
+            if lock_calls == 1 {
+                self.detected.insert(adt_def.did(), field);
             }
-            _ => DeadlockPossibility::Unlikely,
         }
     }
 }
@@ -188,7 +330,16 @@ pub struct LockGuardInfo<'tcx> {
     pub gen_locs: SmallVec<[Location; 4]>,
     pub move_gen_locs: SmallVec<[Location; 4]>,
     pub recursive_gen_locs: SmallVec<[Location; 4]>,
+    /// The gens moving another lockguard into it, e.g., `let g2 = g1;` or `break 'outer g1`,
+    /// which continue the live range of the other lockguard rather than acquire the lock.
+    pub transfer_gen_locs: SmallVec<[Location; 4]>,
     pub kill_locs: SmallVec<[Location; 4]>,
+    /// The struct type owning the lock as a field, e.g., `Service` of `self.state.read()`,
+    /// traced from the lockguard acquired by call, see `lock_owner_ty`.
+    pub owner_ty: Option<ty::Ty<'tcx>>,
+    /// The source expression of the lock, e.g., `self.inner.state_mu` of `self.inner.state_mu.lock()`,
+    /// traced from the lockguard acquired by call, see `lock_name`.
+    pub lock_name: Option<String>,
 }
 
 impl<'tcx> LockGuardInfo<'tcx> {
@@ -199,7 +350,10 @@ impl<'tcx> LockGuardInfo<'tcx> {
             gen_locs: Default::default(),
             move_gen_locs: Default::default(),
             recursive_gen_locs: Default::default(),
+            transfer_gen_locs: Default::default(),
             kill_locs: Default::default(),
+            owner_ty: None,
+            lock_name: None,
         }
     }
 
@@ -214,6 +368,22 @@ impl<'tcx> LockGuardInfo<'tcx> {
 
 pub type LockGuardMap<'tcx> = FxHashMap<LockGuardId, LockGuardInfo<'tcx>>;
 
+/// A guard holder, i.e., a struct constructed from more than one lockguard moved into its fields, e.g.,
+/// ```ignore
+/// struct Transfer<'a> {
+///     accounts: MutexGuard<'a, Accounts>,
+///     ledger: MutexGuard<'a, Ledger>,
+/// }
+/// Transfer { accounts: bank.accounts.lock().unwrap(), ledger: bank.ledger.lock().unwrap() }
+/// ```
+/// The fields are dropped in the order of declaration, so the locks are released in that order.
+#[derive(Clone, Debug)]
+pub struct GuardHolder {
+    pub def_id: DefId,
+    /// The lockguards moved into the fields, in the order of declaration.
+    pub lockguards: Vec<LockGuardId>,
+}
+
 /// Collect lockguard info.
 pub struct LockGuardCollector<'a, 'b, 'tcx> {
     instance_id: InstanceId,
@@ -221,7 +391,11 @@ pub struct LockGuardCollector<'a, 'b, 'tcx> {
     body: &'b Body<'tcx>,
     tcx: TyCtxt<'tcx>,
     param_env: ParamEnv<'tcx>,
+    guard_newtypes: &'a GuardNewtypes,
     pub lockguards: LockGuardMap<'tcx>,
+    pub holders: Vec<GuardHolder>,
+    /// (converted lockguard or enum of lockguards, the lockguard moved into the call converting it)
+    conversions: Vec<(Local, Local)>,
 }
 
 impl<'a, 'b, 'tcx> LockGuardCollector<'a, 'b, 'tcx> {
@@ -231,6 +405,7 @@ impl<'a, 'b, 'tcx> LockGuardCollector<'a, 'b, 'tcx> {
         body: &'b Body<'tcx>,
         tcx: TyCtxt<'tcx>,
         param_env: ParamEnv<'tcx>,
+        guard_newtypes: &'a GuardNewtypes,
     ) -> Self {
         Self {
             instance_id,
@@ -238,7 +413,10 @@ impl<'a, 'b, 'tcx> LockGuardCollector<'a, 'b, 'tcx> {
             body,
             tcx,
             param_env,
+            guard_newtypes,
             lockguards: Default::default(),
+            holders: Vec::new(),
+            conversions: Vec::new(),
         }
     }
 
@@ -249,19 +427,360 @@ impl<'a, 'b, 'tcx> LockGuardCollector<'a, 'b, 'tcx> {
                 self.param_env,
                 EarlyBinder::bind(local_decl.ty),
             );
-            if let Some(lockguard_ty) = LockGuardTy::from_local_ty(local_ty, self.tcx) {
+            if let Some(lockguard_ty) = self.guard_newtypes.lockguard_ty(local_ty, self.tcx) {
                 let lockguard_id = LockGuardId::new(self.instance_id, local);
                 let lockguard_info = LockGuardInfo::new(lockguard_ty, local_decl.source_info.span);
                 self.lockguards.insert(lockguard_id, lockguard_info);
             }
         }
         self.visit_body(self.body);
+        for (lockguard_id, info) in self.lockguards.iter_mut() {
+            if info.is_gen_only_by_move() {
+                continue;
+            }
+            info.owner_ty = lock_owner_ty(self.body, lockguard_id.local, self.tcx).map(|ty| {
+                self.instance.instantiate_mir_and_normalize_erasing_regions(
+                    self.tcx,
+                    self.param_env,
+                    EarlyBinder::bind(ty),
+                )
+            });
+            info.lock_name = lock_name(self.body, lockguard_id.local, self.tcx);
+        }
+        // The lockguards converted from others, e.g., by `RwLockUpgradableReadGuard::upgrade` or `MutexGuard::map`,
+        // acquire the locks of the others. The lockguards converted into enums,
+        // e.g., the `Result` of `MutexGuard::try_map`, are the ones moved out of their variants.
+        let conversions = std::mem::take(&mut self.conversions)
+            .into_iter()
+            .flat_map(|(converted, from)| {
+                if self
+                    .lockguards
+                    .contains_key(&LockGuardId::new(self.instance_id, converted))
+                {
+                    return vec![(converted, from)];
+                }
+                self.lockguards
+                    .keys()
+                    .filter(|lockguard_id| {
+                        matches!(
+                            assigned_place(self.body, lockguard_id.local),
+                            Some((place, false)) if place.local == converted
+                                && place
+                                    .projection
+                                    .iter()
+                                    .any(|elem| matches!(elem, ProjectionElem::Downcast(..)))
+                        )
+                    })
+                    .map(|lockguard_id| (lockguard_id.local, from))
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        for (converted, mut from) in conversions {
+            // The lockguard is usually moved into a temp before the call, e.g., `_9 = move _2`.
+            for _ in 0..MAX_OWNER_TRACE {
+                match assigned_place(self.body, from) {
+                    Some((place, false))
+                        if self
+                            .lockguards
+                            .contains_key(&LockGuardId::new(self.instance_id, place.local))
+                            && place.as_local().is_some() =>
+                    {
+                        from = place.local
+                    }
+                    _ => break,
+                }
+            }
+            let from = self.lockguards[&LockGuardId::new(self.instance_id, from)].clone();
+            let info = self
+                .lockguards
+                .get_mut(&LockGuardId::new(self.instance_id, converted))
+                .unwrap();
+            if info.lockguard_ty.pattern.lock() == from.lockguard_ty.pattern.lock() {
+                info.lockguard_ty = info.lockguard_ty.with_data_of(&from.lockguard_ty);
+            }
+            info.owner_ty = from.owner_ty;
+            info.lock_name = from.lock_name;
+        }
+    }
+
+    /// Whether the statement at `location` moves (or copies) a lockguard into another, e.g., `_2 = move _1`.
+    fn is_lockguard_transfer(&self, location: Location) -> bool {
+        let Some(stmt) = self.body[location.block]
+            .statements
+            .get(location.statement_index)
+        else {
+            return false;
+        };
+        match &stmt.kind {
+            StatementKind::Assign(box (
+                _,
+                Rvalue::Use(Operand::Move(src) | Operand::Copy(src)),
+            )) => src.as_local().map_or(false, |src| {
+                self.lockguards
+                    .contains_key(&LockGuardId::new(self.instance_id, src))
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// The maximal number of assignments traced back from a lockguard to its lock.
+const MAX_OWNER_TRACE: usize = 8;
+
+/// The struct type owning the lock as a field, traced back from the lockguard `local`
+/// through the calls returning it (by their first args), the borrows, and the moves, e.g., `Service` of
+/// `_3 = Result::unwrap(move _4); _4 = RwLock::read(move _5); _5 = &((*_1).0: RwLock<State>)`.
+fn lock_owner_ty<'tcx>(body: &Body<'tcx>, local: Local, tcx: TyCtxt<'tcx>) -> Option<ty::Ty<'tcx>> {
+    let mut local = local;
+    for _ in 0..MAX_OWNER_TRACE {
+        let (place, _) = assigned_place(body, local)?;
+        match place.iter_projections().last() {
+            Some((base, ProjectionElem::Field(..))) => {
+                let owner_ty = Place::ty_from(base.local, base.projection, body, tcx).ty;
+                return matches!(owner_ty.kind(), ty::Adt(adt_def, _) if adt_def.is_struct())
+                    .then_some(owner_ty);
+            }
+            _ if place
+                .projection
+                .iter()
+                .all(|elem| elem == ProjectionElem::Deref) =>
+            {
+                local = place.local;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// The source expression of the lock, traced back from the lockguard `local` like `lock_owner_ty`
+/// to the receiver of the lock call, e.g., `self.inner.state_mu` of
+/// `_3 = Result::unwrap(move _4); _4 = Mutex::lock(move _5); _5 = &((*_6).1: Mutex<i32>); _6 = &((*_1).0: Inner)`,
+/// where `_1` is `self`. The enums on the way (e.g., the `LockResult` of `lock()`) are not locks.
+fn lock_name<'tcx>(body: &Body<'tcx>, local: Local, tcx: TyCtxt<'tcx>) -> Option<String> {
+    let mut local = local;
+    let mut after_call = false;
+    for _ in 0..MAX_OWNER_TRACE {
+        let (place, by_call) = assigned_place(body, local)?;
+        after_call |= by_call;
+        let is_enum = matches!(place.ty(body, tcx).ty.peel_refs().kind(), ty::Adt(adt_def, _) if adt_def.is_enum());
+        if after_call && !is_enum {
+            if let Some(name) = place_name(body, place.as_ref(), tcx, MAX_OWNER_TRACE) {
+                return Some(name);
+            }
+        }
+        if place
+            .projection
+            .iter()
+            .any(|elem| elem != ProjectionElem::Deref)
+        {
+            return None;
+        }
+        local = place.local;
+    }
+    None
+}
+
+/// The source expression of the place rooted at a user variable (or upvar) or a static,
+/// with the derefs (`*` and `Deref::deref`) elided as autoderef does, e.g.,
+/// `self.inner.state_mu` of `((*_6).1: Mutex<i32>)`, where `_6 = &((*_1).0: Inner)` and `_1` is `self`.
+fn place_name<'tcx>(
+    body: &Body<'tcx>,
+    place: PlaceRef<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    depth: usize,
+) -> Option<String> {
+    if depth == 0 {
+        return None;
+    }
+    let name = body
+        .var_debug_info
+        .iter()
+        .find_map(|info| match info.value {
+            VarDebugInfoContents::Place(var) if var.as_ref() == place => {
+                Some(info.name.to_string())
+            }
+            _ => None,
+        });
+    if name.is_some() {
+        return name;
+    }
+    match place.last_projection() {
+        Some((base, ProjectionElem::Deref)) => place_name(body, base, tcx, depth - 1),
+        Some((base, ProjectionElem::Field(field, _))) => {
+            let field_name = match base.ty(body, tcx).ty.kind() {
+                ty::Adt(adt_def, _) if adt_def.is_struct() => {
+                    adt_def.non_enum_variant().fields[field].name.to_string()
+                }
+                ty::Tuple(_) => field.index().to_string(),
+                _ => return None,
+            };
+            let base_name = place_name(body, base, tcx, depth - 1)?;
+            Some(format!("{}.{}", base_name, field_name))
+        }
+        Some(_) => None,
+        // A temp borrowing (or moved from) another place, or a static.
+        None => {
+            for data in body.basic_blocks.iter() {
+                for stmt in &data.statements {
+                    match &stmt.kind {
+                        StatementKind::Assign(box (lhs, rvalue))
+                            if lhs.as_local() == Some(place.local) =>
+                        {
+                            return match rvalue {
+                                Rvalue::Ref(_, _, source)
+                                | Rvalue::AddressOf(_, source)
+                                | Rvalue::CopyForDeref(source)
+                                | Rvalue::Use(Operand::Move(source) | Operand::Copy(source)) => {
+                                    place_name(body, source.as_ref(), tcx, depth - 1)
+                                }
+                                Rvalue::Use(Operand::Constant(constant)) => constant
+                                    .check_static_ptr(tcx)
+                                    .map(|def_id| tcx.def_path_str(def_id)),
+                                _ => None,
+                            };
+                        }
+                        _ => {}
+                    }
+                }
+                if let TerminatorKind::Call {
+                    func,
+                    args,
+                    destination,
+                    ..
+                } = &data.terminator().kind
+                {
+                    if destination.as_local() == Some(place.local) {
+                        let source = match (func.ty(body, tcx).kind(), args.first()) {
+                            (
+                                ty::FnDef(def_id, _),
+                                Some(Operand::Move(source) | Operand::Copy(source)),
+                            ) if is_deref(*def_id, tcx) => *source,
+                            _ => return None,
+                        };
+                        return place_name(body, source.as_ref(), tcx, depth - 1);
+                    }
+                }
+            }
+            None
+        }
     }
 }
 
+/// Whether the fn is `Deref::deref` or `DerefMut::deref_mut`.
+fn is_deref(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    let lang_items = tcx.lang_items();
+    tcx.trait_of_item(def_id).map_or(false, |trait_def_id| {
+        Some(trait_def_id) == lang_items.deref_trait()
+            || Some(trait_def_id) == lang_items.deref_mut_trait()
+    })
+}
+
+/// The place assigned to `local` by a borrow or a move (or copy),
+/// or passed as the first arg to the call returning `local`, and whether it is by the call.
+fn assigned_place<'tcx>(body: &Body<'tcx>, local: Local) -> Option<(Place<'tcx>, bool)> {
+    body.basic_blocks.iter().find_map(|data| {
+        let assigned = data.statements.iter().find_map(|stmt| match &stmt.kind {
+            StatementKind::Assign(box (lhs, rvalue)) if lhs.as_local() == Some(local) => {
+                match rvalue {
+                    Rvalue::Ref(_, _, place)
+                    | Rvalue::AddressOf(_, place)
+                    | Rvalue::CopyForDeref(place)
+                    | Rvalue::Use(Operand::Move(place) | Operand::Copy(place)) => {
+                        Some((*place, false))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        });
+        assigned.or_else(|| match &data.terminator().kind {
+            TerminatorKind::Call {
+                destination, args, ..
+            } if destination.as_local() == Some(local) => match args.first()? {
+                Operand::Move(place) | Operand::Copy(place) => Some((*place, true)),
+                Operand::Constant(_) => None,
+            },
+            _ => None,
+        })
+    })
+}
+
 impl<'a, 'b, 'tcx> Visitor<'tcx> for LockGuardCollector<'a, 'b, 'tcx> {
+    /// A struct aggregate with more than one lockguard moved into its fields is a guard holder.
+    fn visit_rvalue(&mut self, rvalue: &Rvalue<'tcx>, location: Location) {
+        if let Rvalue::Aggregate(box AggregateKind::Adt(def_id, _, _, _, None), operands) = rvalue {
+            let lockguards = operands
+                .iter()
+                .filter_map(|operand| match operand {
+                    Operand::Move(place) => place.as_local(),
+                    _ => None,
+                })
+                .map(|local| LockGuardId::new(self.instance_id, local))
+                .filter(|lockguard_id| self.lockguards.contains_key(lockguard_id))
+                .collect::<Vec<_>>();
+            if lockguards.len() > 1 && self.tcx.adt_def(*def_id).is_struct() {
+                self.holders.push(GuardHolder {
+                    def_id: *def_id,
+                    lockguards,
+                });
+            }
+        }
+        self.super_rvalue(rvalue, location);
+    }
+
+    /// `drop_in_place(&mut lockguard)` or `ManuallyDrop::drop(&mut lockguard)`
+    /// unlocks the lockguard early, thus also a kill location.
+    /// A call moving a lockguard as the first arg and returning a lockguard (or an enum of lockguards) converts it,
+    /// e.g., `RwLockUpgradableReadGuard::upgrade(move _2)` kills `_2` and gens a write lockguard of the same lock,
+    /// `MutexGuard::map(move _2, move _3)` kills `_2` and gens a mapped lockguard of the same lock and data,
+    /// and `RwLockWriteGuard::downgrade(move _2)` kills the write lockguard `_2` and gens a read lockguard,
+    /// so that the relations formed after the downgrade have the read semantics.
+    fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
+        if let TerminatorKind::Call {
+            ref func,
+            ref args,
+            ref destination,
+            ..
+        } = terminator.kind
+        {
+            if let (Some(converted), Some(Operand::Move(from))) =
+                (destination.as_local(), args.first())
+            {
+                if let Some(from) = from.as_local() {
+                    let is_lockguard = |local| {
+                        self.lockguards
+                            .contains_key(&LockGuardId::new(self.instance_id, local))
+                    };
+                    if is_lockguard(from) {
+                        self.conversions.push((converted, from));
+                    }
+                }
+            }
+            if let (ty::FnDef(def_id, _), [Operand::Move(arg) | Operand::Copy(arg)]) =
+                (func.ty(self.body, self.tcx).kind(), args.as_slice())
+            {
+                if ownership::is_explicit_drop(*def_id, self.tcx) {
+                    if let Some(pointee) = arg
+                        .as_local()
+                        .and_then(|ptr| ownership::pointee_place(self.body, ptr, location))
+                        .and_then(|pointee| pointee.as_local())
+                    {
+                        let lockguard_id = LockGuardId::new(self.instance_id, pointee);
+                        if let Some(info) = self.lockguards.get_mut(&lockguard_id) {
+                            info.kill_locs.push(location);
+                        }
+                    }
+                }
+            }
+        }
+        self.super_terminator(terminator, location);
+    }
+
     fn visit_local(&mut self, local: Local, context: PlaceContext, location: Location) {
         let lockguard_id = LockGuardId::new(self.instance_id, local);
+        let is_transfer = context == PlaceContext::MutatingUse(MutatingUseContext::Store)
+            && self.is_lockguard_transfer(location);
         // local is lockguard
         if let Some(info) = self.lockguards.get_mut(&lockguard_id) {
             match context {
@@ -269,14 +788,20 @@ impl<'a, 'b, 'tcx> Visitor<'tcx> for LockGuardCollector<'a, 'b, 'tcx> {
                     info.kill_locs.push(location);
                 }
                 PlaceContext::MutatingUse(context) => match context {
+                    // The temporary lockguards, e.g., of `*m.lock().unwrap() += 1;`, are dropped at the end of
+                    // their statements (or the enclosing statements of the tail expressions) rather than the blocks,
+                    // while those of the `match` scrutinees are dropped after the arms.
                     MutatingUseContext::Drop => info.kill_locs.push(location),
                     MutatingUseContext::Store => {
                         info.gen_locs.push(location);
                         info.move_gen_locs.push(location);
+                        if is_transfer {
+                            info.transfer_gen_locs.push(location);
+                        }
                     }
                     MutatingUseContext::Call => {
                         // if lockguard = parking_lot::recursive_read() then record to recursive_gen_locs
-                        if let LockGuardTy::ParkingLotRead(_) = info.lockguard_ty {
+                        if info.lockguard_ty.access() == LockAccess::Read {
                             let term = self.body[location.block].terminator();
                             if let TerminatorKind::Call { ref func, .. } = term.kind {
                                 let func_ty = func.ty(self.body, self.tcx);
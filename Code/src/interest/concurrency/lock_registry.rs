@@ -0,0 +1,335 @@
+//! The registry of lockguard patterns.
+//! Each pattern declares a lockguard ADT by its crate and name, which type arg is the protected data,
+//! and how the guard accesses the lock, e.g., `lock_api::RwLockReadGuard<'a, R, T>` reads the `ParkingLotRwLock` protecting `T`.
+//! The built-in patterns model std, parking_lot (lock_api), spin, tokio, crossbeam `ShardedLock`, and dashmap `DashMap`,
+//! as well as the `RefCell` borrows, whose conflicts panic rather than block, see `LockGuardTy::is_borrow`.
+//! More patterns can be declared by `locks` in the config file, e.g.,
+//! ```json
+//! {
+//!     "locks": [
+//!         { "name": "MyMutex", "crate": "my_lock", "guard": "MutexGuard", "access": "mutex" }
+//!     ]
+//! }
+//! ```
+//! User patterns take precedence over the built-in ones.
+//! A guard named like a std guard (e.g., `MutexGuard`) in other crates is treated as a std guard (e.g., a wrapper),
+//! unless it comes from async crates. The loom guards are the std ones, since they block the same way.
+extern crate rustc_hir;
+
+use std::sync::{Arc, OnceLock};
+
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::{self, TyCtxt};
+use serde::Deserialize;
+
+use super::lock::LockGuardTy;
+
+/// How a lockguard accesses its lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockAccess {
+    Mutex,
+    Read,
+    Write,
+    /// Coexists with reads, but excludes writes and other upgradable reads.
+    Upgradable,
+}
+
+/// A lockguard pattern.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GuardPattern {
+    /// The name of the lockguard family in reports, e.g., `ParkingLotRead`.
+    pub name: String,
+    /// The lock acquired by the guard. Only the guards of the same lock may deadlock.
+    /// Defaults to `name`, e.g., `ParkingLotRead` and `ParkingLotWrite` share the lock `ParkingLotRwLock`.
+    #[serde(default)]
+    pub lock: Option<String>,
+    /// The crate defining the guard ADT, or any crate if not given.
+    #[serde(rename = "crate", default)]
+    pub krate: Option<String>,
+    /// The substring of the guard ADT name, e.g., `MutexGuard` also matches `MappedMutexGuard`.
+    pub guard: String,
+    /// The guard ADT name equals `guard` rather than contains it, e.g., `Ref` matches neither `RefCell` nor `RefMut`.
+    #[serde(default)]
+    pub exact: bool,
+    /// The index of the type arg for the protected data.
+    #[serde(default)]
+    pub data_arg: usize,
+    pub access: LockAccess,
+    /// The lock can be acquired recursively by the same thread, e.g., `ReentrantMutex`.
+    #[serde(default)]
+    pub reentrant: bool,
+    /// The read locks can be acquired recursively, i.e., the lock has no write priority.
+    #[serde(default)]
+    pub recursive_read: bool,
+    /// The guard of an async lock, which is meant to be held across await points.
+    #[serde(rename = "async", default)]
+    pub asynchronous: bool,
+    /// The guard locks one of the shards of the lock picked by a key, e.g., the `Ref` of `DashMap::get`,
+    /// so the guards of the same lock only possibly lock the same shard.
+    #[serde(default)]
+    pub sharded: bool,
+}
+
+impl GuardPattern {
+    fn builtin(
+        name: &str,
+        lock: &str,
+        krate: Option<&str>,
+        guard: &str,
+        data_arg: usize,
+        access: LockAccess,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            lock: Some(lock.to_owned()),
+            krate: krate.map(str::to_owned),
+            guard: guard.to_owned(),
+            exact: false,
+            data_arg,
+            access,
+            reentrant: false,
+            recursive_read: false,
+            asynchronous: false,
+            sharded: false,
+        }
+    }
+
+    pub fn lock(&self) -> &str {
+        self.lock.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// The built-in patterns, from the more specific ones.
+/// In parking_lot, the read lock is by default non-recursive if not specified.
+/// if two recursively acquired read locks in one thread are interleaved
+/// by a write lock from another thread, a deadlock may happen.
+/// The reason is write lock has higher priority than read lock in parking_lot.
+/// In std::sync, the implementation of read lock depends on the underlying OS.
+/// AFAIK, the implementation on Windows and Mac have write priority.
+/// So read lock in std::sync cannot be acquired recursively on the two systems.
+/// spin explicitly documents no write priority. So the read lock in spin can
+/// be acquired recursively.
+/// The upgradable read lock in parking_lot excludes the other upgradable reads and the writes,
+/// and `RwLockUpgradableReadGuard::upgrade` consumes it for a write lock.
+/// The reentrant mutexes (parking_lot `ReentrantMutex` and std `ReentrantLock`, of which
+/// the internal `ReentrantMutex` guards the stdio) can be acquired again by the thread holding them,
+/// so their guards precede the `MutexGuard`s they contain in the names.
+/// The `Ref` and `RefMut` of a `RefCell` are modeled as the read and write guards of a lock with recursive reads,
+/// matched by the exact names since `RefCell` itself contains `Ref`.
+/// The `Ref` (of `get`), `RefMut` (of `get_mut`), and `Entry` (of `entry`) of a `DashMap` are the guards of the shard
+/// of the key, modeled as the read and write guards of the map ignoring the keys, whose reads are recursive.
+#[rustfmt::skip]
+fn builtin_patterns() -> Vec<GuardPattern> {
+    use LockAccess::*;
+    let mut patterns = vec![
+        GuardPattern::builtin("StdReentrantLock", "StdReentrantLock", Some("std"), "ReentrantLockGuard", 0, Mutex),
+        GuardPattern::builtin("StdReentrantLock", "StdReentrantLock", Some("std"), "ReentrantMutexGuard", 0, Mutex),
+        GuardPattern::builtin("StdMutex", "StdMutex", Some("std"), "MutexGuard", 0, Mutex),
+        GuardPattern::builtin("StdRwLockRead", "StdRwLock", Some("std"), "RwLockReadGuard", 0, Read),
+        GuardPattern::builtin("StdRwLockWrite", "StdRwLock", Some("std"), "RwLockWriteGuard", 0, Write),
+        // The loom guards for model checking, e.g., `loom::sync::MutexGuard` under `cfg(loom)`.
+        GuardPattern::builtin("StdMutex", "StdMutex", Some("loom"), "MutexGuard", 0, Mutex),
+        GuardPattern::builtin("StdRwLockRead", "StdRwLock", Some("loom"), "RwLockReadGuard", 0, Read),
+        GuardPattern::builtin("StdRwLockWrite", "StdRwLock", Some("loom"), "RwLockWriteGuard", 0, Write),
+        GuardPattern::builtin("ParkingLotReentrantMutex", "ParkingLotReentrantMutex", Some("lock_api"), "ReentrantMutexGuard", 2, Mutex),
+        GuardPattern::builtin("ParkingLotMutex", "ParkingLotMutex", Some("lock_api"), "MutexGuard", 1, Mutex),
+        GuardPattern::builtin("ParkingLotRead", "ParkingLotRwLock", Some("lock_api"), "RwLockReadGuard", 1, Read),
+        GuardPattern::builtin("ParkingLotWrite", "ParkingLotRwLock", Some("lock_api"), "RwLockWriteGuard", 1, Write),
+        GuardPattern::builtin("ParkingLotUpgradableRead", "ParkingLotRwLock", Some("lock_api"), "RwLockUpgradableReadGuard", 1, Upgradable),
+        GuardPattern::builtin("SpinMutex", "SpinMutex", Some("spin"), "MutexGuard", 0, Mutex),
+        GuardPattern::builtin("SpinRead", "SpinRwLock", Some("spin"), "RwLockReadGuard", 0, Read),
+        GuardPattern::builtin("SpinWrite", "SpinRwLock", Some("spin"), "RwLockWriteGuard", 0, Write),
+        GuardPattern::builtin("TokioMutex", "TokioMutex", Some("tokio"), "MutexGuard", 0, Mutex),
+        GuardPattern::builtin("TokioRead", "TokioRwLock", Some("tokio"), "RwLockReadGuard", 0, Read),
+        GuardPattern::builtin("TokioWrite", "TokioRwLock", Some("tokio"), "RwLockWriteGuard", 0, Write),
+        GuardPattern::builtin("ShardedLockRead", "ShardedLock", Some("crossbeam_utils"), "ShardedLockReadGuard", 0, Read),
+        GuardPattern::builtin("ShardedLockWrite", "ShardedLock", Some("crossbeam_utils"), "ShardedLockWriteGuard", 0, Write),
+        GuardPattern::builtin("DashMapRef", "DashMap", Some("dashmap"), "Ref", 1, Read),
+        GuardPattern::builtin("DashMapRefMut", "DashMap", Some("dashmap"), "RefMut", 1, Write),
+        GuardPattern::builtin("DashMapEntry", "DashMap", Some("dashmap"), "Entry", 1, Write),
+        GuardPattern::builtin("RefCellRef", "RefCell", Some("core"), "Ref", 0, Read),
+        GuardPattern::builtin("RefCellRefMut", "RefCell", Some("core"), "RefMut", 0, Write),
+        // The wrappers of std guards in other crates.
+        GuardPattern::builtin("StdMutex", "StdMutex", None, "MutexGuard", 0, Mutex),
+        GuardPattern::builtin("StdRwLockRead", "StdRwLock", None, "RwLockReadGuard", 0, Read),
+        GuardPattern::builtin("StdRwLockWrite", "StdRwLock", None, "RwLockWriteGuard", 0, Write),
+    ];
+    for pattern in patterns.iter_mut() {
+        match pattern.lock() {
+            "SpinRwLock" => pattern.recursive_read = true,
+            "StdReentrantLock" | "ParkingLotReentrantMutex" => pattern.reentrant = true,
+            "TokioMutex" | "TokioRwLock" => pattern.asynchronous = true,
+            "RefCell" => {
+                pattern.recursive_read = true;
+                pattern.exact = true;
+            }
+            "DashMap" => {
+                pattern.recursive_read = true;
+                pattern.sharded = true;
+                // `Entry` also matches `OccupiedEntry` and `VacantEntry`.
+                pattern.exact = pattern.guard != "Entry";
+            }
+            _ => {}
+        }
+    }
+    patterns
+}
+
+/// The guards in the paths containing these are not treated as std guards.
+const UNSUPPORTED_WRAPPER_PATHS: [&str; 3] = ["async", "tokio", "future"];
+
+/// The registry of lockguard patterns.
+#[derive(Clone, Debug)]
+pub struct LockRegistry {
+    patterns: Vec<Arc<GuardPattern>>,
+}
+
+impl Default for LockRegistry {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl LockRegistry {
+    /// The registry of the user patterns followed by the built-in ones.
+    pub fn new(user_patterns: Vec<GuardPattern>) -> Self {
+        Self {
+            patterns: user_patterns
+                .into_iter()
+                .chain(builtin_patterns())
+                .map(Arc::new)
+                .collect(),
+        }
+    }
+
+    /// The registry of the built-in patterns only.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<LockRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(Self::default)
+    }
+
+    /// The pattern named `name`.
+    pub fn family(&self, name: &str) -> Option<&Arc<GuardPattern>> {
+        self.patterns.iter().find(|pattern| pattern.name == name)
+    }
+
+    fn matches(&self, did: DefId, tcx: TyCtxt<'_>) -> Option<&Arc<GuardPattern>> {
+        let name = tcx.item_name(did);
+        let name = name.as_str();
+        let matches_name = |pattern: &GuardPattern| {
+            if pattern.exact {
+                name == pattern.guard
+            } else {
+                name.contains(&pattern.guard)
+            }
+        };
+        // quick fail
+        if !self.patterns.iter().any(|pattern| matches_name(pattern)) {
+            return None;
+        }
+        let krate = tcx.crate_name(did.krate);
+        let krate = krate.as_str();
+        let path = tcx.def_path_str(did);
+        self.patterns.iter().find(|pattern| {
+            matches_name(pattern)
+                && match &pattern.krate {
+                    Some(pattern_krate) => pattern_krate == krate,
+                    None => !UNSUPPORTED_WRAPPER_PATHS
+                        .iter()
+                        .any(|unsupported| path.contains(unsupported)),
+                }
+        })
+    }
+
+    pub fn lockguard_ty<'tcx>(
+        &self,
+        local_ty: ty::Ty<'tcx>,
+        tcx: TyCtxt<'tcx>,
+    ) -> Option<LockGuardTy<'tcx>> {
+        if let ty::TyKind::Adt(adt_def, substs) = local_ty.kind() {
+            // ManuallyDrop<MutexGuard<i32>> is held until `ManuallyDrop::drop` or `into_inner`
+            if adt_def.is_manually_drop() {
+                return self.lockguard_ty(substs.type_at(0), tcx);
+            }
+            let pattern = self.matches(adt_def.did(), tcx)?;
+            let data_ty = substs.types().nth(pattern.data_arg)?;
+            Some(LockGuardTy::new(pattern.clone(), data_ty))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_patterns() {
+        let registry = LockRegistry::builtin();
+        let read = registry.family("ParkingLotRead").unwrap();
+        let write = registry.family("ParkingLotWrite").unwrap();
+        assert_eq!(read.lock(), write.lock());
+        assert_eq!((read.data_arg, read.access), (1, LockAccess::Read));
+        assert!(!read.recursive_read);
+        let upgradable = registry.family("ParkingLotUpgradableRead").unwrap();
+        assert_eq!(upgradable.lock(), read.lock());
+        assert_eq!(upgradable.access, LockAccess::Upgradable);
+        assert!(registry.family("SpinRead").unwrap().recursive_read);
+        let reentrant = registry.family("ParkingLotReentrantMutex").unwrap();
+        assert!(reentrant.reentrant);
+        assert_ne!(
+            reentrant.lock(),
+            registry.family("ParkingLotMutex").unwrap().lock()
+        );
+        // The reentrant guards precede the mutex guards contained in their names.
+        let position = |guard: &str| {
+            registry
+                .patterns
+                .iter()
+                .position(|pattern| pattern.guard == guard)
+                .unwrap()
+        };
+        assert!(position("ReentrantMutexGuard") < position("MutexGuard"));
+        assert!(registry.family("StdReentrantLock").unwrap().reentrant);
+        assert!(registry.family("TokioMutex").unwrap().asynchronous);
+        let borrow = registry.family("RefCellRef").unwrap();
+        let borrow_mut = registry.family("RefCellRefMut").unwrap();
+        assert_eq!(borrow.lock(), borrow_mut.lock());
+        assert!(borrow.exact && borrow.recursive_read);
+        assert_eq!(borrow_mut.access, LockAccess::Write);
+        let dashmap_ref = registry.family("DashMapRef").unwrap();
+        assert!(dashmap_ref.sharded && dashmap_ref.exact);
+        assert_eq!(dashmap_ref.data_arg, 1);
+        assert!(!registry.family("DashMapEntry").unwrap().exact);
+        assert_eq!(
+            registry.family("ShardedLockWrite").unwrap().lock(),
+            registry.family("ShardedLockRead").unwrap().lock()
+        );
+        // The std patterns precede their wrappers.
+        assert_eq!(
+            registry.family("StdMutex").unwrap().krate.as_deref(),
+            Some("std")
+        );
+        assert!(registry.family("Unknown").is_none());
+    }
+
+    #[test]
+    fn test_user_patterns_first() {
+        let pattern: GuardPattern = serde_json::from_str(
+            r#"{"name": "MyMutex", "crate": "my_lock", "guard": "MutexGuard", "access": "mutex", "reentrant": true}"#,
+        )
+        .unwrap();
+        assert_eq!(pattern.lock(), "MyMutex");
+        let registry = LockRegistry::new(vec![pattern]);
+        assert_eq!(registry.patterns[0].name, "MyMutex");
+        assert!(registry.patterns[0].reentrant);
+        assert!(registry.family("StdMutex").is_some());
+        assert!(serde_json::from_str::<GuardPattern>(
+            r#"{"name": "MyMutex", "guard": "MutexGuard", "access": "exclusive"}"#
+        )
+        .is_err());
+    }
+}
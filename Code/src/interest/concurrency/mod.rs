@@ -1,3 +1,10 @@
 pub mod atomic;
 pub mod condvar;
+pub mod executor;
 pub mod lock;
+pub mod lock_registry;
+pub mod once;
+pub mod pool;
+pub mod rayon;
+pub mod rendezvous;
+pub mod thread;
@@ -0,0 +1,71 @@
+//! Denotes the APIs running an initializer at most once, blocking the other callers until it completes.
+//!
+//! 1. std::sync::Once::call_once(&Once, F), std::sync::Once::call_once_force(&Once, F)
+//! 2. std::sync::OnceLock::get_or_init(&OnceLock, F)
+//! 3. `lazy_static!`, which expands to `Lazy::get(&'static Lazy, __static_ref_initialize)` calling `Once::call_once`,
+//!    recognized by the generated initializer `__static_ref_initialize` rather than the path of `Lazy`,
+//!    which varies with the features of `lazy_static`.
+//!
+//! The initializers are the closures (or fn items) in the generic args of the API's instance.
+//! An initializer reaching the same API on the same receiver blocks forever on the initialization in progress.
+use rustc_middle::ty::{self, Instance, ParamEnv, TyCtxt};
+
+use super::thread::api_path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnceApi {
+    CallOnce,
+    GetOrInit,
+    LazyStatic,
+}
+
+impl OnceApi {
+    pub fn from_instance<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
+        match api_path(instance, tcx).as_str() {
+            "std::sync::Once::call_once" | "std::sync::Once::call_once_force" => {
+                Some(OnceApi::CallOnce)
+            }
+            "std::sync::OnceLock::get_or_init" => Some(OnceApi::GetOrInit),
+            // Calling the initializer, e.g., `FnOnce::call_once(__static_ref_initialize, ())`, is not the API.
+            _ if !is_fn_trait_method(instance, tcx)
+                && instance.args.types().any(|ty| match ty.kind() {
+                    ty::FnDef(def_id, _) => {
+                        tcx.item_name(*def_id).as_str() == "__static_ref_initialize"
+                    }
+                    _ => false,
+                }) =>
+            {
+                Some(OnceApi::LazyStatic)
+            }
+            _ => None,
+        }
+    }
+
+    /// Resolve the initializers from the generic args of the API's instance.
+    pub fn initializer_instances<'tcx>(
+        instance: &Instance<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        param_env: ParamEnv<'tcx>,
+    ) -> Vec<Instance<'tcx>> {
+        instance
+            .args
+            .types()
+            .filter_map(|ty| match ty.kind() {
+                ty::Closure(def_id, args) | ty::FnDef(def_id, args) => {
+                    Instance::resolve(tcx, param_env, *def_id, args)
+                        .ok()
+                        .flatten()
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Whether the instance is a method of `Fn`, `FnMut`, or `FnOnce`.
+fn is_fn_trait_method<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    tcx.trait_of_item(instance.def_id())
+        .map_or(false, |trait_def_id| {
+            tcx.fn_trait_kind_from_def_id(trait_def_id).is_some()
+        })
+}
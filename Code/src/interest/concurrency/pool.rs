@@ -0,0 +1,46 @@
+//! Denotes the APIs of the custom thread pools declared in the config.
+//! e.g., for a pool declared with
+//! `"install": ["my_pool::ThreadPool::install"], "wait": ["my_pool::ScopeHandle::wait"]`,
+//! `my_pool::ThreadPool::install(&pool, F)` runs closure F on the pool and is PoolApi::Install,
+//! `my_pool::ScopeHandle::wait(&handle)` blocks on the work scheduled into the pool and is PoolApi::Wait.
+extern crate rustc_hir;
+
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_middle::ty::{Instance, TyCtxt};
+
+use super::thread::api_path;
+use crate::config::PoolConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolApi {
+    /// Index of the pool in the config
+    Install(usize),
+    Wait(usize),
+}
+
+impl PoolApi {
+    pub fn from_instance<'tcx>(
+        instance: &Instance<'tcx>,
+        pools: &[PoolConfig],
+        tcx: TyCtxt<'tcx>,
+    ) -> Option<Self> {
+        if pools.is_empty() {
+            return None;
+        }
+        let path = api_path(instance, tcx);
+        let matches = |declared: &String| {
+            *declared == path
+                || (instance.def_id().is_local()
+                    && *declared == format!("{}::{}", tcx.crate_name(LOCAL_CRATE), path))
+        };
+        pools.iter().enumerate().find_map(|(idx, pool)| {
+            if pool.install.iter().any(matches) {
+                Some(PoolApi::Install(idx))
+            } else if pool.wait.iter().any(matches) {
+                Some(PoolApi::Wait(idx))
+            } else {
+                None
+            }
+        })
+    }
+}
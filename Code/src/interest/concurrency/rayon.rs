@@ -0,0 +1,72 @@
+//! Denotes the rayon APIs blocking the caller on the closures run on the pool.
+//!
+//! 1. rayon::iter::ParallelIterator::for_each(self, F), for_each_with, for_each_init, try_for_each, etc.
+//! 2. rayon::join(A, B), rayon::join_context(A, B), rayon::ThreadPool::join(&ThreadPool, A, B)
+//! 3. rayon::scope(F), rayon::scope_fifo(F), rayon::ThreadPool::scope(&ThreadPool, F),
+//!    rayon::ThreadPool::install(&ThreadPool, F)
+//!
+//! The closures are generic args of the API's instance,
+//! including those of the adaptors in the parallel iterator, e.g., the closure of `map` in `par_iter().map(..).for_each(..)`.
+//! Unlike the spawn APIs, the caller waits for the closures to complete,
+//! so a closure acquiring a lock held by the caller deadlocks.
+//! `rayon::in_place_scope` is not one of them: its closure runs on the current thread.
+use rustc_middle::ty::{self, Instance, ParamEnv, Ty, TyCtxt};
+
+use super::thread::api_path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RayonApi {
+    ParIter,
+    Join,
+    Scope,
+}
+
+impl RayonApi {
+    pub fn from_instance<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
+        let path = api_path(instance, tcx);
+        match path.as_str() {
+            "rayon::iter::ParallelIterator::for_each"
+            | "rayon::iter::ParallelIterator::for_each_with"
+            | "rayon::iter::ParallelIterator::for_each_init"
+            | "rayon::iter::ParallelIterator::try_for_each"
+            | "rayon::iter::ParallelIterator::try_for_each_with"
+            | "rayon::iter::ParallelIterator::try_for_each_init" => Some(RayonApi::ParIter),
+            "rayon::join"
+            | "rayon::join_context"
+            | "rayon::ThreadPool::join"
+            | "rayon_core::join"
+            | "rayon_core::join_context"
+            | "rayon_core::ThreadPool::join" => Some(RayonApi::Join),
+            "rayon::scope"
+            | "rayon::scope_fifo"
+            | "rayon::ThreadPool::scope"
+            | "rayon::ThreadPool::scope_fifo"
+            | "rayon::ThreadPool::install"
+            | "rayon_core::scope"
+            | "rayon_core::scope_fifo"
+            | "rayon_core::ThreadPool::scope"
+            | "rayon_core::ThreadPool::scope_fifo"
+            | "rayon_core::ThreadPool::install" => Some(RayonApi::Scope),
+            _ => None,
+        }
+    }
+
+    /// Resolve the closures run on the pool from the generic args of the API's instance.
+    pub fn closure_instances<'tcx>(
+        instance: &Instance<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        param_env: ParamEnv<'tcx>,
+    ) -> Vec<Instance<'tcx>> {
+        instance
+            .args
+            .types()
+            .flat_map(Ty::walk)
+            .filter_map(|arg| match arg.as_type()?.kind() {
+                ty::Closure(def_id, args) => Instance::resolve(tcx, param_env, *def_id, args)
+                    .ok()
+                    .flatten(),
+                _ => None,
+            })
+            .collect()
+    }
+}
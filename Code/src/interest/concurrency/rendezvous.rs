@@ -0,0 +1,47 @@
+//! Denotes the APIs blocking the current thread until a peer thread reaches the matching API.
+//!
+//! 1. std::sync::Barrier::wait(&Barrier), matched by the same API in the peers
+//! 2. std::sync::mpsc::SyncSender::send(&SyncSender, T), matched by std::sync::mpsc::Receiver::recv(&Receiver)
+//!
+//! `SyncSender::send` only waits for the receiver on a zero-capacity channel created by `std::sync::mpsc::sync_channel(0)`,
+//! see `is_sync_channel`.
+use rustc_middle::ty::{Instance, TyCtxt};
+
+use super::thread::api_path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RendezvousApi {
+    BarrierWait,
+    SyncSend,
+    Recv,
+}
+
+impl RendezvousApi {
+    pub fn from_instance<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
+        match api_path(instance, tcx).as_str() {
+            "std::sync::Barrier::wait" => Some(RendezvousApi::BarrierWait),
+            "std::sync::mpsc::SyncSender::send" => Some(RendezvousApi::SyncSend),
+            "std::sync::mpsc::Receiver::recv" => Some(RendezvousApi::Recv),
+            _ => None,
+        }
+    }
+
+    /// Whether the API blocks the caller until a peer reaches the matching API.
+    pub fn is_blocking(&self) -> bool {
+        !matches!(self, RendezvousApi::Recv)
+    }
+
+    /// The API the peers call to release the caller of `self`.
+    pub fn peer(&self) -> Self {
+        match self {
+            RendezvousApi::BarrierWait => RendezvousApi::BarrierWait,
+            RendezvousApi::SyncSend => RendezvousApi::Recv,
+            RendezvousApi::Recv => RendezvousApi::SyncSend,
+        }
+    }
+}
+
+/// Whether the instance is `std::sync::mpsc::sync_channel`, whose first arg is the capacity.
+pub fn is_sync_channel<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    api_path(instance, tcx) == "std::sync::mpsc::sync_channel"
+}
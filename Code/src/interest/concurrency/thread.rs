@@ -0,0 +1,141 @@
+//! Denotes thread spawn APIs in std, rayon, and tokio.
+//!
+//! 1. std::thread::spawn(F), std::thread::Builder::spawn(self, F), std::thread::Builder::spawn_scoped(self, &Scope, F),
+//!    std::thread::Scope::spawn(&Scope, F),
+//!    and their loom counterparts, e.g., loom::thread::spawn(F)
+//! 2. rayon::spawn(F), rayon::spawn_fifo(F), rayon::Scope::spawn(&Scope, F)
+//! 3. tokio::spawn(Future), tokio::task::spawn_blocking(F), tokio::runtime::Runtime::spawn(&Runtime, Future)
+//! 4. crossbeam::thread::Scope::spawn(&Scope, F), crossbeam::thread::ScopedThreadBuilder::spawn(self, F)
+//!
+//! The spawned closure (or future, or fn item, e.g., `thread::spawn(worker)`) is a generic arg of the API's instance.
+//!
+//! The local spawn APIs in tokio run the future on the current thread, so it need not be Send, see `is_local_spawn_api`:
+//! tokio::task::spawn_local(Future), tokio::task::LocalSet::spawn_local(&LocalSet, Future),
+//! tokio::task::LocalSet::run_until(&LocalSet, Future), tokio::task::LocalSet::block_on(&LocalSet, &Runtime, Future)
+//!
+//! `std::panic::catch_unwind(F)` is not a spawn API: its closure is executed synchronously in the caller.
+use rustc_middle::ty::{self, Instance, ParamEnv, Ty, TyCtxt};
+
+/// The path of the API without generic args.
+/// Drop the generics of the impl self type, e.g., `std::thread::Scope::<'scope, 'env>::spawn`.
+/// The methods of an inherent impl in another module than the self type, e.g.,
+/// `std::thread::scoped::<impl std::thread::Builder>::spawn_scoped`, are named after the self type.
+pub fn api_path<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> String {
+    let def_id = instance.def_id();
+    let self_ty = tcx
+        .impl_of_method(def_id)
+        .filter(|impl_def_id| tcx.trait_id_of_impl(*impl_def_id).is_none())
+        .and_then(|impl_def_id| tcx.type_of(impl_def_id).skip_binder().ty_adt_def());
+    let path = match self_ty {
+        Some(adt_def) => format!(
+            "{}::{}",
+            tcx.def_path_str(adt_def.did()),
+            tcx.item_name(def_id)
+        ),
+        None => tcx.def_path_str(def_id),
+    };
+    path.split("::")
+        .filter(|segment| !segment.starts_with('<'))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpawnApi {
+    Std,
+    Rayon,
+    Tokio,
+    Crossbeam,
+}
+
+impl SpawnApi {
+    pub fn from_instance<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
+        let path = api_path(instance, tcx);
+        match path.as_str() {
+            "std::thread::spawn"
+            | "std::thread::Builder::spawn"
+            | "std::thread::Builder::spawn_scoped"
+            | "std::thread::Scope::spawn"
+            | "loom::thread::spawn"
+            | "loom::thread::Builder::spawn" => Some(SpawnApi::Std),
+            "rayon::spawn"
+            | "rayon::spawn_fifo"
+            | "rayon::Scope::spawn"
+            | "rayon::ScopeFifo::spawn_fifo"
+            | "rayon_core::spawn"
+            | "rayon_core::spawn_fifo"
+            | "rayon_core::Scope::spawn"
+            | "rayon_core::ScopeFifo::spawn_fifo" => Some(SpawnApi::Rayon),
+            "tokio::spawn"
+            | "tokio::task::spawn"
+            | "tokio::task::spawn_blocking"
+            | "tokio::runtime::Runtime::spawn"
+            | "tokio::runtime::Runtime::spawn_blocking"
+            | "tokio::runtime::Handle::spawn"
+            | "tokio::runtime::Handle::spawn_blocking" => Some(SpawnApi::Tokio),
+            "crossbeam::thread::Scope::spawn"
+            | "crossbeam::thread::ScopedThreadBuilder::spawn"
+            | "crossbeam_utils::thread::Scope::spawn"
+            | "crossbeam_utils::thread::ScopedThreadBuilder::spawn" => Some(SpawnApi::Crossbeam),
+            _ => None,
+        }
+    }
+
+    /// Resolve the spawned closures (or futures, or fn items) from the generic args of the API's instance.
+    pub fn spawned_instances<'tcx>(
+        instance: &Instance<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        param_env: ParamEnv<'tcx>,
+    ) -> Vec<Instance<'tcx>> {
+        instance
+            .args
+            .types()
+            .filter_map(|ty| match ty.kind() {
+                ty::Closure(def_id, args)
+                | ty::Generator(def_id, args, _)
+                | ty::FnDef(def_id, args) => Instance::resolve(tcx, param_env, *def_id, args)
+                    .ok()
+                    .flatten(),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Whether the instance is a local spawn API, whose future is resolved by `SpawnApi::spawned_instances`.
+/// They are not thread roots since the future runs on the current thread.
+pub fn is_local_spawn_api<'tcx>(instance: &Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    matches!(
+        api_path(instance, tcx).as_str(),
+        "tokio::task::spawn_local"
+            | "tokio::task::LocalSet::spawn_local"
+            | "tokio::task::LocalSet::run_until"
+            | "tokio::task::LocalSet::block_on"
+    )
+}
+
+/// Resolve the closures called synchronously by `std::panic::catch_unwind`,
+/// including those wrapped in `AssertUnwindSafe`.
+/// Returns None if the instance is not `catch_unwind`.
+pub fn catch_unwind_instances<'tcx>(
+    instance: &Instance<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    param_env: ParamEnv<'tcx>,
+) -> Option<Vec<Instance<'tcx>>> {
+    if api_path(instance, tcx) != "std::panic::catch_unwind" {
+        return None;
+    }
+    Some(
+        instance
+            .args
+            .types()
+            .flat_map(Ty::walk)
+            .filter_map(|arg| match arg.as_type()?.kind() {
+                ty::Closure(def_id, args) => Instance::resolve(tcx, param_env, *def_id, args)
+                    .ok()
+                    .flatten(),
+                _ => None,
+            })
+            .collect(),
+    )
+}
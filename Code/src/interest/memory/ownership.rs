@@ -2,7 +2,10 @@ extern crate rustc_hir;
 extern crate rustc_middle;
 
 use rustc_hir::def_id::DefId;
-use rustc_middle::ty::TyCtxt;
+use rustc_middle::mir::{
+    Body, Local, Location, Operand, Place, ProjectionElem, Rvalue, StatementKind,
+};
+use rustc_middle::ty::{Ty, TyCtxt, TyKind};
 
 use rustc_middle::ty::{GenericArg, List};
 
@@ -27,7 +30,7 @@ pub fn is_arc_or_rc_clone<'tcx>(
 
 #[inline]
 pub fn is_arc(arg_ty_name: &str) -> bool {
-    arg_ty_name.starts_with("std::sync::Arc<")
+    arg_ty_name.starts_with("std::sync::Arc<") || arg_ty_name.starts_with("loom::sync::Arc<")
 }
 
 #[inline]
@@ -41,8 +44,379 @@ pub fn is_ptr_read(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
     tcx.def_path_str(def_id).starts_with("std::ptr::read::<")
 }
 
+/// How a fn on raw ptrs passes the pointers between its args and its return value z.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawPtrPassing {
+    /// z = NonNull::new(x), NonNull::as_ptr(x), NonNull::cast(x), transmute(x), etc., z is a copy of x.
+    Copy,
+    /// z = NonNull::as_ref(x) or NonNull::as_mut(x) where `x: &NonNull<T>`, z is a copy of *x.
+    Load,
+    /// ptr::write(x, y) or <*mut T>::write(x, y), *x = y.
+    Store,
+    /// ptr::copy_nonoverlapping(x, y, n) or ptr::copy(x, y, n), *y = *x,
+    /// where x and y are the args at `src` and `dst`, e.g., swapped for `<*mut T>::copy_from(y, x, n)`.
+    CopyPointee { src: usize, dst: usize },
+}
+
+/// The fns publishing or converting raw ptrs in unsafe code, see `RawPtrPassing`.
+#[inline]
+pub fn raw_ptr_passing(def_id: DefId, tcx: TyCtxt<'_>) -> Option<RawPtrPassing> {
+    let passing = match tcx.def_path_str(def_id).as_str() {
+        "std::ptr::NonNull::<T>::new"
+        | "std::ptr::NonNull::<T>::new_unchecked"
+        | "std::ptr::NonNull::<T>::as_ptr"
+        | "std::ptr::NonNull::<T>::cast"
+        | "std::intrinsics::transmute" => RawPtrPassing::Copy,
+        "std::ptr::NonNull::<T>::as_ref" | "std::ptr::NonNull::<T>::as_mut" => RawPtrPassing::Load,
+        "std::ptr::write"
+        | "std::ptr::write_unaligned"
+        | "std::ptr::write_volatile"
+        | "std::ptr::mut_ptr::<impl *mut T>::write"
+        | "std::ptr::mut_ptr::<impl *mut T>::write_unaligned"
+        | "std::ptr::mut_ptr::<impl *mut T>::write_volatile" => RawPtrPassing::Store,
+        "std::intrinsics::copy_nonoverlapping"
+        | "std::intrinsics::copy"
+        | "std::ptr::const_ptr::<impl *const T>::copy_to_nonoverlapping"
+        | "std::ptr::const_ptr::<impl *const T>::copy_to"
+        | "std::ptr::mut_ptr::<impl *mut T>::copy_to_nonoverlapping"
+        | "std::ptr::mut_ptr::<impl *mut T>::copy_to" => {
+            RawPtrPassing::CopyPointee { src: 0, dst: 1 }
+        }
+        "std::ptr::mut_ptr::<impl *mut T>::copy_from_nonoverlapping"
+        | "std::ptr::mut_ptr::<impl *mut T>::copy_from" => {
+            RawPtrPassing::CopyPointee { src: 1, dst: 0 }
+        }
+        _ => return None,
+    };
+    Some(passing)
+}
+
 /// z = <_ as Index<_>>::index(x, y)
 #[inline]
 pub fn is_index(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
     tcx.def_path_str(def_id).ends_with("::index")
 }
+
+/// z = <_ as Index<_>>::index(x, i), <_ as IndexMut<_>>::index_mut(x, i), or <[T]>::get(x, i)
+/// (and `get_mut`, `get_unchecked`, `get_unchecked_mut`) borrows the element i of the collection x.
+#[inline]
+pub fn is_element_access(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    is_index(def_id, tcx)
+        || matches!(
+            tcx.def_path_str(def_id).as_str(),
+            "std::ops::IndexMut::index_mut"
+                | "core::slice::<impl [T]>::get"
+                | "core::slice::<impl [T]>::get_mut"
+                | "core::slice::<impl [T]>::get_unchecked"
+                | "core::slice::<impl [T]>::get_unchecked_mut"
+        )
+}
+
+/// z = Result::unwrap_or_else(x, f), e.g., `m.lock().unwrap_or_else(PoisonError::into_inner)`
+/// recovers the guard from a poisoned lock, which is moved from x either way.
+#[inline]
+pub fn is_result_unwrap_or_else(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    tcx.def_path_str(def_id) == "std::result::Result::<T, E>::unwrap_or_else"
+}
+
+/// How the result z of an identity-like combinator of `Option` or `Result` derives from its receiver x.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadPassing {
+    /// The payload of x is moved into z, e.g., `z = Result::unwrap(x)` or `z = Try::branch(x)` of `?`.
+    Move,
+    /// z borrows the payload of *x, e.g., `z = Option::as_ref(x)` where `x: &Option<T>`.
+    Borrow,
+}
+
+/// z = Option::unwrap(x), z = Result::ok(x), z = Option::as_ref(x), etc.,
+/// the common combinators that pass the payload of x through to z,
+/// including the 2-arg ones like `Result::expect(x, msg)` and `Result::map_err(x, f)`.
+#[inline]
+pub fn option_result_combinator(def_id: DefId, tcx: TyCtxt<'_>) -> Option<PayloadPassing> {
+    match tcx.def_path_str(def_id).as_str() {
+        "std::option::Option::<T>::unwrap"
+        | "std::option::Option::<T>::expect"
+        | "std::option::Option::<T>::unwrap_or_default"
+        | "std::option::Option::<T>::unwrap_unchecked"
+        | "std::result::Result::<T, E>::unwrap"
+        | "std::result::Result::<T, E>::expect"
+        | "std::result::Result::<T, E>::unwrap_or_default"
+        | "std::result::Result::<T, E>::ok"
+        | "std::result::Result::<T, E>::map_err"
+        | "std::ops::Try::branch" => Some(PayloadPassing::Move),
+        "std::option::Option::<T>::as_ref"
+        | "std::option::Option::<T>::as_mut"
+        | "std::result::Result::<T, E>::as_ref"
+        | "std::result::Result::<T, E>::as_mut" => Some(PayloadPassing::Borrow),
+        _ => None,
+    }
+}
+
+/// z = MutexGuard::map(x, f) or z = MutexGuard::try_map(x, f) (and those of the other lockguards),
+/// e.g., `lock_api::MutexGuard::<'a, R, T>::map`, which maps the lockguard x to a part of the data
+/// still guarded by the same lock.
+#[inline]
+pub fn is_lockguard_map(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    let fn_name = tcx.def_path_str(def_id);
+    match fn_name.rsplit_once("::") {
+        Some((ty_path, "map" | "try_map")) => ty_path
+            .split("::<")
+            .next()
+            .map_or(false, |ty_path| ty_path.ends_with("Guard")),
+        _ => false,
+    }
+}
+
+/// z = DashMap::get(x, k) (and the other keyed accesses of a `DashMap`), e.g., `map.get_mut(&k)`,
+/// which locks the shard of k in the map x.
+#[inline]
+pub fn is_sharded_lock_access(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    tcx.crate_name(def_id.krate).as_str() == "dashmap"
+        && matches!(
+            tcx.item_name(def_id).as_str(),
+            "get" | "get_mut" | "try_get" | "try_get_mut" | "entry" | "try_entry"
+        )
+}
+
+/// std::ptr::drop_in_place(x) or ManuallyDrop::drop(x)
+/// drops the pointee of x in place.
+#[inline]
+pub fn is_explicit_drop(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    let fn_name = tcx.def_path_str(def_id);
+    fn_name == "std::ptr::drop_in_place" || fn_name == "std::mem::ManuallyDrop::<T>::drop"
+}
+
+/// std::mem::forget(x), ManuallyDrop::new(x), or Box::leak(x)
+/// takes the ownership of x without ever dropping it (unless dropped manually).
+#[inline]
+pub fn is_leak(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    matches!(
+        tcx.def_path_str(def_id).as_str(),
+        "std::mem::forget" | "std::mem::ManuallyDrop::<T>::new" | "std::boxed::Box::<T, A>::leak"
+    )
+}
+
+/// p = Vec::as_ptr(x), Vec::as_mut_ptr(x), <[T]>::as_ptr(x), or str::as_ptr(x) (and their `as_mut_ptr`)
+/// derives the raw ptr p to the buffer borrowed by x.
+#[inline]
+pub fn is_buffer_as_ptr(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    matches!(
+        tcx.def_path_str(def_id).as_str(),
+        "std::vec::Vec::<T, A>::as_ptr"
+            | "std::vec::Vec::<T, A>::as_mut_ptr"
+            | "core::slice::<impl [T]>::as_ptr"
+            | "core::slice::<impl [T]>::as_mut_ptr"
+            | "core::str::<impl str>::as_ptr"
+            | "core::str::<impl str>::as_mut_ptr"
+    )
+}
+
+/// Whether the type is `Vec` or `String`, whose buffer is reallocated as it grows.
+fn is_vec_or_string(ty: Ty<'_>, tcx: TyCtxt<'_>) -> bool {
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) => matches!(
+            tcx.def_path_str(adt_def.did()).as_str(),
+            "std::vec::Vec" | "std::string::String"
+        ),
+        _ => false,
+    }
+}
+
+/// The trait method of a `Vec` or `String` receiver, e.g., `<Vec<T> as Deref>::deref`.
+fn is_trait_method_of_vec_or_string<'tcx>(
+    def_id: DefId,
+    substs: &List<GenericArg<'tcx>>,
+    tcx: TyCtxt<'tcx>,
+    trait_method: &str,
+) -> bool {
+    tcx.def_path_str(def_id) == trait_method
+        && substs
+            .get(0)
+            .and_then(|arg| arg.as_type())
+            .map_or(false, |self_ty| is_vec_or_string(self_ty, tcx))
+}
+
+/// y = Vec::as_slice(x), String::as_str(x), or y = Deref::deref(x) or Index::index(x, ..) of a `Vec` or `String` x
+/// (and their `mut` versions) borrows the buffer of x.
+pub fn is_buffer_borrow<'tcx>(
+    def_id: DefId,
+    substs: &List<GenericArg<'tcx>>,
+    tcx: TyCtxt<'tcx>,
+) -> bool {
+    matches!(
+        tcx.def_path_str(def_id).as_str(),
+        "std::vec::Vec::<T, A>::as_slice"
+            | "std::vec::Vec::<T, A>::as_mut_slice"
+            | "std::string::String::as_str"
+            | "std::string::String::as_mut_str"
+    ) || [
+        "std::ops::Deref::deref",
+        "std::ops::DerefMut::deref_mut",
+        "std::ops::Index::index",
+        "std::ops::IndexMut::index_mut",
+    ]
+    .into_iter()
+    .any(|trait_method| is_trait_method_of_vec_or_string(def_id, substs, tcx, trait_method))
+}
+
+/// Vec::push(x, ..), String::push_str(x, ..), Extend::extend(x, ..) of a `Vec` or `String` x, etc.
+/// may reallocate the buffer of x or drop its elements, invalidating the raw ptrs to the buffer.
+/// Returns the name of the method.
+pub fn buffer_invalidation<'tcx>(
+    def_id: DefId,
+    substs: &List<GenericArg<'tcx>>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<&'static str> {
+    const VEC_METHODS: [&str; 11] = [
+        "push",
+        "insert",
+        "extend_from_slice",
+        "append",
+        "resize",
+        "reserve",
+        "reserve_exact",
+        "shrink_to_fit",
+        "clear",
+        "truncate",
+        "retain",
+    ];
+    const STRING_METHODS: [&str; 8] = [
+        "push",
+        "push_str",
+        "insert",
+        "insert_str",
+        "reserve",
+        "shrink_to_fit",
+        "clear",
+        "truncate",
+    ];
+    let fn_name = tcx.def_path_str(def_id);
+    let (ty_path, method) = fn_name.rsplit_once("::")?;
+    let methods = match ty_path {
+        "std::vec::Vec::<T, A>" => VEC_METHODS.as_slice(),
+        "std::string::String" => STRING_METHODS.as_slice(),
+        _ if is_trait_method_of_vec_or_string(def_id, substs, tcx, "std::iter::Extend::extend") => {
+            return Some("extend");
+        }
+        _ => return None,
+    };
+    methods.iter().find(|m| **m == method).copied()
+}
+
+/// y = Vec::with_capacity(..) or String::with_capacity(..) preallocates the buffer of y.
+#[inline]
+pub fn is_with_capacity(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    matches!(
+        tcx.def_path_str(def_id).as_str(),
+        "std::vec::Vec::<T>::with_capacity" | "std::string::String::with_capacity"
+    )
+}
+
+/// p = CStr::as_ptr(x) derives the raw ptr p to the C string borrowed by x.
+#[inline]
+pub fn is_cstr_as_ptr(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    tcx.def_path_str(def_id) == "std::ffi::CStr::as_ptr"
+}
+
+/// y = CString::as_c_str(x) or y = Deref::deref(x) of a `CString` x borrows the C string of x.
+pub fn is_cstring_borrow<'tcx>(
+    def_id: DefId,
+    substs: &List<GenericArg<'tcx>>,
+    tcx: TyCtxt<'tcx>,
+) -> bool {
+    match tcx.def_path_str(def_id).as_str() {
+        "std::ffi::CString::as_c_str" => true,
+        "std::ops::Deref::deref" => {
+            substs
+                .get(0)
+                .and_then(|arg| arg.as_type())
+                .map_or(false, |self_ty| match self_ty.kind() {
+                    TyKind::Adt(adt_def, _) => {
+                        tcx.def_path_str(adt_def.did()) == "std::ffi::CString"
+                    }
+                    _ => false,
+                })
+        }
+        _ => false,
+    }
+}
+
+/// p = Box::into_raw(b), Rc::into_raw(b), Arc::into_raw(b), or (p, len, cap) = Vec::into_raw_parts(b)
+/// leaks the ownership of b to the raw ptr p.
+/// Rc::increment_strong_count(p) and Arc::increment_strong_count(p) also leak an ownership to p.
+/// Returns the name of the API.
+pub fn leak_to_raw(def_id: DefId, tcx: TyCtxt<'_>) -> Option<&'static str> {
+    match tcx.def_path_str(def_id).as_str() {
+        "std::boxed::Box::<T, A>::into_raw" => Some("Box::into_raw"),
+        "std::rc::Rc::<T, A>::into_raw" => Some("Rc::into_raw"),
+        "std::sync::Arc::<T, A>::into_raw" => Some("Arc::into_raw"),
+        "std::vec::Vec::<T, A>::into_raw_parts" => Some("Vec::into_raw_parts"),
+        "std::string::String::into_raw_parts" => Some("String::into_raw_parts"),
+        "std::rc::Rc::<T>::increment_strong_count" => Some("Rc::increment_strong_count"),
+        "std::sync::Arc::<T>::increment_strong_count" => Some("Arc::increment_strong_count"),
+        _ => None,
+    }
+}
+
+/// b = Box::from_raw(p), Rc::from_raw(p), Arc::from_raw(p), or Vec::from_raw_parts(p, len, cap)
+/// reconstructs the owner b from the raw ptr p, which frees the pointee when b is dropped.
+/// Rc::decrement_strong_count(p) and Arc::decrement_strong_count(p) also reconstruct and drop an owner.
+/// Returns the name of the API.
+pub fn reconstruct_from_raw(def_id: DefId, tcx: TyCtxt<'_>) -> Option<&'static str> {
+    match tcx.def_path_str(def_id).as_str() {
+        "std::boxed::Box::<T>::from_raw" | "std::boxed::Box::<T, A>::from_raw_in" => {
+            Some("Box::from_raw")
+        }
+        "std::rc::Rc::<T>::from_raw" | "std::rc::Rc::<T, A>::from_raw_in" => Some("Rc::from_raw"),
+        "std::sync::Arc::<T>::from_raw" | "std::sync::Arc::<T, A>::from_raw_in" => {
+            Some("Arc::from_raw")
+        }
+        "std::vec::Vec::<T>::from_raw_parts" | "std::vec::Vec::<T, A>::from_raw_parts_in" => {
+            Some("Vec::from_raw_parts")
+        }
+        "std::string::String::from_raw_parts" => Some("String::from_raw_parts"),
+        "std::rc::Rc::<T>::decrement_strong_count" => Some("Rc::decrement_strong_count"),
+        "std::sync::Arc::<T>::decrement_strong_count" => Some("Arc::decrement_strong_count"),
+        _ => None,
+    }
+}
+
+/// Find the place pointed to by `ptr` before `location` in the same basic block
+/// by tracking back the assignments to `ptr`, e.g.,
+/// ```ignore
+/// _5 = &mut _2;
+/// _4 = &raw mut (*_5);
+/// drop_in_place::<MutexGuard<i32>>(move _4)
+/// ```
+/// `_4` points to `_2`.
+pub fn pointee_place<'tcx>(
+    body: &Body<'tcx>,
+    ptr: Local,
+    location: Location,
+) -> Option<Place<'tcx>> {
+    let statements = &body.basic_blocks[location.block].statements;
+    let mut ptr = ptr;
+    for stmt in statements[..location.statement_index].iter().rev() {
+        let rvalue = match &stmt.kind {
+            StatementKind::Assign(box (lhs, rvalue)) if lhs.as_local() == Some(ptr) => rvalue,
+            _ => continue,
+        };
+        match rvalue {
+            Rvalue::Ref(_, _, place) | Rvalue::AddressOf(_, place) => {
+                if let [ProjectionElem::Deref] = place.projection.as_slice() {
+                    // ptr = &(*ptr1): continue to find the pointee of ptr1
+                    ptr = place.local;
+                } else {
+                    return Some(*place);
+                }
+            }
+            Rvalue::Use(Operand::Copy(place) | Operand::Move(place))
+            | Rvalue::Cast(_, Operand::Copy(place) | Operand::Move(place), _) => {
+                // ptr = ptr1: continue to find the pointee of ptr1
+                ptr = place.as_local()?;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
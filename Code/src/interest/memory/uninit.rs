@@ -2,11 +2,16 @@
 //! 1. _1 = uninitialized::<Vec<i32>>() -> bb1;
 //! 2. _1 = MaybeUninit::<Vec<i32>>::uninit() -> bb1;
 //! 3. _2 = MaybeUninit::<Vec<i32>>::assume_init(move _1) -> bb4;
+//! 4. _1 = zeroed::<&i32>() -> bb1;
+//! 5. _1 = MaybeUninit::<Box<i32>>::zeroed() -> bb1;
 //! initialize:
 //! 1. _2 = MaybeUninit::<Vec<i32>>::write(move _3, move _4) -> bb3;
 //! 2. _2 = MaybeUninit::<Obj>::as_mut_ptr(move _3) -> bb2;
 //! 3. _5 = &raw mut ((*_2).0: std::vec::Vec<i32>);
 //! 4. _4 = ptr::mut_ptr::<impl *mut Vec<i32>>::write(move _5, move _6) -> bb4;
+//!    or _4 = ptr::write::<Vec<i32>>(move _5, move _6) -> bb4;
+//!    where each field of Obj is written through the ptr returned by as_mut_ptr,
+//!    or the whole Obj is written by _4 = ptr::mut_ptr::<impl *mut Obj>::write(_2, move _6) -> bb4;
 extern crate rustc_data_structures;
 extern crate rustc_middle;
 
@@ -14,7 +19,7 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 
 use rustc_data_structures::fx::FxHashMap;
-use rustc_middle::ty::{Instance, TyCtxt};
+use rustc_middle::ty::{self, Instance, Ty, TyCtxt, TyKind};
 
 static UNINIT_API_REGEX: Lazy<FxHashMap<UninitApi, Regex>> = Lazy::new(|| {
     use UninitApi::*;
@@ -28,14 +33,29 @@ static UNINIT_API_REGEX: Lazy<FxHashMap<UninitApi, Regex>> = Lazy::new(|| {
         Uninitialized,
         Regex::new(r"^(std|core)::mem::uninitialized::<.*>").unwrap(),
     );
+    m.insert(
+        Zeroed,
+        Regex::new(r"^(std|core)::mem::zeroed::<.*>").unwrap(),
+    );
+    m.insert(
+        MaybeUninitZeroed,
+        Regex::new(r"^(std|core)::mem::MaybeUninit::<.*>::zeroed").unwrap(),
+    );
     m.insert(
         MaybeUninitWrite,
         Regex::new(r"^(std|core)::mem::MaybeUninit::<.*>::write").unwrap(),
     );
     m.insert(
-        PtrWrite,
+        AsMutPtr,
         Regex::new(r"^(std|core)::mem::MaybeUninit::<.*>::as_mut_ptr").unwrap(),
     );
+    m.insert(
+        PtrWrite,
+        Regex::new(
+            r"^(std|core)::ptr::(mut_ptr::<impl \*mut .*>::)?write(_unaligned|_volatile)?(::<.*>)?$",
+        )
+        .unwrap(),
+    );
     m.insert(
         AssumeInit,
         Regex::new(r"^(std|core)::mem::MaybeUninit::<.*>::assume_init(_mut)?").unwrap(),
@@ -47,14 +67,17 @@ static UNINIT_API_REGEX: Lazy<FxHashMap<UninitApi, Regex>> = Lazy::new(|| {
 pub enum UninitApi {
     Uninitialized,
     MaybeUninit,
+    Zeroed,
+    MaybeUninitZeroed,
     AssumeInit,
     MaybeUninitWrite,
+    AsMutPtr,
     PtrWrite,
 }
 
 impl UninitApi {
     pub fn from_instance<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Self> {
-        let path = tcx.def_path_str_with_args(instance.def_id(), &*instance.args);
+        let path = tcx.def_path_str_with_args(instance.def_id(), instance.args);
         Self::from_str(&path)
     }
 
@@ -69,6 +92,74 @@ impl UninitApi {
     }
 }
 
+/// The max depth of the fields to find a component invalid when zeroed.
+const MAX_ZEROED_DEPTH: usize = 16;
+
+/// The component of `ty` (maybe `ty` itself) that is invalid when zeroed, e.g., a reference, a `Box`,
+/// a `NonNull`, a fn ptr, a `char`, or an enum without a zero variant,
+/// found recursively through the fields of the ADTs, tuples, and arrays.
+/// An enum is valid when zeroed if a variant of zeroable fields has the discriminant zero,
+/// or if a variant without fields may be encoded in the niche of another variant,
+/// e.g., `Option<&T>` and `Option<NonNull<T>>` are `None` when zeroed.
+pub fn non_zeroable_component<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    non_zeroable_component_impl(ty, tcx, 0)
+}
+
+fn non_zeroable_component_impl<'tcx>(
+    ty: Ty<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    depth: usize,
+) -> Option<Ty<'tcx>> {
+    if depth > MAX_ZEROED_DEPTH {
+        return None;
+    }
+    match ty.kind() {
+        TyKind::Ref(..) | TyKind::FnPtr(_) | TyKind::Char | TyKind::Never => Some(ty),
+        TyKind::Adt(..) if ty.is_box() => Some(ty),
+        TyKind::Adt(adt_def, substs) if adt_def.is_struct() => {
+            // e.g., `#[rustc_layout_scalar_valid_range_start(1)]` of `NonNull` and `NonZeroUsize`
+            if let (std::ops::Bound::Included(start), _) =
+                tcx.layout_scalar_valid_range(adt_def.did())
+            {
+                if start > 0 {
+                    return Some(ty);
+                }
+            }
+            adt_def.all_fields().find_map(|field| {
+                non_zeroable_component_impl(field.ty(tcx, substs), tcx, depth + 1)
+            })
+        }
+        TyKind::Adt(adt_def, substs) if adt_def.is_enum() => {
+            let has_data = adt_def
+                .variants()
+                .iter()
+                .any(|variant| !variant.fields.is_empty());
+            let valid = adt_def.discriminants(tcx).any(|(idx, discr)| {
+                let variant = adt_def.variant(idx);
+                (discr.val == 0 || (variant.fields.is_empty() && has_data))
+                    && variant.fields.iter().all(|field| {
+                        non_zeroable_component_impl(field.ty(tcx, substs), tcx, depth + 1).is_none()
+                    })
+            });
+            if valid {
+                None
+            } else {
+                Some(ty)
+            }
+        }
+        TyKind::Tuple(tys) => tys
+            .iter()
+            .find_map(|ty| non_zeroable_component_impl(ty, tcx, depth + 1)),
+        TyKind::Array(elem_ty, len) => {
+            match len.try_eval_target_usize(tcx, ty::ParamEnv::reveal_all()) {
+                Some(0) => None,
+                _ => non_zeroable_component_impl(*elem_ty, tcx, depth + 1),
+            }
+        }
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,14 +175,39 @@ mod tests {
             Uninitialized,
             UninitApi::from_str("std::mem::uninitialized::<std::vec::Vec<i32>>").unwrap()
         );
+        assert_eq!(
+            Zeroed,
+            UninitApi::from_str("std::mem::zeroed::<&i32>").unwrap()
+        );
+        assert_eq!(
+            MaybeUninitZeroed,
+            UninitApi::from_str("std::mem::MaybeUninit::<std::boxed::Box<i32>>::zeroed").unwrap()
+        );
         assert_eq!(
             MaybeUninitWrite,
             UninitApi::from_str("std::mem::MaybeUninit::<std::vec::Vec<i32>>::write").unwrap()
         );
         assert_eq!(
-            PtrWrite,
+            AsMutPtr,
             UninitApi::from_str("std::mem::MaybeUninit::<std::vec::Vec<i32>>::as_mut_ptr").unwrap()
         );
+        assert_eq!(
+            PtrWrite,
+            UninitApi::from_str("std::ptr::mut_ptr::<impl *mut std::vec::Vec<i32>>::write")
+                .unwrap()
+        );
+        assert_eq!(
+            PtrWrite,
+            UninitApi::from_str("std::ptr::write::<bool>").unwrap()
+        );
+        assert_eq!(
+            PtrWrite,
+            UninitApi::from_str("std::ptr::mut_ptr::<impl *mut Obj>::write_unaligned").unwrap()
+        );
+        assert_eq!(
+            None,
+            UninitApi::from_str("std::ptr::mut_ptr::<impl *mut u8>::write_bytes")
+        );
         assert_eq!(
             AssumeInit,
             UninitApi::from_str("std::mem::MaybeUninit::<std::vec::Vec<i32>>::assume_init")
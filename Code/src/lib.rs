@@ -0,0 +1,55 @@
+//! lockbud as a library: statically detect bugs on the MIR of a crate in a rustc session.
+//! The `lockbud` binary is a rustc driver over `run_analysis` (see `callbacks.rs`),
+//! and other tools can call it from the `after_analysis` of their own `rustc_driver::Callbacks`, e.g.,
+//! ```ignore
+//! let options = Options::parse_from_str("-k deadlock")?;
+//! let output = queries.global_ctxt().unwrap().enter(|tcx| run_analysis(tcx, &options));
+//! for report in output.deadlock { /* ... */ }
+//! ```
+//! The reports, i.e., `Report`, `ReportContent`, and the diagnoses (`DeadlockDiagnosis`, `AtomicityViolationDiagnosis`, etc.,
+//! while the memory bugs are diagnosed in text), are the serde types of the `lockbud-report-types` crate,
+//! which are stable within a `SCHEMA_VERSION`.
+//! The rest of the API, e.g., `Options` and `AnalysisOutput`, follows the nightly toolchain pinned by `rust-toolchain.toml`
+//! and may change with it.
+#![feature(rustc_private)]
+#![feature(box_patterns)]
+
+extern crate rustc_driver;
+extern crate rustc_interface;
+extern crate rustc_middle;
+extern crate rustc_session;
+
+mod analysis;
+mod baseline;
+pub mod callbacks;
+mod config;
+mod detector;
+mod diagnostic;
+mod interest;
+pub mod options;
+mod plugin;
+mod progress;
+mod report_file;
+mod run;
+mod snippet;
+pub mod suppression;
+#[cfg(test)]
+mod test_utils;
+
+pub use analysis::budget::SkippedFunction;
+pub use lockbud_report_types::atomic::AtomicityViolationDiagnosis;
+pub use lockbud_report_types::lock::DeadlockDiagnosis;
+pub use lockbud_report_types::{
+    self as report_types, Report, ReportContent, ReportEnvelope, SCHEMA_VERSION,
+};
+pub use run::{run_analysis, AnalysisOutput};
+
+/// The sysroot of the toolchain lockbud is built with.
+pub fn find_sysroot() -> Option<String> {
+    let home = option_env!("RUSTUP_HOME");
+    let toolchain = option_env!("RUSTUP_TOOLCHAIN");
+    match (home, toolchain) {
+        (Some(home), Some(toolchain)) => Some(format!("{}/toolchains/{}", home, toolchain)),
+        _ => option_env!("RUST_SYSROOT").map(str::to_owned),
+    }
+}
@@ -1,21 +1,14 @@
 //! The general rustc plugin framework.
 //! Inspired by <https://github.com/facebookexperimental/MIRAI/blob/9cf3067309d591894e2d0cd9b1ee6e18d0fdd26c/checker/src/main.rs>
+//! The detection itself is in the lockbud library, see `lib.rs`.
 #![feature(rustc_private)]
-#![feature(box_patterns)]
 
 extern crate rustc_driver;
-extern crate rustc_interface;
-extern crate rustc_middle;
 extern crate rustc_session;
 
-mod analysis;
-mod callbacks;
-mod detector;
-mod interest;
-mod options;
-
+use lockbud::options::Options;
+use lockbud::{callbacks, find_sysroot, suppression};
 use log::debug;
-use options::Options;
 use rustc_session::config::ErrorOutputType;
 use rustc_session::EarlyErrorHandler;
 
@@ -31,9 +24,10 @@ fn main() {
             .write_style("LOCKBUD_LOG_STYLE");
         env_logger::init_from_env(e);
     }
-    // Get any options specified via the LOCKBUD_FLAGS environment variable
+    // Get any options specified via the LOCKBUD_FLAGS environment variable,
+    // failing at startup on invalid ones (e.g., unknown detectors) rather than falling back to the defaults.
     let options = Options::parse_from_str(&std::env::var("LOCKBUD_FLAGS").unwrap_or_default())
-        .unwrap_or_default();
+        .unwrap_or_else(|err| handler.early_error(format!("invalid LOCKBUD_FLAGS: {}", err)));
     debug!("LOCKBUD options from environment: {:?}", options);
     let mut args = std::env::args_os()
         .enumerate()
@@ -53,6 +47,7 @@ fn main() {
 
     let mut rustc_command_line_arguments: Vec<String> = args[1..].into();
     rustc_driver::install_ice_hook("ice ice ice baby", |_| ());
+    let mut callbacks = callbacks::LockBudCallbacks::new(options);
     let result = rustc_driver::catch_fatal_errors(|| {
         // Add back the binary name
         rustc_command_line_arguments.insert(0, args[0].clone());
@@ -75,7 +70,12 @@ fn main() {
                 // Tell compiler where to find the std library and so on.
                 // The compiler relies on the standard rustc driver to tell it, so we have to do likewise.
                 rustc_command_line_arguments.push(sysroot);
-                rustc_command_line_arguments.push(find_sysroot());
+                rustc_command_line_arguments.push(find_sysroot().unwrap_or_else(|| {
+                    handler.early_error(
+                        "Could not find sysroot. Run `cargo lockbud setup` to install the toolchain with rustup, \
+                         or specify the RUST_SYSROOT environment variable when building LOCKBUD",
+                    )
+                }));
             }
 
             let always_encode_mir: String = "always-encode-mir".into();
@@ -87,9 +87,17 @@ fn main() {
                 rustc_command_line_arguments.push("-Z".into());
                 rustc_command_line_arguments.push(always_encode_mir);
             }
+
+            // Enable `#[cfg_attr(lockbud, allow(lockbud::..))]`, see `suppression.rs`.
+            if !rustc_command_line_arguments
+                .iter()
+                .any(|arg| arg.contains("register_tool(lockbud)"))
+            {
+                rustc_command_line_arguments
+                    .extend(suppression::RUSTC_ARGS.iter().map(|arg| arg.to_string()));
+            }
         }
 
-        let mut callbacks = callbacks::LockBudCallbacks::new(options);
         debug!(
             "rustc_command_line_arguments {:?}",
             rustc_command_line_arguments
@@ -100,22 +108,10 @@ fn main() {
     })
     .and_then(|result| result);
     let exit_code = match result {
-        Ok(_) => rustc_driver::EXIT_SUCCESS,
+        // Exits with `EXIT_DENIED` if any deny-level report is emitted.
+        // With `--print`, nothing is analyzed, so it always exits with 0 as rustc does.
+        Ok(_) => callbacks.exit_code(),
         Err(_) => rustc_driver::EXIT_FAILURE,
     };
     std::process::exit(exit_code);
 }
-
-fn find_sysroot() -> String {
-    let home = option_env!("RUSTUP_HOME");
-    let toolchain = option_env!("RUSTUP_TOOLCHAIN");
-    match (home, toolchain) {
-        (Some(home), Some(toolchain)) => format!("{}/toolchains/{}", home, toolchain),
-        _ => option_env!("RUST_SYSROOT")
-            .expect(
-                "Could not find sysroot. Specify the RUST_SYSROOT environment variable, \
-                 or use rustup to set the compiler to use for LOCKBUD",
-            )
-            .to_owned(),
-    }
-}
@@ -1,10 +1,76 @@
 //! Parsing Options.
-//! `--detector-kind {kind}` or `-k`, currently support only deadlock
+//! `--detector-kind {kind}` or `-k`, the detectors to run, deadlock by default.
+//! `--detectors [detector1,-detector2]`, enables (or disables if prefixed by `-`) detectors after `-k` (repeatable),
+//! e.g., `--detectors=-conflictlock,atomicity_violation`, see `detector/config.rs`.
+//! `--set detector.key=value`, sets a parameter of a detector (repeatable), e.g., `--set doublelock.min_possibility=probably`.
 //! `--blacklist-mode` or `-b`, sets backlist than the default whitelist.
 //! `--crate-name-list [crate1,crate2]` or `-l`, white or black lists of crates decided by `-b`.
 //! if `-l` not specified, then do not white-or-black list the crates.
+//! `--all-crates`, also analyzes the dependencies when run by cargo, rather than only the primary packages
+//! (the workspace members or the packages selected by `-p`), see `callbacks.rs`.
+//! `--no-thread-filter`, reports conflictlock even if the locks are only acquired in the same thread,
+//! the same as `--set conflict_lock.thread_filter=false`.
+//! `--alias-stats`, prints statistics on alias queries and the points-to cache broken down by detectors,
+//! and records them in the `alias_stats` of the report envelope.
+//! `--analyze-deps-guards`, also collects lockguards in the fns from dependencies (e.g., generic lock wrappers).
+//! `--analyze-proc-macros`, also analyzes the proc-macro crates, which are skipped like the build scripts by default.
+//! `--quiet` or `-q`, disables progress output on stderr.
+//! `--max-callchains N`, the maximal number of callchains in each deadlock diagnosis, 8 by default.
+//! `--max-callchain-depth N`, the maximal number of calls in each callchain, 20 by default.
+//! They are overridden by `--set deadlock.max_callchains=N` and `--set deadlock.max_callchain_depth=N`.
+//! `--fn-time-budget SECS`, the time budget of analyzing each fn, 10 by default, 0 for unlimited.
+//! `--fn-node-budget N`, the maximal number of constraint graph nodes in points-to analysis of each fn,
+//! 50000 by default, 0 for unlimited. The fns exceeding the budgets are skipped and listed in the output.
+//! `--jobs N`, the number of threads solving the points-to constraints, 1 by default.
+//! `--max-reports N`, the maximal number of reports of each crate, 0 (unlimited) by default.
+//! The most actionable reports are kept, and the numbers of dropped reports are recorded in the summary.
+//! `--min-confidence N`, drops the deadlock reports whose confidence (0 to 100) is lower than N, 0 by default,
+//! see `DeadlockEvidence` in `detector/lock/mod.rs`.
+//! `--dropped-reports-dir {dir}`, where the dropped reports of each crate are written to `{crate_name}.dropped.json`.
+//! `--report-path {path}`, appends the reports of each crate to the file rather than printing them, see `report_file.rs`.
+//! `--baseline {path}`, suppresses the reports whose fingerprints are in the baseline file, see `baseline.rs`.
+//! `--write-baseline {path}`, adds the fingerprints of the reports of each crate to the baseline file.
+//! `--group-by lock`, groups the reports by the alias classes of their primary locks, see `detector/lock/inventory.rs`.
+//! `--emit-lock-graph {dir}`, where the lock order graph of each crate is written to `{crate_name}.lock-graph.dot`
+//! and `{crate_name}.lock-graph.json`, see `detector/lock/lock_graph.rs`.
+//! `--message-format {json|diagnostic}`, prints the reports as JSON or as rustc diagnostics, see `diagnostic.rs`.
+//! By default, diagnostics if stderr is a terminal, otherwise JSON.
+//! `--deny {kind|possibility}`, denies the reports of the kind (a key of `levels`, e.g., `double_lock` or `deadlock`)
+//! or at least of the possibility (`probably` or `possibly`) (repeatable or comma-separated), see `DenyFilter`.
+//! `--no-int-ptr-casts`, does not track the pointers through the casts between integers and pointers (and transmutes),
+//! which are copies in the points-to analysis by default, see `Andersen::set_int_ptr_casts`.
+//! `--context-sensitive-alias`, evaluates the aliases of the locks only reaching the params per callsite,
+//! rather than merging all the callers, at the cost of the points-to analysis of the callers,
+//! see `AliasAnalysis::set_context_sensitive`.
+//! `--paranoid`, reports the deadlocks, condvar deadlocks, and atomicity violations with unknown alias results
+//! (e.g., of the fns skipped by the budgets) as possibly, marked by `alias_confidence: unknown`.
+//! `--config {path}`, the JSON config file, e.g., declaring custom thread pools, see `config.rs`.
+//! `--guard-newtype path=Family`, declares a guard newtype acting as the lockguard of `Family` (repeatable),
+//! where `Family` is the name of a lockguard pattern in `LockRegistry`,
+//! e.g., `--guard-newtype facade::sync::RawLocked=ParkingLotMutex`.
+//! `--with-snippets`, embeds the source lines of the spans into the deadlock, condvar deadlock,
+//! and atomicity violation diagnoses, see `snippet.rs`.
+//! `--hot-path-fn path`, designates an entry point (e.g., a request handler) whose locks are reported by `hot_path_lock`
+//! (repeatable), matching the fns of the path or ending with `::path`, see `detector/lock/hot_path.rs`.
+//! `--panic-include-tests`, also reports the panic sites in `#[test]` fns and `#[cfg(test)]` modules by `panic_site`.
+//! `--panic-kinds [kind1,kind2]`, only reports the panic sites of the kinds by `panic_site`, all by default,
+//! e.g., `--panic-kinds=unwrap,expect`, see `PANIC_KINDS` in `detector/panic/mod.rs`.
+//! `--focus {path}`, only analyzes the fns whose def paths contain the path and the fns they call, for debugging,
+//! while the callgraph is still built for the whole crate, see `CallGraph::set_focus`.
+//! `--dump-mir`, prints the MIR of the fns matching `--focus` with the live lockguards before each location to stderr.
+//! `--dump-pointsto {path}`, writes the constraint graph in DOT and the points-to info in JSON of the fns
+//! whose def paths contain the path to `target/lockbud/debug`, see `analysis/pointsto/dump.rs`.
 use clap::{Arg, Command};
 use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::analysis::callgraph::CallChainLimits;
+use crate::baseline;
+use crate::config::{Config, LEVEL_KEYS};
+use crate::detector::config::DetectorConfig;
+use crate::detector::panic::PANIC_KINDS;
+use crate::detector::report::{Baseline, Report};
+use crate::interest::concurrency::lock_registry::LockRegistry;
 
 #[derive(Debug)]
 pub enum CrateNameList {
@@ -29,6 +95,62 @@ pub enum DetectorKind {
     // More to be supported.
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageFormat {
+    Json,
+    Diagnostic,
+}
+
+/// The reports denied by `--deny`, in addition to the deny-level ones in the config file.
+/// A report is denied if it is of any of the kinds (or `kinds` is empty)
+/// and at least of the `possibility` (or `possibility` is None).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DenyFilter {
+    /// The keys of `levels`.
+    pub kinds: Vec<String>,
+    /// `Probably` or `Possibly`.
+    pub possibility: Option<String>,
+}
+
+impl DenyFilter {
+    /// Parse a `--deny` value, where the underscores in the kinds may be omitted, e.g., `doublelock`.
+    fn add(&mut self, value: &str) -> Result<(), String> {
+        let normalized = value.to_lowercase().replace('_', "");
+        match normalized.as_str() {
+            "probably" if self.possibility.is_none() => {
+                self.possibility = Some("Probably".to_owned());
+            }
+            "probably" => {}
+            "possibly" => self.possibility = Some("Possibly".to_owned()),
+            _ => {
+                let kind = LEVEL_KEYS
+                    .iter()
+                    .find(|key| key.replace('_', "") == normalized)
+                    .ok_or_else(|| format!("InvalidDeny: {}", value))?;
+                self.kinds.push(kind.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty() && self.possibility.is_none()
+    }
+
+    pub fn matches(&self, report: &Report) -> bool {
+        !self.is_empty()
+            && (self.kinds.is_empty()
+                || report
+                    .level_keys()
+                    .iter()
+                    .any(|key| self.kinds.iter().any(|kind| kind == key)))
+            && match self.possibility.as_deref() {
+                Some("Probably") => report.possibility() == "Probably",
+                _ => true,
+            }
+    }
+}
+
 fn make_options_parser<'help>() -> Command<'help> {
     let parser = Command::new("LOCKBUD")
         .no_binary_name(true)
@@ -41,6 +163,23 @@ fn make_options_parser<'help>() -> Command<'help> {
                 .default_values(&["deadlock"])
                 .help("The detector kind"),
         )
+        .arg(
+            Arg::new("detectors")
+                .long("detectors")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .allow_hyphen_values(true)
+                .help("enable the detectors, or disable the ones prefixed by -, e.g., doublelock,-conflictlock"),
+        )
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("set a parameter of a detector, e.g., deadlock.max_callchain_depth=5"),
+        )
         .arg(
             Arg::new("black")
                 .short('b')
@@ -54,6 +193,221 @@ fn make_options_parser<'help>() -> Command<'help> {
                 .long("crate-name-list")
                 .takes_value(true)
                 .help("The crate names seperated by ,"),
+        )
+        .arg(
+            Arg::new("all_crates")
+                .long("all-crates")
+                .takes_value(false)
+                .help("also analyze the dependencies when run by cargo, not only the primary packages"),
+        )
+        .arg(
+            Arg::new("no_thread_filter")
+                .long("no-thread-filter")
+                .takes_value(false)
+                .help("report conflictlock even if the locks are only acquired in the same thread"),
+        )
+        .arg(
+            Arg::new("alias_stats")
+                .long("alias-stats")
+                .takes_value(false)
+                .help("print statistics on alias queries and record them in the report"),
+        )
+        .arg(
+            Arg::new("analyze_deps_guards")
+                .long("analyze-deps-guards")
+                .takes_value(false)
+                .help("also collect lockguards in the fns from dependencies with MIR available"),
+        )
+        .arg(
+            Arg::new("analyze_proc_macros")
+                .long("analyze-proc-macros")
+                .takes_value(false)
+                .help("also analyze the proc-macro crates"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .takes_value(false)
+                .help("do not print progress to stderr"),
+        )
+        .arg(
+            Arg::new("max_callchains")
+                .long("max-callchains")
+                .takes_value(true)
+                .default_value("8")
+                .help("the maximal number of callchains in each deadlock diagnosis"),
+        )
+        .arg(
+            Arg::new("max_callchain_depth")
+                .long("max-callchain-depth")
+                .takes_value(true)
+                .default_value("20")
+                .help("the maximal number of calls in each callchain"),
+        )
+        .arg(
+            Arg::new("fn_time_budget")
+                .long("fn-time-budget")
+                .takes_value(true)
+                .default_value("10")
+                .help("the time budget in seconds of analyzing each fn, 0 for unlimited"),
+        )
+        .arg(
+            Arg::new("fn_node_budget")
+                .long("fn-node-budget")
+                .takes_value(true)
+                .default_value("50000")
+                .help("the maximal number of constraint graph nodes of each fn, 0 for unlimited"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .takes_value(true)
+                .default_value("1")
+                .help("the number of threads solving the points-to constraints"),
+        )
+        .arg(
+            Arg::new("max_reports")
+                .long("max-reports")
+                .takes_value(true)
+                .default_value("0")
+                .help("the maximal number of reports of each crate, 0 for unlimited"),
+        )
+        .arg(
+            Arg::new("min_confidence")
+                .long("min-confidence")
+                .takes_value(true)
+                .default_value("0")
+                .help("the minimal confidence (0 to 100) of the deadlock reports"),
+        )
+        .arg(
+            Arg::new("dropped_reports_dir")
+                .long("dropped-reports-dir")
+                .takes_value(true)
+                .help("the dir to write the reports dropped by --max-reports"),
+        )
+        .arg(
+            Arg::new("report_path")
+                .long("report-path")
+                .takes_value(true)
+                .help("append the reports of each crate to the file as JSON lines"),
+        )
+        .arg(
+            Arg::new("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .help("suppress the reports whose fingerprints are in the JSON baseline file"),
+        )
+        .arg(
+            Arg::new("write_baseline")
+                .long("write-baseline")
+                .takes_value(true)
+                .help("add the fingerprints of the reports to the JSON baseline file"),
+        )
+        .arg(
+            Arg::new("group_by")
+                .long("group-by")
+                .takes_value(true)
+                .possible_values(["lock"])
+                .help("group the reports by the alias classes of their primary locks"),
+        )
+        .arg(
+            Arg::new("emit_lock_graph")
+                .long("emit-lock-graph")
+                .takes_value(true)
+                .help("the dir to write the lock order graph of each crate in DOT and JSON"),
+        )
+        .arg(
+            Arg::new("message_format")
+                .long("message-format")
+                .takes_value(true)
+                .possible_values(["json", "diagnostic"])
+                .help("print the reports as JSON or as rustc diagnostics, diagnostics on a terminal by default"),
+        )
+        .arg(
+            Arg::new("deny")
+                .long("deny")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("deny the reports of the kind (e.g., double_lock) or at least of the possibility (probably or possibly)"),
+        )
+        .arg(
+            Arg::new("no_int_ptr_casts")
+                .long("no-int-ptr-casts")
+                .takes_value(false)
+                .help("do not track the pointers through the casts between integers and pointers"),
+        )
+        .arg(
+            Arg::new("context_sensitive_alias")
+                .long("context-sensitive-alias")
+                .takes_value(false)
+                .help("evaluate the aliases of the locks only reaching the params per callsite"),
+        )
+        .arg(
+            Arg::new("paranoid")
+                .long("paranoid")
+                .takes_value(false)
+                .help("report the bugs with unknown alias results as possibly, for audits"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .takes_value(true)
+                .help("the JSON config file"),
+        )
+        .arg(
+            Arg::new("guard_newtype")
+                .long("guard-newtype")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("declare a guard newtype as path=Family, e.g., facade::sync::Locked=ParkingLotMutex"),
+        )
+        .arg(
+            Arg::new("with_snippets")
+                .long("with-snippets")
+                .takes_value(false)
+                .help("embed the source lines of the spans into the diagnoses"),
+        )
+        .arg(
+            Arg::new("hot_path_fn")
+                .long("hot-path-fn")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("report the locks acquired on the paths through the entry point, e.g., server::handle_request"),
+        )
+        .arg(
+            Arg::new("panic_include_tests")
+                .long("panic-include-tests")
+                .takes_value(false)
+                .help("also report the panic sites in the test code"),
+        )
+        .arg(
+            Arg::new("panic_kinds")
+                .long("panic-kinds")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("only report the panic sites of the kinds, e.g., unwrap,expect"),
+        )
+        .arg(
+            Arg::new("focus")
+                .long("focus")
+                .takes_value(true)
+                .help("only analyze the fns whose def paths contain the path and the fns they call, e.g., server::handle"),
+        )
+        .arg(
+            Arg::new("dump_mir")
+                .long("dump-mir")
+                .takes_value(false)
+                .requires("focus")
+                .help("print the MIR of the focused fns with the live lockguards to stderr"),
+        )
+        .arg(
+            Arg::new("dump_pointsto")
+                .long("dump-pointsto")
+                .takes_value(true)
+                .help("write the constraint graph and the points-to info of the fns whose def paths contain the path to target/lockbud/debug"),
         );
     parser
 }
@@ -61,14 +415,93 @@ fn make_options_parser<'help>() -> Command<'help> {
 #[derive(Debug)]
 pub struct Options {
     pub detector_kind: DetectorKind,
+    /// The detectors enabled by `-k` and `--detectors`, and their parameters.
+    pub detectors: DetectorConfig,
     pub crate_name_list: CrateNameList,
+    /// Also analyze the non-primary packages (the dependencies) when run by cargo.
+    pub all_crates: bool,
+    pub alias_stats: bool,
+    pub analyze_deps_guards: bool,
+    /// Analyze the proc-macro crates rather than skip them.
+    pub analyze_proc_macros: bool,
+    pub quiet: bool,
+    /// In seconds.
+    pub fn_time_budget: u64,
+    pub fn_node_budget: usize,
+    pub jobs: usize,
+    /// 0 for unlimited.
+    pub max_reports: usize,
+    /// The reports without a confidence are kept.
+    pub min_confidence: u8,
+    pub dropped_reports_dir: Option<PathBuf>,
+    pub report_path: Option<PathBuf>,
+    /// The fingerprints of the accepted reports.
+    pub baseline: Baseline,
+    pub write_baseline: Option<PathBuf>,
+    pub group_by_lock: bool,
+    /// The dir of the lock order graphs.
+    pub emit_lock_graph: Option<PathBuf>,
+    /// None for diagnostics if stderr is a terminal, otherwise JSON.
+    pub message_format: Option<MessageFormat>,
+    pub deny: DenyFilter,
+    /// Track the pointers through the casts between integers and pointers, unless `--no-int-ptr-casts`.
+    pub int_ptr_casts: bool,
+    /// Evaluate the aliases through the params per callsite, see `AliasAnalysis::set_context_sensitive`.
+    pub context_sensitive_alias: bool,
+    pub paranoid: bool,
+    /// (newtype path, lock family)
+    pub guard_newtypes: Vec<(String, String)>,
+    /// The paths of the entry points of `hot_path_lock`.
+    pub hot_path_fns: Vec<String>,
+    /// Report the panic sites in the test code.
+    pub panic_include_tests: bool,
+    /// The kinds of the reported panic sites in `PANIC_KINDS`, all if empty.
+    pub panic_kinds: Vec<String>,
+    pub with_snippets: bool,
+    /// The def path pattern of the fns analyzed, see `CallGraph::set_focus`.
+    pub focus: Option<String>,
+    pub dump_mir: bool,
+    /// The def path pattern of the fns whose points-to info is dumped, see `AliasAnalysis::set_dump_pointsto`.
+    pub dump_pointsto: Option<String>,
+    pub config: Config,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Options {
             detector_kind: DetectorKind::Deadlock,
+            detectors: DetectorConfig::default(),
             crate_name_list: CrateNameList::Black(Vec::new()),
+            all_crates: false,
+            alias_stats: false,
+            analyze_deps_guards: false,
+            analyze_proc_macros: false,
+            quiet: false,
+            fn_time_budget: 10,
+            fn_node_budget: 50000,
+            jobs: 1,
+            max_reports: 0,
+            min_confidence: 0,
+            dropped_reports_dir: None,
+            report_path: None,
+            baseline: Baseline::default(),
+            write_baseline: None,
+            group_by_lock: false,
+            emit_lock_graph: None,
+            message_format: None,
+            deny: DenyFilter::default(),
+            int_ptr_casts: true,
+            context_sensitive_alias: false,
+            paranoid: false,
+            guard_newtypes: Vec::new(),
+            hot_path_fns: Vec::new(),
+            panic_include_tests: false,
+            panic_kinds: Vec::new(),
+            with_snippets: false,
+            focus: None,
+            dump_mir: false,
+            dump_pointsto: None,
+            config: Config::default(),
         }
     }
 }
@@ -90,6 +523,29 @@ impl Options {
             Some("panic") => DetectorKind::Panic,
             _ => return Err("UnsupportedDetectorKind")?,
         };
+        let mut detectors = DetectorConfig::new(match detector_kind {
+            DetectorKind::Deadlock => &["deadlock"],
+            DetectorKind::AtomicityViolation => &["atomicity_violation"],
+            DetectorKind::Memory => &["memory"],
+            DetectorKind::All => &["deadlock", "atomicity_violation", "memory", "panic"],
+            DetectorKind::Panic => &["panic"],
+        });
+        // `panic_site` reports every `unwrap` and `expect`, so `-k all` leaves it to `-k panic` and `--detectors`.
+        if matches!(detector_kind, DetectorKind::All) {
+            detectors.toggle("-panic_site")?;
+        }
+        for value in matches.values_of("detectors").into_iter().flatten() {
+            detectors.toggle(value)?;
+        }
+        detectors.thread_filter = !matches.is_present("no_thread_filter");
+        detectors.callchain_limits = CallChainLimits {
+            max_paths: matches.value_of_t("max_callchains")?,
+            max_depth: matches.value_of_t("max_callchain_depth")?,
+        };
+        // The parameters set by `--set` take precedence over the flags.
+        for param in matches.values_of("set").into_iter().flatten() {
+            detectors.set(param)?;
+        }
         let black = matches.is_present("black");
         let crate_name_list = matches
             .value_of("crates")
@@ -102,9 +558,118 @@ impl Options {
                 }
             })
             .unwrap_or_default();
+        let all_crates = matches.is_present("all_crates");
+        let alias_stats = matches.is_present("alias_stats");
+        let analyze_deps_guards = matches.is_present("analyze_deps_guards");
+        let analyze_proc_macros = matches.is_present("analyze_proc_macros");
+        let quiet = matches.is_present("quiet");
+        let fn_time_budget = matches.value_of_t("fn_time_budget")?;
+        let fn_node_budget = matches.value_of_t("fn_node_budget")?;
+        let jobs = matches.value_of_t("jobs")?;
+        if jobs == 0 {
+            return Err("InvalidJobs: 0")?;
+        }
+        let max_reports = matches.value_of_t("max_reports")?;
+        let min_confidence = matches.value_of_t("min_confidence")?;
+        if min_confidence > 100 {
+            return Err(format!("InvalidMinConfidence: {}", min_confidence))?;
+        }
+        let dropped_reports_dir = matches.value_of("dropped_reports_dir").map(PathBuf::from);
+        let report_path = matches.value_of("report_path").map(PathBuf::from);
+        let baseline = match matches.value_of("baseline") {
+            Some(path) => baseline::read_baseline(Path::new(path))?,
+            None => Baseline::default(),
+        };
+        let write_baseline = matches.value_of("write_baseline").map(PathBuf::from);
+        let group_by_lock = matches.value_of("group_by") == Some("lock");
+        let emit_lock_graph = matches.value_of("emit_lock_graph").map(PathBuf::from);
+        let message_format = match matches.value_of("message_format") {
+            Some("json") => Some(MessageFormat::Json),
+            Some("diagnostic") => Some(MessageFormat::Diagnostic),
+            _ => None,
+        };
+        let mut deny = DenyFilter::default();
+        for value in matches.values_of("deny").into_iter().flatten() {
+            deny.add(value)?;
+        }
+        let int_ptr_casts = !matches.is_present("no_int_ptr_casts");
+        let context_sensitive_alias = matches.is_present("context_sensitive_alias");
+        let paranoid = matches.is_present("paranoid");
+        let config = match matches.value_of("config") {
+            Some(path) => Config::from_file(Path::new(path))?,
+            None => Config::default(),
+        };
+        // The lock families a guard newtype can act as, including the ones in the config file.
+        let registry = LockRegistry::new(config.locks.clone());
+        let guard_newtypes = matches
+            .values_of("guard_newtype")
+            .into_iter()
+            .flatten()
+            .map(|decl| match decl.split_once('=') {
+                Some((path, family)) if registry.family(family).is_some() => {
+                    Ok((path.to_owned(), family.to_owned()))
+                }
+                _ => Err(format!("InvalidGuardNewtype: {}", decl)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let hot_path_fns = matches
+            .values_of("hot_path_fn")
+            .into_iter()
+            .flatten()
+            .map(str::to_owned)
+            .collect();
+        let panic_include_tests = matches.is_present("panic_include_tests");
+        let panic_kinds = matches
+            .values_of("panic_kinds")
+            .into_iter()
+            .flatten()
+            .map(|kind| {
+                let normalized = kind.to_lowercase();
+                if PANIC_KINDS.contains(&normalized.as_str()) {
+                    Ok(normalized)
+                } else {
+                    Err(format!("InvalidPanicKind: {}", kind))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let with_snippets = matches.is_present("with_snippets");
+        let focus = matches.value_of("focus").map(str::to_owned);
+        let dump_mir = matches.is_present("dump_mir");
+        let dump_pointsto = matches.value_of("dump_pointsto").map(str::to_owned);
         Ok(Options {
             detector_kind,
+            detectors,
             crate_name_list,
+            all_crates,
+            alias_stats,
+            analyze_deps_guards,
+            analyze_proc_macros,
+            quiet,
+            fn_time_budget,
+            fn_node_budget,
+            jobs,
+            max_reports,
+            min_confidence,
+            dropped_reports_dir,
+            report_path,
+            baseline,
+            write_baseline,
+            group_by_lock,
+            emit_lock_graph,
+            message_format,
+            deny,
+            int_ptr_casts,
+            context_sensitive_alias,
+            paranoid,
+            guard_newtypes,
+            hot_path_fns,
+            panic_include_tests,
+            panic_kinds,
+            with_snippets,
+            focus,
+            dump_mir,
+            dump_pointsto,
+            config,
         })
     }
 }
@@ -168,6 +733,372 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_from_str_no_thread_filter() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(options.detectors.thread_filter);
+        let options = Options::parse_from_str("-k deadlock --no-thread-filter").unwrap();
+        assert!(!options.detectors.thread_filter);
+    }
+
+    #[test]
+    fn test_parse_from_str_alias_stats() {
+        let options = Options::parse_from_str("-k all").unwrap();
+        assert!(!options.alias_stats);
+        let options = Options::parse_from_str("-k all --alias-stats").unwrap();
+        assert!(options.alias_stats);
+    }
+
+    #[test]
+    fn test_parse_from_str_analyze_deps_guards() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(!options.analyze_deps_guards);
+        let options = Options::parse_from_str("-k deadlock --analyze-deps-guards").unwrap();
+        assert!(options.analyze_deps_guards);
+    }
+
+    #[test]
+    fn test_parse_from_str_analyze_proc_macros() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(!options.analyze_proc_macros);
+        let options = Options::parse_from_str("-k deadlock --analyze-proc-macros").unwrap();
+        assert!(options.analyze_proc_macros);
+    }
+
+    #[test]
+    fn test_parse_from_str_quiet() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(!options.quiet);
+        let options = Options::parse_from_str("-k deadlock -q").unwrap();
+        assert!(options.quiet);
+        let options = Options::parse_from_str("-k deadlock --quiet").unwrap();
+        assert!(options.quiet);
+    }
+
+    #[test]
+    fn test_parse_from_str_callchain_limits() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.detectors.callchain_limits.max_paths, 8);
+        assert_eq!(options.detectors.callchain_limits.max_depth, 20);
+        let options =
+            Options::parse_from_str("-k deadlock --max-callchains=2 --max-callchain-depth 5")
+                .unwrap();
+        assert_eq!(options.detectors.callchain_limits.max_paths, 2);
+        assert_eq!(options.detectors.callchain_limits.max_depth, 5);
+        let options = Options::parse_from_str("-k deadlock --max-callchains=many");
+        assert!(options.is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_detectors() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.detectors, DetectorConfig::default());
+        let options = Options::parse_from_str("-k all").unwrap();
+        assert!(options.detectors.is_enabled("use_after_free"));
+        assert!(options.detectors.is_enabled("panic_in_drop"));
+        assert!(!options.detectors.is_enabled("panic_site"));
+        let options = Options::parse_from_str("-k all --detectors panic_site").unwrap();
+        assert!(options.detectors.is_enabled("panic_site"));
+        let options = Options::parse_from_str("-k panic").unwrap();
+        assert!(!options.detectors.is_group_enabled("deadlock"));
+        assert!(options.detectors.is_enabled("panic_in_drop"));
+        assert!(options.detectors.is_enabled("panic_holding_lock"));
+        assert!(options.detectors.is_enabled("panic_site"));
+        let options = Options::parse_from_str(
+            "-k deadlock --detectors=doublelock,condvar,-conflictlock --detectors atomicity_violation",
+        )
+        .unwrap();
+        assert!(options.detectors.is_enabled("double_lock"));
+        assert!(!options.detectors.is_enabled("conflict_lock"));
+        assert!(options.detectors.is_enabled("atomicity_violation"));
+        let options = Options::parse_from_str("-k deadlock --detectors -deadlock").unwrap();
+        assert!(!options.detectors.is_group_enabled("deadlock"));
+        let err =
+            Options::parse_from_str("-k deadlock --detectors=doublelock,livelock").unwrap_err();
+        assert!(err.to_string().starts_with("UnknownDetector: livelock"));
+    }
+
+    #[test]
+    fn test_parse_from_str_set() {
+        let options = Options::parse_from_str(
+            "-k deadlock --max-callchain-depth 5 --set deadlock.max_callchain_depth=3 --set conflictlock.thread_filter=false,deadlock.max_callchains=1",
+        )
+        .unwrap();
+        assert_eq!(options.detectors.callchain_limits.max_depth, 3);
+        assert_eq!(options.detectors.callchain_limits.max_paths, 1);
+        assert!(!options.detectors.thread_filter);
+        let options = Options::parse_from_str("-k deadlock --set livelock.max_callchains=1");
+        assert!(options.is_err());
+        let options = Options::parse_from_str("-k deadlock --set deadlock.max_depth=1");
+        assert!(options.is_err());
+        let options = Options::parse_from_str("-k deadlock --set deadlock.max_callchains");
+        assert!(options.is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_fn_budgets() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.fn_time_budget, 10);
+        assert_eq!(options.fn_node_budget, 50000);
+        let options =
+            Options::parse_from_str("-k deadlock --fn-time-budget 0 --fn-node-budget=1000")
+                .unwrap();
+        assert_eq!(options.fn_time_budget, 0);
+        assert_eq!(options.fn_node_budget, 1000);
+        let options = Options::parse_from_str("-k deadlock --fn-time-budget 1.5");
+        assert!(options.is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_jobs() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.jobs, 1);
+        let options = Options::parse_from_str("-k deadlock --jobs 4").unwrap();
+        assert_eq!(options.jobs, 4);
+        assert!(Options::parse_from_str("-k deadlock --jobs=0").is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_max_reports() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.max_reports, 0);
+        assert_eq!(options.dropped_reports_dir, None);
+        let options = Options::parse_from_str(
+            "-k all --max-reports 100 --dropped-reports-dir lockbud-dropped",
+        )
+        .unwrap();
+        assert_eq!(options.max_reports, 100);
+        assert_eq!(
+            options.dropped_reports_dir,
+            Some(PathBuf::from("lockbud-dropped"))
+        );
+        assert!(Options::parse_from_str("-k deadlock --max-reports=-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_min_confidence() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.min_confidence, 0);
+        let options = Options::parse_from_str("-k deadlock --min-confidence=60").unwrap();
+        assert_eq!(options.min_confidence, 60);
+        assert!(Options::parse_from_str("-k deadlock --min-confidence 101").is_err());
+        assert!(Options::parse_from_str("-k deadlock --min-confidence=high").is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_report_path() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.report_path, None);
+        let options =
+            Options::parse_from_str("-k deadlock --report-path=/tmp/lockbud.jsonl").unwrap();
+        assert_eq!(
+            options.report_path,
+            Some(PathBuf::from("/tmp/lockbud.jsonl"))
+        );
+    }
+
+    #[test]
+    fn test_parse_from_str_baseline() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(options.baseline.fingerprints.is_empty());
+        assert_eq!(options.write_baseline, None);
+        let path = std::env::temp_dir().join("lockbud_test_parse_from_str_baseline.json");
+        std::fs::write(&path, r#"["0123456789abcdef"]"#).unwrap();
+        let options = Options::parse_from_str(&format!(
+            "-k deadlock --baseline {} --write-baseline lockbud-baseline.json",
+            path.display()
+        ))
+        .unwrap();
+        assert!(options.baseline.fingerprints.contains("0123456789abcdef"));
+        assert_eq!(
+            options.write_baseline,
+            Some(PathBuf::from("lockbud-baseline.json"))
+        );
+        std::fs::remove_file(&path).unwrap();
+        let options =
+            Options::parse_from_str(&format!("-k deadlock --baseline {}", path.display()));
+        assert!(options.is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_group_by() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(!options.group_by_lock);
+        let options = Options::parse_from_str("-k deadlock --group-by=lock").unwrap();
+        assert!(options.group_by_lock);
+        assert!(Options::parse_from_str("-k deadlock --group-by=crate").is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_emit_lock_graph() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.emit_lock_graph, None);
+        let options =
+            Options::parse_from_str("-k deadlock --emit-lock-graph=target/locks").unwrap();
+        assert_eq!(options.emit_lock_graph, Some(PathBuf::from("target/locks")));
+    }
+
+    #[test]
+    fn test_parse_from_str_message_format() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert_eq!(options.message_format, None);
+        let options = Options::parse_from_str("-k deadlock --message-format json").unwrap();
+        assert_eq!(options.message_format, Some(MessageFormat::Json));
+        let options = Options::parse_from_str("-k deadlock --message-format=diagnostic").unwrap();
+        assert_eq!(options.message_format, Some(MessageFormat::Diagnostic));
+        assert!(Options::parse_from_str("-k deadlock --message-format=short").is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_deny() {
+        let options = Options::parse_from_str("-k all").unwrap();
+        assert!(options.deny.is_empty());
+        let options = Options::parse_from_str("-k all --deny=doublelock --deny Memory").unwrap();
+        assert_eq!(
+            options.deny,
+            DenyFilter {
+                kinds: vec!["double_lock".to_owned(), "memory".to_owned()],
+                possibility: None,
+            }
+        );
+        let options = Options::parse_from_str("-k all --deny=possibly,probably").unwrap();
+        assert_eq!(options.deny.possibility.as_deref(), Some("Possibly"));
+        let options = Options::parse_from_str("-k all --deny=deadlock,probably").unwrap();
+        assert_eq!(options.deny.kinds, vec!["deadlock".to_owned()]);
+        assert_eq!(options.deny.possibility.as_deref(), Some("Probably"));
+        assert!(Options::parse_from_str("-k all --deny=racy").is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_all_crates() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(!options.all_crates);
+        let options = Options::parse_from_str("-k deadlock --all-crates -l cc").unwrap();
+        assert!(options.all_crates);
+        assert!(
+            matches!(options.crate_name_list, CrateNameList::White(v) if v == vec!["cc".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_parse_from_str_paranoid() {
+        let options = Options::parse_from_str("-k all").unwrap();
+        assert!(!options.paranoid);
+        let options = Options::parse_from_str("-k all --paranoid").unwrap();
+        assert!(options.paranoid);
+    }
+
+    #[test]
+    fn test_parse_from_str_no_int_ptr_casts() {
+        let options = Options::parse_from_str("-k memory").unwrap();
+        assert!(options.int_ptr_casts);
+        let options = Options::parse_from_str("-k memory --no-int-ptr-casts").unwrap();
+        assert!(!options.int_ptr_casts);
+    }
+
+    #[test]
+    fn test_parse_from_str_context_sensitive_alias() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(!options.context_sensitive_alias);
+        let options = Options::parse_from_str("-k deadlock --context-sensitive-alias").unwrap();
+        assert!(options.context_sensitive_alias);
+    }
+
+    #[test]
+    fn test_parse_from_str_hot_path_fns() {
+        let options = Options::parse_from_str(
+            "-k deadlock --hot-path-fn server::handle --hot-path-fn Service::call",
+        )
+        .unwrap();
+        assert_eq!(options.hot_path_fns, ["server::handle", "Service::call"]);
+    }
+
+    #[test]
+    fn test_parse_from_str_focus() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(options.focus.is_none() && !options.dump_mir);
+        let options =
+            Options::parse_from_str("-k deadlock --focus server::handle --dump-mir").unwrap();
+        assert_eq!(options.focus.as_deref(), Some("server::handle"));
+        assert!(options.dump_mir);
+        // `--dump-mir` dumps the focused fns only.
+        assert!(Options::parse_from_str("-k deadlock --dump-mir").is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_dump_pointsto() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(options.dump_pointsto.is_none());
+        let options =
+            Options::parse_from_str("-k deadlock --dump-pointsto server::handle").unwrap();
+        assert_eq!(options.dump_pointsto.as_deref(), Some("server::handle"));
+    }
+
+    #[test]
+    fn test_parse_from_str_panic_filters() {
+        let options = Options::parse_from_str("-k panic").unwrap();
+        assert!(!options.panic_include_tests);
+        assert!(options.panic_kinds.is_empty());
+        let options = Options::parse_from_str(
+            "-k panic --panic-include-tests --panic-kinds=unwrap,Expect --panic-kinds todo",
+        )
+        .unwrap();
+        assert!(options.panic_include_tests);
+        assert_eq!(options.panic_kinds, ["unwrap", "expect", "todo"]);
+        assert_eq!(
+            Options::parse_from_str("-k panic --panic-kinds=unwrap,bounds")
+                .unwrap_err()
+                .to_string(),
+            "InvalidPanicKind: bounds"
+        );
+    }
+
+    #[test]
+    fn test_parse_from_str_guard_newtypes() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(options.guard_newtypes.is_empty());
+        let options = Options::parse_from_str(
+            "-k deadlock --guard-newtype facade::sync::Locked=ParkingLotMutex --guard-newtype facade::sync::ReadLocked=StdRwLockRead",
+        )
+        .unwrap();
+        assert_eq!(
+            options.guard_newtypes,
+            vec![
+                (
+                    "facade::sync::Locked".to_owned(),
+                    "ParkingLotMutex".to_owned()
+                ),
+                (
+                    "facade::sync::ReadLocked".to_owned(),
+                    "StdRwLockRead".to_owned()
+                ),
+            ]
+        );
+        let options = Options::parse_from_str("-k deadlock --guard-newtype facade::sync::Locked");
+        assert!(options.is_err());
+        let options =
+            Options::parse_from_str("-k deadlock --guard-newtype facade::sync::Locked=Mutex");
+        assert!(options.is_err());
+    }
+
+    #[test]
+    fn test_parse_from_str_config() {
+        let options = Options::parse_from_str("-k deadlock").unwrap();
+        assert!(options.config.pools.is_empty());
+        let path = std::env::temp_dir().join("lockbud_test_parse_from_str_config.json");
+        std::fs::write(
+            &path,
+            r#"{"pools": [{"name": "my_pool::ThreadPool", "schedules_into_same_pool": true}]}"#,
+        )
+        .unwrap();
+        let options =
+            Options::parse_from_str(&format!("-k deadlock --config {}", path.display())).unwrap();
+        assert_eq!(options.config.pools.len(), 1);
+        std::fs::remove_file(&path).unwrap();
+        let options = Options::parse_from_str(&format!("-k deadlock --config {}", path.display()));
+        assert!(options.is_err());
+    }
+
     #[test]
     fn test_parse_from_args_err() {
         let options = Options::parse_from_args(&[
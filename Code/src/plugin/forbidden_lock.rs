@@ -0,0 +1,126 @@
+//! An example plugin reporting the locks forbidden in some modules, e.g.,
+//! never take the config lock from request handlers, including the fns called by the handlers:
+//! ```json
+//! { "plugins": { "forbidden_lock": [{ "lock": "app::CONFIG", "from": "app::handlers" }] } }
+//! ```
+//! `lock` is the name of the lock declaration as in `--group-by lock`, i.e.,
+//! a static (`app::CONFIG`) or a struct field (`app::State.config`),
+//! and `from` is the module (or fn) path, where the crate name of the crate being detected may be omitted.
+//! Each acquisition of the lock reachable from a fn in `from` is reported once with the callchains from the fn.
+extern crate rustc_hir;
+
+use petgraph::algo;
+use petgraph::visit::IntoNodeReferences;
+use rustc_hir::def_id::LOCAL_CRATE;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use super::{lock_declaration, InstanceId, Plugin, PluginContext, PluginDiagnosis, ReportContent};
+
+pub const NAME: &str = "forbidden_lock";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForbiddenLockRule {
+    pub lock: String,
+    pub from: String,
+}
+
+pub struct ForbiddenLock {
+    rules: Vec<ForbiddenLockRule>,
+}
+
+impl ForbiddenLock {
+    pub fn from_settings(settings: &Value) -> Result<Self, String> {
+        let rules = Vec::<ForbiddenLockRule>::deserialize(settings)
+            .map_err(|err| format!("invalid settings of plugin `{}`: {}", NAME, err))?;
+        Ok(Self { rules })
+    }
+}
+
+impl Plugin for ForbiddenLock {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn check(&self, cx: &mut PluginContext<'_, '_, '_>) {
+        let crate_name = cx.tcx.crate_name(LOCAL_CRATE);
+        // The fns in the module (or the fn itself) of each rule.
+        let sources = self
+            .rules
+            .iter()
+            .map(|rule| {
+                cx.callgraph
+                    .graph
+                    .node_references()
+                    .filter(|(_, node)| {
+                        let def_id = node.instance().def_id();
+                        let path = cx.tcx.def_path_str(def_id);
+                        let path = if def_id.is_local() {
+                            format!("{}::{}", crate_name, path)
+                        } else {
+                            path
+                        };
+                        [rule.from.clone(), format!("{}::{}", crate_name, rule.from)]
+                            .iter()
+                            .any(|from| path == *from || path.starts_with(&format!("{}::", from)))
+                    })
+                    .map(|(instance_id, _)| instance_id)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut lockguards = cx
+            .lockguards
+            .iter()
+            .filter(|(_, info)| !info.is_gen_only_by_move())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        lockguards.sort_by_key(|id| (id.instance_id, id.local));
+        let mut reported = BTreeSet::new();
+        for id in lockguards {
+            let name = match lock_declaration(id, cx.callgraph, cx.tcx) {
+                Some((name, _)) => name,
+                None => continue,
+            };
+            for (rule, sources) in self.rules.iter().zip(&sources) {
+                if rule.lock != name && rule.lock != format!("{}::{}", crate_name, name) {
+                    continue;
+                }
+                let source = sources.iter().copied().find(|source| {
+                    *source == id.instance_id
+                        || algo::has_path_connecting(
+                            &cx.callgraph.graph,
+                            *source,
+                            id.instance_id,
+                            None,
+                        )
+                });
+                let source: InstanceId = match source {
+                    Some(source) => source,
+                    None => continue,
+                };
+                let span = format!("{:?}", cx.lockguards[&id].span);
+                if !reported.insert((rule.lock.clone(), rule.from.clone(), span.clone())) {
+                    continue;
+                }
+                let callchains = if source == id.instance_id {
+                    Vec::new()
+                } else {
+                    cx.callchains(source, id.instance_id)
+                };
+                cx.report(ReportContent::new(
+                    "ForbiddenLock".to_owned(),
+                    "Probably".to_owned(),
+                    PluginDiagnosis {
+                        plugin: NAME.to_owned(),
+                        subject: rule.lock.clone(),
+                        span,
+                        callchains,
+                    },
+                    format!("`{}` must not be locked from `{}`", rule.lock, rule.from),
+                ));
+            }
+        }
+    }
+}
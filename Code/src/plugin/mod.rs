@@ -0,0 +1,111 @@
+//! The hook for custom checks against the facts computed by lockbud,
+//! e.g., organization-specific rules like "never take the config lock from request handlers",
+//! without re-running the MIR analyses.
+//! A plugin implements `Plugin` and is registered in `registered_plugins`, i.e., compiled into a fork of lockbud.
+//! The plugins configured by `plugins` in the config file (keyed by their names) run after the deadlock detector,
+//! with a `PluginContext` providing read access to the callgraph, the lockguards, the lock relations,
+//! and the alias analysis, and collecting the reports of the plugins.
+//! The reports are `Report::Plugin` of the kind `plugin` in `levels` and `--detectors`.
+//! See `forbidden_lock.rs` for an example.
+//!
+//! There is no stability guarantee across versions:
+//! the facade re-exports the internal types of lockbud, which may change at any time.
+extern crate rustc_hash;
+extern crate rustc_middle;
+
+pub mod forbidden_lock;
+
+use std::collections::BTreeMap;
+
+use rustc_hash::FxHashSet;
+use rustc_middle::ty::TyCtxt;
+use serde_json::Value;
+
+pub use crate::analysis::callgraph::{CallChainLimits, CallGraph, CallGraphNode, InstanceId};
+pub use crate::analysis::pointsto::{AliasAnalysis, ApproximateAliasKind};
+pub use crate::detector::lock::inventory::lock_declaration;
+pub use crate::detector::report::{Report, ReportContent};
+pub use crate::interest::concurrency::lock::{LockGuardId, LockGuardInfo, LockGuardMap};
+pub use lockbud_report_types::plugin::PluginDiagnosis;
+
+use crate::detector::lock::track_callchains;
+use forbidden_lock::ForbiddenLock;
+
+/// A custom check.
+pub trait Plugin {
+    /// The key in `plugins` of the config file, e.g., `forbidden_lock`.
+    fn name(&self) -> &'static str;
+
+    /// Check the facts in `cx` and report the bugs by `PluginContext::report`.
+    fn check(&self, cx: &mut PluginContext<'_, '_, '_>);
+}
+
+/// The plugins configured in `plugins` of the config file. A fork registers its plugins here.
+pub fn registered_plugins(
+    settings: &BTreeMap<String, Value>,
+) -> Result<Vec<Box<dyn Plugin>>, String> {
+    settings
+        .iter()
+        .map(|(name, settings)| -> Result<Box<dyn Plugin>, String> {
+            match name.as_str() {
+                forbidden_lock::NAME => Ok(Box::new(ForbiddenLock::from_settings(settings)?)),
+                _ => Err(format!("unknown plugin `{}` in plugins", name)),
+            }
+        })
+        .collect()
+}
+
+/// The facts computed by the deadlock detector,
+/// read-only except for the caches of the alias analysis, and the sink of the reports.
+pub struct PluginContext<'b, 'a, 'tcx> {
+    pub tcx: TyCtxt<'tcx>,
+    pub callgraph: &'a CallGraph<'tcx>,
+    /// The lockguards of all the instances analyzed.
+    pub lockguards: &'b LockGuardMap<'tcx>,
+    /// relation(a, b) denotes that a is live when b is acquired.
+    pub relations: &'b FxHashSet<(LockGuardId, LockGuardId)>,
+    pub alias_analysis: &'b mut AliasAnalysis<'a, 'tcx>,
+    /// The limits on `callchains`.
+    pub callchain_limits: CallChainLimits,
+    reports: Vec<Report>,
+}
+
+impl<'b, 'a, 'tcx> PluginContext<'b, 'a, 'tcx> {
+    pub fn new(
+        tcx: TyCtxt<'tcx>,
+        callgraph: &'a CallGraph<'tcx>,
+        lockguards: &'b LockGuardMap<'tcx>,
+        relations: &'b FxHashSet<(LockGuardId, LockGuardId)>,
+        alias_analysis: &'b mut AliasAnalysis<'a, 'tcx>,
+        callchain_limits: CallChainLimits,
+    ) -> Self {
+        Self {
+            tcx,
+            callgraph,
+            lockguards,
+            relations,
+            alias_analysis,
+            callchain_limits,
+            reports: Vec::new(),
+        }
+    }
+
+    /// The callchains from source to target within `callchain_limits`, in the format of the deadlock diagnoses.
+    pub fn callchains(&self, source: InstanceId, target: InstanceId) -> Vec<Vec<Vec<String>>> {
+        track_callchains(
+            source,
+            target,
+            self.callgraph,
+            self.callchain_limits,
+            self.tcx,
+        )
+    }
+
+    pub fn report(&mut self, content: ReportContent<PluginDiagnosis>) {
+        self.reports.push(Report::Plugin(content));
+    }
+
+    pub fn into_reports(self) -> Vec<Report> {
+        self.reports
+    }
+}
@@ -0,0 +1,168 @@
+//! Progress reporting for long analyses.
+//! Print phase banners, processed/total counts, and elapsed time per phase to stderr,
+//! so that users can tell a long analysis from a hang.
+//! Intermediate counts are throttled.
+//! Disabled with `--quiet` or when stderr is not a terminal.
+//! Since cargo pipes the stderr of rustc (thus lockbud),
+//! `cargo lockbud` tells lockbud whether its stderr is a terminal via `LOCKBUD_STDERR_IS_TTY`.
+use std::cell::RefCell;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// The minimal interval between two intermediate counts of a phase.
+const THROTTLE_INTERVAL: Duration = Duration::from_secs(2);
+
+pub const STDERR_IS_TTY_ENV: &str = "LOCKBUD_STDERR_IS_TTY";
+
+/// Whether the stderr of lockbud (or of `cargo lockbud`) is a terminal.
+pub fn stderr_is_tty() -> bool {
+    std::io::stderr().is_terminal() || std::env::var_os(STDERR_IS_TTY_ENV).is_some()
+}
+
+struct Phase {
+    name: &'static str,
+    start: Instant,
+    last_update: Instant,
+    /// (processed, total, unit)
+    count: Option<(usize, usize, &'static str)>,
+}
+
+/// The progress handle passed down from callbacks to analyses and detectors.
+pub struct Progress {
+    sink: Option<RefCell<Box<dyn Write>>>,
+    phase: RefCell<Option<Phase>>,
+}
+
+impl Progress {
+    /// Print progress to stderr if not `quiet` and stderr is a terminal.
+    pub fn new(quiet: bool) -> Self {
+        if !quiet && stderr_is_tty() {
+            Self::with_sink(Box::new(std::io::stderr()))
+        } else {
+            Self::disabled()
+        }
+    }
+
+    /// Print progress to the given sink.
+    pub fn with_sink(sink: Box<dyn Write>) -> Self {
+        Self {
+            sink: Some(RefCell::new(sink)),
+            phase: RefCell::new(None),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self {
+            sink: None,
+            phase: RefCell::new(None),
+        }
+    }
+
+    fn print(&self, msg: std::fmt::Arguments) {
+        if let Some(sink) = &self.sink {
+            // Progress is best-effort, so ignore the failures to write.
+            let _ = writeln!(sink.borrow_mut(), "[lockbud] {}", msg);
+        }
+    }
+
+    /// Finish the current phase (if any) and start a new phase.
+    pub fn phase(&self, name: &'static str) {
+        if self.sink.is_none() {
+            return;
+        }
+        self.finish();
+        self.print(format_args!("{}...", name));
+        let now = Instant::now();
+        *self.phase.borrow_mut() = Some(Phase {
+            name,
+            start: now,
+            last_update: now,
+            count: None,
+        });
+    }
+
+    /// Update the count of the current phase, printed at most once per `THROTTLE_INTERVAL`.
+    pub fn update(&self, processed: usize, total: usize, unit: &'static str) {
+        if self.sink.is_none() {
+            return;
+        }
+        let mut phase = self.phase.borrow_mut();
+        let phase = match phase.as_mut() {
+            Some(phase) => phase,
+            None => return,
+        };
+        phase.count = Some((processed, total, unit));
+        let now = Instant::now();
+        if now.duration_since(phase.last_update) >= THROTTLE_INTERVAL {
+            phase.last_update = now;
+            self.print(format_args!(
+                "{}: {}/{} {}",
+                phase.name, processed, total, unit
+            ));
+        }
+    }
+
+    /// Finish the current phase (if any) with its last count and elapsed time.
+    pub fn finish(&self) {
+        let phase = match self.phase.borrow_mut().take() {
+            Some(phase) => phase,
+            None => return,
+        };
+        let elapsed = phase.start.elapsed();
+        match phase.count {
+            Some((processed, total, unit)) => self.print(format_args!(
+                "{} done: {}/{} {} in {:.2?}",
+                phase.name, processed, total, unit, elapsed
+            )),
+            None => self.print(format_args!("{} done in {:.2?}", phase.name, elapsed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    /// A sink shared with the test to capture the output.
+    #[derive(Clone, Default)]
+    struct SharedSink(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_progress_phases_in_order() {
+        let sink = SharedSink::default();
+        let progress = Progress::with_sink(Box::new(sink.clone()));
+        progress.phase("callgraph");
+        progress.update(1, 2, "instances");
+        progress.update(2, 2, "instances");
+        progress.phase("doublelock");
+        progress.finish();
+        let output = String::from_utf8(sink.0.borrow().clone()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "[lockbud] callgraph...");
+        // The intermediate counts are throttled, but the last count is printed when finished.
+        assert!(lines[1].starts_with("[lockbud] callgraph done: 2/2 instances in "));
+        assert_eq!(lines[2], "[lockbud] doublelock...");
+        assert!(lines[3].starts_with("[lockbud] doublelock done in "));
+    }
+
+    #[test]
+    fn test_progress_disabled() {
+        let progress = Progress::disabled();
+        progress.phase("callgraph");
+        progress.update(1, 1, "instances");
+        progress.finish();
+        assert!(progress.phase.borrow().is_none());
+    }
+}
@@ -0,0 +1,113 @@
+//! Write the reports to the file given by `--report-path` rather than stderr.
+//! cargo runs lockbud once per crate, possibly in parallel,
+//! so each crate appends one line, i.e., its `ReportEnvelope` in compact JSON,
+//! while holding the exclusive lock on `{report-path}.lock`.
+//! Thus the report file is a JSON Lines file of envelopes, one per analyzed crate.
+//! Under `cargo lockbud`, the envelope of each analyzed crate is also cached in `LOCKBUD_CACHE_DIR`,
+//! in `{crate_name}-{metadata}.json` after the `-C metadata` of cargo, which is stable across the rebuilds,
+//! so that `cargo lockbud` reports the crates not rebuilt (thus not analyzed) from their cached envelopes.
+extern crate rustc_data_structures;
+
+use std::ffi::OsString;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use rustc_data_structures::flock::Lock;
+
+use crate::detector::report::ReportEnvelope;
+
+/// The lock file guarding the appends to `path`.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut lock_path = OsString::from(path.as_os_str());
+    lock_path.push(".lock");
+    PathBuf::from(lock_path)
+}
+
+/// Append `envelope` as one line to `path`, creating it if not exists.
+pub fn append_envelope(path: &Path, envelope: &ReportEnvelope) -> io::Result<()> {
+    let mut line = serde_json::to_string(envelope)?;
+    line.push('\n');
+    let _lock = Lock::new(&lock_path(path), true, true, true)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
+
+/// Cache `envelope` in `dir` as `{crate_name}-{metadata}.json`, replacing the one of the last analysis.
+pub fn write_cache(dir: &Path, metadata: &str, envelope: &ReportEnvelope) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}-{}.json", envelope.crate_name, metadata));
+    // Renamed into place, so `cargo lockbud` never reads a partial envelope.
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_string(envelope)?)?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::report::{Report, ReportContent};
+
+    fn envelope(crate_name: &str, num_reports: usize) -> ReportEnvelope {
+        let reports = (0..num_reports)
+            .map(|i| {
+                Report::UseAfterFree(ReportContent::new(
+                    "UseAfterFree".to_owned(),
+                    "Possibly".to_owned(),
+                    // Long enough to exceed the pipe buffer if written in pieces.
+                    format!("{} at src/main.rs:{}:1: {}:2 (#0)", "x".repeat(1000), i, i),
+                    String::new(),
+                ))
+            })
+            .collect();
+        ReportEnvelope::new(crate_name.to_owned(), reports)
+    }
+
+    #[test]
+    fn test_append_envelope_concurrently() {
+        let dir = std::env::temp_dir().join("lockbud-test-report-file");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reports.jsonl");
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    for j in 0..4 {
+                        let crate_name = format!("crate{}_{}", i, j);
+                        append_envelope(path, &envelope(&crate_name, 64)).unwrap();
+                    }
+                });
+            }
+        });
+        let content = std::fs::read_to_string(&path).unwrap();
+        let mut crate_names = content
+            .lines()
+            .map(|line| {
+                let envelope: ReportEnvelope = serde_json::from_str(line).unwrap();
+                assert_eq!(envelope.reports.len(), 64);
+                envelope.crate_name
+            })
+            .collect::<Vec<_>>();
+        crate_names.sort();
+        crate_names.dedup();
+        assert_eq!(crate_names.len(), 32);
+        assert!(lock_path(&path).exists());
+    }
+
+    #[test]
+    fn test_write_cache() {
+        let dir = std::env::temp_dir().join("lockbud-test-cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = write_cache(&dir, "0123abcd", &envelope("app", 2)).unwrap();
+        assert_eq!(path, dir.join("app-0123abcd.json"));
+        // The cache of the same crate is replaced.
+        write_cache(&dir, "0123abcd", &envelope("app", 1)).unwrap();
+        write_cache(&dir, "4567ef01", &envelope("app", 0)).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let envelope: ReportEnvelope = serde_json::from_str(&content).unwrap();
+        assert_eq!(envelope.reports.len(), 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,387 @@
+//! The entry point of the analysis: run the detectors enabled by `Options` on the mono items of the local crate.
+//! The reports are returned per detector, with the in-source suppressions applied and the fingerprints assigned,
+//! while the baseline, the levels, and the emission are left to the driver, see `callbacks.rs`.
+extern crate rustc_hir;
+
+use std::time::Duration;
+
+use log::{debug, warn};
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_middle::mir::mono::MonoItem;
+use rustc_middle::ty::{Instance, ParamEnv, TyCtxt};
+
+use crate::analysis::budget::{AnalysisBudget, SkippedFunction};
+use crate::analysis::callgraph::CallGraph;
+use crate::analysis::pointsto::stats::{AliasQueryContext, AliasStats};
+use crate::analysis::pointsto::AliasAnalysis;
+use crate::baseline;
+use crate::callbacks::debug_dir;
+use crate::detector::atomic::AtomicityViolationDetector;
+use crate::detector::lock::lock_graph::LockGraph;
+use crate::detector::lock::report::LockClass;
+use crate::detector::lock::DeadlockDetector;
+use crate::detector::memory::{DoubleFreeDetector, InvalidFreeDetector, UseAfterFreeDetector};
+use crate::detector::panic::PanicDetector;
+use crate::detector::report::Report;
+use crate::interest::concurrency::lock::GuardNewtypes;
+use crate::interest::concurrency::lock_registry::LockRegistry;
+use crate::options::Options;
+use crate::plugin;
+use crate::progress::Progress;
+use crate::snippet;
+use crate::suppression;
+
+/// The output of `run_analysis` on a crate.
+#[derive(Debug, Default)]
+pub struct AnalysisOutput {
+    pub crate_name: String,
+    /// The reports of the deadlock detectors, including `await_holding_lock`, `pool_self_join`, and the plugins.
+    pub deadlock: Vec<Report>,
+    pub atomicity_violation: Vec<Report>,
+    pub invalid_free: Vec<Report>,
+    pub use_after_free: Vec<Report>,
+    pub double_free: Vec<Report>,
+    pub panic_in_drop: Vec<Report>,
+    pub panic: Vec<Report>,
+    /// The alias classes of the locks in the deadlock reports.
+    pub lock_classes: Vec<LockClass>,
+    /// The lock order graph for `--emit-lock-graph`.
+    pub lock_graph: Option<LockGraph>,
+    /// The fns skipped by the budgets of the analyses, sorted.
+    pub skipped_functions: Vec<SkippedFunction>,
+    /// The counters of the alias queries and the points-to cache with `--alias-stats`.
+    pub alias_stats: Option<AliasStats>,
+    /// The number of the reports suppressed in source.
+    pub suppressed: usize,
+}
+
+impl AnalysisOutput {
+    /// The reports of all the detectors.
+    pub fn into_reports(self) -> Vec<Report> {
+        let mut reports = self.deadlock;
+        reports.extend(self.atomicity_violation);
+        reports.extend(self.invalid_free);
+        reports.extend(self.use_after_free);
+        reports.extend(self.double_free);
+        reports.extend(self.panic_in_drop);
+        reports.extend(self.panic);
+        reports
+    }
+}
+
+/// The instances of the fns in the mono items of the local crate.
+pub(crate) fn mono_instances(tcx: TyCtxt<'_>) -> Vec<Instance<'_>> {
+    let cgus = tcx.collect_and_partition_mono_items(()).1;
+    cgus.iter()
+        .flat_map(|cgu| {
+            cgu.items().iter().filter_map(|(mono_item, _)| {
+                if let MonoItem::Fn(instance) = mono_item {
+                    Some(*instance)
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+/// Run the detectors enabled by `options.detectors` on the local crate.
+pub fn run_analysis(tcx: TyCtxt<'_>, options: &Options) -> AnalysisOutput {
+    let crate_name = tcx.crate_name(LOCAL_CRATE).to_string();
+    let instances = mono_instances(tcx);
+    let progress = Progress::new(options.quiet);
+    progress.phase("callgraph");
+    let mut callgraph = CallGraph::new();
+    let param_env = ParamEnv::reveal_all();
+    callgraph.analyze(instances, tcx, param_env, &progress);
+    if let Some(focus) = &options.focus {
+        callgraph.set_focus(focus, tcx);
+    }
+    let budget = AnalysisBudget {
+        time: Duration::from_secs(options.fn_time_budget),
+        max_nodes: options.fn_node_budget,
+    };
+    // One AliasAnalysis shares the points-to cache across the detectors.
+    let indirect_pts = callgraph.take_points_to();
+    let mut alias_analysis = AliasAnalysis::new(tcx, &callgraph);
+    alias_analysis.set_budget(budget);
+    alias_analysis.set_jobs(options.jobs);
+    alias_analysis.set_int_ptr_casts(options.int_ptr_casts);
+    alias_analysis.set_context_sensitive(options.context_sensitive_alias);
+    alias_analysis.reuse_points_to(indirect_pts);
+    if let Some(pattern) = &options.dump_pointsto {
+        alias_analysis.set_dump_pointsto(pattern, debug_dir());
+    }
+    let mut output = AnalysisOutput {
+        crate_name,
+        ..Default::default()
+    };
+    let detectors = &options.detectors;
+    // `panic_holding_lock` needs the lockguards live at the panic sites,
+    // and `refcell_double_borrow` the relations of the `RefCell` borrows like doublelock.
+    if detectors.is_group_enabled("deadlock")
+        || detectors.is_enabled("panic_holding_lock")
+        || detectors.is_enabled("refcell_double_borrow")
+    {
+        debug!("Detecting deadlock");
+        alias_analysis.set_context(AliasQueryContext::Deadlock);
+        let mut deadlock_detector = DeadlockDetector::new(
+            tcx,
+            param_env,
+            detectors.clone(),
+            options.analyze_deps_guards,
+            GuardNewtypes::new(
+                LockRegistry::new(options.config.locks.clone()),
+                options.guard_newtypes.clone(),
+            ),
+            options.config.pools.clone(),
+        );
+        deadlock_detector.set_budget(budget);
+        deadlock_detector.set_paranoid(options.paranoid);
+        deadlock_detector.set_hot_path_fns(options.hot_path_fns.clone());
+        deadlock_detector.set_dump_mir(options.dump_mir);
+        deadlock_detector.set_emit_lock_graph(options.emit_lock_graph.is_some());
+        deadlock_detector.set_plugins(
+            plugin::registered_plugins(&options.config.plugins)
+                .expect("the plugins are validated with the config"),
+        );
+        output.deadlock = deadlock_detector.detect(&callgraph, &mut alias_analysis, &progress);
+        output
+            .skipped_functions
+            .extend(deadlock_detector.skipped_functions);
+        output.lock_classes = deadlock_detector.lock_classes;
+        output.lock_graph = deadlock_detector.lock_graph;
+        alias_analysis.record_reports_gated_on_possibly(possibly_reports(&output.deadlock));
+    }
+    if detectors.is_enabled("atomicity_violation") {
+        debug!("Detecting atomicity violation");
+        alias_analysis.set_context(AliasQueryContext::AtomicityViolation);
+        progress.phase("atomic");
+        let mut atomicity_violation_detector =
+            AtomicityViolationDetector::new(tcx, detectors.clone());
+        atomicity_violation_detector.set_paranoid(options.paranoid);
+        output.atomicity_violation =
+            atomicity_violation_detector.detect(&callgraph, &mut alias_analysis);
+        alias_analysis
+            .record_reports_gated_on_possibly(possibly_reports(&output.atomicity_violation));
+    }
+    if detectors.is_group_enabled("memory") {
+        debug!("Detecting memory bugs");
+        progress.phase("memory");
+    }
+    if detectors.is_enabled("invalid_free") {
+        alias_analysis.set_context(AliasQueryContext::InvalidFree);
+        let invalid_free_detector = InvalidFreeDetector::new(tcx, detectors.clone());
+        output.invalid_free =
+            invalid_free_detector.detect(&callgraph, &mut alias_analysis, &progress);
+        alias_analysis.record_reports_gated_on_possibly(possibly_reports(&output.invalid_free));
+    }
+    if detectors.is_enabled("use_after_free") {
+        alias_analysis.set_context(AliasQueryContext::UseAfterFree);
+        let use_after_free_detector = UseAfterFreeDetector::new(tcx, detectors.clone());
+        output.use_after_free =
+            use_after_free_detector.detect(&callgraph, &mut alias_analysis, &progress);
+        alias_analysis.record_reports_gated_on_possibly(possibly_reports(&output.use_after_free));
+    }
+    if detectors.is_enabled("double_free") {
+        alias_analysis.set_context(AliasQueryContext::DoubleFree);
+        let double_free_detector = DoubleFreeDetector::new(tcx, detectors.clone());
+        output.double_free =
+            double_free_detector.detect(&callgraph, &mut alias_analysis, &progress);
+        alias_analysis.record_reports_gated_on_possibly(possibly_reports(&output.double_free));
+    }
+    if detectors.is_enabled("panic_site") {
+        debug!("Detecting panic sites");
+        progress.phase("panic");
+        let mut panic_detector = PanicDetector::new(tcx);
+        panic_detector.set_filter(options.panic_include_tests, options.panic_kinds.clone());
+        output.panic = panic_detector.detect_panic_sites(&callgraph);
+    }
+    if detectors.is_enabled("panic_in_drop") {
+        debug!("Detecting panics in drop");
+        progress.phase("panic in drop");
+        let mut panic_detector = PanicDetector::new(tcx);
+        output.panic_in_drop =
+            panic_detector.detect_panic_in_drop(&callgraph, detectors.callchain_limits);
+    }
+    progress.finish();
+    for reports in [
+        &mut output.deadlock,
+        &mut output.atomicity_violation,
+        &mut output.invalid_free,
+        &mut output.use_after_free,
+        &mut output.double_free,
+        &mut output.panic_in_drop,
+        &mut output.panic,
+    ] {
+        output.suppressed += suppression::apply_suppressions(tcx, reports);
+        baseline::assign_fingerprints(tcx, reports);
+        if options.with_snippets {
+            snippet::attach_snippets(reports, &callgraph, tcx);
+        }
+    }
+    output
+        .skipped_functions
+        .extend(alias_analysis.skipped_functions());
+    output.skipped_functions.sort();
+    if options.alias_stats {
+        let j = serde_json::to_string_pretty(alias_analysis.stats()).unwrap();
+        warn!("crate {} alias query stats: {}", output.crate_name, j);
+        let j = serde_json::to_string_pretty(alias_analysis.cache_stats()).unwrap();
+        warn!("crate {} points-to cache stats: {}", output.crate_name, j);
+        output.alias_stats = Some(alias_analysis.alias_stats());
+    }
+    output
+}
+
+/// The number of reports gated on a Possibly result,
+/// i.e., of the kinds reported on the alias of the locks or ptrs whose possibility is Possibly.
+/// The other kinds, e.g., AwaitHoldingLock, Panic, and LockCoverage, are Possibly without querying aliases.
+fn possibly_reports(reports: &[Report]) -> usize {
+    reports
+        .iter()
+        .filter(|report| is_alias_gated(report) && report.possibility() == "Possibly")
+        .count()
+}
+
+/// Whether the kind of the report is gated on an alias query.
+fn is_alias_gated(report: &Report) -> bool {
+    matches!(
+        report,
+        Report::DoubleLock(_)
+            | Report::ConflictLock(_)
+            | Report::CondvarDeadlock(_)
+            | Report::CondvarMultipleMutexes(_)
+            | Report::RefCellDoubleBorrow(_)
+            | Report::LockGuardLeaked(_)
+            | Report::DoubleUnlock(_)
+            | Report::UseAfterUnlock(_)
+            | Report::ImplicitReleaseOrder(_)
+            | Report::AtomicityViolation(_)
+            | Report::InvalidFree(_)
+            | Report::UseAfterFree(_)
+            | Report::DoubleFree(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{with_tcx, write_src};
+
+    /// Two doublelocks, one through a callee, and the panic sites of the `unwrap`s.
+    const FOCUS_SRC: &str = r#"use std::sync::Mutex;
+
+pub fn relock_through_read(m: &Mutex<i32>) -> i32 {
+    let a = m.lock().unwrap();
+    *a + read(m)
+}
+
+fn read(m: &Mutex<i32>) -> i32 {
+    *m.lock().unwrap()
+}
+
+pub fn relock_twice(m: &Mutex<i32>) {
+    let a = m.lock().unwrap();
+    let b = m.lock().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_focus() {
+        let src = write_src("focus", FOCUS_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let line = |span: &str| span.split(':').nth(1).unwrap().parse::<usize>().unwrap();
+                let options = Options::parse_from_str("-k all --detectors panic_site").unwrap();
+                let output = run_analysis(tcx, &options);
+                assert_eq!(output.deadlock.len(), 2);
+                assert!(output
+                    .panic
+                    .iter()
+                    .any(|report| line(report.spans().0) > 12));
+                // The callee `read` is analyzed with the focused fn, while `relock_twice` is not.
+                let options =
+                    Options::parse_from_str("-k all --detectors panic_site --focus relock_through")
+                        .unwrap();
+                let output = run_analysis(tcx, &options);
+                let deadlocks = output
+                    .deadlock
+                    .iter()
+                    .map(|report| {
+                        assert!(matches!(report, Report::DoubleLock(_)));
+                        let (first, second) = report.spans();
+                        (line(first), line(second))
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(deadlocks, [(4, 9)]);
+                assert!(!output.panic.is_empty());
+                assert!(output
+                    .into_reports()
+                    .iter()
+                    .all(|report| line(report.spans().0) < 12));
+            }),
+        );
+    }
+
+    /// The doublelocks through a helper, Possibly by the param types, and a discarded lockguard, Possibly without aliases.
+    const ALIAS_STATS_SRC: &str = r#"use std::sync::Mutex;
+pub struct Registry {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+}
+impl Registry {
+    fn with(&self, m: &Mutex<i32>) -> i32 {
+        let value = m.lock().unwrap();
+        *value
+    }
+    pub fn a_then_b(&self) -> i32 {
+        let a = self.a.lock().unwrap();
+        *a + self.with(&self.b)
+    }
+}
+#[allow(let_underscore_lock)]
+pub fn discard(m: &Mutex<i32>) {
+    let _ = m.lock().unwrap();
+}
+"#;
+
+    #[test]
+    fn test_alias_stats() {
+        let src = write_src("alias_stats", ALIAS_STATS_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let options = Options::parse_from_str("-k all").unwrap();
+                assert!(run_analysis(tcx, &options).alias_stats.is_none());
+                let options = Options::parse_from_str("-k all --alias-stats").unwrap();
+                let output = run_analysis(tcx, &options);
+                let kinds = output
+                    .deadlock
+                    .iter()
+                    .map(|report| (is_alias_gated(report), report.possibility()))
+                    .collect::<Vec<_>>();
+                assert!(kinds.contains(&(true, "Possibly")));
+                assert!(kinds.contains(&(false, "Possibly")));
+                let alias_stats = output.alias_stats.unwrap();
+                let deadlock = &alias_stats.queries[&AliasQueryContext::Deadlock];
+                assert!(deadlock.alias.total() > 0);
+                // Only the alias-gated reports are counted, not the discarded lockguard.
+                assert_eq!(
+                    deadlock.reports_gated_on_possibly,
+                    possibly_reports(&output.deadlock)
+                );
+                assert!(deadlock.reports_gated_on_possibly > 0);
+                let possibly = kinds.iter().filter(|(_, p)| *p == "Possibly").count();
+                assert!(deadlock.reports_gated_on_possibly < possibly);
+                assert!(deadlock.reports_gated_on_possibly <= deadlock.alias.total());
+                let cache = &alias_stats.points_to_cache[&AliasQueryContext::Deadlock];
+                assert!(cache.misses > 0);
+                assert!(cache.cross_detector_hits <= cache.hits);
+            }),
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Embed the source lines of the spans into the diagnoses under `--with-snippets`,
+//! so that the JSON reports can be triaged without opening the files at the spans.
+//! The snippets cover the locks of `DeadlockDiagnosis` (not the callchains),
+//! the condvar callsites and the locks of `CondvarDeadlockDiagnosis`, and the atomic reader and writer,
+//! keyed by the span strings in the `snippet` field of each diagnosis.
+//! A snippet has the lines of the span and one line of context before and after, prefixed by the line numbers
+//! and dedented, where the span is capped to `MAX_SPAN_LINES` lines and each line to `MAX_LINE_CHARS` chars.
+//! A span from a macro expansion (of a non-root syntax context, e.g., `(#4)`) falls back to the callsite
+//! of the outermost expansion, e.g., the `lock!(m)` rather than the `m.lock()` in the macro definition.
+//! Since the syntax context cannot be recovered from the span string alone,
+//! the expanded spans are collected from the MIR bodies in CallGraph and matched by their span strings.
+extern crate rustc_hash;
+extern crate rustc_span;
+
+use std::collections::BTreeMap;
+
+use rustc_hash::FxHashMap;
+use rustc_middle::mir::visit::Visitor;
+use rustc_middle::mir::SourceInfo;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+
+use crate::analysis::callgraph::{CallGraph, CallGraphNode};
+use crate::detector::lock::report::DeadlockDiagnosis;
+use crate::detector::report::Report;
+use crate::diagnostic::locate_span_text;
+
+/// The lines of context before and after a span.
+const CONTEXT_LINES: usize = 1;
+/// The maximal number of lines of a span, the rest of which is elided as `...`.
+const MAX_SPAN_LINES: usize = 4;
+const MAX_LINE_CHARS: usize = 120;
+
+/// The callsites of the spans from macro expansions in the MIR bodies, keyed by the span strings.
+struct ExpansionCallsites(FxHashMap<String, Span>);
+
+impl ExpansionCallsites {
+    fn new<'tcx>(callgraph: &CallGraph<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
+        struct SpanCollector<'a>(&'a mut FxHashMap<String, Span>);
+        impl<'tcx> Visitor<'tcx> for SpanCollector<'_> {
+            fn visit_source_info(&mut self, source_info: &SourceInfo) {
+                let span = source_info.span;
+                if span.from_expansion() {
+                    self.0
+                        .entry(format!("{:?}", span))
+                        .or_insert_with(|| span.source_callsite());
+                }
+            }
+        }
+        let mut callsites = FxHashMap::default();
+        for node in callgraph.graph.node_weights() {
+            if let CallGraphNode::WithBody(instance) = node {
+                SpanCollector(&mut callsites).visit_body(tcx.instance_mir(instance.def));
+            }
+        }
+        Self(callsites)
+    }
+}
+
+/// Fill in the `snippet` fields of the diagnoses of the reports.
+pub fn attach_snippets<'tcx>(
+    reports: &mut [Report],
+    callgraph: &CallGraph<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) {
+    if reports.is_empty() {
+        return;
+    }
+    let callsites = ExpansionCallsites::new(callgraph, tcx);
+    for report in reports {
+        match report {
+            Report::DoubleLock(content) => {
+                attach_deadlock_snippets(tcx, &callsites, &mut content.diagnosis)
+            }
+            Report::ConflictLock(content) => {
+                for diagnosis in &mut content.diagnosis {
+                    attach_deadlock_snippets(tcx, &callsites, diagnosis);
+                }
+            }
+            Report::CondvarDeadlock(content) => {
+                let diagnosis = &mut content.diagnosis;
+                let mut spans = vec![
+                    &diagnosis.condvar_wait_callsite_span,
+                    &diagnosis.condvar_notify_callsite_span,
+                ];
+                for locks in &diagnosis.deadlocks {
+                    spans.extend([&locks.wait_lock_span, &locks.notify_lock_span]);
+                }
+                diagnosis.snippet = snippets(tcx, &callsites, spans);
+            }
+            Report::AtomicityViolation(content) => {
+                let diagnosis = &mut content.diagnosis;
+                diagnosis.snippet = snippets(
+                    tcx,
+                    &callsites,
+                    vec![&diagnosis.atomic_reader, &diagnosis.atomic_writer],
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn attach_deadlock_snippets(
+    tcx: TyCtxt<'_>,
+    callsites: &ExpansionCallsites,
+    diagnosis: &mut DeadlockDiagnosis,
+) {
+    diagnosis.snippet = snippets(
+        tcx,
+        callsites,
+        vec![&diagnosis.first_lock_span, &diagnosis.second_lock_span],
+    );
+}
+
+fn snippets(
+    tcx: TyCtxt<'_>,
+    callsites: &ExpansionCallsites,
+    spans: Vec<&String>,
+) -> BTreeMap<String, String> {
+    spans
+        .into_iter()
+        .filter_map(|span| Some((span.clone(), snippet(tcx, callsites, span)?)))
+        .collect()
+}
+
+/// The snippet of a span string, or None if its source is unavailable, e.g., in the dependencies.
+fn snippet(tcx: TyCtxt<'_>, callsites: &ExpansionCallsites, text: &str) -> Option<String> {
+    let span = match callsites.0.get(text) {
+        Some(callsite) => *callsite,
+        None => locate_span_text(tcx, text)?,
+    };
+    let source_map = tcx.sess.source_map();
+    let (lo, hi) = (
+        source_map.lookup_char_pos(span.lo()),
+        source_map.lookup_char_pos(span.hi()),
+    );
+    lo.file.src.as_ref()?;
+    // 1-based line numbers, None for the elided lines.
+    let span_end = hi.line.min(lo.line + MAX_SPAN_LINES - 1);
+    let mut lines = Vec::new();
+    for line in lo.line.saturating_sub(CONTEXT_LINES).max(1)..=hi.line + CONTEXT_LINES {
+        if line > span_end && line <= hi.line {
+            if line == span_end + 1 {
+                lines.push((None, String::new()));
+            }
+            continue;
+        }
+        match lo.file.get_line(line - 1) {
+            Some(text) => lines.push((Some(line), text.trim_end().to_owned())),
+            None => break,
+        }
+    }
+    let indent = lines
+        .iter()
+        .filter(|(_, text)| !text.is_empty())
+        .map(|(_, text)| text.len() - text.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let width = (hi.line + CONTEXT_LINES).to_string().len();
+    let snippet = lines
+        .into_iter()
+        .map(|(line, text)| match line {
+            Some(line) => {
+                let text = text.get(indent..).unwrap_or_default();
+                let text = match text.char_indices().nth(MAX_LINE_CHARS) {
+                    Some((end, _)) => format!("{}...", &text[..end]),
+                    None => text.to_owned(),
+                };
+                format!("{:>width$} | {}", line, text).trim_end().to_owned()
+            }
+            None => format!("{:>width$} | ...", ""),
+        })
+        .collect::<Vec<_>>();
+    Some(snippet.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use crate::run::run_analysis;
+    use crate::test_utils::{with_tcx, write_src};
+
+    /// A doublelock, and one in a macro expansion reported at the `lock!` callsites.
+    const SNIPPET_SRC: &str = r#"
+use std::sync::Mutex;
+
+macro_rules! lock {
+    ($m:expr) => {
+        $m.lock().unwrap()
+    };
+}
+
+pub fn relock(m: &Mutex<i32>) {
+    let a = m.lock().unwrap();
+    let b = m.lock().unwrap();
+}
+
+pub fn relock_in_macro(m: &Mutex<i32>) {
+    let a = lock!(m);
+    let b = lock!(m);
+}
+"#;
+
+    #[test]
+    fn test_attach_snippets() {
+        let src = write_src("snippet", SNIPPET_SRC);
+        with_tcx(
+            &src,
+            &[],
+            Box::new(|tcx| {
+                let options = Options::parse_from_str("-k deadlock --with-snippets").unwrap();
+                let output = run_analysis(tcx, &options);
+                let mut snippets = output
+                    .deadlock
+                    .iter()
+                    .map(|report| match report {
+                        Report::DoubleLock(content) => content
+                            .diagnosis
+                            .snippet
+                            .values()
+                            .cloned()
+                            .collect::<Vec<_>>(),
+                        _ => panic!("{:?}", report),
+                    })
+                    .collect::<Vec<_>>();
+                snippets.sort();
+                assert_eq!(
+                    snippets,
+                    [
+                        [
+                            "10 | pub fn relock(m: &Mutex<i32>) {\n11 |     let a = m.lock().unwrap();\n12 |     let b = m.lock().unwrap();",
+                            "11 |     let a = m.lock().unwrap();\n12 |     let b = m.lock().unwrap();\n13 | }",
+                        ],
+                        [
+                            "15 | pub fn relock_in_macro(m: &Mutex<i32>) {\n16 |     let a = lock!(m);\n17 |     let b = lock!(m);",
+                            "16 |     let a = lock!(m);\n17 |     let b = lock!(m);\n18 | }",
+                        ],
+                    ]
+                );
+            }),
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Suppress the reports in source, for the deliberate lock patterns that should be silenced in place.
+//! lockbud compiles the crates with `--cfg lockbud` and registers the tool `lockbud` (see `RUSTC_ARGS`),
+//! so an item can allow the reports of some kinds or families (see `Report::level_keys`) in it, e.g.,
+//! `#[cfg_attr(lockbud, allow(lockbud::double_lock))]`, which is a no-op in normal builds.
+//! `allow(double_lock)` without the tool prefix also works but rustc warns about the unknown lint.
+//! The allows of all the items enclosing a lock span of the report (fn, impl, mod, and crate) apply.
+//! For macro-heavy code where attributes can't be placed,
+//! a `// lockbud:ignore(double_lock)` comment on the line of a lock span, or alone on the line above it,
+//! suppresses the report as well.
+extern crate rustc_data_structures;
+extern crate rustc_hir;
+extern crate rustc_span;
+
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::symbol::sym;
+
+use crate::detector::report::Report;
+use lockbud_report_types::span_lines;
+
+/// The rustc args enabling `#[cfg_attr(lockbud, allow(lockbud::..))]`.
+pub const RUSTC_ARGS: [&str; 4] = [
+    "--cfg",
+    "lockbud",
+    "-Zcrate-attr=feature(register_tool)",
+    "-Zcrate-attr=register_tool(lockbud)",
+];
+
+/// The comment marker suppressing the reports of the kinds in the parentheses.
+const IGNORE_MARKER: &str = "lockbud:ignore(";
+
+/// Drop the reports suppressed in source, returning the number of dropped reports.
+pub fn apply_suppressions(tcx: TyCtxt<'_>, reports: &mut Vec<Report>) -> usize {
+    let suppressions = Suppressions::new(tcx);
+    let num_reports = reports.len();
+    reports.retain(|report| !suppressions.is_suppressed(report));
+    num_reports - reports.len()
+}
+
+struct Suppressions<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    /// file -> (first line, last line, def id) of the fns and closures in it
+    fns: FxHashMap<String, Vec<(usize, usize, LocalDefId)>>,
+}
+
+impl<'tcx> Suppressions<'tcx> {
+    fn new(tcx: TyCtxt<'tcx>) -> Self {
+        let source_map = tcx.sess.source_map();
+        let mut fns: FxHashMap<String, Vec<_>> = FxHashMap::default();
+        for def_id in tcx.hir().body_owners() {
+            if !tcx.def_kind(def_id).is_fn_like() {
+                continue;
+            }
+            let span = tcx.source_span(def_id);
+            let lo = source_map.lookup_char_pos(span.lo());
+            let hi = source_map.lookup_char_pos(span.hi());
+            fns.entry(lo.file.name.prefer_remapped().to_string())
+                .or_default()
+                .push((lo.line, hi.line, def_id));
+        }
+        Self { tcx, fns }
+    }
+
+    fn is_suppressed(&self, report: &Report) -> bool {
+        let keys = report.level_keys();
+        lock_spans(report)
+            .into_iter()
+            .flat_map(span_lines)
+            .any(|(file, line)| {
+                let allowed = self.allowed_kinds(file, line);
+                let ignored = self.ignored_kinds(file, line);
+                keys.iter()
+                    .any(|key| allowed.contains(*key) || ignored.contains(*key))
+            })
+    }
+
+    /// The kinds allowed by the items enclosing the line, from the innermost fn up to the crate.
+    fn allowed_kinds(&self, file: &str, line: usize) -> FxHashSet<String> {
+        let innermost = self.fns.get(file).and_then(|fns| {
+            fns.iter()
+                .filter(|(first, last, _)| *first <= line && line <= *last)
+                .min_by_key(|(first, last, _)| last - first)
+        });
+        let mut kinds = FxHashSet::default();
+        let mut def_id = innermost.map(|(_, _, def_id)| def_id.to_def_id());
+        while let Some(id) = def_id {
+            kinds.extend(self.allows(id));
+            def_id = self.tcx.opt_parent(id);
+        }
+        kinds
+    }
+
+    /// The lints in `allow(lockbud::kind)` or `allow(kind)` on the item.
+    fn allows(&self, def_id: DefId) -> Vec<String> {
+        self.tcx
+            .get_attrs(def_id, sym::allow)
+            .flat_map(|attr| attr.meta_item_list().unwrap_or_default())
+            .filter_map(|nested| {
+                let segments = &nested.meta_item()?.path.segments;
+                match segments.as_slice() {
+                    [kind] => Some(kind.ident.to_string()),
+                    [tool, kind] if tool.ident.as_str() == "lockbud" => {
+                        Some(kind.ident.to_string())
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The kinds in the `lockbud:ignore(..)` comment on the line or alone on the line above.
+    fn ignored_kinds(&self, file: &str, line: usize) -> FxHashSet<String> {
+        let source_map = self.tcx.sess.source_map();
+        let files = source_map.files();
+        let source_file = match files
+            .iter()
+            .find(|source_file| source_file.name.prefer_remapped().to_string() == file)
+        {
+            Some(source_file) => source_file,
+            None => return FxHashSet::default(),
+        };
+        let mut kinds = FxHashSet::default();
+        if let Some(text) = source_file.get_line(line.saturating_sub(1)) {
+            kinds.extend(parse_ignore(&text));
+        }
+        if line >= 2 {
+            if let Some(text) = source_file.get_line(line - 2) {
+                if text.trim_start().starts_with("//") {
+                    kinds.extend(parse_ignore(&text));
+                }
+            }
+        }
+        kinds
+    }
+}
+
+/// The spans of the locks in the report, or the textual diagnosis of the memory reports.
+fn lock_spans(report: &Report) -> Vec<&str> {
+    match report {
+        Report::ConflictLock(content) => content
+            .diagnosis
+            .iter()
+            .flat_map(|diagnosis| [&diagnosis.first_lock_span, &diagnosis.second_lock_span])
+            .map(String::as_str)
+            .collect(),
+        _ => {
+            let (primary, secondary) = report.spans();
+            vec![primary, secondary]
+        }
+    }
+}
+
+/// The kinds in `// lockbud:ignore(kind1, kind2)`.
+fn parse_ignore(text: &str) -> Vec<String> {
+    let comment = match text.find("//") {
+        Some(idx) => &text[idx..],
+        None => return Vec::new(),
+    };
+    let kinds = match comment.find(IGNORE_MARKER) {
+        Some(idx) => &comment[idx + IGNORE_MARKER.len()..],
+        None => return Vec::new(),
+    };
+    let kinds = kinds.split(')').next().unwrap_or_default();
+    kinds
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ignore() {
+        assert_eq!(
+            parse_ignore("let _b = m.lock(); // lockbud:ignore(double_lock)"),
+            vec!["double_lock"]
+        );
+        assert_eq!(
+            parse_ignore("    // lockbud:ignore( double_lock, deadlock ) on purpose"),
+            vec!["double_lock", "deadlock"]
+        );
+        assert!(parse_ignore(r#"let s = "lockbud:ignore(double_lock)";"#).is_empty());
+        assert!(parse_ignore("// lockbud:ignore()").is_empty());
+        assert!(parse_ignore("let _b = m.lock();").is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! Utilities for the tests driving rustc in-process.
+extern crate rustc_driver;
+extern crate rustc_interface;
+
+use std::path::{Path, PathBuf};
+
+use rustc_middle::mir::mono::MonoItem;
+use rustc_middle::ty::{Instance, TyCtxt};
+
+pub type AfterAnalysis = Box<dyn for<'tcx> FnOnce(TyCtxt<'tcx>) + Send>;
+
+struct TestCallbacks(Option<AfterAnalysis>);
+
+impl rustc_driver::Callbacks for TestCallbacks {
+    fn after_analysis<'tcx>(
+        &mut self,
+        compiler: &rustc_interface::interface::Compiler,
+        queries: &'tcx rustc_interface::Queries<'tcx>,
+    ) -> rustc_driver::Compilation {
+        compiler.session().abort_if_errors();
+        let f = self.0.take().unwrap();
+        queries.global_ctxt().unwrap().enter(f);
+        rustc_driver::Compilation::Stop
+    }
+}
+
+/// Compile the source file with the extra rustc args and call `f` after analysis.
+/// The source is compiled as a lib unless the extra args give another `--crate-type`.
+pub fn with_tcx(src: &Path, extra_args: &[String], f: AfterAnalysis) {
+    let mut args = vec!["rustc".to_owned()];
+    if !extra_args.iter().any(|arg| arg.starts_with("--crate-type")) {
+        args.push("--crate-type=lib".to_owned());
+    }
+    args.extend([
+        "--sysroot".to_owned(),
+        crate::find_sysroot().expect("the sysroot of the tests"),
+        "-Zalways-encode-mir".to_owned(),
+        "-Awarnings".to_owned(),
+    ]);
+    args.extend(extra_args.iter().cloned());
+    args.push(src.to_str().unwrap().to_owned());
+    let mut callbacks = TestCallbacks(Some(f));
+    rustc_driver::RunCompiler::new(&args, &mut callbacks)
+        .run()
+        .unwrap();
+}
+
+/// Write the source to `lib.rs` in a temp dir named after the test.
+pub fn write_src(test_name: &str, src: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lockbud-test-{}", test_name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lib.rs");
+    std::fs::write(&path, src).unwrap();
+    path
+}
+
+/// The mono instances and the non-generic fns of the crate.
+pub fn test_instances(tcx: TyCtxt<'_>) -> Vec<Instance<'_>> {
+    let instances = tcx
+        .mir_keys(())
+        .iter()
+        .map(|def_id| def_id.to_def_id())
+        .filter(|def_id| {
+            tcx.def_kind(def_id).is_fn_like()
+                && !tcx.generics_of(*def_id).requires_monomorphization(tcx)
+        })
+        .map(|def_id| Instance::mono(tcx, def_id))
+        .collect::<Vec<_>>();
+    let cgus = tcx.collect_and_partition_mono_items(()).1;
+    let mut instances = instances
+        .into_iter()
+        .chain(cgus.iter().flat_map(|cgu| {
+            cgu.items()
+                .iter()
+                .filter_map(|(mono_item, _)| match mono_item {
+                    MonoItem::Fn(instance) => Some(*instance),
+                    _ => None,
+                })
+        }))
+        .collect::<Vec<_>>();
+    instances.sort_by_key(|instance| format!("{:?}", instance));
+    instances.dedup();
+    instances
+}
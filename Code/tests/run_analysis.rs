@@ -0,0 +1,81 @@
+//! Drive rustc in-process over a fixture and call `run_analysis` like a tool built on the lockbud library.
+#![feature(rustc_private)]
+
+extern crate rustc_driver;
+extern crate rustc_interface;
+
+use lockbud::options::Options;
+use lockbud::{run_analysis, AnalysisOutput, Report};
+
+const DOUBLELOCK_SRC: &str = r#"
+use std::sync::Mutex;
+
+pub fn relock(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    let _b = m.lock().unwrap();
+}
+"#;
+
+struct AnalysisCallbacks {
+    options: Options,
+    output: Option<AnalysisOutput>,
+}
+
+impl rustc_driver::Callbacks for AnalysisCallbacks {
+    fn after_analysis<'tcx>(
+        &mut self,
+        compiler: &rustc_interface::interface::Compiler,
+        queries: &'tcx rustc_interface::Queries<'tcx>,
+    ) -> rustc_driver::Compilation {
+        compiler.session().abort_if_errors();
+        self.output = Some(
+            queries
+                .global_ctxt()
+                .unwrap()
+                .enter(|tcx| run_analysis(tcx, &self.options)),
+        );
+        rustc_driver::Compilation::Stop
+    }
+}
+
+fn analyze(name: &str, src: &str, flags: &str) -> AnalysisOutput {
+    let dir = std::env::temp_dir().join(format!("lockbud-test-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lib.rs");
+    std::fs::write(&path, src).unwrap();
+    let args = vec![
+        "rustc".to_owned(),
+        "--crate-type=lib".to_owned(),
+        "--sysroot".to_owned(),
+        lockbud::find_sysroot().expect("the sysroot of the tests"),
+        "-Zalways-encode-mir".to_owned(),
+        "-Awarnings".to_owned(),
+        path.to_str().unwrap().to_owned(),
+    ];
+    let mut callbacks = AnalysisCallbacks {
+        options: Options::parse_from_str(flags).unwrap(),
+        output: None,
+    };
+    rustc_driver::RunCompiler::new(&args, &mut callbacks)
+        .run()
+        .unwrap();
+    callbacks.output.unwrap()
+}
+
+#[test]
+fn test_run_analysis_doublelock() {
+    let output = analyze("lib-doublelock", DOUBLELOCK_SRC, "-k deadlock");
+    assert_eq!(output.crate_name, "lib");
+    assert!(output.atomicity_violation.is_empty() && output.use_after_free.is_empty());
+    let [Report::DoubleLock(content)] = &output.deadlock[..] else {
+        panic!("expected a doublelock, got {:?}", output.deadlock);
+    };
+    assert_eq!(content.possibility, "Probably");
+    assert_eq!(content.diagnosis.first_lock_type, "StdMutex(i32)");
+    assert!(content.diagnosis.second_lock_span.contains("lib.rs:6:"));
+    assert!(!content.fingerprint.is_empty());
+    let reports = output.into_reports();
+    assert!(serde_json::to_string(&reports)
+        .unwrap()
+        .contains("DoubleLock"));
+}
@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+async fn flush() {}
+
+#[async_trait(?Send)]
+trait Store {
+    async fn put(&self, v: i32);
+    async fn get(&self) -> i32;
+}
+
+struct MemStore {
+    inner: Mutex<i32>,
+}
+
+#[async_trait(?Send)]
+impl Store for MemStore {
+    // The guard is held across `flush().await`.
+    async fn put(&self, v: i32) {
+        let mut guard = self.inner.lock();
+        flush().await;
+        *guard = v;
+    }
+
+    // DoubleLock inside the generated generator body.
+    async fn get(&self) -> i32 {
+        let guard = self.inner.lock();
+        let v = *self.inner.lock();
+        *guard + v
+    }
+}
+
+fn main() {
+    let store = MemStore {
+        inner: Mutex::new(1),
+    };
+    futures::executor::block_on(async {
+        store.put(2).await;
+        store.get().await;
+    });
+}
@@ -0,0 +1,44 @@
+use std::sync::Mutex;
+
+struct Cache {
+    entries: Mutex<Vec<u64>>,
+    pending: tokio::sync::Mutex<Vec<u64>>,
+}
+
+impl Cache {
+    // The future may wait for a task locking `entries`.
+    fn refresh(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        let fresh = futures::executor::block_on(async { vec![1, 2] });
+        *entries = fresh;
+    }
+
+    fn refresh_on(&self, rt: &tokio::runtime::Runtime) {
+        let mut entries = self.entries.lock().unwrap();
+        *entries = rt.block_on(async { vec![3] });
+    }
+
+    // The lock is released before blocking.
+    fn refresh_released(&self) {
+        let fresh = futures::executor::block_on(async { vec![4] });
+        *self.entries.lock().unwrap() = fresh;
+    }
+
+    // The guard of an async lock is not reported.
+    fn refresh_pending(&self, rt: &tokio::runtime::Runtime) {
+        let mut pending = self.pending.blocking_lock();
+        *pending = rt.block_on(async { vec![5] });
+    }
+}
+
+fn main() {
+    let cache = Cache {
+        entries: Mutex::new(Vec::new()),
+        pending: tokio::sync::Mutex::new(Vec::new()),
+    };
+    let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    cache.refresh();
+    cache.refresh_on(&rt);
+    cache.refresh_released();
+    cache.refresh_pending(&rt);
+}
@@ -0,0 +1,19 @@
+use std::sync::Mutex;
+
+/// Run at build time, so not analyzed: `count` is locked again while `first` is alive.
+fn relock(count: &Mutex<i32>) -> i32 {
+    let first = count.lock().unwrap();
+    let second = count.lock().unwrap();
+    *first + *second
+}
+
+fn main() {
+    let count = Mutex::new(0);
+    // Never deadlocks the build.
+    let count = if std::env::var_os("BUILD_TIME_RELOCK").is_some() {
+        relock(&count)
+    } else {
+        *count.lock().unwrap()
+    };
+    println!("cargo:rustc-env=COUNT={}", count);
+}
@@ -0,0 +1,5 @@
+use macros::count_items;
+
+fn main() {
+    println!("{} {}", env!("COUNT"), count_items!(a b c));
+}
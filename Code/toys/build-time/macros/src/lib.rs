@@ -0,0 +1,22 @@
+use std::sync::Mutex;
+
+use proc_macro::TokenStream;
+
+/// Expanded at build time, so only analyzed with `--analyze-proc-macros`:
+/// `count` is locked again while `first` is alive.
+fn relock(count: &Mutex<usize>) -> usize {
+    let first = count.lock().unwrap();
+    let second = count.lock().unwrap();
+    *first + *second
+}
+
+#[proc_macro]
+pub fn count_items(input: TokenStream) -> TokenStream {
+    let count = Mutex::new(input.into_iter().count());
+    // Never deadlocks the build.
+    if std::env::var_os("BUILD_TIME_RELOCK").is_some() {
+        relock(&count);
+    }
+    let count = *count.lock().unwrap();
+    count.to_string().parse().unwrap()
+}
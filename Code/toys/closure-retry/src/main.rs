@@ -0,0 +1,27 @@
+use std::sync::Mutex;
+
+use retry::with_retry;
+
+struct Store {
+    state: Mutex<i32>,
+}
+
+impl Store {
+    fn do_locked_thing(&self) -> Result<(), String> {
+        *self.state.lock().unwrap() += 1;
+        Ok(())
+    }
+
+    /// The closure passed to `with_retry` locks `self.state` again.
+    fn retry_under_lock(&self) -> Result<(), String> {
+        let _state = self.state.lock().unwrap();
+        with_retry(|| self.do_locked_thing())
+    }
+}
+
+fn main() {
+    let store = Store {
+        state: Mutex::new(0),
+    };
+    store.retry_under_lock().unwrap();
+}
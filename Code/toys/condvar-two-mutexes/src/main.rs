@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::thread;
+
+/// One condvar waited with the guards of two mutexes, which panics at runtime:
+/// "attempted to use a condition variable with two mutexes".
+fn std_two_mutexes() {
+    use std::sync::{Condvar, Mutex};
+
+    struct Shared {
+        ready: Mutex<bool>,
+        done: Mutex<bool>,
+        cvar: Condvar,
+    }
+
+    impl Shared {
+        fn wait_ready(&self) {
+            let mut ready = self.ready.lock().unwrap();
+            while !*ready {
+                ready = self.cvar.wait(ready).unwrap();
+            }
+        }
+        fn wait_ready_again(&self) {
+            let ready = self.ready.lock().unwrap();
+            let _ready = self.cvar.wait_while(ready, |ready| !*ready).unwrap();
+        }
+        fn wait_done(&self) {
+            let mut done = self.done.lock().unwrap();
+            while !*done {
+                done = self.cvar.wait(done).unwrap();
+            }
+        }
+        fn finish(&self) {
+            *self.ready.lock().unwrap() = true;
+            *self.done.lock().unwrap() = true;
+            self.cvar.notify_all();
+        }
+    }
+
+    let shared = Arc::new(Shared {
+        ready: Mutex::new(false),
+        done: Mutex::new(false),
+        cvar: Condvar::new(),
+    });
+    let shared1 = shared.clone();
+    let shared2 = shared.clone();
+    let th1 = thread::spawn(move || {
+        shared1.wait_ready();
+        shared1.wait_ready_again();
+    });
+    let th2 = thread::spawn(move || shared2.wait_done());
+    shared.finish();
+    th1.join().unwrap();
+    th2.join().unwrap();
+}
+
+/// parking_lot has the same restriction.
+fn parking_lot_two_mutexes() {
+    use parking_lot::{Condvar, Mutex};
+
+    struct Shared {
+        ready: Mutex<bool>,
+        done: Mutex<bool>,
+        cvar: Condvar,
+    }
+
+    impl Shared {
+        fn wait_ready(&self) {
+            let mut ready = self.ready.lock();
+            while !*ready {
+                self.cvar.wait(&mut ready);
+            }
+        }
+        fn wait_done(&self) {
+            let mut done = self.done.lock();
+            while !*done {
+                self.cvar.wait(&mut done);
+            }
+        }
+        fn finish(&self) {
+            *self.ready.lock() = true;
+            *self.done.lock() = true;
+            self.cvar.notify_all();
+        }
+    }
+
+    let shared = Arc::new(Shared {
+        ready: Mutex::new(false),
+        done: Mutex::new(false),
+        cvar: Condvar::new(),
+    });
+    let shared1 = shared.clone();
+    let shared2 = shared.clone();
+    let th1 = thread::spawn(move || shared1.wait_ready());
+    let th2 = thread::spawn(move || shared2.wait_done());
+    shared.finish();
+    th1.join().unwrap();
+    th2.join().unwrap();
+}
+
+/// One mutex per condvar: not reported.
+fn std_one_mutex_per_condvar() {
+    use std::sync::{Condvar, Mutex};
+
+    struct Pair {
+        lock: Mutex<bool>,
+        cvar: Condvar,
+    }
+
+    impl Pair {
+        fn wait(&self) {
+            let mut started = self.lock.lock().unwrap();
+            while !*started {
+                started = self.cvar.wait(started).unwrap();
+            }
+        }
+        fn notify(&self) {
+            *self.lock.lock().unwrap() = true;
+            self.cvar.notify_one();
+        }
+    }
+
+    let ready = Arc::new(Pair {
+        lock: Mutex::new(false),
+        cvar: Condvar::new(),
+    });
+    let done = Arc::new(Pair {
+        lock: Mutex::new(false),
+        cvar: Condvar::new(),
+    });
+    let (ready1, done1) = (ready.clone(), done.clone());
+    let th = thread::spawn(move || {
+        ready1.wait();
+        done1.wait();
+    });
+    ready.notify();
+    done.notify();
+    th.join().unwrap();
+}
+
+fn main() {
+    std_two_mutexes();
+    parking_lot_two_mutexes();
+    std_one_mutex_per_condvar();
+}
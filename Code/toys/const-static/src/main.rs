@@ -0,0 +1,46 @@
+//! Two const-initialized parking_lot statics of the same type without lazy wrappers.
+//! `insert` on a spawned thread and `evict` acquire them in the opposite order (a conflictlock),
+//! `refresh` holds `CACHE` while calling `touch` re-locking it (a doublelock),
+//! and `sweep` re-locks `CACHE` in a loop after releasing it (no deadlock).
+use parking_lot::Mutex;
+
+static CACHE: Mutex<Option<u64>> = Mutex::new(None);
+static STAGING: Mutex<Option<u64>> = Mutex::new(None);
+
+fn insert(value: u64) {
+    let cache = CACHE.lock();
+    let mut staging = STAGING.lock();
+    *staging = cache.map(|cached| cached + value);
+}
+
+fn evict() {
+    let mut staging = STAGING.lock();
+    let mut cache = CACHE.lock();
+    *cache = staging.take();
+}
+
+fn touch() {
+    *CACHE.lock() = Some(0);
+}
+
+fn refresh() {
+    let cache = CACHE.lock();
+    if cache.is_none() {
+        touch();
+    }
+}
+
+fn sweep() {
+    for _ in 0..3 {
+        let mut cache = CACHE.lock();
+        *cache = None;
+    }
+}
+
+fn main() {
+    let inserter = std::thread::spawn(|| insert(1));
+    evict();
+    inserter.join().unwrap();
+    refresh();
+    sweep();
+}
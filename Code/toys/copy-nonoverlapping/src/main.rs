@@ -0,0 +1,34 @@
+use std::ptr;
+
+fn copy_after_scope() {
+    let mut slot: *const i32 = ptr::null();
+    {
+        let v = vec![1];
+        let src: *const i32 = v.as_ptr();
+        unsafe {
+            ptr::copy_nonoverlapping(&src, &mut slot, 1);
+        }
+    }
+    unsafe {
+        println!("{}", *slot);
+    }
+}
+
+fn copy_method_after_drop() {
+    let mut slot: *const i32 = ptr::null();
+    let v = vec![1];
+    let src: *const i32 = v.as_ptr();
+    let src_ptr: *const *const i32 = &src;
+    unsafe {
+        src_ptr.copy_to_nonoverlapping(&mut slot, 1);
+    }
+    drop(v);
+    unsafe {
+        println!("{}", *slot);
+    }
+}
+
+fn main() {
+    copy_after_scope();
+    copy_method_after_drop();
+}
@@ -0,0 +1,109 @@
+//! A custom thread pool whose scoped work is scheduled into the pool itself.
+//! Waiting for the scoped work in a task running on the pool deadlocks
+//! when the pool is single-threaded.
+mod pool {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    type Job = Box<dyn FnOnce() + Send + 'static>;
+
+    pub struct ThreadPool {
+        sender: Mutex<Sender<Job>>,
+    }
+
+    impl ThreadPool {
+        pub fn new(num_threads: usize) -> Arc<Self> {
+            let (sender, receiver) = channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for _ in 0..num_threads {
+                let receiver = receiver.clone();
+                thread::spawn(move || loop {
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                });
+            }
+            Arc::new(Self {
+                sender: Mutex::new(sender),
+            })
+        }
+
+        fn execute(&self, job: Job) {
+            self.sender.lock().unwrap().send(job).unwrap();
+        }
+
+        /// Run `f` on the pool and wait for its result.
+        pub fn install<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce() -> R + Send + 'static,
+            R: Send + 'static,
+        {
+            let (sender, receiver) = channel();
+            self.execute(Box::new(move || {
+                let _ = sender.send(f());
+            }));
+            receiver.recv().unwrap()
+        }
+
+        /// Schedule the jobs spawned by `f` into the pool.
+        pub fn scope<F>(self: &Arc<Self>, f: F) -> ScopeHandle
+        where
+            F: FnOnce(&mut Vec<Job>),
+        {
+            let mut jobs = Vec::new();
+            f(&mut jobs);
+            let (sender, receiver) = channel();
+            let num_jobs = jobs.len();
+            for job in jobs {
+                let sender = sender.clone();
+                self.execute(Box::new(move || {
+                    job();
+                    let _ = sender.send(());
+                }));
+            }
+            ScopeHandle { receiver, num_jobs }
+        }
+    }
+
+    pub struct ScopeHandle {
+        receiver: Receiver<()>,
+        num_jobs: usize,
+    }
+
+    impl ScopeHandle {
+        /// Block until all the jobs in the scope complete.
+        pub fn wait(self) {
+            for _ in 0..self.num_jobs {
+                self.receiver.recv().unwrap();
+            }
+        }
+    }
+}
+
+use pool::ThreadPool;
+use std::sync::Arc;
+
+fn sum_in_scope(pool: &Arc<ThreadPool>) {
+    pool.scope(|jobs| {
+        jobs.push(Box::new(|| println!("job 1")));
+        jobs.push(Box::new(|| println!("job 2")));
+    })
+    .wait();
+}
+
+fn main() {
+    let pool = ThreadPool::new(1);
+    // Fine: waiting on the pool work outside the pool.
+    sum_in_scope(&pool);
+    let pool2 = pool.clone();
+    // Deadlock: the only thread of the pool waits for the jobs queued behind itself.
+    pool.install(move || sum_in_scope(&pool2));
+    let pool3 = pool.clone();
+    // Fine: no waiting inside the pool.
+    pool.install(move || {
+        let _handle = pool3.scope(|jobs| jobs.push(Box::new(|| println!("job 3"))));
+    });
+}
@@ -0,0 +1,38 @@
+use crossbeam_utils::sync::ShardedLock;
+use dashmap::DashMap;
+
+struct Cache {
+    map: DashMap<u32, String>,
+}
+
+impl Cache {
+    fn bump(&self, key: u32) {
+        let value = self.map.get(&key).unwrap();
+        // May lock the shard of `key` again.
+        if let Some(mut other) = self.map.get_mut(&(key + 1)) {
+            other.push_str(&value);
+        }
+    }
+
+    fn read_twice(&self, key: u32) -> usize {
+        let a = self.map.get(&key).unwrap();
+        let b = self.map.get(&(key + 1)).unwrap();
+        a.len() + b.len()
+    }
+}
+
+fn sharded_lock(lock: &ShardedLock<u32>) {
+    let read = lock.read().unwrap();
+    *lock.write().unwrap() += *read;
+}
+
+fn main() {
+    let cache = Cache {
+        map: DashMap::new(),
+    };
+    cache.map.insert(1, "a".to_owned());
+    cache.map.insert(2, "b".to_owned());
+    cache.bump(1);
+    println!("{}", cache.read_twice(1));
+    sharded_lock(&ShardedLock::new(1));
+}
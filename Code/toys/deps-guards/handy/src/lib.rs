@@ -0,0 +1,24 @@
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+pub trait HandyRwLock<T> {
+    fn wl(&self) -> RwLockWriteGuard<'_, T>;
+    fn rl(&self) -> RwLockReadGuard<'_, T>;
+    fn update_with<F: FnOnce(&T) -> T>(&self, f: F);
+}
+
+impl<T> HandyRwLock<T> for RwLock<T> {
+    fn wl(&self) -> RwLockWriteGuard<'_, T> {
+        self.write().unwrap()
+    }
+
+    fn rl(&self) -> RwLockReadGuard<'_, T> {
+        self.read().unwrap()
+    }
+
+    // The read lock is still held when acquiring the write lock.
+    fn update_with<F: FnOnce(&T) -> T>(&self, f: F) {
+        let old = self.read().unwrap();
+        let new = f(&old);
+        *self.write().unwrap() = new;
+    }
+}
@@ -0,0 +1,25 @@
+use handy::HandyRwLock;
+use std::sync::RwLock;
+
+struct Foo {
+    inner: RwLock<i32>,
+}
+
+impl Foo {
+    fn new() -> Self {
+        Self {
+            inner: RwLock::new(1),
+        }
+    }
+
+    // DoubleLock inside the generic `handy::update_with` instantiated in this crate,
+    // only detected with `--analyze-deps-guards`.
+    fn foo(&self) {
+        self.inner.update_with(|v| *v + 1);
+    }
+}
+
+fn main() {
+    let f = Foo::new();
+    f.foo();
+}
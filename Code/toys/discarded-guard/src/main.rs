@@ -0,0 +1,38 @@
+//! `let _ = ..` drops the lockguard at once, so the lock is released right after the acquisition
+//! (reported as DiscardedGuard), while `let _guard = ..` holds it to the end of the scope.
+//! rustc denies `let _ = ..` on std locks (`let_underscore_lock`) but not on parking_lot locks.
+#![allow(let_underscore_lock)]
+use std::sync::Mutex;
+
+struct Counter {
+    std_count: Mutex<u64>,
+    pl_count: parking_lot::Mutex<u64>,
+}
+
+impl Counter {
+    /// Meant to exclude the other writers while resetting, but the locks are released at once:
+    /// DiscardedGuard, and no doublelock with the locks acquired later.
+    fn reset_discarded(&self) {
+        let _ = self.std_count.lock();
+        let _ = self.pl_count.lock();
+        *self.std_count.lock().unwrap() = 0;
+        *self.pl_count.lock() = 0;
+    }
+
+    /// The locks are held to the end of the scope: doublelock with the locks acquired later.
+    fn reset_held(&self) {
+        let _std_guard = self.std_count.lock().unwrap();
+        let _pl_guard = self.pl_count.lock();
+        *self.std_count.lock().unwrap() = 0;
+        *self.pl_count.lock() = 0;
+    }
+}
+
+fn main() {
+    let counter = Counter {
+        std_count: Mutex::new(1),
+        pl_count: parking_lot::Mutex::new(1),
+    };
+    counter.reset_discarded();
+    counter.reset_held();
+}
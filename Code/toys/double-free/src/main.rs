@@ -0,0 +1,47 @@
+//! Reconstruct the ownership leaked by `Box::into_raw` twice.
+struct Node {
+    val: u32,
+}
+
+/// Reconstructed once in the error path and again in the success path.
+fn double_from_raw(val: u32) -> u32 {
+    let p = Box::into_raw(Box::new(Node { val }));
+    if val == 0 {
+        // Forgets to return after freeing the node.
+        unsafe { drop(Box::from_raw(p)) };
+    }
+    let node = unsafe { Box::from_raw(p) };
+    node.val
+}
+
+/// Reconstructed on exclusive paths, which is safe.
+fn single_from_raw(val: u32) -> u32 {
+    let p = Box::into_raw(Box::new(Node { val }));
+    if val == 0 {
+        unsafe { drop(Box::from_raw(p)) };
+        return 0;
+    }
+    let node = unsafe { Box::from_raw(p) };
+    node.val
+}
+
+fn release(p: *mut Node) {
+    unsafe { drop(Box::from_raw(p)) };
+}
+
+/// Reconstructed by `release` and then used.
+fn cross_function(val: u32) -> u32 {
+    let p = Box::into_raw(Box::new(Node { val }));
+    if val == 0 {
+        release(p);
+    }
+    let val = unsafe { (*p).val };
+    release(p);
+    val
+}
+
+fn main() {
+    println!("{}", double_from_raw(1));
+    println!("{}", single_from_raw(1));
+    println!("{}", cross_function(1));
+}
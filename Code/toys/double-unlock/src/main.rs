@@ -0,0 +1,43 @@
+//! `force_unlock` unlocks the lock without its lockguard, so the lockguard still alive
+//! unlocks it again when dropped (reported as DoubleUnlock).
+//! Moving the lockguard into `ManuallyDrop` (or `mem::forget`-ing it) first is the correct raw-lock pattern.
+use std::mem::{self, ManuallyDrop};
+
+use parking_lot::Mutex;
+
+struct Counter {
+    count: Mutex<u64>,
+}
+
+impl Counter {
+    /// DoubleUnlock: `guard` is dropped at the end of the scope after the lock is force-unlocked.
+    fn incr_double_unlock(&self) {
+        let mut guard = self.count.lock();
+        *guard += 1;
+        unsafe { self.count.force_unlock() };
+    }
+
+    /// No report: the lockguard is never dropped, and the lock is unlocked exactly once.
+    fn incr_manually_drop(&self) {
+        let mut guard = ManuallyDrop::new(self.count.lock());
+        **guard += 1;
+        unsafe { self.count.force_unlock() };
+    }
+
+    /// No report: the same with `mem::forget`.
+    fn incr_forget(&self) {
+        let mut guard = self.count.lock();
+        *guard += 1;
+        mem::forget(guard);
+        unsafe { self.count.force_unlock() };
+    }
+}
+
+fn main() {
+    let counter = Counter {
+        count: Mutex::new(0),
+    };
+    counter.incr_double_unlock();
+    counter.incr_manually_drop();
+    counter.incr_forget();
+}
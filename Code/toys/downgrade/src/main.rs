@@ -0,0 +1,28 @@
+//! parking_lot `RwLockWriteGuard::downgrade`, which consumes a write guard for a read guard.
+//! `publish_then_read` reads `CONFIG` again (recursively) after downgrading its write (no deadlock),
+//! while `publish_then_write` writes `CONFIG` again after downgrading (a doublelock).
+use parking_lot::{RwLock, RwLockWriteGuard};
+
+static CONFIG: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+fn publish_then_read(entry: &str) -> usize {
+    let mut config = CONFIG.write();
+    config.push(entry.to_owned());
+    let config = RwLockWriteGuard::downgrade(config);
+    let len = CONFIG.read_recursive().len();
+    len + config.len()
+}
+
+fn publish_then_write(entry: &str) {
+    let mut config = CONFIG.write();
+    config.push(entry.to_owned());
+    let config = RwLockWriteGuard::downgrade(config);
+    if config.is_empty() {
+        CONFIG.write().push(entry.to_owned());
+    }
+}
+
+fn main() {
+    publish_then_read("a");
+    publish_then_write("b");
+}
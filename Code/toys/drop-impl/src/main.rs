@@ -0,0 +1,59 @@
+//! `Session::drop` unregisters the session, locking `SESSIONS`.
+//! Dropping a `Session` while `SESSIONS` is held locks it again in the drop glue (reported as DoubleLock).
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+static SESSIONS: Mutex<Option<HashMap<u64, String>>> = Mutex::new(None);
+
+struct Session {
+    id: u64,
+}
+
+impl Session {
+    fn open(id: u64, user: &str) -> Session {
+        SESSIONS
+            .lock()
+            .unwrap()
+            .get_or_insert_with(HashMap::new)
+            .insert(id, user.to_owned());
+        Session { id }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(sessions) = SESSIONS.lock().unwrap().as_mut() {
+            sessions.remove(&self.id);
+        }
+    }
+}
+
+/// DoubleLock: the expired session is dropped at the end of the scope before `sessions` is released,
+/// since it is declared later.
+fn expire(session: Session) -> usize {
+    let sessions = SESSIONS.lock().unwrap();
+    let _expired = session;
+    sessions.as_ref().map_or(0, HashMap::len)
+}
+
+/// DoubleLock: the old session is dropped by the assignment while `sessions` is held.
+fn renew(session: &mut Session, id: u64) {
+    let sessions = SESSIONS.lock().unwrap();
+    if sessions.as_ref().map_or(false, |sessions| sessions.contains_key(&id)) {
+        *session = Session { id };
+    }
+}
+
+/// No deadlock: `sessions` is released before the session is dropped.
+fn close(session: Session) {
+    let count = SESSIONS.lock().unwrap().as_ref().map_or(0, HashMap::len);
+    println!("closing one of {} sessions", count);
+    drop(session);
+}
+
+fn main() {
+    let mut session = Session::open(1, "alice");
+    renew(&mut session, 2);
+    println!("{}", expire(session));
+    close(Session::open(3, "bob"));
+}
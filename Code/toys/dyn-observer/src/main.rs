@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+
+trait Observer {
+    fn notify(&self, counter: &Counter);
+}
+
+struct Counter {
+    count: Mutex<i32>,
+    observer: Box<dyn Observer>,
+}
+
+impl Counter {
+    fn incr(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count += 1;
+        // The observer calls back into `self.get()` through dynamic dispatch,
+        // which locks `self.count` again.
+        self.observer.notify(self);
+    }
+
+    fn get(&self) -> i32 {
+        *self.count.lock().unwrap()
+    }
+}
+
+struct Logger;
+
+impl Observer for Logger {
+    fn notify(&self, counter: &Counter) {
+        println!("count: {}", counter.get());
+    }
+}
+
+struct Silent;
+
+impl Observer for Silent {
+    fn notify(&self, _counter: &Counter) {}
+}
+
+fn main() {
+    let counter = Counter {
+        count: Mutex::new(0),
+        observer: Box::new(Logger),
+    };
+    counter.incr();
+    let silent = Counter {
+        count: Mutex::new(0),
+        observer: Box::new(Silent),
+    };
+    silent.incr();
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+trait Plugin: Send + Sync {
+    fn process(&self, state: &State);
+}
+
+#[derive(Default)]
+struct State {
+    entries: Vec<String>,
+}
+
+struct Service {
+    state: RwLock<State>,
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Service {
+    fn run(&self) {
+        for plugin in &self.plugins {
+            // The read guard is live during the dyn call.
+            plugin.process(&*self.state.read().unwrap());
+        }
+    }
+}
+
+/// The services by their ids.
+static SERVICES: OnceLock<Mutex<HashMap<u32, Arc<Service>>>> = OnceLock::new();
+
+fn service(id: u32) -> Option<Arc<Service>> {
+    SERVICES.get()?.lock().unwrap().get(&id).cloned()
+}
+
+/// Navigates back to the owning service through a stored handle (its id),
+/// and writes `Service::state` while the caller holds its read guard.
+struct Recorder {
+    service: u32,
+}
+
+impl Plugin for Recorder {
+    fn process(&self, state: &State) {
+        if let Some(service) = service(self.service) {
+            let mut entries = service.state.write().unwrap();
+            entries.entries.push(format!("{} entries", state.entries.len()));
+        }
+    }
+}
+
+/// Another struct with a lock of the same type, not the one held by the caller.
+struct Audit {
+    state: RwLock<State>,
+}
+
+struct Auditor {
+    audit: Arc<Audit>,
+}
+
+impl Plugin for Auditor {
+    fn process(&self, state: &State) {
+        let mut audit = self.audit.state.write().unwrap();
+        audit.entries.extend(state.entries.iter().cloned());
+    }
+}
+
+fn main() {
+    let audit = Arc::new(Audit {
+        state: RwLock::new(State::default()),
+    });
+    let service = Arc::new(Service {
+        state: RwLock::new(State::default()),
+        plugins: vec![
+            Box::new(Recorder { service: 1 }),
+            Box::new(Auditor { audit }),
+        ],
+    });
+    SERVICES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .insert(1, service.clone());
+    service.run();
+}
@@ -0,0 +1,71 @@
+use std::mem::ManuallyDrop;
+use std::sync::Mutex;
+
+struct Cache {
+    data: Mutex<Vec<i32>>,
+}
+
+impl Cache {
+    /// The guard is unlocked early by `drop_in_place`, so relocking is not a doublelock.
+    fn drop_in_place_relock(&self) {
+        let mut guard = self.data.lock().unwrap();
+        guard.push(1);
+        unsafe {
+            std::ptr::drop_in_place(&mut guard as *mut _);
+        }
+        self.data.lock().unwrap().push(2);
+        std::mem::forget(guard);
+    }
+
+    /// The guard is unlocked early by `ManuallyDrop::drop`, so relocking is not a doublelock.
+    fn manually_drop_relock(&self) {
+        let mut guard = ManuallyDrop::new(self.data.lock().unwrap());
+        guard.push(1);
+        unsafe {
+            ManuallyDrop::drop(&mut guard);
+        }
+        self.data.lock().unwrap().push(2);
+    }
+
+    /// The data is still used through a raw ptr after the guard is unlocked by `drop_in_place`.
+    fn drop_in_place_use_after_unlock(&self) {
+        let mut guard = self.data.lock().unwrap();
+        let inner: *const Vec<i32> = &*guard;
+        unsafe {
+            std::ptr::drop_in_place(&mut guard as *mut _);
+        }
+        println!("{}", unsafe { (*inner).len() });
+        std::mem::forget(guard);
+    }
+
+    /// The data is still used through a raw ptr after the guard is unlocked by `ManuallyDrop::drop`.
+    fn manually_drop_use_after_unlock(&self) {
+        let mut guard = ManuallyDrop::new(self.data.lock().unwrap());
+        let inner: *const Vec<i32> = &**guard;
+        unsafe {
+            ManuallyDrop::drop(&mut guard);
+        }
+        println!("{}", unsafe { (*inner).len() });
+    }
+
+    /// ManuallyDrop keeps the guard alive until the end, so relocking is a doublelock.
+    fn manually_drop_doublelock(&self) {
+        let mut guard = ManuallyDrop::new(self.data.lock().unwrap());
+        guard.push(1);
+        self.data.lock().unwrap().push(2);
+        unsafe {
+            ManuallyDrop::drop(&mut guard);
+        }
+    }
+}
+
+fn main() {
+    let cache = Cache {
+        data: Mutex::new(Vec::new()),
+    };
+    cache.drop_in_place_relock();
+    cache.manually_drop_relock();
+    cache.drop_in_place_use_after_unlock();
+    cache.manually_drop_use_after_unlock();
+    cache.manually_drop_doublelock();
+}
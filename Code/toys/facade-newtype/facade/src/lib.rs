@@ -0,0 +1,81 @@
+pub mod sync {
+    use parking_lot::lock_api::RawMutex as _;
+    use std::cell::UnsafeCell;
+    use std::ops::{Deref, DerefMut};
+
+    /// A re-export and a type alias are recognized as parking_lot locks.
+    pub use parking_lot::Mutex as ReexportedMutex;
+    pub type AliasedMutex<T> = parking_lot::Mutex<T>;
+
+    /// A newtype whose `lock()` simply forwards to parking_lot.
+    pub struct Mutex<T>(parking_lot::Mutex<T>);
+
+    pub struct Locked<'a, T>(parking_lot::MutexGuard<'a, T>);
+
+    impl<T> Mutex<T> {
+        pub fn new(data: T) -> Self {
+            Self(parking_lot::Mutex::new(data))
+        }
+
+        pub fn lock(&self) -> Locked<'_, T> {
+            Locked(self.0.lock())
+        }
+    }
+
+    impl<'a, T> Deref for Locked<'a, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            &self.0
+        }
+    }
+
+    impl<'a, T> DerefMut for Locked<'a, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            &mut self.0
+        }
+    }
+
+    /// A newtype built on the raw lock, whose guard wraps no lockguard.
+    /// It needs `--guard-newtype facade::sync::RawLocked=ParkingLotMutex`.
+    pub struct RawMutex<T> {
+        raw: parking_lot::RawMutex,
+        data: UnsafeCell<T>,
+    }
+
+    unsafe impl<T: Send> Sync for RawMutex<T> {}
+
+    pub struct RawLocked<'a, T>(&'a RawMutex<T>);
+
+    impl<T> RawMutex<T> {
+        pub fn new(data: T) -> Self {
+            Self {
+                raw: parking_lot::RawMutex::INIT,
+                data: UnsafeCell::new(data),
+            }
+        }
+
+        pub fn lock(&self) -> RawLocked<'_, T> {
+            self.raw.lock();
+            RawLocked(self)
+        }
+    }
+
+    impl<'a, T> Deref for RawLocked<'a, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            unsafe { &*self.0.data.get() }
+        }
+    }
+
+    impl<'a, T> DerefMut for RawLocked<'a, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            unsafe { &mut *self.0.data.get() }
+        }
+    }
+
+    impl<'a, T> Drop for RawLocked<'a, T> {
+        fn drop(&mut self) {
+            unsafe { self.0.raw.unlock() };
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use facade::sync::{AliasedMutex, Mutex, RawMutex, ReexportedMutex};
+
+struct Facade {
+    reexported: ReexportedMutex<i32>,
+    aliased: AliasedMutex<i32>,
+}
+
+impl Facade {
+    fn reexported_twice(&self) {
+        let _a = self.reexported.lock();
+        // Deadlock
+        let _b = self.reexported.lock();
+    }
+
+    fn aliased_twice(&self) {
+        let _a = self.aliased.lock();
+        // Deadlock
+        let _b = self.aliased.lock();
+    }
+}
+
+struct Counter {
+    count: Mutex<i32>,
+}
+
+impl Counter {
+    fn get(&self) -> i32 {
+        *self.count.lock()
+    }
+
+    fn incr(&self) {
+        let mut count = self.count.lock();
+        // Deadlock: `self.count` is locked again through the facade newtype.
+        *count += self.get();
+    }
+}
+
+struct RawCounter {
+    count: RawMutex<i32>,
+}
+
+impl RawCounter {
+    fn get(&self) -> i32 {
+        *self.count.lock()
+    }
+
+    fn incr(&self) {
+        let mut count = self.count.lock();
+        // Deadlock: only reported with `--guard-newtype facade::sync::RawLocked=ParkingLotMutex`.
+        *count += self.get();
+    }
+}
+
+fn main() {
+    let facade = Facade {
+        reexported: ReexportedMutex::new(1),
+        aliased: AliasedMutex::new(1),
+    };
+    facade.reexported_twice();
+    facade.aliased_twice();
+    let counter = Counter {
+        count: Mutex::new(1),
+    };
+    counter.incr();
+    let raw_counter = RawCounter {
+        count: RawMutex::new(1),
+    };
+    raw_counter.incr();
+}
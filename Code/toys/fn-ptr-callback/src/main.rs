@@ -0,0 +1,40 @@
+use std::sync::Mutex;
+
+struct Counter {
+    count: Mutex<i32>,
+    on_change: fn(&Counter),
+}
+
+impl Counter {
+    /// The callback stored in `on_change` locks `self.count` again.
+    fn incr(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count += 1;
+        (self.on_change)(self);
+    }
+
+    /// The fn ptr may point to either `log_count` or `reset_count`,
+    /// both of which lock `self.count` again.
+    fn notify(&self, verbose: bool) {
+        let callback: fn(&Counter) = if verbose { log_count } else { reset_count };
+        let _count = self.count.lock().unwrap();
+        callback(self);
+    }
+}
+
+fn log_count(counter: &Counter) {
+    println!("count: {}", *counter.count.lock().unwrap());
+}
+
+fn reset_count(counter: &Counter) {
+    *counter.count.lock().unwrap() = 0;
+}
+
+fn main() {
+    let counter = Counter {
+        count: Mutex::new(0),
+        on_change: log_count,
+    };
+    counter.incr();
+    counter.notify(std::env::args().count() > 1);
+}
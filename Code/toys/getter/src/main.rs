@@ -0,0 +1,60 @@
+use std::sync::Mutex;
+
+struct Inner {
+    mu: Mutex<i32>,
+}
+
+impl Inner {
+    fn mu(&self) -> &Mutex<i32> {
+        &self.mu
+    }
+}
+
+struct S {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+    inner: Inner,
+}
+
+impl S {
+    fn a(&self) -> &Mutex<i32> {
+        &self.a
+    }
+
+    fn b(&self) -> &Mutex<i32> {
+        &self.b
+    }
+
+    // Not a doublelock: the getters return different fields.
+    fn a_then_b(&self) {
+        let _a = self.a().lock().unwrap();
+        let _b = self.b().lock().unwrap();
+    }
+
+    // A doublelock.
+    fn a_then_a(&self) {
+        let _a = self.a().lock().unwrap();
+        *self.a.lock().unwrap() += 1;
+    }
+
+    // A doublelock with `lock_inner`.
+    fn inner_then_inner(&self) {
+        let _a = self.inner.mu().lock().unwrap();
+        self.lock_inner();
+    }
+
+    fn lock_inner(&self) {
+        *self.inner.mu.lock().unwrap() += 1;
+    }
+}
+
+fn main() {
+    let s = S {
+        a: Mutex::new(1),
+        b: Mutex::new(2),
+        inner: Inner { mu: Mutex::new(3) },
+    };
+    s.a_then_b();
+    s.a_then_a();
+    s.inner_then_inner();
+}
@@ -0,0 +1,32 @@
+//! Lockguards leaked by `std::mem::forget`, `ManuallyDrop::new`, or `Box::leak` are never dropped,
+//! so their locks are never released (reported as LockGuardLeaked),
+//! except a `ManuallyDrop` returned to the caller, which unlocks it manually.
+use std::mem::ManuallyDrop;
+use std::sync::{Mutex, MutexGuard};
+
+static STATE: Mutex<i32> = Mutex::new(0);
+
+fn pause() {
+    let guard = STATE.lock().unwrap();
+    std::mem::forget(guard);
+}
+
+fn pause_manually() {
+    let _guard = ManuallyDrop::new(STATE.lock().unwrap());
+}
+
+fn pause_forever() -> &'static mut i32 {
+    &mut **Box::leak(Box::new(STATE.lock().unwrap()))
+}
+
+fn raw_lock() -> ManuallyDrop<MutexGuard<'static, i32>> {
+    ManuallyDrop::new(STATE.lock().unwrap())
+}
+
+fn main() {
+    pause();
+    pause_manually();
+    *pause_forever() += 1;
+    let mut guard = raw_lock();
+    unsafe { ManuallyDrop::drop(&mut guard) };
+}
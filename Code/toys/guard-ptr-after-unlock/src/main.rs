@@ -0,0 +1,68 @@
+//! A raw ptr derived from the data of a lockguard outlives the lockguard,
+//! so dereferencing it after the unlock races with the other holders of the lock (reported as UseAfterFree).
+use std::sync::{Mutex, RwLock};
+
+struct Stats {
+    hits: u64,
+    misses: u64,
+}
+
+/// UseAfterFree: `p` is read after `guard` is dropped.
+fn read_after_drop(m: &Mutex<u64>) -> u64 {
+    let guard = m.lock().unwrap();
+    let p: *const u64 = &*guard;
+    drop(guard);
+    unsafe { *p }
+}
+
+/// UseAfterFree: `hits` points into a field of the data of `guard`.
+fn field_after_drop(m: &Mutex<Stats>) -> u64 {
+    let mut guard = m.lock().unwrap();
+    let hits: *mut u64 = &mut guard.hits;
+    drop(guard);
+    unsafe {
+        *hits += 1;
+        *hits
+    }
+}
+
+/// UseAfterFree: `p` is written after the write lockguard is dropped at the end of the block.
+fn write_after_scope(l: &RwLock<Vec<u8>>) {
+    let p: *mut Vec<u8> = {
+        let mut guard = l.write().unwrap();
+        &mut *guard
+    };
+    unsafe { (*p).push(1) }
+}
+
+/// No report: `p` is re-derived from the second lockguard before the use.
+fn relock(m: &Mutex<Stats>) -> u64 {
+    let guard = m.lock().unwrap();
+    let mut p: *const Stats = &*guard;
+    drop(guard);
+    let guard = m.lock().unwrap();
+    p = &*guard;
+    let misses = unsafe { (*p).misses };
+    drop(guard);
+    misses
+}
+
+/// No report: `p` is only used while `guard` is alive.
+fn read_before_drop(m: &Mutex<u64>) -> u64 {
+    let guard = m.lock().unwrap();
+    let p: *const u64 = &*guard;
+    let n = unsafe { *p };
+    drop(guard);
+    n
+}
+
+fn main() {
+    let m = Mutex::new(1);
+    let stats = Mutex::new(Stats { hits: 0, misses: 0 });
+    let l = RwLock::new(Vec::new());
+    read_after_drop(&m);
+    field_after_drop(&stats);
+    write_after_scope(&l);
+    relock(&stats);
+    read_before_drop(&m);
+}
@@ -0,0 +1,41 @@
+//! `handle` acquires `REQUESTS` on every path and `self.cache` only on a cache miss,
+//! reported by `cargo lockbud -k deadlock --hot-path-fn handle`.
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+static REQUESTS: Mutex<u64> = Mutex::new(0);
+
+struct Server {
+    cache: Mutex<HashMap<String, String>>,
+}
+
+impl Server {
+    fn count_request(&self) {
+        *REQUESTS.lock().unwrap() += 1;
+    }
+
+    fn lookup(&self, key: &str) -> String {
+        key.to_uppercase()
+    }
+
+    fn handle(&self, key: &str, cached: bool) -> String {
+        self.count_request();
+        if cached {
+            return key.to_owned();
+        }
+        let value = self.lookup(key);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), value.clone());
+        value
+    }
+}
+
+fn main() {
+    let server = Server {
+        cache: Mutex::new(HashMap::new()),
+    };
+    println!("{}", server.handle("a", false));
+    println!("{}", server.handle("b", true));
+}
@@ -0,0 +1,58 @@
+//! The closures passed to the combinators of `Option`, `Result`, and `Iterator` are called inline,
+//! so they run with the locks held by their callers (reported as DoubleLock).
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+struct Cache {
+    map: HashMap<u64, u64>,
+    hits: Mutex<u64>,
+}
+
+impl Cache {
+    /// DoubleLock: `Option::map` calls the closure while `hits` is held.
+    fn get(&self, key: u64) -> Option<u64> {
+        let _hits = self.hits.lock().unwrap();
+        self.map.get(&key).map(|value| {
+            *self.hits.lock().unwrap() += 1;
+            *value
+        })
+    }
+
+    /// DoubleLock: `Option::unwrap_or_else` calls the closure while `hits` is held.
+    fn get_or_zero(&self, key: u64) -> u64 {
+        let hits = self.hits.lock().unwrap();
+        let value = self.map.get(&key).copied().unwrap_or_else(|| *self.hits.lock().unwrap());
+        drop(hits);
+        value
+    }
+
+    /// DoubleLock: `Result::and_then` calls the closure while `hits` is held.
+    fn parse(&self, s: &str) -> Result<u64, std::num::ParseIntError> {
+        let _hits = self.hits.lock().unwrap();
+        s.parse::<u64>().and_then(|key| Ok(key + *self.hits.lock().unwrap()))
+    }
+
+    /// DoubleLock: `Iterator::for_each` calls the closure while `hits` is held.
+    fn count_all(&self) {
+        let _hits = self.hits.lock().unwrap();
+        self.map.keys().for_each(|_| *self.hits.lock().unwrap() += 1);
+    }
+
+    /// No deadlock: the closure runs after `hits` is released.
+    fn get_released(&self, key: u64) -> Option<u64> {
+        *self.hits.lock().unwrap() += 1;
+        self.map.get(&key).map(|value| *value + *self.hits.lock().unwrap())
+    }
+}
+
+fn main() {
+    let cache = Cache {
+        map: HashMap::new(),
+        hits: Mutex::new(0),
+    };
+    cache.get(1);
+    cache.get_or_zero(1);
+    let _ = cache.parse("1");
+    cache.count_all();
+    cache.get_released(1);
+}
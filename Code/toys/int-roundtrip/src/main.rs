@@ -0,0 +1,26 @@
+use std::mem;
+
+fn usize_roundtrip() {
+    let s = String::from("freed");
+    let addr = &s as *const String as usize;
+    drop(s);
+    let p = addr as *const String;
+    unsafe {
+        println!("{}", (*p).len());
+    }
+}
+
+fn transmute_roundtrip() {
+    let s = String::from("freed");
+    let addr: usize = unsafe { mem::transmute(&s as *const String) };
+    drop(s);
+    let p: *const String = unsafe { mem::transmute(addr) };
+    unsafe {
+        println!("{}", (*p).len());
+    }
+}
+
+fn main() {
+    usize_roundtrip();
+    transmute_roundtrip();
+}
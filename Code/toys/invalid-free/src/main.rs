@@ -32,6 +32,20 @@ fn assume_ptr_write_fp() {
     }
 }
 
+fn assume_ptr_write_partial() {
+    struct Obj {
+        a: Vec<i32>,
+        b: bool,
+    }
+
+    let mut uninit = std::mem::MaybeUninit::<Obj>::uninit();
+    unsafe {
+        let ptr = uninit.as_mut_ptr();
+        addr_of_mut!((*ptr).b).write(true);
+        uninit.assume_init();
+    }
+}
+
 fn assume() {
     let uninit = std::mem::MaybeUninit::<Vec<i32>>::uninit();
     unsafe {
@@ -42,6 +56,7 @@ fn assume() {
 fn main() {
     assume_write_fp();
     assume_ptr_write_fp();
+    assume_ptr_write_partial();
     assume();
     uninit();
 }
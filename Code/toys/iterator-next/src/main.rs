@@ -0,0 +1,84 @@
+use std::sync::{Arc, Mutex};
+
+struct Queue {
+    items: Mutex<Vec<i32>>,
+}
+
+/// Pops the items of the queue, locking it on each `next()`.
+struct Drain<'a> {
+    queue: &'a Queue,
+}
+
+impl<'a> Iterator for Drain<'a> {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        self.queue.items.lock().unwrap().pop()
+    }
+}
+
+impl<'a> IntoIterator for &'a Queue {
+    type Item = i32;
+    type IntoIter = Drain<'a>;
+
+    fn into_iter(self) -> Drain<'a> {
+        Drain { queue: self }
+    }
+}
+
+impl Queue {
+    fn drain(&self) -> Drain<'_> {
+        Drain { queue: self }
+    }
+
+    /// Deadlocks on the first `next()`: the queue is already locked.
+    fn sum_under_lock(&self) -> i32 {
+        let items = self.items.lock().unwrap();
+        let mut sum = items.len() as i32;
+        for item in self.drain() {
+            sum += item;
+        }
+        sum
+    }
+
+    /// Deadlocks on the first `next()` through `<&mut Drain as Iterator>::next`.
+    fn sum_by_ref_under_lock(&self) -> i32 {
+        let mut drain = self.drain();
+        let items = self.items.lock().unwrap();
+        let mut sum = items.len() as i32;
+        for item in &mut drain {
+            sum += item;
+        }
+        sum
+    }
+
+    /// Deadlocks on the first `next()` of `<&Queue as IntoIterator>::IntoIter`.
+    fn sum_into_iter_under_lock(&self) -> i32 {
+        let items = self.items.lock().unwrap();
+        let mut sum = items.len() as i32;
+        for item in self {
+            sum += item;
+        }
+        sum
+    }
+
+    /// No deadlock: the guard is dropped before the loop.
+    fn sum_after_unlock(&self) -> i32 {
+        let len = self.items.lock().unwrap().len() as i32;
+        let mut sum = len;
+        for item in self.drain() {
+            sum += item;
+        }
+        sum
+    }
+}
+
+fn main() {
+    let queue = Arc::new(Queue {
+        items: Mutex::new(vec![1, 2, 3]),
+    });
+    println!("{}", queue.sum_after_unlock());
+    println!("{}", queue.sum_under_lock());
+    println!("{}", queue.sum_by_ref_under_lock());
+    println!("{}", queue.sum_into_iter_under_lock());
+}
@@ -0,0 +1,94 @@
+use std::panic;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+fn one_closure_one_caller() {
+    let lock_a1 = Arc::new(Mutex::new(1));
+    let lock_a2 = lock_a1.clone();
+    let lock_b1 = Arc::new(Mutex::new(true));
+    let lock_b2 = lock_b1.clone();
+    {
+        let _b = lock_b1.lock().unwrap();
+        let _a = lock_a1.lock().unwrap();
+    }
+    let th = thread::Builder::new()
+        .name("one".to_owned())
+        .spawn(move || {
+            let _a = lock_a2.lock().unwrap();
+            let _b = lock_b2.lock().unwrap();
+        })
+        .unwrap();
+    th.join().unwrap();
+}
+
+fn two_closures() {
+    let lock_a1 = Arc::new(Mutex::new(1));
+    let lock_a2 = lock_a1.clone();
+    let lock_b1 = Arc::new(Mutex::new(true));
+    let lock_b2 = lock_b1.clone();
+    let th1 = thread::Builder::new()
+        .name("first".to_owned())
+        .spawn(move || {
+            let _b = lock_b1.lock().unwrap();
+            let _a = lock_a1.lock().unwrap();
+        })
+        .unwrap();
+    let th2 = thread::Builder::new()
+        .name("second".to_owned())
+        .spawn(move || {
+            let _a = lock_a2.lock().unwrap();
+            let _b = lock_b2.lock().unwrap();
+        })
+        .unwrap();
+    th1.join().unwrap();
+    th2.join().unwrap();
+}
+
+fn scoped_closures() {
+    let lock_a = Mutex::new(1);
+    let lock_b = Mutex::new(true);
+    thread::scope(|s| {
+        thread::Builder::new()
+            .name("first".to_owned())
+            .spawn_scoped(s, || {
+                let _b = lock_b.lock().unwrap();
+                let _a = lock_a.lock().unwrap();
+            })
+            .unwrap();
+        thread::Builder::new()
+            .name("second".to_owned())
+            .spawn_scoped(s, || {
+                let _a = lock_a.lock().unwrap();
+                let _b = lock_b.lock().unwrap();
+            })
+            .unwrap();
+    });
+}
+
+/// The closure of `catch_unwind` runs in the caller while `_a` is held.
+fn catch_unwind_holding_lock() {
+    let lock_a = Mutex::new(1);
+    let _a = lock_a.lock().unwrap();
+    let _ = panic::catch_unwind(|| {
+        let _a = lock_a.lock().unwrap();
+    });
+}
+
+/// The closure of `catch_unwind` runs after `_a` is dropped.
+fn catch_unwind_after_unlock() {
+    let lock_a = Mutex::new(1);
+    {
+        let _a = lock_a.lock().unwrap();
+    }
+    let _ = panic::catch_unwind(|| {
+        let _a = lock_a.lock().unwrap();
+    });
+}
+
+fn main() {
+    one_closure_one_caller();
+    two_closures();
+    scoped_closures();
+    catch_unwind_holding_lock();
+    catch_unwind_after_unlock();
+}
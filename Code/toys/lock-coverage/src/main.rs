@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+struct Cache {
+    mu: Mutex<Vec<String>>,
+    len: AtomicUsize,
+}
+
+impl Cache {
+    fn insert(&self, key: String) {
+        let mut keys = self.mu.lock().unwrap();
+        keys.push(key);
+        self.len.store(keys.len(), Ordering::Relaxed);
+    }
+
+    fn remove(&self, key: &str) {
+        let mut keys = self.mu.lock().unwrap();
+        keys.retain(|k| k != key);
+        self.len.store(keys.len(), Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.mu.lock().unwrap().clear();
+        // The lockguard is dropped above, racing with `insert` between its push and store.
+        self.len.store(0, Ordering::Relaxed);
+    }
+
+    fn len(&self) -> usize {
+        let _keys = self.mu.lock().unwrap();
+        self.len.load(Ordering::Relaxed)
+    }
+
+    fn is_empty(&self) -> bool {
+        let _keys = self.mu.lock().unwrap();
+        self.len.load(Ordering::Relaxed) == 0
+    }
+}
+
+fn main() {
+    let cache = Arc::new(Cache {
+        mu: Mutex::new(Vec::new()),
+        len: AtomicUsize::new(0),
+    });
+    let c = cache.clone();
+    let th = thread::spawn(move || {
+        c.insert("a".to_owned());
+        c.remove("b");
+        c.clear();
+    });
+    println!("{} {}", cache.len(), cache.is_empty());
+    th.join().unwrap();
+}
@@ -0,0 +1,38 @@
+//! parking_lot mapped guards, which keep holding the locks of the guards they are mapped from.
+//! `bump_hits` locks `STATS` again while holding the guard mapped to its `hits` (a doublelock),
+//! `peek_misses` reads `CACHE` again while holding the read guard mapped to its `misses` (a doublelock with a waiting writer),
+//! and `reset_hits` drops the mapped guard before locking `STATS` again (no deadlock).
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+
+struct Stats {
+    hits: u64,
+    misses: u64,
+}
+
+static STATS: Mutex<Stats> = Mutex::new(Stats { hits: 0, misses: 0 });
+static CACHE: RwLock<Stats> = RwLock::new(Stats { hits: 0, misses: 0 });
+
+fn bump_hits() {
+    let mut hits = MutexGuard::map(STATS.lock(), |stats| &mut stats.hits);
+    *hits += 1;
+    STATS.lock().misses = 0;
+}
+
+fn peek_misses() -> u64 {
+    let misses = RwLockReadGuard::map(CACHE.read(), |stats| &stats.misses);
+    *misses + CACHE.read().hits
+}
+
+fn reset_hits() {
+    let hits = MutexGuard::try_map(STATS.lock(), |stats| Some(&mut stats.hits));
+    if let Ok(mut hits) = hits {
+        *hits = 0;
+    }
+    STATS.lock().misses = 0;
+}
+
+fn main() {
+    bump_hits();
+    peek_misses();
+    reset_hits();
+}
@@ -0,0 +1,26 @@
+use std::ptr::NonNull;
+
+fn as_ref_after_drop() {
+    let mut v = vec![1];
+    let p: NonNull<u8> = NonNull::new(v.as_mut_ptr()).unwrap().cast();
+    drop(v);
+    let q: *const u8 = unsafe { p.as_ref() };
+    unsafe {
+        println!("{}", *q);
+    }
+}
+
+fn as_mut_after_drop() {
+    let mut v = vec![1];
+    let mut p = NonNull::new(v.as_mut_ptr()).unwrap();
+    drop(v);
+    let q: *mut i32 = unsafe { p.as_mut() };
+    unsafe {
+        *q = 2;
+    }
+}
+
+fn main() {
+    as_ref_after_drop();
+    as_mut_after_drop();
+}
@@ -0,0 +1,30 @@
+use std::sync::{Mutex, OnceLock};
+
+static REGISTRY: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+static SIZE: OnceLock<u32> = OnceLock::new();
+
+fn size() -> u32 {
+    *SIZE.get_or_init(|| REGISTRY.lock().unwrap().len() as u32)
+}
+
+// `REGISTRY` is held while `size` initializes `SIZE` by locking `REGISTRY`.
+fn register(id: u32) {
+    let mut registry = REGISTRY.lock().unwrap();
+    if size() < 16 {
+        registry.push(id);
+    }
+}
+
+// `REGISTRY` is released before the initialization.
+fn register_released(id: u32) {
+    let size = size();
+    let mut registry = REGISTRY.lock().unwrap();
+    if size < 16 {
+        registry.push(id);
+    }
+}
+
+fn main() {
+    register(1);
+    register_released(2);
+}
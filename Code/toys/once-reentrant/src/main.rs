@@ -0,0 +1,41 @@
+use lazy_static::lazy_static;
+use std::sync::{Once, OnceLock};
+
+static CONFIG: OnceLock<u32> = OnceLock::new();
+static INIT: Once = Once::new();
+
+// The initializer reads `CONFIG` again through `default_config`.
+fn config() -> u32 {
+    *CONFIG.get_or_init(|| default_config() + 1)
+}
+
+fn default_config() -> u32 {
+    config()
+}
+
+fn init() {
+    INIT.call_once(init);
+}
+
+lazy_static! {
+    static ref TABLE: Vec<u32> = table();
+}
+
+// The initializer of `TABLE` derefs `TABLE`.
+fn table() -> Vec<u32> {
+    TABLE.clone()
+}
+
+static NAME: OnceLock<String> = OnceLock::new();
+
+// A different `OnceLock` is initialized inside the initializer.
+fn name() -> &'static str {
+    NAME.get_or_init(|| format!("config-{}", CONFIG.get().copied().unwrap_or(0)))
+}
+
+fn main() {
+    config();
+    init();
+    table();
+    name();
+}
@@ -0,0 +1,31 @@
+use std::sync::Mutex;
+
+struct Registry {
+    entries: Mutex<Vec<u32>>,
+    last: Option<u32>,
+}
+
+fn flush(last: Option<u32>) {
+    let _ = last.expect("flushed twice");
+}
+
+fn check(last: Option<u32>) -> u32 {
+    last.unwrap()
+}
+
+impl Drop for Registry {
+    fn drop(&mut self) {
+        // Panics if the lock is poisoned by a panicking thread.
+        self.entries.lock().unwrap().clear();
+        flush(self.last);
+        check(self.last);
+    }
+}
+
+fn main() {
+    let registry = Registry {
+        entries: Mutex::new(vec![1]),
+        last: Some(1),
+    };
+    println!("{}", check(registry.last));
+}
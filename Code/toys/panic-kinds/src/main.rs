@@ -0,0 +1,21 @@
+fn parse(s: &str, mode: u8) -> u32 {
+    match mode {
+        0 => s.parse().unwrap(),
+        1 => todo!(),
+        2 => unimplemented!("mode {}", mode),
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    println!("{}", parse("1", 0));
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_ok() {
+        assert_eq!(super::parse("1", 0), 1);
+        "2".parse::<u32>().expect("a number");
+    }
+}
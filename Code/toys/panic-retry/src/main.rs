@@ -0,0 +1,39 @@
+//! A retry on poison inside `catch_unwind`.
+//! `transfer` records into `JOURNAL` while holding `ACCOUNTS`, so a panic in `record` poisons both.
+//! The retry recovers the poisoned `JOURNAL` first and re-locks `ACCOUNTS` while holding it,
+//! in the opposite order of the attempt before the panic (a panic-retry inversion).
+use std::panic;
+use std::sync::{Mutex, PoisonError};
+
+static ACCOUNTS: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+static JOURNAL: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn record(journal: &mut Vec<String>, amount: u64) {
+    if amount == 0 {
+        panic!("empty transfer");
+    }
+    journal.push(format!("transfer {}", amount));
+}
+
+fn transfer(amount: u64) {
+    let mut accounts = ACCOUNTS.lock().unwrap();
+    let mut journal = JOURNAL.lock().unwrap();
+    record(&mut journal, amount);
+    accounts.push(amount);
+}
+
+fn transfer_with_retry(amount: u64) {
+    if panic::catch_unwind(|| transfer(amount)).is_ok() {
+        return;
+    }
+    let _ = panic::catch_unwind(|| {
+        let mut journal = JOURNAL.lock().unwrap_or_else(PoisonError::into_inner);
+        journal.push(format!("retry {}", amount));
+        let mut accounts = ACCOUNTS.lock().unwrap_or_else(PoisonError::into_inner);
+        accounts.push(amount.max(1));
+    });
+}
+
+fn main() {
+    transfer_with_retry(0);
+}
@@ -0,0 +1,39 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+struct Scores {
+    values: Mutex<Vec<u32>>,
+}
+
+impl Scores {
+    fn get(&self, i: usize) -> u32 {
+        let values = self.values.lock().unwrap();
+        // Panics with `i` out of bounds and poisons `values`.
+        values[i]
+    }
+
+    fn bump(&self, i: usize) {
+        let mut values = self.values.lock().unwrap();
+        bump_at(&mut values, i);
+    }
+
+    fn len(&self) -> usize {
+        // Only panics if another thread poisoned `values`.
+        self.values.lock().unwrap().len()
+    }
+}
+
+fn bump_at(values: &mut [u32], i: usize) {
+    values[i] += 1;
+}
+
+fn main() {
+    let scores = Arc::new(Scores {
+        values: Mutex::new(vec![1, 2, 3]),
+    });
+    let scores2 = scores.clone();
+    let handle = thread::spawn(move || scores2.get(4));
+    let _ = handle.join();
+    scores.bump(1);
+    println!("{}", scores.len());
+}
@@ -0,0 +1,32 @@
+use std::ptr;
+
+fn write_after_scope() {
+    let mut slot: *const i32 = ptr::null();
+    {
+        let v = vec![1];
+        unsafe {
+            ptr::write(&mut slot, v.as_ptr());
+        }
+    }
+    unsafe {
+        println!("{}", *slot);
+    }
+}
+
+fn write_method_after_drop() {
+    let mut slot: *const i32 = ptr::null();
+    let v = vec![1];
+    let dst: *mut *const i32 = &mut slot;
+    unsafe {
+        dst.write(v.as_ptr());
+    }
+    drop(v);
+    unsafe {
+        println!("{}", *slot);
+    }
+}
+
+fn main() {
+    write_after_scope();
+    write_method_after_drop();
+}
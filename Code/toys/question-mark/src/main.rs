@@ -0,0 +1,84 @@
+use std::sync::Mutex;
+
+#[derive(Debug)]
+struct MyError;
+
+impl<T> From<std::sync::PoisonError<T>> for MyError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        MyError
+    }
+}
+
+impl From<std::num::ParseIntError> for MyError {
+    fn from(_: std::num::ParseIntError) -> Self {
+        MyError
+    }
+}
+
+struct Foo {
+    m: Mutex<i32>,
+}
+
+impl Foo {
+    fn helper(&self) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    fn helper_opt(&self) -> Option<i32> {
+        Some(1)
+    }
+
+    fn result(&self) -> Result<(), MyError> {
+        let _g = self.m.lock().unwrap();
+        self.helper()?;
+        let _g2 = self.m.lock().unwrap();
+        Ok(())
+    }
+
+    fn result_from(&self, s: &str) -> Result<i32, MyError> {
+        let _g = self.m.lock().unwrap();
+        let n: i32 = s.parse()?;
+        self.helper()?;
+        let _g2 = self.m.lock().unwrap();
+        Ok(n)
+    }
+
+    fn lock_result(&self) -> Result<(), MyError> {
+        let _g = self.m.lock()?;
+        self.helper()?;
+        let _g2 = self.m.lock()?;
+        Ok(())
+    }
+
+    fn lock_map_err(&self) -> Result<(), MyError> {
+        let _g = self.m.lock().map_err(|_| MyError)?;
+        self.helper()?;
+        let _g2 = self.m.lock().map_err(|_| MyError)?;
+        Ok(())
+    }
+
+    fn lock_ok(&self) -> Option<i32> {
+        let g = self.m.lock().ok()?;
+        let a = self.helper_opt()?;
+        let g2 = self.m.lock().ok()?;
+        Some(*g + *g2 + a)
+    }
+
+    fn option(&self) -> Option<i32> {
+        let _g = self.m.lock().unwrap();
+        let a = self.helper_opt()?;
+        let b = self.helper_opt()?;
+        let _g2 = self.m.lock().unwrap();
+        Some(a + b)
+    }
+}
+
+fn main() {
+    let foo = Foo { m: Mutex::new(1) };
+    let _ = foo.result();
+    let _ = foo.result_from("1");
+    let _ = foo.option();
+    let _ = foo.lock_result();
+    let _ = foo.lock_map_err();
+    let _ = foo.lock_ok();
+}
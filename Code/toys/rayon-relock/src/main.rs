@@ -0,0 +1,46 @@
+use rayon::prelude::*;
+use std::sync::Mutex;
+
+struct Stats {
+    total: Mutex<u64>,
+}
+
+impl Stats {
+    // The closures run on the pool while the caller holds `total`.
+    fn add_all(&self, items: &[u64]) {
+        let _total = self.total.lock().unwrap();
+        items.par_iter().for_each(|item| {
+            *self.total.lock().unwrap() += item;
+        });
+    }
+
+    fn add_pair(&self, a: u64, b: u64) {
+        let _total = self.total.lock().unwrap();
+        rayon::join(
+            || *self.total.lock().unwrap() += a,
+            || *self.total.lock().unwrap() += b,
+        );
+    }
+
+    fn add_scoped(&self, a: u64) {
+        let _total = self.total.lock().unwrap();
+        rayon::scope(|_| *self.total.lock().unwrap() += a);
+    }
+
+    // The lock is released before the closures run.
+    fn add_all_released(&self, items: &[u64]) {
+        let sum: u64 = items.par_iter().sum();
+        items.par_iter().for_each(|_| {});
+        *self.total.lock().unwrap() += sum;
+    }
+}
+
+fn main() {
+    let stats = Stats {
+        total: Mutex::new(0),
+    };
+    stats.add_all(&[1, 2]);
+    stats.add_pair(1, 2);
+    stats.add_scoped(1);
+    stats.add_all_released(&[1, 2]);
+}
@@ -0,0 +1,39 @@
+//! parking_lot `ReentrantMutex`, which can be locked again by the thread holding it.
+//! `log_twice` locks `LOG` again through `log` (no deadlock),
+//! while `flush` on a spawned thread and `rotate` lock `LOG` and `FILE` in the opposite order (a conflictlock).
+use std::cell::RefCell;
+
+use parking_lot::{Mutex, ReentrantMutex};
+
+static LOG: ReentrantMutex<RefCell<Vec<String>>> = ReentrantMutex::new(RefCell::new(Vec::new()));
+static FILE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn log(line: &str) {
+    LOG.lock().borrow_mut().push(line.to_owned());
+}
+
+fn log_twice(line: &str) {
+    let log_guard = LOG.lock();
+    log(line);
+    log_guard.borrow_mut().push(line.to_owned());
+}
+
+fn flush() {
+    let log_guard = LOG.lock();
+    let mut file = FILE.lock();
+    file.append(&mut log_guard.borrow_mut());
+}
+
+fn rotate() {
+    let mut file = FILE.lock();
+    let log_guard = LOG.lock();
+    file.clear();
+    log_guard.borrow_mut().clear();
+}
+
+fn main() {
+    log_twice("start");
+    let flusher = std::thread::spawn(flush);
+    rotate();
+    flusher.join().unwrap();
+}
@@ -0,0 +1,67 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn borrow_then_borrow_mut() {
+    let c1 = RefCell::new(1);
+    match *c1.borrow() {
+        1 => {}
+        _ => {
+            *c1.borrow_mut() += 1;
+        }
+    };
+}
+
+fn borrow_mut_then_borrow() -> i32 {
+    let c1 = RefCell::new(1);
+    let mut a = 0;
+    match *c1.borrow_mut() {
+        1 => {
+            a = *c1.borrow();
+        }
+        _ => {}
+    };
+    a
+}
+
+fn borrow_twice() -> i32 {
+    let c1 = RefCell::new(1);
+    let g1 = c1.borrow();
+    let g2 = c1.borrow();
+    *g1 + *g2
+}
+
+fn try_borrow_mut() {
+    let c1 = RefCell::new(1);
+    let _g = c1.borrow();
+    if let Ok(mut g) = c1.try_borrow_mut() {
+        *g += 1;
+    };
+}
+
+struct Counter {
+    count: Rc<RefCell<i32>>,
+}
+
+impl Counter {
+    fn incr(&self) {
+        *self.count.borrow_mut() += 1;
+    }
+
+    fn incr_while_reading(&self) {
+        let count = self.count.borrow();
+        if *count < 10 {
+            self.incr();
+        }
+    }
+}
+
+fn main() {
+    borrow_then_borrow_mut();
+    borrow_mut_then_borrow();
+    borrow_twice();
+    try_borrow_mut();
+    let counter = Counter {
+        count: Rc::new(RefCell::new(0)),
+    };
+    counter.incr_while_reading();
+}
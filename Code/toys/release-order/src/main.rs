@@ -0,0 +1,88 @@
+use std::sync::{Mutex, MutexGuard};
+
+struct Accounts {
+    balance: u64,
+}
+
+struct Ledger {
+    entries: Vec<u64>,
+}
+
+struct Bank {
+    accounts: Mutex<Accounts>,
+    ledger: Mutex<Ledger>,
+}
+
+/// Releases the accounts before the ledger.
+struct Transfer<'a> {
+    accounts: MutexGuard<'a, Accounts>,
+    ledger: MutexGuard<'a, Ledger>,
+}
+
+/// Releases the ledger before the accounts: the fields were swapped in a refactor.
+struct Audit<'a> {
+    ledger: MutexGuard<'a, Ledger>,
+    accounts: MutexGuard<'a, Accounts>,
+}
+
+/// Releases the accounts before the ledger as `Transfer` does, no report.
+struct Deposit<'a> {
+    accounts: MutexGuard<'a, Accounts>,
+    ledger: MutexGuard<'a, Ledger>,
+}
+
+impl Bank {
+    fn transfer(&self) -> Transfer<'_> {
+        Transfer {
+            accounts: self.accounts.lock().unwrap(),
+            ledger: self.ledger.lock().unwrap(),
+        }
+    }
+
+    fn audit(&self) -> Audit<'_> {
+        Audit {
+            ledger: self.ledger.lock().unwrap(),
+            accounts: self.accounts.lock().unwrap(),
+        }
+    }
+
+    fn deposit(&self) -> Deposit<'_> {
+        Deposit {
+            accounts: self.accounts.lock().unwrap(),
+            ledger: self.ledger.lock().unwrap(),
+        }
+    }
+
+    /// Releases the ledger before the accounts inline, opposite to `Transfer` and `Deposit`.
+    fn close(&self) {
+        let accounts = self.accounts.lock().unwrap();
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.entries.push(accounts.balance);
+        drop(ledger);
+        drop(accounts);
+    }
+}
+
+fn main() {
+    let bank = Bank {
+        accounts: Mutex::new(Accounts { balance: 0 }),
+        ledger: Mutex::new(Ledger {
+            entries: Vec::new(),
+        }),
+    };
+    {
+        let mut transfer = bank.transfer();
+        transfer.accounts.balance += 1;
+        transfer.ledger.entries.push(1);
+    }
+    {
+        let audit = bank.audit();
+        assert_eq!(audit.ledger.entries.len() as u64, audit.accounts.balance);
+    }
+    {
+        let mut deposit = bank.deposit();
+        deposit.accounts.balance += 1;
+        deposit.ledger.entries.push(1);
+    }
+    bank.close();
+}
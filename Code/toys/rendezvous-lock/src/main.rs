@@ -0,0 +1,58 @@
+use std::sync::mpsc;
+use std::sync::{Barrier, Mutex};
+use std::thread;
+
+struct Stats {
+    hits: Mutex<u64>,
+}
+
+impl Stats {
+    // The worker blocks on `hits` before reaching the barrier.
+    fn sync_workers(&self) {
+        let barrier = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                *self.hits.lock().unwrap() += 1;
+                barrier.wait();
+            });
+            let hits = self.hits.lock().unwrap();
+            barrier.wait();
+            println!("{}", *hits);
+        });
+    }
+
+    // The receiver blocks on `hits` before receiving from the zero-capacity channel.
+    fn hand_off(&self) {
+        let (tx, rx) = mpsc::sync_channel(0);
+        thread::scope(|s| {
+            s.spawn(move || {
+                let mut hits = self.hits.lock().unwrap();
+                *hits += rx.recv().unwrap();
+            });
+            let hits = self.hits.lock().unwrap();
+            tx.send(*hits).unwrap();
+        });
+    }
+
+    // The buffered channel never blocks the sender.
+    fn buffered(&self, events: &Mutex<Vec<u32>>) {
+        let (tx, rx) = mpsc::sync_channel(1);
+        thread::scope(|s| {
+            s.spawn(move || {
+                let mut events = events.lock().unwrap();
+                events.push(rx.recv().unwrap());
+            });
+            let events = events.lock().unwrap();
+            tx.send(events.len() as u32).unwrap();
+        });
+    }
+}
+
+fn main() {
+    let stats = Stats {
+        hits: Mutex::new(0),
+    };
+    stats.sync_workers();
+    stats.hand_off();
+    stats.buffered(&Mutex::new(Vec::new()));
+}
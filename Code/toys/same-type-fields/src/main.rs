@@ -0,0 +1,37 @@
+use std::sync::Mutex;
+
+struct S {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+}
+
+impl S {
+    fn f(&self) {
+        let _a = self.a.lock().unwrap();
+        g(self);
+    }
+
+    fn h(&self) {
+        let _a = self.a.lock().unwrap();
+        k(self);
+    }
+}
+
+// Not a doublelock with `f`: `s.b` is a different field from `self.a`.
+fn g(s: &S) {
+    *s.b.lock().unwrap() += 1;
+}
+
+// A doublelock with `h`.
+fn k(s: &S) {
+    *s.a.lock().unwrap() += 1;
+}
+
+fn main() {
+    let s = S {
+        a: Mutex::new(1),
+        b: Mutex::new(2),
+    };
+    s.f();
+    s.h();
+}
@@ -0,0 +1,65 @@
+//! The conflictlock of `toys/conflict` with scoped threads:
+//! the parent locks `a` then `b` while the scoped child locks `b` then `a` (reported as ConflictLock).
+//! The children run in new threads, so the locks held by the parents do not reach them,
+//! which misses the deadlocks of the parents holding a lock until `thread::scope` joins the children, see `deposit`.
+use std::sync::Mutex;
+use std::thread;
+
+struct Accounts {
+    a: Mutex<u64>,
+    b: Mutex<u64>,
+}
+
+/// ConflictLock with the child spawned by `std::thread::Scope::spawn`.
+fn transfer(accounts: &Accounts) {
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut b = accounts.b.lock().unwrap();
+            let mut a = accounts.a.lock().unwrap();
+            *a += *b;
+            *b = 0;
+        });
+        let mut a = accounts.a.lock().unwrap();
+        let mut b = accounts.b.lock().unwrap();
+        *b += *a;
+        *a = 0;
+    });
+}
+
+/// ConflictLock with the child spawned by `crossbeam::thread::Scope::spawn`.
+fn audit(journal: &Mutex<Vec<u64>>, total: &Mutex<u64>) {
+    crossbeam::scope(|s| {
+        s.spawn(|_| {
+            let journal = journal.lock().unwrap();
+            let total = total.lock().unwrap();
+            println!("{} {}", journal.len(), *total);
+        });
+        let total = total.lock().unwrap();
+        journal.lock().unwrap().push(*total);
+    })
+    .unwrap();
+}
+
+/// Known false negative (not reported): the child spawned while `a` is held blocks on `a`,
+/// while `thread::scope` in `spawn_audit` waits for the child before `deposit` releases `a`, so it hangs.
+fn deposit(accounts: &Accounts) {
+    let mut a = accounts.a.lock().unwrap();
+    *a += 1;
+    spawn_audit(accounts);
+}
+
+fn spawn_audit(accounts: &Accounts) {
+    thread::scope(|s| {
+        s.spawn(|| println!("{}", *accounts.a.lock().unwrap()));
+    });
+}
+
+fn main() {
+    let accounts = Accounts {
+        a: Mutex::new(1),
+        b: Mutex::new(2),
+    };
+    transfer(&accounts);
+    audit(&Mutex::new(Vec::new()), &Mutex::new(0));
+    deposit(&accounts);
+}
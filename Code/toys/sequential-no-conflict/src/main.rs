@@ -0,0 +1,32 @@
+use std::sync::Mutex;
+
+struct Foo {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+}
+
+impl Foo {
+    fn new() -> Self {
+        Self {
+            a: Mutex::new(1),
+            b: Mutex::new(1),
+        }
+    }
+
+    fn a_b(&self) {
+        let a = self.a.lock().unwrap();
+        *self.b.lock().unwrap() += *a;
+    }
+
+    fn b_a(&self) {
+        let b = self.b.lock().unwrap();
+        *self.a.lock().unwrap() += *b;
+    }
+}
+
+// a -> b and b -> a are only executed one after another in the main thread.
+fn main() {
+    let foo = Foo::new();
+    foo.a_b();
+    foo.b_a();
+}
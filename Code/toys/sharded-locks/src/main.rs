@@ -0,0 +1,36 @@
+use std::sync::Mutex;
+
+fn vec_same_shard(shards: &Vec<Mutex<i32>>) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[0].lock().unwrap();
+}
+
+fn vec_different_shards(shards: &Vec<Mutex<i32>>) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[1].lock().unwrap();
+}
+
+fn array_same_shard(shards: &[Mutex<i32>; 2]) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[0].lock().unwrap();
+}
+
+fn array_different_shards(shards: &[Mutex<i32>; 2]) {
+    let _first = shards[0].lock().unwrap();
+    let _second = shards[1].lock().unwrap();
+}
+
+fn get_same_shard(shards: &[Mutex<i32>]) {
+    let _first = shards.get(0).unwrap().lock().unwrap();
+    let _second = shards.get(0).unwrap().lock().unwrap();
+}
+
+fn main() {
+    let shards = vec![Mutex::new(1), Mutex::new(2)];
+    vec_same_shard(&shards);
+    vec_different_shards(&shards);
+    let shards = [Mutex::new(1), Mutex::new(2)];
+    array_same_shard(&shards);
+    array_different_shards(&shards);
+    get_same_shard(&shards);
+}
@@ -0,0 +1,32 @@
+use std::sync::Mutex;
+
+struct Registry {
+    a: Mutex<i32>,
+    b: Mutex<i32>,
+}
+
+impl Registry {
+    fn with(&self, m: &Mutex<i32>) -> i32 {
+        let value = m.lock().unwrap();
+        *value
+    }
+
+    fn a_then_b(&self) -> i32 {
+        let a = self.a.lock().unwrap();
+        *a + self.with(&self.b)
+    }
+
+    fn a_then_a(&self) -> i32 {
+        let a = self.a.lock().unwrap();
+        *a + self.with(&self.a)
+    }
+}
+
+fn main() {
+    let registry = Registry {
+        a: Mutex::new(1),
+        b: Mutex::new(2),
+    };
+    println!("{}", registry.a_then_b());
+    println!("{}", registry.a_then_a());
+}
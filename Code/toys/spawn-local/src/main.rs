@@ -0,0 +1,48 @@
+//! The futures spawned by `spawn_local` need not be Send,
+//! so holding a std guard or a `RefCell` borrow across an await compiles,
+//! until the future is moved to `tokio::spawn`.
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::{self, LocalSet};
+
+async fn tick() {
+    tokio::time::sleep(Duration::from_millis(1)).await;
+}
+
+async fn bump_std(counter: Arc<Mutex<i32>>) {
+    let mut guard = counter.lock().unwrap();
+    tick().await;
+    *guard += 1;
+}
+
+async fn bump_tokio(counter: Arc<tokio::sync::Mutex<i32>>) {
+    let mut guard = counter.lock().await;
+    tick().await;
+    *guard += 1;
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let local = LocalSet::new();
+    let cache = Rc::new(RefCell::new(Vec::new()));
+    local.block_on(&rt, async move {
+        let std_counter = Arc::new(Mutex::new(0));
+        let tokio_counter = Arc::new(tokio::sync::Mutex::new(0));
+        let a = task::spawn_local(bump_std(std_counter.clone()));
+        let b = task::spawn_local(bump_tokio(tokio_counter.clone()));
+        let c = task::spawn_local(async move {
+            let mut entries = cache.borrow_mut();
+            tick().await;
+            entries.push(1);
+        });
+        for handle in [a, b, c] {
+            handle.await.unwrap();
+        }
+    });
+}
@@ -0,0 +1,63 @@
+use std::sync::Mutex;
+
+/// The guard is carried out of the spinning loop by the labeled break,
+/// so the lock after the loop deadlocks.
+fn spin_then_relock(m: &Mutex<i32>) {
+    let g = 'outer: loop {
+        if let Ok(g) = m.try_lock() {
+            break 'outer g;
+        }
+        std::hint::spin_loop();
+    };
+    let _g2 = m.lock().unwrap();
+    drop(g);
+}
+
+/// The guard is carried out of two nested loops.
+fn nested_spin_then_relock(m: &Mutex<i32>) {
+    let g = 'outer: loop {
+        for _ in 0..100 {
+            if let Ok(g) = m.try_lock() {
+                break 'outer g;
+            }
+        }
+        std::thread::yield_now();
+    };
+    let _g2 = m.lock().unwrap();
+    drop(g);
+}
+
+/// The spinning-acquire helper returns the guard, which is held by the caller when relocking.
+fn spin_lock(m: &parking_lot::Mutex<i32>) -> parking_lot::MutexGuard<'_, i32> {
+    'spin: loop {
+        if let Some(g) = m.try_lock() {
+            break 'spin g;
+        }
+        std::hint::spin_loop();
+    }
+}
+
+fn spin_helper_then_relock(m: &parking_lot::Mutex<i32>) {
+    let _g = spin_lock(m);
+    let _g2 = m.lock();
+}
+
+/// The guard is dropped before the second lock, no deadlock.
+fn spin_then_unlock(m: &Mutex<i32>) {
+    let g = 'outer: loop {
+        if let Ok(g) = m.try_lock() {
+            break 'outer g;
+        }
+    };
+    drop(g);
+    let _g2 = m.lock().unwrap();
+}
+
+fn main() {
+    let m = Mutex::new(1);
+    spin_then_relock(&m);
+    nested_spin_then_relock(&m);
+    spin_then_unlock(&m);
+    let m = parking_lot::Mutex::new(1);
+    spin_helper_then_relock(&m);
+}
@@ -0,0 +1,71 @@
+use std::sync::Mutex;
+
+/// Reported: no suppression.
+fn reported(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the attribute on the fn, a no-op without lockbud.
+#[cfg_attr(lockbud, allow(lockbud::double_lock))]
+fn allowed_fn(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the family `deadlock` allowed on the enclosing module.
+#[cfg_attr(lockbud, allow(lockbud::deadlock))]
+mod wrapper {
+    use std::sync::Mutex;
+
+    pub fn allowed_in_mod(m: &Mutex<i32>) {
+        let _a = m.lock().unwrap();
+        let _b = m.lock().unwrap();
+    }
+}
+
+/// Reported: the attribute allows another kind.
+#[cfg_attr(lockbud, allow(lockbud::conflict_lock))]
+fn allowed_other_kind(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the comment in the macro.
+macro_rules! lock_twice {
+    ($m:expr) => {{
+        let _a = $m.lock().unwrap(); // lockbud:ignore(double_lock)
+        let _b = $m.lock().unwrap();
+    }};
+}
+
+/// Suppressed by the comment on the lock line, where an attribute can't be placed.
+fn ignored_on_line(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap(); // lockbud:ignore(double_lock)
+    let _b = m.lock().unwrap();
+}
+
+/// Suppressed by the comment alone on the line above the lock.
+fn ignored_above(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap();
+    // lockbud:ignore(double_lock): guarded by a timeout in the test
+    let _b = m.lock().unwrap();
+}
+
+/// Reported: the comment ignores another kind.
+fn ignored_other_kind(m: &Mutex<i32>) {
+    let _a = m.lock().unwrap(); // lockbud:ignore(use_after_free)
+    let _b = m.lock().unwrap();
+}
+
+fn main() {
+    let m = Mutex::new(1);
+    reported(&m);
+    allowed_fn(&m);
+    wrapper::allowed_in_mod(&m);
+    allowed_other_kind(&m);
+    lock_twice!(m);
+    ignored_on_line(&m);
+    ignored_above(&m);
+    ignored_other_kind(&m);
+}
@@ -0,0 +1,35 @@
+//! Temporary guards dropped at the end of their statements, which are not held by the next lines.
+//! Each one-liner in `Counter::bump` and `Counter::reset` locks `count` and releases it before the next line (no deadlock).
+use std::sync::Mutex;
+
+struct Counter {
+    count: Mutex<u64>,
+    total: Mutex<u64>,
+}
+
+impl Counter {
+    fn bump(&self) {
+        *self.count.lock().unwrap() += 1;
+        *self.count.lock().unwrap() += 1;
+        let count = *self.count.lock().unwrap();
+        *self.total.lock().unwrap() += count;
+        self.count.lock().unwrap().clone_from(&0);
+    }
+
+    fn reset(&self) -> u64 {
+        let count = std::mem::take(&mut *self.count.lock().unwrap());
+        if *self.count.lock().unwrap() == 0 {
+            *self.total.lock().unwrap() = count;
+        }
+        count
+    }
+}
+
+fn main() {
+    let counter = Counter {
+        count: Mutex::new(0),
+        total: Mutex::new(0),
+    };
+    counter.bump();
+    counter.reset();
+}
@@ -0,0 +1,7 @@
+use counter::Counter;
+
+fn main() {
+    let counter = Counter::new();
+    counter.incr();
+    println!("{}", counter.get());
+}
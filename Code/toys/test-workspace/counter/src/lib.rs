@@ -0,0 +1,43 @@
+use std::sync::Mutex;
+
+pub struct Counter {
+    count: Mutex<u32>,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Self {
+            count: Mutex::new(0),
+        }
+    }
+
+    pub fn incr(&self) -> u32 {
+        let mut count = self.count.lock().unwrap();
+        *count += 1;
+        *count
+    }
+
+    pub fn get(&self) -> u32 {
+        *self.count.lock().unwrap()
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Only built with `--tests`: `count` is locked again while `guard` is alive.
+    #[test]
+    fn test_incr() {
+        let counter = Counter::new();
+        let guard = counter.count.lock().unwrap();
+        let again = counter.count.lock().unwrap();
+        assert_eq!(*guard, *again);
+    }
+}
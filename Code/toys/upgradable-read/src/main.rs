@@ -0,0 +1,53 @@
+//! parking_lot upgradable reads, which exclude each other and the writes but not the plain reads.
+//! `credit` on a spawned thread and `debit` hold the upgradable reads of `CHECKING` and `SAVINGS`
+//! in the opposite order before upgrading them (a conflictlock),
+//! `audit` reads `CHECKING` while holding its upgradable read (a doublelock with a waiting writer),
+//! and `top_up` upgrades a single upgradable read (no deadlock).
+use parking_lot::{RwLock, RwLockUpgradableReadGuard};
+
+static CHECKING: RwLock<u64> = RwLock::new(0);
+static SAVINGS: RwLock<u64> = RwLock::new(0);
+
+fn credit(amount: u64) {
+    let checking = CHECKING.upgradable_read();
+    let savings = SAVINGS.upgradable_read();
+    if *checking >= amount {
+        let mut checking = RwLockUpgradableReadGuard::upgrade(checking);
+        let mut savings = RwLockUpgradableReadGuard::upgrade(savings);
+        *checking -= amount;
+        *savings += amount;
+    }
+}
+
+fn debit(amount: u64) {
+    let savings = SAVINGS.upgradable_read();
+    let checking = CHECKING.upgradable_read();
+    if *savings >= amount {
+        let mut savings = RwLockUpgradableReadGuard::upgrade(savings);
+        let mut checking = RwLockUpgradableReadGuard::upgrade(checking);
+        *savings -= amount;
+        *checking += amount;
+    }
+}
+
+fn audit() -> u64 {
+    let checking = CHECKING.upgradable_read();
+    let total = *CHECKING.read();
+    total + *checking
+}
+
+fn top_up(amount: u64) {
+    let checking = CHECKING.upgradable_read();
+    if *checking < amount {
+        let mut checking = RwLockUpgradableReadGuard::upgrade(checking);
+        *checking = amount;
+    }
+}
+
+fn main() {
+    let creditor = std::thread::spawn(|| credit(1));
+    debit(1);
+    creditor.join().unwrap();
+    audit();
+    top_up(10);
+}
@@ -0,0 +1,36 @@
+//! A lockguard moved into `ManuallyDrop` is not dropped, but still points to the data after `force_unlock`,
+//! so touching it accesses the data no longer protected by the lock (reported as UseAfterUnlock).
+use std::mem::ManuallyDrop;
+
+use parking_lot::RwLock;
+
+struct Config {
+    version: RwLock<u64>,
+}
+
+impl Config {
+    /// UseAfterUnlock: `guard` is written after the lock is force-unlocked.
+    fn bump_use_after_unlock(&self) -> u64 {
+        let mut guard = ManuallyDrop::new(self.version.write());
+        unsafe { self.version.force_unlock_write() };
+        **guard += 1;
+        **guard
+    }
+
+    /// No report: the data is only touched before the unlock.
+    fn bump(&self) -> u64 {
+        let mut guard = ManuallyDrop::new(self.version.write());
+        **guard += 1;
+        let version = **guard;
+        unsafe { self.version.force_unlock_write() };
+        version
+    }
+}
+
+fn main() {
+    let config = Config {
+        version: RwLock::new(0),
+    };
+    config.bump_use_after_unlock();
+    config.bump();
+}
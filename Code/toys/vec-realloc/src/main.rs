@@ -0,0 +1,32 @@
+fn push_after_as_mut_ptr() {
+    let mut v = vec![1];
+    let p = v.as_mut_ptr();
+    v.push(2);
+    unsafe {
+        println!("{}", *p);
+    }
+}
+
+fn preallocated() {
+    let mut v = Vec::with_capacity(4);
+    v.push(1);
+    let p = v.as_slice().as_ptr();
+    v.push(2);
+    unsafe {
+        println!("{}", *p);
+    }
+}
+
+fn push_str_after_as_ptr(s: &mut String) {
+    let p = s.as_ptr();
+    s.push_str("tail");
+    unsafe {
+        println!("{}", *p);
+    }
+}
+
+fn main() {
+    push_after_as_mut_ptr();
+    preallocated();
+    push_str_after_as_ptr(&mut String::from("head"));
+}
@@ -0,0 +1,371 @@
+//! The regression corpus: the crates whose findings must still be reported,
+//! and whose false positives must stay fixed, checking the MIR shapes of real code that the toys miss.
+//! Each crate is described by an expectation file `xtask/corpus/<name>.json`, see `Expectation`.
+//! The source of a crate is either a git repo pinned to a revision, checked out under `target/corpus/<name>`,
+//! or a dir in the workspace, e.g., a toy.
+//! The findings are keyed by their fingerprints, which are stable across unrelated edits of the crates.
+//! The crates that cannot be fetched (e.g., without network) are skipped rather than failed.
+//! With `--update`, the expected findings are rewritten with the current ones, except the fixed false positives,
+//! recording the `AnalysisMetadata` of the analysis, which is shown when the findings are checked.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use lockbud_report_types::{AnalysisMetadata, Report, ReportEnvelope};
+use serde::{Deserialize, Serialize};
+
+/// The exit code of `cargo lockbud` when any deny-level report is emitted.
+const EXIT_DENIED: i32 = 42;
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// A git repo pinned to a revision.
+    Git { url: String, rev: String },
+    /// A dir relative to the workspace root.
+    Path(String),
+}
+
+/// The expectation file of a crate in the corpus.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expectation {
+    pub source: Source,
+    /// The dir to run `cargo lockbud` in, relative to the source, if not its root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    /// The flags of `cargo lockbud`, e.g., `["-k", "deadlock"]`.
+    pub flags: Vec<String>,
+    /// The findings that must be reported, keyed by fingerprint, with their descriptions.
+    pub findings: BTreeMap<String, String>,
+    /// The false positives that must not be reported again, keyed by fingerprint, with their notes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fixed_false_positives: BTreeMap<String, String>,
+    /// The analysis the findings were last updated by with `--update`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<AnalysisMetadata>,
+}
+
+/// The differences between the expected and the current findings of a crate.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Check {
+    /// The expected findings not reported.
+    missing: Vec<String>,
+    /// The fixed false positives reported again.
+    regressed: Vec<String>,
+    /// The findings neither expected nor fixed, which do not fail the check.
+    new: Vec<String>,
+}
+
+impl Check {
+    fn new(expectation: &Expectation, found: &BTreeMap<String, String>) -> Self {
+        let describe = |(fingerprint, description): (&String, &String)| {
+            format!("{} {}", fingerprint, description)
+        };
+        Self {
+            missing: expectation
+                .findings
+                .iter()
+                .filter(|(fingerprint, _)| !found.contains_key(*fingerprint))
+                .map(describe)
+                .collect(),
+            regressed: found
+                .iter()
+                .filter(|(fingerprint, _)| {
+                    expectation.fixed_false_positives.contains_key(*fingerprint)
+                })
+                .map(describe)
+                .collect(),
+            new: found
+                .iter()
+                .filter(|(fingerprint, _)| {
+                    !expectation.findings.contains_key(*fingerprint)
+                        && !expectation.fixed_false_positives.contains_key(*fingerprint)
+                })
+                .map(describe)
+                .collect(),
+        }
+    }
+
+    fn passed(&self) -> bool {
+        self.missing.is_empty() && self.regressed.is_empty()
+    }
+}
+
+enum Outcome {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+/// Run lockbud on the crates of the corpus (all if `names` is empty), returning whether none failed.
+pub fn run(root: &Path, update: bool, names: &[String]) -> bool {
+    let corpus_dir = root.join("xtask").join("corpus");
+    let mut crates = fs::read_dir(corpus_dir)
+        .expect("could not read the corpus dir")
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_stem()?.to_str()?.to_owned();
+            (path.extension()? == "json").then_some((name, path))
+        })
+        .filter(|(name, _)| names.is_empty() || names.contains(name))
+        .collect::<Vec<_>>();
+    crates.sort();
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !crates.iter().any(|(known, _)| known == *name))
+    {
+        eprintln!("corpus: unknown crate {}", unknown);
+        return false;
+    }
+    let built = Command::new(env!("CARGO"))
+        .args(["build", "--bin", "lockbud", "--bin", "cargo-lockbud"])
+        .current_dir(root)
+        .status()
+        .map_or(false, |status| status.success());
+    if !built {
+        eprintln!("corpus: could not build lockbud");
+        return false;
+    }
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for (name, path) in &crates {
+        match run_one(root, name, path, update) {
+            Outcome::Passed => passed += 1,
+            Outcome::Failed(reason) => {
+                eprintln!("corpus: {} FAILED: {}", name, reason);
+                failed += 1;
+            }
+            Outcome::Skipped(reason) => {
+                eprintln!("corpus: {} skipped: {}", name, reason);
+                skipped += 1;
+            }
+        }
+    }
+    eprintln!(
+        "corpus: {} passed, {} failed, {} skipped",
+        passed, failed, skipped
+    );
+    failed == 0
+}
+
+fn run_one(root: &Path, name: &str, path: &Path, update: bool) -> Outcome {
+    let mut expectation = match fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|content| {
+            serde_json::from_str::<Expectation>(&content).map_err(|err| err.to_string())
+        }) {
+        Ok(expectation) => expectation,
+        Err(err) => return Outcome::Failed(format!("malformed {}: {}", path.display(), err)),
+    };
+    let work_dir = root.join("target").join("corpus").join(name);
+    let source_dir = match &expectation.source {
+        Source::Git { url, rev } => match checkout(url, rev, &work_dir.join("checkout")) {
+            Ok(dir) => dir,
+            Err(outcome) => return outcome,
+        },
+        Source::Path(path) => root.join(path),
+    };
+    let crate_dir = match &expectation.subdir {
+        Some(subdir) => source_dir.join(subdir),
+        None => source_dir,
+    };
+    let (found, metadata) = match analyze(root, &crate_dir, &work_dir, &expectation.flags) {
+        Ok(analyzed) => analyzed,
+        Err(err) => return Outcome::Failed(err),
+    };
+    if update {
+        expectation.findings = found
+            .into_iter()
+            .filter(|(fingerprint, _)| !expectation.fixed_false_positives.contains_key(fingerprint))
+            .collect();
+        expectation.updated_by = metadata;
+        let content = serde_json::to_string_pretty(&expectation).expect("serializable");
+        if let Err(err) = fs::write(path, content + "\n") {
+            return Outcome::Failed(format!("could not write {}: {}", path.display(), err));
+        }
+        eprintln!(
+            "corpus: {} updated with {} findings",
+            name,
+            expectation.findings.len()
+        );
+        return Outcome::Passed;
+    }
+    let check = Check::new(&expectation, &found);
+    for finding in &check.new {
+        eprintln!("corpus: {} new finding {}", name, finding);
+    }
+    if let Some(updated_by) = &expectation.updated_by {
+        eprintln!(
+            "corpus: {} expected findings updated by {}",
+            name, updated_by
+        );
+    }
+    if check.passed() {
+        eprintln!(
+            "corpus: {} passed with {} expected findings",
+            name,
+            expectation.findings.len()
+        );
+        return Outcome::Passed;
+    }
+    let mut reasons = Vec::new();
+    reasons.extend(
+        check
+            .missing
+            .iter()
+            .map(|finding| format!("\n    missing {}", finding)),
+    );
+    reasons.extend(
+        check
+            .regressed
+            .iter()
+            .map(|finding| format!("\n    false positive again {}", finding)),
+    );
+    Outcome::Failed(reasons.concat())
+}
+
+fn git(dir: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_or(false, |status| status.success())
+}
+
+/// Check out the revision of the repo into `dir`, cloning or fetching it if missing.
+/// Skipped if the repo cannot be fetched, e.g., without network.
+fn checkout(url: &str, rev: &str, dir: &Path) -> Result<PathBuf, Outcome> {
+    if !dir.join(".git").is_dir() {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).map_err(|err| Outcome::Failed(err.to_string()))?;
+        let cloned = Command::new("git")
+            .args(["clone", "--quiet", "--no-checkout", url, "."])
+            .current_dir(dir)
+            .status();
+        match cloned {
+            Ok(status) if status.success() => {}
+            Ok(_) => return Err(Outcome::Skipped(format!("could not clone {}", url))),
+            Err(err) => return Err(Outcome::Skipped(format!("could not run git: {}", err))),
+        }
+    }
+    let commit = format!("{}^{{commit}}", rev);
+    if !git(dir, &["rev-parse", "--verify", "--quiet", &commit])
+        && !git(dir, &["fetch", "--quiet", "origin"])
+    {
+        return Err(Outcome::Skipped(format!("could not fetch {}", url)));
+    }
+    if !git(dir, &["checkout", "--quiet", "--force", "--detach", rev]) {
+        return Err(Outcome::Failed(format!("no revision {} in {}", rev, url)));
+    }
+    Ok(dir.to_owned())
+}
+
+/// The findings of `cargo lockbud` in `crate_dir` keyed by fingerprint, analyzed from scratch,
+/// and the metadata of the analysis.
+fn analyze(
+    root: &Path,
+    crate_dir: &Path,
+    work_dir: &Path,
+    flags: &[String],
+) -> Result<(BTreeMap<String, String>, Option<AnalysisMetadata>), String> {
+    let target_dir = work_dir.join("target");
+    let _ = fs::remove_dir_all(&target_dir);
+    let output = work_dir.join("reports.jsonl");
+    let status = Command::new(root.join("target").join("debug").join("cargo-lockbud"))
+        .arg("lockbud")
+        .args(flags)
+        .arg("--output")
+        .arg(&output)
+        .current_dir(crate_dir)
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|err| format!("could not run cargo lockbud: {}", err))?;
+    if !status.success() && status.code() != Some(EXIT_DENIED) {
+        return Err(format!("cargo lockbud exited with {}", status));
+    }
+    let content = fs::read_to_string(&output).map_err(|err| err.to_string())?;
+    let (mut found, mut metadata) = (BTreeMap::new(), None);
+    for line in content.lines() {
+        let envelope =
+            serde_json::from_str::<ReportEnvelope>(line).map_err(|err| err.to_string())?;
+        for report in &envelope.reports {
+            found.insert(report.fingerprint().to_owned(), describe(report));
+        }
+        metadata = metadata.or(envelope.metadata);
+    }
+    Ok((found, metadata))
+}
+
+/// The kind and the primary span of the report, e.g., `double_lock src/main.rs:25:16: 25:40 (#0)`.
+fn describe(report: &Report) -> String {
+    format!("{} {}", report.level_keys()[0], report.spans().0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn findings(fingerprints: &[&str]) -> BTreeMap<String, String> {
+        fingerprints
+            .iter()
+            .map(|fingerprint| (fingerprint.to_string(), "double_lock src/lib.rs".to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_findings() {
+        let expectation: Expectation = serde_json::from_str(
+            r#"{
+                "source": {"git": {"url": "https://example.com/repo.git", "rev": "v1.0.0"}},
+                "flags": ["-k", "deadlock"],
+                "findings": {"a": "double_lock src/lib.rs", "b": "double_lock src/lib.rs"},
+                "fixed_false_positives": {"c": "fixed by the thread filter"}
+            }"#,
+        )
+        .unwrap();
+        let check = Check::new(&expectation, &findings(&["a", "b", "d"]));
+        assert!(check.passed());
+        assert_eq!(check.new, ["d double_lock src/lib.rs"]);
+        let check = Check::new(&expectation, &findings(&["a", "c"]));
+        assert!(!check.passed());
+        assert_eq!(check.missing, ["b double_lock src/lib.rs"]);
+        assert_eq!(check.regressed, ["c double_lock src/lib.rs"]);
+        let path: Expectation = serde_json::from_str(
+            r#"{"source": {"path": "toys/inter"}, "flags": [], "findings": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(path.source, Source::Path("toys/inter".to_owned()));
+        assert_eq!(path.updated_by, None);
+    }
+
+    #[test]
+    fn test_expectation_updated_by_round_trip() {
+        let expectation: Expectation = serde_json::from_str(
+            r#"{
+                "source": {"path": "toys/inter"},
+                "flags": [],
+                "findings": {},
+                "updated_by": {"lockbud_version": "0.1.0", "analyzed_at": "2024-01-31T12:00:00Z", "git_commit": "0123abcd", "options_hash": "4567ef01"}
+            }"#,
+        )
+        .unwrap();
+        let updated_by = expectation.updated_by.as_ref().unwrap();
+        assert_eq!(updated_by.git_commit.as_deref(), Some("0123abcd"));
+        let json = serde_json::to_string(&expectation).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Expectation>(&json).unwrap(),
+            expectation
+        );
+    }
+
+    /// Run the whole corpus, fetching the crates if needed.
+    #[test]
+    #[ignore]
+    fn test_corpus() {
+        assert!(run(&crate::workspace_root(), false, &[]));
+    }
+}
@@ -0,0 +1,44 @@
+//! The development tasks of lockbud, run by `cargo xtask <task>`.
+//! `corpus [--update] [<name>...]` runs lockbud on the regression corpus, see `corpus.rs`.
+mod corpus;
+
+use std::path::{Path, PathBuf};
+
+const XTASK_HELP: &str = r#"Usage:
+    cargo xtask <task> [options]
+Tasks:
+    corpus [--update] [<name>...]  Run lockbud on the crates of the regression corpus (all by default),
+                                   and check the findings against the expectation files in xtask/corpus.
+                                   --update rewrites the expected findings with the current ones
+"#;
+
+/// The root of the lockbud workspace.
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is in the workspace")
+        .to_owned()
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("corpus") => {
+            let mut update = false;
+            let mut names = Vec::new();
+            for arg in args {
+                match arg.as_str() {
+                    "--update" => update = true,
+                    _ => names.push(arg),
+                }
+            }
+            if !corpus::run(&workspace_root(), update, &names) {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            print!("{}", XTASK_HELP);
+            std::process::exit(2);
+        }
+    }
+}